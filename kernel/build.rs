@@ -0,0 +1,31 @@
+// build.rs — カーネルに埋め込むシンボルテーブルを用意する
+//
+// 環境変数 SABOS_SYMBOLS にシンボルテーブルのファイル（scripts/gen-kernel-symbols.py の出力）が
+// 指定されていれば、それを OUT_DIR/symbols.bin にコピーし、symbols.rs が include_bytes! で埋め込む。
+// 指定が無いかファイルがまだ無ければ空のテーブルにする（シンボル名なしで動く）。
+//
+// テーブルはカーネル自身をビルドしてからでないと作れないので、
+// scripts/build-kernel.sh が「ビルド → テーブル生成 → 再ビルド」をテーブルが変わらなくなるまで繰り返す。
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SABOS_SYMBOLS");
+
+    let table = match env::var("SABOS_SYMBOLS") {
+        Ok(path) if !path.is_empty() => {
+            println!("cargo:rerun-if-changed={path}");
+            fs::read(&path).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("symbols.bin");
+    // 中身が同じなら書き直さない（タイムスタンプが変わるとカーネル全体が再コンパイルされる）
+    if fs::read(&out).ok().as_deref() != Some(table.as_slice()) {
+        fs::write(&out, table).unwrap();
+    }
+}
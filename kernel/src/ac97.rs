@@ -38,6 +38,10 @@ const NAM_RESET: u16 = 0x00;
 const NAM_MASTER_VOL: u16 = 0x02;
 /// PCM 出力ボリューム（16bit, 0x0000 = 最大音量）
 const NAM_PCM_OUT_VOL: u16 = 0x18;
+/// Extended Audio Status/Control（16bit, bit0 = VRA: 可変サンプルレート有効）
+const NAM_EXT_AUDIO_CTRL: u16 = 0x2A;
+/// PCM Front DAC Rate（16bit, Hz 単位。VRA 有効時のみ書き換えられる）
+const NAM_PCM_FRONT_DAC_RATE: u16 = 0x2C;
 
 // =================================================================
 // NABM (Native Audio Bus Master) レジスタオフセット
@@ -49,14 +53,16 @@ const NAM_PCM_OUT_VOL: u16 = 0x18;
 /// PCM Out — Buffer Descriptor List Base Address（32bit, BDL の物理アドレス）
 const PO_BDBAR: u16 = 0x10;
 /// PCM Out — Current Index Value（8bit, 現在再生中のバッファ番号、読み取り専用）
-/// 現在は直接使用していないが、デバッグ時に有用なのでコメントとして残す。
-// const PO_CIV: u16 = 0x14;
+/// PCM ストリーミングで「どの BD まで消費されたか」を判定するのに使う。
+const PO_CIV: u16 = 0x14;
 /// PCM Out — Last Valid Index（8bit, 最後の有効な BDL エントリの番号）
 const PO_LVI: u16 = 0x15;
 /// PCM Out — Status Register（16bit, write-clear でエラー/完了フラグをクリア）
 const PO_SR: u16 = 0x16;
 /// PCM Out — Control Register（8bit, bit0=Run, bit1=Reset）
 const PO_CR: u16 = 0x1B;
+/// PO_SR bit0 — DCH (DMA Controller Halted): LVI まで再生し終えて DMA が止まった
+const SR_DCH: u16 = 0x01;
 /// Global Control（32bit, bit1=Cold Reset Release）
 const GLOB_CNT: u16 = 0x2C;
 /// Global Status（32bit, bit0=Primary Codec Ready）
@@ -74,7 +80,8 @@ const GLOB_STA: u16 = 0x30;
 ///
 /// - addr: PCM バッファの物理アドレス（32bit）
 /// - samples: バッファ内のサンプル数（16bit）
-///   ※ サンプル = 1つのステレオペア (左16bit + 右16bit = 4bytes)
+///   ※ AC97 仕様ではこの「サンプル」は 16bit ワード単位なので、
+///   ステレオ 1 フレーム (左16bit + 右16bit = 4bytes) は 2 ワードになる。
 /// - flags: 制御フラグ（16bit, bit15 = IOC = Interrupt on Completion）
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
@@ -98,6 +105,11 @@ const SAMPLES_PER_BUF: usize = 4096;
 /// 1 バッファあたりのバイト数（サンプル数 × 4 bytes/sample）
 const BYTES_PER_BUF: usize = SAMPLES_PER_BUF * 4;
 
+/// SYS_SOUND_PLAY_PCM で受け付けるサンプルレートの範囲（Hz）
+/// AC97 コーデックの VRA (Variable Rate Audio) がサポートする範囲に合わせる。
+pub const PCM_MIN_RATE: u32 = 8000;
+pub const PCM_MAX_RATE: u32 = 48000;
+
 // =================================================================
 // 256 エントリの sin ルックアップテーブル（振幅 32000）
 // =================================================================
@@ -149,8 +161,7 @@ static SIN_TABLE: [i16; 256] = [
 /// AC97 ドライバの状態を保持する構造体
 pub struct Ac97 {
     /// NAM (Native Audio Mixer) のベース I/O ポートアドレス
-    /// 将来のミキサー制御（音量変更等）で使用予定
-    #[allow(dead_code)]
+    /// PCM 再生時の DAC サンプルレート設定で使う
     nam_base: u16,
     /// NABM (Native Audio Bus Master) のベース I/O ポートアドレス
     nabm_base: u16,
@@ -160,6 +171,61 @@ pub struct Ac97 {
     /// PCM バッファ群の先頭ポインタ
     /// 32 バッファ × 16KB = 512KB
     pcm_buf_ptr: *mut u8,
+    /// PCM ストリーミング再生の状態
+    pcm: PcmStream,
+}
+
+/// PCM ストリーミング再生の状態。
+///
+/// BDL の 32 エントリをリングバッファとして使う。
+/// head に書き込み、DMA が tail から順に消費していく。
+/// CIV (Current Index Value) が tail を通り過ぎたら、その BD は再生済みとして回収する。
+///
+/// 全エントリを埋めると head == tail になり、LVI を「tail の 1 つ前」に書くことになって
+/// 空と満杯の区別がつかなくなるので、同時にキューに入れるのは BDL_ENTRIES - 1 個までにする。
+struct PcmStream {
+    /// DMA エンジンを起動済みか
+    running: bool,
+    /// 次に書き込む BD のインデックス
+    head: usize,
+    /// まだ回収していない最古の BD のインデックス
+    tail: usize,
+    /// キューに入っている（未回収の）BD の数
+    in_flight: usize,
+    /// 各 BD に入れたフレーム数（ステレオ 1 組 = 1 フレーム）
+    bd_frames: [u16; BDL_ENTRIES],
+    /// 累計でキューに入れたフレーム数
+    queued: u64,
+    /// 累計で DMA が消費したフレーム数
+    consumed: u64,
+    /// 現在 DAC に設定しているサンプルレート（Hz）
+    rate: u32,
+}
+
+impl PcmStream {
+    const fn new() -> Self {
+        Self {
+            running: false,
+            head: 0,
+            tail: 0,
+            in_flight: 0,
+            bd_frames: [0; BDL_ENTRIES],
+            queued: 0,
+            consumed: 0,
+            rate: SAMPLE_RATE,
+        }
+    }
+}
+
+/// PCM ストリーミング再生の統計情報（selftest やデバッグ用）
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PcmStats {
+    /// 累計でキューに入れたフレーム数
+    pub queued: u64,
+    /// 累計で DMA が消費したフレーム数
+    pub consumed: u64,
+    /// 現在キューに入っている BD の数
+    pub in_flight: usize,
 }
 
 // Ac97 は Mutex で保護するので Send + Sync を実装
@@ -313,6 +379,7 @@ pub fn init() {
         nabm_base,
         bdl_ptr: bdl_ptr as *mut BdlEntry,
         pcm_buf_ptr,
+        pcm: PcmStream::new(),
     };
 
     *AC97.lock() = Some(ac97);
@@ -338,6 +405,11 @@ impl Ac97 {
             return;
         }
 
+        // ビープ音は BDL とバッファを先頭から使い直すので、
+        // 再生中の PCM ストリームは打ち切ってレートを 48kHz に戻す。
+        self.stop_pcm();
+        self.set_pcm_rate(SAMPLE_RATE);
+
         // 使用するバッファ数を計算（切り上げ、最大 BDL_ENTRIES）
         let num_bufs = ((total_samples + SAMPLES_PER_BUF - 1) / SAMPLES_PER_BUF).min(BDL_ENTRIES);
         let mut remaining = total_samples;
@@ -379,7 +451,7 @@ impl Ac97 {
             // BDL エントリを設定
             let bdl_entry = BdlEntry {
                 addr: buf_addr as u32,  // 物理アドレス（アイデンティティマッピング前提）
-                samples: samples_in_buf as u16,
+                samples: (samples_in_buf * 2) as u16,  // 16bit ワード単位
                 // 最後のバッファに IOC (Interrupt on Completion) フラグを立てる
                 flags: if i == num_bufs - 1 { 0x8000 } else { 0 },
             };
@@ -459,6 +531,227 @@ impl Ac97 {
         serial_println!("AC97: playback finished");
     }
 
+    /// DAC のサンプルレートを設定する。
+    ///
+    /// 48kHz 以外を使うには Extended Audio Control の VRA ビットを立てる必要がある。
+    /// コーデックが対応していないレートは丸められることがあるので、
+    /// 書いた値を読み戻して一致したときだけ成功とする。
+    /// 再生中の BD があるときに呼ぶと音程が狂うので、呼び出し側で空になるのを待つこと。
+    pub fn set_pcm_rate(&mut self, rate: u32) -> bool {
+        if self.pcm.rate == rate {
+            return true;
+        }
+        let actual = unsafe {
+            let mut ext = Port::<u16>::new(self.nam_base + NAM_EXT_AUDIO_CTRL);
+            let v = ext.read();
+            ext.write(v | 0x0001); // VRA 有効
+            let mut dac = Port::<u16>::new(self.nam_base + NAM_PCM_FRONT_DAC_RATE);
+            dac.write(rate as u16);
+            dac.read() as u32
+        };
+        if actual != rate {
+            serial_println!("AC97: DAC rate {} not supported (codec reports {})", rate, actual);
+            // 中途半端なレートのまま残さないよう、元のレートに戻しておく
+            unsafe {
+                Port::<u16>::new(self.nam_base + NAM_PCM_FRONT_DAC_RATE).write(self.pcm.rate as u16);
+            }
+            return false;
+        }
+        self.pcm.rate = rate;
+        true
+    }
+
+    /// 現在の DAC サンプルレート（Hz）を返す。
+    pub fn pcm_rate(&self) -> u32 {
+        self.pcm.rate
+    }
+
+    /// 再生待ち・再生中の PCM データが残っているかを返す。
+    pub fn pcm_busy(&mut self) -> bool {
+        self.reap_pcm();
+        self.pcm.in_flight > 0
+    }
+
+    /// PCM ストリーミングの統計情報を返す。
+    pub fn pcm_stats(&mut self) -> PcmStats {
+        self.reap_pcm();
+        PcmStats {
+            queued: self.pcm.queued,
+            consumed: self.pcm.consumed,
+            in_flight: self.pcm.in_flight,
+        }
+    }
+
+    /// 16bit stereo PCM データを BDL リングの空きエントリに詰めてキューに入れる。
+    ///
+    /// `data` の長さは 4 の倍数（フレーム境界）であること。
+    /// 1 エントリに最大 SAMPLES_PER_BUF フレームずつ分割して書き込み、
+    /// 空きがなくなったところで止める。戻り値は受け付けたバイト数。
+    /// 受け付けきれなかった残りは、DMA が BD を消費してから再度呼べば続きを詰められる。
+    pub fn queue_pcm(&mut self, data: &[u8]) -> usize {
+        self.reap_pcm();
+
+        // DMA が止まっているときは CIV が 0 から始まるので、リングも先頭から使う
+        if !self.pcm.running {
+            self.pcm.head = 0;
+            self.pcm.tail = 0;
+        }
+
+        let mut written = 0;
+        while written < data.len() && self.pcm.in_flight < BDL_ENTRIES - 1 {
+            let chunk = (data.len() - written).min(BYTES_PER_BUF);
+            let frames = chunk / 4;
+            let idx = self.pcm.head;
+            let buf_addr = unsafe { self.pcm_buf_ptr.add(idx * BYTES_PER_BUF) };
+            unsafe {
+                core::ptr::copy_nonoverlapping(data[written..].as_ptr(), buf_addr, chunk);
+                *self.bdl_ptr.add(idx) = BdlEntry {
+                    addr: buf_addr as u32,
+                    samples: (frames * 2) as u16, // 16bit ワード単位
+                    flags: 0,
+                };
+            }
+            self.pcm.bd_frames[idx] = frames as u16;
+            self.pcm.head = (idx + 1) % BDL_ENTRIES;
+            self.pcm.in_flight += 1;
+            self.pcm.queued += frames as u64;
+            written += chunk;
+        }
+
+        if written > 0 {
+            self.kick_pcm();
+        }
+        written
+    }
+
+    /// 新しく詰めた BD を DMA に知らせる。
+    ///
+    /// 停止中ならリセットして BDL を設定し直してから Run を立てる。
+    /// 動作中なら LVI を進めるだけでよい（LVI で止まっていた DMA も再開する）。
+    fn kick_pcm(&mut self) {
+        let lvi = ((self.pcm.head + BDL_ENTRIES - 1) % BDL_ENTRIES) as u8;
+        if self.pcm.running {
+            unsafe {
+                Port::<u8>::new(self.nabm_base + PO_LVI).write(lvi);
+            }
+            return;
+        }
+
+        unsafe {
+            Port::<u8>::new(self.nabm_base + PO_CR).write(0x02); // Reset
+        }
+        for _ in 0..10000 {
+            core::hint::spin_loop();
+        }
+        unsafe {
+            Port::<u8>::new(self.nabm_base + PO_CR).write(0x00);
+            Port::<u16>::new(self.nabm_base + PO_SR).write(0x1C);
+            Port::<u32>::new(self.nabm_base + PO_BDBAR).write(self.bdl_ptr as u32);
+            Port::<u8>::new(self.nabm_base + PO_LVI).write(lvi);
+            Port::<u8>::new(self.nabm_base + PO_CR).write(0x01); // Run
+        }
+        self.pcm.running = true;
+    }
+
+    /// DMA が消費し終えた BD を回収して consumed を進める。
+    ///
+    /// CIV は「今 DMA が処理している BD」なので、tail != CIV なら tail は再生済み。
+    /// DCH (DMA Halted) が立っていれば LVI まで全部再生し終えているので全て回収し、
+    /// DMA を止めて次回の queue_pcm でリセットからやり直せる状態にする。
+    fn reap_pcm(&mut self) {
+        if !self.pcm.running {
+            return;
+        }
+        let (civ, sr) = unsafe {
+            (
+                Port::<u8>::new(self.nabm_base + PO_CIV).read() as usize % BDL_ENTRIES,
+                Port::<u16>::new(self.nabm_base + PO_SR).read(),
+            )
+        };
+        let halted = sr & SR_DCH != 0;
+        while self.pcm.in_flight > 0 && (self.pcm.tail != civ || halted) {
+            self.pcm.consumed += self.pcm.bd_frames[self.pcm.tail] as u64;
+            self.pcm.tail = (self.pcm.tail + 1) % BDL_ENTRIES;
+            self.pcm.in_flight -= 1;
+        }
+        if halted && self.pcm.in_flight == 0 {
+            self.stop_pcm();
+        }
+    }
+
+    /// PCM ストリームの DMA を止め、未再生の BD を破棄する。
+    fn stop_pcm(&mut self) {
+        if !self.pcm.running {
+            return;
+        }
+        unsafe {
+            Port::<u8>::new(self.nabm_base + PO_CR).write(0x00); // Stop
+            Port::<u16>::new(self.nabm_base + PO_SR).write(0x1C);
+        }
+        self.pcm.running = false;
+        self.pcm.in_flight = 0;
+        self.pcm.head = 0;
+        self.pcm.tail = 0;
+    }
+}
+
+/// PCM 再生 API のエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcmError {
+    /// AC97 デバイスが見つからない
+    NotAvailable,
+    /// コーデックが対応していないサンプルレート
+    UnsupportedRate,
+    /// 別のサンプルレートのデータがまだ再生中
+    Busy,
+}
+
+/// サンプルレートを合わせてから data を BDL リングの空きに詰める（1 回だけ試す）。
+///
+/// 受け付けたバイト数を返す（リングが満杯なら 0）。
+/// レートを切り替えるには前のデータを再生し終えている必要があり、残っていれば Busy。
+pub fn try_queue_pcm(data: &[u8], rate: u32) -> Result<usize, PcmError> {
+    if !(PCM_MIN_RATE..=PCM_MAX_RATE).contains(&rate) {
+        return Err(PcmError::UnsupportedRate);
+    }
+    let mut ac97 = AC97.lock();
+    let driver = ac97.as_mut().ok_or(PcmError::NotAvailable)?;
+    if driver.pcm_rate() != rate {
+        if driver.pcm_busy() {
+            return Err(PcmError::Busy);
+        }
+        if !driver.set_pcm_rate(rate) {
+            return Err(PcmError::UnsupportedRate);
+        }
+    }
+    Ok(driver.queue_pcm(data))
+}
+
+/// data を全てキューに入れ終わるまでブロックする（再生完了までは待たない）。
+///
+/// リングが満杯のあいだや、前のデータのレートが違うあいだはスリープして待つ。
+/// スピンロックを持ったままスリープしないよう、1 回ごとにロックを取り直す。
+pub fn queue_pcm_all(data: &[u8], rate: u32) -> Result<(), PcmError> {
+    let mut written = 0;
+    while written < data.len() {
+        match try_queue_pcm(&data[written..], rate) {
+            Ok(n) => written += n,
+            Err(PcmError::Busy) => {}
+            Err(e) => return Err(e),
+        }
+        if written < data.len() {
+            // BD 1 つ (4096 フレーム) は 48kHz で約 85ms なので、10ms 間隔で見れば十分間に合う
+            crate::scheduler::sleep_ms(10);
+        }
+    }
+    Ok(())
+}
+
+/// キューに入っている PCM データを全て再生し終えるまで待つ。
+pub fn wait_pcm_drain() {
+    while AC97.lock().as_mut().is_some_and(|d| d.pcm_busy()) {
+        crate::scheduler::sleep_ms(10);
+    }
 }
 
 /// AC97 デバイスが利用可能かどうかを返す（selftest 用の便利関数）
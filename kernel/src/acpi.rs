@@ -32,6 +32,10 @@ pub struct AcpiApicInfo {
     /// （APIC 初期化時に PIC のマスク処理の判断に使う可能性がある）
     #[allow(dead_code)]
     pub has_legacy_pic: bool,
+    /// BSP（今動いている CPU）の Local APIC ID（smp::init() が実際の ID と照合する）
+    pub bsp_apic_id: u32,
+    /// 起動できる AP の Local APIC ID（MADT で無効になっているものは除く）
+    pub ap_apic_ids: Vec<u32>,
 }
 
 /// I/O APIC の情報。
@@ -161,10 +165,26 @@ pub fn init(rsdp_phys: u64) {
             let has_legacy_pic = apic_model.also_has_legacy_pics;
             crate::kprintln!("ACPI: Legacy PIC: {}", if has_legacy_pic { "yes" } else { "no" });
 
+            // MADT に載っているプロセッサ（smp::init() が AP を起動するのに使う）
+            let (bsp_apic_id, ap_apic_ids) = match &platform_info.processor_info {
+                Some(info) => (
+                    info.boot_processor.local_apic_id,
+                    info.application_processors.iter()
+                        .filter(|p| p.state != acpi::platform::ProcessorState::Disabled)
+                        .map(|p| p.local_apic_id)
+                        .collect(),
+                ),
+                None => (0, Vec::new()),
+            };
+            crate::kprintln!("ACPI: {} processor(s) (BSP APIC ID {})",
+                ap_apic_ids.len() + 1, bsp_apic_id);
+
             ACPI_INFO.call_once(|| AcpiApicInfo {
                 local_apic_address: apic_model.local_apic_address,
                 io_apics,
                 has_legacy_pic,
+                bsp_apic_id,
+                ap_apic_ids,
             });
         }
         acpi::InterruptModel::Unknown => {
@@ -26,6 +26,7 @@
 // - 各ポートに Command List 1 スロット分 + Command Table 1 つを確保
 //   （シングルスレッドでポーリング待ちするため、同時に 1 コマンドしか発行しない）
 
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::alloc::Layout;
 use core::sync::atomic::{fence, Ordering};
@@ -326,6 +327,40 @@ const SATA_SIG_DISK: u32 = 0x00000101;
 /// 各要素は 1 つの SATA ディスクに対応する。
 pub static AHCI_DEVICES: Mutex<Vec<AhciDevice>> = Mutex::new(Vec::new());
 
+/// init() で走査した AHCI コントローラの一覧（ポートの走査結果つき）。
+/// ディスクがつながっていないポートも含めて、PI で実装済みのポートをすべて記録する。
+pub static AHCI_CONTROLLERS: Mutex<Vec<AhciController>> = Mutex::new(Vec::new());
+
+/// 走査した AHCI コントローラ 1 つ分の情報
+pub struct AhciController {
+    /// PCI の bus / device / function
+    pub bdf: (u8, u8, u8),
+    /// PI (Ports Implemented) レジスタの値
+    pub ports_implemented: u32,
+    /// PI のビットが立っている各ポートの走査結果（ポート番号順）
+    pub ports: Vec<AhciPortInfo>,
+}
+
+/// ポート 1 つ分の走査結果
+pub struct AhciPortInfo {
+    /// ポート番号（0〜31）
+    pub port: u8,
+    pub status: AhciPortStatus,
+}
+
+/// ポートの状態
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AhciPortStatus {
+    /// SATA ディスクを初期化し、AHCI_DEVICES に登録した
+    Disk,
+    /// デバイスがつながっていない（SSTS.DET != 3）
+    NoDevice,
+    /// ディスク以外のデバイス（SATAPI 等）。値は SIG レジスタ
+    NonDisk(u32),
+    /// ディスクだが初期化か IDENTIFY に失敗した
+    Failed,
+}
+
 /// AHCI デバイス（1 つの SATA ディスクポートに対応）。
 ///
 /// HBA の MMIO ベースアドレスとポート番号、
@@ -349,6 +384,8 @@ pub struct AhciDevice {
     fis_base: *mut u8,
     /// デバイスの総セクタ数（IDENTIFY DEVICE で取得、48-bit LBA）。
     capacity: u64,
+    /// モデル名（IDENTIFY DEVICE で取得）。
+    model: String,
 }
 
 // AhciDevice は raw pointer を含むが、Mutex で保護されるため Send/Sync は安全
@@ -372,6 +409,7 @@ pub fn init() {
     }
 
     let mut devices = Vec::new();
+    let mut scanned = Vec::new();
 
     for ctrl in controllers {
         serial_println!(
@@ -455,39 +493,23 @@ pub fn init() {
             let pi = core::ptr::read_volatile(&(*hba).pi);
             serial_println!("AHCI: ports implemented = {:#010x}", pi);
 
-            // 各ポートをチェック
+            // PI のビットが立っているポートをすべてチェックし、結果を記録する。
+            // 実機では SATA ディスクが複数つながっていることがあるので、
+            // 最初のディスクで止めずに全部 AHCI_DEVICES に登録する。
+            let mut ports = Vec::new();
             for port_idx in 0..32u8 {
                 if pi & (1 << port_idx) == 0 {
                     // このポートは実装されていない
                     continue;
                 }
-
-                // SATA Status (SSTS) を確認
-                let ssts = core::ptr::read_volatile(&(*hba).ports[port_idx as usize].ssts);
-                let det = ssts & 0xF; // Device Detection
-                if det != 3 {
-                    // DET=3: デバイス検出済み＋Phy通信確立
-                    // それ以外はデバイスが接続されていないか、まだ初期化中
-                    continue;
-                }
-
-                // Signature を確認
-                let sig = core::ptr::read_volatile(&(*hba).ports[port_idx as usize].sig);
-                if sig != SATA_SIG_DISK {
-                    serial_println!(
-                        "AHCI: port {}: non-disk device (sig={:#010x}), skipping",
-                        port_idx, sig
-                    );
-                    continue;
-                }
-
-                serial_println!("AHCI: port {}: SATA disk detected", port_idx);
-
-                // ポートを初期化
-                if let Some(dev) = init_port(hba, port_idx) {
-                    devices.push(dev);
-                }
+                let status = scan_port(hba, port_idx, &mut devices);
+                ports.push(AhciPortInfo { port: port_idx, status });
             }
+            scanned.push(AhciController {
+                bdf: (ctrl.bus, ctrl.device, ctrl.function),
+                ports_implemented: pi,
+                ports,
+            });
         }
     }
 
@@ -498,6 +520,7 @@ pub fn init() {
     }
 
     *AHCI_DEVICES.lock() = devices;
+    *AHCI_CONTROLLERS.lock() = scanned;
 }
 
 /// 検出された AHCI デバイスの数を返す。
@@ -505,6 +528,42 @@ pub fn device_count() -> usize {
     AHCI_DEVICES.lock().len()
 }
 
+/// 実装済みのポート 1 つを調べ、SATA ディスクなら初期化して devices に追加する。
+///
+/// # Safety
+/// `hba` は有効な HbaMemory MMIO 領域を指していること。
+fn scan_port(hba: *mut HbaMemory, port_idx: u8, devices: &mut Vec<AhciDevice>) -> AhciPortStatus {
+    // SATA Status (SSTS) を確認
+    let ssts = unsafe { core::ptr::read_volatile(&(*hba).ports[port_idx as usize].ssts) };
+    let det = ssts & 0xF; // Device Detection
+    if det != 3 {
+        // DET=3: デバイス検出済み＋Phy通信確立
+        // それ以外はデバイスが接続されていないか、まだ初期化中
+        return AhciPortStatus::NoDevice;
+    }
+
+    // Signature を確認
+    let sig = unsafe { core::ptr::read_volatile(&(*hba).ports[port_idx as usize].sig) };
+    if sig != SATA_SIG_DISK {
+        serial_println!(
+            "AHCI: port {}: non-disk device (sig={:#010x}), skipping",
+            port_idx, sig
+        );
+        return AhciPortStatus::NonDisk(sig);
+    }
+
+    serial_println!("AHCI: port {}: SATA disk detected", port_idx);
+
+    // ポートを初期化
+    match init_port(hba, port_idx) {
+        Some(dev) => {
+            devices.push(dev);
+            AhciPortStatus::Disk
+        }
+        None => AhciPortStatus::Failed,
+    }
+}
+
 /// 個別のポートを初期化する。
 ///
 /// 1. ポートをアイドル状態にする（ST, FRE をクリアして CR, FR の停止を待つ）
@@ -590,12 +649,12 @@ fn init_port(hba: *mut HbaMemory, port_idx: u8) -> Option<AhciDevice> {
             cmd_table: cmd_table_ptr as *mut HbaCmdTable,
             fis_base: fis_ptr,
             capacity: 0,
+            model: String::new(),
         };
 
         // --- IDENTIFY DEVICE コマンドを発行 ---
         match dev.identify() {
             Ok((capacity, model)) => {
-                dev.capacity = capacity;
                 let size_mib = capacity * 512 / 1024 / 1024;
                 serial_println!(
                     "AHCI: port {}: {}, {} sectors ({} MiB)",
@@ -605,6 +664,8 @@ fn init_port(hba: *mut HbaMemory, port_idx: u8) -> Option<AhciDevice> {
                     "  [AHCI port {}] {} ({} MiB)",
                     port_idx, model, size_mib
                 );
+                dev.capacity = capacity;
+                dev.model = model;
                 Some(dev)
             }
             Err(e) => {
@@ -679,7 +740,7 @@ impl AhciDevice {
     ///
     /// ATA コマンド 0xEC を使い、512 バイトの識別情報データを読み取る。
     /// 返り値: (セクタ数, モデル名文字列)
-    fn identify(&mut self) -> Result<(u64, alloc::string::String), &'static str> {
+    fn identify(&mut self) -> Result<(u64, String), &'static str> {
         // IDENTIFY レスポンス用の 512 バイトバッファ
         let mut identify_buf = [0u8; 512];
 
@@ -903,11 +964,20 @@ impl AhciDevice {
     }
 
     /// デバイスの容量（セクタ数）を返す。
-    #[allow(dead_code)]
     pub fn capacity(&self) -> u64 {
         self.capacity
     }
 
+    /// モデル名を返す。
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// 接続されている HBA ポートの番号を返す。
+    pub fn port_index(&self) -> u8 {
+        self.port_index
+    }
+
     /// Command List のスロット 0 のコマンドを発行し、完了をポーリングで待つ。
     ///
     /// 事前条件: cmd_list[0] と cmd_table が正しく設定されていること。
@@ -12,7 +12,7 @@
 // ヒープ領域は UEFI メモリマップの CONVENTIONAL 領域から確保する。
 // もし確保に失敗した場合は、BSS の固定領域にフォールバックする。
 
-use crate::slab_allocator::LockedSlabAllocator;
+use crate::slab_allocator::{HeapStats, LockedSlabAllocator};
 use uefi::mem::memory_map::{MemoryMap, MemoryMapOwned, MemoryType};
 use core::alloc::Layout;
 
@@ -107,6 +107,11 @@ pub fn heap_from_conventional() -> bool {
     unsafe { HEAP_FROM_CONVENTIONAL }
 }
 
+/// ヒープの使用状況と断片化の統計（mem コマンドや /proc/meminfo 用）
+pub fn heap_stats() -> HeapStats {
+    ALLOCATOR.stats().unwrap_or_default()
+}
+
 /// alloc の OOM ハンドラ
 ///
 /// 方針: 失敗したら即 panic で停止する。
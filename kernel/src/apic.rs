@@ -14,6 +14,9 @@
 //   1. PIC を全マスク（APIC に移行するため PIC からの割り込みを止める）
 //   2. Local APIC を初期化（タイマー、スプリアス、エラーベクタを設定）
 //   3. I/O APIC を初期化（キーボード IRQ1、マウス IRQ12 を有効化）
+//
+// PCI デバイスの割り込みは I/O APIC を経由せず、MSI-X で Local APIC に直接届ける
+// （msi_address() と pci::enable_msix() を参照）。
 
 use core::sync::atomic::{AtomicBool, Ordering};
 use x2apic::ioapic::IoApic;
@@ -50,6 +53,58 @@ pub fn local_apic_eoi() {
     }
 }
 
+/// MSI/MSI-X のメッセージアドレスを返す。
+///
+/// デバイスがこのアドレスに書き込むと、Local APIC が割り込みとして受け取る。
+///   [31:20] = 0xFEE（固定）
+///   [19:12] = 宛先 Local APIC ID（この CPU = BSP）
+///   [3] RH = 0, [2] DM = 0（物理宛先モード）
+/// APIC が有効でない（PIC モード）なら None。MSI の EOI は Local APIC に
+/// 送る必要があるので、PIC モードでは MSI を使えない。
+pub fn msi_address() -> Option<u64> {
+    if !is_apic_active() {
+        return None;
+    }
+    let apic_id = local_apic_id()?;
+    Some(0xFEE0_0000 | ((apic_id as u64) << 12))
+}
+
+/// この CPU の Local APIC ID を返す。APIC を初期化していなければ None。
+pub fn local_apic_id() -> Option<u32> {
+    let base = *LOCAL_APIC_BASE.get()?;
+    // Local APIC ID レジスタ (base + 0x20) の [31:24] が APIC ID
+    Some(unsafe { core::ptr::read_volatile((base + 0x20) as *const u32) } >> 24)
+}
+
+/// ICR (Interrupt Command Register) で apic_id の CPU に IPI を送り、送り終わるまで待つ。
+///
+/// ICR は上位 (base + 0x310) に宛先、下位 (base + 0x300) にコマンドを書く。
+/// 下位を書いた時点で送信が始まり、ビット 12 (Delivery Status) が 0 に戻ったら送信済み。
+fn send_ipi(apic_id: u32, command: u32) {
+    let Some(&base) = LOCAL_APIC_BASE.get() else {
+        return;
+    };
+    unsafe {
+        core::ptr::write_volatile((base + 0x310) as *mut u32, apic_id << 24);
+        core::ptr::write_volatile((base + 0x300) as *mut u32, command);
+        while core::ptr::read_volatile((base + 0x300) as *const u32) & (1 << 12) != 0 {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// INIT IPI を送る（AP の起動手順の最初。AP をリセットして SIPI を待つ状態にする）。
+/// 0x4500 = Level Assert (ビット 14) | Delivery Mode INIT (0b101)
+pub fn send_init_ipi(apic_id: u32) {
+    send_ipi(apic_id, 0x4500);
+}
+
+/// SIPI (Startup IPI) を送る。AP は物理アドレス vector × 4KiB からリアルモードで動き出す。
+/// 0x4600 = Level Assert (ビット 14) | Delivery Mode Start Up (0b110)
+pub fn send_startup_ipi(apic_id: u32, vector: u8) {
+    send_ipi(apic_id, 0x4600 | vector as u32);
+}
+
 /// APIC を初期化する。
 ///
 /// ACPI テーブルから取得した情報を使って Local APIC と I/O APIC を設定する。
@@ -115,6 +170,10 @@ pub fn init() {
             // ベクタ 33 (= 32 + 1) に配送される → 既存の keyboard_interrupt_handler を再利用
             io_apic.enable_irq(1);
 
+            // IRQ4: シリアル COM1 の受信を有効化
+            // ベクタ 36 (= 32 + 4) に配送される → serial_interrupt_handler
+            io_apic.enable_irq(4);
+
             // IRQ12: マウス（PS/2）を有効化
             // ベクタ 44 (= 32 + 12) に配送される → 既存の mouse_interrupt_handler を再利用
             io_apic.enable_irq(12);
@@ -122,7 +181,7 @@ pub fn init() {
             // IRQ0 (PIT タイマー) は無効のまま。
             // タイマーは Local APIC タイマーを使用する（より正確で省電力）。
         }
-        crate::kprintln!("APIC: I/O APIC initialized at {:#x} (IRQ1=kbd, IRQ12=mouse)",
+        crate::kprintln!("APIC: I/O APIC initialized at {:#x} (IRQ1=kbd, IRQ4=com1, IRQ12=mouse)",
             io_apic_addr);
     } else {
         crate::kprintln!("APIC: No I/O APIC found");
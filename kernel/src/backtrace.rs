@@ -0,0 +1,92 @@
+// backtrace.rs — フレームポインタを辿るスタックバックトレース
+//
+// パニックのメッセージだけでは「どこから呼ばれて落ちたか」が分からず、
+// カーネルのクラッシュを追うのに時間がかかる。
+//
+// カーネルは -C force-frame-pointers=yes でビルドする（kernel/.cargo/config.toml）。
+// するとすべての関数の先頭が `push rbp; mov rbp, rsp` になり、スタック上に
+//
+//   [rbp]     = 呼び出し元の rbp
+//   [rbp + 8] = 呼び出し元への戻りアドレス
+//
+// が鎖のように並ぶ。これを辿れば、デバッグ情報を読まなくても戻りアドレスの列が得られる。
+//
+// パニック中に使うので、ヒープもロックも使わない。壊れたスタックで
+// おかしなアドレスを読みに行かないよう、次のフレームが「今より上にあり、離れすぎていない」
+// ことを確かめてから進み、フレーム数にも上限を設ける。
+// タスクの最初のフレームは rbp = 0 で始まる（scheduler::spawn）ので、そこで止まる。
+//
+// core など事前ビルドのライブラリはフレームポインタを省略していることがあり、
+// その場合は鎖がそこで途切れる（上のチェックで止まる）。
+//
+// シンボルテーブルが埋め込まれていれば（symbols.rs）、各行に関数名とオフセットも付ける。
+
+use core::arch::asm;
+use core::fmt;
+
+/// 辿るフレーム数の上限
+pub const MAX_FRAMES: usize = 32;
+
+/// 隣り合うフレームの間隔の上限（バイト）。
+/// これより離れていたら別のスタックか壊れた値とみなして止める。
+const MAX_FRAME_GAP: u64 = 64 * 1024;
+
+/// 今の関数の rbp（フレームポインタ）を返す。
+///
+/// inline(always) なので、呼び出した関数自身のフレームが得られる。
+#[inline(always)]
+pub fn current_rbp() -> u64 {
+    let rbp: u64;
+    unsafe {
+        asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags));
+    }
+    rbp
+}
+
+/// rbp から始まるフレームの鎖を辿り、戻りアドレスを浅い順に f(番号, アドレス) へ渡す。
+/// 辿ったフレーム数を返す。
+///
+/// # Safety
+/// rbp は 0 か、今のスタック上の有効なフレームを指していること。
+pub unsafe fn walk(mut rbp: u64, mut f: impl FnMut(usize, u64)) -> usize {
+    let mut depth = 0;
+    while depth < MAX_FRAMES && rbp != 0 && rbp.is_multiple_of(8) {
+        let frame = rbp as *const u64;
+        let next = unsafe { frame.read() };
+        let ret = unsafe { frame.add(1).read() };
+        if ret == 0 {
+            break;
+        }
+        f(depth, ret);
+        depth += 1;
+        // スタックは下に伸びるので、呼び出し元のフレームは必ず上にある
+        if next <= rbp || next - rbp > MAX_FRAME_GAP {
+            break;
+        }
+        rbp = next;
+    }
+    depth
+}
+
+/// rbp から辿ったバックトレースを 1 フレーム 1 行で書き出す。書いたフレーム数を返す。
+///
+/// パニックハンドラがシリアルと画面に出すときと、selftest が中身を確かめるときの両方で使う。
+///
+/// # Safety
+/// walk() と同じ。
+pub unsafe fn write_backtrace(w: &mut impl fmt::Write, rbp: u64) -> usize {
+    let _ = w.write_str("Backtrace:\n");
+    let depth = unsafe { walk(rbp, |i, addr| {
+        let _ = write!(w, "  #{:<2} {:#018x}", i, addr);
+        // 戻りアドレスは call の直後なので、1 引いて呼び出し元の関数を引く
+        // （呼び出しが関数の末尾にあると、戻りアドレスは次の関数の先頭になる）
+        if let Some((name, offset)) = crate::symbols::resolve(addr - 1) {
+            let _ = write!(w, "  {}+{:#x}", name, offset + 1);
+        }
+        let _ = w.write_str("\n");
+    }) };
+    if depth == 0 {
+        let _ = w.write_str("  (no frames)\n");
+    }
+    depth
+}
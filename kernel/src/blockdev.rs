@@ -0,0 +1,148 @@
+// blockdev.rs — ブロックデバイスのレジストリ
+//
+// virtio-blk / AHCI / NVMe のディスクはドライバごとのリスト
+// （VIRTIO_BLKS, AHCI_DEVICES, NVME_DEVICES）に入っていて、番号の付け方がばらばら。
+// ここで検出されたディスクすべてに通し番号を振り、SYS_BLOCK_READ / WRITE と
+// FAT32 のマウントが同じ番号で、同じ BlockDevice trait を通してアクセスできるようにする。
+//
+// 番号はドライバの初期化後に register_all() で一度だけ決まり、以後変わらない。
+// virtio-blk → AHCI → NVMe の順に並べるので、0 = disk.img, 1 = hostfs.img は従来どおり。
+
+use alloc::vec::Vec;
+use spin::Mutex;
+
+pub use sabos_blockdev::{BlockDevice, BlockError};
+
+/// ブロックデバイスのバックエンド種別（どのドライバの何番目のデバイスか）。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockBackend {
+    /// virtio-blk デバイス。値は VIRTIO_BLKS 内のインデックス。
+    VirtioBlk(usize),
+    /// AHCI (SATA) デバイス。値は AHCI_DEVICES 内のインデックス。
+    Ahci(usize),
+    /// NVMe デバイス。値は NVME_DEVICES 内のインデックス。
+    Nvme(usize),
+}
+
+/// レジストリに登録されたブロックデバイスの情報
+#[derive(Clone, Copy, Debug)]
+pub struct BlockDeviceInfo {
+    /// レジストリの通し番号（SYS_BLOCK_READ の dev_index と同じ）
+    pub index: usize,
+    pub backend: BlockBackend,
+    /// 容量（デバイスのセクタ数）
+    pub sectors: u64,
+}
+
+/// 登録済みのブロックデバイス。Vec の位置がそのまま通し番号になる。
+static REGISTRY: Mutex<Vec<BlockDeviceInfo>> = Mutex::new(Vec::new());
+
+/// 検出済みのディスクをすべてレジストリに登録する。
+///
+/// virtio_blk::init / ahci::init / nvme::init の後に一度だけ呼ぶ。
+pub fn register_all() {
+    let mut backends = Vec::new();
+    for (i, d) in crate::virtio_blk::VIRTIO_BLKS.lock().iter().enumerate() {
+        backends.push((BlockBackend::VirtioBlk(i), d.capacity()));
+    }
+    for (i, d) in crate::ahci::AHCI_DEVICES.lock().iter().enumerate() {
+        backends.push((BlockBackend::Ahci(i), d.capacity()));
+    }
+    for (i, d) in crate::nvme::NVME_DEVICES.lock().iter().enumerate() {
+        backends.push((BlockBackend::Nvme(i), d.capacity()));
+    }
+
+    let mut registry = REGISTRY.lock();
+    registry.clear();
+    for (index, (backend, sectors)) in backends.into_iter().enumerate() {
+        crate::serial_println!("blockdev: [{}] {:?} ({} sectors)", index, backend, sectors);
+        registry.push(BlockDeviceInfo { index, backend, sectors });
+    }
+}
+
+/// 登録済みのブロックデバイスの一覧を返す
+pub fn devices() -> Vec<BlockDeviceInfo> {
+    REGISTRY.lock().clone()
+}
+
+/// 指定したバックエンドのデバイスの通し番号を返す
+pub fn find(backend: BlockBackend) -> Option<usize> {
+    REGISTRY.lock().iter().find(|d| d.backend == backend).map(|d| d.index)
+}
+
+/// 通し番号のデバイスを BlockDevice として開く
+pub fn open(index: usize) -> Result<KernelBlockDevice, BlockError> {
+    let registry = REGISTRY.lock();
+    let info = registry.get(index).ok_or(BlockError::InvalidArgument)?;
+    Ok(KernelBlockDevice { dev_index: index, backend: info.backend })
+}
+
+/// カーネル用のブロックデバイス。
+/// backend でどのデバイスドライバを使うか指定する。
+/// 読み書きのたびに該当ドライバのリストをロックしてアクセスする。
+#[derive(Clone, Copy)]
+pub struct KernelBlockDevice {
+    /// レジストリの通し番号
+    pub dev_index: usize,
+    pub backend: BlockBackend,
+}
+
+impl BlockDevice for KernelBlockDevice {
+    fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        match self.backend {
+            BlockBackend::VirtioBlk(idx) => {
+                let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
+                if let Some(d) = devs.get_mut(idx) {
+                    d.read_sector(sector, buf).map_err(|_| BlockError::IoError)
+                } else {
+                    Err(BlockError::IoError)
+                }
+            }
+            BlockBackend::Ahci(idx) => {
+                let mut devs = crate::ahci::AHCI_DEVICES.lock();
+                if let Some(d) = devs.get_mut(idx) {
+                    d.read_sector(sector, buf).map_err(|_| BlockError::IoError)
+                } else {
+                    Err(BlockError::IoError)
+                }
+            }
+            BlockBackend::Nvme(idx) => {
+                let mut devs = crate::nvme::NVME_DEVICES.lock();
+                if let Some(d) = devs.get_mut(idx) {
+                    d.read_sector(sector, buf).map_err(|_| BlockError::IoError)
+                } else {
+                    Err(BlockError::IoError)
+                }
+            }
+        }
+    }
+
+    fn write_sector(&mut self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
+        match self.backend {
+            BlockBackend::VirtioBlk(idx) => {
+                let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
+                if let Some(d) = devs.get_mut(idx) {
+                    d.write_sector(sector, buf).map_err(|_| BlockError::IoError)
+                } else {
+                    Err(BlockError::IoError)
+                }
+            }
+            BlockBackend::Ahci(idx) => {
+                let mut devs = crate::ahci::AHCI_DEVICES.lock();
+                if let Some(d) = devs.get_mut(idx) {
+                    d.write_sector(sector, buf).map_err(|_| BlockError::IoError)
+                } else {
+                    Err(BlockError::IoError)
+                }
+            }
+            BlockBackend::Nvme(idx) => {
+                let mut devs = crate::nvme::NVME_DEVICES.lock();
+                if let Some(d) = devs.get_mut(idx) {
+                    d.write_sector(sector, buf).map_err(|_| BlockError::IoError)
+                } else {
+                    Err(BlockError::IoError)
+                }
+            }
+        }
+    }
+}
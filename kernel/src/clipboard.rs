@@ -0,0 +1,49 @@
+// clipboard.rs — カーネルが持つクリップボード
+//
+// GUI アプリ・エディタ・シェルのあいだでテキストを受け渡す（コピー & ペースト）ための
+// 1 つだけの共有バッファ。SYS_CLIPBOARD_SET / SYS_CLIPBOARD_GET で読み書きする。
+//
+// - 大きさは CLIPBOARD_MAX（64KiB）まで。超える set はエラーにして、中身は変えない。
+// - 最後に set したものが残る（last-write-wins）。履歴は持たない。
+// - 中身はバイト列のまま持つ。テキストとして扱うかは読む側に任せる。
+
+use alloc::vec::Vec;
+use spin::Mutex;
+
+/// クリップボードに置ける最大バイト数
+pub const CLIPBOARD_MAX: usize = 64 * 1024;
+
+/// クリップボードの中身
+static CLIPBOARD: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// クリップボードが大きすぎるデータを受け付けなかった
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLarge;
+
+/// クリップボードの中身を data で置き換える
+pub fn set(data: &[u8]) -> Result<(), TooLarge> {
+    if data.len() > CLIPBOARD_MAX {
+        return Err(TooLarge);
+    }
+    // 確保と古い中身の解放はロックの外で済ませる（ロックを持つのは差し替えの一瞬だけ）
+    let data = data.to_vec();
+    let old = core::mem::replace(&mut *CLIPBOARD.lock(), data);
+    drop(old);
+    Ok(())
+}
+
+/// クリップボードの中身を buf にコピーし、中身の長さを返す。
+/// buf が足りなければ何もコピーせず Err(中身の長さ) を返す。
+pub fn get(buf: &mut [u8]) -> Result<usize, usize> {
+    let clipboard = CLIPBOARD.lock();
+    if buf.len() < clipboard.len() {
+        return Err(clipboard.len());
+    }
+    buf[..clipboard.len()].copy_from_slice(&clipboard);
+    Ok(clipboard.len())
+}
+
+/// クリップボードの中身をコピーして返す（シェルの clip コマンド用）
+pub fn contents() -> Vec<u8> {
+    CLIPBOARD.lock().clone()
+}
@@ -90,7 +90,7 @@ pub fn has_input() -> bool {
 /// 入力を受け取れなくなる。
 pub fn grab_keyboard(task_id: u64) {
     KEYBOARD_FOCUS_TASK.store(task_id, Ordering::SeqCst);
-    crate::serial_println!("[console] keyboard focus grabbed by task {}", task_id);
+    crate::klog!(Debug, Console, "keyboard focus grabbed by task {}", task_id);
 }
 
 /// キーボードフォーカスを解放する
@@ -102,7 +102,7 @@ pub fn release_keyboard(task_id: u64) {
     let _ = KEYBOARD_FOCUS_TASK.compare_exchange(
         task_id, 0, Ordering::SeqCst, Ordering::SeqCst
     );
-    crate::serial_println!("[console] keyboard focus released by task {}", task_id);
+    crate::klog!(Debug, Console, "keyboard focus released by task {}", task_id);
 }
 
 /// フォーカス対応のノンブロッキング入力読み取り
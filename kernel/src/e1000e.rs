@@ -15,6 +15,13 @@
 // RX/TX ともにリングバッファ構造で、ハードウェアが Head を進め、
 // ソフトウェアが Tail を進めることでバッファを管理する。
 //
+// ## 受信リングのあふれ
+//
+// net_poller が走れない間に RX ディスクリプタがすべて埋まると (RDH == RDT)、
+// ハードウェアは以降のフレームを捨てる。その回数は統計レジスタ MPC
+// (Missed Packets Count) に積まれるので dropped として集計し、
+// あふれを検出したら取り出し終えた後に recover_rx() で RDT を合わせ直す。
+//
 // ## 参考資料
 //
 // Intel 82574L GbE Controller Family Datasheet
@@ -22,15 +29,16 @@
 
 use alloc::vec::Vec;
 use core::alloc::Layout;
-use spin::Mutex;
 
+use crate::lock_order::{LockClass, OrderedMutex};
+use crate::netstack::NicRxStats;
 use crate::pci;
 use crate::serial_println;
 
 /// グローバルな e1000e ドライバインスタンス。
 /// PCI で検出・初期化された e1000e デバイスを保持する。
 /// virtio-net と同様に Option<E1000e> で管理し、None なら未検出。
-pub static E1000E: Mutex<Option<E1000e>> = Mutex::new(None);
+pub static E1000E: OrderedMutex<Option<E1000e>> = OrderedMutex::new(LockClass::E1000e, None);
 
 // ============================================================
 // レジスタオフセット定数
@@ -88,6 +96,11 @@ mod regs {
     /// TX Descriptor Tail — ソフトウェアが最後に書き込んだ位置
     pub const TDT: u64 = 0x3818;
 
+    /// Missed Packets Count — 受信バッファ不足で捨てたフレーム数（読み取りでクリア）
+    pub const MPC: u64 = 0x4010;
+    /// Receive No Buffers Count — 空きディスクリプタがなかった回数（読み取りでクリア）
+    pub const RNBC: u64 = 0x40A0;
+
     /// Receive Address Low — MAC アドレスの下位 32 ビット（QEMU が自動設定）
     pub const RAL: u64 = 0x5400;
     /// Receive Address High — MAC アドレスの上位 16 ビット + AV（Address Valid）ビット
@@ -105,6 +118,13 @@ const CTRL_SLU: u32 = 1 << 6;
 /// セットするとハードウェアが自動的にクリアする。
 const CTRL_RST: u32 = 1 << 26;
 
+// ============================================================
+// ICR (Interrupt Cause Read) レジスタのビット定数
+// ============================================================
+
+/// Receiver Overrun — 受信 FIFO があふれた
+const ICR_RXO: u32 = 1 << 6;
+
 // ============================================================
 // RCTL (Receive Control) レジスタのビット定数
 // ============================================================
@@ -112,6 +132,11 @@ const CTRL_RST: u32 = 1 << 26;
 /// Receiver Enable — 受信機能を有効化
 const RCTL_EN: u32 = 1 << 1;
 
+/// Multicast Promiscuous Enable — 全マルチキャストフレームを受信
+/// IPv6 の Router Advertisement は ff02::1（MAC 33:33:00:00:00:01）宛てに届くため、
+/// マルチキャストテーブルを設定しない代わりにすべて受け取る。
+const RCTL_MPE: u32 = 1 << 4;
+
 /// Broadcast Accept Mode — ブロードキャストフレームを受信
 const RCTL_BAM: u32 = 1 << 15;
 
@@ -243,6 +268,10 @@ pub struct E1000e {
     rx_cur: usize,
     /// 現在の TX インデックス（次に使うディスクリプタ）
     tx_cur: usize,
+    /// RX リングのあふれを検出し、まだ回復していないか
+    rx_ring_exhausted: bool,
+    /// 受信統計
+    rx_stats: NicRxStats,
     /// MAC アドレス（RAL/RAH レジスタから読み取った値）
     pub mac_address: [u8; 6],
 }
@@ -406,10 +435,11 @@ impl E1000e {
 
         // --- RCTL（受信制御）設定 ---
         // EN: 受信有効化
+        // MPE: マルチキャストフレーム受信（IPv6 NDP / SLAAC 用）
         // BAM: ブロードキャストフレーム受信
         // SECRC: CRC をストリップ
         // Buffer Size = 2048 (RCTL[16:17] = 00b、デフォルト)
-        mmio_write32(bar0, regs::RCTL, RCTL_EN | RCTL_BAM | RCTL_SECRC);
+        mmio_write32(bar0, regs::RCTL, RCTL_EN | RCTL_MPE | RCTL_BAM | RCTL_SECRC);
 
         // --- TCTL（送信制御）設定 ---
         // EN: 送信有効化
@@ -430,6 +460,8 @@ impl E1000e {
             rx_buffers,
             rx_cur: 0,
             tx_cur: 0,
+            rx_ring_exhausted: false,
+            rx_stats: NicRxStats::default(),
             mac_address,
         })
     }
@@ -515,9 +547,15 @@ impl E1000e {
             return None;
         }
 
+        // Head が Tail に追いついていれば空きディスクリプタがない（あふれ）
+        if mmio_read32(self.bar0, regs::RDH) == mmio_read32(self.bar0, regs::RDT) {
+            self.note_rx_overflow();
+        }
+
         let length = desc.length as usize;
         if length == 0 || length > RX_BUFFER_SIZE {
             // 不正なサイズのパケットはスキップ
+            self.rx_stats.dropped += 1;
             desc.status = 0;
             let old_tail = mmio_read32(self.bar0, regs::RDT);
             mmio_write32(self.bar0, regs::RDT, (old_tail + 1) % RX_DESC_COUNT as u32);
@@ -540,6 +578,7 @@ impl E1000e {
         mmio_write32(self.bar0, regs::RDT, idx as u32);
 
         self.rx_cur = (idx + 1) % RX_DESC_COUNT;
+        self.rx_stats.packets += 1;
 
         Some(packet)
     }
@@ -547,8 +586,88 @@ impl E1000e {
     /// ICR を読み取って保留中の割り込みをクリアする。
     /// ポーリングモードでは、イベントフラグをクリアしないとハードウェアが
     /// 新しいイベントを通知しない場合があるため、定期的に呼び出す。
+    ///
+    /// ついでに受信あふれの兆候（ICR.RXO、RNBC）と MPC を統計に取り込む。
+    /// MPC / RNBC は読み取りでクリアされるので差分を足していける。
     pub fn clear_interrupts(&mut self) {
-        let _ = mmio_read32(self.bar0, regs::ICR);
+        let icr = mmio_read32(self.bar0, regs::ICR);
+        let missed = mmio_read32(self.bar0, regs::MPC);
+        let no_buffers = mmio_read32(self.bar0, regs::RNBC);
+        self.rx_stats.dropped += missed as u64;
+        if icr & ICR_RXO != 0 || missed != 0 || no_buffers != 0 {
+            self.note_rx_overflow();
+        }
+    }
+
+    /// あふれを記録する。回復するまでの間は 1 回として数える。
+    fn note_rx_overflow(&mut self) {
+        if !self.rx_ring_exhausted {
+            self.rx_ring_exhausted = true;
+            self.rx_stats.overflows += 1;
+        }
+    }
+
+    /// 受信統計を返す
+    pub fn rx_stats(&self) -> NicRxStats {
+        self.rx_stats
+    }
+
+    /// あふれた RX リングを立て直す
+    ///
+    /// 受信を取り出し終えた後（receive_packet が None を返した後）に呼ぶ。
+    /// rx_cur の 1 つ手前まで全ディスクリプタをハードウェアに渡すよう RDT を
+    /// 書き直す。あふれを検出していたか、RDT がずれていた場合だけ行い、
+    /// 回復処理を行ったら true を返す。
+    pub fn recover_rx(&mut self) -> bool {
+        let desc = unsafe { &*self.rx_descs.add(self.rx_cur) };
+        if desc.status & STATUS_DD != 0 {
+            // まだ取り出していないフレームがある
+            return false;
+        }
+        let expected_tail = ((self.rx_cur + RX_DESC_COUNT - 1) % RX_DESC_COUNT) as u32;
+        if !self.rx_ring_exhausted && mmio_read32(self.bar0, regs::RDT) == expected_tail {
+            return false;
+        }
+        mmio_write32(self.bar0, regs::RDT, expected_tail);
+        self.rx_ring_exhausted = false;
+        self.rx_stats.recoveries += 1;
+        true
+    }
+
+    /// RX / TX リングを最初から作り直す
+    ///
+    /// net_poller が止まったときにウォッチドッグから呼ぶ。送受信を止めてから
+    /// ディスクリプタを初期化し、Head / Tail を new() と同じ位置に戻して再開する。
+    /// デバイスリセットはしないので、RAL/RAH の MAC アドレスはそのまま残る。
+    pub fn reset_rings(&mut self) {
+        let bar0 = self.bar0;
+        let rctl = mmio_read32(bar0, regs::RCTL);
+        let tctl = mmio_read32(bar0, regs::TCTL);
+        mmio_write32(bar0, regs::RCTL, rctl & !RCTL_EN);
+        mmio_write32(bar0, regs::TCTL, tctl & !TCTL_EN);
+
+        for (i, buf) in self.rx_buffers.iter().enumerate() {
+            unsafe {
+                let desc = &mut *self.rx_descs.add(i);
+                desc.addr = *buf as u64;
+                desc.length = 0;
+                desc.status = 0;
+                desc.errors = 0;
+            }
+        }
+        unsafe {
+            core::ptr::write_bytes(self.tx_descs, 0, TX_DESC_COUNT);
+        }
+        mmio_write32(bar0, regs::RDH, 0);
+        mmio_write32(bar0, regs::RDT, (RX_DESC_COUNT - 1) as u32);
+        mmio_write32(bar0, regs::TDH, 0);
+        mmio_write32(bar0, regs::TDT, 0);
+        self.rx_cur = 0;
+        self.tx_cur = 0;
+        self.rx_ring_exhausted = false;
+
+        mmio_write32(bar0, regs::RCTL, rctl | RCTL_EN);
+        mmio_write32(bar0, regs::TCTL, tctl | TCTL_EN);
     }
 }
 
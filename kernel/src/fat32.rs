@@ -1,104 +1,20 @@
 // fat32.rs — FAT32 ファイルシステムドライバ（カーネル統合層）
 //
 // コアロジック（Fat32Fs<D>、DirEntry 等）は libs/fat32 に分離済み。
-// このファイルにはカーネル固有の部分（KernelBlockDevice、VFS 実装）だけを残す。
+// このファイルにはカーネル固有の部分（コンストラクタ、VFS 実装）だけを残す。
+// ディスクへのアクセスは blockdev レジストリ（KernelBlockDevice）を通す。
 
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 
-use sabos_blockdev::{BlockDevice, BlockError};
+use crate::blockdev::{self, KernelBlockDevice};
 
 // sabos-fat32 ライブラリから再エクスポート
 pub use sabos_fat32::{
     Fat32Fs, DirEntry, ATTR_DIRECTORY,
 };
 
-use crate::vfs::{FileSystem, VfsDirEntry, VfsError, VfsNode, VfsNodeKind};
-
-/// ブロックデバイスのバックエンド種別。
-/// virtio-blk と AHCI (SATA) の両方に対応する。
-/// VFS マウント時にどちらのバックエンドを使うか選択する。
-#[derive(Clone, Copy, Debug)]
-pub enum BlockBackend {
-    /// virtio-blk デバイス。dev_index はデバイスリスト内のインデックス。
-    VirtioBlk(usize),
-    /// AHCI (SATA) デバイス。dev_index はデバイスリスト内のインデックス。
-    Ahci(usize),
-    /// NVMe デバイス。dev_index はデバイスリスト内のインデックス。
-    Nvme(usize),
-}
-
-/// カーネル用のブロックデバイス。
-/// backend でどのデバイスドライバを使うか指定する。
-/// デフォルトでは VirtioBlk を使い、AHCI デバイスがある場合はそちらも指定可能。
-#[derive(Clone, Copy)]
-pub struct KernelBlockDevice {
-    /// デバイスインデックス。BlockBackend にも格納されるが、
-    /// Fat32Fs のデバッグ表示等で直接参照する場合に使う。
-    #[allow(dead_code)]
-    pub dev_index: usize,
-    pub backend: BlockBackend,
-}
-
-impl BlockDevice for KernelBlockDevice {
-    fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
-        match self.backend {
-            BlockBackend::VirtioBlk(idx) => {
-                let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
-                if let Some(d) = devs.get_mut(idx) {
-                    d.read_sector(sector, buf).map_err(|_| BlockError::IoError)
-                } else {
-                    Err(BlockError::IoError)
-                }
-            }
-            BlockBackend::Ahci(idx) => {
-                let mut devs = crate::ahci::AHCI_DEVICES.lock();
-                if let Some(d) = devs.get_mut(idx) {
-                    d.read_sector(sector, buf).map_err(|_| BlockError::IoError)
-                } else {
-                    Err(BlockError::IoError)
-                }
-            }
-            BlockBackend::Nvme(idx) => {
-                let mut devs = crate::nvme::NVME_DEVICES.lock();
-                if let Some(d) = devs.get_mut(idx) {
-                    d.read_sector(sector, buf).map_err(|_| BlockError::IoError)
-                } else {
-                    Err(BlockError::IoError)
-                }
-            }
-        }
-    }
-
-    fn write_sector(&mut self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
-        match self.backend {
-            BlockBackend::VirtioBlk(idx) => {
-                let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
-                if let Some(d) = devs.get_mut(idx) {
-                    d.write_sector(sector, buf).map_err(|_| BlockError::IoError)
-                } else {
-                    Err(BlockError::IoError)
-                }
-            }
-            BlockBackend::Ahci(idx) => {
-                let mut devs = crate::ahci::AHCI_DEVICES.lock();
-                if let Some(d) = devs.get_mut(idx) {
-                    d.write_sector(sector, buf).map_err(|_| BlockError::IoError)
-                } else {
-                    Err(BlockError::IoError)
-                }
-            }
-            BlockBackend::Nvme(idx) => {
-                let mut devs = crate::nvme::NVME_DEVICES.lock();
-                if let Some(d) = devs.get_mut(idx) {
-                    d.write_sector(sector, buf).map_err(|_| BlockError::IoError)
-                } else {
-                    Err(BlockError::IoError)
-                }
-            }
-        }
-    }
-}
+use crate::vfs::{DentryLocation, FileSystem, FsSpace, VfsDirEntry, VfsError, VfsNode, VfsNodeKind};
 
 /// カーネル用の FAT32 ドライバ（ニュータイプラッパー）。
 ///
@@ -122,22 +38,15 @@ impl core::ops::DerefMut for Fat32 {
 }
 
 impl Fat32 {
-    /// デバイスインデックス 0（最初の virtio-blk）で Fat32 を初期化する。
+    /// ブロックデバイス 0（最初の virtio-blk = disk.img）で Fat32 を初期化する。
     pub fn new() -> Result<Self, &'static str> {
-        Self::new_with_backend(BlockBackend::VirtioBlk(0))
+        Self::new_with_index(0)
     }
 
-    /// 指定したデバイスインデックスの virtio-blk で Fat32 を初期化する。
+    /// blockdev レジストリの通し番号のデバイスで Fat32 を初期化する。
     pub fn new_with_index(dev_index: usize) -> Result<Self, &'static str> {
-        Self::new_with_backend(BlockBackend::VirtioBlk(dev_index))
-    }
-
-    /// 指定したバックエンドで Fat32 を初期化する。
-    pub fn new_with_backend(backend: BlockBackend) -> Result<Self, &'static str> {
-        let dev_index = match backend {
-            BlockBackend::VirtioBlk(idx) | BlockBackend::Ahci(idx) | BlockBackend::Nvme(idx) => idx,
-        };
-        let inner = Fat32Fs::new_with_device(KernelBlockDevice { dev_index, backend })?;
+        let dev = blockdev::open(dev_index).map_err(|_| "no such block device")?;
+        let inner = Fat32Fs::new_with_device(dev)?;
         Ok(Fat32 { inner })
     }
 
@@ -147,19 +56,13 @@ impl Fat32 {
     }
 
     /// 指定デバイスインデックスの VFS マネージャ用ファクトリ関数。
-    #[allow(dead_code)]
     pub fn new_fs_with_index(dev_index: usize) -> Self {
-        Fat32::new_with_index(dev_index).expect("Fat32::new_fs_with_index: virtio-blk not initialized")
+        Fat32::new_with_index(dev_index).expect("Fat32::new_fs_with_index: device not initialized")
     }
 
-    /// 指定バックエンドの VFS マネージャ用ファクトリ関数。
-    pub fn new_fs_with_backend(backend: BlockBackend) -> Self {
-        Fat32::new_with_backend(backend).expect("Fat32::new_fs_with_backend: device not initialized")
-    }
-
-    /// 内部のバックエンドを取得する
-    fn backend(&self) -> BlockBackend {
-        self.inner.dev.backend
+    /// 内部のブロックデバイスの通し番号を取得する
+    fn dev_index(&self) -> usize {
+        self.inner.dev.dev_index
     }
 }
 
@@ -178,6 +81,19 @@ fn fat32_list_dir(fs: &mut Fat32Fs<KernelBlockDevice>, path: &str) -> Result<Vec
     fs.list_dir(path)
 }
 
+/// FAT32 のディレクトリエントリを VFS のエントリに変換する
+fn to_vfs_dir_entry(e: DirEntry) -> VfsDirEntry {
+    VfsDirEntry {
+        name: e.name,
+        kind: if e.attr & ATTR_DIRECTORY != 0 {
+            VfsNodeKind::Directory
+        } else {
+            VfsNodeKind::File
+        },
+        size: e.size as usize,
+    }
+}
+
 fn fat32_create_file(fs: &mut Fat32Fs<KernelBlockDevice>, path: &str, data: &[u8]) -> Result<(), &'static str> {
     fs.create_file(path, data)
 }
@@ -194,6 +110,10 @@ fn fat32_delete_dir(fs: &mut Fat32Fs<KernelBlockDevice>, path: &str) -> Result<(
     fs.delete_dir(path)
 }
 
+fn fat32_rename(fs: &mut Fat32Fs<KernelBlockDevice>, from: &str, to: &str) -> Result<(), &'static str> {
+    fs.rename(from, to)
+}
+
 struct Fat32File {
     data: Vec<u8>,
 }
@@ -228,7 +148,7 @@ impl FileSystem for Fat32 {
     }
 
     fn open(&self, path: &str) -> Result<Box<dyn VfsNode>, VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         if path == "/" || path.is_empty() {
             return Err(VfsError::NotAFile);
         }
@@ -238,53 +158,130 @@ impl FileSystem for Fat32 {
     }
 
     fn list_dir(&self, path: &str) -> Result<Vec<VfsDirEntry>, VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         let entries = fat32_list_dir(&mut fs.inner, path)
             .map_err(|_| VfsError::NotFound)?;
-        Ok(entries
-            .into_iter()
-            .map(|e| VfsDirEntry {
-                name: e.name,
-                kind: if e.attr & ATTR_DIRECTORY != 0 {
-                    VfsNodeKind::Directory
-                } else {
-                    VfsNodeKind::File
-                },
-                size: e.size as usize,
+        Ok(entries.into_iter().map(to_vfs_dir_entry).collect())
+    }
+
+    /// ディスク上のエントリは VFS を通して書き換えるので、パス検索の結果をキャッシュできる
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    fn lookup(&self, path: &str) -> Result<DentryLocation, VfsError> {
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
+        let entry = fs.find_entry(path).map_err(|e| match e {
+            "File not found" | "Directory not found" | "Not a directory" => VfsError::NotFound,
+            _ => VfsError::IoError,
+        })?;
+        Ok(DentryLocation {
+            first_cluster: entry.first_cluster,
+            size: entry.size,
+            kind: if entry.attr & ATTR_DIRECTORY != 0 {
+                VfsNodeKind::Directory
+            } else {
+                VfsNodeKind::File
+            },
+        })
+    }
+
+    fn read_file_at(&self, loc: &DentryLocation) -> Result<Vec<u8>, VfsError> {
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
+        fs.inner.read_file_at(loc.first_cluster, loc.size)
+            .map_err(|_| VfsError::IoError)
+    }
+
+    fn read_file_chunked_at(
+        &self,
+        loc: &DentryLocation,
+        f: &mut dyn FnMut(&[u8]) -> Result<(), VfsError>,
+    ) -> Result<(), VfsError> {
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
+        // コールバックのエラーはライブラリに &str でしか渡せないので、横に取っておいて返す
+        let mut callback_error = None;
+        fs.inner
+            .read_file_chunks_at(loc.first_cluster, loc.size, |chunk| {
+                f(chunk).map_err(|e| {
+                    callback_error = Some(e);
+                    "aborted by callback"
+                })
             })
-            .collect())
+            .map_err(|_| callback_error.take().unwrap_or(VfsError::IoError))
+    }
+
+    /// パスを辿ってから 1 セクタずつ読む（open() はファイル全体を読み込むので使わない）
+    fn read_file_chunked(
+        &self,
+        path: &str,
+        f: &mut dyn FnMut(&[u8]) -> Result<(), VfsError>,
+    ) -> Result<(), VfsError> {
+        let loc = self.lookup(path)?;
+        if loc.kind != VfsNodeKind::File {
+            return Err(VfsError::NotAFile);
+        }
+        self.read_file_chunked_at(&loc, f)
+    }
+
+    fn list_dir_at(&self, loc: &DentryLocation) -> Result<Vec<VfsDirEntry>, VfsError> {
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
+        let entries = fs.inner.list_dir_at(loc.first_cluster)
+            .map_err(|_| VfsError::IoError)?;
+        Ok(entries.into_iter().map(to_vfs_dir_entry).collect())
     }
 
     fn create_file(&self, path: &str, data: &[u8]) -> Result<(), VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         fat32_create_file(&mut fs.inner, path, data)
             .map_err(|_| VfsError::IoError)
     }
 
     fn delete_file(&self, path: &str) -> Result<(), VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         fat32_delete_file(&mut fs.inner, path)
             .map_err(|_| VfsError::NotFound)
     }
 
     fn create_dir(&self, path: &str) -> Result<(), VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         fat32_create_dir(&mut fs.inner, path)
             .map_err(|_| VfsError::IoError)
     }
 
     fn delete_dir(&self, path: &str) -> Result<(), VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         fat32_delete_dir(&mut fs.inner, path)
             .map_err(|_| VfsError::IoError)
     }
 
+    fn rename(&self, from: &str, to: &str) -> Result<(), VfsError> {
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
+        fat32_rename(&mut fs.inner, from, to).map_err(|e| match e {
+            "already exists" => VfsError::AlreadyExists,
+            "File not found" | "Directory not found" => VfsError::NotFound,
+            _ => VfsError::IoError,
+        })
+    }
+
+    /// クラスタ数 × クラスタサイズから容量を計算する。
+    /// 空きクラスタ数は FAT 全体を走査して数える。
+    fn space(&self) -> Result<FsSpace, VfsError> {
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
+        let cluster_bytes = fs.cluster_bytes() as u64;
+        let total_clusters = fs.total_clusters() as u64;
+        let free_clusters = fs.free_clusters().map_err(|_| VfsError::IoError)? as u64;
+        Ok(FsSpace {
+            total_bytes: total_clusters * cluster_bytes,
+            free_bytes: free_clusters * cluster_bytes,
+        })
+    }
+
     /// ファイルの全内容を一括読み取り（Fat32 最適化版）
     ///
     /// open() → VfsNode::read() を使うと二重にメモリを確保してしまうため、
     /// Fat32 の read_file() を直接呼んでコピーを 1 回に抑える。
     fn read_file(&self, path: &str) -> Result<Vec<u8>, VfsError> {
-        let mut fs = Fat32::new_with_backend(self.backend()).map_err(|_| VfsError::IoError)?;
+        let mut fs = Fat32::new_with_index(self.dev_index()).map_err(|_| VfsError::IoError)?;
         fat32_read_file(&mut fs.inner, path)
             .map_err(|_| VfsError::NotFound)
     }
@@ -9,7 +9,9 @@
 
 use alloc::vec::Vec;
 use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
 use font8x8::UnicodeFonts;
+use sabos_syscall::FbMode;
 use spin::Mutex;
 use uefi::proto::console::gop::{GraphicsOutput, PixelFormat};
 
@@ -43,12 +45,21 @@ pub fn set_global_colors(fg: (u8, u8, u8), bg: (u8, u8, u8)) {
     }
 }
 
+/// (前景色, 背景色) の組。各色は (R, G, B)。
+pub type ColorPair = ((u8, u8, u8), (u8, u8, u8));
+
+/// グローバルライターの現在の (前景色, 背景色) を返す。
+/// ANSI エスケープシーケンスで色が変わったかの確認（selftest）に使う。
+pub fn global_colors() -> Option<ColorPair> {
+    WRITER.lock().as_ref().map(|w| (w.fg_color, w.bg_color))
+}
+
 /// グローバルフレームバッファの画面をクリアする。
 /// シェルの clear コマンドで使う。
 pub fn clear_global_screen() {
     if let Some(writer) = WRITER.lock().as_mut() {
         writer.clear();
-        writer.flush_dirty();
+        writer.flush_if_direct();
     }
 }
 
@@ -73,10 +84,12 @@ pub struct FramebufferInfoSmall {
 }
 
 /// グローバルフレームバッファ情報を取得する。
+///
+/// 論理解像度を設定していれば、幅と高さは論理解像度を返す（描画の座標系に合わせる）。
 pub fn screen_info() -> Option<FramebufferInfoSmall> {
     WRITER.lock().as_ref().map(|writer| FramebufferInfoSmall {
-        width: writer.width as u32,
-        height: writer.height as u32,
+        width: writer.draw_size().0 as u32,
+        height: writer.draw_size().1 as u32,
         stride: writer.stride as u32,
         pixel_format: pixel_format_to_u32(writer.pixel_format),
         bytes_per_pixel: 4,
@@ -106,6 +119,163 @@ struct DirtyRect {
     y_max: usize, // exclusive
 }
 
+/// クリップ領域（シザー矩形）。座標の意味は DirtyRect と同じ（右下は exclusive）。
+///
+/// set_clip() で設定すると、draw_*_global はこの矩形の外には描かない。
+/// コンポジタがアプリの描画をウィンドウ内に閉じ込めるために使う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClipRect {
+    x_min: usize,
+    y_min: usize,
+    x_max: usize, // exclusive
+    y_max: usize, // exclusive
+}
+
+/// 論理解像度のビューポート（set_logical_size() で設定する）。
+///
+/// 低解像度を前提にしたゲームなどのために、draw_*_global の座標を論理解像度の座標として
+/// 受け取り、整数倍に拡大して画面中央の矩形に描く。GOP は ExitBootServices の後は
+/// 使えず画面モードを切り替えられないので、解像度の変更はこの拡大で代わりにする。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Viewport {
+    /// 論理解像度の幅・高さ
+    width: usize,
+    height: usize,
+    /// 拡大率（論理 1 ピクセル = 画面の scale x scale ピクセル）
+    scale: usize,
+    /// ビューポートの左上（画面のピクセル座標）
+    x: usize,
+    y: usize,
+}
+
+/// 記録しておく画面モードの最大数
+pub const MAX_FB_MODES: usize = 64;
+
+/// ExitBootServices の前に GOP から集めた画面モードの一覧（解像度のみ）。
+/// Exit 後はモードを切り替えられないので、SYS_FB_LIST_MODES で見せる情報としてだけ使う。
+static FB_MODES: Mutex<([FbMode; MAX_FB_MODES], usize)> =
+    Mutex::new(([FbMode { width: 0, height: 0 }; MAX_FB_MODES], 0));
+
+/// GOP が対応している画面モードの解像度を記録する。Exit Boot Services の前に呼ぶこと。
+///
+/// ヒープの初期化前に呼ぶので、固定長の配列に入る分だけ（同じ解像度は 1 つにまとめて）覚える。
+pub fn record_gop_modes(gop: &GraphicsOutput) {
+    let mut guard = FB_MODES.lock();
+    let (modes, count) = &mut *guard;
+    for mode in gop.modes() {
+        let (width, height) = mode.info().resolution();
+        let mode = FbMode { width: width as u32, height: height as u32 };
+        if *count < MAX_FB_MODES && !modes[..*count].contains(&mode) {
+            modes[*count] = mode;
+            *count += 1;
+        }
+    }
+}
+
+/// 記録した画面モードを out に詰め、詰めた数を返す
+pub fn list_modes(out: &mut [FbMode]) -> usize {
+    let guard = FB_MODES.lock();
+    let (modes, count) = &*guard;
+    let n = (*count).min(out.len());
+    out[..n].copy_from_slice(&modes[..n]);
+    n
+}
+
+/// マウスカーソル画像の幅・高さの上限（ピクセル）
+pub const CURSOR_MAX_SIZE: usize = 64;
+
+/// 既定の矢印カーソル。'X' は黒の縁、'.' は白の塗り、' ' は透明。
+/// 左上の 1 ピクセルがホットスポット（クリック位置）。
+const ARROW_CURSOR: [&str; 16] = [
+    "X          ",
+    "XX         ",
+    "X.X        ",
+    "X..X       ",
+    "X...X      ",
+    "X....X     ",
+    "X.....X    ",
+    "X......X   ",
+    "X.......X  ",
+    "X........X ",
+    "X.....XXXXX",
+    "X..X..X    ",
+    "X.X X..X   ",
+    "XX  X..X   ",
+    "X    X..X  ",
+    "     XXXX  ",
+];
+
+/// マウスの最新位置（上位 32bit = x, 下位 32bit = y）。
+///
+/// IRQ12 のハンドラは WRITER のロックを取れないことがある（割り込まれたタスクが
+/// 描画中など）ので、位置はここに置いておき、次に画面へ転送するときに反映する。
+static CURSOR_POS: AtomicU64 = AtomicU64::new(0);
+
+/// カーネルが描くマウスカーソル
+///
+/// カーソルはバックバッファには描かず、MMIO（実際の画面）にだけ描く。
+/// アプリの描画（バックバッファ）を汚さないので、カーソルの下に描いても壊れない。
+/// 画面に描く前に下にあったピクセルを under に退避（save-under）しておき、
+/// 動かしたり隠したりするときに書き戻す。
+struct MouseCursor {
+    /// 表示するか
+    visible: bool,
+    /// カーソル画像（R, G, B, A の 4 bytes/pixel）
+    image: Vec<u8>,
+    /// 画像の幅
+    w: usize,
+    /// 画像の高さ
+    h: usize,
+    /// ホットスポット（マウス位置に合わせる画像内の座標）
+    hot_x: usize,
+    hot_y: usize,
+    /// 画面に描いた位置のマウス座標
+    x: usize,
+    y: usize,
+    /// 画面に描いた矩形 (x, y, w, h)。None なら画面に出ていない
+    drawn: Option<(usize, usize, usize, usize)>,
+    /// drawn の矩形に元々あったピクセル（ハードウェアの並び）
+    under: Vec<u8>,
+    /// カーソルを表示・変更したタスク ID（終了時の自動解除用）
+    owner: Option<u64>,
+}
+
+impl MouseCursor {
+    fn new() -> Self {
+        let (image, w, h) = arrow_image();
+        Self {
+            visible: false,
+            image,
+            w,
+            h,
+            hot_x: 0,
+            hot_y: 0,
+            x: 0,
+            y: 0,
+            drawn: None,
+            under: Vec::new(),
+            owner: None,
+        }
+    }
+}
+
+/// ARROW_CURSOR を RGBA の画像に変換する。(画像, 幅, 高さ) を返す。
+fn arrow_image() -> (Vec<u8>, usize, usize) {
+    let w = ARROW_CURSOR[0].len();
+    let h = ARROW_CURSOR.len();
+    let mut image = Vec::with_capacity(w * h * 4);
+    for row in ARROW_CURSOR {
+        for c in row.bytes() {
+            image.extend_from_slice(match c {
+                b'X' => &[0, 0, 0, 255],
+                b'.' => &[255, 255, 255, 255],
+                _ => &[0, 0, 0, 0],
+            });
+        }
+    }
+    (image, w, h)
+}
+
 /// 描画エラー。
 /// ユーザー空間からの引数ミスを検出するために使う。
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -116,6 +286,8 @@ pub enum DrawError {
     OutOfBounds,
     /// サイズが 0
     InvalidSize,
+    /// 他のタスクがダブルバッファモードを使用中
+    Busy,
 }
 
 /// 1 ピクセルを描画する（グローバル）。
@@ -125,13 +297,13 @@ pub fn draw_pixel_global(x: usize, y: usize, r: u8, g: u8, b: u8) -> Result<(),
         return Err(DrawError::NotInitialized);
     };
 
-    if x >= writer.width || y >= writer.height {
+    let (width, height) = writer.draw_size();
+    if x >= width || y >= height {
         return Err(DrawError::OutOfBounds);
     }
 
-    writer.put_pixel(x, y, r, g, b);
-    writer.mark_dirty(x, y, 1, 1);
-    writer.flush_dirty();
+    writer.plot(x, y, r, g, b);
+    writer.flush_if_direct();
     Ok(())
 }
 
@@ -153,35 +325,19 @@ pub fn draw_rect_global(
     if w == 0 || h == 0 {
         return Err(DrawError::InvalidSize);
     }
-    if x >= writer.width || y >= writer.height {
+    let (width, height) = writer.draw_size();
+    if x >= width || y >= height {
         return Err(DrawError::OutOfBounds);
     }
     let end_x = x.checked_add(w).ok_or(DrawError::OutOfBounds)?;
     let end_y = y.checked_add(h).ok_or(DrawError::OutOfBounds)?;
-    if end_x > writer.width || end_y > writer.height {
+    if end_x > width || end_y > height {
         return Err(DrawError::OutOfBounds);
     }
 
-    // バックバッファに矩形を描画してから、その領域だけ MMIO に転送。
-    // 行テンプレートを作って行ごとに copy_within することで put_pixel ループより高速。
-    let pixel = writer.make_pixel(r, g, b);
-    let bpp = 4;
-    let row_bytes = w * bpp;
-
-    // 最初の行をテンプレートとして作成
-    let first_row_offset = (y * writer.stride + x) * bpp;
-    for xx in 0..w {
-        let offset = first_row_offset + xx * bpp;
-        writer.backbuf[offset..offset + bpp].copy_from_slice(&pixel);
-    }
-
-    // 残りの行は最初の行を copy_within でコピー（同じ色パターンなので行コピーで済む）
-    for yy in (y + 1)..end_y {
-        let dst = (yy * writer.stride + x) * bpp;
-        writer.backbuf.copy_within(first_row_offset..first_row_offset + row_bytes, dst);
-    }
-    writer.mark_dirty(x, y, w, h);
-    writer.flush_dirty();
+    let (x, y, w, h) = writer.to_screen(x, y, w, h);
+    writer.fill_rect(x, y, w, h, (r, g, b));
+    writer.flush_if_direct();
     Ok(())
 }
 
@@ -200,7 +356,8 @@ pub fn draw_line_global(
         return Err(DrawError::NotInitialized);
     };
 
-    if x0 >= writer.width || y0 >= writer.height || x1 >= writer.width || y1 >= writer.height {
+    let (width, height) = writer.draw_size();
+    if x0 >= width || y0 >= height || x1 >= width || y1 >= height {
         return Err(DrawError::OutOfBounds);
     }
 
@@ -210,6 +367,7 @@ pub fn draw_line_global(
     let bb_y = y0.min(y1);
     let bb_w = x0.max(x1) - bb_x + 1;
     let bb_h = y0.max(y1) - bb_y + 1;
+    let (bb_x, bb_y, bb_w, bb_h) = writer.to_screen(bb_x, bb_y, bb_w, bb_h);
 
     // Bresenham
     let mut x0 = x0 as i32;
@@ -224,7 +382,7 @@ pub fn draw_line_global(
 
     loop {
         if x0 >= 0 && y0 >= 0 {
-            writer.put_pixel(x0 as usize, y0 as usize, r, g, b);
+            writer.plot(x0 as usize, y0 as usize, r, g, b);
         }
         if x0 == x1 && y0 == y1 {
             break;
@@ -242,19 +400,41 @@ pub fn draw_line_global(
 
     // bounding box だけをダーティにして転送（以前の全画面 flush より高速）
     writer.mark_dirty(bb_x, bb_y, bb_w, bb_h);
-    writer.flush_dirty();
+    writer.flush_if_direct();
     Ok(())
 }
 
+/// draw_blit_global に渡すバッファのピクセルの並び。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlitFormat {
+    /// ハードウェアと同じ並び（screen_info() の pixel_format を見て詰めたもの）
+    Native,
+    /// R, G, B, X の順
+    Rgbx,
+}
+
 /// バッファの内容を矩形として描画する（グローバル）。
 ///
-/// buf は RGBX（4 bytes/pixel）を想定。alpha は無視する。
+/// buf は 4 bytes/pixel、1 行 w ピクセルで詰めたもの。alpha（4 バイト目）は無視する。
+/// 左上 (x, y) は画面内でなければならないが、右端・下端からはみ出した部分は
+/// 切り捨てて描く（ウィンドウを画面端に寄せたときなど）。クリップ領域の外も描かない。
+///
+/// buf の並びがハードウェアと同じなら行単位の copy_from_slice だけで済む（高速パス）。
+/// 違う場合はピクセルごとに make_pixel で並べ替える（低速パス）。
+/// 320x240 なら 76800 回のピクセル変換が 240 回の行コピーになる。
+/// 実際の差は shell の blit_bench コマンドで測れる。
+///
+/// alpha = true なら 4 バイト目をアルファ値（0 = 透明, 255 = 不透明）として、
+/// いまバックバッファにある内容と `out = src * a + dst * (1 - a)` で合成する。
+/// 影やハイライトのような半透明の重ね描き用。常にピクセル単位の処理になる。
 pub fn draw_blit_global(
     x: usize,
     y: usize,
     w: usize,
     h: usize,
     buf: &[u8],
+    format: BlitFormat,
+    alpha: bool,
 ) -> Result<(), DrawError> {
     let mut guard = WRITER.lock();
     let Some(writer) = guard.as_mut() else {
@@ -264,12 +444,8 @@ pub fn draw_blit_global(
     if w == 0 || h == 0 {
         return Err(DrawError::InvalidSize);
     }
-    if x >= writer.width || y >= writer.height {
-        return Err(DrawError::OutOfBounds);
-    }
-    let end_x = x.checked_add(w).ok_or(DrawError::OutOfBounds)?;
-    let end_y = y.checked_add(h).ok_or(DrawError::OutOfBounds)?;
-    if end_x > writer.width || end_y > writer.height {
+    let (width, height) = writer.draw_size();
+    if x >= width || y >= height {
         return Err(DrawError::OutOfBounds);
     }
 
@@ -279,63 +455,446 @@ pub fn draw_blit_global(
         return Err(DrawError::InvalidSize);
     }
 
-    // ユーザー空間のバッファはネイティブピクセルフォーマット（BGR/RGB）で
-    // 書き込み済みなので、ピクセル単位のフォーマット変換は不要。
-    // 行単位の memcpy でバックバッファにコピーする。
-    // これにより 78万回のピクセル変換 → 768回の行コピーに削減される。
-    let row_bytes = w * 4;
-    let mut src_offset = 0;
-    for yy in y..end_y {
-        let dst_offset = (yy * writer.stride + x) * 4;
-        writer.backbuf[dst_offset..dst_offset + row_bytes]
-            .copy_from_slice(&buf[src_offset..src_offset + row_bytes]);
-        src_offset += row_bytes;
+    // 論理解像度なら、バッファを拡大率ぶん引き伸ばしたものを画面の座標に描く（最近傍法）
+    let scaled;
+    let (buf, x, y, w, h) = match writer.viewport {
+        Some(vp) if vp.scale > 1 => {
+            scaled = scale_image(&buf[..byte_len], w, h, vp.scale);
+            let (x, y, w, h) = writer.to_screen(x, y, w, h);
+            (&scaled[..], x, y, w, h)
+        }
+        _ => {
+            let (x, y, w, h) = writer.to_screen(x, y, w, h);
+            (buf, x, y, w, h)
+        }
+    };
+
+    // 画面・クリップ領域からはみ出す分を切り詰める。バッファ側の 1 行は元の w のまま。
+    // クリップ領域の左上が (x, y) より右下にあれば、バッファの途中から使う
+    let src_x = x;
+    let src_y = y;
+    let Some((x, y, visible_w, visible_h)) = writer.clip_rect(x, y, w, h) else {
+        return Ok(());
+    };
+    let src_row_bytes = w * 4;
+    let src_start = ((y - src_y) * w + (x - src_x)) * 4;
+    let row_bytes = visible_w * 4;
+
+    let same_format = match format {
+        BlitFormat::Native => true,
+        BlitFormat::Rgbx => writer.pixel_format == PixelFormat::Rgb,
+    };
+
+    if alpha {
+        // アルファ合成: ピクセルごとにバックバッファの内容と混ぜる
+        for row in 0..visible_h {
+            let src = src_start + row * src_row_bytes;
+            let src_row = &buf[src..src + row_bytes];
+            let dst = ((y + row) * writer.stride + x) * 4;
+            for (i, px) in src_row.chunks_exact(4).enumerate() {
+                let a = px[3];
+                if a == 0 {
+                    // 完全に透明なピクセルは何もしない（影の外側など、よくあるので速くしておく）
+                    continue;
+                }
+                let src = if same_format {
+                    [px[0], px[1], px[2], 0]
+                } else {
+                    writer.make_pixel(px[0], px[1], px[2])
+                };
+                let out = &mut writer.backbuf[dst + i * 4..dst + i * 4 + 4];
+                if a == 255 {
+                    out.copy_from_slice(&src);
+                } else {
+                    for c in 0..3 {
+                        out[c] = blend_channel(src[c], out[c], a);
+                    }
+                }
+            }
+        }
+    } else if same_format && x == 0 && visible_w == w && w == writer.stride {
+        // 高速パス（全幅）: バッファもバックバッファも行間に隙間がないので 1 回の memcpy
+        let len = visible_h * row_bytes;
+        writer.backbuf[y * row_bytes..y * row_bytes + len].copy_from_slice(&buf[src_start..src_start + len]);
+    } else if same_format {
+        // 高速パス: フォーマット変換は不要なので行単位の memcpy（stride を考慮）
+        for row in 0..visible_h {
+            let src = src_start + row * src_row_bytes;
+            let dst = ((y + row) * writer.stride + x) * 4;
+            writer.backbuf[dst..dst + row_bytes].copy_from_slice(&buf[src..src + row_bytes]);
+        }
+    } else {
+        // 低速パス: RGBX → ハードウェアの並びにピクセルごとに変換
+        for row in 0..visible_h {
+            let src = src_start + row * src_row_bytes;
+            let src_row = &buf[src..src + row_bytes];
+            let dst = ((y + row) * writer.stride + x) * 4;
+            for (i, px) in src_row.chunks_exact(4).enumerate() {
+                let pixel = writer.make_pixel(px[0], px[1], px[2]);
+                writer.backbuf[dst + i * 4..dst + i * 4 + 4].copy_from_slice(&pixel);
+            }
+        }
     }
 
     // 変更された矩形領域をダーティとしてマークし、MMIO に転送
-    writer.mark_dirty(x, y, w, h);
-    writer.flush_dirty();
+    writer.mark_dirty(x, y, visible_w, visible_h);
+    writer.flush_if_direct();
     Ok(())
 }
 
+/// 4 bytes/pixel の画像を縦横 scale 倍に引き伸ばす（最近傍法）
+fn scale_image(buf: &[u8], w: usize, h: usize, scale: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len() * scale * scale);
+    for row in buf.chunks_exact(w * 4).take(h) {
+        let start = out.len();
+        for px in row.chunks_exact(4) {
+            for _ in 0..scale {
+                out.extend_from_slice(px);
+            }
+        }
+        // 引き伸ばした 1 行を残りの scale - 1 行にそのまま複製する
+        let row_len = out.len() - start;
+        for _ in 1..scale {
+            out.extend_from_within(start..start + row_len);
+        }
+    }
+    out
+}
+
+/// 1 チャンネル分のアルファ合成。src * a / 255 + dst * (255 - a) / 255 を四捨五入する。
+#[inline(always)]
+fn blend_channel(src: u8, dst: u8, a: u8) -> u8 {
+    let a = a as u32;
+    ((src as u32 * a + dst as u32 * (255 - a) + 127) / 255) as u8
+}
+
+/// draw_text_global で指定できる拡大率の上限
+pub const MAX_TEXT_SCALE: usize = 8;
+
 /// 指定位置に文字列を描画する（グローバル）。
+///
+/// scale は整数倍の拡大率（1 = 8x8 のまま）で、各グリフを最近傍法で拡大して描く。
+/// '\n' で x に戻って 1 行（8 * scale ピクセル）下に進む。
+/// コンソールと違って折り返しやスクロールはせず、画面からはみ出した部分は描かない。
+/// 呼び出し側が続けて文字列を並べられるように、最も長い行の幅（ピクセル）を返す。
 pub fn draw_text_global(
     x: usize,
     y: usize,
     fg: (u8, u8, u8),
     bg: (u8, u8, u8),
     text: &str,
-) -> Result<(), DrawError> {
+    scale: usize,
+) -> Result<usize, DrawError> {
+    if scale == 0 || scale > MAX_TEXT_SCALE {
+        return Err(DrawError::InvalidSize);
+    }
+
     let mut guard = WRITER.lock();
     let Some(writer) = guard.as_mut() else {
         return Err(DrawError::NotInitialized);
     };
 
-    if x >= writer.width || y >= writer.height {
+    let (width, height) = writer.draw_size();
+    if x >= width || y >= height {
         return Err(DrawError::OutOfBounds);
     }
+    // 論理解像度なら、座標を画面に直して文字も拡大率ぶん大きく描く
+    let (x, y, _, _) = writer.to_screen(x, y, 0, 0);
+    let unit = writer.viewport.map_or(1, |vp| vp.scale);
+    let scale = scale * unit;
 
     let old_fg = writer.fg_color;
     let old_bg = writer.bg_color;
-    let old_x = writer.cursor_x;
-    let old_y = writer.cursor_y;
-
     writer.set_colors(fg, bg);
-    writer.cursor_x = x;
-    writer.cursor_y = y;
 
-    let _ = writer.write_str(text);
+    let (mut cx, mut cy) = (x, y);
+    let mut width = 0;
+    for c in text.chars() {
+        if c == '\n' {
+            cx = x;
+            cy += CHAR_HEIGHT * scale;
+            continue;
+        }
+        if cx < writer.width && cy < writer.height {
+            writer.draw_char(cx, cy, c, scale);
+        }
+        cx += CHAR_WIDTH * scale;
+        width = width.max(cx - x);
+    }
 
     writer.set_colors(old_fg, old_bg);
-    writer.cursor_x = old_x;
-    writer.cursor_y = old_y;
 
     // 各 draw_char が mark_dirty しているので、まとめて flush_dirty で転送。
     // 以前の全画面 flush() より効率的（テキスト領域の bounding box だけ転送）。
+    // ダブルバッファモード中は present() まで転送を遅らせる。
+    writer.flush_if_direct();
+    Ok(width / unit)
+}
+
+/// ダブルバッファモードを切り替える（グローバル）。
+///
+/// 有効にすると draw_*_global や kprint! はバックバッファに描くだけになり、
+/// present() を呼ぶまで画面に反映されない。GUI アプリが 1 フレーム分を
+/// まとめて描いてから一度に転送するために使う。
+/// 同時に有効にできるのは 1 タスクだけで、他のタスクが有効にしていれば Busy を返す。
+/// 無効にするときは溜まっている描画を転送してから直接モードに戻す。
+pub fn set_double_buffering(task_id: u64, enabled: bool) -> Result<(), DrawError> {
+    let mut guard = WRITER.lock();
+    let Some(writer) = guard.as_mut() else {
+        return Err(DrawError::NotInitialized);
+    };
+
+    match writer.double_buffer_owner {
+        Some(owner) if owner != task_id => return Err(DrawError::Busy),
+        _ => {}
+    }
+
+    if enabled {
+        writer.double_buffer_owner = Some(task_id);
+    } else {
+        writer.double_buffer_owner = None;
+        writer.flush_dirty();
+    }
+    Ok(())
+}
+
+/// クリップ領域（シザー矩形）を設定する（グローバル）。
+///
+/// 以降の draw_*_global は (x, y, w, h) の外には描かなくなる。
+/// 画面からはみ出す部分は画面に合わせて切り詰める。
+/// カーネルのコンソール出力（kprint!）にはクリップは効かない。
+/// 設定したタスクが終了すると release_task() で自動的に解除される。
+pub fn set_clip(x: usize, y: usize, w: usize, h: usize) -> Result<(), DrawError> {
+    let mut guard = WRITER.lock();
+    let Some(writer) = guard.as_mut() else {
+        return Err(DrawError::NotInitialized);
+    };
+
+    if w == 0 || h == 0 {
+        return Err(DrawError::InvalidSize);
+    }
+    let (width, height) = writer.draw_size();
+    if x >= width || y >= height {
+        return Err(DrawError::OutOfBounds);
+    }
+    let (x, y, w, h) = writer.to_screen(x, y, w.min(width - x), h.min(height - y));
+    writer.clip = Some(ClipRect {
+        x_min: x,
+        y_min: y,
+        x_max: x + w,
+        y_max: y + h,
+    });
+    writer.clip_owner = Some(crate::scheduler::current_task_id());
+    Ok(())
+}
+
+/// クリップ領域を解除して、画面全体に描けるようにする（グローバル）。
+pub fn clear_clip() {
+    if let Some(writer) = WRITER.lock().as_mut() {
+        writer.clip = None;
+        writer.clip_owner = None;
+    }
+}
+
+/// マウスの位置を更新する（IRQ12 のハンドラから呼ぶ）。
+///
+/// ロックが取れればその場でカーソルを描き直す。描画中のタスクがロックを
+/// 持っていれば位置だけ記録し、そのタスクが画面に転送するときに反映される。
+pub fn move_cursor(x: usize, y: usize) {
+    CURSOR_POS.store(((x as u64) << 32) | (y as u64 & 0xFFFF_FFFF), Ordering::Relaxed);
+    if let Some(mut guard) = WRITER.try_lock()
+        && let Some(writer) = guard.as_mut()
+        && writer.mouse_cursor.visible
+    {
+        writer.erase_cursor();
+        (writer.mouse_cursor.x, writer.mouse_cursor.y) = (x, y);
+        writer.paint_cursor();
+    }
+}
+
+/// カーネルが描くマウスカーソルの表示・非表示を切り替える（グローバル）。
+///
+/// 既定では非表示（テキストコンソールの邪魔にならないように）。
+/// 表示したタスクが終了すると自動で非表示に戻る。
+pub fn set_cursor_visible(visible: bool) {
+    if let Some(writer) = WRITER.lock().as_mut() {
+        writer.erase_cursor();
+        writer.mouse_cursor.visible = visible;
+        if visible {
+            let pos = CURSOR_POS.load(Ordering::Relaxed);
+            writer.mouse_cursor.x = (pos >> 32) as usize;
+            writer.mouse_cursor.y = (pos & 0xFFFF_FFFF) as usize;
+            writer.mouse_cursor.owner = Some(crate::scheduler::current_task_id());
+            writer.paint_cursor();
+        } else {
+            writer.mouse_cursor.owner = None;
+        }
+    }
+}
+
+/// マウスカーソルが表示中かどうか。
+pub fn cursor_visible() -> bool {
+    WRITER.lock().as_ref().is_some_and(|writer| writer.mouse_cursor.visible)
+}
+
+/// マウスカーソルの画像を変える（グローバル）。表示・非表示は変えない。
+///
+/// image は R, G, B, A の 4 bytes/pixel で w * h ピクセル。None なら既定の矢印に戻す。
+/// (hot_x, hot_y) は画像内のホットスポット（マウス位置に合わせる点）。
+pub fn set_cursor_image(image: Option<&[u8]>, w: usize, h: usize, hot_x: usize, hot_y: usize) -> Result<(), DrawError> {
+    let (image, w, h, hot_x, hot_y) = match image {
+        Some(image) => {
+            if w == 0 || h == 0 || w > CURSOR_MAX_SIZE || h > CURSOR_MAX_SIZE || image.len() != w * h * 4 {
+                return Err(DrawError::InvalidSize);
+            }
+            if hot_x >= w || hot_y >= h {
+                return Err(DrawError::OutOfBounds);
+            }
+            (image.to_vec(), w, h, hot_x, hot_y)
+        }
+        None => {
+            let (image, w, h) = arrow_image();
+            (image, w, h, 0, 0)
+        }
+    };
+
+    let mut guard = WRITER.lock();
+    let Some(writer) = guard.as_mut() else {
+        return Err(DrawError::NotInitialized);
+    };
+    writer.erase_cursor();
+    let cursor = &mut writer.mouse_cursor;
+    cursor.image = image;
+    (cursor.w, cursor.h, cursor.hot_x, cursor.hot_y) = (w, h, hot_x, hot_y);
+    cursor.owner = Some(crate::scheduler::current_task_id());
+    writer.paint_cursor();
+    Ok(())
+}
+
+/// 論理解像度を設定する（グローバル）。拡大率を返す。
+///
+/// 以降の draw_*_global と set_clip は (0, 0)〜(w, h) の論理座標で描き、
+/// 画面に収まる最大の整数倍に拡大して画面中央に描く。screen_info() も論理解像度を返す。
+/// ビューポートの外（上下左右の余白）は設定時に黒く塗り、以降は描かない。
+/// w = h = 0 なら解除して画面のピクセル座標に戻す。
+/// カーネルのコンソール出力（kprint!）とマウスカーソルの座標は画面のピクセル座標のまま。
+/// 設定したタスクが終了すると release_task() で自動的に解除される。
+pub fn set_logical_size(w: usize, h: usize) -> Result<usize, DrawError> {
+    let mut guard = WRITER.lock();
+    let Some(writer) = guard.as_mut() else {
+        return Err(DrawError::NotInitialized);
+    };
+
+    if (w == 0) != (h == 0) {
+        return Err(DrawError::InvalidSize);
+    }
+    if w > writer.width || h > writer.height {
+        return Err(DrawError::OutOfBounds);
+    }
+    // 座標系が変わるので、前の座標系で設定したクリップ領域は捨てる
+    writer.clip = None;
+    writer.clip_owner = None;
+    if w == 0 {
+        writer.viewport = None;
+        writer.viewport_owner = None;
+        return Ok(1);
+    }
+
+    let scale = (writer.width / w).min(writer.height / h);
+    let vp = Viewport {
+        width: w,
+        height: h,
+        scale,
+        x: (writer.width - w * scale) / 2,
+        y: (writer.height - h * scale) / 2,
+    };
+    // 余白を黒く塗る（前に画面にあった内容がビューポートの周りに残らないように）
+    writer.viewport = None;
+    let (right, bottom) = (vp.x + w * scale, vp.y + h * scale);
+    writer.fill_rect(0, 0, writer.width, vp.y, (0, 0, 0));
+    writer.fill_rect(0, bottom, writer.width, writer.height - bottom, (0, 0, 0));
+    writer.fill_rect(0, vp.y, vp.x, h * scale, (0, 0, 0));
+    writer.fill_rect(right, vp.y, writer.width - right, h * scale, (0, 0, 0));
+    writer.flush_if_direct();
+
+    writer.viewport = Some(vp);
+    writer.viewport_owner = Some(crate::scheduler::current_task_id());
+    Ok(scale)
+}
+
+/// タスク終了時に、そのタスクが設定した描画モードを解除する。
+///
+/// - ダブルバッファモード: present() を呼ばずに終了（クラッシュ含む）したアプリのせいで
+///   コンソール出力が画面に出なくなるのを防ぐ。
+/// - クリップ領域: 次に起動したアプリの描画が前のアプリのウィンドウに閉じ込められるのを防ぐ。
+/// - 論理解像度: 次に起動したアプリの座標が拡大されたままにならないように戻す。
+/// - マウスカーソル: GUI アプリが表示したカーソルがシェルに戻っても残らないように、
+///   隠して既定の矢印に戻す。
+///
+/// どれも、そのタスクが設定したものでなければ何もしない。
+pub fn release_task(task_id: u64) {
+    if let Some(writer) = WRITER.lock().as_mut() {
+        if writer.mouse_cursor.owner == Some(task_id) {
+            writer.erase_cursor();
+            writer.mouse_cursor = MouseCursor::new();
+        }
+        if writer.double_buffer_owner == Some(task_id) {
+            writer.double_buffer_owner = None;
+            writer.flush_dirty();
+        }
+        if writer.clip_owner == Some(task_id) {
+            writer.clip = None;
+            writer.clip_owner = None;
+        }
+        if writer.viewport_owner == Some(task_id) {
+            writer.viewport = None;
+            writer.viewport_owner = None;
+        }
+    }
+}
+
+/// ダブルバッファモードが有効かどうか。
+pub fn is_double_buffered() -> bool {
+    WRITER
+        .lock()
+        .as_ref()
+        .is_some_and(|writer| writer.double_buffer_owner.is_some())
+}
+
+/// バックバッファに溜まった描画を MMIO フレームバッファへ一括転送する。
+///
+/// 前回の present() 以降に変更された領域の bounding box を 1 回で転送する。
+/// 直接モードでは描画のたびに転送済みなので、通常は何もしない。
+pub fn present() -> Result<(), DrawError> {
+    let mut guard = WRITER.lock();
+    let Some(writer) = guard.as_mut() else {
+        return Err(DrawError::NotInitialized);
+    };
     writer.flush_dirty();
     Ok(())
 }
 
+/// MMIO フレームバッファ（実際に画面に出ている内容）から 1 ピクセル読み出す。
+///
+/// バックバッファではなくハードウェア側を読むので、
+/// selftest でダブルバッファの転送タイミングを確認するのに使う。
+pub fn read_screen_pixel(x: usize, y: usize) -> Option<[u8; 4]> {
+    let guard = WRITER.lock();
+    let writer = guard.as_ref()?;
+    if x >= writer.width || y >= writer.height {
+        return None;
+    }
+    let offset = (y * writer.stride + x) * 4;
+    if offset + 4 > writer.fb_size {
+        return None;
+    }
+    let mut pixel = [0u8; 4];
+    for (i, byte) in pixel.iter_mut().enumerate() {
+        *byte = unsafe { core::ptr::read_volatile(writer.fb_ptr.add(offset + i)) };
+    }
+    Some(pixel)
+}
+
 /// kprint!/kprintln! マクロの内部実装。
 /// フレームバッファとシリアルの両方に出力する。
 /// spin::Mutex で排他制御する。
@@ -346,16 +905,35 @@ pub fn draw_text_global(
 /// タイマー割り込みが発火せずデッドロックする問題があった。
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
+    // 後から /proc/kmsg や dmesg で読めるよう、カーネルログにも残す
+    crate::kmsg::append_fmt(args);
+    _print_console(args);
+}
+
+/// コンソールのカーソル位置を文字セル単位（桁, 行）で返す。初期化前は None。
+pub fn text_cursor() -> Option<(usize, usize)> {
+    WRITER.lock().as_ref().map(|w| (w.cursor_x / CHAR_WIDTH, w.cursor_y / CHAR_HEIGHT))
+}
+
+/// カーネルログには残さず、フレームバッファとシリアルにだけ出力する。
+/// dmesg がログの中身を表示するときに使う（表示した内容がまたログに積まれないように）。
+pub fn _print_console(args: fmt::Arguments) {
     use core::fmt::Write;
     // フレームバッファに出力
     if let Some(writer) = WRITER.lock().as_mut() {
+        // アプリが設定したクリップ領域と論理解像度はコンソール出力には効かせない
+        let clip = writer.clip.take();
+        let viewport = writer.viewport.take();
         writer.write_fmt(args).unwrap();
+        writer.clip = clip;
+        writer.viewport = viewport;
 
         // draw_char / scroll_up が mark_dirty しているので、
         // flush_dirty で変更領域の bounding box だけ MMIO に転送する。
         // 以前は手動でスクロール判定して flush/flush_rect を切り替えていたが、
         // dirty rect トラッキングにより自動的に最小限の領域が転送される。
-        writer.flush_dirty();
+        // ダブルバッファモード中はテキストも次の present() でまとめて表示される。
+        writer.flush_if_direct();
     }
     // シリアルにも出力（デュアル出力）
     // Exit Boot Services 後のデバッグに便利。
@@ -382,6 +960,186 @@ macro_rules! kprintln {
     ($($arg:tt)*) => ($crate::kprint!("{}\n", format_args!($($arg)*)));
 }
 
+// =================================================================
+// ANSI エスケープシーケンス
+// =================================================================
+//
+// ユーザープログラムの色付き出力（"\x1b[31mError\x1b[0m" など）を
+// そのまま描くと ESC 以降がゴミ文字として画面に出てしまう。
+// コンソールへの書き込み経路で CSI シーケンスを解釈し、
+// SGR（色）とカーソル移動の基本だけを反映する。
+//
+// 解釈しないシーケンスは「最後まで読み飛ばす」だけにして、
+// 途中で打ち切って残りを文字として描く（= 誤解釈する）ことはしない。
+// ただし終端が来ないまま長く続くものは壊れた入力とみなして捨てる。
+
+/// CSI のパラメータの最大個数。これを超えるシーケンスは無視する。
+const ANSI_MAX_PARAMS: usize = 8;
+
+/// ESC を含めた 1 シーケンスの最大長（バイト）。超えたら破棄して通常状態に戻る。
+const ANSI_MAX_SEQ_LEN: usize = 32;
+
+/// パラメータ値の上限。大きな数字が来ても桁あふれしないよう飽和させる。
+const ANSI_MAX_PARAM_VALUE: u16 = 9999;
+
+/// SGR 30-37 / 40-47 に対応する 8 色。
+/// シェルのエラー表示などと同じ色味にして、紺の背景でも読めるようにしている。
+pub const ANSI_COLORS: [(u8, u8, u8); 8] = [
+    (0, 0, 0),       // 0: 黒
+    (255, 100, 100), // 1: 赤
+    (0, 255, 0),     // 2: 緑
+    (255, 255, 0),   // 3: 黄
+    (100, 100, 255), // 4: 青
+    (255, 0, 255),   // 5: マゼンタ
+    (0, 255, 255),   // 6: シアン
+    (255, 255, 255), // 7: 白
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnsiState {
+    /// 通常の文字
+    Ground,
+    /// ESC を受け取った直後
+    Escape,
+    /// "ESC [" の後、終端文字（0x40-0x7E）待ち
+    Csi,
+}
+
+/// 解釈し終えた CSI シーケンス
+#[derive(Debug, Clone, Copy)]
+struct CsiSequence {
+    /// 終端文字（'m', 'H' など）
+    action: char,
+    params: [u16; ANSI_MAX_PARAMS],
+    nparams: usize,
+}
+
+impl CsiSequence {
+    /// i 番目のパラメータ。省略または 0 なら default を返す（カーソル移動の慣習）。
+    fn param_or(&self, i: usize, default: u16) -> u16 {
+        match self.params.get(i) {
+            Some(&v) if i < self.nparams && v != 0 => v,
+            _ => default,
+        }
+    }
+}
+
+/// ANSI エスケープシーケンスのパーサ（状態機械）。
+///
+/// 1 文字ずつ feed() に渡し、描くべき文字か、完成した CSI シーケンスを受け取る。
+/// 状態は FramebufferWriter に持たせるので、kprint! の呼び出しをまたいで
+/// シーケンスが分断されても正しく解釈できる。
+struct AnsiParser {
+    state: AnsiState,
+    params: [u16; ANSI_MAX_PARAMS],
+    nparams: usize,
+    /// ESC からの長さ
+    len: usize,
+    /// "?" などの private マーカーや中間文字を含む、対応外のシーケンス
+    unsupported: bool,
+}
+
+/// feed() の結果
+enum AnsiOutput {
+    /// エスケープシーケンス外の文字。そのまま描く
+    Char(char),
+    /// CSI シーケンスが完成した
+    Csi(CsiSequence),
+    /// シーケンスの途中、または読み捨てた
+    None,
+}
+
+impl AnsiParser {
+    const fn new() -> Self {
+        Self {
+            state: AnsiState::Ground,
+            params: [0; ANSI_MAX_PARAMS],
+            nparams: 0,
+            len: 0,
+            unsupported: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn feed(&mut self, c: char) -> AnsiOutput {
+        match self.state {
+            AnsiState::Ground => {
+                if c == '\x1b' {
+                    self.reset();
+                    self.state = AnsiState::Escape;
+                    self.len = 1;
+                    AnsiOutput::None
+                } else {
+                    AnsiOutput::Char(c)
+                }
+            }
+            AnsiState::Escape => {
+                if c == '[' {
+                    self.state = AnsiState::Csi;
+                    self.len += 1;
+                } else if c != '\x1b' {
+                    // CSI 以外の 2 文字シーケンス（ESC c など）は読み捨てる
+                    self.reset();
+                }
+                AnsiOutput::None
+            }
+            AnsiState::Csi => {
+                self.len += 1;
+                if self.len > ANSI_MAX_SEQ_LEN {
+                    self.reset();
+                    return AnsiOutput::None;
+                }
+                match c {
+                    '0'..='9' => {
+                        if self.nparams == 0 {
+                            self.nparams = 1;
+                        }
+                        match self.params.get_mut(self.nparams - 1) {
+                            Some(p) => {
+                                let d = c as u16 - b'0' as u16;
+                                *p = p.saturating_mul(10).saturating_add(d).min(ANSI_MAX_PARAM_VALUE);
+                            }
+                            None => self.unsupported = true,
+                        }
+                        AnsiOutput::None
+                    }
+                    ';' => {
+                        // 省略されたパラメータ（"ESC[;5H" の先頭など）は 0 扱い
+                        if self.nparams == 0 {
+                            self.nparams = 1;
+                        }
+                        if self.nparams < ANSI_MAX_PARAMS {
+                            self.nparams += 1;
+                        } else {
+                            self.unsupported = true;
+                        }
+                        AnsiOutput::None
+                    }
+                    // private マーカー（ESC[?25l）と中間文字は対応しない。終端まで読み飛ばす
+                    '<'..='?' | ' '..='/' => {
+                        self.unsupported = true;
+                        AnsiOutput::None
+                    }
+                    '@'..='~' => {
+                        let seq = CsiSequence { action: c, params: self.params, nparams: self.nparams };
+                        let unsupported = self.unsupported;
+                        self.reset();
+                        if unsupported { AnsiOutput::None } else { AnsiOutput::Csi(seq) }
+                    }
+                    // 制御文字や範囲外の文字が来たら壊れたシーケンスとして捨てる
+                    _ => {
+                        self.reset();
+                        AnsiOutput::None
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// フレームバッファの情報を保持する構造体。
 /// Exit Boot Services の前に GOP から情報を取得して保存しておく。
 /// Exit 後は GOP が使えなくなるが、フレームバッファの物理アドレス自体は有効なまま残る。
@@ -443,9 +1201,28 @@ pub struct FramebufferWriter {
     fg_color: (u8, u8, u8),
     /// テキストの背景色 (R, G, B)
     bg_color: (u8, u8, u8),
+    /// set_colors() で設定された既定の色 (前景, 背景)。SGR 0 / 39 / 49 でここに戻す。
+    default_colors: ColorPair,
+    /// テキスト出力中の ANSI エスケープシーケンスの解釈状態
+    ansi: AnsiParser,
     /// ダーティ矩形。描画操作で変更された領域を追跡する。
     /// flush_dirty() でこの領域だけを MMIO に転送し、None にリセットする。
     dirty: Option<DirtyRect>,
+    /// ダブルバッファモードを有効にしているタスク ID。
+    /// None なら直接モード（描画のたびに MMIO に転送する。テキストコンソールの既定）。
+    /// Some のあいだは描画をバックバッファに貯めるだけで、present() で一括転送する。
+    double_buffer_owner: Option<u64>,
+    /// クリップ領域。Some のあいだ put_pixel と draw_*_global はこの外に描かない。
+    /// コンソール出力（_print）のあいだだけは一時的に外す。
+    clip: Option<ClipRect>,
+    /// クリップ領域を設定したタスク ID（終了時の自動解除用）
+    clip_owner: Option<u64>,
+    /// 論理解像度。Some のあいだ draw_*_global の座標は論理座標で、拡大して描く
+    viewport: Option<Viewport>,
+    /// 論理解像度を設定したタスク ID（終了時の自動解除用）
+    viewport_owner: Option<u64>,
+    /// マウスカーソル（MMIO にだけ描く）
+    mouse_cursor: MouseCursor,
 }
 
 // FramebufferWriter は *mut u8（フレームバッファの生ポインタ）を持つため、
@@ -478,14 +1255,24 @@ impl FramebufferWriter {
             cursor_y: 0,
             fg_color: (255, 255, 255), // デフォルト白
             bg_color: (0, 0, 128),     // デフォルト紺
+            default_colors: ((255, 255, 255), (0, 0, 128)),
+            ansi: AnsiParser::new(),
             dirty: None,
+            double_buffer_owner: None,
+            clip: None,
+            clip_owner: None,
+            viewport: None,
+            viewport_owner: None,
+            mouse_cursor: MouseCursor::new(),
         }
     }
 
     /// 前景色と背景色を設定する。
+    /// ANSI のリセット（ESC[0m）で戻る先の色もこれになる。
     pub fn set_colors(&mut self, fg: (u8, u8, u8), bg: (u8, u8, u8)) {
         self.fg_color = fg;
         self.bg_color = bg;
+        self.default_colors = (fg, bg);
     }
 
     /// 指定した矩形領域をダーティとしてマークする。
@@ -526,6 +1313,16 @@ impl FramebufferWriter {
         }
     }
 
+    /// 直接モードならダーティ領域をすぐ MMIO に転送する。
+    ///
+    /// ダブルバッファモード中は何もせず、ダーティ矩形を present() まで累積させる。
+    /// 描画途中のフレームが画面に出ないので、アニメーションのちらつき（ティアリング）を防げる。
+    fn flush_if_direct(&mut self) {
+        if self.double_buffer_owner.is_none() {
+            self.flush_dirty();
+        }
+    }
+
     /// 画面全体を背景色で塗りつぶす。
     ///
     /// バックバッファ全体を背景色ピクセルで埋める。
@@ -542,6 +1339,94 @@ impl FramebufferWriter {
         self.mark_dirty(0, 0, self.width, self.height);
     }
 
+    /// 描ける範囲（画面のピクセル座標）。クリップ領域と論理解像度のビューポートの共通部分
+    fn bounds(&self) -> ClipRect {
+        let mut bounds = self.clip.unwrap_or(ClipRect {
+            x_min: 0,
+            y_min: 0,
+            x_max: self.width,
+            y_max: self.height,
+        });
+        if let Some(vp) = self.viewport {
+            bounds.x_min = bounds.x_min.max(vp.x);
+            bounds.y_min = bounds.y_min.max(vp.y);
+            bounds.x_max = bounds.x_max.min(vp.x + vp.width * vp.scale);
+            bounds.y_max = bounds.y_max.min(vp.y + vp.height * vp.scale);
+        }
+        bounds
+    }
+
+    /// draw_*_global の座標系の幅と高さ。論理解像度を設定していればその大きさ
+    fn draw_size(&self) -> (usize, usize) {
+        match self.viewport {
+            Some(vp) => (vp.width, vp.height),
+            None => (self.width, self.height),
+        }
+    }
+
+    /// 論理解像度の座標の矩形を画面のピクセル座標に直す。論理解像度を設定していなければそのまま
+    fn to_screen(&self, x: usize, y: usize, w: usize, h: usize) -> (usize, usize, usize, usize) {
+        match self.viewport {
+            Some(vp) => (vp.x + x * vp.scale, vp.y + y * vp.scale, w * vp.scale, h * vp.scale),
+            None => (x, y, w, h),
+        }
+    }
+
+    /// 論理座標の 1 点を描く。論理解像度なら拡大率ぶんの正方形になる
+    fn plot(&mut self, x: usize, y: usize, r: u8, g: u8, b: u8) {
+        match self.viewport {
+            Some(_) => {
+                let (x, y, w, h) = self.to_screen(x, y, 1, 1);
+                self.fill_rect(x, y, w, h, (r, g, b));
+            }
+            None => {
+                self.put_pixel(x, y, r, g, b);
+                self.mark_dirty(x, y, 1, 1);
+            }
+        }
+    }
+
+    /// 画面のピクセル座標の矩形を塗りつぶす。描ける範囲の外は描かない。
+    ///
+    /// 行テンプレートを作って行ごとに copy_within することで put_pixel ループより高速。
+    fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, (r, g, b): (u8, u8, u8)) {
+        let Some((x, y, w, h)) = self.clip_rect(x, y, w, h) else {
+            return;
+        };
+        let pixel = self.make_pixel(r, g, b);
+        let bpp = 4;
+        let row_bytes = w * bpp;
+
+        // 最初の行をテンプレートとして作成
+        let first_row_offset = (y * self.stride + x) * bpp;
+        for xx in 0..w {
+            let offset = first_row_offset + xx * bpp;
+            self.backbuf[offset..offset + bpp].copy_from_slice(&pixel);
+        }
+
+        // 残りの行は最初の行を copy_within でコピー（同じ色パターンなので行コピーで済む）
+        for yy in (y + 1)..(y + h) {
+            let dst = (yy * self.stride + x) * bpp;
+            self.backbuf.copy_within(first_row_offset..first_row_offset + row_bytes, dst);
+        }
+        self.mark_dirty(x, y, w, h);
+    }
+
+    /// 矩形 (x, y, w, h) を画面とクリップ領域（論理解像度ならビューポートも）の内側に切り詰める。
+    ///
+    /// 切り詰めた矩形を (x, y, w, h) で返す。内側に 1 ピクセルも残らなければ None。
+    fn clip_rect(&self, x: usize, y: usize, w: usize, h: usize) -> Option<(usize, usize, usize, usize)> {
+        let bounds = self.bounds();
+        let x0 = x.max(bounds.x_min);
+        let y0 = y.max(bounds.y_min);
+        let x1 = x.saturating_add(w).min(bounds.x_max);
+        let y1 = y.saturating_add(h).min(bounds.y_max);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+        Some((x0, y0, x1 - x0, y1 - y0))
+    }
+
     /// RGB 値をピクセルフォーマットに応じた 4 バイト配列に変換する。
     /// RGB フォーマットなら [R, G, B, 0]、BGR なら [B, G, R, 0]。
     #[inline(always)]
@@ -562,6 +1447,10 @@ impl FramebufferWriter {
         if x >= self.width || y >= self.height {
             return;
         }
+        let bounds = self.bounds();
+        if x < bounds.x_min || x >= bounds.x_max || y < bounds.y_min || y >= bounds.y_max {
+            return;
+        }
 
         // 1ピクセル = 4バイト (32bit)
         // オフセット = (y * stride + x) * 4
@@ -578,9 +1467,113 @@ impl FramebufferWriter {
 
     /// バックバッファの指定矩形領域だけを MMIO に転送する。
     ///
+    /// マウスカーソルは常に一番上に重ねる。転送先にカーソルが描かれていれば
+    /// いったん消して（退避したピクセルを戻して）から転送し、転送後に描き直す。
+    /// IRQ12 から届いたマウスの移動もここで反映する。
+    fn flush_rect(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        let pos = CURSOR_POS.load(Ordering::Relaxed);
+        let pos = ((pos >> 32) as usize, (pos & 0xFFFF_FFFF) as usize);
+        let cursor = &self.mouse_cursor;
+        let moved = cursor.visible && pos != (cursor.x, cursor.y);
+        let covered = cursor.drawn.is_some_and(|(cx, cy, cw, ch)| {
+            cx < x.saturating_add(w) && x < cx + cw && cy < y.saturating_add(h) && y < cy + ch
+        });
+
+        if moved || covered {
+            self.erase_cursor();
+        }
+        self.copy_to_screen(x, y, w, h);
+        if moved || covered {
+            (self.mouse_cursor.x, self.mouse_cursor.y) = pos;
+            self.paint_cursor();
+        }
+    }
+
+    /// 画面に描いたマウスカーソルを消し、退避しておいたピクセルを書き戻す。
+    fn erase_cursor(&mut self) {
+        let Some((x, y, w, h)) = self.mouse_cursor.drawn.take() else {
+            return;
+        };
+        for row in 0..h {
+            let start = ((y + row) * self.stride + x) * 4;
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    self.mouse_cursor.under.as_ptr().add(row * w * 4),
+                    self.fb_ptr.add(start),
+                    w * 4,
+                );
+            }
+        }
+    }
+
+    /// マウスカーソルを MMIO に描く。
+    ///
+    /// 描く矩形の元のピクセルを under に退避してから、アルファ値で合成して描く。
+    /// 画面からはみ出す部分は描かない。表示しない設定なら何もしない。
+    fn paint_cursor(&mut self) {
+        let c = &self.mouse_cursor;
+        if !c.visible || c.drawn.is_some() {
+            return;
+        }
+        // 画像の左上の画面座標（ホットスポットが左上より手前なら負になる）
+        let ox = c.x as isize - c.hot_x as isize;
+        let oy = c.y as isize - c.hot_y as isize;
+        let x0 = ox.max(0) as usize;
+        let y0 = oy.max(0) as usize;
+        let x1 = (ox + c.w as isize).clamp(0, self.width as isize) as usize;
+        let y1 = (oy + c.h as isize).clamp(0, self.height as isize) as usize;
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+        let (w, h) = (x1 - x0, y1 - y0);
+        if ((y1 - 1) * self.stride + x1) * 4 > self.fb_size {
+            return;
+        }
+
+        let mut under = core::mem::take(&mut self.mouse_cursor.under);
+        under.clear();
+        under.resize(w * h * 4, 0);
+        for row in 0..h {
+            let start = ((y0 + row) * self.stride + x0) * 4;
+            unsafe {
+                core::ptr::copy_nonoverlapping(self.fb_ptr.add(start), under.as_mut_ptr().add(row * w * 4), w * 4);
+            }
+        }
+
+        let c = &self.mouse_cursor;
+        for row in 0..h {
+            let src_y = (y0 + row) as isize - oy;
+            for col in 0..w {
+                let src_x = (x0 + col) as isize - ox;
+                let src = ((src_y as usize) * c.w + src_x as usize) * 4;
+                let a = c.image[src + 3];
+                if a == 0 {
+                    continue;
+                }
+                let pixel = self.make_pixel(c.image[src], c.image[src + 1], c.image[src + 2]);
+                let dst = &under[(row * w + col) * 4..(row * w + col) * 4 + 4];
+                let out = [
+                    blend_channel(pixel[0], dst[0], a),
+                    blend_channel(pixel[1], dst[1], a),
+                    blend_channel(pixel[2], dst[2], a),
+                    0,
+                ];
+                let offset = ((y0 + row) * self.stride + x0 + col) * 4;
+                unsafe {
+                    core::ptr::write_volatile(self.fb_ptr.add(offset) as *mut [u8; 4], out);
+                }
+            }
+        }
+
+        self.mouse_cursor.under = under;
+        self.mouse_cursor.drawn = Some((x0, y0, w, h));
+    }
+
+    /// バックバッファの指定矩形領域を MMIO にそのままコピーする。
+    ///
     /// 画面の一部だけが変更された場合、全画面 flush() より高速。
     /// 全幅かつ stride == width の場合は 1 回の連続コピーで転送する最適化つき。
-    fn flush_rect(&self, x: usize, y: usize, w: usize, h: usize) {
+    fn copy_to_screen(&self, x: usize, y: usize, w: usize, h: usize) {
         let bpp = 4usize;
         let end_y = (y + h).min(self.height);
         let actual_w = w.min(self.width.saturating_sub(x));
@@ -623,11 +1616,9 @@ impl FramebufferWriter {
     /// font8x8 のグリフデータは 8 バイトの配列で、
     /// 各バイトが 1 行分（8 ピクセル）のビットパターン。
     /// ビットが 1 なら前景色、0 なら背景色を描く。
-    fn draw_char(&mut self, x: usize, y: usize, c: char) {
-        // font8x8 からグリフを取得。未対応文字は '?' で代用。
-        let glyph = font8x8::BASIC_FONTS
-            .get(c)
-            .unwrap_or_else(|| font8x8::BASIC_FONTS.get('?').unwrap());
+    /// scale 倍のときはグリフの 1 ピクセルを scale x scale のブロックで描く（最近傍法）。
+    fn draw_char(&mut self, x: usize, y: usize, c: char, scale: usize) {
+        let glyph = glyph_for(c);
 
         for (row, &bits) in glyph.iter().enumerate() {
             for col in 0..CHAR_WIDTH {
@@ -638,19 +1629,100 @@ impl FramebufferWriter {
                 } else {
                     self.bg_color
                 };
-                self.put_pixel(x + col, y + row, r, g, b);
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        self.put_pixel(x + col * scale + dx, y + row * scale + dy, r, g, b);
+                    }
+                }
             }
         }
         // 文字 1 個分の矩形をダーティとしてマーク（put_pixel 64回分を 1 回で記録）
-        self.mark_dirty(x, y, CHAR_WIDTH, CHAR_HEIGHT);
+        self.mark_dirty(x, y, CHAR_WIDTH * scale, CHAR_HEIGHT * scale);
     }
 
     /// 文字列を現在のカーソル位置から描画する。
     /// 改行 ('\n') でカーソルを次の行に移す。
     /// 画面右端に達したら自動で折り返す。
+    /// ANSI エスケープシーケンスは描かずに解釈する（色・カーソル移動）。
     pub fn write_str(&mut self, s: &str) {
         for c in s.chars() {
-            self.write_char(c);
+            match self.ansi.feed(c) {
+                AnsiOutput::Char(c) => self.write_char(c),
+                AnsiOutput::Csi(seq) => self.apply_csi(&seq),
+                AnsiOutput::None => {}
+            }
+        }
+    }
+
+    /// 完成した CSI シーケンスを反映する。対応していない終端文字は無視する。
+    fn apply_csi(&mut self, seq: &CsiSequence) {
+        // カーソルは文字セル単位で動かす。画面外には出さない。
+        let max_x = self.width.saturating_sub(CHAR_WIDTH);
+        let max_y = self.height.saturating_sub(CHAR_HEIGHT);
+        match seq.action {
+            'm' => self.apply_sgr(seq),
+            'H' | 'f' => {
+                // ESC[row;colH（1 始まり）
+                let row = seq.param_or(0, 1) as usize - 1;
+                let col = seq.param_or(1, 1) as usize - 1;
+                self.cursor_y = (row * CHAR_HEIGHT).min(max_y);
+                self.cursor_x = (col * CHAR_WIDTH).min(max_x);
+            }
+            'A' => {
+                let n = seq.param_or(0, 1) as usize * CHAR_HEIGHT;
+                self.cursor_y = self.cursor_y.saturating_sub(n);
+            }
+            'B' => {
+                let n = seq.param_or(0, 1) as usize * CHAR_HEIGHT;
+                self.cursor_y = (self.cursor_y + n).min(max_y);
+            }
+            'C' => {
+                let n = seq.param_or(0, 1) as usize * CHAR_WIDTH;
+                self.cursor_x = (self.cursor_x + n).min(max_x);
+            }
+            'D' => {
+                let n = seq.param_or(0, 1) as usize * CHAR_WIDTH;
+                self.cursor_x = self.cursor_x.saturating_sub(n);
+            }
+            'J' => {
+                // 画面全体の消去（ESC[2J）だけ対応する
+                if seq.nparams == 1 && seq.params[0] == 2 {
+                    let (x, y) = (self.cursor_x, self.cursor_y);
+                    self.clear();
+                    self.cursor_x = x;
+                    self.cursor_y = y;
+                }
+            }
+            'K' => {
+                // カーソルから行末までの消去（ESC[K / ESC[0K）だけ対応する
+                if seq.param_or(0, 0) == 0 {
+                    let (r, g, b) = self.bg_color;
+                    let x0 = self.cursor_x;
+                    for row in 0..CHAR_HEIGHT {
+                        for x in x0..self.width {
+                            self.put_pixel(x, self.cursor_y + row, r, g, b);
+                        }
+                    }
+                    self.mark_dirty(x0, self.cursor_y, self.width.saturating_sub(x0), CHAR_HEIGHT);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// SGR（ESC[...m）で色を変える。パラメータなしは ESC[0m と同じ。
+    fn apply_sgr(&mut self, seq: &CsiSequence) {
+        let n = seq.nparams.max(1);
+        for &p in &seq.params[..n] {
+            match p {
+                0 => (self.fg_color, self.bg_color) = self.default_colors,
+                30..=37 => self.fg_color = ANSI_COLORS[(p - 30) as usize],
+                39 => self.fg_color = self.default_colors.0,
+                40..=47 => self.bg_color = ANSI_COLORS[(p - 40) as usize],
+                49 => self.bg_color = self.default_colors.1,
+                // 太字・下線などは描き分けられないので無視する
+                _ => {}
+            }
         }
     }
 
@@ -733,13 +1805,33 @@ impl FramebufferWriter {
                 // 画面下端を超えていたらスクロール
                 self.ensure_cursor_visible();
 
-                self.draw_char(self.cursor_x, self.cursor_y, c);
+                self.draw_char(self.cursor_x, self.cursor_y, c, 1);
                 self.cursor_x += CHAR_WIDTH;
             }
         }
     }
 }
 
+/// フォントにない文字の代わりに描くグリフ（枠だけの四角）。
+/// '?' と見分けがつくよう、フォントにない形にしておく。
+pub const REPLACEMENT_GLYPH: [u8; 8] = [0x7E, 0x42, 0x42, 0x42, 0x42, 0x42, 0x7E, 0x00];
+
+/// 文字（コードポイント）に対応する 8x8 のグリフを返す。
+///
+/// ASCII・Latin-1 補助（é など）・罫線・ブロック要素・ギリシャ文字・ひらがなを
+/// font8x8 の各表から探す。どれにもない文字（不正な UTF-8 を置き換えた U+FFFD を含む）は
+/// REPLACEMENT_GLYPH になる。1 文字は常に 1 セル（8x8）を占める。
+pub fn glyph_for(c: char) -> [u8; 8] {
+    font8x8::BASIC_FONTS
+        .get(c)
+        .or_else(|| font8x8::LATIN_FONTS.get(c))
+        .or_else(|| font8x8::BOX_FONTS.get(c))
+        .or_else(|| font8x8::BLOCK_FONTS.get(c))
+        .or_else(|| font8x8::GREEK_FONTS.get(c))
+        .or_else(|| font8x8::HIRAGANA_FONTS.get(c))
+        .unwrap_or(REPLACEMENT_GLYPH)
+}
+
 /// core::fmt::Write を実装して write!() マクロが使えるようにする。
 /// これで write!(fb, "Hello {}!", name) のような書き方ができる。
 impl fmt::Write for FramebufferWriter {
@@ -79,18 +79,11 @@ pub fn futex_wait(addr: u64, expected: u32, timeout_ms: u64) -> Result<u64, Sysc
         table.entry((cr3, addr)).or_insert_with(Vec::new).push(task_id);
     }
 
-    // タスクを Sleeping 状態にする
-    // timeout_ms == 0 なら無期限待ち（u64::MAX は実質無限）
-    let wake_at = if timeout_ms == 0 {
-        u64::MAX // 無期限待ち（futex_wake で明示的に起こされるまで）
-    } else {
-        // PIT は約 18.2 Hz (1 tick ≈ 55ms)
-        // ticks = ms * 182 / 10000
-        let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-        now + (timeout_ms * 182 / 10000).max(1)
-    };
-    crate::scheduler::set_current_sleeping(wake_at);
-    crate::scheduler::yield_now();
+    // futex_wake で起こされるか、タイムアウトするまで眠る
+    // timeout_ms == 0 なら無期限待ち（futex_wake で明示的に起こされるまで）。
+    // 登録してから眠るまでの間に値が変わって futex_wake が空振りしていたら、眠らずに戻る。
+    let deadline = crate::scheduler::deadline_after_ms(timeout_ms);
+    crate::scheduler::park_until(deadline, || unsafe { *(addr as *const u32) } != expected);
 
     // ここに到達 = 起床した（futex_wake で起こされた or タイムアウト）
     // FUTEX_TABLE から自分を削除（futex_wake で既に削除されている場合もある）
@@ -56,32 +56,34 @@ lazy_static! {
     /// ユーザーデータセグメントをユーザーコードセグメントの前に置くのは、
     /// sysret 命令の規約に合わせるため（将来の最適化に備える）。
     static ref GDT: (GlobalDescriptorTable, Selectors) = {
-        let mut gdt = GlobalDescriptorTable::new();
-
-        // カーネルモードのコードセグメント（Ring 0, 64-bit）
-        let kernel_code_selector = gdt.append(Descriptor::kernel_code_segment());
-        // カーネルモードのデータセグメント（Ring 0）
-        let kernel_data_selector = gdt.append(Descriptor::kernel_data_segment());
-        // ユーザーモードのデータセグメント（Ring 3）
-        let _user_data_selector = gdt.append(Descriptor::user_data_segment());
-        // ユーザーモードのコードセグメント（Ring 3, 64-bit）
-        let user_code_selector = gdt.append(Descriptor::user_code_segment());
-
         // TSS セグメント（CPU に TSS の場所を教える）
         // static mut な TSS を raw pointer で渡す（tss_segment_unchecked は unsafe）
-        let tss_selector = unsafe {
-            gdt.append(Descriptor::tss_segment_unchecked(&raw const TSS))
-        };
-
-        (gdt, Selectors {
-            kernel_code_selector,
-            kernel_data_selector,
-            user_code_selector,
-            tss_selector,
-        })
+        build_gdt(unsafe { Descriptor::tss_segment_unchecked(&raw const TSS) })
     };
 }
 
+/// GDT を組み立てる。BSP と AP で TSS だけが違う。
+fn build_gdt(tss: Descriptor) -> (GlobalDescriptorTable, Selectors) {
+    let mut gdt = GlobalDescriptorTable::new();
+
+    // カーネルモードのコードセグメント（Ring 0, 64-bit）
+    let kernel_code_selector = gdt.append(Descriptor::kernel_code_segment());
+    // カーネルモードのデータセグメント（Ring 0）
+    let kernel_data_selector = gdt.append(Descriptor::kernel_data_segment());
+    // ユーザーモードのデータセグメント（Ring 3）
+    let _user_data_selector = gdt.append(Descriptor::user_data_segment());
+    // ユーザーモードのコードセグメント（Ring 3, 64-bit）
+    let user_code_selector = gdt.append(Descriptor::user_code_segment());
+    let tss_selector = gdt.append(tss);
+
+    (gdt, Selectors {
+        kernel_code_selector,
+        kernel_data_selector,
+        user_code_selector,
+        tss_selector,
+    })
+}
+
 /// GDT に登録したセグメントのセレクタ（インデックス）を保持する。
 /// GDT をロードした後、CPU のセグメントレジスタをこれらの値に設定する。
 struct Selectors {
@@ -99,9 +101,6 @@ struct Selectors {
 /// 3. コードセグメント (CS) とデータセグメント (SS) を新しいセレクタに切り替え
 /// 4. TSS をロード（ltr 命令）して IST が有効になる
 pub fn init() {
-    use x86_64::instructions::segmentation::{CS, DS, ES, Segment, SS};
-    use x86_64::instructions::tables::load_tss;
-
     // TSS にダブルフォルト用スタックを設定する。
     // static mut へのアクセスなので unsafe。GDT の lazy_static 初期化前に行う。
     unsafe {
@@ -114,16 +113,71 @@ pub fn init() {
     }
 
     GDT.0.load();
+    unsafe { load_segments(&GDT.1) };
+}
+
+/// GDT をロードした後に、セグメントレジスタと TSS を selectors に切り替える。
+///
+/// # Safety
+/// selectors は今ロードしている GDT のものであること。
+unsafe fn load_segments(selectors: &Selectors) {
+    use x86_64::instructions::segmentation::{CS, DS, ES, Segment, SS};
+    use x86_64::instructions::tables::load_tss;
+
     unsafe {
         // CS (Code Segment) の切り替えは特殊で、far return を使って行われる。
         // x86_64 crate が内部でよろしくやってくれる。
-        CS::set_reg(GDT.1.kernel_code_selector);
+        CS::set_reg(selectors.kernel_code_selector);
         // SS, DS, ES はゼロか同じデータセレクタを設定。
-        SS::set_reg(GDT.1.kernel_data_selector);
-        DS::set_reg(GDT.1.kernel_data_selector);
+        SS::set_reg(selectors.kernel_data_selector);
+        DS::set_reg(selectors.kernel_data_selector);
         ES::set_reg(SegmentSelector(0));
         // TSS をロード。これで IST（ダブルフォルト用スタック）が有効になる。
-        load_tss(GDT.1.tss_selector);
+        load_tss(selectors.tss_selector);
+    }
+}
+
+/// AP（BSP 以外の CPU）用の GDT。
+///
+/// TSS はロード中に CPU が Busy ビットを立てるので、CPU ごとに別のものが要る。
+/// ダブルフォルト用の IST スタックも CPU ごとに持つ。
+pub struct CpuGdt {
+    gdt: GlobalDescriptorTable,
+    selectors: Selectors,
+    tss: *mut TaskStateSegment,
+}
+
+// tss はこの CpuGdt を使う CPU だけが書き換える
+unsafe impl Sync for CpuGdt {}
+
+/// AP 用の GDT と TSS を確保する（リークして 'static にする）。
+///
+/// AP はヒープを使わずに済むよう、BSP が起動前に呼んでおく。
+pub fn alloc_cpu_gdt() -> &'static CpuGdt {
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    let stack = Box::leak(vec![0u8; DOUBLE_FAULT_STACK_SIZE].into_boxed_slice());
+    let mut tss = TaskStateSegment::new();
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+        VirtAddr::new(stack.as_ptr() as u64 + DOUBLE_FAULT_STACK_SIZE as u64);
+    // rsp0 を後から書き換えるので、&'static ではなく生ポインタで持つ
+    let tss = Box::into_raw(Box::new(tss));
+
+    let (gdt, selectors) = build_gdt(unsafe { Descriptor::tss_segment_unchecked(tss) });
+    Box::leak(Box::new(CpuGdt { gdt, selectors, tss }))
+}
+
+impl CpuGdt {
+    /// 今の CPU にこの GDT と TSS をロードする（AP が起動直後に呼ぶ）
+    pub fn load(&'static self) {
+        self.gdt.load();
+        unsafe { load_segments(&self.selectors) };
+    }
+
+    /// この GDT に登録した TSS
+    pub fn tss(&self) -> *mut TaskStateSegment {
+        self.tss
     }
 }
 
@@ -134,19 +188,8 @@ pub fn user_code_selector() -> SegmentSelector {
     GDT.1.user_code_selector
 }
 
-/// TSS の rsp0（privilege_stack_table[0]）を設定する。
-///
-/// Ring 3 → Ring 0 への遷移時（int 命令、例外など）、CPU は自動的に
-/// TSS の rsp0 が指すアドレスにスタックポインタを切り替える。
-/// ユーザータスクを実行する前に、そのタスク用のカーネルスタックの
-/// トップアドレスをここに設定する必要がある。
-///
-/// # Safety
-/// - rsp0 は有効なスタック領域のトップアドレスでなければならない
-/// - 16 バイトアラインされていること
-pub unsafe fn set_tss_rsp0(rsp0: VirtAddr) {
-    let tss = &raw mut TSS;
-    unsafe {
-        (*tss).privilege_stack_table[0] = rsp0;
-    }
+/// BSP の TSS を返す。percpu::init() が CPU ごとのブロックに記録し、
+/// rsp0 の書き換え（percpu::set_kernel_stack()）はそこを経由する。
+pub fn bsp_tss() -> *mut TaskStateSegment {
+    &raw mut TSS
 }
@@ -27,6 +27,9 @@
 //
 // - File: 通常のファイル（読み取り・書き込み）
 // - Directory: ディレクトリ（列挙・作成・削除・lookup）
+// - PipeRead / PipeWrite: パイプの両端
+// - Socket: 統一ソケット API のソケット（read / write は recv / send に委譲）
+// - Capture: Ethernet フレームのキャプチャ（read でフレームを 1 つずつ取り出す）
 
 // 将来使用する権限ビットと関数の dead_code 警告を抑制
 #![allow(dead_code)]
@@ -72,6 +75,9 @@ pub const HANDLE_RIGHTS_DIRECTORY: u32 = HANDLE_RIGHT_STAT | HANDLE_RIGHT_ENUM |
 /// ディレクトリ用の権限セット（読み取りのみ）
 pub const HANDLE_RIGHTS_DIRECTORY_READ: u32 = HANDLE_RIGHT_STAT | HANDLE_RIGHT_ENUM | HANDLE_RIGHT_LOOKUP;
 
+/// ソケット用の権限セット（READ = 受信 / accept、WRITE = 送信 / bind / connect / listen）
+pub const HANDLE_RIGHTS_SOCKET: u32 = HANDLE_RIGHT_READ | HANDLE_RIGHT_WRITE;
+
 // =================================================================
 // Handle 構造体
 // =================================================================
@@ -100,6 +106,10 @@ pub enum HandleKind {
     PipeRead,
     /// パイプの書き込み端
     PipeWrite,
+    /// ソケット（socket.rs）
+    Socket,
+    /// フレームキャプチャ（netstack/capture.rs）
+    Capture,
 }
 
 /// ハンドルの中身（カーネル内）
@@ -120,6 +130,10 @@ struct HandleEntry {
     dirty: bool,
     /// パイプ ID（PipeRead / PipeWrite の場合のみ使用）
     pipe_id: Option<usize>,
+    /// ソケット ID（Socket の場合のみ使用）
+    socket_id: Option<usize>,
+    /// キャプチャ ID（Capture の場合のみ使用）
+    capture_id: Option<usize>,
 }
 
 lazy_static! {
@@ -165,6 +179,8 @@ pub fn create_handle_with_path(data: Vec<u8>, rights: u32, path: String) -> Hand
         pos: 0,
         dirty: false,
         pipe_id: None,
+        socket_id: None,
+        capture_id: None,
     };
 
     insert_entry(entry, token)
@@ -189,6 +205,8 @@ pub fn create_directory_handle(path: String, rights: u32) -> Handle {
         pos: 0,
         dirty: false,
         pipe_id: None,
+        socket_id: None,
+        capture_id: None,
     };
 
     insert_entry(entry, token)
@@ -231,13 +249,18 @@ fn insert_entry(entry: HandleEntry, token: u64) -> Handle {
 ///
 /// # エラー
 /// - `InvalidHandle`: ハンドルが無効
+/// - `NotSupported`: キャプチャハンドル（キューを共有する複製は作れない）
 pub fn duplicate_handle(handle: &Handle) -> Result<Handle, SyscallError> {
     let table = HANDLE_TABLE.lock();
     let entry = get_entry(&table, handle)?;
+    if entry.kind == HandleKind::Capture {
+        return Err(SyscallError::NotSupported);
+    }
 
     let new_token = next_token();
     let kind = entry.kind;
     let pipe_id = entry.pipe_id;
+    let socket_id = entry.socket_id;
     let new_entry = HandleEntry {
         token: new_token,
         rights: entry.rights,
@@ -247,6 +270,8 @@ pub fn duplicate_handle(handle: &Handle) -> Result<Handle, SyscallError> {
         pos: 0,     // ポジションは先頭にリセット
         dirty: false,
         pipe_id,
+        socket_id,
+        capture_id: None,
     };
 
     drop(table); // ロックを解放してから insert_entry を呼ぶ
@@ -257,6 +282,10 @@ pub fn duplicate_handle(handle: &Handle) -> Result<Handle, SyscallError> {
             crate::pipe::add_writer(pid);
         }
     }
+    // ソケットは全ハンドルが閉じられるまで生かしておく
+    if let Some(sid) = socket_id {
+        crate::socket::add_ref(sid);
+    }
 
     Ok(insert_entry(new_entry, new_token))
 }
@@ -293,10 +322,30 @@ pub fn read(handle: &Handle, buf: &mut [u8]) -> Result<usize, SyscallError> {
         return match crate::pipe::read(pipe_id, buf) {
             Ok(n) => Ok(n),
             Err(crate::pipe::PipeError::WouldBlock) => Err(SyscallError::WouldBlock),
-            Err(_) => Err(SyscallError::Other),
+            Err(crate::pipe::PipeError::InvalidPipe) => Err(SyscallError::InvalidHandle),
+            Err(crate::pipe::PipeError::BrokenPipe) => Err(SyscallError::Other),
         };
     }
 
+    // ソケットはノンブロッキング受信に委譲（データがなければ WouldBlock で
+    // sys_handle_read が yield + retry する。パイプと同じ扱い）
+    if entry.kind == HandleKind::Socket {
+        let socket_id = entry.socket_id.ok_or(SyscallError::InvalidHandle)?;
+        drop(table);
+        return crate::socket::recv(socket_id, buf, 0).map(|(n, _)| n);
+    }
+
+    // キャプチャは溜まっているフレームを 1 つ返す（なければ WouldBlock）。
+    // ヘッダすら入らないバッファではフレームを失うので受け付けない。
+    if entry.kind == HandleKind::Capture {
+        let capture_id = entry.capture_id.ok_or(SyscallError::InvalidHandle)?;
+        drop(table);
+        if buf.len() <= core::mem::size_of::<sabos_syscall::CaptureFrameHeader>() {
+            return Err(SyscallError::InvalidArgument);
+        }
+        return crate::netstack::capture_read(capture_id, buf).ok_or(SyscallError::WouldBlock);
+    }
+
     // ファイルのみ読み取り可能
     if entry.kind != HandleKind::File {
         return Err(SyscallError::NotSupported);
@@ -313,6 +362,35 @@ pub fn read(handle: &Handle, buf: &mut [u8]) -> Result<usize, SyscallError> {
     Ok(copy_len)
 }
 
+/// ファイルの offset の位置から最大 max_len バイトを取り出す（ファイルポジションは変えない）
+///
+/// mmap のファイルマッピングで使う。READ 権限が必要で、通常のファイルのみ対応。
+///
+/// # 戻り値
+/// 取り出した内容（offset がファイル末尾以降なら空）
+///
+/// # エラー
+/// - `InvalidHandle`: ハンドルが無効
+/// - `PermissionDenied`: READ 権限がない
+/// - `NotSupported`: ファイル以外のハンドル
+pub fn read_range(handle: &Handle, offset: usize, max_len: usize) -> Result<Vec<u8>, SyscallError> {
+    let table = HANDLE_TABLE.lock();
+    let entry = get_entry(&table, handle)?;
+
+    if (entry.rights & HANDLE_RIGHT_READ) == 0 {
+        return Err(SyscallError::PermissionDenied);
+    }
+    if entry.kind != HandleKind::File {
+        return Err(SyscallError::NotSupported);
+    }
+    if offset >= entry.data.len() {
+        return Ok(Vec::new());
+    }
+
+    let copy_len = core::cmp::min(entry.data.len() - offset, max_len);
+    Ok(entry.data[offset..offset + copy_len].to_vec())
+}
+
 /// Handle に書き込む
 ///
 /// インメモリの data バッファに書き込み、dirty フラグを立てる。
@@ -349,6 +427,13 @@ pub fn write(handle: &Handle, buf: &[u8]) -> Result<usize, SyscallError> {
         };
     }
 
+    // ソケットは接続先への送信に委譲
+    if entry.kind == HandleKind::Socket {
+        let socket_id = entry.socket_id.ok_or(SyscallError::InvalidHandle)?;
+        drop(table);
+        return crate::socket::send(socket_id, buf, None);
+    }
+
     // ファイルのみ書き込み可能
     if entry.kind != HandleKind::File {
         return Err(SyscallError::NotSupported);
@@ -398,6 +483,20 @@ pub fn close(handle: &Handle) -> Result<(), SyscallError> {
             crate::pipe::close_writer(pipe_id);
             return Ok(());
         }
+        HandleKind::Socket => {
+            let socket_id = entry.socket_id.ok_or(SyscallError::InvalidHandle)?;
+            table[handle.id as usize] = None;
+            drop(table);
+            crate::socket::close(socket_id);
+            return Ok(());
+        }
+        HandleKind::Capture => {
+            let capture_id = entry.capture_id.ok_or(SyscallError::InvalidHandle)?;
+            table[handle.id as usize] = None;
+            drop(table);
+            crate::netstack::capture_close(capture_id);
+            return Ok(());
+        }
         _ => {}
     }
 
@@ -411,10 +510,7 @@ pub fn close(handle: &Handle) -> Result<(), SyscallError> {
         table[handle.id as usize] = None;
         drop(table);
 
-        // VFS 経由で書き戻し: 既存ファイルを削除してから新規作成
-        let _ = crate::vfs::delete_file(&path); // 既存ファイルがなくてもエラーにしない
-        crate::vfs::create_file(&path, &data).map_err(|_| SyscallError::Other)?;
-        return Ok(());
+        return write_back(&path, &data);
     }
 
     // dirty でなければそのまま解放
@@ -422,6 +518,49 @@ pub fn close(handle: &Handle) -> Result<(), SyscallError> {
     Ok(())
 }
 
+/// ハンドルに書き込んだ内容をディスクに書き戻す（fsync）
+///
+/// close と同じ書き戻しを、ハンドルを開いたまま行う。
+/// 戻ってきた時点で内容は FAT32 に書かれている（FAT32 のキャッシュは操作ごとに書き戻される）。
+/// パイプ・ソケット・ディレクトリ・procfs のファイルなど、書き戻すものがないハンドルでは何もしない。
+///
+/// # エラー
+/// - `InvalidHandle`: ハンドルが無効
+pub fn fsync(handle: &Handle) -> Result<(), SyscallError> {
+    let mut table = HANDLE_TABLE.lock();
+    let entry = get_entry_mut(&mut table, handle)?;
+
+    if entry.kind != HandleKind::File || !entry.dirty || entry.path.is_empty() {
+        return Ok(());
+    }
+    let path = entry.path.clone();
+    let data = entry.data.clone();
+    // 書き戻している間に書き込まれたら、また dirty になる
+    entry.dirty = false;
+    drop(table);
+
+    // procfs の内容はメモリ上にしかないので、書き戻す先がない
+    if matches!(crate::vfs::fs_space(&path), Ok((name, _)) if name == "procfs") {
+        return Ok(());
+    }
+
+    let result = write_back(&path, &data);
+    if result.is_err() {
+        // 失敗したら close のときにもう一度書き戻せるよう dirty に戻す
+        let mut table = HANDLE_TABLE.lock();
+        if let Ok(entry) = get_entry_mut(&mut table, handle) {
+            entry.dirty = true;
+        }
+    }
+    result
+}
+
+/// ファイルの内容を VFS 経由で書き戻す（既存ファイルを削除してから新規作成）
+fn write_back(path: &str, data: &[u8]) -> Result<(), SyscallError> {
+    let _ = crate::vfs::delete_file(path); // 既存ファイルがなくてもエラーにしない
+    crate::vfs::create_file(path, data).map_err(|_| SyscallError::Other)
+}
+
 // =================================================================
 // Capability-based 権限操作
 // =================================================================
@@ -441,9 +580,13 @@ pub fn close(handle: &Handle) -> Result<(), SyscallError> {
 /// # エラー
 /// - `InvalidHandle`: ハンドルが無効
 /// - `PermissionDenied`: 権限の拡大を試みた場合
+/// - `NotSupported`: キャプチャハンドル（キューを共有する複製は作れない）
 pub fn restrict_rights(handle: &Handle, new_rights: u32) -> Result<Handle, SyscallError> {
     let table = HANDLE_TABLE.lock();
     let entry = get_entry(&table, handle)?;
+    if entry.kind == HandleKind::Capture {
+        return Err(SyscallError::NotSupported);
+    }
 
     // 権限の拡大を検出（new_rights に entry.rights にないビットがある）
     if (new_rights & !entry.rights) != 0 {
@@ -464,9 +607,15 @@ pub fn restrict_rights(handle: &Handle, new_rights: u32) -> Result<Handle, Sysca
         pos: entry.pos,
         dirty: false,
         pipe_id: entry.pipe_id,
+        socket_id: entry.socket_id,
+        capture_id: None,
     };
+    let socket_id = entry.socket_id;
 
     drop(table); // ロックを解放してから insert_entry を呼ぶ
+    if let Some(sid) = socket_id {
+        crate::socket::add_ref(sid);
+    }
     Ok(insert_entry(new_entry, new_token))
 }
 
@@ -563,7 +712,7 @@ pub fn get_size(handle: &Handle) -> Result<usize, SyscallError> {
 pub struct HandleStat {
     /// ファイルサイズ（バイト）
     pub size: u64,
-    /// ハンドルの種別（0 = File, 1 = Directory）
+    /// ハンドルの種別（0 = File, 1 = Directory, 2 = PipeRead, 3 = PipeWrite, 4 = Socket, 5 = Capture）
     pub kind: u64,
     /// 現在のハンドルの権限ビット
     pub rights: u64,
@@ -598,6 +747,8 @@ pub fn stat(handle: &Handle) -> Result<HandleStat, SyscallError> {
             HandleKind::Directory => 1,
             HandleKind::PipeRead => 2,
             HandleKind::PipeWrite => 3,
+            HandleKind::Socket => 4,
+            HandleKind::Capture => 5,
         },
         rights: entry.rights as u64,
     })
@@ -715,6 +866,8 @@ pub fn create_pipe_handles() -> (Handle, Handle) {
         pos: 0,
         dirty: false,
         pipe_id: Some(pipe_id),
+        socket_id: None,
+        capture_id: None,
     };
     let read_handle = insert_entry(read_entry, read_token);
 
@@ -729,12 +882,79 @@ pub fn create_pipe_handles() -> (Handle, Handle) {
         pos: 0,
         dirty: false,
         pipe_id: Some(pipe_id),
+        socket_id: None,
+        capture_id: None,
     };
     let write_handle = insert_entry(write_entry, write_token);
 
     (read_handle, write_handle)
 }
 
+// =================================================================
+// ソケットハンドル
+// =================================================================
+
+/// ソケット用の Handle を作成する
+///
+/// socket_id は socket::create() / socket::accept() が返したもの。
+/// ハンドルを閉じると socket::close() が呼ばれる。
+pub fn create_socket_handle(socket_id: usize) -> Handle {
+    let token = next_token();
+    let entry = HandleEntry {
+        token,
+        rights: HANDLE_RIGHTS_SOCKET,
+        kind: HandleKind::Socket,
+        path: String::new(),
+        data: Vec::new(),
+        pos: 0,
+        dirty: false,
+        pipe_id: None,
+        socket_id: Some(socket_id),
+        capture_id: None,
+    };
+    insert_entry(entry, token)
+}
+
+/// ソケットハンドルから socket_id を取り出す
+///
+/// ソケット以外のハンドルなら NotSupported、required_rights が足りなければ PermissionDenied。
+pub fn get_socket_id(handle: &Handle, required_rights: u32) -> Result<usize, SyscallError> {
+    let table = HANDLE_TABLE.lock();
+    let entry = get_entry(&table, handle)?;
+    if entry.kind != HandleKind::Socket {
+        return Err(SyscallError::NotSupported);
+    }
+    if (entry.rights & required_rights) != required_rights {
+        return Err(SyscallError::PermissionDenied);
+    }
+    entry.socket_id.ok_or(SyscallError::InvalidHandle)
+}
+
+// =================================================================
+// キャプチャハンドル
+// =================================================================
+
+/// フレームキャプチャ用の Handle を作成する
+///
+/// capture_id は netstack::capture_open() が返したもの。読み取り専用で、
+/// ハンドルを閉じると netstack::capture_close() が呼ばれる。
+pub fn create_capture_handle(capture_id: usize) -> Handle {
+    let token = next_token();
+    let entry = HandleEntry {
+        token,
+        rights: HANDLE_RIGHT_READ | HANDLE_RIGHT_STAT,
+        kind: HandleKind::Capture,
+        path: String::new(),
+        data: Vec::new(),
+        pos: 0,
+        dirty: false,
+        pipe_id: None,
+        socket_id: None,
+        capture_id: Some(capture_id),
+    };
+    insert_entry(entry, token)
+}
+
 /// token を生成（単調カウンタ + 定数）
 fn next_token() -> u64 {
     let n = HANDLE_TOKEN_COUNTER.fetch_add(1, Ordering::Relaxed);
@@ -12,11 +12,14 @@
 // PIC が IRQ 0〜15 を IDT の 32〜47 番にマッピングする。
 
 use alloc::collections::VecDeque;
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use lazy_static::lazy_static;
 use pic8259::ChainedPics;
 use spin;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::structures::idt::{
+    InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode, SelectorErrorCode,
+};
 
 use crate::gdt;
 
@@ -24,6 +27,15 @@ use crate::gdt;
 /// プリエンプティブスケジューリングの動作確認や、システムの稼働時間の目安に使える。
 pub static TIMER_TICK_COUNT: AtomicU64 = AtomicU64::new(0);
 
+/// タイマー割り込みのたびに呼ぶ関数（fn ポインタを usize で持つ。0 なら無し）
+static TIMER_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// タイマー割り込みのたびに hook を呼ぶようにする（None で解除）。
+/// selftest が割り込みコンテキストからの処理を試すときだけ使う。
+pub fn set_timer_hook(hook: Option<fn()>) {
+    TIMER_HOOK.store(hook.map_or(0, |f| f as usize), Ordering::Relaxed);
+}
+
 // =================================================================
 // キー入力キュー
 // =================================================================
@@ -39,6 +51,31 @@ lazy_static! {
         spin::Mutex::new(VecDeque::new());
 }
 
+/// キーボードからの入力をシェルに流すか（input コマンドで切り替える）
+static KEYBOARD_INPUT_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// キーボード入力の有効 / 無効を切り替える
+pub fn set_keyboard_input_enabled(enabled: bool) {
+    KEYBOARD_INPUT_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// キーボード入力が有効か
+pub fn keyboard_input_enabled() -> bool {
+    KEYBOARD_INPUT_ENABLED.load(Ordering::Relaxed)
+}
+
+/// 入力された 1 文字をシェルに渡す（キーボードとシリアルの共通入口）。
+///
+/// 入力は2箇所に通知される:
+/// 1. KEY_QUEUE — カーネルシェル用
+/// 2. console::push_input_char() — ユーザー空間 SYS_READ 用
+///
+/// 割り込みハンドラか、割り込み禁止の状態から呼ぶこと。
+pub fn push_input_char(c: char) {
+    KEY_QUEUE.lock().push_back(c);
+    crate::console::push_input_char(c);
+}
+
 /// キューから1文字取り出す。キューが空なら None を返す。
 /// 割り込みを無効化してからロックを取ることで、
 /// キーボードハンドラとのデッドロックを防ぐ。
@@ -82,13 +119,23 @@ pub enum InterruptIndex {
     /// IRQ 1: キーボード (PS/2)
     /// キーが押された/離されたときに発火する。
     Keyboard,
+    /// IRQ 4: シリアルポート COM1
+    /// 受信データがあるときに発火する。
+    Serial1 = PIC_1_OFFSET + 4,
     /// IRQ 12: マウス (PS/2)
     /// マウスのパケット受信時に発火する。
     Mouse = PIC_2_OFFSET + 4,
+    /// MSI-X: virtio-net の受信完了。
+    /// MSI-X のベクタは I/O APIC の IRQ（32〜55）と重ならないよう 0x50 以降に置く。
+    VirtioNetMsix = 0x50,
+    /// MSI-X: virtio-blk のリクエスト完了（複数デバイスで共有）
+    VirtioBlkMsix,
+    /// MSI-X: NVMe の Completion Queue 更新（複数コントローラで共有）
+    NvmeMsix,
 }
 
 impl InterruptIndex {
-    fn as_u8(self) -> u8 {
+    pub fn as_u8(self) -> u8 {
         self as u8
     }
 }
@@ -169,9 +216,16 @@ lazy_static! {
 
         // IRQ 1: キーボード割り込み
         idt[InterruptIndex::Keyboard.as_u8()].set_handler_fn(keyboard_interrupt_handler);
+        // IRQ 4: シリアル受信割り込み
+        idt[InterruptIndex::Serial1.as_u8()].set_handler_fn(serial_interrupt_handler);
         // IRQ 12: マウス割り込み
         idt[InterruptIndex::Mouse.as_u8()].set_handler_fn(mouse_interrupt_handler);
 
+        // MSI-X: PCI デバイスから Local APIC に直接届く割り込み
+        idt[InterruptIndex::VirtioNetMsix.as_u8()].set_handler_fn(virtio_net_msix_handler);
+        idt[InterruptIndex::VirtioBlkMsix.as_u8()].set_handler_fn(virtio_blk_msix_handler);
+        idt[InterruptIndex::NvmeMsix.as_u8()].set_handler_fn(nvme_msix_handler);
+
         // --- ソフトウェア割り込み: システムコール (int 0x80) ---
         //
         // int 0x80 はユーザーモード (Ring 3) からカーネル (Ring 0) への
@@ -212,14 +266,21 @@ pub fn init() {
         //
         // マスクは各ビットが 1 = マスク（無効）、0 = アンマスク（有効）。
         // マスタ PIC: bit 0 = IRQ 0 (タイマー), bit 1 = IRQ 1 (キーボード),
-        //             bit 2 = IRQ 2 (スレーブ連携)
-        //   0b11111000 → IRQ 0, 1, 2 のみ有効
+        //             bit 2 = IRQ 2 (スレーブ連携), bit 4 = IRQ 4 (COM1)
+        //   0b11101000 → IRQ 0, 1, 2, 4 のみ有効
         // スレーブ PIC: bit 4 = IRQ 12 (マウス)
         //   0b11101111 → IRQ 12 のみ有効
-        pics.write_masks(0b11111000, 0b11101111);
+        pics.write_masks(0b11101000, 0b11101111);
     }
 }
 
+/// AP（BSP 以外の CPU）に IDT をロードする。
+/// IDT は全 CPU で共有する。PIC の設定は BSP が済ませているので何もしない。
+/// 先に gdt::CpuGdt::load() で自分の TSS をロードしておくこと。
+pub fn init_ap() {
+    IDT.load();
+}
+
 // =================================================================
 // CPU 例外ハンドラの実装 (0〜31番)
 // =================================================================
@@ -227,20 +288,27 @@ pub fn init() {
 // 各ハンドラは x86_64 の割り込み呼び出し規約 (x86-interrupt) に従う。
 // 第1引数の InterruptStackFrame には例外発生時の RIP, RSP, RFLAGS 等が入っている。
 // エラーコード付きの例外（GPF, PF, DF等）は第2引数にエラーコードが渡される。
+//
+// どのハンドラも先頭で percpu::KernelGs を作り、Ring 3 から来たときの GS ベースを
+// カーネルのものに切り替える（IRQ ハンドラも同じ）。
 
 extern "x86-interrupt" fn divide_error_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: DIVIDE ERROR (#DE)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: DEBUG (#DB)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn nmi_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter_paranoid();
     panic!("CPU EXCEPTION: NON-MASKABLE INTERRUPT (#NMI)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     // ブレークポイントは致命的ではないので panic しない。
     // ただし今はシリアルもフレームバッファもハンドラから使えないので、
     // とりあえず何もせず戻る。テストで使う。
@@ -248,18 +316,22 @@ extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
 }
 
 extern "x86-interrupt" fn overflow_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: OVERFLOW (#OF)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn bound_range_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: BOUND RANGE EXCEEDED (#BR)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: INVALID OPCODE (#UD)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn device_not_available_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: DEVICE NOT AVAILABLE (#NM)\n{:#?}", stack_frame);
 }
 
@@ -267,9 +339,23 @@ extern "x86-interrupt" fn general_protection_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
+    // Ring 3 で特権命令を実行したり、非正規アドレスに触れたりしても #GP になる。
+    // ページフォルトと同じく、ユーザーモードからならそのプログラムだけを終了させる。
+    if is_user_frame(&stack_frame) {
+        crate::kprintln!();
+        crate::kprintln!("General protection fault in user mode!");
+        crate::kprintln!("  Error code: {}", GpErrorCode(error_code));
+        crate::kprintln!("  Task: {}", FaultingTask);
+        crate::kprintln!("  RIP: {:?}", stack_frame.instruction_pointer);
+        crate::kprintln!("  RSP: {:?}", stack_frame.stack_pointer);
+        crate::kprintln!("  Terminating user program...");
+        crate::scheduler::abort_current_user_task_from_exception();
+    }
+
     panic!(
-        "CPU EXCEPTION: GENERAL PROTECTION FAULT (#GP)\nError code: {}\n{:#?}",
-        error_code, stack_frame
+        "CPU EXCEPTION: GENERAL PROTECTION FAULT (#GP)\nError code: {}\nTask: {}\n{:#?}",
+        GpErrorCode(error_code), FaultingTask, stack_frame
     );
 }
 
@@ -277,8 +363,10 @@ extern "x86-interrupt" fn page_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     // CR2 レジスタにはページフォルトを起こしたアドレスが入っている。
     use x86_64::registers::control::Cr2;
+    let fault_addr = Cr2::read_raw();
 
     // Ring 3（ユーザーモード）からの不正アクセスかどうかを判定する。
     // PageFaultErrorCode の USER_MODE ビットが立っていれば Ring 3 からのアクセス。
@@ -286,18 +374,19 @@ extern "x86-interrupt" fn page_fault_handler(
     // カーネル（シェル）に安全に戻る。
     if error_code.contains(PageFaultErrorCode::USER_MODE) {
         crate::kprintln!();
-        crate::kprintln!("Page fault in user mode!");
-        let fault_addr = Cr2::read().expect("Cr2::read failed");
-        crate::kprintln!("  Accessed address: {:#x}", fault_addr.as_u64());
-        crate::kprintln!("  Error code: {:?}", error_code);
-        crate::scheduler::with_current_task(|task| {
-            crate::kprintln!("  Task: {} ({})", task.id, task.name);
-        });
+        crate::kprintln!("Page fault in user mode: {}", PageFaultKind(error_code));
+        // スタック直下のガードページに触れた = スタックオーバーフロー
+        if crate::usermode::is_stack_guard_addr(fault_addr) {
+            crate::kprintln!("  stack overflow in task {}", FaultingTask);
+        }
+        crate::kprintln!("  Accessed address (CR2): {:#x}", fault_addr);
+        crate::kprintln!("  Error code: {:#x} {:?}", error_code.bits(), error_code);
+        crate::kprintln!("  Task: {}", FaultingTask);
         crate::kprintln!("  RIP: {:?}", stack_frame.instruction_pointer);
         crate::kprintln!("  RSP: {:?}", stack_frame.stack_pointer);
 
         // デバッグ: ページテーブルエントリのダンプ（fault_addr のマッピング状態を確認）
-        crate::paging::debug_dump_page_entry(fault_addr.as_u64());
+        crate::paging::debug_dump_page_entry(fault_addr);
 
         crate::kprintln!("  Terminating user program...");
         // ユーザーモード例外は現在のユーザータスクを終了させて
@@ -308,9 +397,12 @@ extern "x86-interrupt" fn page_fault_handler(
 
     // Ring 0（カーネル）からのページフォルトは回復不能なので panic する。
     panic!(
-        "CPU EXCEPTION: PAGE FAULT (#PF)\nAccessed address: {:?}\nError code: {:?}\n{:#?}",
-        Cr2::read(),
+        "CPU EXCEPTION: PAGE FAULT (#PF): {}\nAccessed address (CR2): {:#x}\nError code: {:#x} {:?}\nTask: {}\n{:#?}",
+        PageFaultKind(error_code),
+        fault_addr,
+        error_code.bits(),
         error_code,
+        FaultingTask,
         stack_frame
     );
 }
@@ -319,6 +411,7 @@ extern "x86-interrupt" fn invalid_tss_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!(
         "CPU EXCEPTION: INVALID TSS (#TS)\nError code: {}\n{:#?}",
         error_code, stack_frame
@@ -329,6 +422,7 @@ extern "x86-interrupt" fn segment_not_present_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!(
         "CPU EXCEPTION: SEGMENT NOT PRESENT (#NP)\nError code: {}\n{:#?}",
         error_code, stack_frame
@@ -339,6 +433,7 @@ extern "x86-interrupt" fn stack_segment_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!(
         "CPU EXCEPTION: STACK-SEGMENT FAULT (#SS)\nError code: {}\n{:#?}",
         error_code, stack_frame
@@ -349,15 +444,22 @@ extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) -> ! {
+    let _gs = crate::percpu::KernelGs::enter_paranoid();
     // ダブルフォルトは回復不能。情報を表示して停止する。
     // IST で専用スタックに切り替わっているので、ここまでは来れるはず。
+    // カーネルスタックのあふれなど、ページフォルトの処理中に起きることが多いので、
+    // 直前のページフォルトのアドレス（CR2）も出しておく。
     panic!(
-        "CPU EXCEPTION: DOUBLE FAULT (#DF)\nError code: {}\n{:#?}",
-        error_code, stack_frame
+        "CPU EXCEPTION: DOUBLE FAULT (#DF)\nError code: {}\nLast page fault address (CR2): {:#x}\nTask: {}\n{:#?}",
+        error_code,
+        x86_64::registers::control::Cr2::read_raw(),
+        FaultingTask,
+        stack_frame
     );
 }
 
 extern "x86-interrupt" fn x87_fp_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: x87 FLOATING-POINT (#MF)\n{:#?}", stack_frame);
 }
 
@@ -365,6 +467,7 @@ extern "x86-interrupt" fn alignment_check_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!(
         "CPU EXCEPTION: ALIGNMENT CHECK (#AC)\nError code: {}\n{:#?}",
         error_code, stack_frame
@@ -372,15 +475,88 @@ extern "x86-interrupt" fn alignment_check_handler(
 }
 
 extern "x86-interrupt" fn machine_check_handler(stack_frame: InterruptStackFrame) -> ! {
+    let _gs = crate::percpu::KernelGs::enter_paranoid();
     // Machine Check は回復不能な致命的ハードウェアエラー。
     // diverging ハンドラとして実装する（戻れない）。
     panic!("CPU EXCEPTION: MACHINE CHECK (#MC)\n{:#?}", stack_frame);
 }
 
 extern "x86-interrupt" fn simd_fp_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     panic!("CPU EXCEPTION: SIMD FLOATING-POINT (#XM)\n{:#?}", stack_frame);
 }
 
+// =================================================================
+// 例外の診断表示
+// =================================================================
+//
+// 例外ハンドラの中ではヒープを使いたくないので、Display を実装した型で
+// そのまま kprintln! / panic! のフォーマットに渡す。
+
+/// 例外がユーザーモード（Ring 3）で起きたか（CS のセレクタの RPL で見る）
+fn is_user_frame(stack_frame: &InterruptStackFrame) -> bool {
+    stack_frame.code_segment.rpl() == x86_64::PrivilegeLevel::Ring3
+}
+
+/// ページフォルトのエラーコードを "user write to non-present page" のように表示する
+pub struct PageFaultKind(pub PageFaultErrorCode);
+
+impl fmt::Display for PageFaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = self.0;
+        let mode = if code.contains(PageFaultErrorCode::USER_MODE) { "user" } else { "kernel" };
+        let access = if code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {
+            "instruction fetch from"
+        } else if code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+            "write to"
+        } else {
+            "read from"
+        };
+        // P ビットが立っていれば、ページはあるが権限が足りない
+        let page = if code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+            "protected page"
+        } else {
+            "non-present page"
+        };
+        write!(f, "{} {} {}", mode, access, page)?;
+        if code.contains(PageFaultErrorCode::MALFORMED_TABLE) {
+            f.write_str(" (reserved bit set in page table)")?;
+        }
+        Ok(())
+    }
+}
+
+/// #GP のエラーコード。0 以外ならセグメントセレクタが原因。
+struct GpErrorCode(u64);
+
+impl fmt::Display for GpErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("0 (not segment related)");
+        }
+        let selector = SelectorErrorCode::new_truncate(self.0);
+        write!(
+            f,
+            "{:#x} (selector index {} in {:?}{})",
+            self.0,
+            selector.index(),
+            selector.descriptor_table(),
+            if selector.external() { ", external" } else { "" }
+        )
+    }
+}
+
+/// 例外を起こしたタスクを "id (name)" の形で表示する。
+/// SCHEDULER を持ったまま例外が起きても固まらないよう、取れなければ "unknown"。
+struct FaultingTask;
+
+impl fmt::Display for FaultingTask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        crate::scheduler::try_with_current_task(|task| write!(f, "{} ({})", task.id, task.name))
+            .unwrap_or_else(|| f.write_str("unknown (scheduler locked)"))
+    }
+}
+
 // =================================================================
 // EOI (End Of Interrupt) ヘルパー
 // =================================================================
@@ -421,12 +597,21 @@ fn eoi(vector: u8) {
 ///   「このタスクが再スケジュールされるまで」実行されない。
 ///   EOI を送らずに切り替えると、PIC がタイマー割り込みをブロックし続け、
 ///   切り替え先タスクがタイマー割り込みを受け取れなくなる。
-extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+extern "x86-interrupt" fn timer_interrupt_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     TIMER_TICK_COUNT.fetch_add(1, Ordering::Relaxed);
+    crate::random::add_interrupt_timing();
 
     // EOI を先に送る（プリエンプション前に割り込みコントローラをクリアする）
     eoi(InterruptIndex::Timer.as_u8());
 
+    // selftest が登録したフック（普段は登録されていない）
+    let hook = TIMER_HOOK.load(Ordering::Relaxed);
+    if hook != 0 {
+        let hook: fn() = unsafe { core::mem::transmute(hook) };
+        hook();
+    }
+
     // プリエンプティブスケジューリング:
     // 現在のタスクを中断して、次の Ready タスクに切り替える。
     // try_lock() を使うので、SCHEDULER がロック中なら何もせずスキップする。
@@ -437,11 +622,10 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
 /// PS/2 キーボードからスキャンコードが I/O ポート 0x60 に届く。
 /// スキャンコードを読み取って文字に変換し、入力バッファに追加する。
 ///
-/// 入力は2箇所に通知される:
-/// 1. KEY_QUEUE — カーネルシェル用（後方互換性）
-/// 2. console::push_input_char() — ユーザー空間 SYS_READ 用
-extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+/// 文字は push_input_char() でカーネルシェルとユーザー空間の両方に流す。
+extern "x86-interrupt" fn keyboard_interrupt_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
+    use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, Keyboard, ScancodeSet1};
     use x86_64::instructions::port::Port;
 
     // キーボードの状態をグローバルに保持する。
@@ -463,6 +647,8 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
     // 読み取らないと次の割り込みが来なくなる。
     let mut port = Port::new(0x60);
     let scancode: u8 = unsafe { port.read() };
+    // キーが押されたタイミングは予測しにくいので乱数のエントロピーに使う
+    crate::random::add_interrupt_timing();
 
     // pc-keyboard crate でスキャンコードをキーイベントに変換する。
     // add_byte() でスキャンコードを投入し、process_keyevent() で
@@ -471,15 +657,27 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
         if let Some(key) = keyboard.process_keyevent(key_event) {
             match key {
                 DecodedKey::Unicode(character) => {
-                    // 文字をキー入力キューに追加する（カーネルシェル用）
-                    KEY_QUEUE.lock().push_back(character);
-                    // コンソール入力バッファにも追加（ユーザー空間 SYS_READ 用）
-                    crate::console::push_input_char(character);
+                    if keyboard_input_enabled() {
+                        push_input_char(character);
+                    }
                 }
                 DecodedKey::RawKey(key) => {
-                    // 特殊キー（矢印キー、F1-F12等）は今は無視。
-                    // 将来的にはシェルのカーソル移動等に使う。
-                    let _ = key;
+                    // 矢印キーはシリアル端末と同じ ESC [ A/B/C/D に変換してカーネルシェルに渡す。
+                    // ユーザー空間のシェルはまだエスケープシーケンスを解釈しないので、
+                    // "[A" が行に紛れ込まないよう console 側には流さない。
+                    // それ以外の特殊キー（F1-F12 等）は今は無視。
+                    let arrow = match key {
+                        KeyCode::ArrowUp => Some('A'),
+                        KeyCode::ArrowDown => Some('B'),
+                        KeyCode::ArrowRight => Some('C'),
+                        KeyCode::ArrowLeft => Some('D'),
+                        _ => None,
+                    };
+                    if let Some(c) = arrow
+                        && keyboard_input_enabled()
+                    {
+                        KEY_QUEUE.lock().extend(['\x1b', '[', c]);
+                    }
                 }
             }
         }
@@ -489,13 +687,48 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
     eoi(InterruptIndex::Keyboard.as_u8());
 }
 
+/// IRQ 4: シリアル（COM1）受信割り込みハンドラ。
+/// 受信 FIFO のバイトを読み出してキーボードと同じ入力キューに流す。
+extern "x86-interrupt" fn serial_interrupt_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
+    crate::random::add_interrupt_timing();
+    crate::serial::poll_input();
+
+    eoi(InterruptIndex::Serial1.as_u8());
+}
+
 /// IRQ 12: マウス割り込みハンドラ。
 /// PS/2 マウスからの 1 バイトを読み取り、パケット組み立てへ渡す。
-extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+extern "x86-interrupt" fn mouse_interrupt_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
     use x86_64::instructions::port::Port;
 
     let byte = unsafe { Port::<u8>::new(0x60).read() };
+    crate::random::add_interrupt_timing();
     crate::mouse::handle_irq_byte(byte);
 
     eoi(InterruptIndex::Mouse.as_u8());
 }
+
+/// MSI-X: virtio-net 受信割り込みハンドラ。
+/// net_poller を起こしてパケットを処理させる。
+/// MSI-X は APIC モードでしか有効にしないので EOI は常に Local APIC 宛て。
+extern "x86-interrupt" fn virtio_net_msix_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
+    crate::virtio_net::handle_msix_interrupt();
+    eoi(InterruptIndex::VirtioNetMsix.as_u8());
+}
+
+/// MSI-X: virtio-blk 完了割り込みハンドラ。
+extern "x86-interrupt" fn virtio_blk_msix_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
+    crate::virtio_blk::handle_msix_interrupt();
+    eoi(InterruptIndex::VirtioBlkMsix.as_u8());
+}
+
+/// MSI-X: NVMe 完了割り込みハンドラ。
+extern "x86-interrupt" fn nvme_msix_handler(stack_frame: InterruptStackFrame) {
+    let _gs = crate::percpu::KernelGs::enter(&stack_frame);
+    crate::nvme::handle_msix_interrupt();
+    eoi(InterruptIndex::NvmeMsix.as_u8());
+}
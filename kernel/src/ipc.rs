@@ -7,7 +7,7 @@
 // ## Sleep/Wake 方式
 //
 // recv() はポーリングではなく、futex.rs と同じパターンで
-// set_current_sleeping + wake_task を使って待機する。
+// scheduler::park_until + wake_task を使って待機する。
 // これにより CPU サイクルの浪費を防ぎ、レイテンシも改善する。
 //
 // ## キャンセル機構
@@ -29,13 +29,16 @@ use spin::Mutex;
 
 use crate::handle::{self, Handle};
 use crate::scheduler;
+use crate::slab_allocator::MsgBuf;
 use crate::user_ptr::SyscallError;
 
 /// IPC メッセージ
+///
+/// data は小さければ IPC 専用のスラブキャッシュに置かれる（slab_allocator::MsgBuf）。
 #[derive(Debug, Clone)]
 pub struct IpcMessage {
     pub sender: u64,
-    pub data: Vec<u8>,
+    pub data: MsgBuf,
 }
 
 /// ハンドル付き IPC メッセージ
@@ -84,12 +87,14 @@ lazy_static! {
 
 /// メッセージを送信する
 ///
-/// メッセージを dest タスクの受信キューに追加する。
+/// data をコピーして dest タスクの受信キューに追加する。
+/// 128 バイト以下のメッセージはグローバルアロケータを通さず、専用のスラブキャッシュに置く。
 /// dest が recv 待ち（Sleeping）の場合は wake_task で起床させる。
-pub fn send(sender: u64, dest: u64, data: Vec<u8>) -> Result<(), SyscallError> {
+pub fn send(sender: u64, dest: u64, data: &[u8]) -> Result<(), SyscallError> {
     if !scheduler::task_exists(dest) {
         return Err(SyscallError::InvalidArgument);
     }
+    let data = MsgBuf::from_slice(data);
 
     // メッセージをキューに追加
     {
@@ -113,7 +118,7 @@ pub fn send(sender: u64, dest: u64, data: Vec<u8>) -> Result<(), SyscallError> {
 /// タイムアウトに達するまでループする。各イテレーションで:
 /// 1. try_recv で即チェック → あればすぐ返す
 /// 2. タイムアウトチェック → 達していれば Timeout
-/// 3. IPC_WAITERS に登録 → set_current_sleeping → ダブルチェック → yield
+/// 3. IPC_WAITERS に登録 → park_until（Sleeping にした直後にもう一度確認してから眠る）
 /// 4. 起床後: キャンセルチェック → try_recv → なければループ先頭に戻る
 pub fn recv(task_id: u64, timeout_ms: u64) -> Result<IpcMessage, SyscallError> {
     // timeout_ms == 0 は非ブロッキング: 即座チェックして返却
@@ -123,7 +128,7 @@ pub fn recv(task_id: u64, timeout_ms: u64) -> Result<IpcMessage, SyscallError> {
     }
 
     // タイムアウト計算（ループ全体の期限）
-    let deadline = calc_wake_at(timeout_ms);
+    let deadline = scheduler::deadline_after_ms(timeout_ms);
 
     loop {
         // 1. 即座にチェック
@@ -132,7 +137,7 @@ pub fn recv(task_id: u64, timeout_ms: u64) -> Result<IpcMessage, SyscallError> {
         }
 
         // 2. タイムアウトチェック
-        if is_deadline_reached(deadline) {
+        if scheduler::deadline_reached(deadline) {
             return Err(SyscallError::Timeout);
         }
 
@@ -142,26 +147,22 @@ pub fn recv(task_id: u64, timeout_ms: u64) -> Result<IpcMessage, SyscallError> {
             waiters.insert(task_id);
         }
 
-        // Sleeping に遷移（deadline で自動起床）
-        scheduler::set_current_sleeping(deadline);
-
-        // ダブルチェック: Sleeping にした直後、send() が来ていないか確認
-        // （set_current_sleeping と yield_now の間に send が来た場合の対策）
-        if let Some(msg) = try_recv(task_id) {
-            scheduler::wake_task(task_id);
-            let mut waiters = IPC_WAITERS.lock();
-            waiters.remove(&task_id);
-            return Ok(msg);
-        }
-
-        // スケジューラに制御を渡す
-        scheduler::yield_now();
+        // deadline まで眠る。Sleeping にした直後にもう一度キューを確認し、
+        // 登録してから眠るまでの間に send が来ていたら眠らずに受け取る
+        let mut received = None;
+        scheduler::park_until(deadline, || {
+            received = try_recv(task_id);
+            received.is_some()
+        });
 
         // 起床後の処理
         {
             let mut waiters = IPC_WAITERS.lock();
             waiters.remove(&task_id);
         }
+        if let Some(msg) = received {
+            return Ok(msg);
+        }
 
         // キャンセルチェック
         {
@@ -213,7 +214,7 @@ pub fn recv_from(task_id: u64, from_sender: u64, timeout_ms: u64) -> Result<IpcM
         return try_recv_from(task_id, from_sender).ok_or(SyscallError::Timeout);
     }
 
-    let deadline = calc_wake_at(timeout_ms);
+    let deadline = scheduler::deadline_after_ms(timeout_ms);
 
     loop {
         // 1. 即座にチェック（送信元フィルタリング付き）
@@ -222,7 +223,7 @@ pub fn recv_from(task_id: u64, from_sender: u64, timeout_ms: u64) -> Result<IpcM
         }
 
         // 2. タイムアウトチェック
-        if is_deadline_reached(deadline) {
+        if scheduler::deadline_reached(deadline) {
             return Err(SyscallError::Timeout);
         }
 
@@ -232,25 +233,22 @@ pub fn recv_from(task_id: u64, from_sender: u64, timeout_ms: u64) -> Result<IpcM
             waiters.insert(task_id);
         }
 
-        // Sleeping に遷移（deadline で自動起床）
-        scheduler::set_current_sleeping(deadline);
-
-        // ダブルチェック
-        if let Some(msg) = try_recv_from(task_id, from_sender) {
-            scheduler::wake_task(task_id);
-            let mut waiters = IPC_WAITERS.lock();
-            waiters.remove(&task_id);
-            return Ok(msg);
-        }
-
-        // スケジューラに制御を渡す
-        scheduler::yield_now();
+        // deadline まで眠る。Sleeping にした直後にもう一度キューを確認し、
+        // 登録してから眠るまでの間に send が来ていたら眠らずに受け取る
+        let mut received = None;
+        scheduler::park_until(deadline, || {
+            received = try_recv_from(task_id, from_sender);
+            received.is_some()
+        });
 
         // 起床後の処理
         {
             let mut waiters = IPC_WAITERS.lock();
             waiters.remove(&task_id);
         }
+        if let Some(msg) = received {
+            return Ok(msg);
+        }
 
         // キャンセルチェック
         {
@@ -332,25 +330,22 @@ pub fn recv_with_handle(task_id: u64) -> Result<IpcMessageWithHandle, SyscallErr
             waiters.insert(task_id);
         }
 
-        // 無期限 Sleeping に遷移
-        scheduler::set_current_sleeping(u64::MAX);
-
-        // ダブルチェック
-        if let Some(msg) = try_recv_with_handle(task_id) {
-            scheduler::wake_task(task_id);
-            let mut waiters = IPC_WAITERS.lock();
-            waiters.remove(&task_id);
-            return Ok(msg);
-        }
-
-        // スケジューラに制御を渡す
-        scheduler::yield_now();
+        // 起こされるまで眠る。Sleeping にした直後にもう一度キューを確認し、
+        // 登録してから眠るまでの間に send が来ていたら眠らずに受け取る
+        let mut received = None;
+        scheduler::park_until(u64::MAX, || {
+            received = try_recv_with_handle(task_id);
+            received.is_some()
+        });
 
         // 起床後の処理
         {
             let mut waiters = IPC_WAITERS.lock();
             waiters.remove(&task_id);
         }
+        if let Some(msg) = received {
+            return Ok(msg);
+        }
 
         // キャンセルチェック
         {
@@ -426,34 +421,6 @@ fn wake_if_waiting(dest: u64) {
     }
 }
 
-/// タイムアウトから wake_at（タイマーティック）を計算する
-///
-/// 注意: timeout_ms == 0 は呼び出し元（recv, recv_from）で非ブロッキング処理済み。
-/// この関数は timeout_ms > 0 の場合にのみ呼ばれる想定だが、
-/// 万一 0 が渡された場合は u64::MAX（無期限）として扱う。
-fn calc_wake_at(timeout_ms: u64) -> u64 {
-    if timeout_ms == 0 {
-        u64::MAX
-    } else {
-        let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-        // PIT は約 18.2 Hz (1 tick ≈ 55ms)
-        // ticks = ms * 182 / 10000
-        now + (timeout_ms * 182 / 10000).max(1)
-    }
-}
-
-/// deadline に達したかどうかを判定する
-///
-/// deadline = u64::MAX の場合は無期限待ちなので常に false を返す。
-/// recv() のループで、タイマー起床後にまだ時間が残っているかチェックするのに使う。
-fn is_deadline_reached(deadline: u64) -> bool {
-    if deadline == u64::MAX {
-        return false; // 無期限待ち
-    }
-    let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-    now >= deadline
-}
-
 // =================================================================
 // 型安全 IPC (カーネル内プロトタイプ)
 // =================================================================
@@ -501,7 +468,7 @@ pub fn recv_typed<T: Copy + Send + 'static>(task_id: u64, timeout_ms: u64) -> Re
     }
 
     // タイムアウト計算（ループ全体の期限）
-    let deadline = calc_wake_at(timeout_ms);
+    let deadline = scheduler::deadline_after_ms(timeout_ms);
 
     loop {
         // 即座にチェック
@@ -512,7 +479,7 @@ pub fn recv_typed<T: Copy + Send + 'static>(task_id: u64, timeout_ms: u64) -> Re
         }
 
         // タイムアウトチェック
-        if is_deadline_reached(deadline) {
+        if scheduler::deadline_reached(deadline) {
             return Err(SyscallError::Timeout);
         }
 
@@ -522,34 +489,24 @@ pub fn recv_typed<T: Copy + Send + 'static>(task_id: u64, timeout_ms: u64) -> Re
             waiters.insert(task_id);
         }
 
-        // Sleeping に遷移（deadline で自動起床）
-        scheduler::set_current_sleeping(deadline);
-
-        // ダブルチェック
-        match try_recv_typed_once::<T>(task_id) {
-            Ok(Some(msg)) => {
-                scheduler::wake_task(task_id);
-                let mut waiters = IPC_WAITERS.lock();
-                waiters.remove(&task_id);
-                return Ok(msg);
-            }
-            Err(e) => {
-                scheduler::wake_task(task_id);
-                let mut waiters = IPC_WAITERS.lock();
-                waiters.remove(&task_id);
-                return Err(e);
-            }
-            Ok(None) => {}
-        }
-
-        // スケジューラに制御を渡す
-        scheduler::yield_now();
+        // deadline まで眠る。Sleeping にした直後にもう一度キューを確認し、
+        // 登録してから眠るまでの間に send が来ていたら眠らずに受け取る
+        let mut received = Ok(None);
+        scheduler::park_until(deadline, || {
+            received = try_recv_typed_once::<T>(task_id);
+            !matches!(received, Ok(None))
+        });
 
         // 起床後の処理
         {
             let mut waiters = IPC_WAITERS.lock();
             waiters.remove(&task_id);
         }
+        match received {
+            Ok(Some(msg)) => return Ok(msg),
+            Err(e) => return Err(e),
+            Ok(None) => {}
+        }
 
         // キャンセルチェック
         {
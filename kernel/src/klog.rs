@@ -0,0 +1,146 @@
+// klog.rs — ログレベルとサブシステムごとの出力切り替え
+//
+// 以前は net_debug! が常に出力され、net_trace! は中身をコメントアウトして
+// 無効にしていたので、詳しいログを見たいたびにビルドし直す必要があった。
+// ここではサブシステム（net / fs / sched / console）ごとにログレベルを
+// アトミック変数で持ち、klog! マクロが出力前にレベルを確かめる。
+// レベルが足りなければ format_args! の中身をフォーマットしないので、
+// パケットごとの trace ログを仕込んでおいても普段はほとんどコストがかからない。
+//
+// シェルの `loglevel <subsystem> <level>` で実行中に切り替えられる。
+// 出力先はシリアル（serial_println! と同じ）で、/proc/kmsg にも残る。
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// ログレベル。大きいほど詳しい。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    /// 何も出さない
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    /// パケット単位などの大量に出るログ
+    Trace = 5,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 6] = [
+        LogLevel::Off,
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    /// "debug" のような名前からレベルを得る（大文字小文字は区別しない）
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.name().eq_ignore_ascii_case(name))
+    }
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or(LogLevel::Trace)
+    }
+}
+
+/// ログを出すサブシステム
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Subsystem {
+    Net = 0,
+    Fs = 1,
+    Sched = 2,
+    Console = 3,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] = [Subsystem::Net, Subsystem::Fs, Subsystem::Sched, Subsystem::Console];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Net => "net",
+            Subsystem::Fs => "fs",
+            Subsystem::Sched => "sched",
+            Subsystem::Console => "console",
+        }
+    }
+
+    /// "net" のような名前からサブシステムを得る（大文字小文字は区別しない）
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// 既定のログレベル。
+/// 以前 net_debug! などで常に出ていたログが減らないよう Debug にしておき、
+/// Trace だけを既定で止める。
+const DEFAULT_LEVEL: LogLevel = LogLevel::Debug;
+
+/// サブシステムごとのログレベル（Subsystem の値で引く）
+static LEVELS: [AtomicU8; Subsystem::ALL.len()] = [
+    AtomicU8::new(DEFAULT_LEVEL as u8),
+    AtomicU8::new(DEFAULT_LEVEL as u8),
+    AtomicU8::new(DEFAULT_LEVEL as u8),
+    AtomicU8::new(DEFAULT_LEVEL as u8),
+];
+
+/// サブシステムの今のログレベル
+pub fn level(subsystem: Subsystem) -> LogLevel {
+    LogLevel::from_u8(LEVELS[subsystem as usize].load(Ordering::Relaxed))
+}
+
+/// サブシステムのログレベルを変える。前のレベルを返す。
+pub fn set_level(subsystem: Subsystem, level: LogLevel) -> LogLevel {
+    LogLevel::from_u8(LEVELS[subsystem as usize].swap(level as u8, Ordering::Relaxed))
+}
+
+/// このレベルのログを出すか。klog! がフォーマットの前に呼ぶ。
+#[inline]
+pub fn enabled(subsystem: Subsystem, level: LogLevel) -> bool {
+    level != LogLevel::Off && level as u8 <= LEVELS[subsystem as usize].load(Ordering::Relaxed)
+}
+
+/// klog! マクロの内部実装。レベルの確認は済んでいる前提。
+///
+/// 行頭は "[net] ..." の形にそろえる（以前の net_debug! と同じ）。
+/// Error / Warn はレベルを、Trace は "[net-trace]" を付けて見分けられるようにする。
+#[doc(hidden)]
+pub fn _log(subsystem: Subsystem, level: LogLevel, args: fmt::Arguments) {
+    let name = subsystem.name();
+    match level {
+        LogLevel::Error => crate::serial_println!("[{}] ERROR: {}", name, args),
+        LogLevel::Warn => crate::serial_println!("[{}] WARN: {}", name, args),
+        LogLevel::Trace => crate::serial_println!("[{}-trace] {}", name, args),
+        _ => crate::serial_println!("[{}] {}", name, args),
+    }
+}
+
+/// サブシステムとレベルを指定してログを出す。
+///
+/// 例: `klog!(Debug, Net, "tcp: sending {} bytes", len)`
+/// レベルが足りないときは引数をフォーマットしない。
+#[macro_export]
+macro_rules! klog {
+    ($level:ident, $subsystem:ident, $($arg:tt)*) => {{
+        let level = $crate::klog::LogLevel::$level;
+        let subsystem = $crate::klog::Subsystem::$subsystem;
+        if $crate::klog::enabled(subsystem, level) {
+            $crate::klog::_log(subsystem, level, format_args!($($arg)*));
+        }
+    }};
+}
@@ -0,0 +1,150 @@
+// kmsg.rs — カーネルログのリングバッファ
+//
+// kprint! / serial_print! の出力はフレームバッファとシリアルに流れるだけで、
+// 画面がスクロールしてしまったり、シリアルを見ていなかったりすると後から読めない。
+// そこで出力を固定サイズ（64KiB）のリングバッファにも書き写しておき、
+// /proc/kmsg と dmesg コマンドから読めるようにする。
+// いっぱいになったら古いものから上書きする。
+//
+// 割り込みハンドラ（ページフォルトなど）からも kprintln! が呼ばれるので、
+// ロックを持っているあいだは必ず割り込みを禁止する。こうしておけば
+// 同じ CPU 上でロックの保持者が割り込まれて待たされることはない。
+// それでも取れない場合（フォーマット中のパニックなどで再入したとき）は
+// 少しだけ待ってから諦め、メッセージを捨てて数だけ数える。
+//
+// ロックを持つのはバイト列をコピーするあいだだけで、WRITER / SERIAL1 のように
+// MMIO や UART の待ちを含まないので、割り込み禁止の時間は短い。
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+/// リングバッファの大きさ（バイト）
+pub const KMSG_CAPACITY: usize = 64 * 1024;
+
+/// ロックが取れないときに待つ回数の上限。
+/// 超えたら再入とみなしてメッセージを捨てる。
+const LOCK_SPIN_LIMIT: usize = 10_000;
+
+/// 固定長のリングバッファ
+struct KmsgRing {
+    buf: [u8; KMSG_CAPACITY],
+    /// これまでに書き込んだ総バイト数（次に書く位置は written % KMSG_CAPACITY）
+    written: u64,
+    /// dmesg -c で消したときの written。ここより前は読み出さない。
+    cleared: u64,
+    /// これまでに書き込んだ改行の数（/proc/kmsg の行の通し番号に使う）
+    lines: u64,
+}
+
+impl KmsgRing {
+    const fn new() -> Self {
+        Self { buf: [0; KMSG_CAPACITY], written: 0, cleared: 0, lines: 0 }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.lines += bytes.iter().filter(|&&b| b == b'\n').count() as u64;
+        // 容量より長い書き込みは末尾だけ残る
+        let bytes = &bytes[bytes.len().saturating_sub(KMSG_CAPACITY)..];
+        let pos = (self.written % KMSG_CAPACITY as u64) as usize;
+        let first = bytes.len().min(KMSG_CAPACITY - pos);
+        self.buf[pos..pos + first].copy_from_slice(&bytes[..first]);
+        self.buf[..bytes.len() - first].copy_from_slice(&bytes[first..]);
+        self.written += bytes.len() as u64;
+    }
+
+    /// 読み出せる範囲（総バイト数での開始位置と長さ）
+    fn readable(&self) -> (u64, usize) {
+        let start = self.cleared.max(self.written.saturating_sub(KMSG_CAPACITY as u64));
+        (start, (self.written - start) as usize)
+    }
+
+    fn copy_to(&self, out: &mut Vec<u8>) {
+        let (start, len) = self.readable();
+        let pos = (start % KMSG_CAPACITY as u64) as usize;
+        let first = len.min(KMSG_CAPACITY - pos);
+        out.extend_from_slice(&self.buf[pos..pos + first]);
+        out.extend_from_slice(&self.buf[..len - first]);
+    }
+}
+
+impl fmt::Write for KmsgRing {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// カーネルログ本体。64KiB あるので lazy_static にせず .bss に置く。
+static KMSG: Mutex<KmsgRing> = Mutex::new(KmsgRing::new());
+
+/// ロックが取れずに捨てたメッセージの数
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// 割り込み禁止の状態で KMSG のロックを取って f を実行する。
+/// ロックが取れなければ None を返す。
+fn with_ring<R>(f: impl FnOnce(&mut KmsgRing) -> R) -> Option<R> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut ring = (0..LOCK_SPIN_LIMIT).find_map(|_| {
+            let guard = KMSG.try_lock();
+            if guard.is_none() {
+                core::hint::spin_loop();
+            }
+            guard
+        })?;
+        Some(f(&mut ring))
+    })
+}
+
+/// フォーマット済みのメッセージをリングバッファに追記する。
+/// kprint! / serial_print! の内部から呼ばれる。
+pub fn append_fmt(args: fmt::Arguments) {
+    use core::fmt::Write;
+    if with_ring(|ring| ring.write_fmt(args).ok()).is_none() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 今リングバッファに残っているログをコピーして返す。
+///
+/// 上書きされた直後は先頭が UTF-8 の途中から始まることがあるので、
+/// 表示する側で不正なバイトを読み飛ばすこと。
+pub fn snapshot() -> Vec<u8> {
+    // ヒープ確保は割り込み禁止の外で済ませておく
+    let mut out = Vec::with_capacity(KMSG_CAPACITY);
+    with_ring(|ring| ring.copy_to(&mut out));
+    out
+}
+
+/// snapshot と同じものに、先頭行の通し番号を付けて返す（/proc/kmsg 用）。
+///
+/// 通し番号は起動してから書かれた行を 0 から数えたもので、
+/// 上書きや dmesg -c で古い行が消えても振り直さない。
+pub fn snapshot_with_seq() -> (u64, Vec<u8>) {
+    let mut out = Vec::with_capacity(KMSG_CAPACITY);
+    let lines = with_ring(|ring| {
+        ring.copy_to(&mut out);
+        ring.lines
+    })
+    .unwrap_or(0);
+    // 改行を数えるのはロックの外で行う
+    let newlines = out.iter().filter(|&&b| b == b'\n').count() as u64;
+    (lines - newlines, out)
+}
+
+/// ログを読み出してから消す（dmesg -c）。
+/// 読み出しと消去を 1 回のロックで行うので、そのあいだに書かれたログを取りこぼさない。
+pub fn drain() -> Vec<u8> {
+    let mut out = Vec::with_capacity(KMSG_CAPACITY);
+    with_ring(|ring| {
+        ring.copy_to(&mut out);
+        ring.cleared = ring.written;
+    });
+    out
+}
+
+/// ロックが取れずに捨てたメッセージの数
+pub fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,249 @@
+// lock_order.rs — ロック順序の検査（debug-locks フィーチャ）
+//
+// netstack や paging には「NET_STATE → NIC」「PAGE_TABLE → FRAME_ALLOCATOR」のような
+// ロック順序がコメントで書かれているが、守られているかは誰も確かめていない。
+// 逆順に取るコードが紛れ込むと、たまたま 2 つのタスクがぶつかったときだけ
+// デッドロックするので、原因を探すのが難しい。
+//
+// OrderedMutex は spin::Mutex の薄いラッパーで、ロックごとに LockClass（順位）を持つ。
+// debug-locks フィーチャを有効にしてビルドすると、CPU ごとに「今持っているロック」を
+// ビットマスクで記録し、自分より順位が後ろのロックを持ったまま前のロックを lock() したら
+// どちらのロックかを書いてパニックする。実際にぶつからなくても、逆順に取った時点で見つかる。
+// フィーチャなしのビルドでは何も記録せず、spin::Mutex と同じ動きになる。
+//
+// - try_lock() は待たないのでデッドロックしない。順序は見ずに、持っていることだけ記録する。
+// - 持っているロックはタスクに属するので、コンテキストスイッチで scheduler が
+//   held() / set_held() を使って退避・復帰する（SAVED_RSP と同じやり方）。
+// - SCHEDULER はコンテキストスイッチ自体が取るロックで、いつも最後に取られるので対象にしない。
+
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "debug-locks")]
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use spin::{Mutex, MutexGuard};
+
+/// 順序を検査するロックの種類。順位（rank）の小さいものから先に取ること。
+///
+/// 値はビットマスクのビット番号を兼ねる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LockClass {
+    /// netstack/dns.rs の DNS_RESOLVER
+    DnsResolver,
+    /// netstack の NET_STATE
+    NetState,
+    /// virtio-net ドライバ
+    VirtioNet,
+    /// e1000e ドライバ（virtio-net と同じ順位。2 つを同時に持たない）
+    E1000e,
+    /// paging.rs の PAGE_TABLE
+    PageTable,
+    /// memory.rs の FRAME_ALLOCATOR
+    FrameAllocator,
+    /// selftest 用（先に取る側）
+    #[cfg(feature = "debug-locks")]
+    TestOuter,
+    /// selftest 用（後に取る側）
+    #[cfg(feature = "debug-locks")]
+    TestInner,
+}
+
+impl LockClass {
+    /// 取る順番。小さいほど先（外側）。
+    #[cfg(feature = "debug-locks")]
+    pub const fn rank(self) -> u8 {
+        match self {
+            LockClass::DnsResolver => 10,
+            LockClass::NetState => 20,
+            LockClass::VirtioNet | LockClass::E1000e => 30,
+            LockClass::PageTable => 40,
+            LockClass::FrameAllocator => 50,
+            #[cfg(feature = "debug-locks")]
+            LockClass::TestOuter => 100,
+            #[cfg(feature = "debug-locks")]
+            LockClass::TestInner => 101,
+        }
+    }
+
+    #[cfg(feature = "debug-locks")]
+    pub const fn name(self) -> &'static str {
+        match self {
+            LockClass::DnsResolver => "DNS_RESOLVER",
+            LockClass::NetState => "NET_STATE",
+            LockClass::VirtioNet => "VIRTIO_NET",
+            LockClass::E1000e => "E1000E",
+            LockClass::PageTable => "PAGE_TABLE",
+            LockClass::FrameAllocator => "FRAME_ALLOCATOR",
+            #[cfg(feature = "debug-locks")]
+            LockClass::TestOuter => "TEST_OUTER",
+            #[cfg(feature = "debug-locks")]
+            LockClass::TestInner => "TEST_INNER",
+        }
+    }
+
+    #[cfg(feature = "debug-locks")]
+    const ALL: &'static [LockClass] = &[
+        LockClass::DnsResolver,
+        LockClass::NetState,
+        LockClass::VirtioNet,
+        LockClass::E1000e,
+        LockClass::PageTable,
+        LockClass::FrameAllocator,
+        LockClass::TestOuter,
+        LockClass::TestInner,
+    ];
+
+    #[cfg(feature = "debug-locks")]
+    const fn bit(self) -> u32 {
+        1 << self as u8
+    }
+}
+
+/// ロック順序を検査する spin::Mutex
+pub struct OrderedMutex<T> {
+    inner: Mutex<T>,
+    #[cfg_attr(not(feature = "debug-locks"), allow(dead_code))]
+    class: LockClass,
+}
+
+impl<T> OrderedMutex<T> {
+    pub const fn new(class: LockClass, value: T) -> Self {
+        Self { inner: Mutex::new(value), class }
+    }
+
+    /// ロックを取る。debug-locks 有効時は、順序に反していればパニックする。
+    pub fn lock(&self) -> OrderedGuard<'_, T> {
+        #[cfg(feature = "debug-locks")]
+        check_order(self.class);
+        let guard = self.inner.lock();
+        #[cfg(feature = "debug-locks")]
+        HELD.fetch_or(self.class.bit(), Ordering::Relaxed);
+        OrderedGuard { guard, #[cfg(feature = "debug-locks")] class: self.class }
+    }
+
+    /// ロックを取れれば取る。待たないので順序は検査しない。
+    pub fn try_lock(&self) -> Option<OrderedGuard<'_, T>> {
+        let guard = self.inner.try_lock()?;
+        #[cfg(feature = "debug-locks")]
+        HELD.fetch_or(self.class.bit(), Ordering::Relaxed);
+        Some(OrderedGuard { guard, #[cfg(feature = "debug-locks")] class: self.class })
+    }
+}
+
+/// OrderedMutex のガード。drop で「持っている」記録を消す。
+pub struct OrderedGuard<'a, T> {
+    guard: MutexGuard<'a, T>,
+    #[cfg(feature = "debug-locks")]
+    class: LockClass,
+}
+
+impl<T> Deref for OrderedGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for OrderedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "debug-locks")]
+impl<T> Drop for OrderedGuard<'_, T> {
+    fn drop(&mut self) {
+        HELD.fetch_and(!self.class.bit(), Ordering::Relaxed);
+    }
+}
+
+/// この CPU で今走っているタスクが持っている OrderedMutex（LockClass のビットマスク）。
+/// SMP はまだないので CPU は 1 つ。
+#[cfg(feature = "debug-locks")]
+static HELD: AtomicU32 = AtomicU32::new(0);
+
+/// false の間は順序違反でパニックせず、LAST_VIOLATION に記録するだけにする（selftest 用）
+#[cfg(feature = "debug-locks")]
+static PANIC_ON_VIOLATION: AtomicBool = AtomicBool::new(true);
+
+/// 最後に見つかった順序違反（取ろうとしたロック, 持っていたロック）
+#[cfg(feature = "debug-locks")]
+static LAST_VIOLATION: Mutex<Option<(LockClass, LockClass)>> = Mutex::new(None);
+
+/// class を lock() してよいか調べる。持っているロックに同じか後ろの順位のものがあれば違反。
+#[cfg(feature = "debug-locks")]
+fn check_order(class: LockClass) {
+    let held = HELD.load(Ordering::Relaxed);
+    let Some(&conflict) = LockClass::ALL
+        .iter()
+        .find(|c| held & c.bit() != 0 && c.rank() >= class.rank())
+    else {
+        return;
+    };
+    if PANIC_ON_VIOLATION.load(Ordering::Relaxed) {
+        panic!(
+            "lock order violation: acquiring {} (rank {}) while holding {} (rank {})",
+            class.name(), class.rank(), conflict.name(), conflict.rank()
+        );
+    }
+    *LAST_VIOLATION.lock() = Some((class, conflict));
+}
+
+/// コンテキストスイッチで退避する「持っているロック」の値。
+/// debug-locks なしでは常に 0。
+pub fn held() -> u32 {
+    #[cfg(feature = "debug-locks")]
+    {
+        HELD.load(Ordering::Relaxed)
+    }
+    #[cfg(not(feature = "debug-locks"))]
+    {
+        0
+    }
+}
+
+/// 切り替え先のタスクが持っていたロックを復帰する。debug-locks なしでは何もしない。
+#[cfg_attr(not(feature = "debug-locks"), allow(unused_variables))]
+pub fn set_held(held: u32) {
+    #[cfg(feature = "debug-locks")]
+    HELD.store(held, Ordering::Relaxed);
+}
+
+/// ロック順序検査のテスト（debug-locks 有効時のみ）
+///
+/// TEST_INNER を持ったまま TEST_OUTER を lock() して違反が見つかることを確かめる。
+/// カーネルのパニックは捕まえられないので、このテストの間だけパニックの代わりに記録させる。
+/// 正しい順序（OUTER → INNER）や try_lock() では違反にならないことも確認する。
+#[cfg(feature = "debug-locks")]
+pub fn test_lock_order() -> bool {
+    static OUTER: OrderedMutex<u32> = OrderedMutex::new(LockClass::TestOuter, 0);
+    static INNER: OrderedMutex<u32> = OrderedMutex::new(LockClass::TestInner, 0);
+
+    *LAST_VIOLATION.lock() = None;
+    PANIC_ON_VIOLATION.store(false, Ordering::Relaxed);
+
+    // 正しい順序
+    {
+        let _outer = OUTER.lock();
+        let _inner = INNER.lock();
+    }
+    let ordered_ok = LAST_VIOLATION.lock().is_none();
+
+    // try_lock は待たないので逆順でもよい
+    {
+        let _inner = INNER.lock();
+        let _outer = OUTER.try_lock();
+    }
+    let try_lock_ok = LAST_VIOLATION.lock().is_none();
+
+    // 逆順
+    {
+        let _inner = INNER.lock();
+        let _outer = OUTER.lock();
+    }
+    let detected = *LAST_VIOLATION.lock() == Some((LockClass::TestOuter, LockClass::TestInner));
+
+    PANIC_ON_VIOLATION.store(true, Ordering::Relaxed);
+    let released = HELD.load(Ordering::Relaxed) & (LockClass::TestOuter.bit() | LockClass::TestInner.bit()) == 0;
+
+    ordered_ok && try_lock_ok && detected && released
+}
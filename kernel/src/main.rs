@@ -11,6 +11,9 @@ mod ahci;
 mod e1000e;
 mod allocator;
 mod apic;
+mod backtrace;
+mod clipboard;
+mod blockdev;
 mod console;
 mod elf;
 mod fat32;
@@ -20,6 +23,9 @@ mod gdt;
 mod handle;
 mod interrupts;
 mod ipc;
+mod klog;
+mod kmsg;
+mod lock_order;
 mod memory;
 mod mouse;
 mod nvme;
@@ -31,9 +37,15 @@ mod rtc;
 mod scheduler;
 mod serial;
 mod slab_allocator;
+mod socket;
 mod pci;
+mod percpu;
 mod qemu;
+mod random;
 mod shell;
+mod smp;
+mod symbols;
+mod timer;
 mod syscall;
 mod net_config;
 mod netstack;
@@ -87,6 +99,8 @@ fn main() -> Status {
     // フレームバッファの物理アドレス自体は有効なまま残る。
     // 今のうちにアドレス・サイズ・解像度・ピクセルフォーマットを控えておく。
     let fb_info = FramebufferInfo::from_gop(&mut gop);
+    // 対応している画面モードの一覧も控えておく（Exit 後はモードを切り替えられないので、見せるだけ）
+    framebuffer::record_gop_modes(&gop);
 
     uefi::system::with_stdout(|stdout| {
         let _ = write!(stdout, "FB saved: {:#x}\r\n", fb_info.fb_addr);
@@ -170,6 +184,11 @@ fn main() -> Status {
     // --- GDT (Global Descriptor Table) の初期化 ---
     gdt::init();
 
+    // --- CPU ごとのデータ（BSP = CPU 0）---
+    // GS ベースを CPU 0 のブロックに向ける。scheduler と TSS rsp0 の更新が使うので、
+    // スケジューラやユーザープロセスより先に済ませる。
+    percpu::init(0, gdt::bsp_tss());
+
     // --- IDT + PIC の初期化 ---
     // CPU 例外ハンドラと、ハードウェア割り込み（タイマー、キーボード）のハンドラを登録。
     // PIC を初期化して IRQ 0〜15 を IDT の 32〜47 番にリマップする。
@@ -197,6 +216,10 @@ fn main() -> Status {
         kprintln!("Mouse not available.");
     }
 
+    // --- シリアル入力の有効化 ---
+    // COM1 の受信割り込み (IRQ4) を有効にして、シリアル端末からもシェルを操作できるようにする。
+    serial::init_input();
+
     // タイトルを黄色で表示
     framebuffer::set_global_colors((255, 255, 0), (0, 0, 128));
     kprintln!("=== SABOS ===");
@@ -286,8 +309,19 @@ fn main() -> Status {
     // PIC から APIC に移行し、タイマー・キーボード・マウスの割り込みを APIC 経由にする。
     // ACPI 情報がない場合は PIC のまま動作する。
     apic::init();
+
+    // --- AP (Application Processor) の起動 ---
+    // MADT に載っている他のコアを INIT-SIPI-SIPI で起こし、アイドルループで待たせる。
+    // Local APIC が必要なので apic::init() の後、タスクを spawn する前に呼ぶ。
+    smp::init(&memory_map);
     kprintln!();
 
+    // --- PCI BAR のキャッシュ ---
+    // BAR のサイズを測るには BAR を一時的に書き換える必要がある。
+    // ドライバがデバイスを動かし始める前に全デバイス分を測っておき、
+    // SYS_PCI_READ_BAR はこのキャッシュから答える。
+    pci::cache_bars();
+
     // --- virtio-blk ドライバの初期化 ---
     // PCI バスから virtio-blk デバイスを探して初期化する。
     // ヒープアロケータとページング初期化の後に呼ぶ必要がある
@@ -350,6 +384,11 @@ fn main() -> Status {
     framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
     kprintln!();
 
+    // --- ブロックデバイスのレジストリ ---
+    // virtio-blk / AHCI / NVMe で見つかったディスクに通し番号を振る。
+    // SYS_BLOCK_READ / WRITE と VFS のマウントはこの番号でディスクを指定する。
+    blockdev::register_all();
+
     // --- e1000e NIC の初期化 ---
     // PCI バスから Intel e1000e NIC を探して初期化する。
     // QEMU の `-device e1000e` で追加されたデバイスを検出する。
@@ -405,6 +444,10 @@ fn main() -> Status {
     // net_poller がパケットを処理した後に waiter を起床させる仕組み。
     // これにより httpd と telnetd が同時に tcp_accept を呼んでも競合しない。
     scheduler::spawn("net_poller", netstack::net_poller_task);
+    // net_poller がロック待ちなどで止まったら警告し、NIC のリングを作り直すウォッチドッグ
+    scheduler::spawn("net_watchdog", netstack::net_watchdog_task);
+    // SYS_TIMER_CREATE のタイマーの期限を見て、IPC メッセージを送るタスク
+    scheduler::spawn("timer", timer::timer_task);
 
     // --- virtio-9p ドライバの初期化 ---
     // PCI バスから virtio-9p デバイスを探して初期化する。
@@ -507,8 +550,10 @@ fn main() -> Status {
     shell.print_prompt();
 
     // --- メインループ ---
-    // キーボード割り込みで KEY_QUEUE にプッシュされた文字を読み取り、
+    // キーボード / シリアル割り込みで KEY_QUEUE にプッシュされた文字を読み取り、
     // シェルに渡す。キーがなければ hlt で CPU を省電力モードにして待つ。
+    // シリアルは受信割り込みが届かない環境もあるので、毎回 FIFO も覗いておく
+    // （タイマー割り込みで hlt から起きるので、最悪でもティック単位の遅れで済む）。
     //
     // enable_and_hlt() は sti と hlt をアトミックに実行する。
     // これにより「キューチェック → hlt の間に割り込みが来て取りこぼす」
@@ -516,6 +561,7 @@ fn main() -> Status {
     loop {
         // 割り込みを無効化してキューをチェック
         x86_64::instructions::interrupts::disable();
+        serial::poll_input();
 
         if let Some(c) = interrupts::get_key() {
             // キーがあった場合は割り込みを再有効化してから処理
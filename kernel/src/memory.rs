@@ -27,10 +27,11 @@
 use alloc::vec;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
-use spin::Mutex;
 use x86_64::structures::paging::{FrameAllocator, PhysFrame, Size4KiB};
 use x86_64::PhysAddr;
 
+use crate::lock_order::{LockClass, OrderedMutex};
+
 /// UEFI メモリマップの CONVENTIONAL 領域を表す構造体。
 /// UEFI の MemoryDescriptor から必要な情報だけを抽出して保持する。
 /// start は物理アドレス、page_count は 4KiB ページ数。
@@ -603,8 +604,8 @@ lazy_static! {
     /// グローバルフレームアロケータ。
     /// ページテーブル操作時にフレームを確保するために使う。
     /// ロック順序: PAGE_TABLE → FRAME_ALLOCATOR（デッドロック防止のため必ず守ること）
-    pub static ref FRAME_ALLOCATOR: Mutex<BuddyFrameAllocator> =
-        Mutex::new(BuddyFrameAllocator::new());
+    pub static ref FRAME_ALLOCATOR: OrderedMutex<BuddyFrameAllocator> =
+        OrderedMutex::new(LockClass::FrameAllocator, BuddyFrameAllocator::new());
 }
 
 /// フレームアロケータを初期化する。
@@ -1,11 +1,16 @@
 // mouse.rs — PS/2 マウスドライバ（最小実装）
 //
 // - PS/2 コントローラ (i8042) を初期化してマウスを有効化
-// - IRQ12 の割り込みで 3 バイトパケットを受信
+// - IntelliMouse 拡張（スクロールホイール、4/5 ボタン）を有効化
+// - IRQ12 の割り込みで 3 バイト（拡張時は 4 バイト）パケットを受信
 // - カーソル位置とボタン状態を保持
+// - 位置が変わったら framebuffer::move_cursor でカーネル描画のカーソルを動かす
 //
-// 今は「最小で動く」実装に寄せる。
-// 高機能（ホイール/多ボタン等）は将来拡張。
+// IntelliMouse 拡張は「サンプルレートを決まった順に設定する」という
+// 隠しコマンド（ノック）で有効になり、デバイス ID が変わることで確認できる:
+//   200, 100, 80 → ID 3（ホイール付き、4 バイトパケット）
+//   続けて 200, 200, 80 → ID 4（ホイール + 4/5 ボタン）
+// 対応していないマウスは ID 0 のまま 3 バイトパケットを送り続ける。
 
 use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
@@ -25,10 +30,23 @@ const CMD_WRITE_MOUSE: u8 = 0xD4;
 // PS/2 マウスコマンド
 const MOUSE_SET_DEFAULTS: u8 = 0xF6;
 const MOUSE_ENABLE_STREAMING: u8 = 0xF4;
+const MOUSE_SET_SAMPLE_RATE: u8 = 0xF3;
+const MOUSE_GET_ID: u8 = 0xF2;
+
+/// デバイス ID: 標準の PS/2 マウス（3 バイトパケット）
+pub const DEVICE_ID_STANDARD: u8 = 0;
+/// デバイス ID: IntelliMouse（ホイール付き、4 バイトパケット）
+pub const DEVICE_ID_WHEEL: u8 = 3;
+/// デバイス ID: IntelliMouse Explorer（ホイール + 4/5 ボタン、4 バイトパケット）
+pub const DEVICE_ID_FIVE_BUTTON: u8 = 4;
 
 // ACK
 const MOUSE_ACK: u8 = 0xFA;
 
+/// SYS_MOUSE_READ でユーザー空間に渡すマウス状態
+///
+/// scroll と device_id は以前の _pad の位置に入れたので、構造体のサイズ（20 バイト）は
+/// 変わらない。古いプログラムも同じバッファで読める。
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct MouseState {
@@ -36,8 +54,13 @@ pub struct MouseState {
     pub y: i32,
     pub dx: i32,
     pub dy: i32,
+    /// ボタン: bit0 = 左, bit1 = 右, bit2 = 中, bit3 = 4 番目, bit4 = 5 番目
     pub buttons: u8,
-    pub _pad: [u8; 3],
+    /// 前回の読み取りからのホイールの回転量（正 = 奥へ回した / 上スクロール）
+    pub scroll: i8,
+    /// マウスのデバイス ID（0 = 標準, 3 = ホイール付き, 4 = ホイール + 5 ボタン）
+    pub device_id: u8,
+    pub _pad: [u8; 1],
 }
 
 impl MouseState {
@@ -48,7 +71,9 @@ impl MouseState {
             dx: 0,
             dy: 0,
             buttons: 0,
-            _pad: [0; 3],
+            scroll: 0,
+            device_id: DEVICE_ID_STANDARD,
+            _pad: [0; 1],
         }
     }
 }
@@ -58,8 +83,10 @@ struct MouseInner {
     updated: bool,
     screen_w: i32,
     screen_h: i32,
-    packet: [u8; 3],
+    packet: [u8; 4],
     packet_index: usize,
+    /// 1 パケットのバイト数（標準は 3、IntelliMouse 拡張を有効にしたら 4）
+    packet_size: usize,
 }
 
 lazy_static! {
@@ -68,8 +95,9 @@ lazy_static! {
         updated: false,
         screen_w: 1,
         screen_h: 1,
-        packet: [0; 3],
+        packet: [0; 4],
         packet_index: 0,
+        packet_size: 3,
     });
 }
 
@@ -96,6 +124,14 @@ pub fn init() -> bool {
     if !write_mouse_and_ack(MOUSE_SET_DEFAULTS) {
         return false;
     }
+    // ストリーミング開始前にホイールなどの拡張を有効にする
+    // （開始後だとコマンドの ACK と移動パケットが混ざるため）
+    let device_id = enable_extensions();
+    {
+        let mut inner = MOUSE.lock();
+        inner.state.device_id = device_id;
+        inner.packet_size = if device_id == DEVICE_ID_STANDARD { 3 } else { 4 };
+    }
     if !write_mouse_and_ack(MOUSE_ENABLE_STREAMING) {
         return false;
     }
@@ -114,6 +150,67 @@ pub fn is_initialized() -> bool {
     MOUSE_INITIALIZED.load(Ordering::Relaxed)
 }
 
+/// 初期化時に検出したデバイス ID（DEVICE_ID_*）
+pub fn device_id() -> u8 {
+    x86_64::instructions::interrupts::without_interrupts(|| MOUSE.lock().state.device_id)
+}
+
+/// IntelliMouse 拡張を有効にして、最終的なデバイス ID を返す。
+///
+/// まずホイール（ID 3）のノックを送り、成功したら 4/5 ボタン（ID 4）のノックも試す。
+/// どちらにも対応していないマウスは ID 0 を返す。
+fn enable_extensions() -> u8 {
+    let mut id = DEVICE_ID_STANDARD;
+    if knock(&[200, 100, 80]) {
+        id = get_device_id().unwrap_or(DEVICE_ID_STANDARD);
+    }
+    if id == DEVICE_ID_WHEEL && knock(&[200, 200, 80]) {
+        id = get_device_id().unwrap_or(id);
+    }
+    // ノックで変わったサンプルレートを既定の 100 に戻す
+    knock(&[100]);
+    id
+}
+
+/// サンプルレートを順に設定する（IntelliMouse のノック）
+fn knock(rates: &[u8]) -> bool {
+    rates
+        .iter()
+        .all(|&rate| write_mouse_and_ack(MOUSE_SET_SAMPLE_RATE) && write_mouse_and_ack(rate))
+}
+
+/// デバイス ID を問い合わせる（ACK の後に ID が 1 バイト返ってくる）
+fn get_device_id() -> Option<u8> {
+    if !write_mouse_and_ack(MOUSE_GET_ID) {
+        return None;
+    }
+    Some(read_data())
+}
+
+/// 1 パケット分のバイト列を (dx, dy, buttons, scroll) に変換する。
+///
+/// dy は画面座標に合わせて下が正、scroll は奥へ回したときが正。
+/// - 標準（3 バイト）: ホイールなし、ボタンは 3 つ
+/// - ID 3（4 バイト）: 4 バイト目がホイールの移動量（符号付き 8bit、手前が正）
+/// - ID 4（4 バイト）: 4 バイト目の下位 4bit がホイール（符号付き 4bit）、
+///   bit4 / bit5 が 4 / 5 番目のボタン
+pub fn decode_packet(packet: &[u8], device_id: u8) -> (i32, i32, u8, i8) {
+    let dx = (packet[1] as i8) as i32;
+    // y は上が正なので、画面座標 (下が +) に合わせて反転
+    let dy = -((packet[2] as i8) as i32);
+    let mut buttons = packet[0] & 0x07;
+    let z = match device_id {
+        DEVICE_ID_WHEEL => packet[3] as i8,
+        DEVICE_ID_FIVE_BUTTON => {
+            buttons |= (packet[3] >> 1) & 0x18;
+            // 下位 4bit を符号拡張する
+            ((packet[3] << 4) as i8) >> 4
+        }
+        _ => 0,
+    };
+    (dx, dy, buttons, z.saturating_neg())
+}
+
 /// 画面サイズを設定する（カーソルのクランプ用）
 pub fn set_screen_size(w: i32, h: i32) {
     let mut inner = MOUSE.lock();
@@ -122,6 +219,9 @@ pub fn set_screen_size(w: i32, h: i32) {
     inner.state.x = inner.screen_w / 2;
     inner.state.y = inner.screen_h / 2;
     inner.updated = true;
+    let (x, y) = (inner.state.x, inner.state.y);
+    drop(inner);
+    crate::framebuffer::move_cursor(x as usize, y as usize);
 }
 
 /// IRQ12 ハンドラから呼ぶ。受信バイトを処理する。
@@ -137,24 +237,18 @@ pub fn handle_irq_byte(byte: u8) {
     inner.packet[idx] = byte;
     inner.packet_index = idx + 1;
 
-    if inner.packet_index < 3 {
+    if inner.packet_index < inner.packet_size {
         return;
     }
 
     inner.packet_index = 0;
-    let b0 = inner.packet[0];
-    let b1 = inner.packet[1];
-    let b2 = inner.packet[2];
-
-    let dx = (b1 as i8) as i32;
-    let dy = (b2 as i8) as i32;
-
-    // y は上が正なので、画面座標 (下が +) に合わせて反転
-    let dy = -dy;
+    let (dx, dy, buttons, scroll) = decode_packet(&inner.packet, inner.state.device_id);
 
     inner.state.dx = dx;
     inner.state.dy = dy;
-    inner.state.buttons = b0 & 0x07;
+    inner.state.buttons = buttons;
+    // ホイールは読み取られるまで積算する（読む前に何ノッチか回されても取りこぼさない）
+    inner.state.scroll = inner.state.scroll.saturating_add(scroll);
 
     // 座標更新（画面内にクランプ）
     let mut x = inner.state.x + dx;
@@ -165,6 +259,10 @@ pub fn handle_irq_byte(byte: u8) {
     inner.state.y = y;
 
     inner.updated = true;
+
+    // MOUSE のロックを放してからカーソルを描き直す
+    drop(inner);
+    crate::framebuffer::move_cursor(x as usize, y as usize);
 }
 
 /// マウス状態を取得する。
@@ -179,6 +277,7 @@ pub fn read_state() -> Option<MouseState> {
         let state = inner.state;
         inner.state.dx = 0;
         inner.state.dy = 0;
+        inner.state.scroll = 0;
         Some(state)
     })
 }
@@ -2,12 +2,66 @@
 //
 // DHCP で取得した IP / ゲートウェイ / DNS / サブネットマスクを保持する。
 // デフォルト値は QEMU SLIRP のデフォルト（10.0.2.15 等）。
+// set_static_config()（ifconfig コマンド / SYS_NET_SET_CONFIG）で静的に設定すると
+// DHCP を止め、以降 dhcp_discover() で上書きされないようにする。
+// IPv6 アドレスは SLAAC（Router Advertisement）で設定される。
 //
 // 以前は `pub const` だったが、DHCP クライアントから実行時に変更できるよう
 // `static Mutex<NetConfig>` に変更した。
+//
+// ## ルーティングテーブル
+//
+// 直結サブネット（my_ip & subnet_mask）とデフォルトルート（gateway_ip）は
+// DHCP の設定から毎回組み立てるので、テーブルには `route add` で足した静的ルートだけを持つ。
+// こうしておくと DHCP で IP が変わってもルートの付け替え漏れが起きない。
+// 宛先の検索は最長一致（プレフィックス長が長いほど優先）。
 
+use alloc::string::String;
+use alloc::vec::Vec;
 use spin::Mutex;
 
+/// ルートを出すインターフェース名（NIC は 1 つしか使わないので固定）
+pub const INTERFACE_NAME: &str = "eth0";
+
+/// ルートの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteKind {
+    /// 自分のサブネット（my_ip / subnet_mask から自動生成）
+    Connected,
+    /// デフォルトルート（gateway_ip から自動生成）
+    Default,
+    /// `route add` で追加した静的ルート
+    Static,
+}
+
+/// ルーティングテーブルの 1 エントリ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    /// 宛先ネットワーク（ホスト部は 0 に正規化済み）
+    pub dest: [u8; 4],
+    /// プレフィックス長（0〜32）
+    pub prefix_len: u8,
+    /// 次ホップ。None なら直結（宛先に直接 ARP する）
+    pub gateway: Option<[u8; 4]>,
+    pub kind: RouteKind,
+}
+
+impl Route {
+    /// ip がこのルートの宛先ネットワークに含まれるか
+    fn matches(&self, ip: &[u8; 4]) -> bool {
+        u32::from_be_bytes(*ip) & prefix_mask(self.prefix_len) == u32::from_be_bytes(self.dest)
+    }
+}
+
+/// プレフィックス長からネットマスク（ホストバイトオーダーの u32）を作る
+fn prefix_mask(prefix_len: u8) -> u32 {
+    if prefix_len == 0 {
+        0
+    } else {
+        u32::MAX << (32 - prefix_len as u32)
+    }
+}
+
 /// ネットワーク設定を保持する構造体
 pub struct NetConfig {
     /// ゲストの IP アドレス
@@ -18,6 +72,16 @@ pub struct NetConfig {
     pub dns_server_ip: [u8; 4],
     /// サブネットマスク
     pub subnet_mask: [u8; 4],
+    /// ゲストの IPv6 アドレス。SLAAC で設定され、RA が来なければデフォルトのまま。
+    pub my_ipv6: [u8; 16],
+    /// IPv6 デフォルトルーター（Router Advertisement の送信元）。RA 受信までは None。
+    pub ipv6_router: Option<[u8; 16]>,
+    /// SLAAC でアドレスが設定済みかどうか
+    pub ipv6_slaac: bool,
+    /// `route add` で追加した静的ルート
+    pub static_routes: Vec<Route>,
+    /// DHCP で設定するかどうか。静的に設定したら false になる。
+    pub dhcp_enabled: bool,
 }
 
 /// グローバルネットワーク設定（Mutex で保護）
@@ -28,6 +92,11 @@ static NET_CONFIG: Mutex<NetConfig> = Mutex::new(NetConfig {
     gateway_ip: [10, 0, 2, 2],
     dns_server_ip: [10, 0, 2, 3],
     subnet_mask: [255, 255, 255, 0],
+    my_ipv6: crate::netstack::MY_IPV6,
+    ipv6_router: None,
+    ipv6_slaac: false,
+    static_routes: Vec::new(),
+    dhcp_enabled: true,
 });
 
 /// 自分の IP アドレスを取得する
@@ -51,15 +120,190 @@ pub fn get_subnet_mask() -> [u8; 4] {
 }
 
 /// ネットワーク設定を一括更新する（DHCP 取得時に呼ばれる）
+///
+/// 設定したアドレスは Gratuitous ARP でネットワークに知らせる。
 pub fn set_config(
     my_ip: [u8; 4],
     gateway_ip: [u8; 4],
     dns_server_ip: [u8; 4],
     subnet_mask: [u8; 4],
 ) {
+    {
+        let mut config = NET_CONFIG.lock();
+        config.my_ip = my_ip;
+        config.gateway_ip = gateway_ip;
+        config.dns_server_ip = dns_server_ip;
+        config.subnet_mask = subnet_mask;
+    }
+    crate::netstack::announce_address(my_ip);
+}
+
+/// 静的な IP 設定にする（ifconfig コマンド / SYS_NET_SET_CONFIG から呼ばれる）
+///
+/// 設定したら DHCP を止めるので、dhcp_discover() で上書きされなくなる。
+/// 設定したアドレスは Gratuitous ARP でネットワークに知らせる。
+/// 自分のアドレスに 0.0.0.0 やループバック（127.0.0.0/8）は使えない。
+/// サブネットマスクは上位ビットから 1 が連続している必要がある。
+/// gateway が 0.0.0.0 ならデフォルトルートなし（直結サブネットだけ）になる。
+pub fn set_static_config(
+    my_ip: [u8; 4],
+    subnet_mask: [u8; 4],
+    gateway_ip: [u8; 4],
+    dns_server_ip: [u8; 4],
+) -> Result<(), &'static str> {
+    validate_static_config(my_ip, subnet_mask, gateway_ip)?;
+    {
+        let mut config = NET_CONFIG.lock();
+        config.my_ip = my_ip;
+        config.subnet_mask = subnet_mask;
+        config.gateway_ip = gateway_ip;
+        config.dns_server_ip = dns_server_ip;
+        config.dhcp_enabled = false;
+    }
+    crate::netstack::announce_address(my_ip);
+    Ok(())
+}
+
+/// set_static_config() に渡された値を検証する
+fn validate_static_config(my_ip: [u8; 4], subnet_mask: [u8; 4], gateway_ip: [u8; 4]) -> Result<(), &'static str> {
+    if my_ip == [0, 0, 0, 0] {
+        return Err("address must not be 0.0.0.0");
+    }
+    if my_ip[0] == 127 {
+        return Err("address must not be a loopback address");
+    }
+    if my_ip == [255, 255, 255, 255] || (224..=239).contains(&my_ip[0]) {
+        return Err("address must be a unicast address");
+    }
+    let mask = u32::from_be_bytes(subnet_mask);
+    if mask.leading_ones() + mask.trailing_zeros() != 32 {
+        return Err("invalid subnet mask");
+    }
+    if gateway_ip[0] == 127 {
+        return Err("gateway must not be a loopback address");
+    }
+    Ok(())
+}
+
+/// DHCP で設定するモードかどうか（静的に設定したら false）
+pub fn is_dhcp_enabled() -> bool {
+    NET_CONFIG.lock().dhcp_enabled
+}
+
+/// DHCP を使うかどうかを切り替える（ifconfig dhcp で DHCP に戻すときに使う）
+pub fn set_dhcp_enabled(enabled: bool) {
+    NET_CONFIG.lock().dhcp_enabled = enabled;
+}
+
+/// 自分の IPv6 アドレスを取得する
+pub fn get_my_ipv6() -> [u8; 16] {
+    NET_CONFIG.lock().my_ipv6
+}
+
+/// IPv6 デフォルトルーターを取得する（RA 未受信なら None）
+pub fn get_ipv6_router() -> Option<[u8; 16]> {
+    NET_CONFIG.lock().ipv6_router
+}
+
+/// SLAAC でアドレスが設定済みかどうか
+pub fn is_ipv6_slaac_configured() -> bool {
+    NET_CONFIG.lock().ipv6_slaac
+}
+
+/// SLAAC で得た IPv6 アドレスとルーターを設定する（RA 受信時に呼ばれる）
+pub fn set_ipv6_slaac(my_ipv6: [u8; 16], router: [u8; 16]) {
     let mut config = NET_CONFIG.lock();
-    config.my_ip = my_ip;
-    config.gateway_ip = gateway_ip;
-    config.dns_server_ip = dns_server_ip;
-    config.subnet_mask = subnet_mask;
+    config.my_ipv6 = my_ipv6;
+    config.ipv6_router = Some(router);
+    config.ipv6_slaac = true;
+}
+
+// ============================================================
+// ルーティングテーブル
+// ============================================================
+
+/// ルーティングテーブルの全エントリを返す（直結 → デフォルト → 静的ルートの順）
+pub fn routes() -> Vec<Route> {
+    let config = NET_CONFIG.lock();
+    let mut list = Vec::with_capacity(config.static_routes.len() + 2);
+    let mask = u32::from_be_bytes(config.subnet_mask);
+    list.push(Route {
+        dest: (u32::from_be_bytes(config.my_ip) & mask).to_be_bytes(),
+        prefix_len: mask.leading_ones() as u8,
+        gateway: None,
+        kind: RouteKind::Connected,
+    });
+    if config.gateway_ip != [0, 0, 0, 0] {
+        list.push(Route {
+            dest: [0, 0, 0, 0],
+            prefix_len: 0,
+            gateway: Some(config.gateway_ip),
+            kind: RouteKind::Default,
+        });
+    }
+    list.extend_from_slice(&config.static_routes);
+    list
+}
+
+/// 静的ルートを追加する
+///
+/// dest のホスト部は 0 に揃えてから登録する（`10.1.2.3/16` → `10.1.0.0/16`）。
+/// 同じ宛先・プレフィックス長の静的ルートが既にあればエラー。
+pub fn add_route(dest: [u8; 4], prefix_len: u8, gateway: Option<[u8; 4]>) -> Result<(), &'static str> {
+    if prefix_len > 32 {
+        return Err("invalid prefix length");
+    }
+    let dest = (u32::from_be_bytes(dest) & prefix_mask(prefix_len)).to_be_bytes();
+    let mut config = NET_CONFIG.lock();
+    if config.static_routes.iter().any(|r| r.dest == dest && r.prefix_len == prefix_len) {
+        return Err("route already exists");
+    }
+    config.static_routes.push(Route { dest, prefix_len, gateway, kind: RouteKind::Static });
+    Ok(())
+}
+
+/// 静的ルートを削除する。削除したら true。
+///
+/// 直結ルートとデフォルトルートは DHCP の設定から作られるので消せない。
+pub fn del_route(dest: [u8; 4], prefix_len: u8) -> bool {
+    if prefix_len > 32 {
+        return false;
+    }
+    let dest = (u32::from_be_bytes(dest) & prefix_mask(prefix_len)).to_be_bytes();
+    let mut config = NET_CONFIG.lock();
+    let before = config.static_routes.len();
+    config.static_routes.retain(|r| !(r.dest == dest && r.prefix_len == prefix_len));
+    config.static_routes.len() != before
+}
+
+/// 宛先 IP へ送るときの次ホップ IP を返す（最長一致）
+///
+/// 直結ルートに当たれば宛先そのもの、ゲートウェイ経由ならゲートウェイの IP。
+/// どのルートにも当たらなければ None（デフォルトルートがない場合のみ）。
+pub fn route_lookup(dst: &[u8; 4]) -> Option<[u8; 4]> {
+    routes()
+        .iter()
+        .filter(|r| r.matches(dst))
+        .max_by_key(|r| r.prefix_len)
+        .map(|r| r.gateway.unwrap_or(*dst))
+}
+
+/// ルートを `route` コマンドの 1 行分の文字列にする
+///
+/// 例: `10.0.2.0/24        on-link          eth0   connected`
+pub fn format_route(route: &Route) -> String {
+    let dest = alloc::format!(
+        "{}.{}.{}.{}/{}",
+        route.dest[0], route.dest[1], route.dest[2], route.dest[3], route.prefix_len
+    );
+    let gateway = match route.gateway {
+        Some(gw) => alloc::format!("{}.{}.{}.{}", gw[0], gw[1], gw[2], gw[3]),
+        None => String::from("on-link"),
+    };
+    let kind = match route.kind {
+        RouteKind::Connected => "connected",
+        RouteKind::Default => "default",
+        RouteKind::Static => "static",
+    };
+    alloc::format!("{:<18} {:<16} {:<6} {}", dest, gateway, INTERFACE_NAME, kind)
 }
@@ -4,8 +4,7 @@
 
 use alloc::vec::Vec;
 
-use crate::net_config::{get_my_ip, get_gateway_ip, get_subnet_mask};
-use crate::serial_println;
+use crate::net_config::{get_my_ip, route_lookup};
 
 use super::{
     BROADCAST_MAC, ETHERTYPE_ARP, ETHERTYPE_IPV4,
@@ -18,6 +17,7 @@ use super::types::{EthernetHeader, ArpPacket};
 ///
 /// ARP Request: 自分宛なら Reply を返す。送信元をキャッシュに学習する。
 /// ARP Reply: 送信元をキャッシュに学習する（ARP Request の応答）。
+/// 他のホストが自分の IP を名乗っていたら（アドレスの衝突）警告を出す。
 pub(super) fn handle_arp(_eth_header: &EthernetHeader, payload: &[u8]) {
     if payload.len() < 28 {
         return;
@@ -25,15 +25,24 @@ pub(super) fn handle_arp(_eth_header: &EthernetHeader, payload: &[u8]) {
 
     let arp = unsafe { &*(payload.as_ptr() as *const ArpPacket) };
 
-    // すべての ARP パケットから送信元 IP/MAC を学習する
-    // （Gratuitous ARP にも対応）
-    arp_update(arp.spa, arp.sha);
+    if arp.spa == get_my_ip() && arp.sha != get_my_mac() {
+        // 自分の Gratuitous ARP や Probe への応答、または相手の Announcement。
+        // 自分のアドレスを他人の MAC で学習しないよう、キャッシュには入れない。
+        crate::klog!(Warn, Net, "arp: address conflict: {}.{}.{}.{} is also used by {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            arp.spa[0], arp.spa[1], arp.spa[2], arp.spa[3],
+            arp.sha[0], arp.sha[1], arp.sha[2], arp.sha[3], arp.sha[4], arp.sha[5]
+        );
+    } else if arp.spa != [0, 0, 0, 0] {
+        // すべての ARP パケットから送信元 IP/MAC を学習する
+        // （Gratuitous ARP にも対応。送信元 0.0.0.0 の ARP Probe は学習しない）
+        arp_update(arp.spa, arp.sha);
+    }
 
     match arp.oper_u16() {
         ARP_OP_REQUEST => {
             // ARP Request で、宛先 IP が自分の場合は Reply を返す
             if arp.tpa == get_my_ip() {
-                serial_println!("[net] net: ARP Request for {}.{}.{}.{} from {}.{}.{}.{}",
+                net_trace!("net: ARP Request for {}.{}.{}.{} from {}.{}.{}.{}",
                     arp.tpa[0], arp.tpa[1], arp.tpa[2], arp.tpa[3],
                     arp.spa[0], arp.spa[1], arp.spa[2], arp.spa[3]
                 );
@@ -42,7 +51,7 @@ pub(super) fn handle_arp(_eth_header: &EthernetHeader, payload: &[u8]) {
         }
         ARP_OP_REPLY => {
             // ARP Reply を受信（arp_update は上で済み）
-            serial_println!("[net] net: ARP Reply: {}.{}.{}.{} is {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            net_trace!("net: ARP Reply: {}.{}.{}.{} is {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
                 arp.spa[0], arp.spa[1], arp.spa[2], arp.spa[3],
                 arp.sha[0], arp.sha[1], arp.sha[2], arp.sha[3], arp.sha[4], arp.sha[5]
             );
@@ -86,17 +95,68 @@ fn send_arp_reply(request: &ArpPacket) {
 
     // 送信
     if send_frame(&packet).is_err() {
-        serial_println!("[net] net: failed to send ARP Reply");
+        net_debug!("net: failed to send ARP Reply");
     } else {
-        serial_println!("[net] net: sent ARP Reply");
+        net_trace!("net: sent ARP Reply");
     }
 }
 
 /// ARP Request を送信する
 ///
 /// 指定した IP アドレスの MAC アドレスを問い合わせる。
-/// 宛先 MAC = ブロードキャスト、ターゲット MAC = 00:00:00:00:00:00（不明）。
 fn send_arp_request(target_ip: [u8; 4]) {
+    let packet = build_arp_request(target_ip);
+    if send_frame(&packet).is_err() {
+        net_debug!("net: failed to send ARP Request");
+    } else {
+        net_trace!("net: sent ARP Request for {}.{}.{}.{}",
+            target_ip[0], target_ip[1], target_ip[2], target_ip[3]
+        );
+    }
+}
+
+/// ARP Request のフレーム（Ethernet ヘッダー込み 42 バイト）を組み立てる
+///
+/// 宛先 MAC = ブロードキャスト、ターゲット MAC = 00:00:00:00:00:00（不明）。
+/// 送信元 IP はその時点の net_config の自分のアドレス。
+pub fn build_arp_request(target_ip: [u8; 4]) -> Vec<u8> {
+    build_arp_request_from(get_my_ip(), target_ip)
+}
+
+/// 自分のアドレスを知らせる Gratuitous ARP（ARP Announcement）のフレームを組み立てる
+///
+/// 送信元 IP もターゲット IP も自分のアドレスにした ARP Request。
+/// 受け取ったホスト（ゲートウェイ等）は ARP キャッシュを新しい MAC で更新する。
+fn build_gratuitous_arp(ip: [u8; 4]) -> Vec<u8> {
+    build_arp_request_from(ip, ip)
+}
+
+/// ARP Probe（RFC 5227）のフレームを組み立てる
+///
+/// 送信元 IP を 0.0.0.0 にして、これから使うアドレスを問い合わせる。
+/// 誰かが応答したらそのアドレスは使われている（handle_arp が衝突として警告する）。
+fn build_arp_probe(ip: [u8; 4]) -> Vec<u8> {
+    build_arp_request_from([0, 0, 0, 0], ip)
+}
+
+/// 自分の IP アドレスが決まったことをネットワークに知らせる
+///
+/// DHCP や静的設定でアドレスを設定したときに net_config から呼ばれる。
+/// ARP Probe で衝突がないか問い合わせ、続けて Gratuitous ARP を送って
+/// ゲートウェイ等の古い ARP キャッシュを更新させる。
+/// 応答は待たない（衝突していれば、届いた応答を handle_arp が警告する）。
+pub fn announce_address(ip: [u8; 4]) {
+    for frame in [build_arp_probe(ip), build_gratuitous_arp(ip)] {
+        if send_frame(&frame).is_err() {
+            // NIC がまだない（初期化前）等。次に設定したときにまた送る
+            return;
+        }
+    }
+    net_debug!("arp: announced {}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]);
+}
+
+/// 送信元 IP とターゲット IP を指定して ARP Request のフレームを組み立てる
+fn build_arp_request_from(sender_ip: [u8; 4], target_ip: [u8; 4]) -> Vec<u8> {
     let my_mac = get_my_mac();
 
     let eth_header = EthernetHeader {
@@ -112,7 +172,7 @@ fn send_arp_request(target_ip: [u8; 4]) {
         plen: 4,
         oper: ARP_OP_REQUEST.to_be_bytes(),
         sha: my_mac,
-        spa: get_my_ip(),
+        spa: sender_ip,
         tha: [0; 6], // 不明（これから問い合わせる）
         tpa: target_ip,
     };
@@ -124,20 +184,13 @@ fn send_arp_request(target_ip: [u8; 4]) {
     packet.extend_from_slice(unsafe {
         core::slice::from_raw_parts(&arp_request as *const _ as *const u8, 28)
     });
-
-    if send_frame(&packet).is_err() {
-        serial_println!("[net] net: failed to send ARP Request");
-    } else {
-        serial_println!("[net] net: sent ARP Request for {}.{}.{}.{}",
-            target_ip[0], target_ip[1], target_ip[2], target_ip[3]
-        );
-    }
+    packet
 }
 
 /// 宛先 IP アドレスに対応する MAC アドレスを解決する
 ///
 /// 1. ブロードキャスト IP → ブロードキャスト MAC
-/// 2. サブネット外 → ゲートウェイの MAC を解決対象にする
+/// 2. ルーティングテーブルで次ホップを決める（直結なら宛先、それ以外はゲートウェイ）
 /// 3. ARP キャッシュを検索 → ヒットすれば返す
 /// 4. ミスなら ARP Request を送信し、応答を待つ（最大 3 回リトライ）
 pub fn resolve_mac(dst_ip: &[u8; 4]) -> Result<[u8; 6], &'static str> {
@@ -146,20 +199,9 @@ pub fn resolve_mac(dst_ip: &[u8; 4]) -> Result<[u8; 6], &'static str> {
         return Ok(BROADCAST_MAC);
     }
 
-    // サブネット判定: 10.0.2.0/24（最初の 3 バイトが一致するか）
-    // サブネット外の場合はゲートウェイの MAC を解決する
-    // サブネットマスクを使ってサブネット判定する
-    let my_ip = get_my_ip();
-    let mask = get_subnet_mask();
-    let resolve_ip = if (dst_ip[0] & mask[0]) == (my_ip[0] & mask[0])
-        && (dst_ip[1] & mask[1]) == (my_ip[1] & mask[1])
-        && (dst_ip[2] & mask[2]) == (my_ip[2] & mask[2])
-        && (dst_ip[3] & mask[3]) == (my_ip[3] & mask[3])
-    {
-        *dst_ip
-    } else {
-        get_gateway_ip()
-    };
+    // 次ホップを決める。直結サブネット内なら宛先そのもの、
+    // サブネット外ならデフォルトルートや静的ルートのゲートウェイの MAC を解決する。
+    let resolve_ip = route_lookup(dst_ip).ok_or("no route to host")?;
 
     // ARP キャッシュを検索
     if let Some(mac) = arp_lookup(&resolve_ip) {
@@ -183,3 +225,44 @@ pub fn resolve_mac(dst_ip: &[u8; 4]) -> Result<[u8; 6], &'static str> {
 
     Err("ARP resolve timeout")
 }
+
+/// Gratuitous ARP のテスト
+///
+/// 静的 IP を設定すると、そのアドレスの ARP Probe（送信元 0.0.0.0）と
+/// Gratuitous ARP（送信元 = ターゲット = 新しいアドレス）が送られることを
+/// キャプチャで確かめる。終わったら元の設定と DHCP の状態に戻す。
+pub fn test_gratuitous_arp() -> bool {
+    use crate::net_config::*;
+
+    let (old_ip, gateway, dns, mask, dhcp) =
+        (get_my_ip(), get_gateway_ip(), get_dns_server_ip(), get_subnet_mask(), is_dhcp_enabled());
+    let new_ip = [10, 0, 2, 21];
+
+    let id = super::capture_open(true);
+    let applied = set_static_config(new_ip, mask, gateway, dns).is_ok();
+
+    // 送信は同期的にキャプチャに積まれるので、待たずに読み出せる
+    let probe = build_arp_probe(new_ip);
+    let announcement = build_gratuitous_arp(new_ip);
+    let header_len = core::mem::size_of::<sabos_syscall::CaptureFrameHeader>();
+    let mut buf = [0u8; 1600];
+    let (mut saw_probe, mut saw_announcement) = (false, false);
+    while let Some(n) = super::capture_read(id, &mut buf) {
+        let body = &buf[header_len..n];
+        saw_probe |= body == probe.as_slice();
+        saw_announcement |= body == announcement.as_slice();
+    }
+    super::capture_close(id);
+
+    // Gratuitous ARP の中身: opcode 1（Request）、送信元 IP もターゲット IP も新しいアドレス
+    let well_formed = announcement.len() == 42
+        && announcement[20..22] == [0, 1]
+        && announcement[28..32] == new_ip
+        && announcement[38..42] == new_ip
+        && probe[28..32] == [0, 0, 0, 0];
+
+    set_config(old_ip, gateway, dns, mask);
+    set_dhcp_enabled(dhcp);
+
+    applied && saw_probe && saw_announcement && well_formed && get_my_ip() == old_ip
+}
@@ -0,0 +1,173 @@
+// capture.rs — 生の Ethernet フレームのキャプチャ
+//
+// tcpdump のようなパケット観察ツールのために、NIC で送受信したフレームの
+// コピーをキャプチャハンドル（handle.rs の HandleKind::Capture）に流す。
+//
+// net_poller_task() は受信フレームを handle_packet() に渡す前に、
+// send_frame() は NIC に渡す前に capture_frame() を呼ぶ。
+// 各キャプチャは上限付きのキューを持ち、読み手が追いつかずにあふれた
+// フレームは捨てて dropped カウンタを増やす（ネットワーク処理は止めない）。
+//
+// ソフトウェアループバック（自分宛ての TCP 等）は NIC を通らないので
+// キャプチャされない。
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+use sabos_syscall::{CaptureFrameHeader, CAPTURE_DIR_RX, CAPTURE_DIR_TX};
+
+/// 1 つのキャプチャが溜めておけるフレーム数の上限
+const CAPTURE_QUEUE_LIMIT: usize = 256;
+
+/// キャプチャしたフレーム 1 つ分
+struct CapturedFrame {
+    timestamp_ms: u64,
+    direction: u8,
+    data: Vec<u8>,
+}
+
+/// 1 つのキャプチャハンドルに対応するキュー
+struct CaptureQueue {
+    id: usize,
+    /// 送信フレームもキャプチャするか
+    include_tx: bool,
+    frames: VecDeque<CapturedFrame>,
+    /// キューあふれで捨てたフレームの累計
+    dropped: u32,
+}
+
+static CAPTURES: Mutex<Vec<CaptureQueue>> = Mutex::new(Vec::new());
+
+/// 開いているキャプチャの数。0 のときは送受信のたびにロックを取らずに済ませる。
+static ACTIVE_CAPTURES: AtomicUsize = AtomicUsize::new(0);
+
+static NEXT_CAPTURE_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// キャプチャを開いて ID を返す
+pub fn capture_open(include_tx: bool) -> usize {
+    let id = NEXT_CAPTURE_ID.fetch_add(1, Ordering::Relaxed);
+    let mut captures = CAPTURES.lock();
+    captures.push(CaptureQueue {
+        id,
+        include_tx,
+        frames: VecDeque::new(),
+        dropped: 0,
+    });
+    ACTIVE_CAPTURES.store(captures.len(), Ordering::Release);
+    id
+}
+
+/// キャプチャを閉じる（溜まっていたフレームは捨てる）
+pub fn capture_close(id: usize) {
+    let mut captures = CAPTURES.lock();
+    captures.retain(|c| c.id != id);
+    ACTIVE_CAPTURES.store(captures.len(), Ordering::Release);
+}
+
+/// 送受信したフレームを開いているキャプチャすべてにコピーする
+pub(super) fn capture_frame(direction: u8, data: &[u8]) {
+    if ACTIVE_CAPTURES.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let timestamp_ms = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) * 55;
+    let mut captures = CAPTURES.lock();
+    for capture in captures.iter_mut() {
+        if direction == CAPTURE_DIR_TX && !capture.include_tx {
+            continue;
+        }
+        if capture.frames.len() >= CAPTURE_QUEUE_LIMIT {
+            capture.dropped = capture.dropped.saturating_add(1);
+            continue;
+        }
+        capture.frames.push_back(CapturedFrame {
+            timestamp_ms,
+            direction,
+            data: data.to_vec(),
+        });
+    }
+}
+
+/// キャプチャからフレームを 1 つ取り出し、CaptureFrameHeader + 本体を buf に書く
+///
+/// buf はヘッダより大きいこと（呼び出し側で確認する）。本体が入りきらなければ
+/// 切り詰める（ヘッダの len には本来の長さが入る）。
+/// キューが空なら None。
+pub fn capture_read(id: usize, buf: &mut [u8]) -> Option<usize> {
+    let header_len = core::mem::size_of::<CaptureFrameHeader>();
+    let (frame, dropped) = {
+        let mut captures = CAPTURES.lock();
+        let capture = captures.iter_mut().find(|c| c.id == id)?;
+        (capture.frames.pop_front()?, capture.dropped)
+    };
+
+    let header = CaptureFrameHeader {
+        timestamp_ms: frame.timestamp_ms,
+        len: frame.data.len() as u32,
+        dropped,
+        direction: frame.direction,
+        _reserved: [0; 7],
+    };
+    // SAFETY: 呼び出し側が buf.len() >= header_len を保証している。
+    // バッファのアラインメントは不明なので write_unaligned を使う。
+    unsafe {
+        core::ptr::write_unaligned(buf.as_mut_ptr() as *mut CaptureFrameHeader, header);
+    }
+    let copy_len = core::cmp::min(frame.data.len(), buf.len() - header_len);
+    buf[header_len..header_len + copy_len].copy_from_slice(&frame.data[..copy_len]);
+    Some(header_len + copy_len)
+}
+
+/// キャプチャの動作テスト
+///
+/// キャプチャを開いてゲートウェイに ARP リクエストを送り、
+/// 送信したリクエストと受信したリプライの両方が読み出せることを確認する。
+pub fn test_capture() -> bool {
+    let gateway = crate::net_config::get_gateway_ip();
+    let id = capture_open(true);
+
+    let request = super::build_arp_request(gateway);
+    if super::send_frame(&request).is_err() {
+        capture_close(id);
+        return false;
+    }
+
+    // ゲートウェイからの ARP リプライ（opcode 2、送信元 IP = ゲートウェイ）が届くまで待つ
+    let is_reply = |data: &[u8]| {
+        data.len() >= 42
+            && data[12..14] == super::ETHERTYPE_ARP.to_be_bytes()
+            && data[20..22] == [0, 2]
+            && data[28..32] == gateway
+    };
+    let got_reply = super::wait_net_condition(3000, || {
+        let captures = CAPTURES.lock();
+        let capture = captures.iter().find(|c| c.id == id)?;
+        capture.frames.iter()
+            .any(|f| f.direction == CAPTURE_DIR_RX && is_reply(&f.data))
+            .then_some(())
+    });
+
+    // 読み出して、送信したリクエストと受信したリプライがあるか確かめる
+    let header_len = core::mem::size_of::<CaptureFrameHeader>();
+    let mut buf = [0u8; 1600];
+    let mut saw_request = false;
+    let mut saw_reply = false;
+    while let Some(n) = capture_read(id, &mut buf) {
+        // SAFETY: capture_read はヘッダを書いてから本体を続けて書く
+        let header = unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const CaptureFrameHeader) };
+        let body = &buf[header_len..n];
+        if header.len as usize != body.len() {
+            capture_close(id);
+            return false;
+        }
+        if header.direction == CAPTURE_DIR_TX && body == request.as_slice() {
+            saw_request = true;
+        }
+        if header.direction == CAPTURE_DIR_RX && is_reply(body) {
+            saw_reply = true;
+        }
+    }
+    capture_close(id);
+    got_reply.is_some() && saw_request && saw_reply
+}
@@ -317,7 +317,12 @@ fn parse_dhcp_response(data: &[u8]) -> Option<DhcpResponse> {
 /// 取得した設定を net_config に反映する。
 ///
 /// 失敗してもデフォルト値（10.0.2.15 等）が残るので安全。
+/// ifconfig などで静的に設定した後は、その設定を上書きしないよう何もせずにエラーを返す。
 pub fn dhcp_discover() -> Result<(), &'static str> {
+    if !crate::net_config::is_dhcp_enabled() {
+        return Err("DHCP disabled (static configuration)");
+    }
+
     let mac = get_my_mac();
     if mac == [0; 6] {
         return Err("no MAC address");
@@ -326,7 +331,7 @@ pub fn dhcp_discover() -> Result<(), &'static str> {
     // トランザクション ID（簡易的に MAC の一部を使用）
     let xid: u32 = u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]);
 
-    serial_println!("[net] dhcp: starting DHCP discovery (xid=0x{:08x})", xid);
+    net_debug!("dhcp: starting DHCP discovery (xid=0x{:08x})", xid);
 
     // UDP ソケットをポート 68 にバインド
     let sock_id = udp_bind(DHCP_CLIENT_PORT)?;
@@ -340,11 +345,11 @@ pub fn dhcp_discover() -> Result<(), &'static str> {
         DHCP_SERVER_PORT,
         &discover_pkt,
     )?;
-    serial_println!("[net] dhcp: sent Discover");
+    net_debug!("dhcp: sent Discover");
 
     // --- Step 2: DHCP Offer を待つ ---
     let offer = wait_dhcp_response(sock_id, xid, DHCP_MSG_OFFER, 5000)?;
-    serial_println!("[net] dhcp: received Offer: IP={}.{}.{}.{} from server {}.{}.{}.{}",
+    net_debug!("dhcp: received Offer: IP={}.{}.{}.{} from server {}.{}.{}.{}",
         offer.your_ip[0], offer.your_ip[1], offer.your_ip[2], offer.your_ip[3],
         offer.server_ip[0], offer.server_ip[1], offer.server_ip[2], offer.server_ip[3]
     );
@@ -358,12 +363,12 @@ pub fn dhcp_discover() -> Result<(), &'static str> {
         DHCP_SERVER_PORT,
         &request_pkt,
     )?;
-    serial_println!("[net] dhcp: sent Request for {}.{}.{}.{}",
+    net_debug!("dhcp: sent Request for {}.{}.{}.{}",
         offer.your_ip[0], offer.your_ip[1], offer.your_ip[2], offer.your_ip[3]);
 
     // --- Step 4: DHCP Ack を待つ ---
     let ack = wait_dhcp_response(sock_id, xid, DHCP_MSG_ACK, 5000)?;
-    serial_println!("[net] dhcp: received Ack: IP={}.{}.{}.{} mask={}.{}.{}.{} gw={}.{}.{}.{} dns={}.{}.{}.{}",
+    net_debug!("dhcp: received Ack: IP={}.{}.{}.{} mask={}.{}.{}.{} gw={}.{}.{}.{} dns={}.{}.{}.{}",
         ack.your_ip[0], ack.your_ip[1], ack.your_ip[2], ack.your_ip[3],
         ack.subnet_mask[0], ack.subnet_mask[1], ack.subnet_mask[2], ack.subnet_mask[3],
         ack.gateway_ip[0], ack.gateway_ip[1], ack.gateway_ip[2], ack.gateway_ip[3],
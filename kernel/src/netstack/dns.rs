@@ -4,11 +4,12 @@
 
 use alloc::vec::Vec;
 
+use crate::lock_order::{LockClass, OrderedMutex};
 use crate::net_config::get_dns_server_ip;
-use crate::serial_println;
 
-use super::{with_net_state, kernel_rdrand64, wait_net_condition};
-use super::udp::send_udp_packet;
+use super::wait_net_condition;
+use super::types::IpAddr;
+use super::udp::{udp_bind, udp_send_to, udp_try_recv_from};
 
 /// DNS ポート番号
 const DNS_PORT: u16 = 53;
@@ -16,52 +17,180 @@ const DNS_PORT: u16 = 53;
 const DNS_TYPE_A: u16 = 1;
 /// DNS クラス: IN (Internet)
 const DNS_CLASS_IN: u16 = 1;
+/// 1 回の問い合わせでレスポンスを待つ時間（ms）
+const DNS_TIMEOUT_MS: u64 = 5000;
+/// ランダムなソースポートでの bind を試す回数
+const DNS_BIND_ATTEMPTS: usize = 4;
+
+// ============================================================
+// 問い合わせ中クエリの管理
+// ============================================================
+//
+// 複数タスクが同時に dns_lookup() しても取り違えないよう、
+// 1 つの UDP ソケットを共有し、トランザクション ID → 待機タスクの表で
+// レスポンスを振り分ける。ソケットのキューを最初に覗いたタスクが
+// 届いているレスポンスをまとめて各エントリに配り、持ち主を起こす。
+//
+// ロック順序: DNS_RESOLVER → NET_STATE → SCHEDULER
+// （受信側の handle_udp は DNS_RESOLVER を触らないので逆順は発生しない）
+
+/// 問い合わせ中の 1 クエリ
+struct PendingQuery {
+    /// DNS トランザクション ID（in-flight のクエリ間で一意）
+    query_id: u16,
+    /// レスポンスを待っているタスク
+    task_id: u64,
+    /// 届いたレスポンス（持ち主が取り出すまで保持）
+    response: Option<Vec<u8>>,
+}
+
+/// カーネル内 DNS リゾルバの状態
+struct DnsResolver {
+    /// 全クエリで共有する UDP ソケット（初回の lookup で bind する）
+    socket_id: Option<u32>,
+    /// 問い合わせ中のクエリ一覧
+    pending: Vec<PendingQuery>,
+}
+
+static DNS_RESOLVER: OrderedMutex<DnsResolver> = OrderedMutex::new(LockClass::DnsResolver, DnsResolver {
+    socket_id: None,
+    pending: Vec::new(),
+});
 
 /// DNS クエリを送信して IP アドレスを解決する
+///
+/// ユーザー空間の UdpSocket と同じ UDP ソケット API（udp_bind / udp_send_to /
+/// udp_recv_from）を使う。レスポンスはトランザクション ID で待機タスクに
+/// 振り分けるので、複数タスクから同時に呼んでも互いの答えを取り違えない。
 pub fn dns_lookup(domain: &str) -> Result<[u8; 4], &'static str> {
-    // DNS クエリ ID をランダム化する。
-    // 固定値だと DNS キャッシュポイズニングに脆弱なため。
-    let query_id: u16 = kernel_rdrand64() as u16;
-    // DNS ソースポートをランダム化する（エフェメラルポート範囲: 49152-65535）。
-    // 固定ポートだと DNS キャッシュポイズニングに脆弱なため。
-    let src_port: u16 = 49152 + (kernel_rdrand64() as u16 % (65535 - 49152));
+    let socket_id = resolver_socket()?;
+    let query_id = register_query();
+
+    let result = build_dns_query(query_id, domain)
+        .and_then(|packet| query_via_socket(socket_id, domain, query_id, &packet));
+
+    // 成功・失敗どちらでも表から外す（ID を再利用可能にする）
+    unregister_query(query_id);
+    result
+}
+
+/// 共有 DNS ソケットを返す（未作成ならエフェメラルポートに bind する）
+///
+/// ソースポートはランダム化する（エフェメラルポート範囲: 49152-65535）。
+/// 固定ポートや連番だと DNS キャッシュポイズニングに脆弱なため。
+/// たまたま使用中のポートを引いた場合は引き直し、
+/// それでもだめなら udp_bind(0) の自動割り当てに任せる。
+fn resolver_socket() -> Result<u32, &'static str> {
+    let mut resolver = DNS_RESOLVER.lock();
+    if let Some(id) = resolver.socket_id {
+        return Ok(id);
+    }
+
+    let mut bound = None;
+    for _ in 0..DNS_BIND_ATTEMPTS {
+        let port: u16 = 49152 + (crate::random::random_u64() as u16 % (65535 - 49152));
+        if let Ok(id) = udp_bind(port) {
+            bound = Some(id);
+            break;
+        }
+    }
+    let id = match bound {
+        Some(id) => id,
+        None => udp_bind(0)?,
+    };
+    resolver.socket_id = Some(id);
+    Ok(id)
+}
+
+/// 新しいクエリを表に登録し、一意なトランザクション ID を返す
+///
+/// クエリ ID はランダム化する。固定値や連番だと DNS キャッシュポイズニングに
+/// 脆弱なため。問い合わせ中の ID と衝突したら引き直す。
+fn register_query() -> u16 {
+    let task_id = crate::scheduler::current_task_id();
+    let mut resolver = DNS_RESOLVER.lock();
+    let query_id = loop {
+        let id = crate::random::random_u64() as u16;
+        if !resolver.pending.iter().any(|p| p.query_id == id) {
+            break id;
+        }
+    };
+    resolver.pending.push(PendingQuery { query_id, task_id, response: None });
+    query_id
+}
 
-    let query_packet = build_dns_query(query_id, domain)?;
+/// クエリを表から外す
+fn unregister_query(query_id: u16) {
+    let mut resolver = DNS_RESOLVER.lock();
+    resolver.pending.retain(|p| p.query_id != query_id);
+}
+
+/// bind 済みソケットでクエリを送り、対応するレスポンスを待つ
+fn query_via_socket(
+    socket_id: u32,
+    domain: &str,
+    query_id: u16,
+    query_packet: &[u8],
+) -> Result<[u8; 4], &'static str> {
+    let server_ip = get_dns_server_ip();
 
     // 最大 2 回試行する。初回は ARP 未解決で drop される場合があるためリトライする
     for attempt in 0..2 {
-        // レスポンスバッファをクリア
-        with_net_state(|state| {
-            state.udp_response = None;
-        });
+        net_debug!("dns: sending query for '{}' (attempt {})", domain, attempt);
+        udp_send_to(socket_id, IpAddr::V4(server_ip), DNS_PORT, query_packet)?;
 
-        serial_println!("[net] dns: sending query for '{}' (attempt {})", domain, attempt);
-        let send_result = send_udp_packet(get_dns_server_ip(), DNS_PORT, src_port, &query_packet);
-        if send_result.is_err() {
-            return send_result.map(|_| [0u8; 4]);
+        // net_poller がパケットを処理するのを待ち、自分宛てのレスポンスを探す
+        let response = wait_net_condition(DNS_TIMEOUT_MS, || {
+            take_response(socket_id, server_ip, query_id)
+        });
+        if let Some(data) = response {
+            return parse_dns_response(&data);
         }
+    }
 
-        // net_poller がパケットを処理するのを待ち、DNS レスポンスをチェックする
-        let result = wait_net_condition(5000, || {
-            with_net_state(|state| {
-                if let Some((port, ref data)) = state.udp_response {
-                    if port == src_port && data.len() >= 12 {
-                        let response_id = u16::from_be_bytes([data[0], data[1]]);
-                        if response_id == query_id {
-                            return Some(parse_dns_response(data));
-                        }
-                    }
-                }
-                None
-            })
-        });
+    Err("DNS query timeout")
+}
+
+/// 共有ソケットに届いたレスポンスを振り分け、自分宛てがあれば取り出す
+fn take_response(socket_id: u32, server_ip: [u8; 4], query_id: u16) -> Option<Vec<u8>> {
+    let mut resolver = DNS_RESOLVER.lock();
 
-        if let Some(result) = result {
-            return result;
+    while let Ok(Some((src_ip, src_port, data))) = udp_try_recv_from(socket_id) {
+        if src_ip != IpAddr::V4(server_ip) || src_port != DNS_PORT {
+            net_debug!("dns: ignoring unexpected UDP packet");
+            continue;
         }
+        dispatch_response(&mut resolver, data);
     }
 
-    Err("DNS query timeout")
+    resolver
+        .pending
+        .iter_mut()
+        .find(|p| p.query_id == query_id)
+        .and_then(|p| p.response.take())
+}
+
+/// レスポンスをトランザクション ID に対応するエントリへ配る
+///
+/// 持ち主が別タスクなら起こして、次のチェックで受け取れるようにする。
+/// 対応するクエリがない（タイムアウト済み・偽装など）レスポンスは捨てる。
+/// 配れたら true を返す。
+fn dispatch_response(resolver: &mut DnsResolver, data: Vec<u8>) -> bool {
+    if data.len() < 12 {
+        return false;
+    }
+    let response_id = u16::from_be_bytes([data[0], data[1]]);
+    match resolver.pending.iter_mut().find(|p| p.query_id == response_id) {
+        Some(p) => {
+            p.response = Some(data);
+            crate::scheduler::wake_task(p.task_id);
+            true
+        }
+        None => {
+            net_debug!("dns: ignoring response with unknown ID {}", response_id);
+            false
+        }
+    }
 }
 
 /// DNS クエリパケットを構築する
@@ -104,14 +233,14 @@ fn parse_dns_response(data: &[u8]) -> Result<[u8; 4], &'static str> {
     let flags = u16::from_be_bytes([data[2], data[3]]);
     let rcode = flags & 0x000F;
     if rcode != 0 {
-        serial_println!("[net] dns: response error, RCODE={}", rcode);
+        net_debug!("dns: response error, RCODE={}", rcode);
         return Err("DNS query failed");
     }
 
     let qdcount = u16::from_be_bytes([data[4], data[5]]);
     let ancount = u16::from_be_bytes([data[6], data[7]]);
 
-    serial_println!("[net] dns: response with {} questions, {} answers", qdcount, ancount);
+    net_debug!("dns: response with {} questions, {} answers", qdcount, ancount);
 
     if ancount == 0 {
         return Err("No DNS answer");
@@ -148,7 +277,7 @@ fn parse_dns_response(data: &[u8]) -> Result<[u8; 4], &'static str> {
                 data[offset + 2],
                 data[offset + 3],
             ];
-            serial_println!("[net] dns: resolved to {}.{}.{}.{}",
+            net_debug!("dns: resolved to {}.{}.{}.{}",
                 ip[0], ip[1], ip[2], ip[3]
             );
             return Ok(ip);
@@ -180,3 +309,65 @@ fn skip_dns_name(data: &[u8], mut offset: usize) -> Result<usize, &'static str>
         offset += 1 + len as usize;
     }
 }
+
+// ============================================================
+// テスト用 API
+// ============================================================
+
+/// レスポンス振り分けのテスト
+///
+/// 2 つのクエリを表に登録し、到着順を入れ替えたレスポンスが
+/// それぞれ正しいエントリ（正しい答え）に届くことを確認する。
+/// ネットワークに依存しないよう、レスポンスはその場で組み立てる。
+/// selftest から呼ばれる。
+pub fn test_dns_routing() -> bool {
+    let id_a = register_query();
+    let id_b = register_query();
+    if id_a == id_b {
+        unregister_query(id_a);
+        return false;
+    }
+
+    let ip_a = [192, 0, 2, 1];
+    let ip_b = [198, 51, 100, 2];
+    let ok = match (build_test_response(id_a, "a.test", ip_a), build_test_response(id_b, "b.test", ip_b)) {
+        (Ok(resp_a), Ok(resp_b)) => {
+            let mut resolver = DNS_RESOLVER.lock();
+            // B のレスポンスが先に届くケース
+            let delivered = dispatch_response(&mut resolver, resp_b)
+                && dispatch_response(&mut resolver, resp_a)
+                // 未登録 ID のレスポンスは捨てられること
+                && !dispatch_response(&mut resolver, alloc::vec![0u8; 12]);
+            let mut got = |id: u16| {
+                resolver
+                    .pending
+                    .iter_mut()
+                    .find(|p| p.query_id == id)
+                    .and_then(|p| p.response.take())
+                    .and_then(|data| parse_dns_response(&data).ok())
+            };
+            delivered && got(id_a) == Some(ip_a) && got(id_b) == Some(ip_b)
+        }
+        _ => false,
+    };
+
+    unregister_query(id_a);
+    unregister_query(id_b);
+    ok
+}
+
+/// テスト用: クエリに A レコード 1 件を付けたレスポンスを組み立てる
+fn build_test_response(query_id: u16, domain: &str, ip: [u8; 4]) -> Result<Vec<u8>, &'static str> {
+    let mut packet = build_dns_query(query_id, domain)?;
+    packet[2] = 0x81; // QR=1, RD=1
+    packet[3] = 0x80; // RA=1, RCODE=0
+    packet[7] = 1; // ANCOUNT: 1
+    // Answer: 名前は Question への圧縮ポインタ (0xC00C)
+    packet.extend_from_slice(&[0xC0, 0x0C]);
+    packet.extend_from_slice(&DNS_TYPE_A.to_be_bytes());
+    packet.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 60]); // TTL
+    packet.extend_from_slice(&[0, 4]); // RDLENGTH
+    packet.extend_from_slice(&ip);
+    Ok(packet)
+}
@@ -6,16 +6,16 @@
 use alloc::vec::Vec;
 
 use crate::net_config::get_my_ip;
-use crate::serial_println;
 
 use super::{
     BROADCAST_MAC, ETHERTYPE_IPV4, IP_PROTO_ICMP, IP_PROTO_TCP, IP_PROTO_UDP,
     ICMP_ECHO_REPLY, ICMP_ECHO_REQUEST,
-    arp_lookup, arp_update, get_my_mac, is_local_ip, send_frame, calculate_checksum,
+    arp_lookup, arp_update, get_my_mac, is_local_ip, is_loopback_address, send_frame, calculate_checksum,
 };
 use super::types::{EthernetHeader, Ipv4Header, IcmpHeader};
 use super::tcp::handle_tcp;
 use super::udp::handle_udp;
+use super::ipv4::reassemble;
 
 /// IPv4 パケットを処理する
 pub(super) fn handle_ipv4(eth_header: &EthernetHeader, payload: &[u8]) {
@@ -38,12 +38,30 @@ pub(super) fn handle_ipv4(eth_header: &EthernetHeader, payload: &[u8]) {
     // 受信した IPv4 パケットの送信元 IP/MAC を ARP キャッシュに学習する。
     // これにより、ICMP Echo Reply 等の応答パケット送信時に
     // ARP Request なしで即座に MAC を解決できる。
+    // ループバックで届いた自分からのパケットは学習しない。
     let src_mac = eth_header.src_mac;
-    if src_mac != BROADCAST_MAC {
+    if src_mac != BROADCAST_MAC && !is_loopback_address(&ip_header.src_ip) {
         arp_update(ip_header.src_ip, src_mac);
     }
 
-    let ip_payload = &payload[header_len..];
+    // 最小フレーム長に満たないフレームには Ethernet のパディングが付いてくるので、
+    // IP の全長で切り詰める（TCP / UDP の長さやチェックサムにパディングを含めない）
+    let total_len = ip_header.total_length().clamp(header_len, payload.len());
+    let ip_payload = &payload[header_len..total_len];
+
+    // フラグメントは組がそろうまで上位層に渡さない
+    let reassembled;
+    let ip_payload = if ip_header.is_fragment() {
+        match reassemble(ip_header, ip_payload) {
+            Some(data) => {
+                reassembled = data;
+                &reassembled[..]
+            }
+            None => return,
+        }
+    } else {
+        ip_payload
+    };
 
     match ip_header.protocol {
         IP_PROTO_ICMP => {
@@ -56,7 +74,7 @@ pub(super) fn handle_ipv4(eth_header: &EthernetHeader, payload: &[u8]) {
             handle_udp(ip_header, ip_payload);
         }
         _ => {
-            serial_println!("[net] net: unknown IP protocol {}", ip_header.protocol);
+            net_debug!("net: unknown IP protocol {}", ip_header.protocol);
         }
     }
 }
@@ -70,7 +88,7 @@ fn handle_icmp(ip_header: &Ipv4Header, payload: &[u8]) {
     let icmp_header = unsafe { &*(payload.as_ptr() as *const IcmpHeader) };
 
     if icmp_header.icmp_type == ICMP_ECHO_REQUEST {
-        serial_println!("[net] net: ICMP Echo Request from {}.{}.{}.{}",
+        net_trace!("net: ICMP Echo Request from {}.{}.{}.{}",
             ip_header.src_ip[0], ip_header.src_ip[1],
             ip_header.src_ip[2], ip_header.src_ip[3]
         );
@@ -155,8 +173,8 @@ fn send_icmp_echo_reply(request_ip: &Ipv4Header, icmp_data: &[u8]) {
     packet.extend_from_slice(icmp_payload);
 
     if send_frame(&packet).is_err() {
-        serial_println!("[net] net: failed to send ICMP Echo Reply");
+        net_debug!("net: failed to send ICMP Echo Reply");
     } else {
-        serial_println!("[net] net: sent ICMP Echo Reply");
+        net_trace!("net: sent ICMP Echo Reply");
     }
 }
@@ -0,0 +1,394 @@
+// ipv4.rs — IPv4 パケットの送信（フラグメント化）と受信したフラグメントの再構築
+//
+// 送信: send_ipv4_packet() が IP ヘッダーを付けて送る。IP ヘッダー + データが MTU を
+// 超えるときは、データを 8 バイト単位のフラグメントに分けてそれぞれ 1 フレームで送る。
+// 自分の IP / 127.0.0.1 宛は NIC を通さず handle_packet() に渡す（ソフトウェアループバック）。
+//
+// 受信: handle_ipv4()（icmp.rs）が MF フラグかフラグメントオフセット付きのパケットを
+// reassemble() に渡す。(送信元, 宛先, 識別子, プロトコル) ごとにフラグメントを集め、
+// 隙間なくそろったら元のデータとして上位層（ICMP / TCP / UDP）に渡す。
+// 既に届いた範囲と重なるフラグメントが来たら、その組ごと捨てる（同じ範囲の再送だけは無視する）。
+// 重なりを許すとオフセットをずらしたフラグメントで 1 組に何 MB も溜められてしまうため。
+// そろわないまま REASSEMBLY_TIMEOUT_TICKS を過ぎた組は net_poller が捨てる。
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
+use spin::Mutex;
+
+
+use super::{ETHERTYPE_IPV4, calculate_checksum, get_my_mac, handle_packet, is_loopback_address, send_frame};
+use super::types::{EthernetHeader, Ipv4Header};
+
+/// MTU の既定値（Ethernet の標準）
+pub const DEFAULT_MTU: usize = 1500;
+/// 設定できる MTU の下限（RFC 791 で、すべてのホストが受け取れるとされる大きさ）
+const MIN_MTU: usize = 576;
+/// 設定できる MTU の上限（ジャンボフレーム）
+const MAX_MTU: usize = 9000;
+
+/// IPv4 ヘッダーの長さ（オプションは付けない）
+const IPV4_HEADER_LEN: usize = 20;
+/// 1 つの IP パケットに載せられるデータの最大長（全長 65535 - ヘッダー）
+pub const IPV4_MAX_PAYLOAD: usize = 65535 - IPV4_HEADER_LEN;
+
+/// flags_fragment の DF（Don't Fragment）ビット
+const FLAG_DONT_FRAGMENT: u16 = 0x4000;
+/// flags_fragment の MF（More Fragments）ビット
+const FLAG_MORE_FRAGMENTS: u16 = 0x2000;
+/// flags_fragment のフラグメントオフセット（8 バイト単位）
+const FRAGMENT_OFFSET_MASK: u16 = 0x1FFF;
+
+/// 再構築を諦めるまでの時間（PIT tick、約 18.2 Hz なので約 30 秒）
+const REASSEMBLY_TIMEOUT_TICKS: u64 = 546;
+/// 同時に再構築する組の上限。あふれたら一番古い組を捨てる
+const MAX_REASSEMBLY_BUFFERS: usize = 16;
+
+/// 現在の MTU（IP ヘッダーを含む 1 パケットの最大長）
+static MTU: AtomicUsize = AtomicUsize::new(DEFAULT_MTU);
+
+/// 次に送るパケットの識別子。フラグメントの組を見分けるのに使われる
+static NEXT_IDENTIFICATION: AtomicU16 = AtomicU16::new(1);
+
+/// 現在の MTU を返す
+pub fn mtu() -> usize {
+    MTU.load(Ordering::Relaxed)
+}
+
+/// MTU を設定する（MIN_MTU〜MAX_MTU）
+pub fn set_mtu(mtu: usize) -> Result<(), &'static str> {
+    if !(MIN_MTU..=MAX_MTU).contains(&mtu) {
+        return Err("MTU must be between 576 and 9000");
+    }
+    MTU.store(mtu, Ordering::Relaxed);
+    Ok(())
+}
+
+/// IPv4 パケットを送る（MTU を超えるならフラグメントに分ける）
+///
+/// payload は上位層（TCP / UDP）のヘッダー込みのデータ。
+/// dst_mac は呼び出し側で解決しておく（ループバック宛なら何でもよい）。
+pub(super) fn send_ipv4_packet(
+    dst_mac: [u8; 6],
+    src_ip: [u8; 4],
+    dst_ip: [u8; 4],
+    protocol: u8,
+    payload: &[u8],
+) -> Result<(), &'static str> {
+    let frames = build_ipv4_frames(dst_mac, src_ip, dst_ip, protocol, payload, mtu())?;
+    let loopback = is_loopback_address(&dst_ip);
+    for frame in &frames {
+        if loopback {
+            handle_packet(frame);
+        } else {
+            send_frame(frame).map_err(|_| "send failed")?;
+        }
+    }
+    Ok(())
+}
+
+/// IPv4 パケットのフレーム（Ethernet ヘッダー込み）を組み立てる
+///
+/// MTU に収まれば DF 付きの 1 フレーム、収まらなければフラグメントごとのフレームを返す。
+/// 最後以外のフラグメントのデータ長は 8 の倍数にする（オフセットが 8 バイト単位のため）。
+fn build_ipv4_frames(
+    dst_mac: [u8; 6],
+    src_ip: [u8; 4],
+    dst_ip: [u8; 4],
+    protocol: u8,
+    payload: &[u8],
+    mtu: usize,
+) -> Result<Vec<Vec<u8>>, &'static str> {
+    if payload.len() > IPV4_MAX_PAYLOAD {
+        return Err("packet too large");
+    }
+    let identification = NEXT_IDENTIFICATION.fetch_add(1, Ordering::Relaxed);
+    let build = |flags_fragment: u16, data: &[u8]| {
+        build_ipv4_frame(dst_mac, src_ip, dst_ip, protocol, identification, flags_fragment, data)
+    };
+
+    if IPV4_HEADER_LEN + payload.len() <= mtu {
+        return Ok(alloc::vec![build(FLAG_DONT_FRAGMENT, payload)]);
+    }
+
+    let max_data = (mtu - IPV4_HEADER_LEN) & !7;
+    let frames = payload
+        .chunks(max_data)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let offset = i * max_data;
+            let more = offset + chunk.len() < payload.len();
+            let flags = if more { FLAG_MORE_FRAGMENTS } else { 0 };
+            build(flags | (offset / 8) as u16, chunk)
+        })
+        .collect();
+    Ok(frames)
+}
+
+/// IPv4 パケット 1 つ分のフレーム（Ethernet ヘッダー + IP ヘッダー + データ）を組み立てる
+fn build_ipv4_frame(
+    dst_mac: [u8; 6],
+    src_ip: [u8; 4],
+    dst_ip: [u8; 4],
+    protocol: u8,
+    identification: u16,
+    flags_fragment: u16,
+    data: &[u8],
+) -> Vec<u8> {
+    let eth_header = EthernetHeader {
+        dst_mac,
+        src_mac: get_my_mac(),
+        ethertype: ETHERTYPE_IPV4.to_be_bytes(),
+    };
+
+    let mut ip_header = Ipv4Header {
+        version_ihl: 0x45,
+        tos: 0,
+        total_length: ((IPV4_HEADER_LEN + data.len()) as u16).to_be_bytes(),
+        identification: identification.to_be_bytes(),
+        flags_fragment: flags_fragment.to_be_bytes(),
+        ttl: 64,
+        protocol,
+        checksum: [0, 0],
+        src_ip,
+        dst_ip,
+    };
+    let ip_checksum = calculate_checksum(unsafe {
+        core::slice::from_raw_parts(&ip_header as *const _ as *const u8, IPV4_HEADER_LEN)
+    });
+    ip_header.checksum = ip_checksum.to_be_bytes();
+
+    let mut frame = Vec::with_capacity(14 + IPV4_HEADER_LEN + data.len());
+    frame.extend_from_slice(unsafe {
+        core::slice::from_raw_parts(&eth_header as *const _ as *const u8, 14)
+    });
+    frame.extend_from_slice(unsafe {
+        core::slice::from_raw_parts(&ip_header as *const _ as *const u8, IPV4_HEADER_LEN)
+    });
+    frame.extend_from_slice(data);
+    frame
+}
+
+// ============================================================
+// フラグメントの再構築
+// ============================================================
+
+/// 再構築中のフラグメントの組
+struct Reassembly {
+    src_ip: [u8; 4],
+    dst_ip: [u8; 4],
+    identification: [u8; 2],
+    protocol: u8,
+    /// 届いたフラグメント: (データ中のオフセット, データ)
+    fragments: Vec<(usize, Vec<u8>)>,
+    /// 最後のフラグメント（MF なし）が届いたら、元のデータの全長
+    total_len: Option<usize>,
+    /// この時刻（PIT tick）までにそろわなければ捨てる
+    deadline: u64,
+}
+
+static REASSEMBLY: Mutex<Vec<Reassembly>> = Mutex::new(Vec::new());
+
+/// フラグメントを受け取り、組がそろったら元のデータを返す
+///
+/// まだそろっていない、または不正なフラグメントなら None。
+pub(super) fn reassemble(header: &Ipv4Header, data: &[u8]) -> Option<Vec<u8>> {
+    let flags_fragment = header.flags_fragment_u16();
+    let offset = (flags_fragment & FRAGMENT_OFFSET_MASK) as usize * 8;
+    let more = flags_fragment & FLAG_MORE_FRAGMENTS != 0;
+    // 最後以外のフラグメントは 8 バイト単位。全長が IP パケットの上限を超えるものは捨てる
+    if (more && !data.len().is_multiple_of(8)) || offset + data.len() > IPV4_MAX_PAYLOAD {
+        return None;
+    }
+
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    let mut list = REASSEMBLY.lock();
+    let idx = match list.iter().position(|r| {
+        r.src_ip == header.src_ip
+            && r.dst_ip == header.dst_ip
+            && r.identification == header.identification
+            && r.protocol == header.protocol
+    }) {
+        Some(idx) => idx,
+        None => {
+            if list.len() >= MAX_REASSEMBLY_BUFFERS {
+                list.remove(0);
+            }
+            list.push(Reassembly {
+                src_ip: header.src_ip,
+                dst_ip: header.dst_ip,
+                identification: header.identification,
+                protocol: header.protocol,
+                fragments: Vec::new(),
+                total_len: None,
+                deadline: now + REASSEMBLY_TIMEOUT_TICKS,
+            });
+            list.len() - 1
+        }
+    };
+
+    let entry = &mut list[idx];
+    let end = offset + data.len();
+    // 同じ範囲の再送は 1 つだけ持つ。一部でも重なるフラグメントは組ごと捨てる
+    if entry.fragments.iter().any(|(o, d)| *o == offset && d.len() == data.len()) {
+        return None;
+    }
+    if entry.fragments.iter().any(|(o, d)| offset < o + d.len() && *o < end) {
+        list.remove(idx);
+        net_debug!("ipv4: discarded fragment set with overlapping fragments");
+        return None;
+    }
+    if !more {
+        entry.total_len = Some(end);
+    }
+    entry.fragments.push((offset, data.to_vec()));
+
+    // 先頭から隙間なく全長までそろったか
+    let total_len = entry.total_len?;
+    entry.fragments.sort_by_key(|(o, _)| *o);
+    let mut covered = 0;
+    for (o, d) in &entry.fragments {
+        if *o > covered {
+            return None;
+        }
+        covered = covered.max(o + d.len());
+    }
+    if covered < total_len {
+        return None;
+    }
+
+    let entry = list.remove(idx);
+    drop(list);
+    let mut out = alloc::vec![0u8; total_len];
+    for (o, d) in entry.fragments {
+        if o < total_len {
+            let end = (o + d.len()).min(total_len);
+            out[o..end].copy_from_slice(&d[..end - o]);
+        }
+    }
+    Some(out)
+}
+
+/// 期限までにそろわなかったフラグメントの組を捨てる（net_poller から呼ばれる）
+pub(super) fn expire_reassembly(now: u64) {
+    let mut list = REASSEMBLY.lock();
+    let before = list.len();
+    list.retain(|r| r.deadline > now);
+    let expired = before - list.len();
+    if expired > 0 {
+        net_debug!("ipv4: discarded {} incomplete fragment set(s)", expired);
+    }
+}
+
+/// 再構築中のフラグメントの組の数
+fn reassembly_count() -> usize {
+    REASSEMBLY.lock().len()
+}
+
+/// フラグメント化と再構築のテスト
+///
+/// 1. 3000 バイトの UDP データを 127.0.0.1 に送ると、MTU 1500 では 3 つのフラグメントに
+///    分かれ、ループバックで受け取った側で元どおりに組み立てられて 1 データグラムで届く
+/// 2. フラグメントを逆順に受け取っても組み立てられる
+/// 3. 最後のフラグメントが来ない組は、期限が来ると捨てられる
+///
+/// selftest から呼ばれる。
+pub fn test_ipv4_fragmentation() -> bool {
+    use super::types::IpAddr;
+    use super::LOOPBACK_IP;
+
+    let payload: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+    let Ok(sock) = super::udp::udp_bind(0) else {
+        return false;
+    };
+    let Ok(port) = super::udp::udp_local_port(sock) else {
+        let _ = super::udp::udp_close(sock);
+        return false;
+    };
+
+    // 1. 127.0.0.1 宛の 3000 バイト（UDP ヘッダー込み 3008 バイト → 1480 + 1480 + 48）
+    let frames = build_ipv4_frames(get_my_mac(), LOOPBACK_IP, LOOPBACK_IP, super::IP_PROTO_UDP, &[0; 3008], DEFAULT_MTU);
+    let split_ok = frames.as_ref().is_ok_and(|f| f.len() == 3 && f.iter().all(|frame| frame.len() <= 14 + DEFAULT_MTU));
+    let sent = super::udp::udp_send_to(sock, IpAddr::V4(LOOPBACK_IP), port, &payload).is_ok();
+    let received = matches!(
+        super::udp::udp_try_recv_from(sock),
+        Ok(Some((IpAddr::V4(LOOPBACK_IP), p, ref data))) if p == port && *data == payload
+    );
+    let _ = super::udp::udp_close(sock);
+
+    // 2. 逆順に届けても組み立てられる（ICMP 等の上位層には渡さずに reassemble を直接呼ぶ）
+    let data: Vec<u8> = (0..2000u32).map(|i| (i % 7) as u8).collect();
+    let reversed_ok = match build_ipv4_frames(get_my_mac(), LOOPBACK_IP, LOOPBACK_IP, 253, &data, DEFAULT_MTU) {
+        Ok(frames) => {
+            let mut result = None;
+            for frame in frames.iter().rev() {
+                let header = unsafe { &*(frame[14..].as_ptr() as *const Ipv4Header) };
+                result = reassemble(header, &frame[14 + IPV4_HEADER_LEN..]);
+            }
+            result == Some(data)
+        }
+        Err(_) => false,
+    };
+
+    // 3. 先頭のフラグメントだけ渡して、期限切れで捨てられることを確かめる
+    let before = reassembly_count();
+    let incomplete_ok = match build_ipv4_frames(get_my_mac(), LOOPBACK_IP, LOOPBACK_IP, 253, &[0; 2000], DEFAULT_MTU) {
+        Ok(frames) => {
+            let header = unsafe { &*(frames[0][14..].as_ptr() as *const Ipv4Header) };
+            let pending = reassemble(header, &frames[0][14 + IPV4_HEADER_LEN..]).is_none()
+                && reassembly_count() == before + 1;
+            let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+            expire_reassembly(now + REASSEMBLY_TIMEOUT_TICKS + 1);
+            pending && reassembly_count() == 0
+        }
+        Err(_) => false,
+    };
+
+    split_ok && sent && received && reversed_ok && incomplete_ok
+}
+
+/// 重なったフラグメントのテスト
+///
+/// 2000 バイトを 2 つのフラグメント（1480 + 520）に分け、先頭を渡したあと、
+/// 2 つ目のオフセットを 8 バイト前にずらして先頭と重ねたものを渡す。
+/// 組ごと捨てられ、あとから正しい 2 つ目が届いても組み立てられないことを確かめる。
+/// 同じ範囲の再送は重なりとはみなさず、そのまま組み立てられることも確かめる。
+///
+/// selftest から呼ばれる。
+pub fn test_ipv4_fragment_overlap() -> bool {
+    use super::LOOPBACK_IP;
+
+    let data: Vec<u8> = (0..2000u32).map(|i| (i % 13) as u8).collect();
+    let Ok(frames) = build_ipv4_frames(get_my_mac(), LOOPBACK_IP, LOOPBACK_IP, 253, &data, DEFAULT_MTU) else {
+        return false;
+    };
+    if frames.len() != 2 {
+        return false;
+    }
+    let header_of = |frame: &[u8]| unsafe { *(frame[14..].as_ptr() as *const Ipv4Header) };
+    let first = header_of(&frames[0]);
+    let second = header_of(&frames[1]);
+    let first_data = &frames[0][14 + IPV4_HEADER_LEN..];
+    let second_data = &frames[1][14 + IPV4_HEADER_LEN..];
+
+    // 2 つ目を 8 バイト前にずらして先頭の末尾と重ねる
+    let mut overlapping = second;
+    let shifted = second.flags_fragment_u16() - 1;
+    overlapping.flags_fragment = shifted.to_be_bytes();
+
+    let before = reassembly_count();
+    let dropped = reassemble(&first, first_data).is_none()
+        && reassembly_count() == before + 1
+        && reassemble(&overlapping, second_data).is_none()
+        && reassembly_count() == before;
+    // 先頭が捨てられているので、正しい 2 つ目だけでは組み立てられない
+    let not_rebuilt = reassemble(&second, second_data).is_none();
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    expire_reassembly(now + REASSEMBLY_TIMEOUT_TICKS + 1);
+
+    // 同じ範囲の再送は無視され、組み立ては続く
+    let resent_ok = reassemble(&first, first_data).is_none()
+        && reassemble(&first, first_data).is_none()
+        && reassemble(&second, second_data) == Some(data);
+
+    dropped && not_rebuilt && resent_ok && reassembly_count() == 0
+}
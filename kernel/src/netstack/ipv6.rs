@@ -1,15 +1,37 @@
 // ipv6.rs — IPv6 / ICMPv6 / NDP プロトコル処理
 //
 // IPv6 パケットの処理、ICMPv6 Echo Request/Reply、
-// NDP (Neighbor Discovery Protocol) の Neighbor Solicitation/Advertisement を行う。
+// NDP (Neighbor Discovery Protocol) の Neighbor Solicitation/Advertisement、
+// SLAAC（Router Solicitation/Advertisement によるアドレス自動設定）を行う。
+//
+// ## 近隣キャッシュ
+//
+// IPv4 の ARP キャッシュに相当するもの。Neighbor Advertisement の
+// Target Link-Layer Address や、NS/RA の Source Link-Layer Address から
+// IPv6 → MAC を学習し、ユニキャスト送信時の宛先 MAC に使う。
+// キャッシュミス時は Neighbor Solicitation を送信先の
+// ソリシテッドノードマルチキャスト (ff02::1:ffXX:XXXX) に送って問い合わせる。
+//
+// ## SLAAC (RFC 4862)
+//
+// 1. MAC アドレスから EUI-64 形式のインターフェース ID (64bit) を作る
+// 2. リンクローカルアドレス fe80::<IID> から Router Solicitation を送る
+// 3. ルーターの Router Advertisement に含まれる Prefix Information（/64, A フラグ）
+//    のプレフィックスに IID を連結してグローバルアドレスにする
+//
+// QEMU SLIRP はプレフィックス fec0::/64 を広告するので、
+// デフォルト MAC 52:54:00:12:34:56 なら fec0::5054:ff:fe12:3456 になる。
 
+use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt::Write;
 
-use crate::serial_println;
+use crate::net_config::{get_ipv6_router, get_my_ipv6, is_ipv6_slaac_configured, set_ipv6_slaac};
 
 use super::{
-    BROADCAST_MAC, ETHERTYPE_IPV6, IP_PROTO_ICMPV6, MY_IPV6,
-    with_net_state, get_my_mac, send_frame, calculate_checksum,
+    BROADCAST_MAC, ETHERTYPE_IPV6, IP_PROTO_ICMPV6, IP_PROTO_TCP, IP_PROTO_UDP,
+    with_net_state, get_my_mac, send_frame, calculate_checksum, wait_net_condition,
+    ndp_lookup, ndp_update,
 };
 use super::types::EthernetHeader;
 
@@ -17,14 +39,39 @@ use super::types::EthernetHeader;
 const ICMPV6_ECHO_REQUEST: u8 = 128;
 /// ICMPv6 Echo Reply
 const ICMPV6_ECHO_REPLY: u8 = 129;
+/// ICMPv6 Router Solicitation (NDP)
+const ICMPV6_ROUTER_SOLICITATION: u8 = 133;
 /// ICMPv6 Router Advertisement (NDP)
-#[allow(dead_code)]
 const ICMPV6_ROUTER_ADVERTISEMENT: u8 = 134;
 /// ICMPv6 Neighbor Solicitation (NDP)
 const ICMPV6_NEIGHBOR_SOLICITATION: u8 = 135;
 /// ICMPv6 Neighbor Advertisement (NDP)
 const ICMPV6_NEIGHBOR_ADVERTISEMENT: u8 = 136;
 
+/// NDP オプション: Source Link-Layer Address
+const NDP_OPT_SOURCE_LL_ADDR: u8 = 1;
+/// NDP オプション: Target Link-Layer Address
+const NDP_OPT_TARGET_LL_ADDR: u8 = 2;
+/// NDP オプション: Prefix Information
+const NDP_OPT_PREFIX_INFO: u8 = 3;
+/// Prefix Information の A (autonomous address-configuration) フラグ
+const PREFIX_FLAG_AUTONOMOUS: u8 = 0x40;
+/// NDP メッセージの Hop Limit。RFC 4861 により 255 以外は受信側で破棄される
+/// （ルーターを越えてきた偽の NDP を弾くため）。
+const NDP_HOP_LIMIT: u8 = 255;
+/// 通常パケットの Hop Limit
+const DEFAULT_HOP_LIMIT: u8 = 64;
+/// Router Advertisement を待つ時間（ms）
+const RA_TIMEOUT_MS: u64 = 1000;
+/// Neighbor Advertisement を待つ時間（ms、1 回の Solicitation あたり）
+const NA_TIMEOUT_MS: u64 = 1000;
+/// Neighbor Solicitation の最大送信回数
+const NS_MAX_RETRIES: usize = 3;
+/// 全ルーター マルチキャストアドレス (ff02::2)
+const ALL_ROUTERS_MULTICAST: [u8; 16] = [0xff, 0x02, 0,0,0,0,0,0, 0,0,0,0,0,0,0, 0x02];
+/// 全ノード マルチキャストアドレス (ff02::1)
+const ALL_NODES_MULTICAST: [u8; 16] = [0xff, 0x02, 0,0,0,0,0,0, 0,0,0,0,0,0,0, 0x01];
+
 /// IPv6 ヘッダー (40 バイト固定)
 #[repr(C, packed)]
 #[derive(Clone, Copy, Debug)]
@@ -67,9 +114,11 @@ pub(super) fn handle_ipv6(_eth_header: &EthernetHeader, payload: &[u8]) {
         return;
     }
 
-    let is_my_unicast = ipv6_header.dst_ip == MY_IPV6;
-    let is_solicited_node_multicast = is_solicited_node_multicast_for(&ipv6_header.dst_ip, &MY_IPV6);
-    let is_all_nodes_multicast = ipv6_header.dst_ip == [0xff, 0x02, 0,0,0,0,0,0, 0,0,0,0,0,0,0, 0x01];
+    let is_my_unicast = is_my_address(&ipv6_header.dst_ip);
+    let is_solicited_node_multicast =
+        is_solicited_node_multicast_for(&ipv6_header.dst_ip, &get_my_ipv6())
+        || is_solicited_node_multicast_for(&ipv6_header.dst_ip, &link_local_address());
+    let is_all_nodes_multicast = ipv6_header.dst_ip == ALL_NODES_MULTICAST;
 
     if !is_my_unicast && !is_solicited_node_multicast && !is_all_nodes_multicast {
         return;
@@ -81,12 +130,221 @@ pub(super) fn handle_ipv6(_eth_header: &EthernetHeader, payload: &[u8]) {
         IP_PROTO_ICMPV6 => {
             handle_icmpv6(ipv6_header, ipv6_payload);
         }
+        IP_PROTO_UDP => {
+            super::udp::handle_udp_v6(ipv6_header, ipv6_payload);
+        }
+        IP_PROTO_TCP => {
+            super::tcp::handle_tcp_v6(ipv6_header, ipv6_payload);
+        }
         _ => {
-            serial_println!("[net] ipv6: unknown next_header {}", ipv6_header.next_header);
+            net_debug!("ipv6: unknown next_header {}", ipv6_header.next_header);
         }
     }
 }
 
+/// 自分のアドレス（SLAAC / フォールバックのアドレス、またはリンクローカル）かどうか
+pub(super) fn is_my_address(ip: &[u8; 16]) -> bool {
+    *ip == get_my_ipv6() || *ip == link_local_address()
+}
+
+// ============================================================
+// SLAAC（ステートレスアドレス自動設定）
+// ============================================================
+
+/// MAC アドレスから修正 EUI-64 形式のインターフェース ID を作る（RFC 4291 Appendix A）
+///
+/// 48bit の MAC を真ん中で割って 0xFF 0xFE を挟み、
+/// 先頭バイトの U/L ビット（0x02）を反転する。
+/// 例: 52:54:00:12:34:56 → 5054:00ff:fe12:3456
+pub fn eui64_interface_id(mac: &[u8; 6]) -> [u8; 8] {
+    [
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]
+}
+
+/// /64 プレフィックスとインターフェース ID を連結してアドレスを作る
+///
+/// prefix の上位 64bit だけを使い、下位 64bit は iid で置き換える。
+pub fn slaac_address(prefix: &[u8; 16], iid: &[u8; 8]) -> [u8; 16] {
+    let mut addr = [0u8; 16];
+    addr[..8].copy_from_slice(&prefix[..8]);
+    addr[8..].copy_from_slice(iid);
+    addr
+}
+
+/// 自分のリンクローカルアドレス fe80::<IID>
+fn link_local_address() -> [u8; 16] {
+    let prefix = [0xfe, 0x80, 0,0,0,0,0,0, 0,0,0,0,0,0,0,0];
+    slaac_address(&prefix, &eui64_interface_id(&get_my_mac()))
+}
+
+/// SLAAC でアドレスを設定する
+///
+/// Router Solicitation を送り、Router Advertisement を受けて
+/// アドレスが設定されるのを待つ。RA の処理自体は handle_icmpv6 が行うので、
+/// ここは「送って待つ」だけ。RA が来なければエラーを返し、
+/// 呼び出し元はフォールバックアドレスのまま動作する。
+pub fn slaac_configure() -> Result<[u8; 16], &'static str> {
+    if is_ipv6_slaac_configured() {
+        return Ok(get_my_ipv6());
+    }
+
+    send_router_solicitation();
+
+    let configured = wait_net_condition(RA_TIMEOUT_MS, || {
+        if is_ipv6_slaac_configured() {
+            Some(get_my_ipv6())
+        } else {
+            None
+        }
+    });
+    configured.ok_or("no router advertisement")
+}
+
+/// Router Solicitation を全ルーター宛てに送信する
+fn send_router_solicitation() {
+    let my_mac = get_my_mac();
+    let src_ip = link_local_address();
+
+    let mut rs_payload = Vec::with_capacity(16);
+    rs_payload.push(ICMPV6_ROUTER_SOLICITATION);
+    rs_payload.push(0);
+    rs_payload.push(0);
+    rs_payload.push(0);
+    // Reserved
+    rs_payload.extend_from_slice(&[0, 0, 0, 0]);
+    // Option: Source Link-Layer Address（長さは 8 バイト単位）
+    rs_payload.push(NDP_OPT_SOURCE_LL_ADDR);
+    rs_payload.push(1);
+    rs_payload.extend_from_slice(&my_mac);
+
+    let checksum = calculate_icmpv6_checksum(&src_ip, &ALL_ROUTERS_MULTICAST, &rs_payload);
+    rs_payload[2] = (checksum >> 8) as u8;
+    rs_payload[3] = (checksum & 0xFF) as u8;
+
+    net_debug!("ndp: sending Router Solicitation");
+    send_ipv6_packet_from(&src_ip, &ALL_ROUTERS_MULTICAST, IP_PROTO_ICMPV6, NDP_HOP_LIMIT, &rs_payload);
+}
+
+/// Router Advertisement を処理して SLAAC アドレスを設定する
+///
+/// RFC 4861 §6.1.2 に従い、送信元がリンクローカル (fe80::/10) で hop limit が 255 の
+/// RA だけを受け付ける。ルーターの RA は必ずリンクローカルから送られ、hop limit 255 なら
+/// ルーターを経由していない（同じリンクから来た）ことが分かる。
+/// そうでないものを受け入れると、リンクの外からプレフィックスやルーターを差し替えられてしまう。
+fn handle_router_advertisement(ipv6_header: &Ipv6Header, payload: &[u8]) {
+    if ipv6_header.hop_limit != NDP_HOP_LIMIT {
+        net_debug!("ndp: RA with hop limit {} ignored", ipv6_header.hop_limit);
+        return;
+    }
+    if !is_link_local(&ipv6_header.src_ip) {
+        net_debug!("ndp: RA from non-link-local {} ignored", format_ipv6(&ipv6_header.src_ip));
+        return;
+    }
+    if payload.len() < 16 {
+        return;
+    }
+
+    let prefix = match parse_ra_prefix(payload) {
+        Some(p) => p,
+        None => {
+            net_debug!("ndp: RA without usable prefix, ignoring");
+            return;
+        }
+    };
+
+    // RA の Source Link-Layer Address からルーターの MAC を学習しておく。
+    // サブネット外への送信はルーター経由になるので、最初の送信で NS を待たずに済む。
+    if let Some(mac) = find_link_layer_option(&payload[16..], NDP_OPT_SOURCE_LL_ADDR) {
+        ndp_update(ipv6_header.src_ip, mac);
+    }
+
+    let addr = slaac_address(&prefix, &eui64_interface_id(&get_my_mac()));
+    let router = ipv6_header.src_ip;
+    set_ipv6_slaac(addr, router);
+    net_debug!("ndp: SLAAC address {} (router {})",
+        format_ipv6(&addr), format_ipv6(&router));
+}
+
+/// Router Advertisement から SLAAC に使える /64 プレフィックスを取り出す
+///
+/// RA 本体は 16 バイト（type, code, checksum, hop limit, flags,
+/// router lifetime, reachable time, retrans timer）で、その後ろに
+/// TLV 形式のオプションが並ぶ。Prefix Information のうち
+/// A フラグが立ち、長さ 64、有効期間が 0 でないものを採用する。
+fn parse_ra_prefix(payload: &[u8]) -> Option<[u8; 16]> {
+    let mut offset = 16;
+    while offset + 2 <= payload.len() {
+        let opt_type = payload[offset];
+        let opt_len = payload[offset + 1] as usize * 8;
+        if opt_len == 0 || offset + opt_len > payload.len() {
+            // 長さ 0 のオプションは不正（無限ループ防止のため打ち切る）
+            return None;
+        }
+
+        if opt_type == NDP_OPT_PREFIX_INFO && opt_len >= 32 {
+            let opt = &payload[offset..offset + opt_len];
+            let prefix_len = opt[2];
+            let flags = opt[3];
+            let valid_lifetime = u32::from_be_bytes([opt[4], opt[5], opt[6], opt[7]]);
+            if prefix_len == 64 && flags & PREFIX_FLAG_AUTONOMOUS != 0 && valid_lifetime != 0 {
+                let mut prefix = [0u8; 16];
+                prefix.copy_from_slice(&opt[16..32]);
+                return Some(prefix);
+            }
+        }
+
+        offset += opt_len;
+    }
+    None
+}
+
+/// IPv6 アドレスを文字列にする（最長の 0 グループ連続を "::" に圧縮）
+pub fn format_ipv6(addr: &[u8; 16]) -> String {
+    let groups: [u16; 8] = core::array::from_fn(|i| u16::from_be_bytes([addr[i * 2], addr[i * 2 + 1]]));
+
+    // 2 グループ以上続く最長の 0 の並びを探す
+    let (mut best_start, mut best_len) = (usize::MAX, 0);
+    let mut i = 0;
+    while i < 8 {
+        if groups[i] == 0 {
+            let start = i;
+            while i < 8 && groups[i] == 0 {
+                i += 1;
+            }
+            if i - start > best_len && i - start >= 2 {
+                best_start = start;
+                best_len = i - start;
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < 8 {
+        if i == best_start {
+            out.push_str("::");
+            i += best_len;
+            continue;
+        }
+        if !out.is_empty() && !out.ends_with(':') {
+            out.push(':');
+        }
+        let _ = write!(out, "{:x}", groups[i]);
+        i += 1;
+    }
+    out
+}
+
 /// ソリシテッドノードマルチキャストアドレスの判定
 fn is_solicited_node_multicast_for(multicast: &[u8; 16], target: &[u8; 16]) -> bool {
     let prefix = [0xff, 0x02, 0,0,0,0,0,0, 0,0,0, 0x01, 0xff];
@@ -106,11 +364,11 @@ fn handle_icmpv6(ipv6_header: &Ipv6Header, payload: &[u8]) {
 
     match icmpv6.icmpv6_type {
         ICMPV6_ECHO_REQUEST => {
-            serial_println!("[net] icmpv6: Echo Request received");
+            net_trace!("icmpv6: Echo Request received");
             send_icmpv6_echo_reply(ipv6_header, payload);
         }
         ICMPV6_ECHO_REPLY => {
-            serial_println!("[net] icmpv6: Echo Reply received");
+            net_trace!("icmpv6: Echo Reply received");
             if payload.len() >= 8 {
                 let id = u16::from_be_bytes([payload[4], payload[5]]);
                 let seq = u16::from_be_bytes([payload[6], payload[7]]);
@@ -119,19 +377,20 @@ fn handle_icmpv6(ipv6_header: &Ipv6Header, payload: &[u8]) {
                 });
             }
         }
-        134 => {
-            // ICMPV6_ROUTER_ADVERTISEMENT
-            serial_println!("[net] icmpv6: Router Advertisement received (ignored)");
+        ICMPV6_ROUTER_ADVERTISEMENT => {
+            net_trace!("icmpv6: Router Advertisement received");
+            handle_router_advertisement(ipv6_header, payload);
         }
         ICMPV6_NEIGHBOR_SOLICITATION => {
-            serial_println!("[net] icmpv6: Neighbor Solicitation received");
+            net_trace!("icmpv6: Neighbor Solicitation received");
             handle_ndp_neighbor_solicitation(ipv6_header, payload);
         }
         ICMPV6_NEIGHBOR_ADVERTISEMENT => {
-            serial_println!("[net] icmpv6: Neighbor Advertisement received (ignored)");
+            net_trace!("icmpv6: Neighbor Advertisement received");
+            handle_ndp_neighbor_advertisement(ipv6_header, payload);
         }
         _ => {
-            serial_println!("[net] icmpv6: unknown type {}", icmpv6.icmpv6_type);
+            net_debug!("icmpv6: unknown type {}", icmpv6.icmpv6_type);
         }
     }
 }
@@ -149,15 +408,21 @@ fn send_icmpv6_echo_reply(request_ipv6: &Ipv6Header, icmpv6_data: &[u8]) {
     reply_payload.push(0);
     reply_payload.extend_from_slice(&icmpv6_data[4..]);
 
-    let checksum = calculate_icmpv6_checksum(&MY_IPV6, &request_ipv6.src_ip, &reply_payload);
+    // 宛てられた自分のアドレス（リンクローカル等）から返す。マルチキャスト宛てなら通常のアドレス
+    let src_ip = if is_my_address(&request_ipv6.dst_ip) {
+        request_ipv6.dst_ip
+    } else {
+        get_my_ipv6()
+    };
+    let checksum = calculate_icmpv6_checksum(&src_ip, &request_ipv6.src_ip, &reply_payload);
     reply_payload[2] = (checksum >> 8) as u8;
     reply_payload[3] = (checksum & 0xFF) as u8;
 
-    send_ipv6_packet(&request_ipv6.src_ip, IP_PROTO_ICMPV6, &reply_payload);
+    send_ipv6_packet_from(&src_ip, &request_ipv6.src_ip, IP_PROTO_ICMPV6, DEFAULT_HOP_LIMIT, &reply_payload);
 }
 
 /// NDP Neighbor Solicitation を処理する
-fn handle_ndp_neighbor_solicitation(_ipv6_header: &Ipv6Header, payload: &[u8]) {
+fn handle_ndp_neighbor_solicitation(ipv6_header: &Ipv6Header, payload: &[u8]) {
     if payload.len() < 24 {
         return;
     }
@@ -165,12 +430,163 @@ fn handle_ndp_neighbor_solicitation(_ipv6_header: &Ipv6Header, payload: &[u8]) {
     let mut target = [0u8; 16];
     target.copy_from_slice(&payload[8..24]);
 
-    if target != MY_IPV6 {
-        serial_println!("[net] ndp: NS target is not MY_IPV6, ignoring");
+    if !is_my_address(&target) {
+        net_debug!("ndp: NS target is not my address, ignoring");
+        return;
+    }
+
+    // 問い合わせてきた相手の MAC を学習する（ARP Request から送信元を学習するのと同じ）。
+    // 送信元が未指定アドレス (::) の場合は重複アドレス検出なので学習しない。
+    let source_mac = find_link_layer_option(&payload[24..], NDP_OPT_SOURCE_LL_ADDR);
+    if let Some(mac) = source_mac.filter(|_| ipv6_header.src_ip != [0u8; 16]) {
+        ndp_update(ipv6_header.src_ip, mac);
+    }
+
+    send_ndp_neighbor_advertisement(&target, &ipv6_header.src_ip);
+}
+
+/// NDP Neighbor Advertisement を処理して近隣キャッシュに学習する
+///
+/// NA 本体は 24 バイト（type, code, checksum, flags, reserved, target）で、
+/// その後ろの Target Link-Layer Address オプションが target の MAC になる。
+fn handle_ndp_neighbor_advertisement(ipv6_header: &Ipv6Header, payload: &[u8]) {
+    if ipv6_header.hop_limit != NDP_HOP_LIMIT {
+        net_debug!("ndp: NA with hop limit {} ignored", ipv6_header.hop_limit);
+        return;
+    }
+    if payload.len() < 24 {
+        return;
+    }
+
+    let mut target = [0u8; 16];
+    target.copy_from_slice(&payload[8..24]);
+    // マルチキャストアドレスが target の NA は不正
+    if target[0] == 0xff {
         return;
     }
 
-    send_ndp_neighbor_advertisement(&target, &_ipv6_header.src_ip);
+    match find_link_layer_option(&payload[24..], NDP_OPT_TARGET_LL_ADDR) {
+        Some(mac) => {
+            net_debug!("ndp: {} is {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                format_ipv6(&target), mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]);
+            ndp_update(target, mac);
+        }
+        None => {
+            net_debug!("ndp: NA without target link-layer address, ignoring");
+        }
+    }
+}
+
+/// NDP オプション列から指定種別の Link-Layer Address (MAC) を取り出す
+///
+/// オプションは TLV 形式で、長さは 8 バイト単位。
+/// Ethernet の場合は type(1) + len(1) + MAC(6) のちょうど 8 バイトになる。
+fn find_link_layer_option(options: &[u8], opt_type: u8) -> Option<[u8; 6]> {
+    let mut offset = 0;
+    while offset + 2 <= options.len() {
+        let len = options[offset + 1] as usize * 8;
+        if len == 0 || offset + len > options.len() {
+            // 長さ 0 のオプションは不正（無限ループ防止のため打ち切る）
+            return None;
+        }
+        if options[offset] == opt_type && len >= 8 {
+            let mut mac = [0u8; 6];
+            mac.copy_from_slice(&options[offset + 2..offset + 8]);
+            return Some(mac);
+        }
+        offset += len;
+    }
+    None
+}
+
+/// NDP Neighbor Solicitation を送信する
+///
+/// target のソリシテッドノードマルチキャストアドレス宛てに、
+/// 自分の MAC を Source Link-Layer Address オプションに入れて送る。
+fn send_ndp_neighbor_solicitation(target: &[u8; 16]) {
+    let my_mac = get_my_mac();
+    // リンクローカル宛ての問い合わせはリンクローカルアドレスから送る
+    let src_ip = if is_link_local(target) { link_local_address() } else { get_my_ipv6() };
+    let dst_ip = [
+        0xff, 0x02, 0,0,0,0,0,0, 0,0,0, 0x01, 0xff,
+        target[13], target[14], target[15],
+    ];
+
+    let mut ns_payload = Vec::with_capacity(32);
+    ns_payload.push(ICMPV6_NEIGHBOR_SOLICITATION);
+    ns_payload.push(0);
+    ns_payload.push(0);
+    ns_payload.push(0);
+    // Reserved
+    ns_payload.extend_from_slice(&[0, 0, 0, 0]);
+    ns_payload.extend_from_slice(target);
+    // Option: Source Link-Layer Address
+    ns_payload.push(NDP_OPT_SOURCE_LL_ADDR);
+    ns_payload.push(1);
+    ns_payload.extend_from_slice(&my_mac);
+
+    let checksum = calculate_icmpv6_checksum(&src_ip, &dst_ip, &ns_payload);
+    ns_payload[2] = (checksum >> 8) as u8;
+    ns_payload[3] = (checksum & 0xFF) as u8;
+
+    net_debug!("ndp: sending Neighbor Solicitation for {}", format_ipv6(target));
+    send_ipv6_packet_from(&src_ip, &dst_ip, IP_PROTO_ICMPV6, NDP_HOP_LIMIT, &ns_payload);
+}
+
+/// リンクローカルアドレス (fe80::/10) かどうか
+fn is_link_local(ip: &[u8; 16]) -> bool {
+    ip[0] == 0xfe && (ip[1] & 0xc0) == 0x80
+}
+
+/// 宛先に届けるために MAC を解決すべき次ホップのアドレスを返す
+///
+/// リンクローカル宛てと、自分のアドレスと同じ /64 の宛先は直接届く。
+/// それ以外は RA で得たデフォルトルーター経由にする（ルーター未設定ならそのまま）。
+fn next_hop(dst_ip: &[u8; 16]) -> [u8; 16] {
+    if is_link_local(dst_ip) || dst_ip[..8] == get_my_ipv6()[..8] {
+        return *dst_ip;
+    }
+    get_ipv6_router().unwrap_or(*dst_ip)
+}
+
+/// 送信フレームの宛先 MAC を決める（ブロッキングしない）
+///
+/// マルチキャスト宛ては 33:33 + アドレス下位 32bit の MAC に送る（RFC 2464）。
+/// ユニキャストは近隣キャッシュを引き、ミスならブロードキャストで送る。
+/// net_poller タスクから呼ばれる場合があるため、ブロッキングする
+/// resolve_neighbor() は使えない。必要なら呼び出し元で事前に解決しておくこと。
+fn neighbor_mac(dst_ip: &[u8; 16]) -> [u8; 6] {
+    if dst_ip[0] == 0xff {
+        return [0x33, 0x33, dst_ip[12], dst_ip[13], dst_ip[14], dst_ip[15]];
+    }
+    ndp_lookup(&next_hop(dst_ip)).unwrap_or(BROADCAST_MAC)
+}
+
+/// 宛先 IPv6 アドレスに対応する MAC アドレスを解決する（IPv6 版 resolve_mac）
+///
+/// 1. マルチキャスト → 33:33:xx:xx:xx:xx
+/// 2. サブネット外 → デフォルトルーターを解決対象にする
+/// 3. 近隣キャッシュを検索 → ヒットすれば返す
+/// 4. ミスなら Neighbor Solicitation を送信し、Advertisement を待つ（最大 3 回リトライ）
+pub fn resolve_neighbor(dst_ip: &[u8; 16]) -> Result<[u8; 6], &'static str> {
+    if dst_ip[0] == 0xff {
+        return Ok(neighbor_mac(dst_ip));
+    }
+
+    let resolve_ip = next_hop(dst_ip);
+    if let Some(mac) = ndp_lookup(&resolve_ip) {
+        return Ok(mac);
+    }
+
+    for _ in 0..NS_MAX_RETRIES {
+        send_ndp_neighbor_solicitation(&resolve_ip);
+
+        if let Some(mac) = wait_net_condition(NA_TIMEOUT_MS, || ndp_lookup(&resolve_ip)) {
+            return Ok(mac);
+        }
+    }
+
+    Err("NDP resolve timeout")
 }
 
 /// NDP Neighbor Advertisement を送信する
@@ -193,21 +609,58 @@ fn send_ndp_neighbor_advertisement(target: &[u8; 16], dst_ip: &[u8; 16]) {
     na_payload.extend_from_slice(target);
 
     // Option: Target Link-Layer Address
-    na_payload.push(2);
+    na_payload.push(NDP_OPT_TARGET_LL_ADDR);
     na_payload.push(1);
     na_payload.extend_from_slice(&my_mac);
 
-    let checksum = calculate_icmpv6_checksum(&MY_IPV6, dst_ip, &na_payload);
+    // 問い合わせられたアドレス自身を送信元にする
+    let checksum = calculate_icmpv6_checksum(target, dst_ip, &na_payload);
     na_payload[2] = (checksum >> 8) as u8;
     na_payload[3] = (checksum & 0xFF) as u8;
 
-    send_ipv6_packet(dst_ip, IP_PROTO_ICMPV6, &na_payload);
+    send_ipv6_packet_from(target, dst_ip, IP_PROTO_ICMPV6, NDP_HOP_LIMIT, &na_payload);
 }
 
-/// IPv6 パケットを送信する
-fn send_ipv6_packet(dst_ip: &[u8; 16], next_header: u8, payload: &[u8]) {
+/// IPv6 パケットを自分のアドレスから送信する
+pub(super) fn send_ipv6_packet(dst_ip: &[u8; 16], next_header: u8, payload: &[u8]) {
+    send_ipv6_packet_from(&get_my_ipv6(), dst_ip, next_header, DEFAULT_HOP_LIMIT, payload);
+}
+
+/// 送信元アドレスと Hop Limit を指定して IPv6 パケットを送信する
+///
+/// NDP はリンクローカルアドレスから Hop Limit 255 で送る必要があるため分けている。
+fn send_ipv6_packet_from(
+    src_ip: &[u8; 16],
+    dst_ip: &[u8; 16],
+    next_header: u8,
+    hop_limit: u8,
+    payload: &[u8],
+) {
+    let packet = build_ipv6_frame_with_hop_limit(src_ip, dst_ip, next_header, hop_limit, payload);
+    if send_frame(&packet).is_err() {
+        net_debug!("ipv6: failed to send packet");
+    } else {
+        net_trace!("ipv6: sent packet, next_header={}, len={}", next_header, payload.len());
+    }
+}
+
+/// Ethernet + IPv6 ヘッダーを付けたフレームを組み立てる（Hop Limit は既定値）
+///
+/// 送信せずにフレームだけ返すので、TCP のように自分宛を
+/// ソフトウェアループバックで handle_packet に渡したい呼び出し元が使う。
+pub(super) fn build_ipv6_frame(src_ip: &[u8; 16], dst_ip: &[u8; 16], next_header: u8, payload: &[u8]) -> Vec<u8> {
+    build_ipv6_frame_with_hop_limit(src_ip, dst_ip, next_header, DEFAULT_HOP_LIMIT, payload)
+}
+
+fn build_ipv6_frame_with_hop_limit(
+    src_ip: &[u8; 16],
+    dst_ip: &[u8; 16],
+    next_header: u8,
+    hop_limit: u8,
+    payload: &[u8],
+) -> Vec<u8> {
     let my_mac = get_my_mac();
-    let dst_mac = BROADCAST_MAC;
+    let dst_mac = neighbor_mac(dst_ip);
 
     let eth_header = EthernetHeader {
         dst_mac,
@@ -219,8 +672,8 @@ fn send_ipv6_packet(dst_ip: &[u8; 16], next_header: u8, payload: &[u8]) {
         version_tc_fl: [0x60, 0x00, 0x00, 0x00],
         payload_length: (payload.len() as u16).to_be_bytes(),
         next_header,
-        hop_limit: 64,
-        src_ip: MY_IPV6,
+        hop_limit,
+        src_ip: *src_ip,
         dst_ip: *dst_ip,
     };
 
@@ -235,12 +688,7 @@ fn send_ipv6_packet(dst_ip: &[u8; 16], next_header: u8, payload: &[u8]) {
     });
 
     packet.extend_from_slice(payload);
-
-    if send_frame(&packet).is_err() {
-        serial_println!("[net] ipv6: failed to send packet");
-    } else {
-        serial_println!("[net] ipv6: sent packet, next_header={}, len={}", next_header, payload.len());
-    }
+    packet
 }
 
 /// ICMPv6 チェックサムを計算する（IPv6 疑似ヘッダー含む）
@@ -249,19 +697,32 @@ fn calculate_icmpv6_checksum(
     dst_ip: &[u8; 16],
     icmpv6_data: &[u8],
 ) -> u16 {
-    let icmpv6_len = icmpv6_data.len();
+    calculate_ipv6_checksum(src_ip, dst_ip, IP_PROTO_ICMPV6, icmpv6_data)
+}
+
+/// 上位プロトコル（ICMPv6 / UDP / TCP）のチェックサムを IPv6 疑似ヘッダー込みで計算する
+///
+/// 疑似ヘッダー: 送信元 (16) + 宛先 (16) + 上位層の長さ (4) + ゼロ (3) + Next Header (1)。
+/// チェックサム欄を含めたまま計算すると、正しいパケットなら 0 になる。
+pub(super) fn calculate_ipv6_checksum(
+    src_ip: &[u8; 16],
+    dst_ip: &[u8; 16],
+    next_header: u8,
+    upper_data: &[u8],
+) -> u16 {
+    let upper_len = upper_data.len();
 
-    let mut data = Vec::with_capacity(40 + icmpv6_len);
+    let mut data = Vec::with_capacity(40 + upper_len);
 
     data.extend_from_slice(src_ip);
     data.extend_from_slice(dst_ip);
-    data.extend_from_slice(&(icmpv6_len as u32).to_be_bytes());
+    data.extend_from_slice(&(upper_len as u32).to_be_bytes());
     data.push(0);
     data.push(0);
     data.push(0);
-    data.push(IP_PROTO_ICMPV6);
+    data.push(next_header);
 
-    data.extend_from_slice(icmpv6_data);
+    data.extend_from_slice(upper_data);
 
     calculate_checksum(&data)
 }
@@ -272,6 +733,10 @@ pub fn send_icmpv6_echo_request(dst_ip: &[u8; 16], id: u16, seq: u16) {
         state.icmpv6_echo_reply = None;
     });
 
+    // 送信経路ではブロッキングできないので、ここで近隣キャッシュを温めておく。
+    // 解決できなくてもブロードキャストで送るので結果は無視する。
+    let _ = resolve_neighbor(dst_ip);
+
     let mut echo_payload = Vec::with_capacity(16);
     echo_payload.push(ICMPV6_ECHO_REQUEST);
     echo_payload.push(0);
@@ -281,7 +746,7 @@ pub fn send_icmpv6_echo_request(dst_ip: &[u8; 16], id: u16, seq: u16) {
     echo_payload.extend_from_slice(&seq.to_be_bytes());
     echo_payload.extend_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF, 0xCA, 0xFE, 0xBA, 0xBE]);
 
-    let checksum = calculate_icmpv6_checksum(&MY_IPV6, dst_ip, &echo_payload);
+    let checksum = calculate_icmpv6_checksum(&get_my_ipv6(), dst_ip, &echo_payload);
     echo_payload[2] = (checksum >> 8) as u8;
     echo_payload[3] = (checksum & 0xFF) as u8;
 
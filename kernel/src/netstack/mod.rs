@@ -22,29 +22,70 @@
 // net_poller_task(): NIC ロック→受信→ロック解放→handle_packet() の順。
 // handle_packet() 内の send_arp_reply() 等: NET_STATE から MAC 取得→ロック解放→NIC でフレーム送信。
 // MAC アドレスは初期化時に MY_MAC に保持し、NET_STATE のロック不要。
+// どうしても両方持つときは NET_STATE → NIC の順。debug-locks フィーチャでビルドすると
+// lock_order.rs がこの順序（DNS_RESOLVER → NET_STATE → NIC）を検査する。
+
+// ログマクロはサブモジュール（arp.rs 等）からも使うので、mod 宣言より前に定義する
+
+/// ネットワークスタックのログマクロ
+///
+/// 重要なイベント（接続確立、accept、エラー等）をシリアルに出力する。
+/// `loglevel net info` などで止められる。
+macro_rules! net_debug {
+    ($($arg:tt)*) => {
+        $crate::klog!(Debug, Net, $($arg)*)
+    };
+}
+
+/// パケット単位の詳細トレースログ（デフォルト無効）
+///
+/// 大量のシリアル出力が発生するので、`loglevel net trace` で有効にしたときだけ出す。
+macro_rules! net_trace {
+    ($($arg:tt)*) => {
+        $crate::klog!(Trace, Net, $($arg)*)
+    };
+}
 
 mod types;
 mod arp;
 mod icmp;
+mod ipv4;
 mod tcp;
 mod udp;
 mod dns;
 mod ipv6;
 mod dhcp;
+mod capture;
+mod sockopt;
+mod watchdog;
 
 // Re-exports for external use
-pub use types::{TcpConnection, UnackedPacket, TcpState};
-pub use arp::resolve_mac;
-pub use tcp::{tcp_connect, tcp_listen, tcp_accept, tcp_send, tcp_recv, tcp_close};
-pub use udp::{udp_bind, udp_send_to, udp_recv_from, udp_close, udp_local_port};
-pub use dns::dns_lookup;
-pub use ipv6::{send_icmpv6_echo_request, wait_icmpv6_echo_reply};
+pub use types::{TcpConnection, UnackedPacket, TcpState, IpAddr};
+pub use arp::{build_arp_request, announce_address, resolve_mac, test_gratuitous_arp};
+pub use tcp::{
+    tcp_connect, tcp_connect6, tcp_listen, tcp_unlisten, tcp_accept, tcp_try_accept, tcp_send, tcp_recv, tcp_try_recv,
+    tcp_close, tcp_shutdown, tcp_peer_addr, tcp_local_addr, tcp_mss, tcp_set_nodelay, TcpShutdown, test_tcp_drain, test_tcp_half_close,
+    test_tcp_connect6, test_tcp6_host_echo, test_tcp_loopback, test_tcp_window_scale, test_tcp_nagle, test_tcp_linger_abort,
+    test_tcp_backlog,
+};
+pub use udp::{
+    udp_bind, udp_bind_reuse, udp_send_to, udp_recv_from, udp_try_recv_from, udp_close, udp_local_port, udp_socket_count,
+    build_udp_datagram_v6, parse_udp_datagram_v6, test_udp_loopback,
+};
+pub use dns::{dns_lookup, test_dns_routing};
+pub use ipv6::{send_icmpv6_echo_request, wait_icmpv6_echo_reply, eui64_interface_id, slaac_address, slaac_configure, format_ipv6, resolve_neighbor};
 pub use dhcp::dhcp_discover;
+pub use capture::{capture_open, capture_close, capture_read, test_capture};
+pub use ipv4::{mtu, set_mtu, test_ipv4_fragment_overlap, test_ipv4_fragmentation};
+pub use sockopt::{set_socket_option, get_socket_option, test_sockopt};
+pub use watchdog::{net_watchdog_task, test_net_watchdog};
 
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use spin::Mutex;
 
+use crate::lock_order::{LockClass, OrderedMutex};
 use crate::net_config::get_my_ip;
 use crate::serial_println;
 
@@ -54,61 +95,17 @@ pub const LOOPBACK_IP: [u8; 4] = [127, 0, 0, 1];
 /// ブロードキャスト MAC アドレス
 pub const BROADCAST_MAC: [u8; 6] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
 
-/// ゲストの IPv6 アドレス (QEMU SLIRP デフォルト: fec0::15)
+/// ゲストの IPv6 アドレスのフォールバック値 (QEMU SLIRP デフォルト: fec0::15)
+///
+/// 通常は SLAAC で Router Advertisement のプレフィックス + EUI-64 から
+/// アドレスを作る。RA が来なかった場合だけこの値を使う。
+/// 現在のアドレスは net_config::get_my_ipv6() で取得すること。
 pub const MY_IPV6: [u8; 16] = [0xfe, 0xc0, 0,0,0,0,0,0, 0,0,0,0,0,0,0, 0x15];
 
 /// 自分の MAC アドレス（初期化時に設定、以降変更なし）
 /// NET_STATE のロックなしでアクセスできるよう別のグローバル変数に保持する。
 static MY_MAC: Mutex<[u8; 6]> = Mutex::new([0u8; 6]);
 
-/// ネットワークスタックのログマクロ
-///
-/// 重要なイベント（接続確立、accept、エラー等）をシリアルに出力する。
-macro_rules! net_debug {
-    ($($arg:tt)*) => {{
-        serial_println!("[net] {}", format_args!($($arg)*));
-    }};
-}
-
-/// パケット単位の詳細トレースログ（デフォルト無効）
-///
-/// 有効にするにはコメントを外す。大量のシリアル出力が発生するため
-/// 通常はデバッグ時のみ使用する。
-#[allow(unused_macros)]
-macro_rules! net_trace {
-    ($($arg:tt)*) => {{
-        // serial_println!("[net-trace] {}", format_args!($($arg)*));
-    }};
-}
-
-// ============================================================
-// カーネル内乱数生成
-// ============================================================
-
-/// カーネル内で RDRAND 命令を使って 64 ビット乱数を取得する。
-/// TCP ISN や DNS クエリ ID のランダム化に使用する。
-/// 失敗時は簡易フォールバック（0 を返す）。
-pub(super) fn kernel_rdrand64() -> u64 {
-    for _ in 0..10 {
-        let value: u64;
-        let success: u8;
-        unsafe {
-            core::arch::asm!(
-                "rdrand {val}",
-                "setc {ok}",
-                val = out(reg) value,
-                ok = out(reg_byte) success,
-            );
-        }
-        if success != 0 {
-            return value;
-        }
-    }
-    // フォールバック: RDRAND が使えない場合は PIT カウンタなどで代替すべきだが、
-    // 現代の x86_64 CPU では RDRAND が使えない状況は稀なので 0 を返す
-    0
-}
-
 // ============================================================
 // 内部状態
 // ============================================================
@@ -122,8 +119,12 @@ pub struct UdpSocketEntry {
     pub id: u32,
     /// バインドしているローカルポート
     pub local_port: u16,
-    /// 受信キュー: (送信元 IP, 送信元ポート, データ)
-    pub recv_queue: VecDeque<([u8; 4], u16, Vec<u8>)>,
+    /// 受信キュー: (送信元 IP, 送信元ポート, データ)。IPv4 / IPv6 の両方が入る
+    pub recv_queue: VecDeque<udp::UdpDatagram>,
+    /// SO_RCVTIMEO（ミリ秒）。udp_recv_from に timeout_ms = 0 が渡されたときの待ち時間。0 なら既定の 5000ms
+    pub recv_timeout_ms: u64,
+    /// SO_REUSEADDR。true なら同じポートに後から別のソケットが bind できる
+    pub reuse_addr: bool,
 }
 
 /// ARP キャッシュエントリ
@@ -133,22 +134,61 @@ pub struct UdpSocketEntry {
 struct ArpEntry {
     ip: [u8; 4],
     mac: [u8; 6],
+    /// 最後に学習（更新）したときの TIMER_TICK_COUNT
+    updated_tick: u64,
+    /// `arp -s` で登録した静的エントリ。期限切れで消えず、学習でも上書きされない
+    is_static: bool,
 }
 
 /// ARP キャッシュの最大エントリ数
 const ARP_CACHE_MAX: usize = 64;
 
+/// 動的 ARP エントリの有効期限（ティック、約 5 分）
+///
+/// 相手の NIC が交換されたり IP が付け替えられたりしたとき、
+/// 古い MAC を使い続けないように一定時間で捨てて再解決させる。
+const ARP_ENTRY_TIMEOUT_TICKS: u64 = 300_000 / 55;
+
+/// ARP キャッシュの 1 エントリの情報（arp コマンドの表示用）
+#[derive(Debug, Clone, Copy)]
+pub struct ArpCacheInfo {
+    pub ip: [u8; 4],
+    pub mac: [u8; 6],
+    /// 最後に学習してからの経過時間（ミリ秒）
+    pub age_ms: u64,
+    pub is_static: bool,
+}
+
+/// NDP 近隣キャッシュエントリ
+///
+/// IPv6 版の ARP キャッシュ。IPv6 アドレスから MAC アドレスへのマッピングを保持する。
+/// Neighbor Advertisement / Neighbor Solicitation 受信時に学習し、送信時に参照する。
+struct NdpEntry {
+    ip: [u8; 16],
+    mac: [u8; 6],
+    /// 最後に学習（更新）したときの TIMER_TICK_COUNT
+    updated_tick: u64,
+}
+
+/// NDP 近隣キャッシュの最大エントリ数（ARP キャッシュと同じ）
+const NDP_CACHE_MAX: usize = 64;
+
+/// NDP 近隣エントリの有効期限（ティック、ARP と同じ約 5 分）
+///
+/// 近隣到達不能検出（NUD）はしていないので、相手の MAC が変わっても
+/// 期限が切れるまでは古い MAC に送り続ける。一定時間で捨てて NS からやり直させる。
+const NDP_ENTRY_TIMEOUT_TICKS: u64 = ARP_ENTRY_TIMEOUT_TICKS;
+
 /// ネットワークスタックの内部状態
 pub(crate) struct NetState {
     pub(self) mac: [u8; 6],
     pub(self) tcp_connections: Vec<types::TcpConnection>,
     pub(self) tcp_next_id: u32,
     pub(self) tcp_next_port: u16,
-    /// リスン中のポート一覧（複数サービスが同時に listen 可能）
-    pub(self) tcp_listen_ports: Vec<u16>,
+    /// リスン中のポート一覧: (port, backlog)（複数サービスが同時に listen 可能）
+    pub(self) tcp_listen_ports: Vec<(u16, usize)>,
     /// accept 待ちの接続キュー: (conn_id, local_port)
     pub(self) tcp_pending_accept: VecDeque<(u32, u16)>,
-    pub(self) udp_response: Option<(u16, Vec<u8>)>,
     /// UDP ソケット一覧
     pub(self) udp_sockets: Vec<UdpSocketEntry>,
     /// UDP エフェメラルポートの次の候補（49152〜65535）
@@ -163,10 +203,14 @@ pub(crate) struct NetState {
     /// 送信時に宛先 MAC を解決するために使う。
     /// ARP Reply 受信時や ARP Request 受信時（送信元）に学習する。
     pub(self) arp_cache: Vec<ArpEntry>,
+    /// NDP 近隣キャッシュ: IPv6 → MAC のマッピングテーブル
+    /// IPv6 ユニキャスト送信時に宛先 MAC を解決するために使う。
+    /// Neighbor Advertisement 受信時や Neighbor Solicitation 受信時（送信元）に学習する。
+    pub(self) ndp_cache: Vec<NdpEntry>,
 }
 
 /// グローバルなネットワーク状態（spin::Mutex で保護）
-static NET_STATE: Mutex<Option<NetState>> = Mutex::new(None);
+static NET_STATE: OrderedMutex<Option<NetState>> = OrderedMutex::new(LockClass::NetState, None);
 
 /// NET_STATE のロックを取得し、初期化されていなければ初期化してから返す
 pub(crate) fn with_net_state<F, R>(f: F) -> R
@@ -182,12 +226,12 @@ where
             tcp_next_port: 49152,
             tcp_listen_ports: Vec::new(),
             tcp_pending_accept: VecDeque::new(),
-            udp_response: None,
             udp_sockets: Vec::new(),
             udp_next_port: 49152,
             icmpv6_echo_reply: None,
             net_waiters: Vec::new(),
             arp_cache: Vec::new(),
+            ndp_cache: Vec::new(),
         });
     }
     f(guard.as_mut().unwrap())
@@ -230,11 +274,35 @@ pub fn init() {
                 serial_println!("netstack: DHCP failed ({}), using default config", e);
             }
         }
+
+        // IPv6 は SLAAC（Router Solicitation → Advertisement）でアドレスを決める。
+        // RA が来なければフォールバックの MY_IPV6 のまま動く。
+        match slaac_configure() {
+            Ok(addr) => {
+                serial_println!("netstack: SLAAC configured IPv6={}", format_ipv6(&addr));
+            }
+            Err(e) => {
+                serial_println!("netstack: SLAAC failed ({}), using fallback IPv6 {}",
+                    e, format_ipv6(&MY_IPV6));
+            }
+        }
     } else {
         serial_println!("netstack: no network device available, skipping init");
     }
 }
 
+/// シャットダウン・再起動時に TCP 接続の後始末を待つ最大時間（ミリ秒）
+const SHUTDOWN_DRAIN_TIMEOUT_MS: u64 = 1000;
+
+/// 電源を切る・再起動する前にネットワークを行儀よく止める
+///
+/// 開いている TCP 接続に FIN を送り、最大 SHUTDOWN_DRAIN_TIMEOUT_MS だけ
+/// 相手の応答を待つ。黙って電源を切ると相手側に半開きの接続が残るため。
+/// FIN を送った接続の数を返す。
+pub fn shutdown() -> usize {
+    tcp::tcp_shutdown_all(SHUTDOWN_DRAIN_TIMEOUT_MS)
+}
+
 /// ネットワークリンクの状態を返す。
 ///
 /// virtio-net を優先し、なければ e1000e を確認する。
@@ -277,25 +345,193 @@ pub(self) fn arp_lookup(ip: &[u8; 4]) -> Option<[u8; 6]> {
 
 /// ARP キャッシュに IP → MAC のマッピングを追加/更新する
 ///
-/// 既存エントリがあれば MAC を更新する。
-/// キャッシュが満杯（64 エントリ）の場合は最も古いエントリ（先頭）を削除する。
+/// 既存エントリがあれば MAC と学習時刻を更新する（静的エントリはそのまま）。
+/// キャッシュが満杯（64 エントリ）の場合は最も古い動的エントリを削除する。
 pub(self) fn arp_update(ip: [u8; 4], mac: [u8; 6]) {
+    arp_insert(ip, mac, false);
+}
+
+/// ARP キャッシュに静的エントリを登録する（`arp -s`）
+///
+/// 既に動的エントリがあれば静的エントリに置き換える。
+/// キャッシュが静的エントリだけで満杯なら false を返す。
+pub fn arp_set_static(ip: [u8; 4], mac: [u8; 6]) -> bool {
+    arp_insert(ip, mac, true)
+}
+
+fn arp_insert(ip: [u8; 4], mac: [u8; 6], is_static: bool) -> bool {
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
     with_net_state(|state| {
         // 既存エントリを探して更新
-        for entry in state.arp_cache.iter_mut() {
-            if entry.ip == ip {
-                entry.mac = mac;
-                return;
+        if let Some(entry) = state.arp_cache.iter_mut().find(|e| e.ip == ip) {
+            // 静的エントリは ARP の学習では上書きしない（なりすまし対策にもなる）
+            if entry.is_static && !is_static {
+                return true;
             }
+            entry.mac = mac;
+            entry.updated_tick = now;
+            entry.is_static = is_static;
+            return true;
         }
-        // 新規追加（キャッシュが満杯なら先頭を削除）
+        // 新規追加（キャッシュが満杯なら最も古い動的エントリを削除）
         if state.arp_cache.len() >= ARP_CACHE_MAX {
-            state.arp_cache.remove(0);
+            match state.arp_cache.iter().position(|e| !e.is_static) {
+                Some(idx) => {
+                    state.arp_cache.remove(idx);
+                }
+                None => return false,
+            }
+        }
+        state.arp_cache.push(ArpEntry { ip, mac, updated_tick: now, is_static });
+        true
+    })
+}
+
+/// ARP キャッシュからエントリを削除する（`arp -d`）。静的エントリも消せる。
+///
+/// 削除したら true、見つからなければ false を返す。
+pub fn arp_delete(ip: &[u8; 4]) -> bool {
+    with_net_state(|state| {
+        let before = state.arp_cache.len();
+        state.arp_cache.retain(|e| e.ip != *ip);
+        state.arp_cache.len() != before
+    })
+}
+
+/// ARP キャッシュの全エントリを返す（`arp` コマンドの一覧表示用）
+pub fn arp_entries() -> Vec<ArpCacheInfo> {
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    with_net_state(|state| {
+        state
+            .arp_cache
+            .iter()
+            .map(|e| ArpCacheInfo {
+                ip: e.ip,
+                mac: e.mac,
+                age_ms: now.saturating_sub(e.updated_tick) * 55,
+                is_static: e.is_static,
+            })
+            .collect()
+    })
+}
+
+/// 期限切れの動的 ARP エントリを削除する。削除した数を返す。
+///
+/// net_poller が毎周呼ぶ。now を引数にしているのは、selftest で
+/// 「時間が経ったこと」にして期限切れ処理だけを確かめられるようにするため。
+pub fn arp_purge_expired(now: u64) -> usize {
+    with_net_state(|state| {
+        let before = state.arp_cache.len();
+        state.arp_cache.retain(|e| {
+            e.is_static || now.saturating_sub(e.updated_tick) < ARP_ENTRY_TIMEOUT_TICKS
+        });
+        before - state.arp_cache.len()
+    })
+}
+
+/// 静的 ARP エントリが期限切れ処理を生き残ることのテスト
+///
+/// TEST-NET-1 (192.0.2.0/24) のアドレスで動的・静的エントリを 1 つずつ登録し、
+/// 有効期限を過ぎた時刻で arp_purge_expired() を呼ぶ。
+/// 動的エントリだけが消え、静的エントリは残って ARP 学習でも上書きされないことを確認する。
+/// selftest から呼ばれる。
+pub fn test_arp_static_expiry() -> bool {
+    let dynamic_ip = [192, 0, 2, 10];
+    let static_ip = [192, 0, 2, 11];
+    let static_mac = [0x02, 0x00, 0x00, 0x00, 0x00, 0x11];
+
+    arp_update(dynamic_ip, [0x02, 0x00, 0x00, 0x00, 0x00, 0x10]);
+    if !arp_set_static(static_ip, static_mac) {
+        arp_delete(&dynamic_ip);
+        return false;
+    }
+    // 学習で静的エントリの MAC が書き換わらないこと
+    arp_update(static_ip, [0x02, 0xBA, 0xD0, 0x00, 0x00, 0x00]);
+
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    arp_purge_expired(now + ARP_ENTRY_TIMEOUT_TICKS + 1);
+
+    let ok = arp_lookup(&dynamic_ip).is_none() && arp_lookup(&static_ip) == Some(static_mac);
+
+    arp_delete(&dynamic_ip);
+    let deleted = arp_delete(&static_ip);
+    ok && deleted && arp_lookup(&static_ip).is_none()
+}
+
+// ============================================================
+// NDP 近隣キャッシュ操作
+// ============================================================
+
+/// NDP 近隣キャッシュから IPv6 アドレスに対応する MAC アドレスを検索する
+pub(crate) fn ndp_lookup(ip: &[u8; 16]) -> Option<[u8; 6]> {
+    ndp_lookup_at(ip, crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed))
+}
+
+/// NDP 近隣キャッシュに IPv6 → MAC のマッピングを追加/更新する
+fn ndp_update(ip: [u8; 16], mac: [u8; 6]) {
+    ndp_update_at(ip, mac, crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed));
+}
+
+/// 期限切れの近隣エントリを捨てる。
+///
+/// ARP と違って net_poller では回さず、引くときと学習するときに片付ける。
+/// キャッシュは 64 エントリまでなので、そのたびに全体を見ても負担は小さい。
+fn ndp_purge_expired(state: &mut NetState, now: u64) {
+    state.ndp_cache.retain(|e| now.saturating_sub(e.updated_tick) < NDP_ENTRY_TIMEOUT_TICKS);
+}
+
+/// ndp_lookup の本体。now を引数にしているのは、selftest で
+/// 「時間が経ったこと」にして期限切れを確かめられるようにするため。
+fn ndp_lookup_at(ip: &[u8; 16], now: u64) -> Option<[u8; 6]> {
+    with_net_state(|state| {
+        ndp_purge_expired(state, now);
+        state.ndp_cache.iter().find(|e| e.ip == *ip).map(|e| e.mac)
+    })
+}
+
+/// ndp_update の本体。
+///
+/// ARP キャッシュと同じく、既存エントリがあれば MAC と時刻を更新し、
+/// 期限切れを捨てても満杯（64 エントリ）なら最も古いエントリ（先頭）を削除する。
+fn ndp_update_at(ip: [u8; 16], mac: [u8; 6], now: u64) {
+    with_net_state(|state| {
+        ndp_purge_expired(state, now);
+        // 既存エントリを探して更新
+        if let Some(entry) = state.ndp_cache.iter_mut().find(|e| e.ip == ip) {
+            entry.mac = mac;
+            entry.updated_tick = now;
+            return;
         }
-        state.arp_cache.push(ArpEntry { ip, mac });
+        // 新規追加（キャッシュが満杯なら先頭を削除）
+        if state.ndp_cache.len() >= NDP_CACHE_MAX {
+            state.ndp_cache.remove(0);
+        }
+        state.ndp_cache.push(NdpEntry { ip, mac, updated_tick: now });
     });
 }
 
+/// NDP 近隣エントリの期限切れのテスト
+///
+/// ドキュメント用プレフィックス (2001:db8::/32) のアドレスを学習させ、
+/// 有効期限の直前には引けて、過ぎた時刻で引くと消えていることを確認する。
+/// 期限切れのエントリは引いたときにキャッシュからも取り除かれる。
+/// selftest から呼ばれる。
+pub fn test_ndp_expiry() -> bool {
+    let ip = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10];
+    let mac = [0x02, 0x00, 0x00, 0x00, 0x06, 0x10];
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+
+    ndp_update_at(ip, mac, now);
+    let fresh = ndp_lookup_at(&ip, now + NDP_ENTRY_TIMEOUT_TICKS - 1) == Some(mac);
+    let expired = ndp_lookup_at(&ip, now + NDP_ENTRY_TIMEOUT_TICKS).is_none();
+    let removed = with_net_state(|state| {
+        let gone = !state.ndp_cache.iter().any(|e| e.ip == ip);
+        state.ndp_cache.retain(|e| e.ip != ip);
+        gone
+    });
+    fresh && expired && removed
+}
+
 // ============================================================
 // フレーム送受信ヘルパー（NIC 抽象化）
 // ============================================================
@@ -309,6 +545,7 @@ pub(self) fn arp_update(ip: [u8; 4], mac: [u8; 6]) {
 /// virtio-net を優先し、なければ e1000e にフォールバックする。
 /// どちらも存在しなければエラーを返す。
 pub(self) fn send_frame(data: &[u8]) -> Result<(), &'static str> {
+    capture::capture_frame(sabos_syscall::CAPTURE_DIR_TX, data);
     // virtio-net を優先（QEMU デフォルト）
     {
         let mut drv = crate::virtio_net::VIRTIO_NET.lock();
@@ -348,6 +585,65 @@ fn recv_frame_nonblocking() -> Option<Vec<u8>> {
     None
 }
 
+/// NIC の受信リング統計
+///
+/// 各 NIC ドライバが持ち、/proc/net で公開する。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NicRxStats {
+    /// 上位層に渡したフレーム数
+    pub packets: u64,
+    /// 受信リングがあふれた回数（回復するまでを 1 回と数える）
+    pub overflows: u64,
+    /// 捨てたフレーム数（ドライバが破棄したもの + NIC が数えたもの）
+    pub dropped: u64,
+    /// あふれた受信リングを立て直した回数
+    pub recoveries: u64,
+}
+
+/// 受信リングのあふれをログに出したか（最初の 1 回だけ出す）
+static RX_OVERFLOW_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// 使用中の NIC の名前と受信統計を返す（NIC がなければ None）
+pub fn nic_rx_stats() -> Option<(&'static str, NicRxStats)> {
+    if let Some(d) = crate::virtio_net::VIRTIO_NET.lock().as_ref() {
+        return Some(("virtio-net", d.rx_stats()));
+    }
+    if let Some(d) = crate::e1000e::E1000E.lock().as_ref() {
+        return Some(("e1000e", d.rx_stats()));
+    }
+    None
+}
+
+/// 受信キューを取り出し終えた後に、あふれた受信リングを立て直す
+///
+/// net_poller が長く眠らされるとリングが埋まってフレームが捨てられる。
+/// パケット単位でログを出すとシリアルがあふれるので、最初の 1 回だけ出す。
+fn service_rx_ring() {
+    let stats = {
+        let mut drv = crate::virtio_net::VIRTIO_NET.lock();
+        drv.as_mut().map(|d| {
+            d.recover_rx();
+            d.rx_stats()
+        })
+    };
+    let stats = stats.or_else(|| {
+        let mut drv = crate::e1000e::E1000E.lock();
+        drv.as_mut().map(|d| {
+            d.recover_rx();
+            d.rx_stats()
+        })
+    });
+    let Some(stats) = stats else {
+        return;
+    };
+    if stats.overflows > 0 && !RX_OVERFLOW_LOGGED.swap(true, Ordering::Relaxed) {
+        net_debug!(
+            "rx ring overflow: frames were dropped while net_poller was starved (dropped={}, see /proc/net)",
+            stats.dropped
+        );
+    }
+}
+
 /// NIC デバイスのイベントフラグをクリアする
 ///
 /// virtio-net の場合は ISR ステータスを読み取って QEMU のイベントループをキックする。
@@ -441,6 +737,21 @@ pub(self) fn is_local_ip(ip: &[u8; 4]) -> bool {
     *ip == get_my_ip() || *ip == LOOPBACK_IP || *ip == [255, 255, 255, 255]
 }
 
+/// NIC を通さずソフトウェアループバックで届ける宛先か（自分の IP か 127.0.0.1）
+///
+/// TCP / UDP の送信はこの宛先のフレームを send_frame() ではなく handle_packet() に渡す。
+fn is_loopback_address(ip: &[u8; 4]) -> bool {
+    *ip == get_my_ip() || *ip == LOOPBACK_IP
+}
+
+/// 宛先に合わせた送信元 IPv4 アドレス
+///
+/// 127.0.0.1 宛は 127.0.0.1 から送る。受け手が返信の宛先にも 127.0.0.1 を使うので、
+/// 返信もループバックを通り、送信側の接続の相手アドレスとも一致する。
+fn source_ip_for(dst_ip: &[u8; 4]) -> [u8; 4] {
+    if *dst_ip == LOOPBACK_IP { LOOPBACK_IP } else { get_my_ip() }
+}
+
 // ============================================================
 // チェックサム計算
 // ============================================================
@@ -567,14 +878,13 @@ where
 
     register_net_waiter();
 
-    let start_tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+    let deadline = crate::scheduler::deadline_after_ms(timeout_ms);
 
     loop {
-        // 1 ティック（約 55ms）後に自動起床するようスリープ設定。
+        // 1 ティック（約 55ms）ごとに起きて条件を見直す。
         // net_poller が wake_task を呼べばそれより早く起きる。
-        let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-        crate::scheduler::set_current_sleeping(now + 1);
-        crate::scheduler::yield_now();
+        let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+        crate::scheduler::sleep_until((now + 1).min(deadline));
 
         // 起床後に条件チェック
         if let Some(result) = check_fn() {
@@ -583,29 +893,47 @@ where
         }
 
         // タイムアウトチェック
-        let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-        let elapsed_ticks = now.saturating_sub(start_tick);
-        let elapsed_ms = elapsed_ticks * 55;
-        if elapsed_ms >= timeout_ms {
+        if crate::scheduler::deadline_reached(deadline) {
             unregister_net_waiter();
             return None;
         }
     }
 }
 
+/// net_poller タスクの ID（起動前は 0）。MSI-X 割り込みから起こすために使う。
+static NET_POLLER_TASK_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 受信割り込みが来たがまだ net_poller が処理していないことを示すフラグ。
+/// スリープに入る直前に割り込みが来たときの取りこぼしを防ぐ。
+static NET_RX_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// NIC の受信割り込み（MSI-X）から呼ばれ、net_poller を起床させる。
+pub fn notify_rx_interrupt() {
+    NET_RX_PENDING.store(true, Ordering::Release);
+    let id = NET_POLLER_TASK_ID.load(Ordering::Relaxed);
+    if id != 0 {
+        crate::scheduler::wake_task(id);
+    }
+}
+
 /// ネットワークパケットを受信・処理する専用カーネルタスク
 ///
 /// 無限ループでパケットを受信し、handle_packet() で処理する。
 /// パケット処理後は全 waiter を起床させて条件チェックを促す。
-/// パケットがないときは enable_and_hlt() で CPU を省電力モードにする
-/// （QEMU SLIRP のイベントループ処理にも必要）。
+/// パケットがないときは、NIC が MSI-X を使っていれば受信割り込みまで
+/// スリープし（他のタスクに CPU を譲る）、そうでなければ enable_and_hlt() で
+/// CPU を省電力モードにする（QEMU SLIRP のイベントループ処理にも必要）。
 pub fn net_poller_task() {
     net_debug!("net_poller: started");
+    NET_POLLER_TASK_ID.store(crate::scheduler::current_task_id(), Ordering::Relaxed);
     loop {
+        // 生きていることを net_watchdog に知らせる
+        watchdog::heartbeat();
         let mut received = false;
 
         // 受信キューのフレームをすべて処理する
         while let Some(frame) = recv_frame_nonblocking() {
+            capture::capture_frame(sabos_syscall::CAPTURE_DIR_RX, &frame);
             handle_packet(&frame);
             received = true;
         }
@@ -615,6 +943,12 @@ pub fn net_poller_task() {
             wake_all_net_waiters();
         }
 
+        // 受信リングがあふれていたら立て直す
+        service_rx_ring();
+
+        // 期限切れの動的 ARP エントリを捨てる
+        arp_purge_expired(crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed));
+
         // TIME_WAIT 接続の期限切れチェック
         // タイマー期限が来た接続を削除して、ポートを再利用可能にする。
         with_net_state(|state| {
@@ -635,7 +969,7 @@ pub fn net_poller_task() {
         // TCP 再送タイマーチェック
         // デッドラインを超えた未 ACK パケットを再送する。
         // Mutex デッドロック防止のため、再送情報を収集してから Mutex 外で送信する。
-        let retransmit_list: Vec<(u32, [u8; 4], u16, u16, u32, u32, u8, Vec<u8>)> = with_net_state(|state| {
+        let retransmit_list: Vec<(u32, IpAddr, u16, u16, u32, u32, u8, Vec<u8>)> = with_net_state(|state| {
             let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
             let mut list = Vec::new();
             let mut closed_ids = Vec::new();
@@ -695,15 +1029,29 @@ pub fn net_poller_task() {
             wake_all_net_waiters();
         }
 
+        // 期限が来た遅延 ACK を送る
+        tcp::send_delayed_acks(crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed));
+        ipv4::expire_reassembly(crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed));
+
         // NIC デバイスのイベントフラグをクリアする
         // virtio-net: QEMU SLIRP のイベントループをキック
         // e1000e: ICR を読み取って割り込み原因をクリア
         kick_net_device();
 
-        // CPU を一時停止して割り込みを待つ。
-        // QEMU TCG モードでは、CPU がビジーループしていると
-        // SLIRP のネットワーク I/O が処理されないため、
-        // enable_and_hlt() で QEMU に処理時間を与える。
-        x86_64::instructions::interrupts::enable_and_hlt();
+        if crate::virtio_net::uses_msix() {
+            // 受信割り込みが来るまでスリープする。TIME_WAIT や再送タイマーを
+            // 見るために 1 ティックで必ず起きる。スリープを宣言した後にも
+            // フラグを確認し、その間に来た割り込みを取りこぼさないようにする。
+            if !NET_RX_PENDING.swap(false, Ordering::AcqRel) {
+                let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+                crate::scheduler::park_until(now + 1, || NET_RX_PENDING.load(Ordering::Acquire));
+            }
+        } else {
+            // CPU を一時停止して割り込みを待つ。
+            // QEMU TCG モードでは、CPU がビジーループしていると
+            // SLIRP のネットワーク I/O が処理されないため、
+            // enable_and_hlt() で QEMU に処理時間を与える。
+            x86_64::instructions::interrupts::enable_and_hlt();
+        }
     }
 }
@@ -0,0 +1,159 @@
+// sockopt.rs — ソケットオプション（SO_RCVTIMEO / SO_REUSEADDR / SO_LINGER / TCP_NODELAY）
+//
+// TCP の conn_id と UDP の socket_id は同じ ID 空間（alloc_conn_id）なので、
+// sock_id だけで TCP 接続か UDP ソケットかを引ける。
+// SYS_NET_SETSOCKOPT / SYS_NET_GETSOCKOPT（ID ベース API）と
+// 統一ソケット API（socket.rs）の両方がここを通して netstack に反映する。
+//
+// - SO_RCVTIMEO: tcp_recv / udp_recv_from に timeout_ms = 0 が渡されたときの待ち時間
+// - SO_REUSEADDR: UDP ではポートを後から bind したソケットに引き継げるようにする。
+//   TCP は待ち受けポートの重複チェックをしないので、いつでも再利用できる（設定は無視する）
+// - SO_LINGER: close で RST を送って接続をすぐ消す（linger 時間 0 の強制切断、TCP のみ）
+// - TCP_NODELAY: Nagle アルゴリズムを使わない（TCP のみ）
+
+use sabos_syscall::{IPPROTO_TCP, SOL_SOCKET, SO_LINGER, SO_RCVTIMEO, SO_REUSEADDR, TCP_NODELAY};
+
+use super::{with_net_state, UdpSocketEntry};
+use super::types::{find_conn_index_by_id, TcpConnection};
+
+/// sock_id が指しているもの
+enum SocketEntry<'a> {
+    Tcp(&'a mut TcpConnection),
+    Udp(&'a mut UdpSocketEntry),
+}
+
+/// sock_id の TCP 接続か UDP ソケットを探して f に渡す
+fn with_socket_entry<R>(
+    sock_id: u32,
+    f: impl FnOnce(SocketEntry<'_>) -> Result<R, &'static str>,
+) -> Result<R, &'static str> {
+    with_net_state(|state| {
+        if let Some(idx) = find_conn_index_by_id(state, sock_id) {
+            return f(SocketEntry::Tcp(&mut state.tcp_connections[idx]));
+        }
+        match state.udp_sockets.iter_mut().find(|s| s.id == sock_id) {
+            Some(sock) => f(SocketEntry::Udp(sock)),
+            None => Err("no such socket"),
+        }
+    })
+}
+
+/// ソケットオプションを設定する
+///
+/// 知らないオプションは "unsupported option"、UDP ソケットへの TCP オプションは "invalid option"。
+pub fn set_socket_option(sock_id: u32, level: u64, optname: u64, value: u64) -> Result<(), &'static str> {
+    match (level, optname) {
+        (SOL_SOCKET, SO_RCVTIMEO) => with_socket_entry(sock_id, |entry| {
+            match entry {
+                SocketEntry::Tcp(conn) => conn.recv_timeout_ms = value,
+                SocketEntry::Udp(sock) => sock.recv_timeout_ms = value,
+            }
+            Ok(())
+        }),
+        (SOL_SOCKET, SO_REUSEADDR) => with_socket_entry(sock_id, |entry| {
+            if let SocketEntry::Udp(sock) = entry {
+                sock.reuse_addr = value != 0;
+            }
+            Ok(())
+        }),
+        (SOL_SOCKET, SO_LINGER) => with_socket_entry(sock_id, |entry| match entry {
+            SocketEntry::Tcp(conn) => {
+                conn.linger_abort = value != 0;
+                Ok(())
+            }
+            SocketEntry::Udp(_) => Err("invalid option"),
+        }),
+        (IPPROTO_TCP, TCP_NODELAY) => {
+            with_socket_entry(sock_id, |entry| match entry {
+                SocketEntry::Tcp(_) => Ok(()),
+                SocketEntry::Udp(_) => Err("invalid option"),
+            })?;
+            // 貯めていた送信データを流すことがあるので、NET_STATE のロックの外で呼ぶ
+            super::tcp::tcp_set_nodelay(sock_id, value != 0)
+        }
+        _ => Err("unsupported option"),
+    }
+}
+
+/// ソケットオプションの現在値を取得する（フラグは 0 / 1 で返す）
+pub fn get_socket_option(sock_id: u32, level: u64, optname: u64) -> Result<u64, &'static str> {
+    match (level, optname) {
+        (SOL_SOCKET, SO_RCVTIMEO) => with_socket_entry(sock_id, |entry| {
+            Ok(match entry {
+                SocketEntry::Tcp(conn) => conn.recv_timeout_ms,
+                SocketEntry::Udp(sock) => sock.recv_timeout_ms,
+            })
+        }),
+        (SOL_SOCKET, SO_REUSEADDR) => with_socket_entry(sock_id, |entry| {
+            Ok(match entry {
+                SocketEntry::Tcp(_) => 1,
+                SocketEntry::Udp(sock) => sock.reuse_addr as u64,
+            })
+        }),
+        (SOL_SOCKET, SO_LINGER) => with_socket_entry(sock_id, |entry| match entry {
+            SocketEntry::Tcp(conn) => Ok(conn.linger_abort as u64),
+            SocketEntry::Udp(_) => Err("invalid option"),
+        }),
+        (IPPROTO_TCP, TCP_NODELAY) => with_socket_entry(sock_id, |entry| match entry {
+            SocketEntry::Tcp(conn) => Ok(conn.nodelay as u64),
+            SocketEntry::Udp(_) => Err("invalid option"),
+        }),
+        _ => Err("unsupported option"),
+    }
+}
+
+/// ソケットオプションのテスト
+///
+/// UDP ソケットで SO_RCVTIMEO を設定して読み戻し、timeout_ms = 0 の受信が
+/// 既定の 5 秒ではなく設定した時間で諦めることを確かめる。
+/// SO_REUSEADDR 付きのポートにだけ 2 つ目の bind ができることと、
+/// 知らないオプション / UDP への TCP オプションがエラーになることも確認する。
+pub fn test_sockopt() -> bool {
+    use core::sync::atomic::Ordering;
+
+    const PORT: u16 = 40090;
+
+    let Ok(first) = super::udp::udp_bind(PORT) else {
+        return false;
+    };
+    let result = (|| {
+        // SO_RCVTIMEO の設定と読み戻し
+        if set_socket_option(first, SOL_SOCKET, SO_RCVTIMEO, 100).is_err()
+            || get_socket_option(first, SOL_SOCKET, SO_RCVTIMEO) != Ok(100)
+        {
+            return false;
+        }
+        // 何も届かないので、100ms 程度で timeout になるはず（既定の 5000ms より十分短いこと）
+        let start = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+        let timed_out = super::udp::udp_recv_from(first, 0) == Err("timeout");
+        let elapsed_ms = (crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) - start) * 55;
+        if !timed_out || elapsed_ms >= 2000 {
+            return false;
+        }
+
+        // SO_REUSEADDR がなければ同じポートには bind できない
+        if super::udp::udp_bind(PORT).is_ok() {
+            return false;
+        }
+        if set_socket_option(first, SOL_SOCKET, SO_REUSEADDR, 1).is_err()
+            || get_socket_option(first, SOL_SOCKET, SO_REUSEADDR) != Ok(1)
+        {
+            return false;
+        }
+        let Ok(second) = super::udp::udp_bind(PORT) else {
+            return false;
+        };
+        // 2 つ目は SO_REUSEADDR を付けていないので、3 つ目は bind できない
+        let third_rejected = super::udp::udp_bind(PORT).is_err();
+        let reuse_default = get_socket_option(second, SOL_SOCKET, SO_REUSEADDR) == Ok(0);
+        let _ = super::udp::udp_close(second);
+
+        third_rejected
+            && reuse_default
+            && set_socket_option(first, IPPROTO_TCP, TCP_NODELAY, 1) == Err("invalid option")
+            && get_socket_option(first, SOL_SOCKET, 9999) == Err("unsupported option")
+            && get_socket_option(0xFFFF_FFFF, SOL_SOCKET, SO_RCVTIMEO) == Err("no such socket")
+    })();
+    let _ = super::udp::udp_close(first);
+    result
+}
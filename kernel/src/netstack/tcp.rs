@@ -1,29 +1,63 @@
 // tcp.rs — TCP プロトコル処理
 //
 // TCP の 3-way ハンドシェイク、データ送受信、コネクション管理を行う。
+// IPv4 / IPv6 のどちらでも同じ状態遷移を使い、違うのは IP ヘッダーと
+// チェックサムの疑似ヘッダーだけ（接続ごとの相手アドレスは IpAddr で持つ）。
 
 use alloc::vec::Vec;
 
 use crate::net_config::get_my_ip;
-use crate::serial_println;
 
 use super::{
-    BROADCAST_MAC, ETHERTYPE_IPV4, IP_PROTO_TCP,
+    BROADCAST_MAC, IP_PROTO_TCP,
     with_net_state, arp_lookup, get_my_mac, send_frame,
-    is_local_ip, calculate_checksum, wait_net_condition,
-    handle_packet,
+    is_loopback_address, source_ip_for, calculate_checksum, wait_net_condition,
+    handle_packet, NetState,
 };
 use super::types::{
-    EthernetHeader, Ipv4Header, TcpHeader, TcpState, TcpConnection, UnackedPacket,
+    IpAddr, Ipv4Header, TcpHeader, TcpState, TcpConnection, UnackedPacket,
     TCP_FLAG_FIN, TCP_FLAG_SYN, TCP_FLAG_RST, TCP_FLAG_PSH, TCP_FLAG_ACK,
-    TCP_INITIAL_RTO_TICKS,
+    TCP_INITIAL_RTO_TICKS, TCP_RECV_BUFFER_MAX, TCP_RECV_BUFFER_TOTAL_MAX, TCP_WINDOW_SCALE,
+    TCP_MSS, TCP_DELAYED_ACK_TICKS, TCP_DEFAULT_BACKLOG, TCP_MAX_BACKLOG,
     alloc_conn_id, alloc_local_port, find_conn_index_by_id, find_conn_index_by_tuple,
     remove_conn_by_id,
 };
 use super::arp::resolve_mac;
+use super::ipv4::send_ipv4_packet;
+use super::ipv6::{self, Ipv6Header};
 
-/// TCP パケットを処理する
+/// handle_tcp_segment が組み立てる返信: (宛先 IP, 宛先ポート, 送信元ポート, seq, ack, flags)
+type TcpReply = (IpAddr, u16, u16, u32, u32, u8);
+
+/// 送るデータセグメント: (宛先 IP, 宛先ポート, 送信元ポート, seq, ack, ペイロード)
+type DataSegment = (IpAddr, u16, u16, u32, u32, Vec<u8>);
+
+/// IPv4 で届いた TCP パケットを処理する
+///
+/// TCP のチェックサム（疑似ヘッダー込み）を確かめる。正しければ全体の和が 0 になる。
 pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
+    if calculate_tcp_checksum(&ip_header.src_ip, &ip_header.dst_ip, payload) != 0 {
+        net_debug!("tcp: dropped segment with bad checksum");
+        return;
+    }
+    handle_tcp_segment(IpAddr::V4(ip_header.src_ip), payload);
+}
+
+/// IPv6 で届いた TCP パケットを処理する
+///
+/// IPv6 には IP ヘッダーのチェックサムがないので、TCP のチェックサム
+/// （IPv6 疑似ヘッダー込み）を必ず確かめる。正しければ全体の和が 0 になる。
+pub(super) fn handle_tcp_v6(ipv6_header: &Ipv6Header, payload: &[u8]) {
+    let (src_ip, dst_ip) = (ipv6_header.src_ip, ipv6_header.dst_ip);
+    if ipv6::calculate_ipv6_checksum(&src_ip, &dst_ip, IP_PROTO_TCP, payload) != 0 {
+        net_debug!("tcp6: dropped segment with bad checksum");
+        return;
+    }
+    handle_tcp_segment(IpAddr::V6(src_ip), payload);
+}
+
+/// TCP セグメントを処理する（IP のバージョンによらない部分）
+fn handle_tcp_segment(src_ip: IpAddr, payload: &[u8]) {
     if payload.len() < 20 {
         return;
     }
@@ -31,11 +65,12 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
     let tcp_header = unsafe { &*(payload.as_ptr() as *const TcpHeader) };
     let header_len = tcp_header.data_offset();
 
-    if payload.len() < header_len {
+    if header_len < 20 || payload.len() < header_len {
         return;
     }
 
     let tcp_payload = &payload[header_len..];
+    let offers_window_scale = has_window_scale_option(&payload[20..header_len]);
 
     let src_port = tcp_header.src_port_u16();
     let dst_port = tcp_header.dst_port_u16();
@@ -43,54 +78,88 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
     let ack = tcp_header.ack_num_u32();
     let flags = tcp_header.flags;
 
-    serial_println!("[net] tcp: packet from {}:{} -> :{}, seq={}, ack={}, flags={:#04x}, len={}",
-        ip_header.src_ip[0], src_port, dst_port, seq, ack, flags, tcp_payload.len()
+    net_trace!("tcp: packet from {}:{} -> :{}, seq={}, ack={}, flags={:#04x}, len={}",
+        src_ip, src_port, dst_port, seq, ack, flags, tcp_payload.len()
     );
 
-    let mut send_packet: Option<([u8; 4], u16, u16, u32, u32, u8)> = None;
+    let mut send_packet: Option<TcpReply> = None;
+    let mut send_data: Option<DataSegment> = None;
     let mut push_accept: Option<(u32, u16)> = None;
 
     with_net_state(|state| {
-        let idx = find_conn_index_by_tuple(state, ip_header.src_ip, src_port, dst_port);
+        let buffered_total = recv_buffered_total(state);
+        let idx = find_conn_index_by_tuple(state, src_ip, src_port, dst_port);
         if idx.is_none() {
             // リスン中なら SYN を受け付ける
-            serial_println!("[net] tcp: no existing conn, listen_ports={:?}, dst_port={}", state.tcp_listen_ports, dst_port);
-            if state.tcp_listen_ports.contains(&dst_port) && tcp_header.has_flag(TCP_FLAG_SYN) {
-                serial_println!("[net] tcp: accepting SYN on port {}, sending SYN+ACK", dst_port);
-                let id = alloc_conn_id(state);
-                let mut conn = TcpConnection::new(id, dst_port, ip_header.src_ip, src_port);
-                conn.state = TcpState::SynReceived;
-                conn.ack_num = seq + 1;
-                send_packet = Some((
-                    conn.remote_ip,
-                    conn.remote_port,
-                    conn.local_port,
-                    conn.seq_num,
-                    conn.ack_num,
-                    TCP_FLAG_SYN | TCP_FLAG_ACK,
-                ));
-                // SYN-ACK の再送情報を記録する
-                let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-                conn.unacked_packet = Some(UnackedPacket {
-                    seq_num: conn.seq_num,
-                    ack_num: conn.ack_num,
-                    flags: TCP_FLAG_SYN | TCP_FLAG_ACK,
-                    payload: Vec::new(),
-                    retransmit_deadline: now + TCP_INITIAL_RTO_TICKS,
-                    retransmit_count: 0,
-                });
-                state.tcp_connections.push(conn);
+            net_trace!("tcp: no existing conn, listen_ports={:?}, dst_port={}", state.tcp_listen_ports, dst_port);
+            match listen_backlog(state, dst_port) {
+                Some(backlog) if tcp_header.has_flag(TCP_FLAG_SYN) => {
+                    if accept_queue_len(state, dst_port) >= backlog {
+                        // backlog があふれたら RST で断る。相手の connect はすぐ失敗するので、
+                        // 黙って捨てて SYN の再送を待たせるより早く諦められる
+                        net_debug!("tcp: backlog ({}) full on port {}, refusing SYN", backlog, dst_port);
+                        send_packet = Some((src_ip, src_port, dst_port, 0, seq.wrapping_add(1), TCP_FLAG_RST | TCP_FLAG_ACK));
+                    } else {
+                        net_debug!("tcp: accepting SYN on port {}, sending SYN+ACK", dst_port);
+                        let id = alloc_conn_id(state);
+                        let mut conn = TcpConnection::new(id, dst_port, src_ip, src_port);
+                        conn.state = TcpState::SynReceived;
+                        conn.ack_num = seq + 1;
+                        // 相手が提示してきたときだけ SYN-ACK でスケールを返して合意する
+                        conn.window_scale_ok = offers_window_scale;
+                        send_packet = Some((
+                            conn.remote_ip,
+                            conn.remote_port,
+                            conn.local_port,
+                            conn.seq_num,
+                            conn.ack_num,
+                            TCP_FLAG_SYN | TCP_FLAG_ACK,
+                        ));
+                        // SYN-ACK の再送情報を記録する
+                        let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+                        conn.unacked_packet = Some(UnackedPacket {
+                            seq_num: conn.seq_num,
+                            ack_num: conn.ack_num,
+                            flags: TCP_FLAG_SYN | TCP_FLAG_ACK,
+                            payload: Vec::new(),
+                            retransmit_deadline: now + TCP_INITIAL_RTO_TICKS,
+                            retransmit_count: 0,
+                        });
+                        state.tcp_connections.push(conn);
+                    }
+                }
+                _ => {}
             }
         } else {
             let idx = idx.unwrap();
             let conn = &mut state.tcp_connections[idx];
+            // 相手の強制切断（SO_LINGER の close など）。seq が次に受け取る位置と
+            // 一致するものだけ信じる（RFC 5961 の考え方で、推測した RST で切られないようにする）。
+            // SYN_SENT は下の match で「接続拒否」として扱い、TIME_WAIT は RFC 1337 に従って無視する
+            if tcp_header.has_flag(TCP_FLAG_RST) && !matches!(conn.state, TcpState::SynSent | TcpState::TimeWait) {
+                if seq == conn.ack_num {
+                    net_debug!("tcp: connection reset by peer on port {}", conn.local_port);
+                    if conn.state == TcpState::SynReceived {
+                        // まだ accept 待ちにも入っていないので、誰も close しない。ここで消す
+                        state.tcp_connections.remove(idx);
+                    } else {
+                        conn.state = TcpState::Closed;
+                        conn.unacked_packet = None;
+                        conn.send_pending.clear();
+                        clear_delayed_ack(conn);
+                    }
+                }
+                return;
+            }
             match conn.state {
                 TcpState::SynSent => {
                     if tcp_header.has_flag(TCP_FLAG_SYN) && tcp_header.has_flag(TCP_FLAG_ACK) {
-                        serial_println!("[net] tcp: received SYN-ACK");
+                        net_debug!("tcp: received SYN-ACK");
                         if ack == conn.seq_num + 1 {
                             conn.seq_num = ack;
                             conn.ack_num = seq + 1;
+                            // こちらは SYN で必ず提示しているので、SYN-ACK に載っていれば合意成立
+                            conn.window_scale_ok = offers_window_scale;
                             conn.state = TcpState::Established;
                             conn.unacked_packet = None; // SYN が ACK されたのでクリア
                             send_packet = Some((
@@ -101,10 +170,10 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
                                 conn.ack_num,
                                 TCP_FLAG_ACK,
                             ));
-                            serial_println!("[net] tcp: connection established");
+                            net_debug!("tcp: connection established");
                         }
                     } else if tcp_header.has_flag(TCP_FLAG_RST) {
-                        serial_println!("[net] tcp: connection refused (RST)");
+                        net_debug!("tcp: connection refused (RST)");
                         conn.state = TcpState::Closed;
                     }
                 }
@@ -115,19 +184,20 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
                             conn.state = TcpState::Established;
                             conn.unacked_packet = None; // SYN-ACK が ACK されたのでクリア
                             push_accept = Some((conn.id, conn.local_port));
-                            serial_println!("[net] tcp: server connection established on port {}", conn.local_port);
+                            net_debug!("tcp: server connection established on port {}", conn.local_port);
                         }
                     }
                 }
                 TcpState::Established => {
-                    // ACK を受信したらデータ再送バッファをクリアする
+                    // ACK を受信したらデータ再送バッファをクリアし、貯めていたデータを送る
                     if tcp_header.has_flag(TCP_FLAG_ACK) {
-                        conn.unacked_packet = None;
+                        send_data = ack_received(conn, ack);
                     }
                     if tcp_header.has_flag(TCP_FLAG_FIN) {
-                        serial_println!("[net] tcp: received FIN");
+                        net_debug!("tcp: received FIN");
                         conn.ack_num = seq + 1;
                         conn.state = TcpState::CloseWait;
+                        clear_delayed_ack(conn);
                         send_packet = Some((
                             conn.remote_ip,
                             conn.remote_port,
@@ -137,17 +207,7 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
                             TCP_FLAG_ACK,
                         ));
                     } else if !tcp_payload.is_empty() {
-                        serial_println!("[net] tcp: received {} bytes of data", tcp_payload.len());
-                        conn.recv_buffer.extend_from_slice(tcp_payload);
-                        conn.ack_num = seq + tcp_payload.len() as u32;
-                        send_packet = Some((
-                            conn.remote_ip,
-                            conn.remote_port,
-                            conn.local_port,
-                            conn.seq_num,
-                            conn.ack_num,
-                            TCP_FLAG_ACK,
-                        ));
+                        send_packet = receive_data(conn, seq, tcp_payload, buffered_total);
                     }
                 }
                 TcpState::FinWait1 => {
@@ -174,9 +234,15 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
                             conn.state = TcpState::FinWait2;
                         }
                     }
+                    // 送信側だけ閉じた（half-close）状態でも、相手からのデータは受け取り続ける
+                    if !tcp_header.has_flag(TCP_FLAG_FIN) && !tcp_payload.is_empty() {
+                        send_packet = receive_data(conn, seq, tcp_payload, buffered_total);
+                    }
                 }
                 TcpState::FinWait2 => {
-                    if tcp_header.has_flag(TCP_FLAG_FIN) {
+                    if !tcp_header.has_flag(TCP_FLAG_FIN) && !tcp_payload.is_empty() {
+                        send_packet = receive_data(conn, seq, tcp_payload, buffered_total);
+                    } else if tcp_header.has_flag(TCP_FLAG_FIN) {
                         conn.ack_num = seq + 1;
                         conn.state = TcpState::TimeWait;
                         // TIME_WAIT タイマー設定（FinWait1 と同じ）
@@ -193,6 +259,12 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
                         ));
                     }
                 }
+                TcpState::CloseWait => {
+                    // 相手は送信側を閉じただけなので、こちらからの送信の ACK は届く
+                    if tcp_header.has_flag(TCP_FLAG_ACK) {
+                        send_data = ack_received(conn, ack);
+                    }
+                }
                 TcpState::LastAck => {
                     if tcp_header.has_flag(TCP_FLAG_ACK) {
                         conn.state = TcpState::Closed;
@@ -204,103 +276,312 @@ pub(super) fn handle_tcp(ip_header: &Ipv4Header, payload: &[u8]) {
         }
 
         if let Some((id, port)) = push_accept {
-            serial_println!("[net] tcp: pushing to pending_accept: conn_id={}, port={}, queue_len={}", id, port, state.tcp_pending_accept.len());
+            net_debug!("tcp: pushing to pending_accept: conn_id={}, port={}, queue_len={}", id, port, state.tcp_pending_accept.len());
             state.tcp_pending_accept.push_back((id, port));
         }
     });
 
-    if let Some((dst_ip, dst_port, src_port, seq_num, ack_num, flags)) = send_packet {
-        serial_println!("[net] tcp: sending response to {}.{}.{}.{}:{}, flags={:#04x}", dst_ip[0], dst_ip[1], dst_ip[2], dst_ip[3], dst_port, flags);
+    // 貯めていたデータを送るなら ACK はそれに相乗りさせる
+    if let Some((dst_ip, dst_port, src_port, seq_num, ack_num, payload)) = send_data {
+        net_trace!("tcp: sending {} coalesced bytes", payload.len());
+        let _ = send_tcp_packet_internal(dst_ip, dst_port, src_port, seq_num, ack_num, TCP_FLAG_ACK | TCP_FLAG_PSH, &payload);
+    } else if let Some((dst_ip, dst_port, src_port, seq_num, ack_num, flags)) = send_packet {
+        net_trace!("tcp: sending response to {}:{}, flags={:#04x}", dst_ip, dst_port, flags);
         let result = send_tcp_packet_internal(dst_ip, dst_port, src_port, seq_num, ack_num, flags, &[]);
-        serial_println!("[net] tcp: send result: {:?}", result);
+        net_trace!("tcp: send result: {:?}", result);
     } else {
-        serial_println!("[net] tcp: no response to send (SYN dropped?)");
+        net_debug!("tcp: no response to send (SYN dropped?)");
     }
 }
 
-/// TCP パケットを送信する（内部用）
+/// 受信したデータを recv_buffer に積み、すぐ返す ACK があれば組み立てる
 ///
-/// net_poller タスクから呼ばれる場合があるため、ブロッキングする resolve_mac() は使えない。
-/// ARP キャッシュから検索し、見つからなければフォールバックでブロードキャスト MAC を使う。
-/// 呼び出し元（tcp_connect 等）で事前に resolve_mac() を呼んでキャッシュを温めておくこと。
-pub(super) fn send_tcp_packet_internal(
-    dst_ip: [u8; 4],
+/// ウィンドウが大きいと相手は ACK を待たずに複数のセグメントを送ってくるので、
+/// 次に期待する位置（ack_num）から続く部分だけを受け取る。
+/// 再送で届いた受信済みの部分は読み飛ばし、途中が抜けた先のセグメントは捨てて
+/// 今の ack_num を返す（相手は抜けた部分から再送する）。
+/// 受信バッファに入りきらない分も捨て、受け取った分だけ ACK する。
+///
+/// SHUT_RD で受信側を閉じていればデータは捨てるが、ACK は返す
+/// （返さないと相手が同じデータを再送し続けるため）。
+///
+/// 順番どおりに全部受け取れたときは遅延 ACK にする（None を返す）。
+/// 次のティックか、もう 1 セグメント届いた時点で ACK する。こちらが送るデータが
+/// あればそれに ACK が相乗りするので、対話的なやりとりで ACK だけのパケットが減る。
+/// 抜けや重複、入りきらなかったときは相手に早く知らせるためすぐ ACK する。
+fn receive_data(conn: &mut TcpConnection, seq: u32, payload: &[u8], buffered_total: usize) -> Option<TcpReply> {
+    net_trace!("tcp: received {} bytes of data", payload.len());
+    let already = conn.ack_num.wrapping_sub(seq);
+    let mut complete = false;
+    if (already as i32) >= 0 && (already as usize) < payload.len() {
+        let new_data = &payload[already as usize..];
+        let accepted = if conn.read_shut {
+            new_data.len()
+        } else {
+            let n = new_data.len().min(recv_space(conn, buffered_total));
+            conn.recv_buffer.extend_from_slice(&new_data[..n]);
+            n
+        };
+        conn.ack_num = conn.ack_num.wrapping_add(accepted as u32);
+        complete = already == 0 && accepted == new_data.len();
+    }
+
+    if complete {
+        conn.delayed_ack_segments += 1;
+        if conn.delayed_ack_segments < 2 {
+            let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+            conn.delayed_ack_deadline.get_or_insert(now + TCP_DELAYED_ACK_TICKS);
+            return None;
+        }
+    }
+    clear_delayed_ack(conn);
+    Some((conn.remote_ip, conn.remote_port, conn.local_port, conn.seq_num, conn.ack_num, TCP_FLAG_ACK))
+}
+
+/// 遅らせていた ACK を取り消す（ACK を送ったか、送るパケットに相乗りさせたとき）
+fn clear_delayed_ack(conn: &mut TcpConnection) {
+    conn.delayed_ack_deadline = None;
+    conn.delayed_ack_segments = 0;
+}
+
+/// 期限が来た遅延 ACK を送る（net_poller から毎回呼ばれる）
+pub(super) fn send_delayed_acks(now: u64) {
+    let acks: Vec<TcpReply> = with_net_state(|state| {
+        state
+            .tcp_connections
+            .iter_mut()
+            .filter(|c| c.delayed_ack_deadline.is_some_and(|deadline| now >= deadline))
+            .map(|c| {
+                clear_delayed_ack(c);
+                (c.remote_ip, c.remote_port, c.local_port, c.seq_num, c.ack_num, TCP_FLAG_ACK)
+            })
+            .collect()
+    });
+    for (dst_ip, dst_port, src_port, seq_num, ack_num, flags) in acks {
+        let _ = send_tcp_packet_internal(dst_ip, dst_port, src_port, seq_num, ack_num, flags, &[]);
+    }
+}
+
+/// 送るデータを接続に積む（Nagle アルゴリズム）
+///
+/// 確認待ちのデータがあるうちは、貯めた分と合わせても MSS に満たない小さな送信を
+/// send_pending に貯めておき、ACK が来たときに 1 セグメントにまとめて送る（ack_received）。
+/// telnet のように 1 文字ずつ送るアプリでも、往復ごとに 1 パケットで済む。
+/// TCP_NODELAY なら貯めずにすぐ送る。すぐ送るならそのセグメントを返す。
+fn queue_data(conn: &mut TcpConnection, data: &[u8]) -> Option<DataSegment> {
+    conn.send_pending.extend_from_slice(data);
+    if !conn.nodelay && conn.unacked_packet.is_some() && conn.send_pending.len() < TCP_MSS as usize {
+        return None;
+    }
+    Some(take_pending_segment(conn))
+}
+
+/// 貯めている未送信データを 1 セグメントとして取り出し、再送用に記録する
+///
+/// 送る前に記録するのは、ループバックでは送った時点で ACK まで処理されるため。
+/// 送った後に記録すると、もう届いた ACK を待って再送し続けてしまう。
+fn take_pending_segment(conn: &mut TcpConnection) -> DataSegment {
+    let payload = core::mem::take(&mut conn.send_pending);
+    let seq_num = conn.seq_num;
+    conn.seq_num = conn.seq_num.wrapping_add(payload.len() as u32);
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+    conn.unacked_packet = Some(UnackedPacket {
+        seq_num,
+        ack_num: conn.ack_num,
+        flags: TCP_FLAG_ACK | TCP_FLAG_PSH,
+        payload: payload.clone(),
+        retransmit_deadline: now + TCP_INITIAL_RTO_TICKS,
+        retransmit_count: 0,
+    });
+    // 遅らせていた ACK はこのセグメントに相乗りする
+    clear_delayed_ack(conn);
+    (conn.remote_ip, conn.remote_port, conn.local_port, seq_num, conn.ack_num, payload)
+}
+
+/// データ送信中（Established / CloseWait）に届いた ACK を処理する
+///
+/// 送ったデータがすべて確認されたら再送バッファをクリアし、
+/// Nagle で貯めていたデータがあれば送るセグメントを返す。
+fn ack_received(conn: &mut TcpConnection, ack: u32) -> Option<DataSegment> {
+    if ack != conn.seq_num {
+        return None;
+    }
+    conn.unacked_packet = None;
+    if conn.send_pending.is_empty() {
+        return None;
+    }
+    Some(take_pending_segment(conn))
+}
+
+/// 全接続の受信バッファに溜まっているバイト数の合計
+fn recv_buffered_total(state: &NetState) -> usize {
+    state.tcp_connections.iter().map(|c| c.recv_buffer.len()).sum()
+}
+
+/// この接続がまだ受け取れるバイト数（接続ごとの上限と全体の上限の小さい方）
+fn recv_space(conn: &TcpConnection, buffered_total: usize) -> usize {
+    TCP_RECV_BUFFER_MAX
+        .saturating_sub(conn.recv_buffer.len())
+        .min(TCP_RECV_BUFFER_TOTAL_MAX.saturating_sub(buffered_total))
+}
+
+/// TCP オプション列にウィンドウスケール（kind 3）があるか
+fn has_window_scale_option(options: &[u8]) -> bool {
+    let mut i = 0;
+    while i < options.len() {
+        match options[i] {
+            0 => break,  // End of Option List
+            1 => i += 1, // NOP
+            kind => {
+                let len = match options.get(i + 1) {
+                    Some(&len) if len >= 2 => len as usize,
+                    _ => break,
+                };
+                if kind == 3 && len == 3 && i + len <= options.len() {
+                    return true;
+                }
+                i += len;
+            }
+        }
+    }
+    false
+}
+
+/// 送信する TCP ヘッダー（SYN ならオプション付き）を組み立てる。チェックサムは 0 のまま。
+///
+/// ウィンドウは接続の受信バッファの空きから毎回計算するので、
+/// 再送でも最新の空きが広告される。SYN / SYN-ACK のウィンドウはスケールしない
+/// （RFC 7323）。SYN には MSS とウィンドウスケールを付け、SYN-ACK では
+/// 相手が提示してきたときだけウィンドウスケールを返す。
+fn build_tcp_header(
+    dst_ip: IpAddr,
     dst_port: u16,
     src_port: u16,
     seq_num: u32,
     ack_num: u32,
     flags: u8,
-    payload: &[u8],
-) -> Result<(), &'static str> {
-    let my_mac = get_my_mac();
-    let dst_mac = arp_lookup(&dst_ip).unwrap_or(BROADCAST_MAC);
+) -> Vec<u8> {
+    let (window_scale_ok, space) = with_net_state(|state| {
+        let buffered_total = recv_buffered_total(state);
+        match find_conn_index_by_tuple(state, dst_ip, dst_port, src_port) {
+            Some(idx) => {
+                let conn = &state.tcp_connections[idx];
+                (conn.window_scale_ok, recv_space(conn, buffered_total))
+            }
+            None => (false, 65535),
+        }
+    });
 
-    let eth_header = EthernetHeader {
-        dst_mac,
-        src_mac: my_mac,
-        ethertype: ETHERTYPE_IPV4.to_be_bytes(),
-    };
+    let is_syn = flags & TCP_FLAG_SYN != 0;
+    let mut options = Vec::new();
+    if is_syn {
+        let mss = match dst_ip {
+            IpAddr::V4(_) => TCP_MSS,
+            IpAddr::V6(_) => TCP_MSS - 20,
+        };
+        options.extend_from_slice(&[2, 4]);
+        options.extend_from_slice(&mss.to_be_bytes());
+        // SYN（接続を始める側）は常に提示する。SYN-ACK は合意済みのときだけ
+        if flags & TCP_FLAG_ACK == 0 || window_scale_ok {
+            options.extend_from_slice(&[1, 3, 3, TCP_WINDOW_SCALE]);
+        }
+    }
 
+    let window = if is_syn || !window_scale_ok { space } else { space >> TCP_WINDOW_SCALE };
+    let header_len = 20 + options.len();
     let tcp_header = TcpHeader {
         src_port: src_port.to_be_bytes(),
         dst_port: dst_port.to_be_bytes(),
         seq_num: seq_num.to_be_bytes(),
         ack_num: ack_num.to_be_bytes(),
-        data_offset_reserved: 0x50,
+        data_offset_reserved: ((header_len / 4) as u8) << 4,
         flags,
-        window: 65535u16.to_be_bytes(),
+        window: (window.min(65535) as u16).to_be_bytes(),
         checksum: [0, 0],
         urgent_ptr: [0, 0],
     };
 
-    let tcp_length = 20 + payload.len();
-    let total_length = 20 + tcp_length;
-    let ip_header = Ipv4Header {
-        version_ihl: 0x45,
-        tos: 0,
-        total_length: (total_length as u16).to_be_bytes(),
-        identification: [0, 0],
-        flags_fragment: [0x40, 0x00],
-        ttl: 64,
-        protocol: IP_PROTO_TCP,
-        checksum: [0, 0],
-        src_ip: get_my_ip(),
-        dst_ip,
-    };
-
-    let ip_header_bytes = unsafe {
-        core::slice::from_raw_parts(&ip_header as *const _ as *const u8, 20)
-    };
-    let ip_checksum = calculate_checksum(ip_header_bytes);
-
-    let tcp_checksum = calculate_tcp_checksum(&get_my_ip(), &dst_ip, &tcp_header, payload);
+    let mut header = Vec::with_capacity(header_len);
+    header.extend_from_slice(unsafe {
+        core::slice::from_raw_parts(&tcp_header as *const _ as *const u8, 20)
+    });
+    header.extend_from_slice(&options);
+    header
+}
 
-    let mut packet = Vec::with_capacity(14 + 20 + tcp_length);
+/// TCP パケットを送信する（内部用）
+///
+/// 宛先アドレスの種類で IPv4 / IPv6 を選ぶ。
+/// net_poller タスクから呼ばれる場合があるため、ブロッキングする resolve_mac() /
+/// resolve_neighbor() は使えない。ARP / 近隣キャッシュから検索し、見つからなければ
+/// フォールバックでブロードキャスト MAC を使う。
+/// 呼び出し元（tcp_connect 等）で事前にアドレス解決してキャッシュを温めておくこと。
+pub(super) fn send_tcp_packet_internal(
+    dst_ip: IpAddr,
+    dst_port: u16,
+    src_port: u16,
+    seq_num: u32,
+    ack_num: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Result<(), &'static str> {
+    match dst_ip {
+        IpAddr::V4(v4) => send_tcp_packet_v4(v4, dst_port, src_port, seq_num, ack_num, flags, payload),
+        IpAddr::V6(v6) => send_tcp_packet_v6(v6, dst_port, src_port, seq_num, ack_num, flags, payload),
+    }
+}
 
-    packet.extend_from_slice(unsafe {
-        core::slice::from_raw_parts(&eth_header as *const _ as *const u8, 14)
-    });
+/// IPv4 で TCP パケットを送信する
+fn send_tcp_packet_v4(
+    dst_ip: [u8; 4],
+    dst_port: u16,
+    src_port: u16,
+    seq_num: u32,
+    ack_num: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Result<(), &'static str> {
+    // 自分の IP / 127.0.0.1 宛は send_ipv4_packet がソフトウェアループバックで処理する
+    let dst_mac = if is_loopback_address(&dst_ip) {
+        get_my_mac()
+    } else {
+        arp_lookup(&dst_ip).unwrap_or(BROADCAST_MAC)
+    };
+    let src_ip = source_ip_for(&dst_ip);
 
-    let mut ip_header_with_checksum = ip_header;
-    ip_header_with_checksum.checksum = ip_checksum.to_be_bytes();
-    packet.extend_from_slice(unsafe {
-        core::slice::from_raw_parts(&ip_header_with_checksum as *const _ as *const u8, 20)
-    });
+    let mut segment = build_tcp_header(IpAddr::V4(dst_ip), dst_port, src_port, seq_num, ack_num, flags);
+    segment.extend_from_slice(payload);
+    let tcp_checksum = calculate_tcp_checksum(&src_ip, &dst_ip, &segment);
+    segment[16..18].copy_from_slice(&tcp_checksum.to_be_bytes());
 
-    let mut tcp_header_with_checksum = tcp_header;
-    tcp_header_with_checksum.checksum = tcp_checksum.to_be_bytes();
-    packet.extend_from_slice(unsafe {
-        core::slice::from_raw_parts(&tcp_header_with_checksum as *const _ as *const u8, 20)
-    });
+    send_ipv4_packet(dst_mac, src_ip, dst_ip, IP_PROTO_TCP, &segment)
+}
 
-    packet.extend_from_slice(payload);
+/// IPv6 で TCP パケットを送信する
+///
+/// チェックサムは IPv6 疑似ヘッダー（16 バイトのアドレス 2 つ + 上位層の長さ）で計算する。
+/// 自分のアドレス宛は IPv4 と同じくソフトウェアループバックでその場で処理する。
+fn send_tcp_packet_v6(
+    dst_ip: [u8; 16],
+    dst_port: u16,
+    src_port: u16,
+    seq_num: u32,
+    ack_num: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Result<(), &'static str> {
+    let src_ip = crate::net_config::get_my_ipv6();
+    let mut segment = build_tcp_header(IpAddr::V6(dst_ip), dst_port, src_port, seq_num, ack_num, flags);
+    segment.extend_from_slice(payload);
+    let checksum = ipv6::calculate_ipv6_checksum(&src_ip, &dst_ip, IP_PROTO_TCP, &segment);
+    segment[16..18].copy_from_slice(&checksum.to_be_bytes());
 
-    // ローカル宛のパケットはソフトウェアループバック
-    if is_local_ip(&dst_ip) {
-        handle_packet(&packet);
+    let frame = ipv6::build_ipv6_frame(&src_ip, &dst_ip, IP_PROTO_TCP, &segment);
+    if ipv6::is_my_address(&dst_ip) {
+        handle_packet(&frame);
         Ok(())
     } else {
-        send_frame(&packet).map_err(|_| "send failed")
+        send_frame(&frame).map_err(|_| "send failed")
     }
 }
 
@@ -308,35 +589,46 @@ pub(super) fn send_tcp_packet_internal(
 fn calculate_tcp_checksum(
     src_ip: &[u8; 4],
     dst_ip: &[u8; 4],
-    tcp_header: &TcpHeader,
-    payload: &[u8],
+    segment: &[u8],
 ) -> u16 {
-    let tcp_len = 20 + payload.len();
-
-    let mut data = Vec::with_capacity(12 + tcp_len);
+    let mut data = Vec::with_capacity(12 + segment.len());
 
     data.extend_from_slice(src_ip);
     data.extend_from_slice(dst_ip);
     data.push(0);
     data.push(IP_PROTO_TCP);
-    data.extend_from_slice(&(tcp_len as u16).to_be_bytes());
-
-    data.extend_from_slice(unsafe {
-        core::slice::from_raw_parts(tcp_header as *const _ as *const u8, 20)
-    });
-
-    data.extend_from_slice(payload);
+    data.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+    data.extend_from_slice(segment);
 
     calculate_checksum(&data)
 }
 
 /// TCP コネクションを確立する（3-way ハンドシェイク）
+///
+/// 自分の IP / 127.0.0.1 宛はループバックで処理するので ARP 解決しない。
 pub fn tcp_connect(dst_ip: [u8; 4], dst_port: u16) -> Result<u32, &'static str> {
     // SYN 送信前に ARP キャッシュを温めておく。
     // send_tcp_packet_internal は net_poller から呼ばれる可能性があるため
     // ブロッキングする resolve_mac() を使えない。ここで事前解決する。
-    resolve_mac(&dst_ip)?;
+    if !is_loopback_address(&dst_ip) {
+        resolve_mac(&dst_ip)?;
+    }
+    connect_to(IpAddr::V4(dst_ip), dst_port)
+}
+
+/// IPv6 で TCP コネクションを確立する（3-way ハンドシェイク）
+///
+/// tcp_connect と同じく、SYN を送る前に近隣キャッシュを温めておく。
+/// 自分のアドレス宛はループバックで処理するので解決しない。
+pub fn tcp_connect6(dst_ip: [u8; 16], dst_port: u16) -> Result<u32, &'static str> {
+    if !ipv6::is_my_address(&dst_ip) {
+        ipv6::resolve_neighbor(&dst_ip)?;
+    }
+    connect_to(IpAddr::V6(dst_ip), dst_port)
+}
 
+/// SYN を送って Established になるまで待つ（アドレス解決は呼び出し元で済ませておく）
+fn connect_to(dst_ip: IpAddr, dst_port: u16) -> Result<u32, &'static str> {
     let (conn_id, local_port, initial_seq) = with_net_state(|state| {
         let id = alloc_conn_id(state);
         let local_port = alloc_local_port(state);
@@ -347,7 +639,7 @@ pub fn tcp_connect(dst_ip: [u8; 4], dst_port: u16) -> Result<u32, &'static str>
         (id, local_port, initial_seq)
     });
 
-    serial_println!("[net] tcp: sending SYN");
+    net_debug!("tcp: sending SYN");
     send_tcp_packet_internal(dst_ip, dst_port, local_port, initial_seq, 0, TCP_FLAG_SYN, &[])?;
 
     // SYN の再送情報を記録する
@@ -399,15 +691,63 @@ pub fn tcp_connect(dst_ip: [u8; 4], dst_port: u16) -> Result<u32, &'static str>
 }
 
 /// TCP のリッスンを開始する
-pub fn tcp_listen(port: u16) -> Result<(), &'static str> {
+///
+/// backlog は accept されずに待っている接続（ハンドシェイク中のものを含む）の上限で、
+/// これを超えた SYN には RST を返す。0 なら TCP_DEFAULT_BACKLOG、TCP_MAX_BACKLOG で頭打ちにする。
+/// listen 中のポートにもう一度呼ぶと backlog だけ変える。
+pub fn tcp_listen(port: u16, backlog: usize) -> Result<(), &'static str> {
+    let backlog = match backlog {
+        0 => TCP_DEFAULT_BACKLOG,
+        n => n.min(TCP_MAX_BACKLOG),
+    };
     with_net_state(|state| {
-        if !state.tcp_listen_ports.contains(&port) {
-            state.tcp_listen_ports.push(port);
+        match state.tcp_listen_ports.iter_mut().find(|(p, _)| *p == port) {
+            Some(entry) => entry.1 = backlog,
+            None => state.tcp_listen_ports.push((port, backlog)),
         }
         Ok(())
     })
 }
 
+/// 待ち受け中のポートの backlog（待ち受けていなければ None）
+fn listen_backlog(state: &NetState, port: u16) -> Option<usize> {
+    state.tcp_listen_ports.iter().find(|(p, _)| *p == port).map(|&(_, backlog)| backlog)
+}
+
+/// port で accept されずに待っている接続の数
+///
+/// accept 待ちキューに入ったものに加えて、ハンドシェイク中（SynReceived）のものも数える。
+/// 数えないと、ACK を返さない SYN だけで接続の表をいくらでも伸ばせてしまう。
+fn accept_queue_len(state: &NetState, port: u16) -> usize {
+    let queued = state.tcp_pending_accept.iter().filter(|&&(_, p)| p == port).count();
+    let half_open = state
+        .tcp_connections
+        .iter()
+        .filter(|c| c.local_port == port && c.state == TcpState::SynReceived)
+        .count();
+    queued + half_open
+}
+
+/// TCP のリッスンを終了する
+///
+/// 統一ソケット API でリスニングソケットを閉じたときに呼ぶ。
+/// 既に accept 待ちキューに入っている接続はそのまま残る。
+pub fn tcp_unlisten(port: u16) {
+    with_net_state(|state| {
+        state.tcp_listen_ports.retain(|&(p, _)| p != port);
+    });
+}
+
+/// accept 待ちキューから指定ポートの接続を 1 つ取り出す（ノンブロッキング）
+pub fn tcp_try_accept(listen_port: u16) -> Option<u32> {
+    with_net_state(|state| {
+        let pos = state.tcp_pending_accept.iter().position(|(_, port)| *port == listen_port)?;
+        let (id, _) = state.tcp_pending_accept.remove(pos).unwrap();
+        net_debug!("tcp_accept: found conn_id={} for port {}", id, listen_port);
+        Some(id)
+    })
+}
+
 /// TCP の accept を待つ（ポート指定）
 ///
 /// net_poller がパケットを処理して pending_accept にエントリを追加するのを待つ。
@@ -417,97 +757,315 @@ pub fn tcp_accept(timeout_ms: u64, listen_port: u16) -> Result<u32, &'static str
     // timeout_ms == 0 は「短いポーリング」の意味（旧 poll_and_handle_timeout(100) 相当）
     let effective_timeout = if timeout_ms == 0 { 100 } else { timeout_ms };
 
-    let check = || {
-        with_net_state(|state| {
-            if let Some(pos) = state.tcp_pending_accept.iter().position(|(_, port)| *port == listen_port) {
-                let (id, _) = state.tcp_pending_accept.remove(pos).unwrap();
-                serial_println!("[net] tcp_accept: found conn_id={} for port {}", id, listen_port);
-                Some(id)
-            } else {
-                None
-            }
-        })
-    };
-
-    match wait_net_condition(effective_timeout, check) {
+    match wait_net_condition(effective_timeout, || tcp_try_accept(listen_port)) {
         Some(id) => Ok(id),
         None => Err("timeout"),
     }
 }
 
+/// 接続相手の IP アドレスとポートを返す
+pub fn tcp_peer_addr(conn_id: u32) -> Option<(IpAddr, u16)> {
+    with_net_state(|state| {
+        let idx = find_conn_index_by_id(state, conn_id)?;
+        let conn = &state.tcp_connections[idx];
+        Some((conn.remote_ip, conn.remote_port))
+    })
+}
+
+/// 自分側の IP アドレスとポートを返す
+///
+/// IP は送信元に使うアドレス（127.0.0.1 宛なら 127.0.0.1）で、接続の相手から見えるものと同じ。
+pub fn tcp_local_addr(conn_id: u32) -> Option<(IpAddr, u16)> {
+    let (remote_ip, local_port) = with_net_state(|state| {
+        let idx = find_conn_index_by_id(state, conn_id)?;
+        let conn = &state.tcp_connections[idx];
+        Some((conn.remote_ip, conn.local_port))
+    })?;
+    let local_ip = match remote_ip {
+        IpAddr::V4(ip) => IpAddr::V4(source_ip_for(&ip)),
+        IpAddr::V6(_) => IpAddr::V6(crate::net_config::get_my_ipv6()),
+    };
+    Some((local_ip, local_port))
+}
+
+/// 接続の 1 セグメントに載せられるデータの大きさ（MSS）
+///
+/// tcp_send は渡されたデータを分けずに 1 セグメントで送るので、大きなデータを
+/// カーネル内で送るとき（SYS_SENDFILE）はこの大きさに切って渡す。
+/// IPv6 は IP ヘッダーが 20 バイト長い分だけ小さい。
+pub fn tcp_mss(conn_id: u32) -> Option<usize> {
+    with_net_state(|state| {
+        let idx = find_conn_index_by_id(state, conn_id)?;
+        Some(match state.tcp_connections[idx].remote_ip {
+            IpAddr::V4(_) => TCP_MSS as usize,
+            IpAddr::V6(_) => TCP_MSS as usize - 20,
+        })
+    })
+}
+
 /// TCP でデータを送信する
+///
+/// Nagle アルゴリズムで貯められた小さい送信は、確認待ちのデータの ACK が
+/// 来てから送られる（queue_data）。
 pub fn tcp_send(conn_id: u32, data: &[u8]) -> Result<(), &'static str> {
-    let (dst_ip, dst_port, local_port, seq_num, ack_num) = with_net_state(|state| {
+    let segment = with_net_state(|state| {
         let idx = find_conn_index_by_id(state, conn_id).ok_or("no connection")?;
         let conn = &mut state.tcp_connections[idx];
 
-        if conn.state != TcpState::Established {
+        if conn.write_shut {
+            return Err("write side shut down");
+        }
+        // CloseWait は相手が送信側を閉じただけなので、こちらからはまだ送れる
+        if conn.state != TcpState::Established && conn.state != TcpState::CloseWait {
             return Err("connection not established");
         }
 
-        let result = (conn.remote_ip, conn.remote_port, conn.local_port,
-                     conn.seq_num, conn.ack_num);
-        conn.seq_num += data.len() as u32;
-        Ok(result)
+        Ok(queue_data(conn, data))
     })?;
 
-    serial_println!("[net] tcp: sending {} bytes", data.len());
-    send_tcp_packet_internal(dst_ip, dst_port, local_port, seq_num, ack_num, TCP_FLAG_ACK | TCP_FLAG_PSH, data)?;
+    if let Some((dst_ip, dst_port, local_port, seq_num, ack_num, payload)) = segment {
+        net_trace!("tcp: sending {} bytes", payload.len());
+        send_tcp_packet_internal(dst_ip, dst_port, local_port, seq_num, ack_num, TCP_FLAG_ACK | TCP_FLAG_PSH, &payload)?;
+    }
+    Ok(())
+}
 
-    // データの再送情報を記録する
-    let now = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
-    with_net_state(|state| {
-        if let Some(idx) = find_conn_index_by_id(state, conn_id) {
-            state.tcp_connections[idx].unacked_packet = Some(UnackedPacket {
-                seq_num,
-                ack_num,
-                flags: TCP_FLAG_ACK | TCP_FLAG_PSH,
-                payload: data.to_vec(),
-                retransmit_deadline: now + TCP_INITIAL_RTO_TICKS,
-                retransmit_count: 0,
-            });
+/// TCP_NODELAY を設定する（true なら Nagle アルゴリズムを使わない）
+///
+/// 有効にしたときに貯めているデータがあれば、その場で送る。
+pub fn tcp_set_nodelay(conn_id: u32, nodelay: bool) -> Result<(), &'static str> {
+    let segment = with_net_state(|state| {
+        let idx = find_conn_index_by_id(state, conn_id).ok_or("no connection")?;
+        let conn = &mut state.tcp_connections[idx];
+        conn.nodelay = nodelay;
+        Ok((nodelay && !conn.send_pending.is_empty()).then(|| take_pending_segment(conn)))
+    })?;
+
+    if let Some((dst_ip, dst_port, local_port, seq_num, ack_num, payload)) = segment {
+        send_tcp_packet_internal(dst_ip, dst_port, local_port, seq_num, ack_num, TCP_FLAG_ACK | TCP_FLAG_PSH, &payload)?;
+    }
+    Ok(())
+}
+
+/// 受信済みのデータを取り出す（ノンブロッキング）
+///
+/// データがあれば Ok(Some(data))、まだなければ Ok(None)。
+/// 相手が FIN を送った後（CloseWait / LastAck / TimeWait / Closed）や
+/// SHUT_RD で受信側を閉じた後で、データもなければ Err("connection closed")。
+///
+/// 受信バッファの空きが半分を切っていたら、読み出した後に ACK を送って
+/// 広がったウィンドウを相手に知らせる（ウィンドウ 0 で止まった相手を待たせない）。
+pub fn tcp_try_recv(conn_id: u32) -> Result<Option<Vec<u8>>, &'static str> {
+    let (result, window_update) = with_net_state(|state| {
+        let buffered_total = recv_buffered_total(state);
+        let Some(idx) = find_conn_index_by_id(state, conn_id) else {
+            return (Err("no connection"), None);
+        };
+        let c = &mut state.tcp_connections[idx];
+        if !c.recv_buffer.is_empty() {
+            let was_short = recv_space(c, buffered_total) < TCP_RECV_BUFFER_MAX / 2;
+            let data = core::mem::take(&mut c.recv_buffer);
+            let update = (was_short
+                && matches!(c.state, TcpState::Established | TcpState::FinWait1 | TcpState::FinWait2))
+                .then(|| {
+                    clear_delayed_ack(c);
+                    (c.remote_ip, c.remote_port, c.local_port, c.seq_num, c.ack_num)
+                });
+            return (Ok(Some(data)), update);
+        }
+        if c.read_shut
+            || matches!(c.state, TcpState::CloseWait | TcpState::LastAck | TcpState::TimeWait | TcpState::Closed)
+        {
+            return (Err("connection closed"), None);
         }
+        (Ok(None), None)
     });
 
-    Ok(())
+    if let Some((dst_ip, dst_port, local_port, seq_num, ack_num)) = window_update {
+        let _ = send_tcp_packet_internal(dst_ip, dst_port, local_port, seq_num, ack_num, TCP_FLAG_ACK, &[]);
+    }
+    result
 }
 
 /// TCP でデータを受信する（ブロッキング、タイムアウト付き）
 ///
 /// net_poller がパケットを処理して recv_buffer にデータを追加するのを待つ。
-/// timeout_ms == 0 の場合はデフォルトタイムアウト（5000ms）を使用する。
+/// timeout_ms == 0 の場合は SO_RCVTIMEO、未設定ならデフォルトタイムアウト（5000ms）を使用する。
 pub fn tcp_recv(conn_id: u32, timeout_ms: u64) -> Result<Vec<u8>, &'static str> {
-    // timeout_ms == 0 は「デフォルトタイムアウト」の意味（旧コードでは 50 ループ × 100ms = 5000ms）
-    let effective_timeout = if timeout_ms == 0 { 5000 } else { timeout_ms };
-
-    let check = || {
-        with_net_state(|state| {
-            if let Some(idx) = find_conn_index_by_id(state, conn_id) {
-                let c = &mut state.tcp_connections[idx];
-                if !c.recv_buffer.is_empty() {
-                    let data = core::mem::take(&mut c.recv_buffer);
-                    return Some(Ok(data));
-                }
-                if c.state == TcpState::CloseWait || c.state == TcpState::Closed {
-                    return Some(Err("connection closed"));
-                }
-                None
-            } else {
-                Some(Err("no connection"))
-            }
-        })
+    // timeout_ms == 0 は「接続に設定されたタイムアウト」の意味（旧コードでは 50 ループ × 100ms = 5000ms 固定）
+    let effective_timeout = if timeout_ms == 0 {
+        let configured = with_net_state(|state| {
+            find_conn_index_by_id(state, conn_id).map(|idx| state.tcp_connections[idx].recv_timeout_ms)
+        });
+        match configured {
+            Some(0) | None => 5000,
+            Some(ms) => ms,
+        }
+    } else {
+        timeout_ms
     };
 
-    match wait_net_condition(effective_timeout, check) {
-        Some(Ok(data)) => Ok(data),
-        Some(Err(e)) => Err(e),
+    match wait_net_condition(effective_timeout, || tcp_try_recv(conn_id).transpose()) {
+        Some(result) => result,
         None => Err("timeout"),
     }
 }
 
 /// TCP コネクションを閉じる
+///
+/// tcp_shutdown(SHUT_WR) で FIN を送り済みなら、もう一度は送らずに後始末だけ待つ。
+/// SO_LINGER を設定した接続は FIN ではなく RST を送ってすぐに消す（tcp_abort）。
+/// 相手の RST ですでに Closed になった接続は、何も送らずに消す。
 pub fn tcp_close(conn_id: u32) -> Result<(), &'static str> {
-    let (dst_ip, dst_port, local_port, seq_num, ack_num) = with_net_state(|state| {
+    let (write_shut, linger_abort, closed) = with_net_state(|state| {
+        let idx = find_conn_index_by_id(state, conn_id).ok_or("no connection")?;
+        let conn = &state.tcp_connections[idx];
+        Ok((conn.write_shut, conn.linger_abort, conn.state == TcpState::Closed))
+    })?;
+    if closed {
+        with_net_state(|state| {
+            let _ = remove_conn_by_id(state, conn_id);
+        });
+        return Ok(());
+    }
+    if linger_abort {
+        return tcp_abort(conn_id);
+    }
+    if !write_shut {
+        send_fin(conn_id)?;
+    }
+
+    // net_poller がパケットを処理して接続が TimeWait or Closed になるのを待つ
+    let _done = wait_net_condition(5000, || fin_settled(conn_id).then_some(true));
+
+    // TimeWait の場合は接続を残す（net_poller がタイマー期限で削除する）。
+    // Closed の場合のみ即削除する。
+    with_net_state(|state| {
+        if let Some(idx) = find_conn_index_by_id(state, conn_id) {
+            if state.tcp_connections[idx].state == TcpState::Closed {
+                state.tcp_connections.remove(idx);
+            }
+            // TimeWait の場合はそのまま残す
+        }
+    });
+
+    net_debug!("tcp: connection closed");
+    Ok(())
+}
+
+/// RST を送って接続を強制的に切る（abortive close）
+///
+/// FIN のやりとりも TIME_WAIT も経ずに接続を表から消すので、接続の枠と
+/// ポートがすぐに空く。未送信・未読のデータは捨てる。
+/// 相手は RST を受け取った時点で接続を Closed にする（以降の受信は "connection closed"）。
+pub fn tcp_abort(conn_id: u32) -> Result<(), &'static str> {
+    let conn = with_net_state(|state| remove_conn_by_id(state, conn_id)).ok_or("no connection")?;
+    // 相手がまだ何も知らない SYN_SENT 以外は、相手の受信ウィンドウに入る seq で RST を送る
+    if conn.state != TcpState::SynSent {
+        net_debug!("tcp: sending RST (abortive close) on port {}", conn.local_port);
+        send_tcp_packet_internal(
+            conn.remote_ip,
+            conn.remote_port,
+            conn.local_port,
+            conn.seq_num,
+            conn.ack_num,
+            TCP_FLAG_RST | TCP_FLAG_ACK,
+            &[],
+        )?;
+    }
+    Ok(())
+}
+
+/// tcp_shutdown でどちら側を閉じるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpShutdown {
+    /// 受信側だけ閉じる（SHUT_RD）
+    Read,
+    /// 送信側だけ閉じる（SHUT_WR）。FIN を送るが受信は続けられる
+    Write,
+    /// 両方閉じる（SHUT_RDWR）
+    Both,
+}
+
+/// 接続の片側（または両側）を閉じる（half-close）
+///
+/// tcp_close と違って接続は残り、FIN を送った後もデータを受信できる。
+/// HTTP/1.0 のように「リクエストの終わりを送信側のクローズで伝え、
+/// そのまま応答を読む」プロトコルのために使う。接続の後始末は tcp_close で行う。
+/// 同じ側を 2 回閉じても何もしない。
+pub fn tcp_shutdown(conn_id: u32, how: TcpShutdown) -> Result<(), &'static str> {
+    let send_fin_needed = with_net_state(|state| {
+        let idx = find_conn_index_by_id(state, conn_id).ok_or("no connection")?;
+        let conn = &mut state.tcp_connections[idx];
+        if how != TcpShutdown::Write {
+            conn.read_shut = true;
+            conn.recv_buffer.clear();
+        }
+        if how == TcpShutdown::Read || conn.write_shut {
+            return Ok(false);
+        }
+        if conn.state != TcpState::Established && conn.state != TcpState::CloseWait {
+            return Err("connection not established");
+        }
+        Ok(true)
+    })?;
+
+    if send_fin_needed {
+        send_fin(conn_id)?;
+    }
+    Ok(())
+}
+
+/// 複数の接続に FIN を送り、閉じる処理が進むのを最大 timeout_ms 待つ
+///
+/// tcp_close を 1 本ずつ呼ぶと 1 本あたり最大 5 秒待つので、
+/// 先に全部の FIN を送ってからまとめて待つ。
+/// FIN を送った接続の数を返す（Established / CloseWait 以外の接続は飛ばす）。
+/// 待ち終わった後の接続は TimeWait などのまま残し、net_poller の後始末に任せる。
+pub fn tcp_drain(conn_ids: &[u32], timeout_ms: u64) -> usize {
+    let sent: Vec<u32> = conn_ids.iter().copied().filter(|&id| send_fin(id).is_ok()).collect();
+    if !sent.is_empty() {
+        let _ = wait_net_condition(timeout_ms, || sent.iter().all(|&id| fin_settled(id)).then_some(()));
+    }
+    sent.len()
+}
+
+/// 全接続を閉じる（シャットダウン・再起動の前に呼ぶ）
+///
+/// 新しい接続を受け付けないように待ち受けをすべてやめてから、
+/// Established / CloseWait の接続に FIN を送る。相手が応答しなくても
+/// timeout_ms で諦める（電源断を長く待たせない）。FIN を送った接続の数を返す。
+pub fn tcp_shutdown_all(timeout_ms: u64) -> usize {
+    let ids: Vec<u32> = with_net_state(|state| {
+        state.tcp_listen_ports.clear();
+        state.tcp_pending_accept.clear();
+        state
+            .tcp_connections
+            .iter()
+            .filter(|c| c.state == TcpState::Established || c.state == TcpState::CloseWait)
+            .map(|c| c.id)
+            .collect()
+    });
+    let sent = tcp_drain(&ids, timeout_ms);
+    net_debug!("tcp: shutdown sent FIN on {} connection(s)", sent);
+    sent
+}
+
+/// FIN を送ったあと、相手とのやりとりが一段落したか
+///
+/// TimeWait / Closed になった、またはすでに接続が消えていれば true。
+fn fin_settled(conn_id: u32) -> bool {
+    with_net_state(|state| match find_conn_index_by_id(state, conn_id) {
+        Some(idx) => matches!(state.tcp_connections[idx].state, TcpState::TimeWait | TcpState::Closed),
+        None => true,
+    })
+}
+
+/// FIN を送って状態を FinWait1 / LastAck に進め、再送用に記録する
+///
+/// Nagle で貯めているデータがあれば、FIN の前に送る。
+fn send_fin(conn_id: u32) -> Result<(), &'static str> {
+    let (pending, (dst_ip, dst_port, local_port, seq_num, ack_num)) = with_net_state(|state| {
         let idx = find_conn_index_by_id(state, conn_id).ok_or("no connection")?;
         let conn = &mut state.tcp_connections[idx];
 
@@ -515,6 +1073,7 @@ pub fn tcp_close(conn_id: u32) -> Result<(), &'static str> {
             return Err("invalid state for close");
         }
 
+        let pending = (!conn.send_pending.is_empty()).then(|| take_pending_segment(conn));
         let result = (conn.remote_ip, conn.remote_port, conn.local_port,
                      conn.seq_num, conn.ack_num);
 
@@ -523,11 +1082,15 @@ pub fn tcp_close(conn_id: u32) -> Result<(), &'static str> {
         } else {
             conn.state = TcpState::LastAck;
         }
+        conn.write_shut = true;
         conn.seq_num += 1;
-        Ok(result)
+        Ok((pending, result))
     })?;
 
-    serial_println!("[net] tcp: sending FIN");
+    if let Some((dst_ip, dst_port, local_port, seq_num, ack_num, payload)) = pending {
+        send_tcp_packet_internal(dst_ip, dst_port, local_port, seq_num, ack_num, TCP_FLAG_ACK | TCP_FLAG_PSH, &payload)?;
+    }
+    net_debug!("tcp: sending FIN");
     send_tcp_packet_internal(dst_ip, dst_port, local_port, seq_num, ack_num, TCP_FLAG_FIN | TCP_FLAG_ACK, &[])?;
 
     // FIN の再送情報を記録する
@@ -544,34 +1107,435 @@ pub fn tcp_close(conn_id: u32) -> Result<(), &'static str> {
             });
         }
     });
+    Ok(())
+}
 
-    // net_poller がパケットを処理して接続が TimeWait or Closed になるのを待つ
-    let _done = wait_net_condition(5000, || {
+/// tcp_drain が各接続に FIN を送ることのテスト
+///
+/// TEST-NET-1 (192.0.2.0/24) を相手にした Established の接続を 2 本作って tcp_drain に渡し、
+/// 両方とも FinWait1 に進んで FIN が再送待ちに記録されることを確認する。
+/// 相手は存在しないので応答は来ず、状態はそのまま残る。
+/// 存在しない conn_id は数に入らない。selftest から呼ばれる。
+pub fn test_tcp_drain() -> bool {
+    let remotes = [([192, 0, 2, 20], 40030u16), ([192, 0, 2, 21], 40031u16)];
+    let conns: Vec<(u32, u32)> = with_net_state(|state| {
+        remotes
+            .iter()
+            .map(|&(ip, port)| {
+                let id = alloc_conn_id(state);
+                let mut conn = TcpConnection::new(id, port, IpAddr::V4(ip), 80);
+                conn.state = TcpState::Established;
+                let seq = conn.seq_num;
+                state.tcp_connections.push(conn);
+                (id, seq)
+            })
+            .collect()
+    });
+
+    let mut ids: Vec<u32> = conns.iter().map(|&(id, _)| id).collect();
+    ids.push(u32::MAX);
+    let sent = tcp_drain(&ids, 0);
+
+    with_net_state(|state| {
+        let ok = sent == conns.len()
+            && conns.iter().all(|&(id, seq)| {
+                find_conn_index_by_id(state, id).is_some_and(|idx| {
+                    let c = &state.tcp_connections[idx];
+                    c.state == TcpState::FinWait1
+                        && c.seq_num == seq.wrapping_add(1)
+                        && c.unacked_packet.as_ref().is_some_and(|p| p.flags & TCP_FLAG_FIN != 0)
+                })
+            });
+        state.tcp_connections.retain(|c| !conns.iter().any(|&(id, _)| id == c.id));
+        ok
+    })
+}
+
+/// half-close（SHUT_WR）のテスト
+///
+/// 自分の IP 同士でつながった Established の接続を 2 本（クライアント側・サーバー側）作る。
+/// 自分宛のパケットはソフトウェアループバックでその場で処理されるので、
+/// NIC がなくても FIN や ACK のやりとりが同期的に進む。
+///
+/// 1. サーバーが先にデータを送っておく（クライアントはまだ読まない）
+/// 2. クライアントが SHUT_WR → FIN が届いてサーバーは CloseWait、クライアントは FinWait2
+/// 3. SHUT_WR の前に届いていたデータはそのまま読める。クライアントからの送信はエラーになる
+/// 4. サーバー（CloseWait）からの送信はクライアントが受信できる
+/// 5. サーバーも送信側を閉じると、クライアントの受信は EOF になる
+///
+/// selftest から呼ばれる。
+pub fn test_tcp_half_close() -> bool {
+    let my_ip = IpAddr::V4(get_my_ip());
+    let (client_port, server_port) = (40050u16, 40051u16);
+    let (client, server) = with_net_state(|state| {
+        let client_id = alloc_conn_id(state);
+        let mut client = TcpConnection::new(client_id, client_port, my_ip, server_port);
+        let server_id = alloc_conn_id(state);
+        let mut server = TcpConnection::new(server_id, server_port, my_ip, client_port);
+        client.state = TcpState::Established;
+        server.state = TcpState::Established;
+        client.ack_num = server.seq_num;
+        server.ack_num = client.seq_num;
+        state.tcp_connections.push(client);
+        state.tcp_connections.push(server);
+        (client_id, server_id)
+    });
+    let state_of = |id: u32| {
+        with_net_state(|state| find_conn_index_by_id(state, id).map(|idx| state.tcp_connections[idx].state))
+    };
+
+    let ok = tcp_send(server, b"early").is_ok()
+        && tcp_shutdown(client, TcpShutdown::Write).is_ok()
+        && state_of(client) == Some(TcpState::FinWait2)
+        && state_of(server) == Some(TcpState::CloseWait)
+        && tcp_try_recv(client) == Ok(Some(b"early".to_vec()))
+        && tcp_send(client, b"more").is_err()
+        && tcp_send(server, b"response").is_ok()
+        && tcp_try_recv(client) == Ok(Some(b"response".to_vec()))
+        && tcp_try_recv(client) == Ok(None)
+        && tcp_shutdown(server, TcpShutdown::Write).is_ok()
+        && state_of(client) == Some(TcpState::TimeWait)
+        && state_of(server) == Some(TcpState::Closed)
+        && tcp_try_recv(client) == Err("connection closed");
+
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| c.id != client && c.id != server);
+    });
+    ok
+}
+
+/// IPv6 での TCP 接続のテスト
+///
+/// 自分の IPv6 アドレスの待ち受けポートに tcp_connect6 でつなぐ。
+/// 自分宛はソフトウェアループバックでその場で処理されるので、QEMU の外に相手がいなくても
+/// SYN → SYN-ACK → ACK のハンドシェイクが進み、受け入れ側は相手を IPv6 アドレスとして見る。
+/// 受信側で IPv6 疑似ヘッダー込みのチェックサムを確かめているので、
+/// 計算を間違えるとセグメントが捨てられて接続もデータのやりとりも失敗する。
+///
+/// selftest から呼ばれる。
+pub fn test_tcp_connect6() -> bool {
+    const PORT: u16 = 40060;
+    let my_ip = crate::net_config::get_my_ipv6();
+
+    if tcp_listen(PORT, 0).is_err() {
+        return false;
+    }
+    let client = tcp_connect6(my_ip, PORT);
+    let server = tcp_try_accept(PORT);
+    tcp_unlisten(PORT);
+
+    let ok = match (client, server) {
+        (Ok(client), Some(server)) => {
+            let peer_ok = tcp_peer_addr(server).is_some_and(|(ip, _)| ip == IpAddr::V6(my_ip))
+                && tcp_peer_addr(client) == Some((IpAddr::V6(my_ip), PORT));
+            let data_ok = tcp_send(client, b"ping6").is_ok()
+                && tcp_try_recv(server) == Ok(Some(b"ping6".to_vec()))
+                && tcp_send(server, b"pong6").is_ok()
+                && tcp_try_recv(client) == Ok(Some(b"pong6".to_vec()));
+            peer_ok && data_ok
+        }
+        _ => false,
+    };
+
+    let ids: Vec<u32> = [client.ok(), server].into_iter().flatten().collect();
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| !ids.contains(&c.id));
+    });
+    ok
+}
+
+/// run-selftest.sh がホストの [::1] で動かす IPv6 エコーサーバーのポート
+pub const TCP6_ECHO_PORT: u16 = 12307;
+
+/// QEMU SLIRP のホスト越しの IPv6 TCP 接続のテスト
+///
+/// SLIRP はプレフィックス内の ::2（fec0::2）宛の接続をホストの ::1 に中継する。
+/// run-selftest.sh が [::1]:TCP6_ECHO_PORT でエコーサーバー
+/// (scripts/tcp6-echo-server.py) を動かしているので、自分のアドレスの上位 64 ビットに
+/// ::2 を付けた宛先へ tcp_connect6 でつなぎ、送ったデータがそのまま返ってくるかを見る。
+/// test_tcp_connect6 と違い、NDP の解決から virtio-net の送受信まで実際の経路を通る。
+///
+/// selftest から呼ばれる。
+pub fn test_tcp6_host_echo() -> bool {
+    const MSG: &[u8] = b"ping6 over slirp";
+    let mut host = crate::net_config::get_my_ipv6();
+    host[8..].fill(0);
+    host[15] = 2;
+
+    let conn = match tcp_connect6(host, TCP6_ECHO_PORT) {
+        Ok(conn) => conn,
+        Err(_) => return false,
+    };
+    let mut echoed = Vec::new();
+    let mut ok = tcp_send(conn, MSG).is_ok();
+    while ok && echoed.len() < MSG.len() {
+        match tcp_recv(conn, 3000) {
+            Ok(data) if !data.is_empty() => echoed.extend_from_slice(&data),
+            _ => ok = false,
+        }
+    }
+    let _ = tcp_close(conn);
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| c.id != conn);
+    });
+    ok && echoed == MSG
+}
+
+/// 127.0.0.1 での TCP 接続のテスト
+///
+/// 待ち受けポートに tcp_connect(127.0.0.1) でつなぐ。NIC を通らずその場で処理されるので、
+/// QEMU の外に相手がいなくてもハンドシェイクとデータのやりとりが進む。
+/// 送信元も 127.0.0.1 になるので、両側とも相手を 127.0.0.1 として見る。
+/// 受信側はチェックサムを確かめているので、疑似ヘッダーのアドレスを間違えると
+/// セグメントが捨てられて接続できない。
+///
+/// selftest から呼ばれる。
+pub fn test_tcp_loopback() -> bool {
+    const PORT: u16 = 40100;
+    let loopback = IpAddr::V4(super::LOOPBACK_IP);
+
+    if tcp_listen(PORT, 0).is_err() {
+        return false;
+    }
+    let client = tcp_connect(super::LOOPBACK_IP, PORT);
+    let server = tcp_try_accept(PORT);
+    tcp_unlisten(PORT);
+
+    let ok = match (client, server) {
+        (Ok(client), Some(server)) => {
+            let peer_ok = tcp_peer_addr(server).is_some_and(|(ip, _)| ip == loopback)
+                && tcp_peer_addr(client) == Some((loopback, PORT));
+            let data_ok = tcp_send(client, b"hello lo").is_ok()
+                && tcp_try_recv(server) == Ok(Some(b"hello lo".to_vec()))
+                && tcp_send(server, b"hi").is_ok()
+                && tcp_try_recv(client) == Ok(Some(b"hi".to_vec()));
+            peer_ok && data_ok
+        }
+        _ => false,
+    };
+
+    let ids: Vec<u32> = [client.ok(), server].into_iter().flatten().collect();
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| !ids.contains(&c.id));
+    });
+    ok
+}
+
+/// SO_LINGER（強制切断）のテスト
+///
+/// 127.0.0.1 の待ち受けポートにつなぎ、受け入れ側に SO_LINGER を設定して閉じる。
+/// 1. 受け入れ側の接続は TIME_WAIT を経ずにその場で表から消える
+/// 2. RST を受け取った接続元は Closed になり、受信は "connection closed" になる
+/// 3. 同じポートをすぐに待ち受け直して、もう一度つなげる
+///
+/// ことを確認する。selftest から呼ばれる。
+pub fn test_tcp_linger_abort() -> bool {
+    use sabos_syscall::{SOL_SOCKET, SO_LINGER};
+
+    const PORT: u16 = 40120;
+    let port_in_use = || with_net_state(|state| state.tcp_connections.iter().any(|c| c.local_port == PORT));
+    let state_of = |id: u32| {
+        with_net_state(|state| find_conn_index_by_id(state, id).map(|idx| state.tcp_connections[idx].state))
+    };
+
+    if tcp_listen(PORT, 0).is_err() {
+        return false;
+    }
+    let client = tcp_connect(super::LOOPBACK_IP, PORT);
+    let server = tcp_try_accept(PORT);
+    tcp_unlisten(PORT);
+
+    let ok = match (client, server) {
+        (Ok(client), Some(server)) => {
+            let abort_ok = super::set_socket_option(server, SOL_SOCKET, SO_LINGER, 1).is_ok()
+                && super::get_socket_option(server, SOL_SOCKET, SO_LINGER) == Ok(1)
+                && tcp_close(server).is_ok()
+                && state_of(server).is_none()
+                && !port_in_use()
+                && state_of(client) == Some(TcpState::Closed)
+                && tcp_try_recv(client) == Err("connection closed")
+                && tcp_close(client).is_ok()
+                && state_of(client).is_none();
+
+            // TIME_WAIT が残っていないので、すぐに同じポートで待ち受け直せる
+            let relisten_ok = tcp_listen(PORT, 0).is_ok() && {
+                let again = tcp_connect(super::LOOPBACK_IP, PORT);
+                let accepted = tcp_try_accept(PORT);
+                tcp_unlisten(PORT);
+                let ok = again.is_ok() && accepted.is_some();
+                let ids: Vec<u32> = [again.ok(), accepted].into_iter().flatten().collect();
+                with_net_state(|state| state.tcp_connections.retain(|c| !ids.contains(&c.id)));
+                ok
+            };
+            abort_ok && relisten_ok
+        }
+        _ => false,
+    };
+
+    let ids: Vec<u32> = [client.ok(), server].into_iter().flatten().collect();
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| !ids.contains(&c.id));
+    });
+    ok
+}
+
+/// backlog のテスト
+///
+/// 127.0.0.1 の待ち受けポートを backlog 1 で開き、accept しないまま 2 本つなぐ。
+/// 1 本目はハンドシェイクが済んで accept 待ちに入り、2 本目は RST で断られる
+/// （connect が "connection refused" で失敗し、接続の表にも残らない）。
+/// 1 本目を accept して枠が空けば、次の接続は受け付けられることも確認する。
+///
+/// selftest から呼ばれる。
+pub fn test_tcp_backlog() -> bool {
+    const PORT: u16 = 40130;
+    let half_open = || {
         with_net_state(|state| {
-            if let Some(idx) = find_conn_index_by_id(state, conn_id) {
-                let c = &state.tcp_connections[idx];
-                if c.state == TcpState::TimeWait || c.state == TcpState::Closed {
-                    Some(true)
-                } else {
-                    None
-                }
-            } else {
-                Some(true)
-            }
+            state.tcp_connections.iter().filter(|c| c.local_port == PORT && c.state == TcpState::SynReceived).count()
         })
-    });
+    };
 
-    // TimeWait の場合は接続を残す（net_poller がタイマー期限で削除する）。
-    // Closed の場合のみ即削除する。
+    if tcp_listen(PORT, 1).is_err() {
+        return false;
+    }
+    let first = tcp_connect(super::LOOPBACK_IP, PORT);
+    let second = tcp_connect(super::LOOPBACK_IP, PORT);
+    let refused_ok = first.is_ok() && second == Err("connection refused") && half_open() == 0;
+
+    let accepted = tcp_try_accept(PORT);
+    let third = tcp_connect(super::LOOPBACK_IP, PORT);
+    let third_accepted = tcp_try_accept(PORT);
+    tcp_unlisten(PORT);
+    let ok = refused_ok && accepted.is_some() && third.is_ok() && third_accepted.is_some();
+
+    let ids: Vec<u32> = [first.ok(), second.ok(), accepted, third.ok(), third_accepted]
+        .into_iter()
+        .flatten()
+        .collect();
     with_net_state(|state| {
-        if let Some(idx) = find_conn_index_by_id(state, conn_id) {
-            if state.tcp_connections[idx].state == TcpState::Closed {
-                state.tcp_connections.remove(idx);
-            }
-            // TimeWait の場合はそのまま残す
+        state.tcp_connections.retain(|c| !ids.contains(&c.id));
+    });
+    ok
+}
+
+/// ウィンドウスケールと受信ウィンドウのテスト
+///
+/// 自分の IPv4 アドレスの待ち受けポートにループバックでつなぎ、
+/// 1. 双方の SYN にオプションが載ってウィンドウスケールの合意ができる
+/// 2. 受信側が読まないまま 65535 バイト（スケールなしで広告できる上限）を超えて受け取れる
+/// 3. そのあとも広告ウィンドウ（ヘッダーの値 << スケール）は 65535 より大きい
+/// 4. 再送で同じデータが届いても二重に積まない
+///
+/// ことを確認する。selftest から呼ばれる。
+pub fn test_tcp_window_scale() -> bool {
+    const PORT: u16 = 40070;
+    const CHUNK: usize = 30000;
+    let my_ip = IpAddr::V4(get_my_ip());
+
+    let parse_ok = has_window_scale_option(&[2, 4, 0x05, 0xb4, 1, 3, 3, 2])
+        && !has_window_scale_option(&[2, 4, 0x05, 0xb4])
+        && !has_window_scale_option(&[1, 3, 9]);
+
+    if tcp_listen(PORT, 0).is_err() {
+        return false;
+    }
+    let client = connect_to(my_ip, PORT);
+    let server = tcp_try_accept(PORT);
+    tcp_unlisten(PORT);
+
+    let ok = match (client, server) {
+        (Ok(client), Some(server)) => {
+            let scaled = with_net_state(|state| {
+                [client, server].iter().all(|&id| {
+                    find_conn_index_by_id(state, id).is_some_and(|idx| state.tcp_connections[idx].window_scale_ok)
+                })
+            });
+
+            let chunk = alloc::vec![0x5au8; CHUNK];
+            let sent = (0..3).all(|_| tcp_send(client, &chunk).is_ok());
+
+            // 最後のチャンクを同じシーケンス番号で送り直す（再送の模擬）
+            let resent = with_net_state(|state| {
+                find_conn_index_by_id(state, client).map(|idx| {
+                    let c = &state.tcp_connections[idx];
+                    (c.local_port, c.seq_num.wrapping_sub(CHUNK as u32), c.ack_num)
+                })
+            })
+            .is_some_and(|(client_port, seq, ack)| {
+                send_tcp_packet_internal(my_ip, PORT, client_port, seq, ack, TCP_FLAG_ACK | TCP_FLAG_PSH, &chunk)
+                    .is_ok()
+            });
+
+            let window_ok = tcp_peer_addr(server).is_some_and(|(_, client_port)| {
+                let header = build_tcp_header(my_ip, client_port, PORT, 0, 0, TCP_FLAG_ACK);
+                let field = u16::from_be_bytes([header[14], header[15]]) as usize;
+                (field << TCP_WINDOW_SCALE) > 65535
+            });
+
+            let received = tcp_try_recv(server)
+                .is_ok_and(|data| data.is_some_and(|d| d.len() == 3 * CHUNK && d.iter().all(|&b| b == 0x5a)));
+            scaled && sent && resent && window_ok && received
         }
+        _ => false,
+    };
+
+    let ids: Vec<u32> = [client.ok(), server].into_iter().flatten().collect();
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| !ids.contains(&c.id));
     });
+    parse_ok && ok
+}
 
-    serial_println!("[net] tcp: connection closed");
-    Ok(())
+/// 遅延 ACK と Nagle アルゴリズムのテスト
+///
+/// 自分の IPv4 アドレスにループバックでつなぐ。ループバックは NIC を通らないので
+/// キャプチャには映らないが、受信側の recv_buffer と送信側の send_pending を見れば
+/// セグメントがどうまとめられたかがわかる。
+///
+/// 1. 1 バイトずつ 3 回送ると、最初の "a" だけが届き、受信側は ACK を遅らせる。
+///    確認待ちがあるので "b" "c" は送信側に貯まる
+/// 2. 遅延 ACK の期限が来ると ACK が返り、貯めていた "bc" が 1 セグメントで届く
+/// 3. TCP_NODELAY にすると、確認待ちがあっても 1 バイトずつすぐ届く
+///
+/// selftest から呼ばれる。
+pub fn test_tcp_nagle() -> bool {
+    const PORT: u16 = 40080;
+    let my_ip = IpAddr::V4(get_my_ip());
+
+    if tcp_listen(PORT, 0).is_err() {
+        return false;
+    }
+    let client = connect_to(my_ip, PORT);
+    let server = tcp_try_accept(PORT);
+    tcp_unlisten(PORT);
+
+    let pending_of = |id: u32| {
+        with_net_state(|state| find_conn_index_by_id(state, id).map(|idx| state.tcp_connections[idx].send_pending.clone()))
+    };
+    let ok = match (client, server) {
+        (Ok(client), Some(server)) => {
+            let sent = [b"a", b"b", b"c"].iter().all(|d| tcp_send(client, *d).is_ok());
+            let held = tcp_try_recv(server) == Ok(Some(b"a".to_vec()))
+                && pending_of(client) == Some(b"bc".to_vec());
+
+            send_delayed_acks(u64::MAX);
+            let coalesced = tcp_try_recv(server) == Ok(Some(b"bc".to_vec()))
+                && pending_of(client) == Some(Vec::new());
+
+            // "d" は確認待ちの "bc" があるので、NODELAY でなければ貯まるところ
+            let nodelay = tcp_set_nodelay(client, true).is_ok()
+                && tcp_send(client, b"d").is_ok()
+                && tcp_send(client, b"e").is_ok()
+                && tcp_try_recv(server) == Ok(Some(b"de".to_vec()))
+                && pending_of(client) == Some(Vec::new());
+            sent && held && coalesced && nodelay
+        }
+        _ => false,
+    };
+
+    let ids: Vec<u32> = [client.ok(), server].into_iter().flatten().collect();
+    with_net_state(|state| {
+        state.tcp_connections.retain(|c| !ids.contains(&c.id));
+    });
+    ok
 }
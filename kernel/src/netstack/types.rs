@@ -107,6 +107,21 @@ impl Ipv4Header {
     pub fn header_length(&self) -> usize {
         ((self.version_ihl & 0x0F) as usize) * 4
     }
+
+    /// パケット全長 (ヘッダー + データ、バイト単位)
+    pub fn total_length(&self) -> usize {
+        u16::from_be_bytes(self.total_length) as usize
+    }
+
+    /// フラグ (上位 3 ビット) とフラグメントオフセット (下位 13 ビット)
+    pub fn flags_fragment_u16(&self) -> u16 {
+        u16::from_be_bytes(self.flags_fragment)
+    }
+
+    /// フラグメントの一部か (MF フラグが立っているか、オフセットが 0 でない)
+    pub fn is_fragment(&self) -> bool {
+        self.flags_fragment_u16() & 0x3FFF != 0
+    }
 }
 
 // ============================================================
@@ -133,6 +148,40 @@ pub struct IcmpHeader {
 // UDP ヘッダー
 // ============================================================
 
+/// UDP / TCP の送信元・宛先 IP アドレス（IPv4 と IPv6 のどちらか）
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpAddr {
+    V4([u8; 4]),
+    V6([u8; 16]),
+}
+
+impl IpAddr {
+    /// 16 バイトの IPv6 形式に変換する。
+    /// IPv4 は IPv4-mapped アドレス (::ffff:a.b.c.d) にする。
+    pub fn to_ipv6_mapped(self) -> [u8; 16] {
+        match self {
+            IpAddr::V4(v4) => {
+                let mut addr = [0u8; 16];
+                addr[10] = 0xff;
+                addr[11] = 0xff;
+                addr[12..].copy_from_slice(&v4);
+                addr
+            }
+            IpAddr::V6(v6) => v6,
+        }
+    }
+}
+
+impl core::fmt::Display for IpAddr {
+    /// IPv4 は "a.b.c.d"、IPv6 は "[2001:db8::1]" の形で表示する（ポートを後ろに付けやすいように）
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            IpAddr::V4(v4) => write!(f, "{}.{}.{}.{}", v4[0], v4[1], v4[2], v4[3]),
+            IpAddr::V6(v6) => write!(f, "[{}]", super::ipv6::format_ipv6(v6)),
+        }
+    }
+}
+
 /// UDP ヘッダー (8 バイト)
 #[repr(C, packed)]
 #[derive(Clone, Copy, Debug)]
@@ -252,6 +301,34 @@ pub(super) const TCP_INITIAL_RTO_TICKS: u64 = 18;
 /// RTO は指数バックオフで増加: 1s, 2s, 4s, 8s, 16s（合計約 31 秒）。
 pub(super) const TCP_MAX_RETRANSMIT: u8 = 5;
 
+/// 1 接続の受信バッファの上限（バイト）。広告ウィンドウはこの空きから計算する。
+pub(super) const TCP_RECV_BUFFER_MAX: usize = 256 * 1024;
+
+/// 全接続の受信バッファの合計の上限（バイト）。
+/// 接続が多くても、読まれないデータでカーネルヒープを食いつぶさないようにする。
+pub(super) const TCP_RECV_BUFFER_TOTAL_MAX: usize = 4 * 1024 * 1024;
+
+/// 待ち受けポートごとの backlog の既定値（tcp_listen に 0 を渡したとき）。
+/// accept されていない接続（ハンドシェイク中のものを含む）をこの数まで抱える。
+pub(super) const TCP_DEFAULT_BACKLOG: usize = 16;
+
+/// backlog の上限。大きい値を渡されても、SYN の連打でカーネルヒープを食いつぶさないよう丸める。
+pub(super) const TCP_MAX_BACKLOG: usize = 128;
+
+/// こちらが SYN で提示するウィンドウスケール（RFC 7323）。
+/// ヘッダーの 16 ビットのウィンドウを 2 ビット左シフトして読んでもらうので、
+/// 最大 65535 << 2 ≈ 256 KiB（TCP_RECV_BUFFER_MAX）まで広告できる。
+pub(super) const TCP_WINDOW_SCALE: u8 = 2;
+
+/// こちらの MSS（IPv4。MTU 1500 - IP ヘッダー 20 - TCP ヘッダー 20）。
+/// IPv6 は IP ヘッダーが 20 バイト長いのでここから 20 引いた値を使う。
+/// Nagle アルゴリズムで「小さい送信」を見分ける基準にもなる。
+pub(super) const TCP_MSS: u16 = 1460;
+
+/// 遅延 ACK の期限（PIT tick）。PIT は約 55ms 刻みなので、次のティックで送る
+/// （平均すると 40ms 前後待つことになる）。
+pub(super) const TCP_DELAYED_ACK_TICKS: u64 = 1;
+
 /// 再送待ちパケット
 ///
 /// SYN / SYN-ACK / データ / FIN を送信した後、ACK が返ってこなかった場合に
@@ -278,8 +355,8 @@ pub struct TcpConnection {
     pub state: TcpState,
     /// ローカルポート
     pub local_port: u16,
-    /// リモート IP アドレス
-    pub remote_ip: [u8; 4],
+    /// リモート IP アドレス（IPv4 / IPv6 のどちらでつながっているかもここで決まる）
+    pub remote_ip: IpAddr,
     /// リモートポート
     pub remote_port: u16,
     /// 送信シーケンス番号（次に送るバイトの番号）
@@ -294,14 +371,33 @@ pub struct TcpConnection {
     /// 再送バッファ: 未 ACK のパケット。ACK を受信したらクリアする。
     /// 1 パケットのみ保持（Stop-and-Wait 方式）。
     pub unacked_packet: Option<UnackedPacket>,
+    /// 受信側を閉じた（SHUT_RD）。以降に届いたデータは ACK だけ返して捨てる。
+    pub read_shut: bool,
+    /// 送信側を閉じた（SHUT_WR で FIN を送った）。以降の送信はエラーになるが受信は続けられる。
+    pub write_shut: bool,
+    /// ウィンドウスケールの合意ができた（双方の SYN にオプションがあった）。
+    /// true のときだけ広告ウィンドウを TCP_WINDOW_SCALE ビット右シフトして載せる。
+    pub window_scale_ok: bool,
+    /// TCP_NODELAY（Nagle アルゴリズムを使わず、小さい送信もすぐ送る）
+    pub nodelay: bool,
+    /// Nagle アルゴリズムで貯めている未送信データ。確認待ちのデータが ACK されたら送る。
+    pub send_pending: Vec<u8>,
+    /// 遅延 ACK の期限（PIT tick）。None なら返していない ACK はない。
+    pub delayed_ack_deadline: Option<u64>,
+    /// ACK を遅らせている間に受け取ったセグメント数（2 つ目ですぐ ACK する）
+    pub delayed_ack_segments: u8,
+    /// SO_RCVTIMEO（ミリ秒）。tcp_recv に timeout_ms = 0 が渡されたときの待ち時間。0 なら既定の 5000ms
+    pub recv_timeout_ms: u64,
+    /// SO_LINGER（linger 時間 0）。close で FIN ではなく RST を送り、TIME_WAIT を経ずに接続を消す
+    pub linger_abort: bool,
 }
 
 impl TcpConnection {
-    pub fn new(id: u32, local_port: u16, remote_ip: [u8; 4], remote_port: u16) -> Self {
+    pub fn new(id: u32, local_port: u16, remote_ip: IpAddr, remote_port: u16) -> Self {
         // ISN（Initial Sequence Number）をランダム化する。
         // 固定値だと TCP シーケンス番号予測攻撃に脆弱なため、
-        // RDRAND でランダムな初期値を生成する。
-        let initial_seq = super::kernel_rdrand64() as u32;
+        // カーネルのエントロピープールからランダムな初期値を取る。
+        let initial_seq = crate::random::random_u64() as u32;
         Self {
             id,
             state: TcpState::Closed,
@@ -313,6 +409,15 @@ impl TcpConnection {
             recv_buffer: Vec::new(),
             time_wait_deadline: None,
             unacked_packet: None,
+            read_shut: false,
+            write_shut: false,
+            window_scale_ok: false,
+            nodelay: false,
+            send_pending: Vec::new(),
+            delayed_ack_deadline: None,
+            delayed_ack_segments: 0,
+            recv_timeout_ms: 0,
+            linger_abort: false,
         }
     }
 }
@@ -346,7 +451,7 @@ pub(super) fn find_conn_index_by_id(state: &NetState, id: u32) -> Option<usize>
 
 pub(super) fn find_conn_index_by_tuple(
     state: &NetState,
-    src_ip: [u8; 4],
+    src_ip: IpAddr,
     src_port: u16,
     dst_port: u16,
 ) -> Option<usize> {
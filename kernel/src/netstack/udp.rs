@@ -1,21 +1,42 @@
 // udp.rs — UDP プロトコル処理
 //
 // UDP パケットの送受信と UDP ソケット API を提供する。
+// IPv4 と IPv6 の両方に対応し、ソケットは同じポート空間を共有する。
+// IPv6 では UDP チェックサムが必須なので、受信時に疑似ヘッダー込みで検証する。
 
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
 
-use crate::net_config::get_my_ip;
-use crate::serial_println;
+use crate::net_config::get_my_ipv6;
 
 use super::{
-    ETHERTYPE_IPV4, IP_PROTO_UDP,
-    with_net_state, get_my_mac, send_frame,
-    calculate_checksum, calculate_udp_checksum, wait_net_condition,
+    IP_PROTO_UDP,
+    with_net_state, get_my_mac,
+    is_loopback_address, source_ip_for,
+    calculate_udp_checksum, wait_net_condition,
     UdpSocketEntry,
 };
-use super::types::{EthernetHeader, Ipv4Header, UdpHeader};
+use super::types::{IpAddr, Ipv4Header, UdpHeader};
 use super::arp::resolve_mac;
+use super::ipv4::{send_ipv4_packet, IPV4_MAX_PAYLOAD};
+use super::ipv6::{calculate_ipv6_checksum, resolve_neighbor, send_ipv6_packet, Ipv6Header};
+
+/// 受信した UDP データグラム: (送信元 IP, 送信元ポート, データ)
+pub type UdpDatagram = (IpAddr, u16, Vec<u8>);
+
+/// 受信したデータグラムを宛先ポートにバインドされたソケットのキューに積む
+///
+/// カーネル内の DNS クライアントも同じソケット API を使うので、
+/// バインドされていないポート宛てのパケットは捨てる。
+/// SO_REUSEADDR で同じポートに複数のソケットがあるときは、最後に bind したソケットに渡す
+/// （落ちたサービスが残したソケットではなく、再起動したサービスが受け取れるように）。
+fn deliver_datagram(dst_port: u16, src_ip: IpAddr, src_port: u16, data: &[u8]) {
+    with_net_state(|state| {
+        if let Some(sock) = state.udp_sockets.iter_mut().rev().find(|s| s.local_port == dst_port) {
+            sock.recv_queue.push_back((src_ip, src_port, data.to_vec()));
+        }
+    });
+}
 
 /// UDP パケットを処理する
 pub(super) fn handle_udp(ip_header: &Ipv4Header, payload: &[u8]) {
@@ -29,41 +50,116 @@ pub(super) fn handle_udp(ip_header: &Ipv4Header, payload: &[u8]) {
     let src_port = udp_header.src_port_u16();
     let dst_port = udp_header.dst_port_u16();
 
-    serial_println!("[net] net: UDP packet from port {} to port {}, len={}",
+    net_trace!("net: UDP packet from port {} to port {}, len={}",
         src_port, dst_port, udp_payload.len()
     );
 
-    with_net_state(|state| {
-        // 宛先ポートにバインドされた UDP ソケットがあるか探す
-        if let Some(sock) = state.udp_sockets.iter_mut().find(|s| s.local_port == dst_port) {
-            sock.recv_queue.push_back((ip_header.src_ip, src_port, udp_payload.to_vec()));
-            return;
-        }
+    deliver_datagram(dst_port, IpAddr::V4(ip_header.src_ip), src_port, udp_payload);
+}
 
-        // バインドされたソケットがなければ、既存の DNS レスポンス処理にフォールバック
-        if src_port == 53 {
-            state.udp_response = Some((dst_port, udp_payload.to_vec()));
-        }
-    });
+/// IPv6 で届いた UDP パケットを処理する
+pub(super) fn handle_udp_v6(ipv6_header: &Ipv6Header, payload: &[u8]) {
+    let src_ip = ipv6_header.src_ip;
+    let dst_ip = ipv6_header.dst_ip;
+    let Some((src_port, dst_port, udp_payload)) = parse_udp_datagram_v6(&src_ip, &dst_ip, payload) else {
+        net_debug!("udp6: dropped malformed datagram");
+        return;
+    };
+
+    net_trace!("net: UDP6 packet from port {} to port {}, len={}",
+        src_port, dst_port, udp_payload.len()
+    );
+
+    deliver_datagram(dst_port, IpAddr::V6(src_ip), src_port, udp_payload);
+}
+
+/// IPv6 用の UDP データグラム（UDP ヘッダー + データ）を組み立てる
+///
+/// チェックサムは IPv6 疑似ヘッダー込みで計算する。IPv6 では 0 は
+/// 「チェックサムなし」を意味して不正なので、計算結果が 0 なら 0xFFFF を入れる。
+pub fn build_udp_datagram_v6(
+    src_ip: &[u8; 16],
+    dst_ip: &[u8; 16],
+    src_port: u16,
+    dst_port: u16,
+    payload: &[u8],
+) -> Vec<u8> {
+    let udp_length = 8 + payload.len();
+    let mut datagram = Vec::with_capacity(udp_length);
+    datagram.extend_from_slice(&src_port.to_be_bytes());
+    datagram.extend_from_slice(&dst_port.to_be_bytes());
+    datagram.extend_from_slice(&(udp_length as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+
+    let checksum = calculate_ipv6_checksum(src_ip, dst_ip, IP_PROTO_UDP, &datagram);
+    let checksum = if checksum == 0 { 0xFFFF } else { checksum };
+    datagram[6..8].copy_from_slice(&checksum.to_be_bytes());
+    datagram
+}
+
+/// IPv6 で受信した UDP データグラムを検証して (送信元ポート, 宛先ポート, データ) を返す
+///
+/// 長さフィールドが範囲外、チェックサムが 0（IPv6 では必須）、
+/// または疑似ヘッダー込みのチェックサムが合わなければ None。
+/// 長さフィールドより後ろ（Ethernet のパディング等）は無視する。
+pub fn parse_udp_datagram_v6<'a>(
+    src_ip: &[u8; 16],
+    dst_ip: &[u8; 16],
+    data: &'a [u8],
+) -> Option<(u16, u16, &'a [u8])> {
+    if data.len() < 8 {
+        return None;
+    }
+    let udp_length = u16::from_be_bytes([data[4], data[5]]) as usize;
+    if udp_length < 8 || udp_length > data.len() {
+        return None;
+    }
+    if data[6] == 0 && data[7] == 0 {
+        return None;
+    }
+    let datagram = &data[..udp_length];
+    if calculate_ipv6_checksum(src_ip, dst_ip, IP_PROTO_UDP, datagram) != 0 {
+        return None;
+    }
+    let src_port = u16::from_be_bytes([data[0], data[1]]);
+    let dst_port = u16::from_be_bytes([data[2], data[3]]);
+    Some((src_port, dst_port, &datagram[8..]))
+}
+
+/// IPv6 で UDP パケットを送信する
+///
+/// 宛先（またはルーター）の MAC アドレスは NDP 近隣キャッシュで解決する。
+pub fn send_udp_packet_v6(
+    dst_ip: [u8; 16],
+    dst_port: u16,
+    src_port: u16,
+    payload: &[u8],
+) -> Result<(), &'static str> {
+    // ARP と同じく、解決できない相手には送らずにエラーを返す
+    resolve_neighbor(&dst_ip)?;
+
+    let datagram = build_udp_datagram_v6(&get_my_ipv6(), &dst_ip, src_port, dst_port, payload);
+    send_ipv6_packet(&dst_ip, IP_PROTO_UDP, &datagram);
+    Ok(())
 }
 
 /// UDP パケットを送信する
+///
+/// 自分の IP / 127.0.0.1 宛は NIC を通さず、その場で受信処理に回す（ソフトウェアループバック）。
 pub fn send_udp_packet(
     dst_ip: [u8; 4],
     dst_port: u16,
     src_port: u16,
     payload: &[u8],
 ) -> Result<(), &'static str> {
-    let my_mac = get_my_mac();
-    let dst_mac = resolve_mac(&dst_ip)?;
-
-    let eth_header = EthernetHeader {
-        dst_mac,
-        src_mac: my_mac,
-        ethertype: ETHERTYPE_IPV4.to_be_bytes(),
-    };
-
+    // UDP の長さフィールドと IP の全長（どちらも 16 ビット）に収まること
     let udp_length = 8 + payload.len();
+    if udp_length > IPV4_MAX_PAYLOAD {
+        return Err("datagram too large");
+    }
+    let dst_mac = if is_loopback_address(&dst_ip) { get_my_mac() } else { resolve_mac(&dst_ip)? };
+
     let udp_header = UdpHeader {
         src_port: src_port.to_be_bytes(),
         dst_port: dst_port.to_be_bytes(),
@@ -71,49 +167,19 @@ pub fn send_udp_packet(
         checksum: [0, 0],
     };
 
-    let my_ip = get_my_ip();
+    let my_ip = source_ip_for(&dst_ip);
     let udp_checksum = calculate_udp_checksum(&my_ip, &dst_ip, &udp_header, payload);
 
-    let total_length = 20 + udp_length;
-    let ip_header = Ipv4Header {
-        version_ihl: 0x45,
-        tos: 0,
-        total_length: (total_length as u16).to_be_bytes(),
-        identification: [0, 0],
-        flags_fragment: [0x40, 0x00],
-        ttl: 64,
-        protocol: IP_PROTO_UDP,
-        checksum: [0, 0],
-        src_ip: my_ip,
-        dst_ip,
-    };
-
-    let ip_header_bytes = unsafe {
-        core::slice::from_raw_parts(&ip_header as *const _ as *const u8, 20)
-    };
-    let ip_checksum = calculate_checksum(ip_header_bytes);
-
-    let mut packet = Vec::with_capacity(14 + 20 + udp_length);
-
-    packet.extend_from_slice(unsafe {
-        core::slice::from_raw_parts(&eth_header as *const _ as *const u8, 14)
-    });
-
-    let mut ip_header_with_checksum = ip_header;
-    ip_header_with_checksum.checksum = ip_checksum.to_be_bytes();
-    packet.extend_from_slice(unsafe {
-        core::slice::from_raw_parts(&ip_header_with_checksum as *const _ as *const u8, 20)
-    });
-
+    let mut datagram = Vec::with_capacity(udp_length);
     let mut udp_header_with_checksum = udp_header;
     udp_header_with_checksum.checksum = udp_checksum.to_be_bytes();
-    packet.extend_from_slice(unsafe {
+    datagram.extend_from_slice(unsafe {
         core::slice::from_raw_parts(&udp_header_with_checksum as *const _ as *const u8, 8)
     });
+    datagram.extend_from_slice(payload);
 
-    packet.extend_from_slice(payload);
-
-    send_frame(&packet).map_err(|_| "send failed")
+    // MTU を超える分は IP 層でフラグメントに分けて送る
+    send_ipv4_packet(dst_mac, my_ip, dst_ip, IP_PROTO_UDP, &datagram)
 }
 
 // ============================================================
@@ -122,6 +188,14 @@ pub fn send_udp_packet(
 
 /// UDP ソケットをバインドする
 pub fn udp_bind(port: u16) -> Result<u32, &'static str> {
+    udp_bind_reuse(port, false)
+}
+
+/// SO_REUSEADDR を指定して UDP ソケットをバインドする
+///
+/// 使用中のポートでも、そのポートのソケットがすべて SO_REUSEADDR 付きなら bind できる。
+/// reuse_addr は新しいソケット自身の SO_REUSEADDR（さらに後から bind されるのを許すか）。
+pub fn udp_bind_reuse(port: u16, reuse_addr: bool) -> Result<u32, &'static str> {
     with_net_state(|state| {
         let local_port = if port == 0 {
             let p = state.udp_next_port;
@@ -129,7 +203,7 @@ pub fn udp_bind(port: u16) -> Result<u32, &'static str> {
             state.udp_next_port = if next < 49152 { 49152 } else { next };
             p
         } else {
-            if state.udp_sockets.iter().any(|s| s.local_port == port) {
+            if state.udp_sockets.iter().any(|s| s.local_port == port && !s.reuse_addr) {
                 return Err("port already in use");
             }
             port
@@ -141,17 +215,19 @@ pub fn udp_bind(port: u16) -> Result<u32, &'static str> {
             id,
             local_port,
             recv_queue: VecDeque::new(),
+            recv_timeout_ms: 0,
+            reuse_addr,
         });
 
-        serial_println!("[net] udp: bind socket id={} port={}", id, local_port);
+        net_debug!("udp: bind socket id={} port={}", id, local_port);
         Ok(id)
     })
 }
 
-/// UDP ソケットでデータを送信する
+/// UDP ソケットでデータを送信する（宛先の種類で IPv4 / IPv6 を選ぶ）
 pub fn udp_send_to(
     socket_id: u32,
-    dst_ip: [u8; 4],
+    dst_ip: IpAddr,
     dst_port: u16,
     data: &[u8],
 ) -> Result<(), &'static str> {
@@ -164,23 +240,42 @@ pub fn udp_send_to(
         Ok(sock.local_port)
     })?;
 
-    serial_println!("[net] udp: send_to socket id={} -> {}.{}.{}.{}:{} len={}",
-        socket_id, dst_ip[0], dst_ip[1], dst_ip[2], dst_ip[3], dst_port, data.len()
-    );
-
-    send_udp_packet(dst_ip, dst_port, src_port, data)
+    match dst_ip {
+        IpAddr::V4(v4) => {
+            net_debug!("udp: send_to socket id={} -> {}.{}.{}.{}:{} len={}",
+                socket_id, v4[0], v4[1], v4[2], v4[3], dst_port, data.len()
+            );
+            send_udp_packet(v4, dst_port, src_port, data)
+        }
+        IpAddr::V6(v6) => {
+            net_debug!("udp: send_to socket id={} -> [{}]:{} len={}",
+                socket_id, super::ipv6::format_ipv6(&v6), dst_port, data.len()
+            );
+            send_udp_packet_v6(v6, dst_port, src_port, data)
+        }
+    }
 }
 
 /// UDP ソケットからデータを受信する（ブロッキング、タイムアウト付き）
 ///
 /// net_poller がパケットを処理して recv_queue にデータを追加するのを待つ。
-/// timeout_ms == 0 の場合はデフォルトタイムアウト（5000ms）を使用する。
+/// timeout_ms == 0 の場合は SO_RCVTIMEO、未設定ならデフォルトタイムアウト（5000ms）を使用する。
 pub fn udp_recv_from(
     socket_id: u32,
     timeout_ms: u64,
-) -> Result<([u8; 4], u16, Vec<u8>), &'static str> {
-    // timeout_ms == 0 は「デフォルトタイムアウト」の意味
-    let effective_timeout = if timeout_ms == 0 { 5000 } else { timeout_ms };
+) -> Result<UdpDatagram, &'static str> {
+    // timeout_ms == 0 は「ソケットに設定されたタイムアウト」の意味
+    let effective_timeout = if timeout_ms == 0 {
+        let configured = with_net_state(|state| {
+            state.udp_sockets.iter().find(|s| s.id == socket_id).map(|s| s.recv_timeout_ms)
+        });
+        match configured {
+            Some(0) | None => 5000,
+            Some(ms) => ms,
+        }
+    } else {
+        timeout_ms
+    };
 
     let check = || {
         with_net_state(|state| {
@@ -208,6 +303,23 @@ pub fn udp_recv_from(
     }
 }
 
+/// UDP ソケットから受信済みデータを 1 件取り出す（ノンブロッキング）
+///
+/// キューが空なら Ok(None)。共有ソケットを複数の待機者で捌く
+/// カーネル内 DNS リゾルバのように、待ち方を呼び出し側で決めたい場合に使う。
+pub fn udp_try_recv_from(
+    socket_id: u32,
+) -> Result<Option<UdpDatagram>, &'static str> {
+    with_net_state(|state| {
+        let sock = state
+            .udp_sockets
+            .iter_mut()
+            .find(|s| s.id == socket_id)
+            .ok_or("no such UDP socket")?;
+        Ok(sock.recv_queue.pop_front())
+    })
+}
+
 /// UDP ソケットを閉じる
 pub fn udp_close(socket_id: u32) -> Result<(), &'static str> {
     with_net_state(|state| {
@@ -217,7 +329,7 @@ pub fn udp_close(socket_id: u32) -> Result<(), &'static str> {
             .position(|s| s.id == socket_id)
             .ok_or("no such UDP socket")?;
         state.udp_sockets.remove(idx);
-        serial_println!("[net] udp: close socket id={}", socket_id);
+        net_debug!("udp: close socket id={}", socket_id);
         Ok(())
     })
 }
@@ -233,3 +345,37 @@ pub fn udp_local_port(socket_id: u32) -> Result<u16, &'static str> {
         Ok(sock.local_port)
     })
 }
+
+/// バインド中の UDP ソケット数を返す
+///
+/// カーネル内部（DNS など）で使ったソケットが閉じ忘れられていないかの確認用。
+pub fn udp_socket_count() -> usize {
+    with_net_state(|state| state.udp_sockets.len())
+}
+
+/// 127.0.0.1 での UDP 送受信のテスト
+///
+/// 自分のソケットに 127.0.0.1 宛で送ると、NIC を通らずその場で受信キューに入り、
+/// 送信元は 127.0.0.1 になる。自分の IP 宛も同じくループバックで届く。
+///
+/// selftest から呼ばれる。
+pub fn test_udp_loopback() -> bool {
+    let Ok(sock) = udp_bind(0) else {
+        return false;
+    };
+    let ok = (|| {
+        let port = udp_local_port(sock).ok()?;
+        udp_send_to(sock, IpAddr::V4(super::LOOPBACK_IP), port, b"lo").ok()?;
+        let (src_ip, src_port, data) = udp_try_recv_from(sock).ok()??;
+        let via_loopback = src_ip == IpAddr::V4(super::LOOPBACK_IP) && src_port == port && data == b"lo";
+
+        let my_ip = crate::net_config::get_my_ip();
+        udp_send_to(sock, IpAddr::V4(my_ip), port, b"me").ok()?;
+        let (src_ip, _, data) = udp_try_recv_from(sock).ok()??;
+        let via_my_ip = src_ip == IpAddr::V4(my_ip) && data == b"me";
+
+        Some(via_loopback && via_my_ip)
+    })();
+    let _ = udp_close(sock);
+    ok == Some(true)
+}
@@ -0,0 +1,166 @@
+// netstack/watchdog.rs — net_poller のウォッチドッグ
+//
+// net_poller が NIC や NET_STATE のロックで固まると、ネットワーク全体が
+// 何のログも出さずに止まってしまい、長時間のネットワークテストで原因が追えない。
+//
+// net_poller はループのたびにハートビートを 1 つ進め、net_watchdog タスクが
+// 約 1 秒ごとにそれを見に行く。数秒間進んでいなければ警告を出し、
+// NIC のロックが取れれば RX/TX リングを作り直して（MAC アドレスはそのまま）
+// net_poller を起こす。ロックを握ったまま固まっている場合は、
+// 作り直すと持ち主とデータを取り合うので警告だけ出す。
+// 止まったことの検出は一度だけ行い、ハートビートが再び進んだら解除する。
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// ハートビートが止まったとみなすまでのティック数の既定値（約 5 秒。1 ティック ≈ 55ms）
+const DEFAULT_STALL_TICKS: u64 = 91;
+
+/// ウォッチドッグがハートビートを見に行く間隔
+const CHECK_INTERVAL_MS: u64 = 1000;
+
+/// net_poller のループが回った回数
+static HEARTBEAT: AtomicU64 = AtomicU64::new(0);
+
+/// ハートビートが止まったとみなすまでのティック数（selftest が短くする）
+static STALL_TICKS: AtomicU64 = AtomicU64::new(DEFAULT_STALL_TICKS);
+
+/// 止まったことを検出した回数
+static STALLS: AtomicU64 = AtomicU64::new(0);
+
+/// NIC のリングを作り直した回数
+static NIC_RESETS: AtomicU64 = AtomicU64::new(0);
+
+/// selftest 用: true の間、net_poller はハートビートを進めずに待ち続ける
+static SIMULATE_STALL: AtomicBool = AtomicBool::new(false);
+
+/// net_poller がループの先頭で呼ぶ
+pub(super) fn heartbeat() {
+    HEARTBEAT.fetch_add(1, Ordering::Relaxed);
+    // selftest が止まった状態を再現している間はここで待つ（ロックは何も持っていない）
+    while SIMULATE_STALL.load(Ordering::Relaxed) {
+        crate::scheduler::sleep_ms(10);
+    }
+}
+
+/// ハートビートの進み具合を見張る
+struct HeartbeatWatch {
+    last_beat: u64,
+    /// last_beat が最後に変わったときのティック
+    last_change: u64,
+    /// 止まったことを検出済みで、まだ再開していないか
+    stalled: bool,
+}
+
+impl HeartbeatWatch {
+    fn new(now: u64) -> Self {
+        Self { last_beat: HEARTBEAT.load(Ordering::Relaxed), last_change: now, stalled: false }
+    }
+
+    /// 今のハートビートを記録し、新たに止まったことを検出したら true を返す
+    fn observe(&mut self, beat: u64, now: u64, stall_ticks: u64) -> bool {
+        if beat != self.last_beat {
+            if self.stalled {
+                net_debug!("watchdog: net_poller resumed");
+            }
+            self.last_beat = beat;
+            self.last_change = now;
+            self.stalled = false;
+            return false;
+        }
+        if self.stalled || now.saturating_sub(self.last_change) < stall_ticks {
+            return false;
+        }
+        self.stalled = true;
+        true
+    }
+}
+
+/// net_poller を見張るカーネルタスク
+pub fn net_watchdog_task() {
+    let ticks = || crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    let mut watch = HeartbeatWatch::new(ticks());
+    loop {
+        crate::scheduler::sleep_ms(CHECK_INTERVAL_MS);
+        let now = ticks();
+        let stall_ticks = STALL_TICKS.load(Ordering::Relaxed);
+        if !watch.observe(HEARTBEAT.load(Ordering::Relaxed), now, stall_ticks) {
+            continue;
+        }
+        crate::klog!(
+            Warn, Net,
+            "watchdog: net_poller has not run for {} ticks (heartbeat={})",
+            now - watch.last_change, watch.last_beat
+        );
+        if reset_nic() {
+            NIC_RESETS.fetch_add(1, Ordering::Relaxed);
+            crate::klog!(Warn, Net, "watchdog: NIC rings reset");
+        } else {
+            crate::klog!(Warn, Net, "watchdog: NIC is locked or missing, not resetting");
+        }
+        // リセットの結果まで反映してから数える（selftest はこの値の変化を待つ）
+        STALLS.fetch_add(1, Ordering::Relaxed);
+        let id = super::NET_POLLER_TASK_ID.load(Ordering::Relaxed);
+        if id != 0 {
+            crate::scheduler::wake_task(id);
+        }
+    }
+}
+
+/// NIC の RX/TX リングを作り直す。ロックが取れないか NIC がなければ false。
+///
+/// net_poller がロックを握ったまま固まっていると lock() では戻れなくなるので try_lock を使う。
+fn reset_nic() -> bool {
+    if let Some(mut drv) = crate::virtio_net::VIRTIO_NET.try_lock() {
+        if let Some(d) = drv.as_mut() {
+            d.reset_rings();
+            return true;
+        }
+    } else {
+        return false;
+    }
+    if let Some(mut drv) = crate::e1000e::E1000E.try_lock()
+        && let Some(d) = drv.as_mut()
+    {
+        d.reset_rings();
+        return true;
+    }
+    false
+}
+
+/// ウォッチドッグのテスト
+///
+/// net_poller にハートビートを止めさせ、ウォッチドッグが検出して NIC のリングを
+/// 作り直すことを確認する。作り直した後も MAC アドレスが変わらず、
+/// ゲートウェイの ARP 解決（送信と受信の両方）ができることも確かめる。
+/// selftest から呼ばれる。
+pub fn test_net_watchdog() -> bool {
+    let mac_before = super::get_my_mac();
+    let stalls_before = STALLS.load(Ordering::Relaxed);
+    let resets_before = NIC_RESETS.load(Ordering::Relaxed);
+
+    // 待ち時間を短くするため、止まったとみなすまでを約 0.5 秒にする
+    let prev_stall_ticks = STALL_TICKS.swap(9, Ordering::Relaxed);
+    SIMULATE_STALL.store(true, Ordering::Relaxed);
+    let mut fired = false;
+    for _ in 0..80 {
+        crate::scheduler::sleep_ms(50);
+        if STALLS.load(Ordering::Relaxed) > stalls_before {
+            fired = true;
+            break;
+        }
+    }
+    SIMULATE_STALL.store(false, Ordering::Relaxed);
+    STALL_TICKS.store(prev_stall_ticks, Ordering::Relaxed);
+    if !fired {
+        crate::kprintln!("    watchdog did not fire");
+        return false;
+    }
+    let reset = NIC_RESETS.load(Ordering::Relaxed) > resets_before;
+
+    // 作り直したリングで送受信できること
+    let gateway = crate::net_config::get_gateway_ip();
+    super::arp_delete(&gateway);
+    let resolved = super::resolve_mac(&gateway).is_ok();
+
+    reset && resolved && super::get_my_mac() == mac_before
+}
@@ -27,7 +27,8 @@
 //
 // ## 現在の実装
 //
-// - ポーリング方式（割り込みは使わない）
+// - 完了はポーリングで検出する。APIC モードで MSI-X があれば
+//   CQ の更新で MSI-X 割り込み (InterruptIndex::NvmeMsix) も発火させ、通知と統計に使う
 // - Admin Queue + I/O Queue 各 1 組
 // - Read/Write は 1 セクタ (512 バイト) 単位
 // - PRP (Physical Region Page) は PRP1 のみ使用（4KB 以内の転送）
@@ -35,7 +36,7 @@
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::alloc::Layout;
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::{fence, AtomicU64, Ordering};
 use spin::Mutex;
 use crate::pci;
 use crate::serial_println;
@@ -321,6 +322,32 @@ pub struct NvmeDevice {
     ns_size: u64,
     /// ネームスペース 1 の論理ブロックサイズ（バイト、通常 512 or 4096）。
     block_size: u32,
+    /// CQ の更新を MSI-X で通知しているか
+    msix: bool,
+}
+
+/// Identify Controller で得たコントローラ情報
+pub struct NvmeControllerInfo {
+    /// モデル名
+    pub model: String,
+    /// シリアル番号
+    pub serial: String,
+    /// ファームウェアリビジョン
+    pub firmware: String,
+    /// ネームスペース ID の最大値 (NN)
+    pub namespace_count: u32,
+}
+
+/// Identify Namespace で得たネームスペース情報
+pub struct NvmeNamespaceInfo {
+    /// ネームスペース ID
+    pub nsid: u32,
+    /// ネームスペースサイズ（論理ブロック数、NSZE）
+    pub size_lbas: u64,
+    /// 割り当て可能な論理ブロック数（NCAP）
+    pub capacity_lbas: u64,
+    /// 論理ブロックサイズ（バイト）
+    pub block_size: u32,
 }
 
 /// NvmeDevice は raw pointer を含むが、Mutex で保護されるため Send/Sync は安全
@@ -335,6 +362,21 @@ unsafe impl Sync for NvmeDevice {}
 /// init() で検出・初期化された NVMe コントローラが格納される。
 pub static NVME_DEVICES: Mutex<Vec<NvmeDevice>> = Mutex::new(Vec::new());
 
+/// MSI-X 割り込みを受けた回数（全コントローラの合計）
+static MSIX_IRQ_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// これまでに受けた MSI-X 割り込みの回数
+pub fn msix_irq_count() -> u64 {
+    MSIX_IRQ_COUNT.load(Ordering::Relaxed)
+}
+
+/// MSI-X 割り込みハンドラ本体（interrupts.rs から呼ばれる）。
+/// 今は数えるだけで、待っている側は起こさない（完了はコマンド発行側がポーリングで拾う）。
+/// 割り込みで待ち手を起こすのは TODO.md の「MSI-X / INTx の残り」を参照。
+pub fn handle_msix_interrupt() {
+    MSIX_IRQ_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
 // ============================================================
 // Doorbell レジスタのアドレス計算
 // ============================================================
@@ -452,6 +494,25 @@ pub fn device_count() -> usize {
     NVME_DEVICES.lock().len()
 }
 
+/// 最初の NVMe コントローラの Identify Controller 情報を取得する。
+pub fn identify_controller() -> Result<NvmeControllerInfo, &'static str> {
+    let mut devs = NVME_DEVICES.lock();
+    let dev = devs.first_mut().ok_or("NVMe: no device")?;
+    dev.identify_controller()
+}
+
+/// 最初の NVMe コントローラのネームスペース nsid の情報を取得する。
+pub fn identify_namespace(nsid: u32) -> Result<NvmeNamespaceInfo, &'static str> {
+    let mut devs = NVME_DEVICES.lock();
+    let dev = devs.first_mut().ok_or("NVMe: no device")?;
+    dev.identify_namespace(nsid)
+}
+
+/// Identify データの空白埋め ASCII フィールドを文字列にする
+fn ascii_field(bytes: &[u8]) -> String {
+    core::str::from_utf8(bytes).unwrap_or("(unknown)").trim().into()
+}
+
 /// 個別の NVMe コントローラを初期化する。
 fn init_controller(ctrl: &pci::PciDevice) -> Result<NvmeDevice, &'static str> {
     // --- PCI Command レジスタで Bus Master + Memory Space を有効化 ---
@@ -516,6 +577,12 @@ fn init_controller(ctrl: &pci::PciDevice) -> Result<NvmeDevice, &'static str> {
         }
     }
 
+    // --- MSI-X の有効化 ---
+    // Admin CQ と I/O CQ をどちらもテーブルのエントリ 0 (NvmeMsix ベクタ) に向ける。
+    // コントローラを有効化する前に済ませておく（Admin CQ の割り込みは常に有効なため）。
+    let msix = enable_msix(ctrl);
+    serial_println!("NVMe: interrupt mode: {}", if msix { "MSI-X" } else { "polling" });
+
     // --- Admin Queue の物理メモリを確保 ---
     // Admin SQ: queue_depth × 64 バイト、ページアライン (4KB)
     // Admin CQ: queue_depth × 16 バイト、ページアライン (4KB)
@@ -590,14 +657,16 @@ fn init_controller(ctrl: &pci::PciDevice) -> Result<NvmeDevice, &'static str> {
         io_queue: None,
         ns_size: 0,
         block_size: 512,
+        msix,
     };
 
     // --- Identify Controller ---
-    let model = dev.identify_controller()?;
+    let model = dev.identify_controller()?.model;
     serial_println!("NVMe: controller: {}", model);
 
     // --- Identify Namespace 1 ---
-    let (ns_size, block_size) = dev.identify_namespace(1)?;
+    let ns = dev.identify_namespace(1)?;
+    let (ns_size, block_size) = (ns.size_lbas, ns.block_size);
     dev.ns_size = ns_size;
     dev.block_size = block_size;
     let size_mib = ns_size * block_size as u64 / 1024 / 1024;
@@ -617,89 +686,117 @@ fn init_controller(ctrl: &pci::PciDevice) -> Result<NvmeDevice, &'static str> {
     Ok(dev)
 }
 
-impl NvmeDevice {
-    /// Identify Controller コマンドを発行し、モデル名を取得する。
-    ///
-    /// Admin opcode 0x06, CNS=1 でコントローラ情報 (4096 バイト) を取得する。
-    /// Identify データ構造体の byte [24..63] にモデル名 (40 文字) が格納されている。
-    fn identify_controller(&mut self) -> Result<String, &'static str> {
-        // Identify レスポンス用の 4096 バイトバッファ（ページアライン）
-        let layout = Layout::from_size_align(4096, 4096).map_err(|_| "NVMe: layout error")?;
-        let buf = unsafe { alloc::alloc::alloc_zeroed(layout) };
-        if buf.is_null() {
-            return Err("NVMe: failed to allocate Identify buffer");
+/// MSI-X を有効化してテーブルのエントリ 0 を NvmeMsix ベクタに向ける。
+///
+/// APIC が無効、MSI-X がない、テーブルの設定に失敗した場合は false
+/// （ポーリングのみで動く）。
+fn enable_msix(ctrl: &pci::PciDevice) -> bool {
+    let (address, cap) = match (
+        crate::apic::msi_address(),
+        pci::find_msix(ctrl.bus, ctrl.device, ctrl.function),
+    ) {
+        (Some(address), Some(cap)) => (address, cap),
+        _ => return false,
+    };
+    let vector = crate::interrupts::InterruptIndex::NvmeMsix.as_u8();
+    match pci::enable_msix(ctrl.bus, ctrl.device, ctrl.function, &cap, address, &[(0, vector)]) {
+        Ok(()) => true,
+        Err(e) => {
+            serial_println!("NVMe: {}", e);
+            false
         }
+    }
+}
 
-        let mut sqe = NvmeSqe::zeroed();
-        sqe.opcode = ADMIN_IDENTIFY;
-        sqe.nsid = 0;
-        sqe.prp1 = buf as u64;
-        sqe.cdw10 = IDENTIFY_CNS_CONTROLLER;
-
-        self.admin_queue.submit(sqe);
-        self.admin_queue.poll_completion()?;
-
-        // モデル名: byte [24..63] (40 bytes), ASCII
-        let data = unsafe { core::slice::from_raw_parts(buf, 4096) };
-        let model_bytes = &data[24..64];
-        let model = core::str::from_utf8(model_bytes)
-            .unwrap_or("(unknown)")
-            .trim()
-            .into();
-
-        unsafe { alloc::alloc::dealloc(buf, layout); }
+impl NvmeDevice {
+    /// CQ の更新を MSI-X 割り込みで通知しているか
+    pub fn uses_msix(&self) -> bool {
+        self.msix
+    }
 
-        Ok(model)
+    /// ネームスペース 1 の容量（論理ブロック数）を返す。
+    pub fn capacity(&self) -> u64 {
+        self.ns_size
     }
 
-    /// Identify Namespace コマンドを発行し、容量とブロックサイズを取得する。
+    /// Identify コマンド (Admin opcode 0x06) を発行し、4096 バイトのデータ構造を返す。
     ///
-    /// Admin opcode 0x06, CNS=0 でネームスペース情報 (4096 バイト) を取得する。
-    /// NSZE (byte [0..7]): ネームスペースサイズ（論理ブロック数）
-    /// LBAF[0] (byte [128..131]): LBA Format 0 — LBADS フィールドからブロックサイズを計算
-    fn identify_namespace(&mut self, nsid: u32) -> Result<(u64, u32), &'static str> {
+    /// コントローラが PRP1 の指すページに書き込むので、ページアラインのバッファを使う。
+    fn admin_identify(&mut self, nsid: u32, cns: u32) -> Result<Vec<u8>, &'static str> {
         let layout = Layout::from_size_align(4096, 4096).map_err(|_| "NVMe: layout error")?;
         let buf = unsafe { alloc::alloc::alloc_zeroed(layout) };
         if buf.is_null() {
-            return Err("NVMe: failed to allocate Identify NS buffer");
+            return Err("NVMe: failed to allocate Identify buffer");
         }
 
         let mut sqe = NvmeSqe::zeroed();
         sqe.opcode = ADMIN_IDENTIFY;
         sqe.nsid = nsid;
         sqe.prp1 = buf as u64;
-        sqe.cdw10 = IDENTIFY_CNS_NAMESPACE;
+        sqe.cdw10 = cns;
 
         self.admin_queue.submit(sqe);
-        self.admin_queue.poll_completion()?;
-
-        let data = unsafe { core::slice::from_raw_parts(buf, 4096) };
+        let result = self.admin_queue.poll_completion();
+        let data = unsafe { core::slice::from_raw_parts(buf, 4096) }.to_vec();
+        unsafe { alloc::alloc::dealloc(buf, layout); }
 
-        // NSZE: byte [0..7] — ネームスペースサイズ（論理ブロック数、リトルエンディアン 64-bit）
-        let nsze = u64::from_le_bytes([
-            data[0], data[1], data[2], data[3],
-            data[4], data[5], data[6], data[7],
-        ]);
+        result.map(|_| data)
+    }
 
-        // FLBAS: byte [26] — Formatted LBA Size
-        // bits [3:0] = 使用中の LBA Format のインデックス
-        let flbas = data[26];
-        let lba_format_index = (flbas & 0x0F) as usize;
+    /// Identify Controller コマンドを発行し、コントローラ情報を取得する。
+    ///
+    /// CNS=1 で返るデータ構造 (4096 バイト) のうち:
+    /// - SN (byte [4..23]): シリアル番号 (20 文字、空白埋め ASCII)
+    /// - MN (byte [24..63]): モデル名 (40 文字)
+    /// - FR (byte [64..71]): ファームウェアリビジョン (8 文字)
+    /// - NN (byte [516..519]): ネームスペース ID の最大値
+    pub fn identify_controller(&mut self) -> Result<NvmeControllerInfo, &'static str> {
+        let data = self.admin_identify(0, IDENTIFY_CNS_CONTROLLER)?;
+        Ok(NvmeControllerInfo {
+            serial: ascii_field(&data[4..24]),
+            model: ascii_field(&data[24..64]),
+            firmware: ascii_field(&data[64..72]),
+            namespace_count: u32::from_le_bytes([data[516], data[517], data[518], data[519]]),
+        })
+    }
 
-        // LBAF (LBA Format) テーブル: byte [128..191]、各 4 バイト
-        // LBAF[n] の byte [2] の bits [7:0] = LBADS (LBA Data Size, 2^n バイト)
+    /// Identify Namespace コマンドを発行し、容量とブロックサイズを取得する。
+    ///
+    /// CNS=0 で返るデータ構造 (4096 バイト) のうち:
+    /// - NSZE (byte [0..7]): ネームスペースサイズ（論理ブロック数）
+    /// - NCAP (byte [8..15]): 割り当て可能な論理ブロック数
+    /// - FLBAS (byte [26]) と LBAF テーブル (byte [128..191]): ブロックサイズ
+    ///
+    /// 使われていない（非アクティブな）ネームスペースは全体が 0 で返るのでエラーにする。
+    pub fn identify_namespace(&mut self, nsid: u32) -> Result<NvmeNamespaceInfo, &'static str> {
+        let data = self.admin_identify(nsid, IDENTIFY_CNS_NAMESPACE)?;
+
+        // NSZE / NCAP: リトルエンディアン 64-bit
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let size_lbas = read_u64(0);
+        let capacity_lbas = read_u64(8);
+
+        // FLBAS: bits [3:0] = 使用中の LBA Format のインデックス
+        let lba_format_index = (data[26] & 0x0F) as usize;
+
+        // LBAF[n] の byte [2] = LBADS (LBA Data Size, 2^n バイト)
         // 通常 LBADS=9 (512B) or LBADS=12 (4KB)
-        let lbaf_offset = 128 + lba_format_index * 4;
-        let lbads = data[lbaf_offset + 2];
-        let block_size = 1u32 << lbads;
-
-        unsafe { alloc::alloc::dealloc(buf, layout); }
+        let lbads = data[128 + lba_format_index * 4 + 2];
 
-        if nsze == 0 {
+        if size_lbas == 0 {
             return Err("NVMe: namespace size is 0");
         }
 
-        Ok((nsze, block_size))
+        Ok(NvmeNamespaceInfo {
+            nsid,
+            size_lbas,
+            capacity_lbas,
+            block_size: 1u32 << lbads,
+        })
     }
 
     /// I/O Completion Queue と I/O Submission Queue を作成する。
@@ -729,12 +826,13 @@ impl NvmeDevice {
 
         // --- Create I/O Completion Queue ---
         // CDW10: bits [31:16] = Queue Size (0-based), bits [15:0] = Queue ID
-        // CDW11: bit [0] = PC (Physically Contiguous) = 1
+        // CDW11: bits [31:16] = Interrupt Vector (MSI-X エントリ), bit [1] = IEN, bit [0] = PC
         let mut sqe = NvmeSqe::zeroed();
         sqe.opcode = ADMIN_CREATE_IO_CQ;
         sqe.prp1 = cq_ptr as u64;
         sqe.cdw10 = ((depth as u32 - 1) << 16) | 1; // QID=1, QSIZE=depth-1
-        sqe.cdw11 = 1; // PC=1 (Physically Contiguous)
+        // PC=1 (Physically Contiguous)。MSI-X 有効時は IEN=1, IV=0 で割り込みも出す
+        sqe.cdw11 = if self.msix { 0b11 } else { 1 };
 
         self.admin_queue.submit(sqe);
         self.admin_queue.poll_completion()?;
@@ -15,7 +15,6 @@
 // オフセットは 0（物理 == 仮想）なので VirtAddr::new(0) を渡す。
 
 use lazy_static::lazy_static;
-use spin::Mutex;
 use uefi::mem::memory_map::{MemoryMap, MemoryMapOwned, MemoryType};
 use x86_64::registers::control::{Cr0, Cr0Flags, Cr3, Cr3Flags};
 use x86_64::structures::paging::mapper::MapToError;
@@ -25,6 +24,7 @@ use x86_64::structures::paging::{
 };
 use x86_64::{PhysAddr, VirtAddr};
 
+use crate::lock_order::{LockClass, OrderedMutex};
 use crate::memory::{self, MemoryRegion, FRAME_ALLOCATOR};
 
 // =================================================================
@@ -189,7 +189,8 @@ lazy_static! {
     ///
     /// OffsetPageTable は仮想→物理アドレス変換やマッピング操作を提供する。
     /// ロック順序: PAGE_TABLE → FRAME_ALLOCATOR（デッドロック防止）
-    static ref PAGE_TABLE: Mutex<Option<OffsetPageTable<'static>>> = Mutex::new(None);
+    static ref PAGE_TABLE: OrderedMutex<Option<OffsetPageTable<'static>>> =
+        OrderedMutex::new(LockClass::PageTable, None);
 }
 
 // =================================================================
@@ -505,6 +506,61 @@ pub fn split_huge_page_if_needed(virt_addr: VirtAddr) {
     }
 }
 
+/// アイデンティティマッピングされた 4KiB ページを実行可能にする。
+///
+/// UEFI はコード以外の領域に NO_EXECUTE を付けていることがある。
+/// AP のトランポリン（smp.rs）はカーネルのページテーブルのまま 1MiB 未満のページで
+/// 命令を実行するので、そのページまでの全階層のエントリから NO_EXECUTE を外す
+/// （上位の階層に 1 つでも NX があると実行できない）。
+/// 2MiB の巨大ページは分割してから 1 ページ分だけ外す。
+///
+/// ページがマッピングされていなければ false を返す。
+pub fn make_identity_page_executable(virt_addr: VirtAddr) -> bool {
+    split_huge_page_if_needed(virt_addr);
+
+    let addr = virt_addr.as_u64();
+    let indices = [
+        ((addr >> 39) & 0x1FF) as usize,
+        ((addr >> 30) & 0x1FF) as usize,
+        ((addr >> 21) & 0x1FF) as usize,
+        ((addr >> 12) & 0x1FF) as usize,
+    ];
+
+    // CR0.WP を一時的にクリアして書き込みを許可
+    let cr0 = Cr0::read();
+    unsafe {
+        Cr0::write(cr0 & !Cr0Flags::WRITE_PROTECT);
+    }
+
+    let mut table: &mut PageTable = unsafe { active_level_4_table() };
+    let mut mapped = false;
+    for (level, &idx) in indices.iter().enumerate() {
+        let entry = &mut table[idx];
+        if !entry.flags().contains(PageTableFlags::PRESENT) {
+            break;
+        }
+        entry.set_flags(entry.flags() - PageTableFlags::NO_EXECUTE);
+        // L1 まで来たか、1GiB の巨大ページ（分割しない）ならここで終わり
+        if level == 3 || entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            mapped = true;
+            break;
+        }
+        table = unsafe { &mut *(entry.addr().as_u64() as *mut PageTable) };
+    }
+
+    // CR0.WP を元に戻す
+    unsafe {
+        Cr0::write(cr0);
+    }
+
+    // TLB をフラッシュして変更を反映
+    let (frame, flags) = Cr3::read();
+    unsafe {
+        Cr3::write(frame, flags);
+    }
+    mapped
+}
+
 // =================================================================
 // USER_ACCESSIBLE の範囲設定/解除
 // =================================================================
@@ -1679,6 +1735,45 @@ pub fn map_anonymous_pages_in_process(
     virt_start: VirtAddr,
     num_pages: usize,
     writable: bool,
+) -> alloc::vec::Vec<PhysFrame<Size4KiB>> {
+    map_pages_in_process_with(process_l4_frame, virt_start, num_pages, writable, |_, page| {
+        page.fill(0);
+    })
+}
+
+/// SYS_MMAP のファイルマッピング用: ページを確保して `data` の内容で埋める。
+///
+/// 遅延読み込み（ページフォルト時に読む）ではなく、マッピング時に全ページへ
+/// コピーする。ハンドルのファイル内容はすでにカーネルのメモリ上にあるので、
+/// フォルトを待つ利点が小さいため。
+/// ページの途中で `data` が尽きたら残りはゼロで埋める（POSIX の mmap と同じ）。
+/// 書き込み可能でマッピングしてもファイルには書き戻さない（MAP_PRIVATE 相当）。
+///
+/// 戻り値: 確保した物理フレームのリスト（プロセス終了時に解放するために使う）
+pub fn map_file_pages_in_process(
+    process_l4_frame: PhysFrame<Size4KiB>,
+    virt_start: VirtAddr,
+    num_pages: usize,
+    writable: bool,
+    data: &[u8],
+) -> alloc::vec::Vec<PhysFrame<Size4KiB>> {
+    map_pages_in_process_with(process_l4_frame, virt_start, num_pages, writable, |i, page| {
+        let start = (i * 4096).min(data.len());
+        let end = (start + 4096).min(data.len());
+        let n = end - start;
+        page[..n].copy_from_slice(&data[start..end]);
+        page[n..].fill(0);
+    })
+}
+
+/// 新しいフレームを確保してマッピングし、各ページの中身を `fill(ページ番号, ページ)` で埋める。
+/// map_anonymous_pages_in_process / map_file_pages_in_process の共通部分。
+fn map_pages_in_process_with(
+    process_l4_frame: PhysFrame<Size4KiB>,
+    virt_start: VirtAddr,
+    num_pages: usize,
+    writable: bool,
+    mut fill: impl FnMut(usize, &mut [u8]),
 ) -> alloc::vec::Vec<PhysFrame<Size4KiB>> {
     if num_pages == 0 {
         return alloc::vec::Vec::new();
@@ -1686,11 +1781,6 @@ pub fn map_anonymous_pages_in_process(
 
     let mut allocated_frames = alloc::vec::Vec::with_capacity(num_pages);
 
-    // 中間テーブル（L4/L3/L2）は常に WRITABLE + USER_ACCESSIBLE
-    let intermediate_flags = PageTableFlags::PRESENT
-        | PageTableFlags::WRITABLE
-        | PageTableFlags::USER_ACCESSIBLE;
-
     // L1 エントリ（リーフ）のフラグ: 書き込み可能 + 実行不可（W^X）
     let mut leaf_flags = PageTableFlags::PRESENT
         | PageTableFlags::USER_ACCESSIBLE
@@ -1712,100 +1802,20 @@ pub fn map_anonymous_pages_in_process(
 
     for i in 0..num_pages {
         let addr = start_addr + (i as u64) * 4096;
-        let l4_idx = ((addr >> 39) & 0x1FF) as usize;
-        let l3_idx = ((addr >> 30) & 0x1FF) as usize;
-        let l2_idx = ((addr >> 21) & 0x1FF) as usize;
-        let l1_idx = ((addr >> 12) & 0x1FF) as usize;
-
-        // === L4 → L3 ===
-        let l4_entry = &mut process_l4[l4_idx];
-        if l4_entry.is_unused() {
-            let new_l3_frame = alloc_zeroed_frame();
-            l4_entry.set_addr(new_l3_frame.start_address(), intermediate_flags);
-        } else {
-            if !kernel_l4[l4_idx].is_unused()
-                && l4_entry.addr() == kernel_l4[l4_idx].addr()
-            {
-                let new_l3_frame = fork_page_table(l4_entry.addr());
-                l4_entry.set_addr(new_l3_frame.start_address(), l4_entry.flags() | intermediate_flags);
-            } else {
-                l4_entry.set_flags(l4_entry.flags() | intermediate_flags);
-            }
-        }
+        let l1_entry = prepare_l1_entry_in_process(process_l4, kernel_l4, addr);
 
-        let l3_table: &mut PageTable = unsafe {
-            &mut *(l4_entry.addr().as_u64() as *mut PageTable)
-        };
-
-        // === L3 → L2 ===
-        let l3_entry = &mut l3_table[l3_idx];
-        if l3_entry.is_unused() {
-            let new_l2_frame = alloc_zeroed_frame();
-            l3_entry.set_addr(new_l2_frame.start_address(), intermediate_flags);
-        } else {
-            // 1GiB 巨大ページの場合は 512 x 2MiB に分割してから処理
-            if l3_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
-                split_1gib_huge_page_for_process(l3_entry);
-            }
-
-            let kernel_l2_addr = get_kernel_subtable_addr(kernel_l4, l4_idx, l3_idx, None);
-            if let Some(k_addr) = kernel_l2_addr {
-                if l3_entry.addr() == k_addr {
-                    let new_l2_frame = fork_page_table(l3_entry.addr());
-                    l3_entry.set_addr(new_l2_frame.start_address(), l3_entry.flags() | intermediate_flags);
-                } else {
-                    l3_entry.set_flags(l3_entry.flags() | intermediate_flags);
-                }
-            } else {
-                l3_entry.set_flags(l3_entry.flags() | intermediate_flags);
-            }
-        }
-
-        let l2_table: &mut PageTable = unsafe {
-            &mut *(l3_entry.addr().as_u64() as *mut PageTable)
-        };
-
-        // === L2 → L1 ===
-        let l2_entry = &mut l2_table[l2_idx];
-        if l2_entry.is_unused() {
-            let new_l1_frame = alloc_zeroed_frame();
-            l2_entry.set_addr(new_l1_frame.start_address(), intermediate_flags);
-        } else {
-            if l2_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
-                split_huge_page_for_process(l2_entry);
-            }
-            let kernel_l1_addr = get_kernel_subtable_addr(kernel_l4, l4_idx, l3_idx, Some(l2_idx));
-            if let Some(k_addr) = kernel_l1_addr {
-                if l2_entry.addr() == k_addr {
-                    let new_l1_frame = fork_page_table(l2_entry.addr());
-                    l2_entry.set_addr(new_l1_frame.start_address(), l2_entry.flags() | intermediate_flags);
-                } else {
-                    l2_entry.set_flags(l2_entry.flags() | intermediate_flags);
-                }
-            } else {
-                l2_entry.set_flags(l2_entry.flags() | intermediate_flags);
-            }
-        }
-
-        let l1_table: &mut PageTable = unsafe {
-            &mut *(l2_entry.addr().as_u64() as *mut PageTable)
-        };
-
-        // === L1 エントリにゼロクリア済みフレームをマッピング ===
-        let l1_entry = &mut l1_table[l1_idx];
-
-        // 新しいフレームを確保（ゼロクリア済み）
+        // 新しいフレームを確保
         let data_frame = {
             let mut fa = FRAME_ALLOCATOR.lock();
             fa.allocate_frame()
-                .expect("map_anonymous_pages_in_process: フレーム確保に失敗")
+                .expect("map_pages_in_process_with: フレーム確保に失敗")
         };
 
-        // ゼロクリア
-        unsafe {
-            let ptr = data_frame.start_address().as_u64() as *mut u8;
-            core::ptr::write_bytes(ptr, 0, 4096);
-        }
+        // 中身を埋める（匿名ならゼロクリア、ファイルならファイルの内容）
+        let page = unsafe {
+            core::slice::from_raw_parts_mut(data_frame.start_address().as_u64() as *mut u8, 4096)
+        };
+        fill(i, page);
 
         // L1 エントリが既に使用中の場合（分岐コピーによるアイデンティティマッピングの残骸など）、
         // 新しいフレームで上書きする。既存のフレームはカーネルのものなので解放しない。
@@ -1821,6 +1831,120 @@ pub fn map_anonymous_pages_in_process(
     allocated_frames
 }
 
+/// プロセスのページテーブルで addr の 4KiB ページの L1 エントリを用意して返す。
+///
+/// 途中のテーブルがなければ作り、カーネルと共有しているテーブルは分岐コピーし、
+/// 巨大ページは 4KiB ページに分割する。返したエントリの中身はそのままなので、
+/// 呼び出し側が設定する（アイデンティティマッピングの残骸が入っていることもある）。
+fn prepare_l1_entry_in_process(
+    process_l4: &mut PageTable,
+    kernel_l4: &PageTable,
+    addr: u64,
+) -> &'static mut x86_64::structures::paging::page_table::PageTableEntry {
+    // 中間テーブル（L4/L3/L2）は常に WRITABLE + USER_ACCESSIBLE
+    let intermediate_flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::USER_ACCESSIBLE;
+
+    let l4_idx = ((addr >> 39) & 0x1FF) as usize;
+    let l3_idx = ((addr >> 30) & 0x1FF) as usize;
+    let l2_idx = ((addr >> 21) & 0x1FF) as usize;
+    let l1_idx = ((addr >> 12) & 0x1FF) as usize;
+
+    // === L4 → L3 ===
+    let l4_entry = &mut process_l4[l4_idx];
+    if l4_entry.is_unused() {
+        let new_l3_frame = alloc_zeroed_frame();
+        l4_entry.set_addr(new_l3_frame.start_address(), intermediate_flags);
+    } else {
+        if !kernel_l4[l4_idx].is_unused()
+            && l4_entry.addr() == kernel_l4[l4_idx].addr()
+        {
+            let new_l3_frame = fork_page_table(l4_entry.addr());
+            l4_entry.set_addr(new_l3_frame.start_address(), l4_entry.flags() | intermediate_flags);
+        } else {
+            l4_entry.set_flags(l4_entry.flags() | intermediate_flags);
+        }
+    }
+
+    let l3_table: &mut PageTable = unsafe {
+        &mut *(l4_entry.addr().as_u64() as *mut PageTable)
+    };
+
+    // === L3 → L2 ===
+    let l3_entry = &mut l3_table[l3_idx];
+    if l3_entry.is_unused() {
+        let new_l2_frame = alloc_zeroed_frame();
+        l3_entry.set_addr(new_l2_frame.start_address(), intermediate_flags);
+    } else {
+        // 1GiB 巨大ページの場合は 512 x 2MiB に分割してから処理
+        if l3_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            split_1gib_huge_page_for_process(l3_entry);
+        }
+
+        let kernel_l2_addr = get_kernel_subtable_addr(kernel_l4, l4_idx, l3_idx, None);
+        if let Some(k_addr) = kernel_l2_addr {
+            if l3_entry.addr() == k_addr {
+                let new_l2_frame = fork_page_table(l3_entry.addr());
+                l3_entry.set_addr(new_l2_frame.start_address(), l3_entry.flags() | intermediate_flags);
+            } else {
+                l3_entry.set_flags(l3_entry.flags() | intermediate_flags);
+            }
+        } else {
+            l3_entry.set_flags(l3_entry.flags() | intermediate_flags);
+        }
+    }
+
+    let l2_table: &mut PageTable = unsafe {
+        &mut *(l3_entry.addr().as_u64() as *mut PageTable)
+    };
+
+    // === L2 → L1 ===
+    let l2_entry = &mut l2_table[l2_idx];
+    if l2_entry.is_unused() {
+        let new_l1_frame = alloc_zeroed_frame();
+        l2_entry.set_addr(new_l1_frame.start_address(), intermediate_flags);
+    } else {
+        if l2_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+            split_huge_page_for_process(l2_entry);
+        }
+        let kernel_l1_addr = get_kernel_subtable_addr(kernel_l4, l4_idx, l3_idx, Some(l2_idx));
+        if let Some(k_addr) = kernel_l1_addr {
+            if l2_entry.addr() == k_addr {
+                let new_l1_frame = fork_page_table(l2_entry.addr());
+                l2_entry.set_addr(new_l1_frame.start_address(), l2_entry.flags() | intermediate_flags);
+            } else {
+                l2_entry.set_flags(l2_entry.flags() | intermediate_flags);
+            }
+        } else {
+            l2_entry.set_flags(l2_entry.flags() | intermediate_flags);
+        }
+    }
+
+    let l1_table: &mut PageTable = unsafe {
+        &mut *(l2_entry.addr().as_u64() as *mut PageTable)
+    };
+
+    &mut l1_table[l1_idx]
+}
+
+/// ユーザースタックの下にガードページを置く。
+///
+/// 指定した 4KiB ページの L1 エントリを未使用にして、どこにもマッピングされていない
+/// 状態を保証する。プロセスのページテーブルは UEFI のアイデンティティマッピングを
+/// 引き継いでいるので、何もしないとこのアドレスにもカーネル用のマッピングが残っている。
+/// 巨大ページは分割してからこのページだけを外す（物理フレームはカーネルのものなので解放しない）。
+pub fn map_guard_page_in_process(process_l4_frame: PhysFrame<Size4KiB>, virt: VirtAddr) {
+    let kernel_l4: &PageTable = unsafe {
+        &*(kernel_cr3().as_u64() as *const PageTable)
+    };
+    let process_l4: &mut PageTable = unsafe {
+        &mut *(process_l4_frame.start_address().as_u64() as *mut PageTable)
+    };
+    prepare_l1_entry_in_process(process_l4, kernel_l4, virt.as_u64()).set_unused();
+    x86_64::instructions::tlb::flush(virt);
+}
+
 /// SYS_MUNMAP 用: プロセスのアドレス空間からページのマッピングを解除する。
 ///
 /// L1 エントリを unused にし、対応する物理フレームを解放する。
@@ -1915,6 +2039,105 @@ pub fn unmap_pages_in_process(
     freed_frames
 }
 
+/// SYS_MPROTECT 用: プロセスのページの保護属性を書き換える。
+///
+/// 対象の L1 エントリの PRESENT / WRITABLE / NO_EXECUTE だけを書き換え、
+/// 物理フレームのアドレスはそのまま残す。アクセス不可（全部 false）のときは
+/// PRESENT を落とすだけなので、あとで mprotect し直せば同じフレームが見える。
+/// x86_64 のページテーブルでは「書けるが読めない」「実行できるが読めない」は
+/// 表現できないので、write / execute のどちらかが true なら読み取りも許可される。
+///
+/// 先に範囲全体を確認し、1 ページでもマッピングされていない（または
+/// USER_ACCESSIBLE でない）ページがあれば何も変更せずに false を返す。
+/// 書き換えたページは invlpg で TLB から追い出す。
+///
+/// - `process_l4_frame`: プロセスの L4 ページテーブル
+/// - `virt_start`: 先頭の仮想アドレス（4KiB アラインされていること）
+/// - `num_pages`: ページ数
+pub fn protect_pages_in_process(
+    process_l4_frame: PhysFrame<Size4KiB>,
+    virt_start: VirtAddr,
+    num_pages: usize,
+    read: bool,
+    write: bool,
+    execute: bool,
+) -> bool {
+    let start_addr = virt_start.as_u64();
+
+    // 1 パス目: 範囲全体がユーザーのページとしてマッピングされているか確認する
+    for i in 0..num_pages {
+        let addr = start_addr + (i as u64) * 4096;
+        match user_l1_entry_in_process(process_l4_frame, addr) {
+            Some(entry) if entry.flags().contains(PageTableFlags::USER_ACCESSIBLE) => {}
+            _ => return false,
+        }
+    }
+
+    let mut flags = PageTableFlags::USER_ACCESSIBLE;
+    if read || write || execute {
+        flags |= PageTableFlags::PRESENT;
+    }
+    if write {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    if !execute {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    // 2 パス目: 書き換えて TLB から追い出す
+    for i in 0..num_pages {
+        let addr = start_addr + (i as u64) * 4096;
+        if let Some(entry) = user_l1_entry_in_process(process_l4_frame, addr) {
+            let frame_addr = entry.addr();
+            entry.set_addr(frame_addr, flags);
+        }
+        x86_64::instructions::tlb::flush(VirtAddr::new(addr));
+    }
+
+    true
+}
+
+/// プロセスのページテーブルを辿って、addr の 4KiB ページの L1 エントリを返す。
+///
+/// 途中のテーブルがない、ヒュージページで L1 がない、L1 エントリが未使用の
+/// いずれかなら None。PRESENT は見ない（mprotect でアクセス不可にしたページも返す）。
+fn user_l1_entry_in_process(
+    process_l4_frame: PhysFrame<Size4KiB>,
+    addr: u64,
+) -> Option<&'static mut x86_64::structures::paging::page_table::PageTableEntry> {
+    let l4_idx = ((addr >> 39) & 0x1FF) as usize;
+    let l3_idx = ((addr >> 30) & 0x1FF) as usize;
+    let l2_idx = ((addr >> 21) & 0x1FF) as usize;
+    let l1_idx = ((addr >> 12) & 0x1FF) as usize;
+
+    let l4: &PageTable = unsafe {
+        &*(process_l4_frame.start_address().as_u64() as *const PageTable)
+    };
+    let l4_entry = &l4[l4_idx];
+    if l4_entry.is_unused() {
+        return None;
+    }
+
+    let l3: &PageTable = unsafe { &*(l4_entry.addr().as_u64() as *const PageTable) };
+    let l3_entry = &l3[l3_idx];
+    if l3_entry.is_unused() || l3_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+        return None;
+    }
+
+    let l2: &PageTable = unsafe { &*(l3_entry.addr().as_u64() as *const PageTable) };
+    let l2_entry = &l2[l2_idx];
+    if l2_entry.is_unused() || l2_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+        return None;
+    }
+
+    let l1: &mut PageTable = unsafe { &mut *(l2_entry.addr().as_u64() as *mut PageTable) };
+    let l1_entry = &mut l1[l1_idx];
+    if l1_entry.is_unused() {
+        return None;
+    }
+    Some(l1_entry)
+}
+
 /// デバッグ用: 現在の CR3 のページテーブルで、指定仮想アドレスの
 /// L4→L3→L2→L1 各エントリのフラグをダンプする。
 /// ページフォルトのデバッグに使う。
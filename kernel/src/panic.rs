@@ -12,10 +12,18 @@
 //   lock() ではなく try_lock() を使い、ロック取得できない場合は:
 //     - シリアル: I/O ポートに直接 1 バイトずつ書くフォールバックを使う
 //     - フレームバッファ: スキップする（シリアルがあればデバッグには十分）
+//
+// メッセージの後に、フレームポインタを辿ったバックトレース（backtrace.rs）も出す。
+// バックトレースを辿る途中でフォルトしてパニックが再入した場合は、
+// 2 回目はバックトレースを出さずに止まる（無限に再入しないように）。
 
 use core::fmt;
 use core::fmt::Write;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// パニックハンドラに一度入ったか（再入したらバックトレースを出さない）
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 /// COM1 データレジスタのアドレス（I/O ポート直接書き込み用）
 const COM1_DATA: u16 = 0x3F8;
@@ -73,6 +81,14 @@ fn panic(info: &PanicInfo) -> ! {
     //    panic 中に割り込みが入ると二重例外やデッドロックの原因になる。
     x86_64::instructions::interrupts::disable();
 
+    // バックトレースの起点はこの関数のフレーム。
+    // 再入したときは 0 にして、バックトレースを出さない。
+    let rbp = if PANICKING.swap(true, Ordering::Relaxed) {
+        0
+    } else {
+        crate::backtrace::current_rbp()
+    };
+
     // 2. シリアルに出力する。
     //    try_lock() でデッドロックを回避する。
     //    ロック取得に失敗した場合は I/O ポートに直接書き込む。
@@ -82,6 +98,9 @@ fn panic(info: &PanicInfo) -> ! {
         let _ = serial.write_str("KERNEL PANIC!\n");
         let _ = serial.write_str("========================================\n");
         let _ = write!(serial, "{}\n", info);
+        if rbp != 0 {
+            unsafe { crate::backtrace::write_backtrace(&mut *serial, rbp) };
+        }
         let _ = serial.write_str("========================================\n");
         let _ = serial.write_str("System halted.\n");
     } else {
@@ -91,6 +110,9 @@ fn panic(info: &PanicInfo) -> ! {
         unsafe { serial_write_raw(b"KERNEL PANIC!\n") };
         unsafe { serial_write_raw(b"========================================\n") };
         let _ = write!(RawSerialWriter, "{}\n", info);
+        if rbp != 0 {
+            unsafe { crate::backtrace::write_backtrace(&mut RawSerialWriter, rbp) };
+        }
         unsafe { serial_write_raw(b"========================================\n") };
         unsafe { serial_write_raw(b"System halted.\n") };
     }
@@ -106,6 +128,9 @@ fn panic(info: &PanicInfo) -> ! {
             let _ = w.write_str("KERNEL PANIC!\n");
             let _ = w.write_str("========================================\n");
             let _ = write!(w, "{}\n", info);
+            if rbp != 0 {
+                unsafe { crate::backtrace::write_backtrace(w, rbp) };
+            }
             let _ = w.write_str("========================================\n");
             let _ = w.write_str("System halted.\n");
         }
@@ -24,6 +24,7 @@
 //   0x3C: 割り込みライン + 割り込みピン
 
 use alloc::vec::Vec;
+use spin::Mutex;
 use x86_64::instructions::port::Port;
 
 /// PCI Configuration Space のアドレスポート（書き込み専用）
@@ -202,6 +203,190 @@ pub fn read_bar64(bus: u8, device: u8, function: u8, bar_index: u8) -> u64 {
     ((high as u64) << 32) | (low as u64)
 }
 
+/// BAR の種類。
+///
+/// BAR の bit 0 が 1 なら I/O ポート、0 ならメモリ。
+/// メモリ BAR の type bits ([2:1]) が 0b10 なら 64 ビット BAR
+/// （次の BAR が上位 32 ビットを持つ）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarKind {
+    /// 32 ビットメモリ空間 (MMIO)
+    Memory32,
+    /// 64 ビットメモリ空間 (MMIO、BAR を 2 つ消費する)
+    Memory64,
+    /// I/O ポート空間
+    Io,
+}
+
+/// デコード済みの BAR 情報。
+#[derive(Debug, Clone, Copy)]
+pub struct PciBar {
+    /// ベースアドレス（フラグビットを除いた値）
+    pub base: u64,
+    /// 領域のサイズ（バイト）
+    pub size: u64,
+    /// BAR の種類
+    pub kind: BarKind,
+    /// プリフェッチ可能か（メモリ BAR の bit 3）
+    pub prefetchable: bool,
+}
+
+/// BAR を読み取ってベースアドレス・サイズ・種類をデコードする。
+///
+/// サイズは「BAR に 0xFFFFFFFF を書いて読み返す」方式で求める。
+/// デバイスはサイズ未満のアドレスビットを 0 に固定しているので、
+/// 読み返した値のフラグビットを除いて反転し +1 するとサイズになる。
+/// 書き換えた BAR は必ず元の値に書き戻す。
+///
+/// プローブ中は BAR が一時的にでたらめなアドレスを指すので、
+/// Command レジスタの I/O / Memory デコードを止め、割り込みも禁止しておく
+/// （割り込みハンドラがそのデバイスの MMIO に触ると事故になるため）。
+///
+/// 未実装の BAR（サイズ 0）や、64 ビット BAR の上位半分を指す bar_index、
+/// ヘッダータイプに存在しない bar_index には None を返す。
+pub fn probe_bar(bus: u8, device: u8, function: u8, bar_index: u8) -> Option<PciBar> {
+    if bar_index >= bar_count(bus, device, function) {
+        return None;
+    }
+    // 直前の BAR が 64 ビットなら、この BAR はその上位半分なので単独では意味を持たない
+    if bar_index > 0 {
+        let prev = read_bar(bus, device, function, bar_index - 1);
+        if prev & 1 == 0 && (prev >> 1) & 0b11 == 0b10 {
+            return None;
+        }
+    }
+
+    let offset = 0x10 + bar_index * 4;
+    let original = pci_config_read32(bus, device, function, offset);
+    let is_io = original & 1 != 0;
+    let is_64 = !is_io && (original >> 1) & 0b11 == 0b10;
+    if is_64 && bar_index + 1 >= bar_count(bus, device, function) {
+        return None;
+    }
+
+    let (mask_lo, mask_hi, original_hi) = x86_64::instructions::interrupts::without_interrupts(|| {
+        // Command レジスタ bit 0 = I/O Space, bit 1 = Memory Space
+        let command = pci_config_read16(bus, device, function, 0x04);
+        pci_config_write16(bus, device, function, 0x04, command & !0b11);
+
+        pci_config_write32(bus, device, function, offset, 0xFFFF_FFFF);
+        let mask_lo = pci_config_read32(bus, device, function, offset);
+        pci_config_write32(bus, device, function, offset, original);
+
+        let (mask_hi, original_hi) = if is_64 {
+            let hi_offset = offset + 4;
+            let original_hi = pci_config_read32(bus, device, function, hi_offset);
+            pci_config_write32(bus, device, function, hi_offset, 0xFFFF_FFFF);
+            let mask_hi = pci_config_read32(bus, device, function, hi_offset);
+            pci_config_write32(bus, device, function, hi_offset, original_hi);
+            (mask_hi, original_hi)
+        } else {
+            (0, 0)
+        };
+
+        pci_config_write16(bus, device, function, 0x04, command);
+        (mask_lo, mask_hi, original_hi)
+    });
+
+    let (base, size, kind) = if is_io {
+        // I/O BAR は bit [1:0] がフラグ。上位 16 ビットを実装しないデバイスも
+        // あるので、その場合は上位を 1 で埋めてからサイズを計算する。
+        let mut mask = mask_lo & !0b11;
+        if mask == 0 {
+            return None;
+        }
+        if mask & 0xFFFF_0000 == 0 {
+            mask |= 0xFFFF_0000;
+        }
+        let size = (!mask).wrapping_add(1) as u64;
+        ((original & !0b11) as u64, size, BarKind::Io)
+    } else if is_64 {
+        let mask = ((mask_hi as u64) << 32) | (mask_lo & !0xF) as u64;
+        if mask == 0 {
+            return None;
+        }
+        let base = ((original_hi as u64) << 32) | (original & !0xF) as u64;
+        (base, (!mask).wrapping_add(1), BarKind::Memory64)
+    } else {
+        let mask = mask_lo & !0xF;
+        if mask == 0 {
+            return None;
+        }
+        ((original & !0xF) as u64, (!mask).wrapping_add(1) as u64, BarKind::Memory32)
+    };
+
+    Some(PciBar {
+        base,
+        size,
+        kind,
+        prefetchable: !is_io && original & 0b1000 != 0,
+    })
+}
+
+/// 起動時にプローブした 1 デバイス分の BAR
+struct CachedBars {
+    bus: u8,
+    device: u8,
+    function: u8,
+    bars: [Option<PciBar>; 6],
+}
+
+/// 起動時にプローブした全デバイスの BAR（SYS_PCI_READ_BAR はここから答える）
+static BAR_CACHE: Mutex<Vec<CachedBars>> = Mutex::new(Vec::new());
+
+/// 全デバイスの BAR をプローブしてキャッシュする。
+///
+/// probe_bar は BAR に 0xFFFFFFFF を書くので、ドライバが動き出した後に
+/// 呼ぶと、プローブ中に別の CPU やデバイス自身の DMA がそのアドレスを使う恐れがある。
+/// そこでドライバを初期化する前に 1 度だけプローブし、あとはキャッシュを返す。
+/// BAR の割り当てはファームウェアが済ませていて、SABOS は付け替えないので古くならない。
+pub fn cache_bars() {
+    let cached = enumerate_all_buses()
+        .iter()
+        .map(|dev| CachedBars {
+            bus: dev.bus,
+            device: dev.device,
+            function: dev.function,
+            bars: core::array::from_fn(|i| probe_bar(dev.bus, dev.device, dev.function, i as u8)),
+        })
+        .collect();
+    *BAR_CACHE.lock() = cached;
+}
+
+/// cache_bars() で記録した BAR を返す。
+///
+/// 起動時に見つからなかったデバイスや、probe_bar が None を返した BAR には None を返す。
+pub fn cached_bar(bus: u8, device: u8, function: u8, bar_index: u8) -> Option<PciBar> {
+    BAR_CACHE
+        .lock()
+        .iter()
+        .find(|c| c.bus == bus && c.device == device && c.function == function)
+        .and_then(|c| c.bars.get(bar_index as usize).copied().flatten())
+}
+
+/// ヘッダータイプから BAR の本数を返す。
+///
+/// Type 0（通常デバイス）は BAR0〜BAR5 の 6 本、
+/// Type 1（PCI-to-PCI ブリッジ）は BAR0〜BAR1 の 2 本。
+/// それ以外（CardBus ブリッジ等）は BAR を扱わない。
+fn bar_count(bus: u8, device: u8, function: u8) -> u8 {
+    let header_type = pci_config_read16(bus, device, function, 0x0E) as u8;
+    match header_type & 0x7F {
+        0 => 6,
+        1 => 2,
+        _ => 0,
+    }
+}
+
+/// デバイスの有効な BAR をすべてプローブして (bar_index, PciBar) の一覧を返す。
+///
+/// 64 ビット BAR の上位半分や未実装の BAR は含まれない。
+pub fn probe_all_bars(bus: u8, device: u8, function: u8) -> Vec<(u8, PciBar)> {
+    (0..bar_count(bus, device, function))
+        .filter_map(|i| probe_bar(bus, device, function, i).map(|bar| (i, bar)))
+        .collect()
+}
+
 /// PCI ケイパビリティリストの先頭オフセットを返す。
 ///
 /// PCI デバイスがケイパビリティリストをサポートしているかどうかは、
@@ -223,17 +408,16 @@ fn capabilities_pointer(bus: u8, device: u8, function: u8) -> Option<u8> {
 }
 
 /// PCI ケイパビリティの種類を表す定数。
-#[allow(dead_code)]
 pub mod capability_id {
     /// MSI (Message Signaled Interrupts) ケイパビリティ
+    #[allow(dead_code)]
     pub const MSI: u8 = 0x05;
     /// MSI-X ケイパビリティ
     pub const MSIX: u8 = 0x11;
 }
 
-/// PCI ケイパビリティ情報（Phase 1 以降の MSI/MSI-X 設定で使用予定）。
+/// PCI ケイパビリティ情報。
 #[derive(Debug, Clone)]
-#[allow(dead_code)]
 pub struct PciCapability {
     /// ケイパビリティ ID（capability_id 定数参照）
     pub id: u8,
@@ -247,8 +431,7 @@ pub struct PciCapability {
 ///   各エントリの [7:0] = Capability ID, [15:8] = Next Pointer
 ///   Next Pointer が 0 でリスト終端。
 ///
-/// Phase 0 では MSI/MSI-X の**検出のみ**を目的とする（設定は Phase 1 以降）。
-#[allow(dead_code)]
+/// MSI-X の設定は find_msix() / enable_msix() を参照。
 pub fn enumerate_capabilities(bus: u8, device: u8, function: u8) -> Vec<PciCapability> {
     let mut caps = Vec::new();
     let mut offset = match capabilities_pointer(bus, device, function) {
@@ -276,6 +459,167 @@ pub fn enumerate_capabilities(bus: u8, device: u8, function: u8) -> Vec<PciCapab
     caps
 }
 
+// ============================================================
+// MSI-X
+// ============================================================
+//
+// MSI-X は「割り込み = メモリ書き込み」にする仕組み。デバイスは割り込みの代わりに
+// テーブルに登録されたアドレス（Local APIC の 0xFEExxxxx）へデータ（ベクタ番号）を
+// 書き込み、Local APIC がそれを割り込みとして CPU に届ける。
+// INTx のように I/O APIC のピンを共有しないので、ドライバごとに専用ベクタを持てる。
+//
+// ケイパビリティ構造 (ID = 0x11):
+//   +0x02: Message Control — [10:0] テーブルサイズ - 1, [14] Function Mask, [15] Enable
+//   +0x04: Table Offset/BIR — [2:0] テーブルのある BAR 番号, [31:3] BAR 内オフセット
+//   +0x08: PBA Offset/BIR   — Pending Bit Array の位置（同じ形式）
+//
+// テーブルの各エントリ (16 バイト):
+//   +0x0: Message Address (下位 32 ビット)
+//   +0x4: Message Address (上位 32 ビット)
+//   +0x8: Message Data
+//   +0xC: Vector Control — bit 0 = マスク
+
+/// MSI-X Message Control: Function Mask（全エントリを一括マスク）
+const MSIX_CONTROL_FUNCTION_MASK: u16 = 1 << 14;
+/// MSI-X Message Control: MSI-X Enable
+const MSIX_CONTROL_ENABLE: u16 = 1 << 15;
+/// MSI-X テーブルエントリのサイズ
+const MSIX_TABLE_ENTRY_SIZE: u64 = 16;
+/// Vector Control: このエントリをマスクする
+const MSIX_VECTOR_CONTROL_MASKED: u32 = 1;
+/// Command レジスタ: Memory Space
+const COMMAND_MEMORY_SPACE: u16 = 1 << 1;
+/// Command レジスタ: Bus Master（MSI-X の書き込みはデバイス発の DMA なので必要）
+const COMMAND_BUS_MASTER: u16 = 1 << 2;
+/// Command レジスタ: INTx Disable
+const COMMAND_INTX_DISABLE: u16 = 1 << 10;
+
+/// MSI-X ケイパビリティの情報。
+#[derive(Debug, Clone, Copy)]
+pub struct MsixCapability {
+    /// Configuration Space 内のケイパビリティのオフセット
+    pub offset: u8,
+    /// テーブルのエントリ数
+    pub table_size: u16,
+    /// テーブルを含む BAR 番号
+    pub table_bar: u8,
+    /// BAR 先頭からテーブルまでのオフセット
+    pub table_offset: u32,
+    /// MSI-X を有効にする前の Command レジスタで INTx Disable が立っていたか
+    /// （disable_msix() で元に戻すために覚えておく）
+    pub intx_was_disabled: bool,
+}
+
+/// デバイスの MSI-X ケイパビリティを探す。なければ None。
+pub fn find_msix(bus: u8, device: u8, function: u8) -> Option<MsixCapability> {
+    let cap = enumerate_capabilities(bus, device, function)
+        .into_iter()
+        .find(|c| c.id == capability_id::MSIX)?;
+    let control = pci_config_read16(bus, device, function, cap.offset + 2);
+    let table = pci_config_read32(bus, device, function, cap.offset + 4);
+    let command = pci_config_read16(bus, device, function, 0x04);
+    Some(MsixCapability {
+        offset: cap.offset,
+        table_size: (control & 0x7FF) + 1,
+        table_bar: (table & 0x7) as u8,
+        table_offset: table & !0x7,
+        intx_was_disabled: command & COMMAND_INTX_DISABLE != 0,
+    })
+}
+
+/// メモリ BAR のベースアドレスを返す（I/O BAR や未設定なら None）。
+fn memory_bar_base(bus: u8, device: u8, function: u8, bar_index: u8) -> Option<u64> {
+    let raw = read_bar(bus, device, function, bar_index);
+    if raw & 1 != 0 {
+        return None;
+    }
+    let base = if (raw >> 1) & 0b11 == 0b10 {
+        read_bar64(bus, device, function, bar_index) & !0xF
+    } else {
+        (raw & !0xF) as u64
+    };
+    if base == 0 { None } else { Some(base) }
+}
+
+/// MSI-X を有効化し、テーブルのエントリにベクタを割り当てる。
+///
+/// entries は (テーブルのエントリ番号, IDT ベクタ番号) の組。
+/// 指定しなかったエントリはマスクしたままにする。
+/// message_address は Local APIC 宛てのアドレス（apic::msi_address() の値）。
+///
+/// プログラミング中に中途半端なエントリで割り込みが飛ばないよう、
+/// Function Mask を立てた状態でテーブルを書き、最後にマスクを外す。
+/// 成功すると INTx は無効化される（MSI-X と INTx は排他）。
+pub fn enable_msix(
+    bus: u8,
+    device: u8,
+    function: u8,
+    cap: &MsixCapability,
+    message_address: u64,
+    entries: &[(u16, u8)],
+) -> Result<(), &'static str> {
+    if entries.iter().any(|&(entry, _)| entry >= cap.table_size) {
+        return Err("MSI-X entry out of range");
+    }
+    let table_base = memory_bar_base(bus, device, function, cap.table_bar)
+        .ok_or("MSI-X table BAR is not a memory BAR")?
+        + cap.table_offset as u64;
+
+    let command = pci_config_read16(bus, device, function, 0x04);
+    pci_config_write16(
+        bus, device, function, 0x04,
+        command | COMMAND_MEMORY_SPACE | COMMAND_BUS_MASTER | COMMAND_INTX_DISABLE,
+    );
+
+    let control_offset = cap.offset + 2;
+    let control = pci_config_read16(bus, device, function, control_offset);
+    pci_config_write16(
+        bus, device, function, control_offset,
+        control | MSIX_CONTROL_ENABLE | MSIX_CONTROL_FUNCTION_MASK,
+    );
+
+    // テーブルは MMIO（アイデンティティマッピングなので物理アドレス = 仮想アドレス）
+    for entry in 0..cap.table_size {
+        let entry_base = table_base + entry as u64 * MSIX_TABLE_ENTRY_SIZE;
+        let write = |offset: u64, value: u32| unsafe {
+            core::ptr::write_volatile((entry_base + offset) as *mut u32, value);
+        };
+        match entries.iter().find(|&&(e, _)| e == entry) {
+            Some(&(_, vector)) => {
+                write(0x0, message_address as u32);
+                write(0x4, (message_address >> 32) as u32);
+                // Message Data: ベクタ番号（Delivery Mode = Fixed, エッジトリガ）
+                write(0x8, vector as u32);
+                write(0xC, 0);
+            }
+            None => write(0xC, MSIX_VECTOR_CONTROL_MASKED),
+        }
+    }
+
+    pci_config_write16(
+        bus, device, function, control_offset,
+        (control | MSIX_CONTROL_ENABLE) & !MSIX_CONTROL_FUNCTION_MASK,
+    );
+    Ok(())
+}
+
+/// MSI-X を無効化する（INTx / ポーリングに戻す）。
+///
+/// enable_msix() が立てた INTx Disable は、find_msix() の時点の状態に戻す。
+/// デバイスがベクタの割り当てを拒否したときもここを通るので、戻さないと
+/// そのデバイスは INTx も MSI-X も出せないままになる。
+/// Memory Space / Bus Master はドライバが DMA に使うのでそのままにする。
+pub fn disable_msix(bus: u8, device: u8, function: u8, cap: &MsixCapability) {
+    let control_offset = cap.offset + 2;
+    let control = pci_config_read16(bus, device, function, control_offset);
+    pci_config_write16(bus, device, function, control_offset, control & !MSIX_CONTROL_ENABLE);
+
+    if !cap.intx_was_disabled {
+        let command = pci_config_read16(bus, device, function, 0x04);
+        pci_config_write16(bus, device, function, 0x04, command & !COMMAND_INTX_DISABLE);
+    }
+}
+
 /// virtio-blk デバイスを PCI バスから探す。
 ///
 /// virtio デバイスの識別:
@@ -0,0 +1,169 @@
+// percpu.rs — CPU ごとのデータ（GS ベースで引く）
+//
+// これまで「今走っているタスク」や TSS の rsp0 はカーネル全体で 1 つだけだった。
+// 複数のコアでタスクを動かすにはこれらを CPU ごとに持つ必要があるので、
+// CPU ごとのブロック（PerCpu）を用意し、各 CPU の GS ベース (IA32_GS_BASE MSR) に
+// 自分のブロックのアドレスを入れておく。
+//
+// ブロックの先頭には自分自身のアドレスを置くので、`mov rax, gs:[0]` の 1 命令で
+// 今の CPU のブロックが得られる（ロックも CPUID も要らない）。
+//
+// CPU 番号は BSP が 0、AP は smp.rs が起動した順に 1, 2, ...。
+//
+// ユーザープログラムは `mov gs, ...` で GS ベースを自由に書き換えられるので、
+// カーネルの GS ベースは Ring 3 の間 IA32_KERNEL_GS_BASE に退避しておく。
+// Ring 3 → Ring 0 の入口と Ring 0 → Ring 3 の出口でちょうど 1 回ずつ swapgs する:
+//   - int 0x80: syscall_handler_asm が保存された CS の RPL を見て swapgs する
+//   - 割り込み・例外: 各ハンドラの先頭で KernelGs::enter()（戻るときに Drop で swapgs）
+//   - 最初の Ring 3 への遷移: jump_to_usermode が iretq の直前に swapgs する
+// こうしておけば、カーネルにいる間は GS ベースが常にこの CPU のブロックを指す。
+//
+// ユーザーの GS ベースはタスクごとには保存しない。FSGSBASE を有効にしていないので
+// ユーザーが作れる GS ベースはセグメントディスクリプタのベース（= 0）だけで、
+// どのタスクから見ても同じ値だから。
+
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
+use x86_64::registers::model_specific::{GsBase, KernelGsBase};
+use x86_64::structures::idt::InterruptStackFrame;
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::VirtAddr;
+
+use crate::smp::MAX_CPUS;
+
+/// CPU ごとのブロック
+///
+/// 持ち主の CPU 以外からも読めるよう（/proc やデバッグ表示用）、中身はすべてアトミックにする。
+#[repr(C)]
+pub struct PerCpu {
+    /// このブロック自身のアドレス。gs:[0] から読むので先頭に置くこと。
+    self_ptr: AtomicU64,
+    /// CPU 番号（BSP = 0）
+    cpu_id: AtomicU32,
+    /// この CPU で今走っているタスクの ID（scheduler が切り替えのたびに書く）
+    current_task_id: AtomicU64,
+    /// この CPU の TSS
+    tss: AtomicPtr<TaskStateSegment>,
+}
+
+impl PerCpu {
+    const fn new() -> Self {
+        Self {
+            self_ptr: AtomicU64::new(0),
+            cpu_id: AtomicU32::new(0),
+            current_task_id: AtomicU64::new(0),
+            tss: AtomicPtr::new(core::ptr::null_mut()),
+        }
+    }
+
+    /// CPU 番号（BSP = 0）
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Relaxed) as usize
+    }
+
+    /// この CPU で今走っているタスクの ID
+    pub fn current_task_id(&self) -> u64 {
+        self.current_task_id.load(Ordering::Relaxed)
+    }
+
+    /// 今走っているタスクを記録する（scheduler が current を切り替えるときに呼ぶ）
+    pub fn set_current_task_id(&self, id: u64) {
+        self.current_task_id.store(id, Ordering::Relaxed);
+    }
+}
+
+/// CPU 番号で引く CPU ごとのブロック
+static CPUS: [PerCpu; MAX_CPUS] = [const { PerCpu::new() }; MAX_CPUS];
+
+/// 今の CPU を CPU 番号 cpu として登録し、GS ベースを自分のブロックに向ける。
+///
+/// BSP は gdt::init() の直後に、AP は自分の GDT をロードした直後に呼ぶ。
+/// ヒープもロックも使わないので AP の起動直後でも呼べる。
+pub fn init(cpu: usize, tss: *mut TaskStateSegment) {
+    let block = &CPUS[cpu];
+    block.self_ptr.store(block as *const PerCpu as u64, Ordering::Relaxed);
+    block.cpu_id.store(cpu as u32, Ordering::Relaxed);
+    block.tss.store(tss, Ordering::Relaxed);
+    GsBase::write(VirtAddr::new(block as *const PerCpu as u64));
+    // 最初に Ring 3 に入るときの swapgs でユーザー側に出てくる値
+    KernelGsBase::write(VirtAddr::new(0));
+}
+
+/// 今の CPU のブロック
+///
+/// init() の前に呼んではいけない（GS ベースが 0 のまま）。
+#[inline]
+pub fn current() -> &'static PerCpu {
+    let ptr: u64;
+    unsafe {
+        core::arch::asm!("mov {}, gs:[0]", out(reg) ptr, options(nostack, preserves_flags, readonly));
+        &*(ptr as *const PerCpu)
+    }
+}
+
+/// 割り込み・例外ハンドラの中で GS ベースをカーネルの値にしておくガード
+///
+/// Ring 3 から割り込まれたときだけ作成時と Drop 時に swapgs する。
+/// ハンドラの先頭（GS を使うどのコードよりも前）で作り、ハンドラの最後まで持っておく。
+/// プリエンプションで別タスクに切り替わっても、戻ってきてから Drop されるので対応は崩れない。
+pub struct KernelGs {
+    from_user: bool,
+}
+
+impl KernelGs {
+    /// 通常の割り込み・例外用。割り込まれた CS の RPL で Ring 3 からかを判定する。
+    #[inline(always)]
+    pub fn enter(stack_frame: &InterruptStackFrame) -> Self {
+        let from_user = stack_frame.code_segment.rpl() == x86_64::PrivilegeLevel::Ring3;
+        if from_user {
+            unsafe { core::arch::asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+        Self { from_user }
+    }
+
+    /// NMI・ダブルフォルト・マシンチェック用。
+    ///
+    /// これらはカーネルの swapgs と iretq の間にも割り込めるので、CS では判定できない。
+    /// 代わりに GS ベースがどれかの CPU のブロックを指しているかを MSR から直接見る。
+    #[inline(always)]
+    pub fn enter_paranoid() -> Self {
+        let gs = GsBase::read().as_u64();
+        let start = CPUS.as_ptr() as u64;
+        let end = start + core::mem::size_of_val(&CPUS) as u64;
+        let from_user = !(start..end).contains(&gs);
+        if from_user {
+            unsafe { core::arch::asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+        Self { from_user }
+    }
+}
+
+impl Drop for KernelGs {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if self.from_user {
+            unsafe { core::arch::asm!("swapgs", options(nostack, preserves_flags)) };
+        }
+    }
+}
+
+/// 今の CPU の番号（BSP = 0）
+pub fn current_cpu_id() -> usize {
+    current().cpu_id()
+}
+
+/// 今の CPU の TSS の rsp0 を設定する。
+///
+/// Ring 3 → Ring 0 への遷移時（int 命令、例外など）、CPU は自動的に
+/// TSS の rsp0 が指すアドレスにスタックポインタを切り替える。
+/// ユーザータスクを実行する前に、そのタスク用のカーネルスタックの
+/// トップアドレスをここに設定する必要がある。
+///
+/// # Safety
+/// - rsp0 は有効なスタック領域のトップアドレスでなければならない
+/// - 16 バイトアラインされていること
+pub unsafe fn set_kernel_stack(rsp0: VirtAddr) {
+    let tss = current().tss.load(Ordering::Relaxed);
+    unsafe {
+        (*tss).privilege_stack_table[0] = rsp0;
+    }
+}
@@ -14,6 +14,9 @@
 // - データがあれば即座に返す
 // - データがなく writer が生きていれば WouldBlock を返す（呼び出し側が yield + retry）
 // - データがなく writer_closed なら 0 を返す（EOF）
+//   子プロセスに渡した write 端は、exit・kill・例外のどれで終わっても閉じられるので、
+//   親の read_to_end は残りを読み切った後に必ず EOF で終わる
+// - 長さ 0 の読み取りは待たずに 0 を返す
 //
 // ## 書き込みの挙動
 //
@@ -118,6 +121,8 @@ pub fn write(pipe_id: usize, data: &[u8]) -> Result<usize, PipeError> {
 ///
 /// # 戻り値
 /// 読み取ったバイト数。writer_closed かつデータなしの場合は 0（EOF）。
+/// 溜まっている分だけ返し、buf が埋まるまでは待たない（BufReader の部分読み取り用）。
+/// 長さ 0 の buf は待たずに 0 を返す（std の Read と同じ。EOF の意味ではない）。
 ///
 /// # エラー
 /// - `InvalidPipe`: パイプ ID が無効
@@ -128,6 +133,11 @@ pub fn read(pipe_id: usize, buf: &mut [u8]) -> Result<usize, PipeError> {
         .and_then(|slot| slot.as_mut())
         .ok_or(PipeError::InvalidPipe)?;
 
+    // 長さ 0 の読み取りでブロックすると、呼び出し側の yield + retry が終わらない
+    if buf.is_empty() {
+        return Ok(0);
+    }
+
     if pipe.buf.is_empty() {
         if pipe.writer_count == 0 {
             // 全 writer が閉じていてデータもない → EOF
@@ -241,6 +251,12 @@ pub fn test_pipe() -> bool {
         }
     }
 
+    // 4.5. 長さ 0 の読み取りはブロックせず 0
+    if read(pipe_id, &mut []) != Ok(0) {
+        crate::serial_println!("[pipe test] zero-length read did not return Ok(0)");
+        return false;
+    }
+
     // 5. writer を閉じる
     close_writer(pipe_id);
 
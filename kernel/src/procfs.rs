@@ -15,6 +15,13 @@
 // - /proc/meminfo: メモリ情報（JSON 形式）
 // - /proc/tasks: タスク一覧（JSON 形式）
 // - /proc/maps: 全プロセスの VMA（仮想メモリ領域）情報（JSON 形式）
+// - /proc/kmsg: カーネルログのリングバッファ（1 行ずつ JSON 形式）
+// - /proc/<pid>/status: タスクの状態とメモリ使用量（JSON 形式）
+// - /proc/<pid>/cmdline: spawn 時の argv（JSON 形式）
+// - /proc/<pid>/maps: そのプロセスのユーザー空間マッピング（JSON 形式）
+//
+// <pid> ディレクトリは実体を持たず、open / list_dir のたびに
+// スケジューラへ問い合わせて存在確認と内容生成を行う。
 
 
 use alloc::boxed::Box;
@@ -32,6 +39,18 @@ const PROC_MEMINFO: &str = "meminfo";
 const PROC_TASKS: &str = "tasks";
 /// VMA マップ情報ファイルのパス
 const PROC_MAPS: &str = "maps";
+/// デバイス割り込み（MSI-X）統計ファイルのパス
+const PROC_INTERRUPTS: &str = "interrupts";
+/// ネットワーク（NIC 受信リング）統計ファイルのパス
+const PROC_NET: &str = "net";
+/// カーネルログ（kmsg リングバッファ）のパス
+const PROC_KMSG: &str = "kmsg";
+/// /proc/<pid>/ 配下: 状態ファイル
+const PROC_PID_STATUS: &str = "status";
+/// /proc/<pid>/ 配下: コマンドラインファイル
+const PROC_PID_CMDLINE: &str = "cmdline";
+/// /proc/<pid>/ 配下のファイル一覧（maps はトップレベルと同名）
+const PROC_PID_FILES: [&str; 3] = [PROC_PID_STATUS, PROC_PID_CMDLINE, PROC_MAPS];
 
 /// procfs ファイルシステム
 pub struct ProcFs;
@@ -94,11 +113,19 @@ impl FileSystem for ProcFs {
         // ここに来るパスは "meminfo", "tasks" 等の相対パス。
         let path = path.trim().trim_start_matches('/');
 
+        // "<pid>" または "<pid>/<file>" ならプロセスごとのファイル
+        if let Some((pid, rest)) = split_pid_path(path) {
+            return open_pid_entry(pid, rest);
+        }
+
         // ファイルの内容を生成
         let data = match path {
             PROC_MEMINFO => generate_meminfo(),
             PROC_TASKS => generate_tasks(),
             PROC_MAPS => generate_maps(),
+            PROC_INTERRUPTS => generate_interrupts(),
+            PROC_NET => generate_net(),
+            PROC_KMSG => generate_kmsg(),
             "" => return Err(VfsError::NotAFile),
             _ => return Err(VfsError::NotFound),
         };
@@ -111,13 +138,31 @@ impl FileSystem for ProcFs {
         // ルートディレクトリは "" or "/" で来る。
         let path = path.trim().trim_start_matches('/');
 
-        // ルートディレクトリのみサポート
+        // /proc/<pid> ディレクトリ
+        if let Some((pid, rest)) = split_pid_path(path) {
+            if !rest.is_empty() {
+                return Err(VfsError::NotADirectory);
+            }
+            if !pid_exists(pid) {
+                return Err(VfsError::NotFound);
+            }
+            return Ok(PROC_PID_FILES
+                .iter()
+                .map(|name| VfsDirEntry {
+                    name: String::from(*name),
+                    kind: VfsNodeKind::File,
+                    size: 0,
+                })
+                .collect());
+        }
+
+        // それ以外はルートディレクトリのみサポート
         if !path.is_empty() {
             return Err(VfsError::NotFound);
         }
 
         // procfs のファイル一覧
-        let entries = vec![
+        let mut entries = vec![
             VfsDirEntry {
                 name: String::from("meminfo"),
                 kind: VfsNodeKind::File,
@@ -133,8 +178,35 @@ impl FileSystem for ProcFs {
                 kind: VfsNodeKind::File,
                 size: 0,
             },
+            VfsDirEntry {
+                name: String::from("interrupts"),
+                kind: VfsNodeKind::File,
+                size: 0,
+            },
+            VfsDirEntry {
+                name: String::from("net"),
+                kind: VfsNodeKind::File,
+                size: 0,
+            },
+            VfsDirEntry {
+                name: String::from("kmsg"),
+                kind: VfsNodeKind::File,
+                size: 0,
+            },
         ];
 
+        // 生存中のタスクごとに数値ディレクトリを並べる
+        for t in crate::scheduler::task_list() {
+            if t.state == crate::scheduler::TaskState::Finished {
+                continue;
+            }
+            entries.push(VfsDirEntry {
+                name: alloc::format!("{}", t.id),
+                kind: VfsNodeKind::Directory,
+                size: 0,
+            });
+        }
+
         Ok(entries)
     }
 
@@ -159,6 +231,102 @@ impl FileSystem for ProcFs {
 // ファイル内容の生成
 // =================================================================
 
+/// デバイスごとの MSI-X 割り込み情報を JSON 形式で生成する
+///
+/// MSI-X を使っていないデバイスの count は常に 0。
+fn generate_interrupts() -> Vec<u8> {
+    let devices: [(&str, bool, u64); 3] = [
+        ("virtio-net", crate::virtio_net::uses_msix(), crate::virtio_net::msix_irq_count()),
+        ("virtio-blk", crate::virtio_blk::VIRTIO_BLKS.lock().iter().any(|d| d.uses_msix()), crate::virtio_blk::msix_irq_count()),
+        ("nvme", crate::nvme::NVME_DEVICES.lock().iter().any(|d| d.uses_msix()), crate::nvme::msix_irq_count()),
+    ];
+
+    let mut buf = Vec::with_capacity(192);
+    let mut writer = VecWriter::new(&mut buf);
+    let _ = write!(writer, "{{\"devices\":[");
+    for (i, (name, msix, count)) in devices.iter().enumerate() {
+        if i != 0 {
+            let _ = write!(writer, ",");
+        }
+        let mode = if *msix { "msix" } else { "polling" };
+        let _ = write!(writer, "{{\"name\":\"{}\",\"mode\":\"{}\",\"count\":{}}}", name, mode, count);
+    }
+    let _ = writeln!(writer, "]}}");
+    buf
+}
+
+/// 使用中の NIC の受信リング統計を JSON 形式で生成する
+///
+/// NIC がなければ "nic" は null になる。
+fn generate_net() -> Vec<u8> {
+    let mut buf = Vec::with_capacity(160);
+    let mut writer = VecWriter::new(&mut buf);
+    match crate::netstack::nic_rx_stats() {
+        Some((nic, rx)) => {
+            let _ = writeln!(
+                writer,
+                "{{\"nic\":\"{}\",\"rx\":{{\"packets\":{},\"overflows\":{},\"dropped\":{},\"recoveries\":{}}}}}",
+                nic, rx.packets, rx.overflows, rx.dropped, rx.recoveries
+            );
+        }
+        None => {
+            let _ = writeln!(writer, "{{\"nic\":null}}");
+        }
+    }
+    buf
+}
+
+/// カーネルログを 1 行ずつの JSON 形式で生成する
+///
+/// seq は起動してからの行の通し番号。level は klog! が付けた行頭の印から読み取り、
+/// 印のない行（kprintln! / serial_println! など）は "info" にする。
+/// 上書きされた直後は先頭行が UTF-8 の途中から始まるので、不正なバイトは U+FFFD に置き換える。
+fn generate_kmsg() -> Vec<u8> {
+    let (first_seq, log) = crate::kmsg::snapshot_with_seq();
+    let text = String::from_utf8_lossy(&log);
+    let body = text.strip_suffix('\n').unwrap_or(&text);
+
+    // エスケープで少し増えるぶんを見込んでおく
+    let mut buf = Vec::with_capacity(log.len() + log.len() / 4 + 16);
+    let mut writer = VecWriter::new(&mut buf);
+    let _ = write!(writer, "{{\"lines\":[");
+    if !body.is_empty() {
+        for (i, line) in body.split('\n').enumerate() {
+            if i != 0 {
+                let _ = write!(writer, ",");
+            }
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let _ = write!(
+                writer,
+                "{{\"seq\":{},\"level\":\"{}\",\"msg\":\"",
+                first_seq + i as u64,
+                kmsg_line_level(line)
+            );
+            let _ = write_json_string(&mut writer, line);
+            let _ = write!(writer, "\"}}");
+        }
+    }
+    let _ = writeln!(writer, "]}}");
+    buf
+}
+
+/// klog::_log が付けた行頭（"[net] ERROR: " / "[net] WARN: " / "[net-trace] "）からレベル名を得る。
+/// klog! の Info と Debug は同じ "[net] " なので区別せず "info" とする。
+fn kmsg_line_level(line: &str) -> &'static str {
+    let Some((tag, rest)) = line.strip_prefix('[').and_then(|l| l.split_once("] ")) else {
+        return "info";
+    };
+    if tag.ends_with("-trace") {
+        "trace"
+    } else if rest.starts_with("ERROR: ") {
+        "error"
+    } else if rest.starts_with("WARN: ") {
+        "warn"
+    } else {
+        "info"
+    }
+}
+
 /// メモリ情報を JSON 形式で生成する
 fn generate_meminfo() -> Vec<u8> {
     use crate::memory::FRAME_ALLOCATOR;
@@ -180,13 +348,14 @@ fn generate_meminfo() -> Vec<u8> {
     } else {
         "bss_fallback"
     };
+    let heap = allocator::heap_stats();
 
     // JSON 形式で書き込む
     let mut buf = Vec::with_capacity(256);
     let mut writer = VecWriter::new(&mut buf);
     let _ = write!(
         writer,
-        "{{\"total_frames\":{},\"allocated_frames\":{},\"free_frames\":{},\"free_kib\":{},\"heap_start\":{},\"heap_size\":{},\"heap_source\":\"{}\",\"processes\":[",
+        "{{\"total_frames\":{},\"allocated_frames\":{},\"free_frames\":{},\"free_kib\":{},\"heap_start\":{},\"heap_size\":{},\"heap_source\":\"{}\",",
         total,
         allocated,
         free,
@@ -195,6 +364,13 @@ fn generate_meminfo() -> Vec<u8> {
         heap_size,
         heap_source
     );
+    let _ = write!(
+        writer,
+        "\"heap_used\":{},\"heap_largest_free\":{},\"heap_free_blocks\":{},\"processes\":[",
+        heap.used_bytes,
+        heap.largest_free_block,
+        heap.free_block_count
+    );
     for (i, p) in processes.iter().enumerate() {
         if i != 0 {
             let _ = write!(writer, ",");
@@ -213,7 +389,7 @@ fn generate_meminfo() -> Vec<u8> {
 
 /// タスク一覧を JSON 形式で生成する
 fn generate_tasks() -> Vec<u8> {
-    use crate::scheduler::{self, TaskState};
+    use crate::scheduler;
 
     // タスク一覧を取得
     let tasks = scheduler::task_list();
@@ -224,12 +400,7 @@ fn generate_tasks() -> Vec<u8> {
 
     let _ = write!(writer, "{{\"tasks\":[");
     for (i, t) in tasks.iter().enumerate() {
-        let state_str = match t.state {
-            TaskState::Ready => "Ready",
-            TaskState::Running => "Running",
-            TaskState::Sleeping(_) => "Sleeping",
-            TaskState::Finished => "Finished",
-        };
+        let state_str = task_state_str(t.state);
         let type_str = if t.is_user_process { "user" } else { "kernel" };
         if i != 0 {
             let _ = write!(writer, ",");
@@ -261,7 +432,6 @@ fn generate_tasks() -> Vec<u8> {
 /// ```
 fn generate_maps() -> Vec<u8> {
     use crate::scheduler::{self, TaskState};
-    use crate::vma::VmaKind;
 
     // タスク一覧を取得
     let tasks = scheduler::task_list();
@@ -289,42 +459,155 @@ fn generate_maps() -> Vec<u8> {
         }
         first_process = false;
 
-        let _ = write!(writer, "{{\"id\":{},\"name\":\"", t.id);
-        let _ = write_json_string(&mut writer, t.name.as_str());
-        let _ = write!(writer, "\",\"vmas\":[");
+        write_process_vmas(&mut writer, t.id, t.name.as_str(), &vmas);
+    }
 
-        for (i, vma) in vmas.iter().enumerate() {
-            if i != 0 {
-                let _ = write!(writer, ",");
-            }
+    let _ = write!(writer, "]}}\n");
 
-            // プロテクション文字列を構築 (例: "rwx", "r-x", "rw-")
-            let prot_str = alloc::format!(
-                "{}{}{}",
-                if vma.prot.read { "r" } else { "-" },
-                if vma.prot.write { "w" } else { "-" },
-                if vma.prot.execute { "x" } else { "-" },
-            );
+    buf
+}
 
-            let kind_str = match vma.kind {
-                VmaKind::Anonymous => "Anonymous",
-                VmaKind::ElfLoad => "ElfLoad",
-                VmaKind::UserStack => "UserStack",
-            };
+/// 1 プロセス分の VMA を {"id":..,"name":..,"vmas":[..]} 形式で書き込む。
+///
+/// /proc/maps（全プロセス）と /proc/<pid>/maps（1 プロセス）で共通の形式にする。
+fn write_process_vmas(writer: &mut VecWriter<'_>, id: u64, name: &str, vmas: &[crate::vma::Vma]) {
+    use crate::vma::VmaKind;
 
-            let _ = write!(
-                writer,
-                "{{\"start\":\"0x{:x}\",\"end\":\"0x{:x}\",\"size\":{},\"prot\":\"{}\",\"kind\":\"{}\",\"name\":\"",
-                vma.start, vma.end, vma.size(), prot_str, kind_str
-            );
-            let _ = write_json_string(&mut writer, vma.name.as_str());
-            let _ = write!(writer, "\"}}");
+    let _ = write!(writer, "{{\"id\":{},\"name\":\"", id);
+    let _ = write_json_string(writer, name);
+    let _ = write!(writer, "\",\"vmas\":[");
+
+    for (i, vma) in vmas.iter().enumerate() {
+        if i != 0 {
+            let _ = write!(writer, ",");
         }
 
-        let _ = write!(writer, "]}}");
+        // プロテクション文字列を構築 (例: "rwx", "r-x", "rw-")
+        let prot_str = alloc::format!(
+            "{}{}{}",
+            if vma.prot.read { "r" } else { "-" },
+            if vma.prot.write { "w" } else { "-" },
+            if vma.prot.execute { "x" } else { "-" },
+        );
+
+        let kind_str = match vma.kind {
+            VmaKind::Anonymous => "Anonymous",
+            VmaKind::ElfLoad => "ElfLoad",
+            VmaKind::UserStack => "UserStack",
+            VmaKind::File => "File",
+            VmaKind::Guard => "Guard",
+        };
+
+        let _ = write!(
+            writer,
+            "{{\"start\":\"0x{:x}\",\"end\":\"0x{:x}\",\"size\":{},\"prot\":\"{}\",\"kind\":\"{}\",\"name\":\"",
+            vma.start, vma.end, vma.size(), prot_str, kind_str
+        );
+        let _ = write_json_string(writer, vma.name.as_str());
+        let _ = write!(writer, "\"}}");
     }
 
-    let _ = write!(writer, "]}}\n");
+    let _ = write!(writer, "]}}");
+}
+
+// =================================================================
+// /proc/<pid>/ 配下
+// =================================================================
+
+/// "3" や "3/status" を (pid, 残りのパス) に分解する。
+/// 先頭要素が数値でなければ None（通常の /proc 直下ファイル）。
+fn split_pid_path(path: &str) -> Option<(u64, &str)> {
+    let (first, rest) = match path.split_once('/') {
+        Some((first, rest)) => (first, rest.trim_matches('/')),
+        None => (path, ""),
+    };
+    if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    first.parse::<u64>().ok().map(|pid| (pid, rest))
+}
+
+/// 生存中（Finished 以外）のタスクかどうか
+fn pid_exists(pid: u64) -> bool {
+    crate::scheduler::task_exists(pid)
+}
+
+/// /proc/<pid>/<rest> を開く
+fn open_pid_entry(pid: u64, rest: &str) -> Result<Box<dyn VfsNode>, VfsError> {
+    let detail = match crate::scheduler::task_detail(pid) {
+        Some(d) if d.state != crate::scheduler::TaskState::Finished => d,
+        _ => return Err(VfsError::NotFound),
+    };
+
+    let data = match rest {
+        "" => return Err(VfsError::NotAFile),
+        PROC_PID_STATUS => generate_pid_status(&detail),
+        PROC_PID_CMDLINE => generate_pid_cmdline(&detail),
+        PROC_MAPS => generate_pid_maps(&detail),
+        _ => return Err(VfsError::NotFound),
+    };
+
+    Ok(Box::new(ProcNode::new(data)))
+}
+
+/// /proc/<pid>/status: タスクの状態とメモリ使用量を JSON 形式で生成する
+fn generate_pid_status(detail: &crate::scheduler::TaskDetail) -> Vec<u8> {
+    let vmas = crate::scheduler::get_vma_list_for_task(detail.id).unwrap_or_default();
+    let vm_bytes: u64 = vmas.iter().map(|v| v.size()).sum();
+
+    let mut buf = Vec::with_capacity(256);
+    let mut writer = VecWriter::new(&mut buf);
+
+    let type_str = if detail.is_user_process { "user" } else { "kernel" };
+    let _ = write!(writer, "{{\"id\":{},\"name\":\"", detail.id);
+    let _ = write_json_string(&mut writer, detail.name.as_str());
+    let _ = write!(writer, "\",\"state\":\"{}\",\"type\":\"{}\"", task_state_str(detail.state), type_str);
+    let _ = write!(writer, ",\"parent_id\":");
+    write_json_opt_u64(&mut writer, detail.parent_id);
+    let _ = write!(writer, ",\"leader_id\":");
+    write_json_opt_u64(&mut writer, detail.process_leader_id);
+    let _ = writeln!(
+        writer,
+        ",\"user_frames\":{},\"user_kib\":{},\"mmap_frames\":{},\"mmap_kib\":{},\"vma_count\":{},\"vm_bytes\":{}}}",
+        detail.user_frames,
+        detail.user_frames * 4,
+        detail.mmap_frames,
+        detail.mmap_frames * 4,
+        vmas.len(),
+        vm_bytes
+    );
+
+    buf
+}
+
+/// /proc/<pid>/cmdline: spawn 時の argv を JSON 形式で生成する
+fn generate_pid_cmdline(detail: &crate::scheduler::TaskDetail) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(128);
+    let mut writer = VecWriter::new(&mut buf);
+
+    let _ = write!(writer, "{{\"argv\":[");
+    for (i, arg) in detail.cmdline.iter().enumerate() {
+        if i != 0 {
+            let _ = write!(writer, ",");
+        }
+        let _ = write!(writer, "\"");
+        let _ = write_json_string(&mut writer, arg.as_str());
+        let _ = write!(writer, "\"");
+    }
+    let _ = writeln!(writer, "]}}");
+
+    buf
+}
+
+/// /proc/<pid>/maps: そのプロセスのユーザー空間マッピングを JSON 形式で生成する。
+/// カーネルタスクはユーザー空間を持たないので vmas は空になる。
+fn generate_pid_maps(detail: &crate::scheduler::TaskDetail) -> Vec<u8> {
+    let vmas = crate::scheduler::get_vma_list_for_task(detail.id).unwrap_or_default();
+
+    let mut buf = Vec::with_capacity(512);
+    let mut writer = VecWriter::new(&mut buf);
+    write_process_vmas(&mut writer, detail.id, detail.name.as_str(), &vmas);
+    let _ = writeln!(writer);
 
     buf
 }
@@ -333,6 +616,25 @@ fn generate_maps() -> Vec<u8> {
 // ユーティリティ
 // =================================================================
 
+/// TaskState を JSON 出力用の文字列にする
+fn task_state_str(state: crate::scheduler::TaskState) -> &'static str {
+    use crate::scheduler::TaskState;
+    match state {
+        TaskState::Ready => "Ready",
+        TaskState::Running => "Running",
+        TaskState::Sleeping(_) => "Sleeping",
+        TaskState::Finished => "Finished",
+    }
+}
+
+/// Option<u64> を JSON の数値または null として書き込む
+fn write_json_opt_u64(writer: &mut VecWriter<'_>, value: Option<u64>) {
+    let _ = match value {
+        Some(v) => write!(writer, "{}", v),
+        None => writer.write_str("null"),
+    };
+}
+
 /// Vec<u8> に書き込むための Write 実装
 struct VecWriter<'a> {
     buf: &'a mut Vec<u8>,
@@ -371,6 +673,10 @@ fn write_json_string(writer: &mut VecWriter<'_>, s: &str) -> core::fmt::Result {
             '\t' => {
                 let _ = writer.write_str("\\t");
             }
+            // ログには ANSI エスケープなどの制御文字も混ざるので、JSON が壊れないよう \u でエスケープする
+            c if (c as u32) < 0x20 => {
+                let _ = write!(writer, "\\u{:04x}", c as u32);
+            }
             _ => {
                 let encoded = ch.encode_utf8(&mut buf);
                 let _ = writer.write_str(encoded);
@@ -0,0 +1,231 @@
+// random.rs — カーネルのエントロピープール
+//
+// SYS_GETRANDOM や TCP の ISN / DNS のクエリ ID に使う乱数を生成する。
+//
+// 以前は RDRAND 命令だけに頼っていて、RDRAND が使えない CPU では
+// SYS_GETRANDOM がエラーになり、カーネル内の乱数は 0 になっていた。
+// 「予測できる値（0）を黙って返す」のは乱数として最悪なので、
+// RDRAND がなくても動くソフトウェアのプールを用意する。
+//
+// ## 仕組み
+//
+// ChaCha20 のブロック関数を CSPRNG として使う。
+// - 鍵（256 ビット）がプールの状態。取り出すたびに新しいエントロピーを鍵に混ぜる
+// - 出力は ChaCha20(鍵, カウンタ) のキーストリーム
+// - 出力のあとは鍵を次のブロックで置き換える（fast key erasure）。
+//   あとから鍵が漏れても、過去に出した乱数は再現できない
+//
+// エントロピー源:
+// - TSC（rdtsc）: 取り出すタイミングのゆらぎ
+// - PIT のティック数
+// - 割り込みのタイミング: タイマー / キーボード割り込みが来た瞬間の TSC を
+//   IRQ_JITTER に畳み込んでおく（割り込みハンドラからはロックを取らない）
+// - RDRAND: 使える CPU なら毎回混ぜる（あればこれが一番強い）
+
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use spin::Mutex;
+
+/// ChaCha20 の定数 "expand 32-byte k"
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// プールの状態
+struct EntropyPool {
+    /// ChaCha20 の鍵。ここにエントロピーを混ぜ込んでいく
+    key: [u32; 8],
+    /// 出力用のブロックカウンタ
+    counter: u64,
+}
+
+static POOL: Mutex<EntropyPool> = Mutex::new(EntropyPool {
+    key: [0; 8],
+    counter: 0,
+});
+
+/// 割り込みが来た瞬間の TSC を畳み込んだ値。
+/// 割り込みハンドラから更新するので Mutex ではなくアトミックにする。
+/// load と store の間に別の割り込みが割り込んでも値が混ざるだけなので問題ない。
+static IRQ_JITTER: AtomicU64 = AtomicU64::new(0);
+
+/// RDRAND の有無のキャッシュ（0 = 未判定, 1 = なし, 2 = あり）
+static RDRAND_STATE: AtomicU8 = AtomicU8::new(0);
+
+/// ChaCha20 の quarter round
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// ChaCha20 のブロック関数。
+/// input は通常の ChaCha20 のカウンタ + nonce にあたる 128 ビット。
+fn chacha20_block(key: &[u32; 8], input: [u32; 4]) -> [u32; 16] {
+    let mut state = [0u32; 16];
+    state[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    state[4..12].copy_from_slice(key);
+    state[12..].copy_from_slice(&input);
+
+    let mut s = state;
+    for _ in 0..10 {
+        // column round
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        // diagonal round
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+    for (out, init) in s.iter_mut().zip(state.iter()) {
+        *out = out.wrapping_add(*init);
+    }
+    s
+}
+
+/// 2 つの u64 を ChaCha20 の 128 ビット入力に並べる
+fn words(a: u64, b: u64) -> [u32; 4] {
+    [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32]
+}
+
+impl EntropyPool {
+    /// 128 ビットの材料を鍵に混ぜる。
+    /// 新しい鍵 = ChaCha20(古い鍵, 材料) の前半 256 ビット。
+    /// 材料が予測可能でも、古い鍵が秘密なら新しい鍵も秘密のまま。
+    fn absorb(&mut self, a: u64, b: u64) {
+        let block = chacha20_block(&self.key, words(a, b));
+        self.key.copy_from_slice(&block[..8]);
+    }
+
+    /// キーストリームで buf を埋め、最後に鍵を使い捨てる
+    fn generate(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(64) {
+            let block = chacha20_block(&self.key, words(self.counter, 0));
+            self.counter = self.counter.wrapping_add(1);
+            for (i, b) in chunk.iter_mut().enumerate() {
+                *b = (block[i / 4] >> ((i % 4) * 8)) as u8;
+            }
+        }
+        // fast key erasure: 出力に使っていない入力で次の鍵を作る
+        let block = chacha20_block(&self.key, words(self.counter, u64::MAX));
+        self.counter = self.counter.wrapping_add(1);
+        self.key.copy_from_slice(&block[..8]);
+    }
+}
+
+/// TSC (Time Stamp Counter) を読む
+fn rdtsc() -> u64 {
+    let lo: u32;
+    let hi: u32;
+    unsafe {
+        core::arch::asm!("rdtsc", out("eax") lo, out("edx") hi, options(nomem, nostack));
+    }
+    ((hi as u64) << 32) | lo as u64
+}
+
+/// CPU が RDRAND 命令を持っているか（CPUID.01H:ECX bit 30）。
+/// 持っていない CPU で RDRAND を実行すると #UD になるので、必ず先に確認する。
+fn rdrand_available() -> bool {
+    match RDRAND_STATE.load(Ordering::Relaxed) {
+        1 => false,
+        2 => true,
+        _ => {
+            let ecx = core::arch::x86_64::__cpuid(1).ecx;
+            let available = ecx & (1 << 30) != 0;
+            RDRAND_STATE.store(if available { 2 } else { 1 }, Ordering::Relaxed);
+            available
+        }
+    }
+}
+
+/// RDRAND 命令で 64 ビットの乱数を取る。
+/// 失敗する場合（エントロピー枯渇など）は最大 10 回リトライし、それでも駄目なら None。
+fn rdrand64() -> Option<u64> {
+    if !rdrand_available() {
+        return None;
+    }
+    for _ in 0..10 {
+        let value: u64;
+        let success: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdrand {val}",
+                "setc {ok}",
+                val = out(reg) value,
+                ok = out(reg_byte) success,
+            );
+        }
+        if success != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// 割り込みハンドラから呼んで、割り込みが来たタイミングをプールに混ぜる。
+/// ロックを取らないので割り込みコンテキストから安全に呼べる。
+pub fn add_interrupt_timing() {
+    let old = IRQ_JITTER.load(Ordering::Relaxed);
+    IRQ_JITTER.store(old.rotate_left(7) ^ rdtsc(), Ordering::Relaxed);
+}
+
+/// 現在のエントロピーを鍵に混ぜてから buf を埋める。
+/// use_rdrand が false なら RDRAND なしのソフトウェア経路だけを使う。
+fn fill(buf: &mut [u8], use_rdrand: bool) {
+    // 割り込みハンドラからはロックを取らないが、タスク切り替えで
+    // ロックを持ったまま止まらないように割り込みを禁止しておく
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut pool = POOL.lock();
+        let ticks = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+        pool.absorb(rdtsc(), ticks);
+        pool.absorb(IRQ_JITTER.load(Ordering::Relaxed), rdtsc());
+        if use_rdrand && let (Some(a), Some(b)) = (rdrand64(), rdrand64()) {
+            pool.absorb(a, b);
+        }
+        pool.generate(buf);
+    });
+}
+
+/// buf を乱数で埋める。
+/// RDRAND が使えればそれも混ぜるが、使えなくても失敗しない。
+pub fn fill_bytes(buf: &mut [u8]) {
+    fill(buf, true);
+}
+
+/// 64 ビットの乱数を返す（TCP の ISN や DNS のクエリ ID 用）
+pub fn random_u64() -> u64 {
+    let mut bytes = [0u8; 8];
+    fill_bytes(&mut bytes);
+    u64::from_le_bytes(bytes)
+}
+
+/// 256 バイトの乱数がそれらしく分布しているかを確認する。
+/// 全ゼロでないこと、同じバイト値が偏って出ていないこと、
+/// ビットの 1 と 0 がおおよそ半々であることを見る。
+fn looks_random(buf: &[u8; 256]) -> bool {
+    let mut counts = [0u32; 256];
+    for &b in buf.iter() {
+        counts[b as usize] += 1;
+    }
+    // 一様なら出現するバイト値は平均約 162 種類、1 つの値の出現は多くても数回
+    let distinct = counts.iter().filter(|&&c| c > 0).count();
+    let max = counts.iter().copied().max().unwrap_or(0);
+    // 2048 ビット中の 1 の数は平均 1024、標準偏差は約 23
+    let ones: u32 = buf.iter().map(|b| b.count_ones()).sum();
+    buf.iter().any(|&b| b != 0) && distinct >= 120 && max <= 10 && (900..=1150).contains(&ones)
+}
+
+/// RDRAND あり / なしの両方の経路で 256 バイトを取り出して分布を確認する。
+/// 連続して取り出した値が同じにならないことも確認する。
+pub fn test_entropy_pool() -> bool {
+    let mut a = [0u8; 256];
+    let mut b = [0u8; 256];
+    fill(&mut a, true);
+    fill(&mut b, false);
+    looks_random(&a) && looks_random(&b) && a != b && random_u64() != random_u64()
+}
@@ -19,7 +19,8 @@ use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::arch::global_asm;
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use sabos_syscall::{PRIORITY_DEFAULT, PRIORITY_MAX};
 use spin::Mutex;
 use x86_64::structures::paging::{PhysFrame, Size4KiB};
 use x86_64::VirtAddr;
@@ -28,6 +29,8 @@ use x86_64::VirtAddr;
 static PREEMPT_CALL_COUNT: AtomicU64 = AtomicU64::new(0);
 /// preempt() で実際にコンテキストスイッチした回数。
 static PREEMPT_SWITCH_COUNT: AtomicU64 = AtomicU64::new(0);
+/// yield_now() でコンテキストスイッチした回数。
+static YIELD_SWITCH_COUNT: AtomicU64 = AtomicU64::new(0);
 
 /// preempt() の統計情報を返す（呼び出し回数, スイッチ回数）。
 /// 起動デモで使うので、機能フラグが有効なときだけ公開する。
@@ -39,6 +42,19 @@ pub fn preempt_stats() -> (u64, u64) {
     )
 }
 
+/// スケジューラの統計情報を返す（SYS_SCHED_STATS 用）
+pub fn sched_stats() -> sabos_syscall::SchedStats {
+    let preempt_switches = PREEMPT_SWITCH_COUNT.load(Ordering::Relaxed);
+    let yield_switches = YIELD_SWITCH_COUNT.load(Ordering::Relaxed);
+    sabos_syscall::SchedStats {
+        timer_ticks: crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed),
+        context_switches: preempt_switches + yield_switches,
+        preempt_calls: PREEMPT_CALL_COUNT.load(Ordering::Relaxed),
+        preempt_switches,
+        yield_switches,
+    }
+}
+
 /// タスクのスタックサイズ（16 KiB）。
 /// カーネルタスクなので大きなスタックは不要だが、
 /// kprintln! 等のフォーマット処理がスタックを使うのである程度必要。
@@ -153,8 +169,12 @@ extern "C" fn task_exit_handler() {
     };
     // キーボードフォーカスを持っていたら自動解放する
     crate::console::release_keyboard(task_id);
+    // ダブルバッファモードやクリップ領域を設定したまま終了したら解除する
+    crate::framebuffer::release_task(task_id);
     // IPC キューをクリーンアップ（未読メッセージを解放）
     crate::ipc::cleanup_task(task_id);
+    // 作ったタイマーを止める
+    crate::timer::cleanup_task(task_id);
     // 他のタスクに切り替える
     yield_now();
     // ここに戻ることはないはず（Finished タスクはスケジュールされない）
@@ -199,6 +219,8 @@ pub struct TaskInfo {
     pub state: TaskState,
     /// ユーザープロセスかどうか
     pub is_user_process: bool,
+    /// 実行中にタイマー割り込みを受けた回数（Task::cpu_ticks）
+    pub cpu_ticks: u64,
 }
 
 /// メモリ使用量の情報（procfs 用の簡易統計）。
@@ -210,6 +232,23 @@ pub struct ProcessMemInfo {
     pub user_frames: usize,
 }
 
+/// 1 タスクの詳細情報（/proc/<pid>/ 用）。
+pub struct TaskDetail {
+    pub id: u64,
+    pub name: String,
+    pub state: TaskState,
+    pub is_user_process: bool,
+    pub parent_id: Option<u64>,
+    /// スレッドの場合はプロセスリーダーの ID
+    pub process_leader_id: Option<u64>,
+    /// そのプロセスが確保したユーザー空間フレーム数
+    pub user_frames: usize,
+    /// user_frames のうち SYS_MMAP で確保したフレーム数
+    pub mmap_frames: usize,
+    /// spawn 時のコマンドライン引数
+    pub cmdline: Vec<String>,
+}
+
 /// ユーザープロセスの情報を保持する構造体。
 /// spawn_user() でユーザープロセスをタスクとして登録する際に使う。
 pub struct UserProcessInfo {
@@ -269,6 +308,9 @@ pub struct Task {
     /// プロセスの環境変数（KEY=VALUE のペア）。
     /// spawn 時に親プロセスから継承され、SYS_SETENV で変更可能。
     pub env_vars: Vec<(String, String)>,
+    /// カレントディレクトリ（normalize_path 済みの絶対パス）。
+    /// spawn 時に親から継承され、SYS_CHDIR でプロセスのスレッドすべてが変わる。
+    pub cwd: String,
     /// スレッドが属するプロセスリーダーのタスク ID。
     /// プロセスリーダー（メインスレッド）は None。
     /// spawn_thread() で作られたスレッドは Some(leader_id)。
@@ -281,10 +323,34 @@ pub struct Task {
     pub exit_saved_rsp: u64,
     /// ユーザータスクの exit_usermode 用 SAVED_RBP バックアップ。
     pub exit_saved_rbp: u64,
+    /// コンテキストスイッチ時に退避した「持っている OrderedMutex」（lock_order::held()）。
+    /// debug-locks フィーチャなしでは常に 0。
+    pub held_locks: u32,
     /// stdin リダイレクト先のパイプハンドル（None = コンソール）
     pub stdin_handle: Option<crate::handle::Handle>,
     /// stdout リダイレクト先のパイプハンドル（None = コンソール）
     pub stdout_handle: Option<crate::handle::Handle>,
+    /// 保留中のシグナル（ビット n = シグナル番号 n）。
+    /// SIG_TERM などの「即死しない」シグナルはここに溜まり、
+    /// プロセスが SYS_SIGPENDING で取り出すまで残る。
+    pub pending_signals: u64,
+    /// spawn 時に渡されたコマンドライン引数（argv）。
+    /// /proc/<pid>/cmdline で表示する。カーネルタスクやスレッドは空。
+    pub cmdline: Vec<String>,
+    /// このタスクが実行中にタイマー割り込みを受けた回数（CPU 時間の目安）。
+    /// preempt() でティックごとに現在のタスクへ加算する。top の CPU 列に使う。
+    pub cpu_ticks: u64,
+    /// スケジューリングの優先度（PRIORITY_MIN..=PRIORITY_MAX、大きいほど CPU を多く使える）。
+    /// 新しいタスクは PRIORITY_DEFAULT から始まる。Scheduler::pick_next() と preempt() を参照。
+    pub priority: u8,
+    /// 今のタイムスライスで使ったティック数（優先度が高いタスクのスライスの計算用）
+    slice_used: u8,
+    /// 優先度が低いために順番を見送った回数
+    skipped: u8,
+    /// スレッドローカルストレージのポインタ（SYS_THREAD_SET_TLS で設定する）。
+    /// std の thread_local がスレッドごとのキーテーブルの置き場所に使う。
+    /// カーネルは中身を解釈せず、新しいタスク・スレッドは 0 から始まる。
+    pub tls_pointer: u64,
 }
 
 // =================================================================
@@ -344,13 +410,23 @@ pub fn init() {
         exit_code: 0,                 // 初期値
         reaped: false,                // wait() は不要
         env_vars: Vec::new(),         // カーネルタスクに環境変数はない
+        cwd: String::from("/"),
         process_leader_id: None,      // カーネルタスクはプロセスリーダーではない
         exit_saved_rsp: 0,
         exit_saved_rbp: 0,
+        held_locks: 0,
         stdin_handle: None,
         stdout_handle: None,
+        pending_signals: 0,
+        cmdline: Vec::new(),
+        cpu_ticks: 0,
+        priority: PRIORITY_DEFAULT,
+        slice_used: 0,
+        skipped: 0,
+        tls_pointer: 0,
     });
     sched.current = 0;
+    crate::percpu::current().set_current_task_id(id);
 }
 
 /// 新しいタスクを作成してスケジューラに登録する。
@@ -436,14 +512,23 @@ pub fn spawn(name: &'static str, entry: fn()) {
         exit_code: 0,                 // 初期値
         reaped: false,                // wait() は不要
         env_vars: Vec::new(),         // カーネルタスクに環境変数はない
+        cwd: String::from("/"),
         process_leader_id: None,      // カーネルタスクはスレッドではない
         exit_saved_rsp: 0,
         exit_saved_rbp: 0,
+        held_locks: 0,
         stdin_handle: None,
         stdout_handle: None,
+        pending_signals: 0,
+        cmdline: Vec::new(),
+        cpu_ticks: 0,
+        priority: PRIORITY_DEFAULT,
+        slice_used: 0,
+        skipped: 0,
+        tls_pointer: 0,
     });
 
-    crate::serial_println!("[scheduler] spawned task {} '{}'", id, name);
+    crate::klog!(Info, Sched, "spawned task {} '{}'", id, name);
 }
 
 /// 現在のタスクの CPU を譲り、次の Ready タスクに切り替える。
@@ -462,19 +547,12 @@ pub fn yield_now() {
 
     let switch_info = {
         let mut sched = SCHEDULER.lock();
+        // 割り込みハンドラが積んでおいた起床要求を先に反映する
+        sched.drain_wake_queue();
         let current = sched.current;
-        let num_tasks = sched.tasks.len();
 
-        // 次の Ready タスクをラウンドロビンで探す。
-        // current+1 から始めて一周する。
-        let mut next = None;
-        for i in 1..=num_tasks {
-            let idx = (current + i) % num_tasks;
-            if sched.tasks[idx].state == TaskState::Ready {
-                next = Some(idx);
-                break;
-            }
-        }
+        // 次の Ready タスクをラウンドロビンで探す（優先度の扱いは pick_next() を参照）
+        let next = sched.pick_next();
 
         match next {
             None => None, // 他に Ready タスクがない
@@ -485,7 +563,9 @@ pub fn yield_now() {
                     sched.tasks[current].state = TaskState::Ready;
                 }
                 sched.tasks[next_idx].state = TaskState::Running;
+                sched.tasks[next_idx].slice_used = 0;
                 sched.current = next_idx;
+                crate::percpu::current().set_current_task_id(sched.tasks[next_idx].id);
 
                 // context_switch に渡すポインタを取得。
                 // Mutex を drop した後にこれらのポインタを使うが、
@@ -522,6 +602,10 @@ pub fn yield_now() {
                 sched.tasks[current].exit_saved_rsp = saved_rsp;
                 sched.tasks[current].exit_saved_rbp = saved_rbp;
 
+                // 持っているロックの記録もタスクごとに入れ替える（lock_order.rs）
+                sched.tasks[current].held_locks = crate::lock_order::held();
+                crate::lock_order::set_held(sched.tasks[next_idx].held_locks);
+
                 Some((old_rsp_ptr, new_rsp, new_cr3, new_kernel_stack_top, current))
             }
         }
@@ -537,13 +621,15 @@ pub fn yield_now() {
             x86_64::instructions::interrupts::enable_and_hlt();
         }
         Some((old_rsp_ptr, new_rsp, new_cr3, new_kernel_stack_top, my_idx)) => {
+            YIELD_SWITCH_COUNT.fetch_add(1, Ordering::Relaxed);
+
             // ユーザープロセスへの切り替え時は TSS rsp0 を更新する。
             // ユーザーモードで割り込み/システムコールが発生したとき、
             // CPU は TSS rsp0 のアドレスをカーネルスタックとして使用する。
             // 各プロセスは独自のカーネルスタックを持つので、切り替え時に更新が必要。
             if let Some(kernel_stack_top) = new_kernel_stack_top {
                 unsafe {
-                    crate::gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+                    crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
                 }
             }
 
@@ -612,6 +698,15 @@ pub fn preempt() {
         // 現在のタイマーティック数を取得して、起床時刻に達した Sleeping タスクを
         // Ready に戻す。これによりタイマーティックごとにスリープの解除判定が行われる。
         let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+
+        // 割り込みハンドラが積んでおいた起床要求も反映する
+        sched.drain_wake_queue();
+
+        // 割り込まれた時点で走っていたタスクにこのティックを計上する。
+        // ロックが取れずにスキップした回は数えないので、あくまで目安の値になる。
+        let current = sched.current;
+        sched.tasks[current].cpu_ticks += 1;
+
         for task in sched.tasks.iter_mut() {
             if let TaskState::Sleeping(wake_at) = task.state {
                 if now >= wake_at {
@@ -620,24 +715,24 @@ pub fn preempt() {
             }
         }
 
-        let current = sched.current;
-        let num_tasks = sched.tasks.len();
-
         // タスクが 1 つ以下ならスイッチ不要
-        if num_tasks <= 1 {
+        if sched.tasks.len() <= 1 {
             return;
         }
 
-        // 次の Ready タスクをラウンドロビンで探す
-        let mut next = None;
-        for i in 1..=num_tasks {
-            let idx = (current + i) % num_tasks;
-            if sched.tasks[idx].state == TaskState::Ready {
-                next = Some(idx);
-                break;
+        // 優先度が PRIORITY_DEFAULT より高いタスクは、(priority - PRIORITY_DEFAULT + 1) ティックの
+        // タイムスライスを使い切るまで切り替えない
+        let task = &mut sched.tasks[current];
+        if task.state == TaskState::Running {
+            task.slice_used = task.slice_used.saturating_add(1);
+            if task.slice_used < task.priority.saturating_sub(PRIORITY_DEFAULT) + 1 {
+                return;
             }
         }
 
+        // 次の Ready タスクをラウンドロビンで探す
+        let next = sched.pick_next();
+
         match next {
             None => None, // 他に Ready タスクがない
             Some(next_idx) => {
@@ -646,7 +741,9 @@ pub fn preempt() {
                     sched.tasks[current].state = TaskState::Ready;
                 }
                 sched.tasks[next_idx].state = TaskState::Running;
+                sched.tasks[next_idx].slice_used = 0;
                 sched.current = next_idx;
+                crate::percpu::current().set_current_task_id(sched.tasks[next_idx].id);
 
                 let old_rsp_ptr =
                     &mut sched.tasks[current].context.rsp as *mut u64;
@@ -673,6 +770,8 @@ pub fn preempt() {
                 let (saved_rsp, saved_rbp) = crate::usermode::get_saved_usermode_context();
                 sched.tasks[current].exit_saved_rsp = saved_rsp;
                 sched.tasks[current].exit_saved_rbp = saved_rbp;
+                sched.tasks[current].held_locks = crate::lock_order::held();
+                crate::lock_order::set_held(sched.tasks[next_idx].held_locks);
 
                 Some((old_rsp_ptr, new_rsp, new_cr3, new_kernel_stack_top, current))
             }
@@ -688,7 +787,7 @@ pub fn preempt() {
         // 各プロセスは独自のカーネルスタックを持つので、切り替え時に更新が必要。
         if let Some(kernel_stack_top) = new_kernel_stack_top {
             unsafe {
-                crate::gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+                crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
             }
         }
 
@@ -714,13 +813,13 @@ pub fn preempt() {
 
 /// 現在のタスクを Sleeping 状態にする（yield は呼び出し元が行う）。
 ///
-/// futex_wait() のように「Sleeping に設定してから追加処理して yield」
-/// というパターンで使う。単純なスリープは sleep_ticks() を使うこと。
+/// park_until() が「Sleeping に設定してから条件を確かめ直して yield」するのに使う。
+/// 待つ側は sleep_until() / park_until() / park_with_timeout() を使うこと。
 ///
 /// # 引数
 /// - `wake_at`: 起床するタイマーティック数（TIMER_TICK_COUNT がこの値以上になったら Ready に戻る）。
 ///              u64::MAX を指定すると、wake_task() で明示的に起こされるまで無期限待ち。
-pub fn set_current_sleeping(wake_at: u64) {
+fn set_current_sleeping(wake_at: u64) {
     let mut sched = SCHEDULER.lock();
     let current = sched.current;
     sched.tasks[current].state = TaskState::Sleeping(wake_at);
@@ -728,16 +827,145 @@ pub fn set_current_sleeping(wake_at: u64) {
 
 /// 指定したタスクを起床させる（Sleeping → Ready）。
 ///
-/// futex_wake やスレッド join の通知で使用する。
+/// futex_wake やスレッド join の通知、NIC の受信割り込みなどで使用する。
 /// タスクが Sleeping 状態でなければ何もしない。
 ///
+/// 割り込みハンドラからも呼べる。割り込まれた側が SCHEDULER のロックを持っていると
+/// lock() で待てばデッドロックするので、ロックが取れなければ起床キュー（WAKE_QUEUE）に
+/// 積んで戻り、次に yield_now() / preempt() がロックを取ったときに反映する。
+/// どちらの場合もロックを待って回ることはない。
+///
 /// # 引数
 /// - `task_id`: 起床させるタスクの ID
 pub fn wake_task(task_id: u64) {
-    let mut sched = SCHEDULER.lock();
-    if let Some(task) = sched.tasks.iter_mut().find(|t| t.id == task_id) {
-        if matches!(task.state, TaskState::Sleeping(_)) {
-            task.state = TaskState::Ready;
+    match SCHEDULER.try_lock() {
+        Some(mut sched) => {
+            sched.drain_wake_queue();
+            sched.wake(task_id);
+        }
+        None => {
+            WAKES_QUEUED.fetch_add(1, Ordering::Relaxed);
+            WAKE_QUEUE.push(task_id);
+        }
+    }
+}
+
+// =================================================================
+// 割り込みからの起床キュー
+// =================================================================
+//
+// ロックを取れなかった wake_task() の要求をためておく、ロックなしのキュー。
+// 複数の CPU・割り込みハンドラが同時に積み（multi-producer）、
+// SCHEDULER のロックを持った側だけが取り出す（single-consumer）。
+//
+// スロットに「タスク ID + 1」を compare_exchange で書き込む（0 は空き）。
+// 同じタスクが既に積まれていれば何もしないので、重複して溜まることはない。
+// 起床は順番に意味がないので、取り出す順番は積んだ順でなくてよい。
+
+/// 起床キューのスロット数（同時に積める、別々のタスクの数）
+const WAKE_QUEUE_SLOTS: usize = 64;
+
+struct WakeQueue {
+    slots: [AtomicU64; WAKE_QUEUE_SLOTS],
+    /// スロットが足りずに積めなかった要求があった
+    overflowed: AtomicBool,
+}
+
+impl WakeQueue {
+    const fn new() -> Self {
+        Self {
+            slots: [const { AtomicU64::new(0) }; WAKE_QUEUE_SLOTS],
+            overflowed: AtomicBool::new(false),
+        }
+    }
+
+    fn push(&self, task_id: u64) {
+        let value = task_id + 1;
+        for slot in &self.slots {
+            match slot.compare_exchange(0, value, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => return,
+                Err(current) if current == value => return,
+                Err(_) => {}
+            }
+        }
+        self.overflowed.store(true, Ordering::Release);
+    }
+
+    /// 積まれているタスク ID を取り出して f に渡す。
+    /// 積めなかった要求があったなら true を返す。
+    fn drain(&self, mut f: impl FnMut(u64)) -> bool {
+        for slot in &self.slots {
+            let value = slot.swap(0, Ordering::AcqRel);
+            if value != 0 {
+                f(value - 1);
+            }
+        }
+        self.overflowed.swap(false, Ordering::AcqRel)
+    }
+}
+
+static WAKE_QUEUE: WakeQueue = WakeQueue::new();
+
+/// ロックが取れずに起床キューへ回した wake_task() の回数
+static WAKES_QUEUED: AtomicU64 = AtomicU64::new(0);
+
+/// 起床キューへ回した wake_task() の回数を返す
+pub fn wakes_queued() -> u64 {
+    WAKES_QUEUED.load(Ordering::Relaxed)
+}
+
+impl Scheduler {
+    /// task_id が Sleeping なら Ready にする
+    fn wake(&mut self, task_id: u64) {
+        if let Some(task) = self.tasks.iter_mut().find(|t| t.id == task_id) {
+            if matches!(task.state, TaskState::Sleeping(_)) {
+                task.state = TaskState::Ready;
+            }
+        }
+    }
+
+    /// 次に走らせる Ready のタスクを、current の次からラウンドロビンで選ぶ。
+    ///
+    /// 優先度が PRIORITY_DEFAULT より低いタスクは、順番が来ても
+    /// (PRIORITY_DEFAULT - priority) 回は見送り、その次に走らせる。
+    /// 見送った結果ほかに Ready のタスクがなければ、見送らずにそれを選ぶ
+    /// （CPU を空けておく意味はないし、低い優先度でも飢えさせない）。
+    fn pick_next(&mut self) -> Option<usize> {
+        let current = self.current;
+        let num_tasks = self.tasks.len();
+        let mut fallback = None;
+        for i in 1..=num_tasks {
+            let idx = (current + i) % num_tasks;
+            let task = &mut self.tasks[idx];
+            if task.state != TaskState::Ready {
+                continue;
+            }
+            if task.skipped < PRIORITY_DEFAULT.saturating_sub(task.priority) {
+                task.skipped += 1;
+                fallback.get_or_insert(idx);
+                continue;
+            }
+            task.skipped = 0;
+            return Some(idx);
+        }
+        if let Some(idx) = fallback {
+            self.tasks[idx].skipped = 0;
+        }
+        fallback
+    }
+
+    /// 起床キューに積まれた要求を反映する。ロックを取ったらタスクを選ぶ前に呼ぶ。
+    fn drain_wake_queue(&mut self) {
+        let overflowed = WAKE_QUEUE.drain(|id| self.wake(id));
+        if overflowed {
+            // どのタスクの要求を落としたか分からないので、起こされるのを待っている
+            // （期限なしで眠っている）タスクをすべて起こす。待つ側は起きたら条件を
+            // 確かめ直すので、余計に起こしても眠り直すだけで済む。
+            for task in self.tasks.iter_mut() {
+                if task.state == TaskState::Sleeping(u64::MAX) {
+                    task.state = TaskState::Ready;
+                }
+            }
         }
     }
 }
@@ -745,35 +973,100 @@ pub fn wake_task(task_id: u64) {
 /// 現在のタスクを指定ティック数だけスリープさせる。
 ///
 /// PIT は約 18.2 Hz で発火するので、1 ティック ≈ 55ms。
-/// タスクを Sleeping 状態にして yield_now() で他のタスクに切り替える。
 /// preempt() のタイマーティックごとの起床チェックで、
 /// 指定ティック数が経過したら自動的に Ready に戻される。
 pub fn sleep_ticks(ticks: u64) {
-    let wake_at = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) + ticks;
-
-    {
-        let mut sched = SCHEDULER.lock();
-        let current = sched.current;
-        sched.tasks[current].state = TaskState::Sleeping(wake_at);
-    }
-
-    // 他のタスクに切り替える。
-    // このタスクが Ready に戻されるのは preempt() の起床チェックで wake_at に達したとき。
-    yield_now();
+    sleep_until(crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) + ticks);
 }
 
 /// 現在のタスクを指定ミリ秒だけスリープさせる。
 ///
-/// PIT のデフォルト周波数は約 18.2 Hz（≈ 55ms 間隔）なので、
 /// ミリ秒をティック数に変換してから sleep_ticks() を呼ぶ。
 /// 精度は PIT の周波数に依存する（最大 55ms の誤差がある）。
 pub fn sleep_ms(ms: u64) {
-    // PIT のデフォルト周波数: 1193182 Hz / 65536 ≈ 18.2065 Hz
-    // 1 ティック ≈ 54.925 ms
-    // ticks = ms / 54.925 ≈ ms * 182 / 10000
-    // 最低でも 1 ティックはスリープする（0 だと即座に起きてしまう）
-    let ticks = (ms * 182 / 10000).max(1);
-    sleep_ticks(ticks);
+    sleep_ticks(ms_to_ticks(ms));
+}
+
+// =================================================================
+// 期限つきの待機
+// =================================================================
+//
+// 「Sleeping にして yield し、起きたら期限を過ぎたか確かめる」という形の待ちを
+// ここにまとめる。ミリ秒 → ティックの変換や期限の判定を呼び出し側で書かずに済む。
+
+/// 眠っていたタスクが起きた理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeReason {
+    /// 期限より前に起きた（wake_task() で起こされた、または眠らずに済んだ）
+    Woken,
+    /// 期限に達した
+    TimedOut,
+}
+
+/// ミリ秒をタイマーティック数に変換する（最低 1 ティック）。
+///
+/// PIT のデフォルト周波数: 1193182 Hz / 65536 ≈ 18.2065 Hz
+/// 1 ティック ≈ 54.925 ms なので ticks = ms / 54.925 ≈ ms * 182 / 10000。
+/// 0 ティックだと即座に起きてしまうので、最低でも 1 ティックにする。
+pub fn ms_to_ticks(ms: u64) -> u64 {
+    (ms * 182 / 10000).max(1)
+}
+
+/// 今から timeout_ms 後の期限（タイマーティック）を返す。
+/// timeout_ms == 0 は無期限（u64::MAX）として扱う。
+pub fn deadline_after_ms(timeout_ms: u64) -> u64 {
+    if timeout_ms == 0 {
+        return u64::MAX;
+    }
+    crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) + ms_to_ticks(timeout_ms)
+}
+
+/// 期限に達したか。u64::MAX（無期限）なら常に false。
+pub fn deadline_reached(deadline: u64) -> bool {
+    deadline != u64::MAX
+        && crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) >= deadline
+}
+
+/// 期限（タイマーティック）まで、または wake_task() で起こされるまで眠る。
+/// deadline に u64::MAX を渡すと、起こされるまで無期限に眠る。
+pub fn sleep_until(deadline: u64) -> WakeReason {
+    park_until(deadline, || false)
+}
+
+/// timeout_ms ミリ秒経つか、wake_task() で起こされるまで眠る。
+/// timeout_ms == 0 は無期限。
+pub fn park_with_timeout(timeout_ms: u64) -> WakeReason {
+    sleep_until(deadline_after_ms(timeout_ms))
+}
+
+/// sleep_until() と同じだが、Sleeping にした直後に ready() を呼び、
+/// true なら眠らずに Woken を返す。
+///
+/// 「待ち行列に登録 → 条件を確認 → 眠る」の間に wake_task() が来ると、
+/// まだ Running なので起床が空振りして取りこぼす。Sleeping にしてから
+/// もう一度条件を確かめれば、その後の wake_task() は必ず Ready に戻してくれる。
+pub fn park_until(deadline: u64, ready: impl FnOnce() -> bool) -> WakeReason {
+    set_current_sleeping(deadline);
+    if ready() {
+        let mut sched = SCHEDULER.lock();
+        let current = sched.current;
+        sched.tasks[current].state = TaskState::Running;
+        return WakeReason::Woken;
+    }
+    // 他に Ready のタスクがないと、yield_now() は hlt で次の割り込みを待っただけで
+    // Sleeping のまま戻ってくる。起こされるか期限が来て Sleeping でなくなるまで待つ。
+    loop {
+        yield_now();
+        let sched = SCHEDULER.lock();
+        if !matches!(sched.tasks[sched.current].state, TaskState::Sleeping(_)) {
+            break;
+        }
+    }
+    if deadline_reached(deadline) {
+        WakeReason::TimedOut
+    } else {
+        WakeReason::Woken
+    }
 }
 
 /// Ready タスクがなくなるまで HLT で待機する（yield に依存しない待ち）
@@ -806,6 +1099,7 @@ pub fn task_list() -> Vec<TaskInfo> {
             name: t.name.clone(),
             state: t.state,
             is_user_process: t.is_user,
+            cpu_ticks: t.cpu_ticks,
         })
         .collect()
 }
@@ -834,10 +1128,35 @@ pub fn process_mem_list() -> Vec<ProcessMemInfo> {
         .collect()
 }
 
+/// 指定したタスクの詳細情報を取得する（/proc/<pid>/ 用）。
+pub fn task_detail(task_id: u64) -> Option<TaskDetail> {
+    let sched = SCHEDULER.lock();
+    let t = sched.tasks.iter().find(|t| t.id == task_id)?;
+    Some(TaskDetail {
+        id: t.id,
+        name: t.name.clone(),
+        state: t.state,
+        is_user_process: t.is_user,
+        parent_id: t.parent_id,
+        process_leader_id: t.process_leader_id,
+        user_frames: t.user_process_info
+            .as_ref()
+            .map(|info| info.process.allocated_frames.len())
+            .unwrap_or(0),
+        mmap_frames: t.user_process_info
+            .as_ref()
+            .map(|info| info.process.mmap_frames)
+            .unwrap_or(0),
+        cmdline: t.cmdline.clone(),
+    })
+}
+
 /// 現在実行中のタスクIDを取得する
+///
+/// scheduler が切り替えのたびに CPU ごとのブロックへ書いておいた値を読むので、
+/// SCHEDULER のロックは取らない。
 pub fn current_task_id() -> u64 {
-    let sched = SCHEDULER.lock();
-    sched.tasks[sched.current].id
+    crate::percpu::current().current_task_id()
 }
 
 /// 現在のタスクの stdin リダイレクトハンドルを取得する
@@ -856,11 +1175,12 @@ pub fn current_stdout_handle() -> Option<crate::handle::Handle> {
     sched.tasks[sched.current].stdout_handle
 }
 
-/// 現在のタスクを参照して処理する（デバッグ用）
-pub fn with_current_task<F: FnOnce(&Task)>(f: F) {
-    let sched = SCHEDULER.lock();
-    let task = &sched.tasks[sched.current];
-    f(task);
+/// 現在のタスクを参照して処理する（例外ハンドラでのデバッグ表示用）
+///
+/// SCHEDULER を持ったまま例外が起きても固まらないよう try_lock で取る。取れなければ None。
+pub fn try_with_current_task<R>(f: impl FnOnce(&Task) -> R) -> Option<R> {
+    let sched = SCHEDULER.try_lock()?;
+    Some(f(&sched.tasks[sched.current]))
 }
 
 /// 現在のタスクの環境変数を取得する。
@@ -918,6 +1238,45 @@ pub fn set_env_var(key: &str, value: &str) {
     }
 }
 
+/// 現在のタスクの環境変数を削除する。無ければ何もしない。
+pub fn remove_env_var(key: &str) {
+    let mut sched = SCHEDULER.lock();
+    let current = sched.current;
+    sched.tasks[current].env_vars.retain(|(k, _)| k != key);
+}
+
+/// 現在のタスクのカレントディレクトリを返す
+pub fn current_cwd() -> String {
+    let sched = SCHEDULER.lock();
+    sched.tasks[sched.current].cwd.clone()
+}
+
+/// 現在のプロセスのカレントディレクトリを変える。
+///
+/// cwd は POSIX と同じくプロセス単位なので、同じプロセスのスレッドすべてを書き換える。
+/// `path` は normalize_path 済みのディレクトリの絶対パスであること（SYS_CHDIR が確かめる）。
+pub fn set_process_cwd(path: &str) {
+    let mut sched = SCHEDULER.lock();
+    let current = sched.current;
+    let process = sched.tasks[current].process_leader_id.unwrap_or(sched.tasks[current].id);
+    for task in sched.tasks.iter_mut() {
+        if task.process_leader_id.unwrap_or(task.id) == process {
+            task.cwd = String::from(path);
+        }
+    }
+}
+
+/// 現在のタスクの環境変数を "KEY=VALUE" 形式で返す。
+///
+/// ユーザープロセスのスタックに置く envp の材料。spawn_user() を通らずに
+/// プロセスを作る経路（シェルの run コマンド）でも、親の環境を渡すために使う。
+pub fn env_strings() -> Vec<String> {
+    let sched = SCHEDULER.lock();
+    sched.tasks[sched.current].env_vars.iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect()
+}
+
 /// 現在のタスクの UserProcess に mmap で確保した物理フレームを追加する。
 /// プロセス終了時に destroy_user_process() がこれらのフレームも解放する。
 pub fn add_mmap_frames_to_current(frames: &[x86_64::structures::paging::PhysFrame<x86_64::structures::paging::Size4KiB>]) {
@@ -926,6 +1285,7 @@ pub fn add_mmap_frames_to_current(frames: &[x86_64::structures::paging::PhysFram
     let task = &mut sched.tasks[current];
     if let Some(ref mut info) = task.user_process_info {
         info.process.allocated_frames.extend_from_slice(frames);
+        info.process.mmap_frames += frames.len();
     }
 }
 
@@ -936,9 +1296,13 @@ pub fn remove_mmap_frames_from_current(frames_to_remove: &[x86_64::structures::p
     let current = sched.current;
     let task = &mut sched.tasks[current];
     if let Some(ref mut info) = task.user_process_info {
+        let before = info.process.allocated_frames.len();
         info.process.allocated_frames.retain(|f| {
             !frames_to_remove.iter().any(|r| r.start_address() == f.start_address())
         });
+        // munmap できるのは mmap 領域だけなので、取り除けた分はすべて mmap で確保したもの
+        let removed = before - info.process.allocated_frames.len();
+        info.process.mmap_frames = info.process.mmap_frames.saturating_sub(removed);
     }
 }
 
@@ -964,6 +1328,45 @@ pub fn find_free_vma_region(size: u64, base: u64, limit: u64) -> Option<u64> {
     }
 }
 
+/// 現在のタスクで [start, end) がどの VMA とも重なっていないか。
+/// ユーザープロセスでなければ false。
+pub fn vma_range_is_free_in_current(start: u64, end: u64) -> bool {
+    let sched = SCHEDULER.lock();
+    let current = sched.current;
+    let task = &sched.tasks[current];
+    if let Some(ref info) = task.user_process_info {
+        info.process.vma_list.is_range_free(start, end)
+    } else {
+        false
+    }
+}
+
+/// 現在のタスクで [start, end) が隙間なく VMA で覆われているか（mprotect の対象確認用）。
+/// ユーザープロセスでなければ false。
+pub fn vma_range_is_covered_in_current(start: u64, end: u64) -> bool {
+    let sched = SCHEDULER.lock();
+    let current = sched.current;
+    let task = &sched.tasks[current];
+    if let Some(ref info) = task.user_process_info {
+        info.process.vma_list.covers(start, end)
+    } else {
+        false
+    }
+}
+
+/// 現在のタスクの [start, end) の VMA の権限を変更する（mprotect 用）。
+/// 範囲が VMA で覆われていない、またはユーザープロセスでなければ false。
+pub fn protect_vma_range_in_current(start: u64, end: u64, prot: crate::vma::VmaProt) -> bool {
+    let mut sched = SCHEDULER.lock();
+    let current = sched.current;
+    let task = &mut sched.tasks[current];
+    if let Some(ref mut info) = task.user_process_info {
+        info.process.vma_list.protect_range(start, end, prot)
+    } else {
+        false
+    }
+}
+
 /// 現在のタスクの VmaList に VMA を追加する。
 pub fn add_vma_to_current(vma: crate::vma::Vma) -> Result<(), &'static str> {
     let mut sched = SCHEDULER.lock();
@@ -1303,16 +1706,17 @@ pub fn kill_all_threads_of_leader(leader_id: u64) {
         if task.process_leader_id == Some(leader_id)
             && task.state != TaskState::Finished
         {
-            crate::serial_println!("[scheduler] killing thread {} (leader {} exiting)", task.id, leader_id);
+            crate::klog!(Info, Sched, "killing thread {} (leader {} exiting)", task.id, leader_id);
             task.state = TaskState::Finished;
             task.exit_code = -1; // 強制終了
         }
     }
 }
 
-/// プロセスの終了コードを設定する（SYS_EXIT から呼ばれる）
-/// 将来的に exit(code) システムコールで使用される
-#[allow(dead_code)]
+/// 現在のタスクの終了コードを設定する（SYS_EXIT / SYS_THREAD_EXIT から呼ばれる）
+///
+/// exit_usermode() で Ring 3 から戻る直前に呼ぶことで、
+/// タスクが Finished になった時点で wait() が終了コードを回収できる。
 pub fn set_exit_code(exit_code: i32) {
     let mut sched = SCHEDULER.lock();
     let current = sched.current;
@@ -1325,7 +1729,7 @@ pub fn set_exit_code(exit_code: i32) {
 /// 自分自身を kill することはできない（SYS_EXIT を使うべき）。
 /// 既に Finished のタスクを kill しようとした場合もエラーになる。
 pub fn kill_task(task_id: u64) -> Result<(), &'static str> {
-    let user_process_info = {
+    let (user_process_info, redirect_handles) = {
         let mut sched = SCHEDULER.lock();
         let current_id = sched.tasks[sched.current].id;
 
@@ -1351,13 +1755,19 @@ pub fn kill_task(task_id: u64) -> Result<(), &'static str> {
         task.exit_code = -1; // 強制終了を示す
 
         // ユーザープロセスのリソースを回収（ページテーブル等）
-        task.user_process_info.take()
+        (task.user_process_info.take(), take_redirect_handles(task))
     };
 
     // キーボードフォーカスを持っていたら自動解放する
     crate::console::release_keyboard(task_id);
+    // ダブルバッファモードやクリップ領域を設定したまま終了したら解除する
+    crate::framebuffer::release_task(task_id);
     // IPC キューをクリーンアップ（未読メッセージを解放）
     crate::ipc::cleanup_task(task_id);
+    // 作ったタイマーを止める
+    crate::timer::cleanup_task(task_id);
+    // stdout のパイプを閉じて、読んでいる親に EOF を届ける
+    close_redirect_handles(redirect_handles);
 
     // ロック外でリソースを解放する
     if let Some(info) = user_process_info {
@@ -1367,13 +1777,139 @@ pub fn kill_task(task_id: u64) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// 終了するタスクのリダイレクトされた stdin/stdout ハンドルを取り出す。
+///
+/// ハンドルを持っているのはプロセス（リーダー）だけ。スレッドは親のハンドルを
+/// 借りて使っているだけなので、スレッドが終わっても閉じない。
+fn take_redirect_handles(task: &mut Task) -> [Option<crate::handle::Handle>; 2] {
+    if task.process_leader_id.is_some() {
+        return [None, None];
+    }
+    [task.stdin_handle.take(), task.stdout_handle.take()]
+}
+
+/// take_redirect_handles() で取り出したハンドルを閉じる（スケジューラのロックの外で呼ぶ）。
+///
+/// stdout の write end が閉じると、パイプを読んでいる親は残りを読み切った後に EOF を受け取る。
+fn close_redirect_handles(handles: [Option<crate::handle::Handle>; 2]) {
+    for h in handles.iter().flatten() {
+        let _ = crate::handle::close(h);
+    }
+}
+
+/// タスクの優先度を返す
+pub fn get_priority(task_id: u64) -> Option<u8> {
+    let sched = SCHEDULER.lock();
+    sched
+        .tasks
+        .iter()
+        .find(|t| t.id == task_id && t.state != TaskState::Finished)
+        .map(|t| t.priority)
+}
+
+/// caller が task_id の優先度を priority にする。
+///
+/// カーネルタスクからはどのタスクでも変えられる。ユーザータスクから変えられるのは
+/// 自分・同じプロセスのスレッド・自分の子だけで、自分の優先度より高くはできない
+/// （子に優先度を上げさせて CPU を取り合う抜け道を作らないため）。
+pub fn set_priority(caller: u64, task_id: u64, priority: u8) -> Result<(), &'static str> {
+    if priority > PRIORITY_MAX {
+        return Err("invalid priority");
+    }
+    let mut sched = SCHEDULER.lock();
+    let caller_task = sched.tasks.iter().find(|t| t.id == caller).ok_or("task not found")?;
+    let caller_is_user = caller_task.is_user;
+    let caller_priority = caller_task.priority;
+    let caller_process = caller_task.process_leader_id.unwrap_or(caller);
+
+    let task = sched
+        .tasks
+        .iter_mut()
+        .find(|t| t.id == task_id && t.state != TaskState::Finished)
+        .ok_or("task not found")?;
+    if caller_is_user {
+        let same_process = task.process_leader_id.unwrap_or(task.id) == caller_process;
+        let child = task.parent_id.is_some_and(|p| p == caller || p == caller_process);
+        if !(same_process || child) || priority > caller_priority {
+            return Err("not permitted");
+        }
+    }
+    task.priority = priority;
+    Ok(())
+}
+
+/// 現在のタスクの TLS ポインタを返す（未設定なら 0）
+pub fn current_tls_pointer() -> u64 {
+    let sched = SCHEDULER.lock();
+    sched.tasks[sched.current].tls_pointer
+}
+
+/// 現在のタスクの TLS ポインタを設定する。
+/// スレッドごとの値なので、同じプロセスのほかのスレッドには影響しない。
+pub fn set_current_tls_pointer(pointer: u64) {
+    let mut sched = SCHEDULER.lock();
+    let current = sched.current;
+    sched.tasks[current].tls_pointer = pointer;
+}
+
+/// 指定したタスクにシグナルを送る（kill の拡張版）
+///
+/// - SIG_KILL: kill_task() と同じく即座に強制終了する
+/// - SIG_TERM: 保留フラグを立てるだけでタスクは殺さない。
+///   スリープ中なら起こして、次のスケジューリングで
+///   SYS_SIGPENDING をポーリングできるようにする。
+///
+/// SIG_TERM は自分自身に送ってもよい（フラグが立つだけなので安全）。
+pub fn send_signal(task_id: u64, signal: u64) -> Result<(), &'static str> {
+    match signal {
+        sabos_syscall::SIG_KILL => kill_task(task_id),
+        sabos_syscall::SIG_TERM => {
+            let mut sched = SCHEDULER.lock();
+            let task = sched
+                .tasks
+                .iter_mut()
+                .find(|t| t.id == task_id)
+                .ok_or("task not found")?;
+            if task.state == TaskState::Finished {
+                return Err("task already finished");
+            }
+            task.pending_signals |= 1 << signal;
+            if matches!(task.state, TaskState::Sleeping(_)) {
+                task.state = TaskState::Ready;
+            }
+            Ok(())
+        }
+        _ => Err("invalid signal"),
+    }
+}
+
+/// 現在のタスクの保留中シグナルを取り出してクリアする（SYS_SIGPENDING 用）
+///
+/// 戻り値はビットマスク（ビット n = シグナル番号 n）。
+/// 取り出した時点でクリアするので、同じシグナルを二重に処理しない。
+pub fn take_pending_signals() -> u64 {
+    let mut sched = SCHEDULER.lock();
+    let current = sched.current;
+    core::mem::take(&mut sched.tasks[current].pending_signals)
+}
+
+/// 指定タスクの保留中シグナルをクリアせずに覗く（テスト・デバッグ用）
+pub fn peek_pending_signals(task_id: u64) -> Option<u64> {
+    let sched = SCHEDULER.lock();
+    sched
+        .tasks
+        .iter()
+        .find(|t| t.id == task_id)
+        .map(|t| t.pending_signals)
+}
+
 /// ユーザータスクが例外で落ちたときに強制終了させる。
 ///
 /// ページフォルトなどの例外ハンドラから呼ぶ前提で、
 /// 例外が起きたタスクを Finished にし、他のタスクへ切り替える。
 /// 割り込みハンドラ内で使うため、割り込みの有効/無効は操作しない。
 pub fn abort_current_user_task_from_exception() -> ! {
-    let (switch_info, user_process_info, task_id) = {
+    let (switch_info, user_process_info, task_id, redirect_handles) = {
         let mut sched = SCHEDULER.lock();
         let current = sched.current;
 
@@ -1384,6 +1920,7 @@ pub fn abort_current_user_task_from_exception() -> ! {
 
         // ユーザープロセス情報を取り出して後で解放する
         let user_process_info = sched.tasks[current].user_process_info.take();
+        let redirect_handles = take_redirect_handles(&mut sched.tasks[current]);
 
         // 次の Ready タスクを探す
         let num_tasks = sched.tasks.len();
@@ -1414,6 +1951,7 @@ pub fn abort_current_user_task_from_exception() -> ! {
         let switch_info = next_idx.map(|next_idx| {
             sched.tasks[next_idx].state = TaskState::Running;
             sched.current = next_idx;
+            crate::percpu::current().set_current_task_id(sched.tasks[next_idx].id);
 
             let old_rsp_ptr = &mut sched.tasks[current].context.rsp as *mut u64;
             let new_rsp = sched.tasks[next_idx].context.rsp;
@@ -1437,13 +1975,19 @@ pub fn abort_current_user_task_from_exception() -> ! {
             (old_rsp_ptr, new_rsp, new_cr3, new_kernel_stack_top)
         });
 
-        (switch_info, user_process_info, task_id)
+        (switch_info, user_process_info, task_id, redirect_handles)
     };
 
     // キーボードフォーカスを持っていたら自動解放する
     crate::console::release_keyboard(task_id);
+    // ダブルバッファモードやクリップ領域を設定したまま終了したら解除する
+    crate::framebuffer::release_task(task_id);
     // IPC キューをクリーンアップ（未読メッセージを解放）
     crate::ipc::cleanup_task(task_id);
+    // 作ったタイマーを止める
+    crate::timer::cleanup_task(task_id);
+    // stdout のパイプを閉じて、読んでいる親に EOF を届ける
+    close_redirect_handles(redirect_handles);
 
     // ユーザープロセスのリソースを解放
     if let Some(info) = user_process_info {
@@ -1453,7 +1997,7 @@ pub fn abort_current_user_task_from_exception() -> ! {
     if let Some((old_rsp_ptr, new_rsp, new_cr3, new_kernel_stack_top)) = switch_info {
         if let Some(kernel_stack_top) = new_kernel_stack_top {
             unsafe {
-                crate::gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+                crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
             }
         }
         unsafe {
@@ -1525,7 +2069,7 @@ global_asm!(
 /// そのグループに属するスレッドを全て終了させてからアドレス空間を破棄する。
 #[unsafe(no_mangle)]
 extern "C" fn user_task_exit_handler() {
-    let (user_process_info, task_id, is_leader, stdin_handle, stdout_handle) = {
+    let (user_process_info, task_id, is_leader, redirect_handles) = {
         let mut sched = SCHEDULER.lock();
         let current = sched.current;
         sched.tasks[current].state = TaskState::Finished;
@@ -1536,11 +2080,9 @@ extern "C" fn user_task_exit_handler() {
             && sched.tasks[current].user_process_info.is_some();
         // リダイレクトされた stdin/stdout ハンドルを取り出す（プロセス終了時にクローズするため）
         // パイプの write end を閉じないと、親プロセスが EOF を受け取れない
-        let stdin_handle = sched.tasks[current].stdin_handle.take();
-        let stdout_handle = sched.tasks[current].stdout_handle.take();
+        let redirect_handles = take_redirect_handles(&mut sched.tasks[current]);
         // ユーザープロセス情報を取り出す（プロセス破棄のため）
-        (sched.tasks[current].user_process_info.take(), task_id, is_leader,
-         stdin_handle, stdout_handle)
+        (sched.tasks[current].user_process_info.take(), task_id, is_leader, redirect_handles)
     };
 
     // プロセスリーダーなら、所属スレッドを全て Finished にする。
@@ -1552,17 +2094,16 @@ extern "C" fn user_task_exit_handler() {
 
     // キーボードフォーカスを持っていたら自動解放する
     crate::console::release_keyboard(task_id);
+    // ダブルバッファモードやクリップ領域を設定したまま終了したら解除する
+    crate::framebuffer::release_task(task_id);
     // IPC キューをクリーンアップ（未読メッセージを解放）
     crate::ipc::cleanup_task(task_id);
+    // 作ったタイマーを止める
+    crate::timer::cleanup_task(task_id);
 
     // リダイレクトされた stdin/stdout パイプハンドルを閉じる。
     // stdout の write end を閉じることで、親プロセスの read が EOF を受け取れるようになる。
-    if let Some(ref h) = stdin_handle {
-        let _ = crate::handle::close(h);
-    }
-    if let Some(ref h) = stdout_handle {
-        let _ = crate::handle::close(h);
-    }
+    close_redirect_handles(redirect_handles);
 
     // ユーザープロセスのリソースを解放
     if let Some(info) = user_process_info {
@@ -1599,12 +2140,12 @@ extern "C" fn user_task_entry_wrapper(task_id: u64) {
                      info.argc, info.argv_addr, info.envp_addr)
                 } else {
                     // ユーザープロセス情報がない → エラー
-                    crate::serial_println!("[scheduler] ERROR: task {} has no user_process_info", task_id);
+                    crate::klog!(Error, Sched, "task {} has no user_process_info", task_id);
                     return;
                 }
             }
             None => {
-                crate::serial_println!("[scheduler] ERROR: task {} not found", task_id);
+                crate::klog!(Error, Sched, "task {} not found", task_id);
                 return;
             }
         }
@@ -1617,7 +2158,7 @@ extern "C" fn user_task_entry_wrapper(task_id: u64) {
     // Ring 3 で int 0x80 が発生したとき、CPU は TSS rsp0 のアドレスに
     // スタックを切り替える。
     unsafe {
-        crate::gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+        crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
     }
 
     // セグメントセレクタを取得
@@ -1669,16 +2210,34 @@ extern "C" fn user_task_entry_wrapper(task_id: u64) {
 /// # 戻り値
 /// 成功時は Ok(タスクID)、失敗時は Err(エラーメッセージ)
 pub fn spawn_user(name: &str, elf_data: &[u8], args: &[&str]) -> Result<u64, &'static str> {
-    // 親プロセスの環境変数を取得してクローンする
+    spawn_user_with_env(name, elf_data, args, None, None)
+}
+
+/// 環境変数とカレントディレクトリを指定してユーザープロセスを起動する
+///
+/// spawn_user() と同じだが、`env_vars` / `cwd` が Some ならその値で子を始める
+/// （std の Command::env / env_clear / current_dir 用）。None なら親から継承する。
+/// `cwd` は normalize_path 済みのディレクトリの絶対パスであること。
+pub fn spawn_user_with_env(
+    name: &str,
+    elf_data: &[u8],
+    args: &[&str],
+    env_vars: Option<Vec<(String, String)>>,
+    cwd: Option<String>,
+) -> Result<u64, &'static str> {
+    // 親プロセスの環境変数とカレントディレクトリを取得してクローンする
     // スケジューラのロックを短く保つために、先にコピーを取得する
-    let parent_env_vars = {
+    let (parent_env_vars, parent_cwd) = {
         let sched = SCHEDULER.lock();
         if sched.tasks.is_empty() {
-            Vec::new()
+            (Vec::new(), String::from("/"))
         } else {
-            sched.tasks[sched.current].env_vars.clone()
+            let parent = &sched.tasks[sched.current];
+            (parent.env_vars.clone(), parent.cwd.clone())
         }
     };
+    let parent_env_vars = env_vars.unwrap_or(parent_env_vars);
+    let parent_cwd = cwd.unwrap_or(parent_cwd);
 
     // 引数が空の場合、プログラム名を args[0] として使う
     let default_args: Vec<&str>;
@@ -1781,14 +2340,23 @@ pub fn spawn_user(name: &str, elf_data: &[u8], args: &[&str]) -> Result<u64, &'s
         exit_code: 0,                 // 初期値
         reaped: false,                // wait() が呼ばれるまで未回収
         env_vars: parent_env_vars,    // 親プロセスの環境変数を継承
+        cwd: parent_cwd,
         process_leader_id: None,      // プロセスリーダー（メインスレッド）
         exit_saved_rsp: 0,
         exit_saved_rbp: 0,
+        held_locks: 0,
         stdin_handle: None,
         stdout_handle: None,
+        pending_signals: 0,
+        cmdline: actual_args.iter().map(|a| String::from(*a)).collect(),
+        cpu_ticks: 0,
+        priority: PRIORITY_DEFAULT,
+        slice_used: 0,
+        skipped: 0,
+        tls_pointer: 0,
     });
 
-    crate::serial_println!("[scheduler] spawned user task {} '{}' (entry: {:#x}, parent: {:?})", id, name, entry_point, parent_id);
+    crate::klog!(Info, Sched, "spawned user task {} '{}' (entry: {:#x}, parent: {:?})", id, name, entry_point, parent_id);
 
     Ok(id)
 }
@@ -1804,15 +2372,18 @@ pub fn spawn_user(name: &str, elf_data: &[u8], args: &[&str]) -> Result<u64, &'s
 /// - `args`: コマンドライン引数
 /// - `stdin_handle`: stdin のリダイレクト先（None = コンソール）
 /// - `stdout_handle`: stdout のリダイレクト先（None = コンソール）
+/// - `env_vars` / `cwd`: spawn_user_with_env() と同じ（None = 親から継承）
 pub fn spawn_user_redirected(
     name: &str,
     elf_data: &[u8],
     args: &[&str],
     stdin_handle: Option<crate::handle::Handle>,
     stdout_handle: Option<crate::handle::Handle>,
+    env_vars: Option<Vec<(String, String)>>,
+    cwd: Option<String>,
 ) -> Result<u64, &'static str> {
-    // まず通常の spawn_user でプロセスを作成
-    let task_id = spawn_user(name, elf_data, args)?;
+    // まず通常の spawn でプロセスを作成
+    let task_id = spawn_user_with_env(name, elf_data, args, env_vars, cwd)?;
 
     // stdin/stdout ハンドルを設定
     if stdin_handle.is_some() || stdout_handle.is_some() {
@@ -1895,7 +2466,7 @@ extern "C" fn thread_entry_wrapper(task_id: u64) {
         match idx {
             Some(i) => infos.remove(i).1,
             None => {
-                crate::serial_println!("[scheduler] ERROR: thread {} has no entry info", task_id);
+                crate::klog!(Error, Sched, "thread {} has no entry info", task_id);
                 return;
             }
         }
@@ -1908,7 +2479,7 @@ extern "C" fn thread_entry_wrapper(task_id: u64) {
     // Ring 3 で int 0x80 が発生したとき、CPU は TSS rsp0 のアドレスに
     // スタックを切り替える。
     unsafe {
-        crate::gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+        crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
     }
 
     // セグメントセレクタを取得
@@ -1956,8 +2527,12 @@ extern "C" fn thread_exit_handler() {
     };
     // キーボードフォーカスを持っていたら自動解放する
     crate::console::release_keyboard(task_id);
+    // ダブルバッファモードやクリップ領域を設定したまま終了したら解除する
+    crate::framebuffer::release_task(task_id);
     // IPC キューをクリーンアップ（未読メッセージを解放）
     crate::ipc::cleanup_task(task_id);
+    // 作ったタイマーを止める
+    crate::timer::cleanup_task(task_id);
     // 他のタスクに切り替える
     yield_now();
     // ここに戻ることはないはず（Finished タスクはスケジュールされない）
@@ -2031,6 +2606,7 @@ pub fn spawn_thread(entry_point: u64, user_stack_top: u64, arg: u64) -> Result<u
     // borrow checker 対策: push 前に必要な値を取り出す
     let parent_task_id = sched.tasks[current].id;
     let parent_env_vars = sched.tasks[current].env_vars.clone();
+    let parent_cwd = sched.tasks[current].cwd.clone();
     // スレッドは親タスクの stdin/stdout リダイレクトを継承する。
     // これにより spawn_redirected で起動したプロセス内のスレッドも
     // パイプ経由で出力をキャプチャできる。
@@ -2050,11 +2626,20 @@ pub fn spawn_thread(entry_point: u64, user_stack_top: u64, arg: u64) -> Result<u
         exit_code: 0,
         reaped: false,
         env_vars: parent_env_vars,
+        cwd: parent_cwd,
         process_leader_id: Some(leader_id),  // スレッドグループのリーダー
         exit_saved_rsp: 0,
         exit_saved_rbp: 0,
+        held_locks: 0,
         stdin_handle: parent_stdin,
         stdout_handle: parent_stdout,
+        pending_signals: 0,
+        cmdline: Vec::new(),
+        cpu_ticks: 0,
+        priority: PRIORITY_DEFAULT,
+        slice_used: 0,
+        skipped: 0,
+        tls_pointer: 0,
     });
 
     // カーネルスタックの所有権をリーダープロセスに移管する。
@@ -2082,7 +2667,7 @@ pub fn spawn_thread(entry_point: u64, user_stack_top: u64, arg: u64) -> Result<u
     // TODO: スレッド終了時にカーネルスタックを回収する仕組み
     core::mem::forget(kernel_stack);
 
-    crate::serial_println!("[scheduler] spawned thread {} (entry: {:#x}, leader: {})", id, entry_point, leader_id);
+    crate::klog!(Info, Sched, "spawned thread {} (entry: {:#x}, leader: {})", id, entry_point, leader_id);
 
     Ok(id)
 }
@@ -16,8 +16,13 @@
 //   0x3FB: ライン制御レジスタ（データビット数、パリティ等）
 //   0x3FC: モデム制御レジスタ
 //   0x3FD: ライン状態レジスタ（送信バッファが空かどうか等）
+//
+// 受信側は IRQ 4 の受信データあり割り込みで拾い、キーボードと同じ入力キューに流す。
+// これでディスプレイのない環境（-nographic / -display none）でも
+// シリアル端末からシェルを操作できる。
 
 use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use x86_64::instructions::port::Port;
@@ -25,6 +30,11 @@ use x86_64::instructions::port::Port;
 /// COM1 のベースアドレス。PC の標準的な設定。
 const COM1_BASE: u16 = 0x3F8;
 
+/// 割り込み有効レジスタ: 受信データあり割り込み
+const IER_RX_AVAILABLE: u8 = 0x01;
+/// ライン状態レジスタ: 受信データあり
+const LSR_DATA_READY: u8 = 0x01;
+
 /// シリアルポートを表す構造体。
 /// I/O ポートのベースアドレスを保持する。
 pub struct SerialPort {
@@ -77,6 +87,15 @@ impl SerialPort {
         }
     }
 
+    /// 受信データあり割り込みを有効にする。
+    /// init() の時点ではまだ割り込みハンドラや入力キュー（ヒープ）の準備ができていないので、
+    /// 受信だけ後から有効にする。
+    pub fn enable_rx_interrupt(&mut self) {
+        unsafe {
+            self.int_enable.write(IER_RX_AVAILABLE);
+        }
+    }
+
     /// 送信バッファが空になるまで待つ。
     /// ライン状態レジスタのビット5が「送信保持レジスタが空」を示す。
     fn wait_for_transmit_empty(&mut self) {
@@ -125,12 +144,66 @@ lazy_static! {
     };
 }
 
+/// 直前に受け取ったバイトが CR だったか。
+/// 端末によって Enter が CR / CR LF / LF のどれで届くか違うので、
+/// CR を改行として扱い、その直後の LF は捨てて改行を 1 つにまとめる。
+static LAST_WAS_CR: AtomicBool = AtomicBool::new(false);
+
+/// シリアルからの入力をシェルに流すか（input コマンドで切り替える）
+static INPUT_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// シリアル入力の有効 / 無効を切り替える
+pub fn set_input_enabled(enabled: bool) {
+    INPUT_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// シリアル入力が有効か
+pub fn input_enabled() -> bool {
+    INPUT_ENABLED.load(Ordering::Relaxed)
+}
+
+/// COM1 の受信割り込みを有効にする。ヒープと IDT の初期化後に呼ぶ。
+pub fn init_input() {
+    SERIAL1.lock().enable_rx_interrupt();
+}
+
+/// 受信 FIFO に溜まっているバイトをすべて読み出し、キー入力として流す。
+///
+/// IRQ 4 のハンドラと、割り込みが来ない環境向けにメインループからも呼ぶ。
+/// SERIAL1 のロックは取らない: 送信中（ロック保持中）に受信割り込みが来ても
+/// デッドロックしないよう、受信データとライン状態のレジスタを直接読む。
+/// 割り込み禁止の状態で呼ぶこと（割り込みハンドラとの取り合いを防ぐ）。
+pub fn poll_input() {
+    let mut line_status = Port::<u8>::new(COM1_BASE + 5);
+    let mut data = Port::<u8>::new(COM1_BASE);
+    while unsafe { line_status.read() } & LSR_DATA_READY != 0 {
+        let byte = unsafe { data.read() };
+        if !input_enabled() {
+            // 読み捨てないと FIFO が詰まって割り込みが来続ける
+            continue;
+        }
+        let was_cr = LAST_WAS_CR.swap(byte == b'\r', Ordering::Relaxed);
+        let c = match byte {
+            b'\r' => '\n',
+            b'\n' if was_cr => continue,
+            // 端末の Backspace は DEL (0x7F) を送ってくるので、キーボードと同じ 0x08 にそろえる
+            0x7F => '\x08',
+            // マルチバイト文字はまだ扱わない
+            0x80.. => continue,
+            _ => byte as char,
+        };
+        crate::interrupts::push_input_char(c);
+    }
+}
+
 /// シリアルポートに出力する内部関数。
 /// spin::Mutex で排他制御する。
 /// 割り込みハンドラは SERIAL1 のロックを取得しないため without_interrupts は不要。
 #[doc(hidden)]
 pub fn _serial_print(args: fmt::Arguments) {
     use core::fmt::Write;
+    // 後から /proc/kmsg や dmesg で読めるよう、カーネルログにも残す
+    crate::kmsg::append_fmt(args);
     SERIAL1
         .lock()
         .write_fmt(args)
@@ -11,7 +11,7 @@ use crate::scheduler;
 use crate::{kprint, kprintln};
 use x86_64::VirtAddr;
 
-use super::rdtsc;
+use super::{rdtsc, tsc_per_ms};
 
 impl super::Shell {
     /// help コマンド: 使えるコマンドの一覧を表示する。
@@ -22,30 +22,61 @@ impl super::Shell {
         kprintln!("  mem             - Show memory information");
         kprintln!("  page [addr]     - Show paging info / translate address");
         kprintln!("  ps              - Show task list");
+        kprintln!("  kill <pid>      - Kill a task");
+        kprintln!("  top             - Live task list with CPU usage (any key to quit)");
+        kprintln!("  dmesg [-c]      - Show kernel log (-c: clear after showing)");
+        kprintln!("  loglevel [<subsystem|all> <level>] - Show/set log level (off..trace)");
         kprintln!("  echo <text>     - Echo text back");
         kprintln!("  usermode        - Run a user-mode (Ring 3) program");
         kprintln!("  usertest        - Test memory protection (Ring 3 access violation)");
         kprintln!("  isolate         - Demo: process isolation with separate page tables");
         kprintln!("  elf             - Load and run an ELF binary in user mode");
-        kprintln!("  lspci           - List PCI devices");
+        kprintln!("  lspci [-v]      - List PCI devices (-v: show BARs)");
+        kprintln!("  ahci            - Show AHCI ports and SATA disks");
+        kprintln!("  nvme            - Show NVMe controllers and namespaces");
         kprintln!("  blkread [sect]  - Read a sector from virtio-blk disk");
         kprintln!("  blkwrite <sect> - Write test pattern to a sector (DANGEROUS!)");
         kprintln!("  ls [path]       - List files on FAT32 disk (e.g., ls /SUBDIR)");
         kprintln!("  cat <path>      - Display file contents (e.g., cat /SUBDIR/FILE.TXT)");
+        kprintln!("  hexdump [-w n] <path> [off] [len] - Hex dump part of a file (default: 256 bytes)");
         kprintln!("  write <name> <text> - Create a file with text content");
-        kprintln!("  rm <name>       - Delete a file");
+        kprintln!("  rm [-r] <name>  - Delete a file (-r: directory tree)");
+        kprintln!("  cp <src> <dst>  - Copy a file (dst may be a directory)");
+        kprintln!("  mv <src> <dst>  - Move/rename a file or directory");
+        kprintln!("  unzip <zip> <dir> - Extract a ZIP archive (stored/deflate) into dir");
+        kprintln!("  df              - Show disk space usage per mount");
+        kprintln!("  fsck [-r] [dev] - Check FAT32 consistency (-r: free lost chains)");
+        kprintln!("  replace [-g] [-i] <from> <to> <name> - Replace text in a file (-i: in place)");
         kprintln!("  run <path>      - Load and run ELF binary (e.g., run /SUBDIR/APP.ELF)");
         kprintln!("  spawn <path>    - Spawn ELF as background process (e.g., spawn HELLO.ELF)");
         kprintln!("  ip              - Show IP configuration");
         kprintln!("  linkstatus        - Show network link status");
+        kprintln!("  arp [-d ip | -s ip mac] - Show/edit ARP cache");
+        kprintln!("  route [add|del net/len [via gw]] - Show/edit routing table");
+        kprintln!("  ifconfig [ip mask [gw] [dns] | dhcp | mtu n] - Set a static IPv4 config, DHCP or MTU");
+        kprintln!("  nc <ip> <port>  - Connect via TCP and send/receive lines (ESC to quit)");
+        kprintln!("  http <host> [path] [-o FILE] - HTTP/1.0 GET and print (or save) the body");
         kprintln!("  selftest [target] - Run automated self-tests (target: all/base/core/fs/net/gui/service/list)");
         kprintln!("  ipc_bench [n]   - IPC round-trip benchmark (default: 1000 iterations)");
+        kprintln!("  blit_bench [n]  - 320x240 blit benchmark, native vs RGBX (default: 100)");
         kprintln!("  beep [freq] [ms] - Play beep sound (default: 440Hz 200ms)");
-        kprintln!("  panic           - Trigger a kernel panic (for testing)");
+        kprintln!("  play <path>     - Play a PCM WAV file (8/16-bit, mono/stereo)");
+        kprintln!("  panic [depth]   - Trigger a kernel panic (for testing)");
+        kprintln!("  addr2line <addr>... - Show the kernel function containing each address");
+        kprintln!("  time <command...> - Run a command and print how long it took");
+        kprintln!("  export KEY=VALUE... - Set shell environment variables (inherited by run/spawn)");
+        kprintln!("  clip [text]     - Show the clipboard, or set it to text");
+        kprintln!("  cmd1 | cmd2 ... - Pipe programs together (echo/cat first, grep last are built-in)");
+        kprintln!("  <program> [args] & - Run a program in the background as a job");
+        kprintln!("  jobs            - List background jobs");
+        kprintln!("  fg <n>          - Wait for job n (or %n) to finish");
+        kprintln!("  env             - List shell environment variables");
+        kprintln!("  unset KEY...    - Remove shell environment variables");
         kprintln!("  shutdown        - ACPI S5 shutdown (power off)");
         kprintln!("  reboot          - ACPI reboot (system reset)");
         kprintln!("  halt            - Halt the system (HLT loop, no power off)");
         kprintln!("  exit_qemu [code] - Exit QEMU via ISA debug exit (0=success, 1=failure)");
+        kprintln!("  input [both|kbd|serial] - Show/select shell input sources");
     }
 
     /// clear コマンド: 画面をクリアする。
@@ -53,16 +84,45 @@ impl super::Shell {
         framebuffer::clear_global_screen();
     }
 
+    /// input コマンド: シェルの入力元（キーボード / シリアル）を表示・切り替える。
+    ///
+    /// 既定は両方。ヘッドレスで動かすときに画面側のキー入力を止めたい場合や、
+    /// シリアルに流れてくるゴミを無視したい場合に片方だけにする。
+    pub(super) fn cmd_input(&self, args: &str) {
+        let (kbd, serial) = match args.trim() {
+            "" => (
+                crate::interrupts::keyboard_input_enabled(),
+                crate::serial::input_enabled(),
+            ),
+            "both" => (true, true),
+            "kbd" => (true, false),
+            "serial" => (false, true),
+            _ => {
+                kprintln!("Usage: input [both|kbd|serial]");
+                return;
+            }
+        };
+        crate::interrupts::set_keyboard_input_enabled(kbd);
+        crate::serial::set_input_enabled(serial);
+        let on_off = |b: bool| if b { "on" } else { "off" };
+        kprintln!("Input: keyboard={} serial={}", on_off(kbd), on_off(serial));
+    }
+
     /// mem コマンド: メモリ情報を表示する。
     pub(super) fn cmd_mem(&self) {
         let fa = FRAME_ALLOCATOR.lock();
         let total = fa.total_frames();
         let allocated = fa.allocated_count();
         let free = fa.free_frames();
+        drop(fa);
+        let heap = crate::allocator::heap_stats();
 
         kprintln!("Memory information:");
         kprintln!("  Usable:    {} MiB ({} pages)", self.usable_mib, self.usable_pages);
-        kprintln!("  Heap:      1024 KiB (static BSS allocation)");
+        kprintln!("  Heap:      {} KiB used / {} KiB",
+            heap.used_bytes / 1024, heap.total_bytes / 1024);
+        kprintln!("  Heap free: largest block {} KiB, {} free blocks",
+            heap.largest_free_block / 1024, heap.free_block_count);
         kprintln!("  Frames:    {} total, {} allocated, {} free",
             total, allocated, free);
         kprintln!("  Free mem:  {} KiB", free * 4);
@@ -124,20 +184,173 @@ impl super::Shell {
         kprintln!("  ID  STATE       TYPE    NAME");
         kprintln!("  --  ----------  ------  ----------");
         for t in &tasks {
-            let state_str = match t.state {
-                scheduler::TaskState::Ready => "Ready",
-                scheduler::TaskState::Running => "Running",
-                scheduler::TaskState::Sleeping(_) => "Sleeping",
-                scheduler::TaskState::Finished => "Finished",
-            };
             let type_str = if t.is_user_process { "user" } else { "kernel" };
-            kprintln!("  {:2}  {:10}  {:6}  {}", t.id, state_str, type_str, t.name);
+            kprintln!("  {:2}  {:10}  {:6}  {}", t.id, task_state_str(t.state), type_str, t.name);
         }
         // 終了済みタスクを除いた数を表示
         let active = tasks.iter().filter(|t| t.state != scheduler::TaskState::Finished).count();
         kprintln!("  Total: {} tasks ({} active)", tasks.len(), active);
     }
 
+    /// kill コマンド: 指定した ID のタスクを強制終了する。
+    ///
+    /// task 0（カーネルのメインタスク）と、シェル自身が動いているタスクは止めると
+    /// 戻ってこられなくなるので拒否する。
+    pub(super) fn cmd_kill(&self, args: &str) {
+        // kill %n はジョブ番号で指定する
+        let id = match args.trim().strip_prefix('%') {
+            Some(_) => {
+                let job = super::jobs::parse_job_spec(args)
+                    .and_then(|n| self.jobs.borrow().get(n).map(|j| j.task_id));
+                let Some(id) = job else {
+                    kprintln!("kill: {}: no such job", args.trim());
+                    return;
+                };
+                id
+            }
+            None => {
+                let Ok(id) = args.trim().parse::<u64>() else {
+                    kprintln!("Usage: kill <pid> | kill %<job>");
+                    return;
+                };
+                id
+            }
+        };
+        if id == 0 || id == scheduler::current_task_id() {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("kill: refusing to kill task {} (kernel/shell task)", id);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            return;
+        }
+        match scheduler::kill_task(id) {
+            Ok(()) => kprintln!("Killed task {}", id),
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("kill: task {}: {}", id, e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            }
+        }
+    }
+
+    /// top コマンド: タスク一覧を画面に出し直し続け、キーが押されたら抜ける。
+    ///
+    /// CPU% は前回の表示から経過したティックのうち、そのタスクが走っていた割合。
+    /// キー入力は interrupts::get_key() で見る。console の入力バッファは
+    /// カーネルシェルが動いている間も全文字を溜め込んでいて古い入力が残っているので、
+    /// console::read_input_nonblocking() だと即座に抜けてしまう。
+    pub(super) fn cmd_top(&self) {
+        const REFRESH_MS: u64 = 1000;
+        const POLL_MS: u64 = 100;
+
+        let mut prev: Vec<(u64, u64)> = Vec::new();
+        let mut prev_tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+        loop {
+            let now_tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+            let elapsed = now_tick.saturating_sub(prev_tick).max(1);
+            let tasks = scheduler::task_list();
+
+            framebuffer::clear_global_screen();
+            kprintln!("top - {} tasks, uptime {} ticks (press any key to quit)", tasks.len(), now_tick);
+            kprintln!("  ID  STATE       TYPE    CPU%  CPU(ms)  NAME");
+            kprintln!("  --  ----------  ------  ----  -------  ----------");
+            for t in tasks.iter().filter(|t| t.state != scheduler::TaskState::Finished) {
+                let before = prev.iter().find(|(id, _)| *id == t.id).map(|(_, c)| *c).unwrap_or(t.cpu_ticks);
+                let percent = (t.cpu_ticks - before) * 100 / elapsed;
+                let type_str = if t.is_user_process { "user" } else { "kernel" };
+                // 1 ティック ≈ 55ms（PIT のデフォルト周波数 18.2Hz）
+                kprintln!(
+                    "  {:2}  {:10}  {:6}  {:3}%  {:7}  {}",
+                    t.id, task_state_str(t.state), type_str, percent.min(100), t.cpu_ticks * 55, t.name
+                );
+            }
+            prev = tasks.iter().map(|t| (t.id, t.cpu_ticks)).collect();
+            prev_tick = now_tick;
+
+            for _ in 0..REFRESH_MS / POLL_MS {
+                scheduler::sleep_ms(POLL_MS);
+                if crate::interrupts::get_key().is_some() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// dmesg コマンド: カーネルログ（/proc/kmsg と同じ内容）を表示する。
+    ///
+    /// 使い方: dmesg [-c]
+    /// -c を付けると表示したあとログを消す。表示する内容そのものがまたログに
+    /// 積まれないよう、kprint! ではなく _print_console で出力する。
+    pub(super) fn cmd_dmesg(&self, args: &str) {
+        let log = match args.trim() {
+            "" => crate::kmsg::snapshot(),
+            "-c" => crate::kmsg::drain(),
+            _ => {
+                kprintln!("Usage: dmesg [-c]");
+                return;
+            }
+        };
+        // 古いログが上書きされた直後は先頭が UTF-8 の途中から始まることがある
+        let text = alloc::string::String::from_utf8_lossy(&log);
+        framebuffer::_print_console(format_args!("{}", text));
+        if !text.is_empty() && !text.ends_with('\n') {
+            framebuffer::_print_console(format_args!("\n"));
+        }
+        let dropped = crate::kmsg::dropped();
+        if dropped > 0 {
+            kprintln!("dmesg: {} messages were dropped", dropped);
+        }
+    }
+
+    /// loglevel コマンド: サブシステムごとのログレベルを表示・変更する。
+    ///
+    /// 使い方: loglevel                       （一覧を表示）
+    ///         loglevel <subsystem|all> <level>
+    /// level は off / error / warn / info / debug / trace。
+    pub(super) fn cmd_loglevel(&self, args: &str) {
+        use crate::klog::{self, LogLevel, Subsystem};
+
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        if parts.is_empty() {
+            for sub in Subsystem::ALL {
+                kprintln!("  {:8} {}", sub.name(), klog::level(sub).name());
+            }
+            return;
+        }
+        let usage = || {
+            kprintln!("Usage: loglevel [<subsystem|all> <level>]");
+            let subs: Vec<&str> = Subsystem::ALL.iter().map(|s| s.name()).collect();
+            let levels: Vec<&str> = LogLevel::ALL.iter().map(|l| l.name()).collect();
+            kprintln!("  subsystems: {}", subs.join(", "));
+            kprintln!("  levels: {}", levels.join(", "));
+        };
+        let (Some(target), Some(level_name), None) = (parts.first(), parts.get(1), parts.get(2)) else {
+            usage();
+            return;
+        };
+        let Some(level) = LogLevel::from_name(level_name) else {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("loglevel: unknown level '{}'", level_name);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            usage();
+            return;
+        };
+        let targets: Vec<Subsystem> = if target.eq_ignore_ascii_case("all") {
+            Subsystem::ALL.to_vec()
+        } else if let Some(sub) = Subsystem::from_name(target) {
+            alloc::vec![sub]
+        } else {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("loglevel: unknown subsystem '{}'", target);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            usage();
+            return;
+        };
+        for sub in targets {
+            let prev = klog::set_level(sub, level);
+            kprintln!("  {:8} {} -> {}", sub.name(), prev.name(), level.name());
+        }
+    }
+
     /// echo コマンド: 引数をそのまま出力する。
     pub(super) fn cmd_echo(&self, args: &str) {
         kprintln!("{}", args);
@@ -166,6 +379,8 @@ impl super::Shell {
     /// Ring 3 で USER_ACCESSIBLE のないアドレスにアクセスする。
     /// メモリ保護が正しく機能していれば、Page Fault が発生して
     /// ユーザープログラムが強制終了され、シェルに安全に戻るはず。
+    /// 続けてマップされていないアドレスへの書き込みも試し、
+    /// ページフォルトの診断表示が "user write to non-present page" になることを確かめる。
     pub(super) fn cmd_usertest(&self) {
         kprintln!("Testing user mode memory protection...");
         kprintln!("Attempting illegal kernel memory access from Ring 3...");
@@ -174,6 +389,21 @@ impl super::Shell {
         crate::usermode::run_in_usermode(&process, &program);
         kprintln!("Protection test passed! User program was terminated safely.");
         crate::usermode::destroy_user_process(process);
+
+        // ページフォルトの診断表示がアクセスの種類を正しく分類しているか
+        kprintln!("Attempting write to unmapped memory from Ring 3...");
+        let program = crate::usermode::get_user_illegal_write();
+        let process = crate::usermode::create_user_process(&program);
+        crate::usermode::run_in_usermode(&process, &program);
+        crate::usermode::destroy_user_process(process);
+        let log = crate::kmsg::snapshot();
+        if alloc::string::String::from_utf8_lossy(&log).contains("Page fault in user mode: user write to non-present page") {
+            kprintln!("Fault diagnostics OK: classified as user write to non-present page.");
+        } else {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("Fault diagnostics FAILED: expected \"user write to non-present page\".");
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+        }
     }
 
     /// isolate コマンド: プロセス分離のデモ。
@@ -322,7 +552,16 @@ impl super::Shell {
     /// PCI Configuration Space を走査し、見つかったデバイスの
     /// バス:デバイス.ファンクション番号、ベンダー ID、デバイス ID、
     /// クラスコードを一覧表示する。
-    pub(super) fn cmd_lspci(&self) {
+    /// `lspci -v` では各デバイスの BAR（ベースアドレス・サイズ・種類）も表示する。
+    pub(super) fn cmd_lspci(&self, args: &str) {
+        let verbose = match args.trim() {
+            "" => false,
+            "-v" => true,
+            _ => {
+                kprintln!("Usage: lspci [-v]");
+                return;
+            }
+        };
         let devices = crate::pci::enumerate_bus();
         kprintln!("PCI devices on bus 0:");
         kprintln!("  BDF       Vendor Device Class");
@@ -334,10 +573,106 @@ impl super::Shell {
                 dev.vendor_id, dev.device_id,
                 dev.class_code, dev.subclass, dev.prog_if,
             );
+            if verbose {
+                for (index, bar) in crate::pci::probe_all_bars(dev.bus, dev.device, dev.function) {
+                    let kind = match bar.kind {
+                        crate::pci::BarKind::Memory32 => "mem32",
+                        crate::pci::BarKind::Memory64 => "mem64",
+                        crate::pci::BarKind::Io => "io",
+                    };
+                    kprintln!(
+                        "      BAR{}: {:<5} base={:#x} size={:#x}{}",
+                        index, kind, bar.base, bar.size,
+                        if bar.prefetchable { " prefetchable" } else { "" },
+                    );
+                }
+            }
         }
         kprintln!("  Total: {} devices", devices.len());
     }
 
+    /// ahci コマンド: AHCI コントローラのポートと SATA ディスクを表示する。
+    ///
+    /// init() で走査した結果を、実装済みの全ポートについて表示する。
+    /// ディスクには SYS_BLOCK_READ などで使う blockdev の通し番号と
+    /// モデル名・容量を添える。
+    pub(super) fn cmd_ahci(&self) {
+        let controllers = crate::ahci::AHCI_CONTROLLERS.lock();
+        if controllers.is_empty() {
+            kprintln!("(no AHCI controllers)");
+            return;
+        }
+        let devs = crate::ahci::AHCI_DEVICES.lock();
+        // AHCI_DEVICES は走査順（コントローラ順 → ポート番号順）に並んでいる
+        let mut disk_index = 0;
+        for c in controllers.iter() {
+            let (bus, device, function) = c.bdf;
+            kprintln!(
+                "AHCI {:02x}:{:02x}.{} (ports implemented {:#010x})",
+                bus, device, function, c.ports_implemented
+            );
+            for p in &c.ports {
+                match p.status {
+                    crate::ahci::AhciPortStatus::Disk => {
+                        let block_index = crate::blockdev::find(crate::blockdev::BlockBackend::Ahci(disk_index));
+                        if let (Some(d), Some(block_index)) = (devs.get(disk_index), block_index) {
+                            kprintln!(
+                                "  port {:>2}: disk {} - {} ({} sectors, {} MiB)",
+                                p.port, block_index, d.model(),
+                                d.capacity(), d.capacity() * 512 / 1024 / 1024
+                            );
+                        }
+                        disk_index += 1;
+                    }
+                    crate::ahci::AhciPortStatus::NoDevice => kprintln!("  port {:>2}: (no device)", p.port),
+                    crate::ahci::AhciPortStatus::NonDisk(sig) => {
+                        kprintln!("  port {:>2}: non-disk device (sig={:#010x})", p.port, sig)
+                    }
+                    crate::ahci::AhciPortStatus::Failed => kprintln!("  port {:>2}: disk (init failed)", p.port),
+                }
+            }
+        }
+    }
+
+    /// nvme コマンド: NVMe コントローラとネームスペースの情報を表示する。
+    ///
+    /// 各コントローラに Identify Controller を発行してモデル名・シリアル番号・
+    /// ファームウェアを表示し、ネームスペース 1〜NN（最大 16 個）に Identify Namespace を
+    /// 発行して、使われているものの容量を表示する。
+    pub(super) fn cmd_nvme(&self) {
+        /// 一覧に出すネームスペースの上限（NN が大きいコントローラでも表示が長くなりすぎないように）
+        const MAX_LISTED_NAMESPACES: u32 = 16;
+
+        let mut devs = crate::nvme::NVME_DEVICES.lock();
+        if devs.is_empty() {
+            kprintln!("(no NVMe controllers)");
+            return;
+        }
+        for (i, dev) in devs.iter_mut().enumerate() {
+            let ctrl = match dev.identify_controller() {
+                Ok(ctrl) => ctrl,
+                Err(e) => {
+                    kprintln!("nvme{}: {}", i, e);
+                    continue;
+                }
+            };
+            kprintln!("nvme{}: {}", i, ctrl.model);
+            kprintln!("  Serial:     {}", ctrl.serial);
+            kprintln!("  Firmware:   {}", ctrl.firmware);
+            kprintln!("  Namespaces: {}", ctrl.namespace_count);
+            for nsid in 1..=ctrl.namespace_count.min(MAX_LISTED_NAMESPACES) {
+                // 使われていないネームスペースはエラーになるので飛ばす
+                if let Ok(ns) = dev.identify_namespace(nsid) {
+                    let size_mib = ns.size_lbas * ns.block_size as u64 / 1024 / 1024;
+                    kprintln!(
+                        "  ns{}: {} LBAs x {} B = {} MiB (capacity {} LBAs)",
+                        ns.nsid, ns.size_lbas, ns.block_size, size_mib, ns.capacity_lbas
+                    );
+                }
+            }
+        }
+    }
+
     /// blkread コマンド: virtio-blk ドライバでディスクの指定セクタを読み取り、
     /// 先頭の内容を 16 進ダンプで表示する。
     ///
@@ -372,23 +707,8 @@ impl super::Shell {
             Ok(()) => {
                 kprintln!("Sector {} (512 bytes):", sector);
                 // 先頭 256 バイトを 16 進ダンプで表示
-                for row in 0..16 {
-                    let offset = row * 16;
-                    kprint!("  {:04x}: ", offset);
-                    for col in 0..16 {
-                        kprint!("{:02x} ", buf[offset + col]);
-                    }
-                    // ASCII 表示
-                    kprint!(" |");
-                    for col in 0..16 {
-                        let b = buf[offset + col];
-                        if b >= 0x20 && b < 0x7F {
-                            kprint!("{}", b as char);
-                        } else {
-                            kprint!(".");
-                        }
-                    }
-                    kprintln!("|");
+                for line in hexdump_lines(&buf[..256], 0, HEXDUMP_DEFAULT_WIDTH) {
+                    kprintln!("  {}", line);
                 }
                 kprintln!("  ... ({} more bytes)", 512 - 256);
             }
@@ -524,6 +844,90 @@ impl super::Shell {
         }
     }
 
+    /// df コマンド: マウントごとの容量（全体・使用中・空き・使用率）を表示する。
+    pub(super) fn cmd_df(&self) {
+        for line in df_lines() {
+            kprintln!("{}", line);
+        }
+    }
+
+    /// fsck コマンド: FAT32 の整合性を調べる。
+    ///
+    /// 使い方: fsck [-r] [DEV]
+    /// DEV は blockdev レジストリの通し番号（省略時は 0 = disk.img）。
+    /// 既定では読むだけ。-r を付けると失われたチェーンを解放し、FSInfo の空き数を直す。
+    pub(super) fn cmd_fsck(&self, args: &str) {
+        let mut repair = false;
+        let mut dev_index = 0;
+        for arg in args.split_whitespace() {
+            match arg {
+                "-r" => repair = true,
+                _ => match arg.parse::<usize>() {
+                    Ok(n) => dev_index = n,
+                    Err(_) => {
+                        kprintln!("Usage: fsck [-r] [DEV]");
+                        return;
+                    }
+                },
+            }
+        }
+
+        let result = crate::fat32::Fat32::new_with_index(dev_index).and_then(|mut fs| {
+            let report = fs.check()?;
+            let freed = if repair && (!report.lost_chains.is_empty() || !report.fsinfo_matches()) {
+                Some(fs.repair(&report)?)
+            } else {
+                None
+            };
+            Ok((report, freed))
+        });
+        let (report, freed) = match result {
+            Ok(r) => r,
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("fsck: {}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                return;
+            }
+        };
+
+        kprintln!("fsck: device {}: {} dirs, {} files, {} clusters in use",
+            dev_index, report.dirs, report.files, report.used_clusters);
+        // 失われたチェーンが大量にあっても画面を埋めないよう、先頭だけ表示する
+        const MAX_LISTED: usize = 16;
+        for chain in report.lost_chains.iter().take(MAX_LISTED) {
+            kprintln!("  lost chain at cluster {} ({} clusters)", chain.start, chain.clusters.len());
+        }
+        if report.lost_chains.len() > MAX_LISTED {
+            kprintln!("  ... {} more lost chains", report.lost_chains.len() - MAX_LISTED);
+        }
+        for link in report.cross_links.iter().take(MAX_LISTED) {
+            kprintln!("  cross-linked cluster {} in {}", link.cluster, link.path);
+        }
+        for path in report.bad_chains.iter().take(MAX_LISTED) {
+            kprintln!("  broken cluster chain in {}", path);
+        }
+        if let Some(free) = report.fsinfo_free.filter(|_| !report.fsinfo_matches()) {
+            kprintln!("  FSInfo free count {} != actual {}", free, report.actual_free);
+        }
+
+        if report.is_clean() {
+            framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+            kprintln!("fsck: clean ({} free clusters)", report.actual_free);
+        } else if let Some(freed) = freed {
+            framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+            kprintln!("fsck: freed {} lost clusters, FSInfo free count set to {}",
+                freed, report.actual_free + freed);
+        } else {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("fsck: problems found ({} lost clusters)", report.lost_clusters());
+            if !report.lost_chains.is_empty() || !report.fsinfo_matches() {
+                kprintln!("  run 'fsck -r {}' to free lost chains and fix FSInfo", dev_index);
+            }
+        }
+        framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+    }
+
     /// cat コマンド: VFS 経由でファイル内容を表示する。
     pub(super) fn cmd_cat(&self, args: &str) {
         let filename = args.trim();
@@ -532,16 +936,36 @@ impl super::Shell {
             return;
         }
 
-        match crate::vfs::read_file(filename) {
-            Ok(data) => {
-                // テキストファイルとして表示を試みる
-                match core::str::from_utf8(&data) {
-                    Ok(text) => {
-                        kprintln!("{}", text);
-                    }
-                    Err(_) => {
-                        // バイナリファイルの場合はサイズだけ表示
-                        kprintln!("(binary file, {} bytes)", data.len());
+        // ファイル全体を読み込まず、読めたチャンクから順に表示する
+        // （ヒープより大きいファイルでも使うメモリはチャンク 1 つ分で済む）
+        let mut decoder = sabos_textutil::Utf8ChunkDecoder::new();
+        let mut binary = None;
+        let mut ends_with_newline = true;
+        let result = crate::vfs::read_file_chunked(filename, |chunk| {
+            // 最初のチャンクに NUL や不正な UTF-8 があればバイナリとみなし、サイズだけ表示する
+            let is_binary = *binary.get_or_insert_with(|| {
+                chunk.contains(&0)
+                    || core::str::from_utf8(chunk).is_err_and(|e| e.error_len().is_some())
+            });
+            if !is_binary {
+                decoder.push(chunk, &mut |text| {
+                    kprint!("{}", text);
+                    ends_with_newline = text.ends_with('\n');
+                });
+            }
+            Ok(())
+        });
+        match result {
+            Ok(size) => {
+                if binary == Some(true) {
+                    kprintln!("(binary file, {} bytes)", size);
+                } else {
+                    decoder.finish(&mut |text| {
+                        kprint!("{}", text);
+                        ends_with_newline = false;
+                    });
+                    if !ends_with_newline {
+                        kprintln!();
                     }
                 }
             }
@@ -594,11 +1018,124 @@ impl super::Shell {
         }
     }
 
+    /// replace コマンド: ファイル中の文字列を置換する（sed s/from/to/ の簡易版）。
+    ///
+    /// 使い方: replace [-g] [-i] <FROM> <TO> <FILENAME>
+    ///   -g: 行内のすべての一致を置換する（省略時は各行の最初の一致だけ）
+    ///   -i: 結果をファイルに書き戻す（省略時は置換後の内容を表示するだけ）
+    ///
+    /// 置換は textutil::replace_literal を 1 行ずつ適用する（正規表現は使わない）。
+    /// 引数は空白で区切るので、FROM / TO に空白は含められない。
+    pub(super) fn cmd_replace(&self, args: &str) {
+        let mut global = false;
+        let mut in_place = false;
+        let mut positional: Vec<&str> = Vec::new();
+        for arg in args.split_whitespace() {
+            match arg {
+                "-g" => global = true,
+                "-i" => in_place = true,
+                _ => positional.push(arg),
+            }
+        }
+        let [from, to, filename] = positional[..] else {
+            kprintln!("Usage: replace [-g] [-i] <FROM> <TO> <FILENAME>");
+            kprintln!("  -g: replace all matches in each line (default: first match only)");
+            kprintln!("  -i: write the result back to the file (default: preview only)");
+            return;
+        };
+
+        let data = match crate::vfs::read_file(filename) {
+            Ok(data) => data,
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("Error: {:?}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                return;
+            }
+        };
+        let Ok(text) = core::str::from_utf8(&data) else {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("Error: '{}' is not a text file", filename);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            return;
+        };
+
+        // 改行は残したまま、行の中身だけを置換する
+        let mut result = alloc::string::String::with_capacity(text.len());
+        let mut changed_lines = 0;
+        for line in text.split_inclusive('\n') {
+            let (body, newline) = match line.strip_suffix('\n') {
+                Some(body) => (body, "\n"),
+                None => (line, ""),
+            };
+            let (replaced, changed) = sabos_textutil::replace_literal(body, from, to, global);
+            if changed {
+                changed_lines += 1;
+            }
+            result.push_str(&replaced);
+            result.push_str(newline);
+        }
+
+        if !in_place {
+            kprint!("{}", result);
+            if !result.is_empty() && !result.ends_with('\n') {
+                kprintln!();
+            }
+            return;
+        }
+        if changed_lines == 0 {
+            kprintln!("No match in '{}' (file unchanged)", filename);
+            return;
+        }
+
+        // VFS には上書きがないので、消してから作り直す。
+        // 作り直しに失敗したら元の内容で作り直して、ファイルが消えたままにならないようにする。
+        let written = crate::vfs::delete_file(filename)
+            .and_then(|()| match crate::vfs::create_file(filename, result.as_bytes()) {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    let _ = crate::vfs::create_file(filename, &data);
+                    Err(e)
+                }
+            });
+        match written {
+            Ok(()) => {
+                framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+                kprintln!("Replaced in {} line(s) of '{}'", changed_lines, filename);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            }
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("Error writing file: {:?}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            }
+        }
+    }
+
     /// rm コマンド: VFS 経由でファイルを削除する。
     pub(super) fn cmd_rm(&self, args: &str) {
-        let filename = args.trim();
+        let (recursive, filename) = match args.trim().strip_prefix("-r") {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => (true, rest.trim()),
+            _ => (false, args.trim()),
+        };
         if filename.is_empty() {
-            kprintln!("Usage: rm <FILENAME>");
+            kprintln!("Usage: rm [-r] <FILENAME>");
+            return;
+        }
+
+        if recursive {
+            match crate::vfs::remove_dir_all(filename) {
+                Ok(removed) => {
+                    framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+                    kprintln!("Directory '{}' removed ({} entries)", filename, removed);
+                    framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                }
+                Err(e) => {
+                    framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                    kprintln!("Error removing directory: {:?}", e);
+                    framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                }
+            }
             return;
         }
 
@@ -616,66 +1153,204 @@ impl super::Shell {
         }
     }
 
-    /// run コマンド: VFS 経由で ELF バイナリを読み込んでユーザーモードで実行する。
+    /// cp コマンド: ファイルをコピーする。
     ///
-    /// 手順:
-    ///   1. VFS 経由でファイルを読み込む
-    ///   2. ELF パース → LOAD セグメント情報を取得
-    ///   3. プロセス作成 → ページテーブル + フレーム確保 + データロード
-    ///   4. Ring 3 で実行
-    ///   5. プロセスを破棄してフレームを返却
-    pub(super) fn cmd_run(&self, args: &str) {
-        let filename = args.trim();
-        if filename.is_empty() {
-            kprintln!("Usage: run <FILENAME>");
-            kprintln!("  Example: run HELLO.ELF");
+    /// 使い方: cp <SRC> <DST>
+    /// DST が既存のディレクトリなら、その中に同じファイル名でコピーする。
+    pub(super) fn cmd_cp(&self, args: &str) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let [src, dst] = parts[..] else {
+            kprintln!("Usage: cp <SRC> <DST>");
+            kprintln!("  Example: cp HELLO.TXT /SUBDIR");
             return;
-        }
+        };
 
-        // VFS 経由でファイルを読み込む
-        kprintln!("Loading {} from disk...", filename);
-        let elf_data = match crate::vfs::read_file(filename) {
-            Ok(data) => data,
+        match crate::vfs::copy_file(src, dst) {
+            Ok((dest, size)) => {
+                framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+                kprintln!("Copied '{}' -> '{}' ({} bytes)", src, dest, size);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            }
             Err(e) => {
                 framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
-                kprintln!("Error reading file: {:?}", e);
+                kprintln!("Error copying file: {:?}", e);
                 framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
-                return;
             }
+        }
+    }
+
+    /// mv コマンド: ファイル・ディレクトリを移動（名前変更）する。
+    ///
+    /// 使い方: mv <SRC> <DST>
+    /// DST が既存のディレクトリなら、その中に同じ名前で移す。
+    /// 同じマウント内ならエントリの付け替えだけで済み、別のマウントへはコピーしてから消す。
+    pub(super) fn cmd_mv(&self, args: &str) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let [src, dst] = parts[..] else {
+            kprintln!("Usage: mv <SRC> <DST>");
+            kprintln!("  Example: mv HELLO.TXT /SUBDIR");
+            return;
         };
-        kprintln!("  Loaded {} bytes", elf_data.len());
 
-        // ELF パース結果を表示
-        match crate::elf::parse_elf(&elf_data) {
-            Ok(info) => {
-                kprintln!("  Entry point: {:#x}", info.entry_point);
-                kprintln!("  LOAD segments: {}", info.load_segments.len());
+        match crate::vfs::move_path(src, dst) {
+            Ok(dest) => {
+                framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+                kprintln!("Moved '{}' -> '{}'", src, dest);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
             }
             Err(e) => {
                 framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
-                kprintln!("ELF parse error: {}", e);
+                kprintln!("Error moving: {:?}", e);
                 framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
-                return;
             }
         }
+    }
 
-        // フレーム数の確認（プロセス作成前）
-        let before_free = {
-            let fa = FRAME_ALLOCATOR.lock();
-            fa.free_frames()
+    /// unzip コマンド: ZIP アーカイブを展開する。
+    ///
+    /// 使い方: unzip <ARCHIVE> <DEST_DIR>
+    /// DEST_DIR がなければ作る。展開先に同じ名前のファイルがあれば上書きする。
+    pub(super) fn cmd_unzip(&self, args: &str) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        let [archive, dest] = parts[..] else {
+            kprintln!("Usage: unzip <ARCHIVE> <DEST_DIR>");
+            kprintln!("  Example: unzip /DOCS.ZIP /DOCS");
+            return;
         };
 
-        // ELF プロセスを作成（引数なし・環境変数なし）
-        let (process, entry_point, user_stack_top, _argc, _argv, _envp) =
-            match crate::usermode::create_elf_process(&elf_data, &[], &[]) {
-                Ok(result) => result,
-                Err(e) => {
-                    framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
-                    kprintln!("Failed to create ELF process: {}", e);
-                    framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
-                    return;
+        let data = match crate::vfs::read_file(archive) {
+            Ok(data) => data,
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("Error: {:?}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                return;
+            }
+        };
+        match unzip_lines(&data, dest) {
+            Ok(lines) => {
+                for line in lines {
+                    kprintln!("{}", line);
                 }
-            };
+            }
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("Error: not a readable ZIP archive ({:?})", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            }
+        }
+    }
+
+    /// hexdump コマンド: ファイルの一部を 16 進ダンプで表示する。
+    ///
+    /// 使い方: hexdump [-w WIDTH] <FILENAME> [OFFSET] [LEN]
+    /// OFFSET / LEN は 10 進または 0x 付きの 16 進。省略時は先頭から 256 バイト。
+    /// -w で 1 行に並べるバイト数を変えられる（既定 16）。
+    pub(super) fn cmd_hexdump(&self, args: &str) {
+        let usage = || {
+            kprintln!("Usage: hexdump [-w WIDTH] <FILENAME> [OFFSET] [LEN]");
+            kprintln!("  Example: hexdump HELLO.ELF 0 64");
+        };
+        let mut width = HEXDUMP_DEFAULT_WIDTH;
+        let mut positional: Vec<&str> = Vec::new();
+        let mut it = args.split_whitespace();
+        while let Some(arg) = it.next() {
+            if arg == "-w" {
+                match it.next().and_then(parse_number) {
+                    Some(w) if (1..=HEXDUMP_MAX_WIDTH).contains(&w) => width = w,
+                    _ => {
+                        kprintln!("hexdump: width must be 1..={}", HEXDUMP_MAX_WIDTH);
+                        return;
+                    }
+                }
+            } else {
+                positional.push(arg);
+            }
+        }
+        let (filename, offset, len) = match positional[..] {
+            [f] => (f, Some(0), Some(256)),
+            [f, o] => (f, parse_number(o), Some(256)),
+            [f, o, l] => (f, parse_number(o), parse_number(l)),
+            _ => return usage(),
+        };
+        let (Some(offset), Some(len)) = (offset, len) else {
+            return usage();
+        };
+
+        let data = match crate::vfs::read_file(filename) {
+            Ok(data) => data,
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("Error: {:?}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                return;
+            }
+        };
+        if offset >= data.len() {
+            kprintln!("Offset {} is beyond end of file ({} bytes)", offset, data.len());
+            return;
+        }
+        let end = offset.saturating_add(len).min(data.len());
+        for line in hexdump_lines(&data[offset..end], offset, width) {
+            kprintln!("{}", line);
+        }
+    }
+
+    /// run コマンド: VFS 経由で ELF バイナリを読み込んでユーザーモードで実行する。
+    ///
+    /// 手順:
+    ///   1. VFS 経由でファイルを読み込む
+    ///   2. ELF パース → LOAD セグメント情報を取得
+    ///   3. プロセス作成 → ページテーブル + フレーム確保 + データロード
+    ///   4. Ring 3 で実行
+    ///   5. プロセスを破棄してフレームを返却
+    pub(super) fn cmd_run(&self, args: &str) {
+        let filename = args.trim();
+        if filename.is_empty() {
+            kprintln!("Usage: run <FILENAME>");
+            kprintln!("  Example: run HELLO.ELF");
+            return;
+        }
+
+        // VFS 経由でファイルを読み込む
+        kprintln!("Loading {} from disk...", filename);
+        let Some(elf_data) = read_executable(filename) else {
+            return;
+        };
+        kprintln!("  Loaded {} bytes", elf_data.len());
+
+        // ELF パース結果を表示
+        match crate::elf::parse_elf(&elf_data) {
+            Ok(info) => {
+                kprintln!("  Entry point: {:#x}", info.entry_point);
+                kprintln!("  LOAD segments: {}", info.load_segments.len());
+            }
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("ELF parse error: {}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                return;
+            }
+        }
+
+        // フレーム数の確認（プロセス作成前）
+        let before_free = {
+            let fa = FRAME_ALLOCATOR.lock();
+            fa.free_frames()
+        };
+
+        // ELF プロセスを作成（引数なし。環境変数はシェルのものを渡す）
+        let env = scheduler::env_strings();
+        let (process, entry_point, user_stack_top, _argc, _argv, _envp) =
+            match crate::usermode::create_elf_process(&elf_data, &[], &env) {
+                Ok(result) => result,
+                Err(e) => {
+                    framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                    kprintln!("Failed to create ELF process: {}", e);
+                    framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                    return;
+                }
+            };
 
         // Ring 3 で実行
         kprintln!("Running in Ring 3...");
@@ -714,14 +1389,8 @@ impl super::Shell {
 
         // VFS 経由でファイルを読み込む
         kprintln!("Loading {} from disk...", filename);
-        let elf_data = match crate::vfs::read_file(filename) {
-            Ok(data) => data,
-            Err(e) => {
-                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
-                kprintln!("Error reading file: {:?}", e);
-                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
-                return;
-            }
+        let Some(elf_data) = read_executable(filename) else {
+            return;
         };
         kprintln!("  Loaded {} bytes", elf_data.len());
 
@@ -753,11 +1422,19 @@ impl super::Shell {
         let gw = crate::net_config::get_gateway_ip();
         let dns = crate::net_config::get_dns_server_ip();
         let mask = crate::net_config::get_subnet_mask();
-        kprintln!("IP Configuration:");
+        let mode = if crate::net_config::is_dhcp_enabled() { "DHCP" } else { "static" };
+        kprintln!("IP Configuration ({}):", mode);
         kprintln!("  IP Address:   {}.{}.{}.{}", my_ip[0], my_ip[1], my_ip[2], my_ip[3]);
         kprintln!("  Subnet Mask:  {}.{}.{}.{}", mask[0], mask[1], mask[2], mask[3]);
         kprintln!("  Gateway:      {}.{}.{}.{}", gw[0], gw[1], gw[2], gw[3]);
         kprintln!("  DNS:          {}.{}.{}.{}", dns[0], dns[1], dns[2], dns[3]);
+        kprintln!("  MTU:          {}", crate::netstack::mtu());
+        let ipv6 = crate::net_config::get_my_ipv6();
+        let ipv6_source = if crate::net_config::is_ipv6_slaac_configured() { "SLAAC" } else { "fallback" };
+        kprintln!("  IPv6 Address: {} ({})", crate::netstack::format_ipv6(&ipv6), ipv6_source);
+        if let Some(router) = crate::net_config::get_ipv6_router() {
+            kprintln!("  IPv6 Router:  {}", crate::netstack::format_ipv6(&router));
+        }
 
         let drv = crate::virtio_net::VIRTIO_NET.lock();
         if let Some(ref d) = *drv {
@@ -769,12 +1446,369 @@ impl super::Shell {
         }
     }
 
+    /// ifconfig コマンド: IPv4 の設定を静的に変える。
+    ///
+    /// # 使い方
+    /// - `ifconfig` — 今の設定を表示（ip コマンドと同じ）
+    /// - `ifconfig 10.0.2.20 255.255.255.0 10.0.2.2 10.0.2.3` — IP / マスク / ゲートウェイ / DNS を設定
+    ///   （ゲートウェイと DNS は省略すると今の値のまま）。以降 DHCP では上書きされない
+    /// - `ifconfig dhcp` — DHCP に戻して取り直す
+    /// - `ifconfig mtu 1400` — MTU を変える（576〜9000）。超える IPv4 パケットはフラグメントに分けて送る
+    pub(super) fn cmd_ifconfig(&self, args: &str) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => self.cmd_ip(),
+            ["dhcp"] => {
+                crate::net_config::set_dhcp_enabled(true);
+                match crate::netstack::dhcp_discover() {
+                    Ok(()) => self.cmd_ip(),
+                    Err(e) => {
+                        framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                        kprintln!("ifconfig: DHCP failed: {}", e);
+                        framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                    }
+                }
+            }
+            ["mtu", n] => {
+                let result = n
+                    .parse::<usize>()
+                    .map_err(|_| "invalid MTU")
+                    .and_then(crate::netstack::set_mtu);
+                match result {
+                    Ok(()) => self.cmd_ip(),
+                    Err(e) => {
+                        framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                        kprintln!("ifconfig: {}", e);
+                        framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                    }
+                }
+            }
+            [ip, mask, rest @ ..] if rest.len() <= 2 => {
+                let gateway = rest.first().map_or(Some(crate::net_config::get_gateway_ip()), |s| parse_ipv4(s));
+                let dns = rest.get(1).map_or(Some(crate::net_config::get_dns_server_ip()), |s| parse_ipv4(s));
+                let (Some(ip), Some(mask), Some(gateway), Some(dns)) = (parse_ipv4(ip), parse_ipv4(mask), gateway, dns) else {
+                    kprintln!("ifconfig: invalid address: {}", args.trim());
+                    return;
+                };
+                match crate::net_config::set_static_config(ip, mask, gateway, dns) {
+                    Ok(()) => self.cmd_ip(),
+                    Err(e) => {
+                        framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                        kprintln!("ifconfig: {}", e);
+                        framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                    }
+                }
+            }
+            _ => {
+                kprintln!("Usage: ifconfig [<ip> <mask> [gateway] [dns] | dhcp | mtu <n>]");
+                kprintln!("  Example: ifconfig 10.0.2.20 255.255.255.0 10.0.2.2 10.0.2.3");
+            }
+        }
+    }
+
     /// linkstatus コマンド: ネットワークリンクの状態を表示する。
     pub(super) fn cmd_linkstatus(&self) {
         let link_up = crate::netstack::is_network_link_up();
         kprintln!("Network link: {}", if link_up { "UP" } else { "DOWN" });
     }
 
+    /// arp コマンド: ARP キャッシュを表示・編集する。
+    ///
+    /// # 使い方
+    /// - `arp` — 一覧表示（IP, MAC, 最終学習からの経過時間）
+    /// - `arp -d 10.0.2.2` — エントリを削除
+    /// - `arp -s 10.0.2.50 52:54:00:12:34:56` — 静的エントリを登録（期限切れで消えない）
+    pub(super) fn cmd_arp(&self, args: &str) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => {
+                let entries = crate::netstack::arp_entries();
+                if entries.is_empty() {
+                    kprintln!("(ARP cache is empty)");
+                    return;
+                }
+                kprintln!("IP Address       MAC Address        Age      Type");
+                for e in entries {
+                    let ip = alloc::format!("{}.{}.{}.{}", e.ip[0], e.ip[1], e.ip[2], e.ip[3]);
+                    let age = if e.is_static {
+                        alloc::string::String::from("-")
+                    } else {
+                        alloc::format!("{}s", e.age_ms / 1000)
+                    };
+                    kprintln!(
+                        "{:<16} {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}  {:<8} {}",
+                        ip, e.mac[0], e.mac[1], e.mac[2], e.mac[3], e.mac[4], e.mac[5],
+                        age, if e.is_static { "static" } else { "dynamic" }
+                    );
+                }
+            }
+            ["-d", ip] => match parse_ipv4(ip) {
+                Some(ip) => {
+                    if crate::netstack::arp_delete(&ip) {
+                        kprintln!("Deleted.");
+                    } else {
+                        kprintln!("Error: no ARP entry for {}", parts[1]);
+                    }
+                }
+                None => kprintln!("Error: invalid IP address"),
+            },
+            ["-s", ip, mac] => match (parse_ipv4(ip), parse_mac(mac)) {
+                (Some(ip), Some(mac)) => {
+                    if crate::netstack::arp_set_static(ip, mac) {
+                        kprintln!("Added static entry.");
+                    } else {
+                        kprintln!("Error: ARP cache is full of static entries");
+                    }
+                }
+                _ => kprintln!("Error: invalid IP or MAC address"),
+            },
+            _ => kprintln!("Usage: arp [-d <ip> | -s <ip> <mac>]"),
+        }
+    }
+
+    /// route コマンド: ルーティングテーブルを表示・編集する。
+    ///
+    /// # 使い方
+    /// - `route` — 一覧表示（宛先/プレフィックス, ゲートウェイ, インターフェース, 種類）
+    /// - `route add 192.168.1.0/24 via 10.0.2.2` — ゲートウェイ経由の静的ルートを追加
+    /// - `route add 10.0.3.0/24` — 直結（on-link）の静的ルートを追加
+    /// - `route del 192.168.1.0/24` — 静的ルートを削除
+    pub(super) fn cmd_route(&self, args: &str) {
+        let parts: Vec<&str> = args.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => {
+                kprintln!("{:<18} {:<16} {:<6} Type", "Destination", "Gateway", "Iface");
+                for route in crate::net_config::routes() {
+                    kprintln!("{}", crate::net_config::format_route(&route));
+                }
+            }
+            ["add", net] | ["add", net, "via", _] => {
+                let Some((dest, prefix_len)) = parse_cidr(net) else {
+                    kprintln!("Error: invalid network (expected a.b.c.d/len)");
+                    return;
+                };
+                let gateway = match parts.get(3) {
+                    Some(gw) => match parse_ipv4(gw) {
+                        Some(gw) => Some(gw),
+                        None => {
+                            kprintln!("Error: invalid gateway address");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                match crate::net_config::add_route(dest, prefix_len, gateway) {
+                    Ok(()) => kprintln!("Route added."),
+                    Err(e) => kprintln!("Error: {}", e),
+                }
+            }
+            ["del", net] => match parse_cidr(net) {
+                Some((dest, prefix_len)) => {
+                    if crate::net_config::del_route(dest, prefix_len) {
+                        kprintln!("Route deleted.");
+                    } else {
+                        kprintln!("Error: no such static route");
+                    }
+                }
+                None => kprintln!("Error: invalid network (expected a.b.c.d/len)"),
+            },
+            _ => kprintln!("Usage: route [add <net>/<len> [via <gw>] | del <net>/<len>]"),
+        }
+    }
+
+    /// nc コマンド: TCP で接続して、キー入力を送り、受信したデータを表示する。
+    ///
+    /// 入力は 1 行ずつ溜めて Enter で "\n" 付きで送る（入力中の文字はこちらでエコーする）。
+    /// 受信は短いタイムアウトの tcp_recv をループで回し、その合間にキーを見るので、
+    /// 相手が黙っていてもシェルが固まらない。相手が閉じるか ESC で終わる。
+    pub(super) fn cmd_nc(&self, args: &str) {
+        const RECV_POLL_MS: u64 = 50;
+
+        let mut parts = args.split_whitespace();
+        let (Some(ip), Some(port), None) = (parts.next(), parts.next(), parts.next()) else {
+            kprintln!("Usage: nc <ip> <port>");
+            return;
+        };
+        let (Some(ip), Ok(port)) = (parse_ipv4(ip), port.parse::<u16>()) else {
+            kprintln!("nc: invalid address: {}", args.trim());
+            return;
+        };
+
+        kprintln!("Connecting to {}.{}.{}.{}:{}...", ip[0], ip[1], ip[2], ip[3], port);
+        let conn = match crate::netstack::tcp_connect(ip, port) {
+            Ok(conn) => conn,
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("nc: connect failed: {}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                return;
+            }
+        };
+        kprintln!("Connected. Type lines to send, ESC to quit.");
+
+        let mut line = alloc::string::String::new();
+        let reason = 'session: loop {
+            while let Some(c) = crate::interrupts::get_key() {
+                match nc_feed_key(&mut line, c) {
+                    NcKey::Quit => break 'session "interrupted",
+                    NcKey::Echo(c) => kprint!("{}", c),
+                    // '\x08' は framebuffer.rs で 1 文字戻して消す処理になる
+                    NcKey::Erase => kprint!("\x08"),
+                    NcKey::Send(data) => {
+                        kprintln!();
+                        if let Err(e) = crate::netstack::tcp_send(conn, data.as_bytes()) {
+                            break 'session e;
+                        }
+                    }
+                    NcKey::Ignore => {}
+                }
+            }
+
+            match crate::netstack::tcp_recv(conn, RECV_POLL_MS) {
+                Ok(data) => kprint!("{}", alloc::string::String::from_utf8_lossy(&data)),
+                Err("timeout") => {}
+                Err(e) => break 'session e,
+            }
+        };
+
+        let _ = crate::netstack::tcp_close(conn);
+        kprintln!();
+        kprintln!("nc: connection closed ({})", reason);
+    }
+
+    /// http コマンド: HTTP/1.0 の GET で取ってきた本文を表示する（-o で保存）。
+    ///
+    /// ホスト名は dns_lookup で解決し、80 番に接続する。応答は Content-Length 分が
+    /// 揃うか、相手が接続を閉じるまで読む（chunked は HTTP/1.0 では来ないので扱わない）。
+    /// 受信は短いタイムアウトで回して ESC で中断できるようにし、しばらく何も来なければ
+    /// 打ち切る。メモリに全部溜めるので大きさに上限を設けている。
+    ///
+    /// QEMU の SLIRP には HTTP サーバがいないので selftest では応答の解析だけを見ている。
+    /// 実際の取得は `http example.com /` のように手で確かめる。
+    pub(super) fn cmd_http(&self, args: &str) {
+        const RECV_POLL_MS: u64 = 200;
+        const IDLE_TIMEOUT_MS: u64 = 5000;
+        const MAX_RESPONSE: usize = 1024 * 1024;
+
+        let mut output = None;
+        let mut rest = Vec::new();
+        let mut parts = args.split_whitespace();
+        while let Some(p) = parts.next() {
+            if p == "-o" {
+                let Some(file) = parts.next() else {
+                    kprintln!("http: -o needs a file name");
+                    return;
+                };
+                output = Some(file);
+            } else {
+                rest.push(p);
+            }
+        }
+        let (host, path) = match rest.as_slice() {
+            [host] => (*host, "/"),
+            [host, path] => (*host, *path),
+            _ => {
+                kprintln!("Usage: http <host> [path] [-o FILE]");
+                kprintln!("  Example: http example.com /");
+                return;
+            }
+        };
+        let error = |msg: &str| {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("http: {}", msg);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+        };
+
+        let ip = match parse_ipv4(host) {
+            Some(ip) => ip,
+            None => {
+                kprintln!("Resolving {}...", host);
+                match crate::netstack::dns_lookup(host) {
+                    Ok(ip) => ip,
+                    Err(e) => return error(&alloc::format!("DNS lookup failed: {}", e)),
+                }
+            }
+        };
+        kprintln!("Connecting to {}.{}.{}.{}:80...", ip[0], ip[1], ip[2], ip[3]);
+        let conn = match crate::netstack::tcp_connect(ip, 80) {
+            Ok(conn) => conn,
+            Err(e) => return error(&alloc::format!("connect failed: {}", e)),
+        };
+
+        let request = alloc::format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: sabos\r\nConnection: close\r\n\r\n",
+            path, host
+        );
+        if let Err(e) = crate::netstack::tcp_send(conn, request.as_bytes()) {
+            let _ = crate::netstack::tcp_close(conn);
+            return error(&alloc::format!("send failed: {}", e));
+        }
+
+        let mut response = Vec::new();
+        let mut idle_ms = 0;
+        let mut stopped = None;
+        loop {
+            // Content-Length 分が揃ったら、相手が閉じるのを待たずに終える
+            if let Some(r) = parse_http_response(&response)
+                && r.content_length.is_some_and(|len| r.body.len() >= len)
+            {
+                break;
+            }
+            if response.len() > MAX_RESPONSE {
+                stopped = Some("response too large, truncated");
+                break;
+            }
+            if crate::interrupts::get_key() == Some('\x1b') {
+                stopped = Some("interrupted");
+                break;
+            }
+            match crate::netstack::tcp_recv(conn, RECV_POLL_MS) {
+                Ok(data) => {
+                    response.extend_from_slice(&data);
+                    idle_ms = 0;
+                }
+                Err("timeout") => {
+                    idle_ms += RECV_POLL_MS;
+                    if idle_ms >= IDLE_TIMEOUT_MS {
+                        stopped = Some("timed out");
+                        break;
+                    }
+                }
+                // 相手が閉じた（HTTP/1.0 では本文の終わり）
+                Err(_) => break,
+            }
+        }
+        let _ = crate::netstack::tcp_close(conn);
+
+        let Some(r) = parse_http_response(&response) else {
+            return error(&alloc::format!(
+                "malformed response ({} bytes){}",
+                response.len(),
+                stopped.map(|s| alloc::format!(", {}", s)).unwrap_or_default()
+            ));
+        };
+        let body = match r.content_length {
+            Some(len) => &r.body[..len.min(r.body.len())],
+            None => r.body,
+        };
+        kprintln!("{}", r.status);
+        if let Some(reason) = stopped {
+            error(reason);
+        }
+
+        match output {
+            Some(file) => match crate::vfs::create_file(file, body) {
+                Ok(()) => {
+                    framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+                    kprintln!("Saved {} bytes to {}", body.len(), file);
+                    framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+                }
+                Err(e) => error(&alloc::format!("cannot write {}: {:?}", file, e)),
+            },
+            None => kprintln!("{}", alloc::string::String::from_utf8_lossy(body)),
+        }
+    }
+
     /// beep コマンド: AC97 ドライバでビープ音を再生する。
     ///
     /// # 使い方
@@ -821,10 +1855,73 @@ impl super::Shell {
         }
     }
 
+    /// play コマンド: VFS 上の WAV ファイルを AC97 で再生する。
+    ///
+    /// # 使い方
+    /// - `play /SOUND.WAV`
+    ///
+    /// AC97 の PCM 出力は 16bit stereo 固定なので、8bit やモノラルの WAV は
+    /// wav-core で 16bit stereo に変換しながら少しずつキューに入れる。
+    /// 全体を一度に変換しないのは、長いファイルでメモリを倍以上使わないため。
+    pub(super) fn cmd_play(&self, args: &str) {
+        let path = args.trim();
+        if path.is_empty() {
+            kprintln!("Usage: play <path>");
+            return;
+        }
+
+        let file = match crate::vfs::read_file(path) {
+            Ok(data) => data,
+            Err(e) => {
+                kprintln!("Error: {:?}", e);
+                return;
+            }
+        };
+        let info = match sabos_wav_core::parse_wav(&file) {
+            Ok(info) => info,
+            Err(e) => {
+                kprintln!("Error: not a playable WAV file ({:?})", e);
+                return;
+            }
+        };
+        if !sabos_wav_core::is_convertible(&info) {
+            kprintln!(
+                "Error: unsupported WAV ({} ch, {} bit)",
+                info.channels, info.bits_per_sample
+            );
+            return;
+        }
+
+        let frames = info.frames();
+        kprintln!(
+            "Playing {} ({} Hz, {} ch, {} bit, {} ms)...",
+            path,
+            info.sample_rate,
+            info.channels,
+            info.bits_per_sample,
+            frames as u64 * 1000 / info.sample_rate as u64
+        );
+
+        // BD 4 つ分ずつ変換してキューに入れる
+        let mut chunk = alloc::vec![0u8; 4 * 4096 * 4];
+        let mut pos = 0;
+        while pos < frames {
+            let n = sabos_wav_core::to_s16_stereo(&info, pos, &mut chunk);
+            if let Err(e) = crate::ac97::queue_pcm_all(&chunk[..n * 4], info.sample_rate) {
+                kprintln!("Error: {:?}", e);
+                return;
+            }
+            pos += n;
+        }
+        crate::ac97::wait_pcm_drain();
+        kprintln!("Done.");
+    }
+
     /// ipc_bench コマンド: IPC ラウンドトリップのベンチマーク
     ///
     /// 自分自身に N 回 send+recv して、TSC サイクル数で
     /// min/avg/max と推定スループットを表示する。
+    /// あわせてメッセージバッファの確保コストを Vec と MsgBuf で比べる。
     ///
     /// # 使い方
     /// - `ipc_bench` — デフォルト (1000 回)
@@ -841,7 +1938,7 @@ impl super::Shell {
 
         // ウォームアップ: 10 回
         for _ in 0..10 {
-            let _ = crate::ipc::send(task_id, task_id, data.to_vec());
+            let _ = crate::ipc::send(task_id, task_id, data);
             let _ = crate::ipc::recv(task_id, 1000);
         }
 
@@ -851,7 +1948,7 @@ impl super::Shell {
 
         for _ in 0..n {
             let start = rdtsc();
-            let _ = crate::ipc::send(task_id, task_id, data.to_vec());
+            let _ = crate::ipc::send(task_id, task_id, data);
             let _ = crate::ipc::recv(task_id, 1000);
             let end = rdtsc();
             let elapsed = end.wrapping_sub(start);
@@ -871,25 +1968,298 @@ impl super::Shell {
         kprintln!("  avg: {} cycles", avg_cycles);
         kprintln!("  max: {} cycles", max_cycles);
         kprintln!("  total: {} cycles", total_cycles);
+
+        // メッセージバッファの確保 + 解放だけのコスト。
+        // 以前の Vec（グローバルアロケータ）と、今の MsgBuf（IPC 専用キャッシュ）を比べる
+        let start = rdtsc();
+        for _ in 0..n {
+            core::hint::black_box(data.to_vec());
+        }
+        let heap_cycles = rdtsc().wrapping_sub(start) / (n as u64);
+        let start = rdtsc();
+        for _ in 0..n {
+            core::hint::black_box(crate::slab_allocator::MsgBuf::from_slice(data));
+        }
+        let cache_cycles = rdtsc().wrapping_sub(start) / (n as u64);
+        kprintln!("  alloc (Vec):     {} cycles/msg", heap_cycles);
+        kprintln!("  alloc (MsgBuf):  {} cycles/msg", cache_cycles);
+    }
+
+    /// blit_bench コマンド: 320x240 の blit のベンチマーク
+    ///
+    /// 同じ画像を BlitFormat::Native（行コピーの高速パス）と
+    /// BlitFormat::Rgbx（ハードウェアが BGR ならピクセル変換の低速パス）で
+    /// N 回ずつ描き、1 回あたりの TSC サイクル数と比を表示する。
+    /// MMIO への転送時間を混ぜないよう、計測中はダブルバッファモードにしておく。
+    ///
+    /// # 使い方
+    /// - `blit_bench` — デフォルト (100 回)
+    /// - `blit_bench 20` — 20 回
+    pub(super) fn cmd_blit_bench(&self, args: &str) {
+        use crate::framebuffer::BlitFormat;
+
+        const W: usize = 320;
+        const H: usize = 240;
+        let n: u64 = args.trim().parse().unwrap_or(100);
+        if n == 0 {
+            kprintln!("Error: n must be > 0");
+            return;
+        }
+        match crate::framebuffer::screen_size() {
+            Some((width, height)) if width >= W && height >= H => {}
+            _ => {
+                kprintln!("Error: screen must be at least {}x{}", W, H);
+                return;
+            }
+        }
+
+        let task_id = crate::scheduler::current_task_id();
+        if crate::framebuffer::set_double_buffering(task_id, true).is_err() {
+            kprintln!("Error: double buffer is in use by another task");
+            return;
+        }
+
+        // グラデーション画像（内容は速度に関係ないが、画面で結果を確認できるように）
+        let mut buf = alloc::vec![0u8; W * H * 4];
+        for (i, px) in buf.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (i % W, i / W);
+            px.copy_from_slice(&[(x * 255 / W) as u8, (y * 255 / H) as u8, 128, 0]);
+        }
+
+        let measure = |format: BlitFormat| -> u64 {
+            let start = rdtsc();
+            for _ in 0..n {
+                let _ = crate::framebuffer::draw_blit_global(0, 0, W, H, &buf, format, false);
+            }
+            rdtsc().wrapping_sub(start) / n
+        };
+        let native = measure(BlitFormat::Native);
+        let rgbx = measure(BlitFormat::Rgbx);
+
+        let _ = crate::framebuffer::set_double_buffering(task_id, false);
+
+        kprintln!("=== Blit Benchmark ({}x{}, {} iterations) ===", W, H, n);
+        kprintln!("  native (row copy): {} cycles/blit", native);
+        kprintln!("  rgbx (convert):    {} cycles/blit", rgbx);
+        if let Some(tenths) = (rgbx * 10).checked_div(native) {
+            kprintln!("  speedup: {}.{}x", tenths / 10, tenths % 10);
+        }
     }
 
     /// panic コマンド: 意図的にカーネルパニックを発生させる。
     /// panic ハンドラのテスト用。シリアルと画面に赤字で panic 情報が表示されるはず。
-    pub(super) fn cmd_panic(&self) {
-        panic!("User-triggered panic from shell command");
+    /// `panic <depth>` は depth 回再帰してからパニックするので、
+    /// バックトレースに再帰関数のフレームが depth 個並ぶことを確かめられる。
+    pub(super) fn cmd_panic(&self, args: &str) {
+        #[inline(never)]
+        fn recurse(depth: usize) -> usize {
+            if depth == 0 {
+                panic!("User-triggered panic from shell command");
+            }
+            core::hint::black_box(recurse(depth - 1))
+        }
+
+        let depth = args.trim().parse().unwrap_or(0);
+        recurse(depth);
+    }
+
+    /// addr2line コマンド: カーネルのアドレスを関数名 + オフセットに直す。
+    /// パニックのバックトレースやページフォルトの RIP をあとから引くのに使う。
+    /// 戻りアドレスではなくアドレスそのものを引く（バックトレースのように 1 引いたりしない）。
+    pub(super) fn cmd_addr2line(&self, args: &str) {
+        if args.trim().is_empty() {
+            kprintln!("Usage: addr2line <addr>...");
+            return;
+        }
+        if !crate::symbols::available() {
+            kprintln!("No kernel symbol table (build with `make build` to embed one)");
+            return;
+        }
+        for arg in args.split_whitespace() {
+            let Some(addr) = parse_number(arg) else {
+                kprintln!("{}: invalid address", arg);
+                continue;
+            };
+            match crate::symbols::resolve(addr as u64) {
+                Some((name, offset)) => kprintln!("{:#018x}  {}+{:#x}", addr, name, offset),
+                None => kprintln!("{:#018x}  ??", addr),
+            }
+        }
+    }
+
+    /// time コマンド: 残りの引数をコマンドとして実行し、かかった時間を表示する。
+    /// run と spawn の起動コストの比較や、ファイル操作の時間を測るのに使う。
+    ///
+    /// # 使い方
+    /// - `time run /HELLO.ELF`
+    /// - `time cat /README.TXT`
+    pub(super) fn cmd_time(&self, args: &str) {
+        if args.trim().is_empty() {
+            kprintln!("Usage: time <command...>");
+            return;
+        }
+        let us = self.time_command(args);
+        kprintln!("real {}.{:03} ms", us / 1000, us % 1000);
+    }
+
+    /// line をコマンドとして実行し、かかった時間（マイクロ秒）を返す。
+    /// selftest からも呼ぶ。
+    pub(super) fn time_command(&self, line: &str) -> u64 {
+        // 初回は TSC の周波数を測るのに時間がかかるので、計測の前に済ませておく
+        let per_ms = tsc_per_ms();
+        let start = rdtsc();
+        self.dispatch_command(line);
+        let cycles = rdtsc().wrapping_sub(start);
+        cycles * 1000 / per_ms
+    }
+
+    /// パイプライン（`cmd1 | cmd2 | ...`）を実行し、最後の段の出力を表示する。
+    /// 段のつなぎ方と使える組み込みコマンドは pipeline.rs を参照。
+    pub(super) fn cmd_pipeline(&self, line: &str) {
+        if let Err(e) = super::pipeline::run(line, &mut |text| kprint!("{}", text)) {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("Pipeline error: {}", e);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+        }
+    }
+
+    /// `<program> [args] &`: ユーザープログラムをバックグラウンドジョブとして起動する。
+    /// spawn と違って引数を渡せて、ジョブ番号で jobs / fg / kill %n から扱える。
+    /// 組み込みコマンドはシェル自身が実行するので、バックグラウンドにはできない。
+    pub(super) fn cmd_background(&self, line: &str) {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let Some(&command) = args.first() else {
+            kprintln!("Usage: <program> [args] &");
+            return;
+        };
+        if super::COMMANDS.contains(&command) || line.contains('|') {
+            kprintln!("{}: only a single program can be run in the background", command);
+            return;
+        }
+        let Some(elf_data) = read_executable(command) else {
+            return;
+        };
+        match super::pipeline::spawn_program(&args, &elf_data, None, None) {
+            Ok(task_id) => {
+                let number = self.jobs.borrow_mut().add(task_id, line);
+                kprintln!("[{}] {}", number, task_id);
+            }
+            Err(e) => {
+                framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+                kprintln!("Failed to start job: {}", e);
+                framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            }
+        }
+    }
+
+    /// jobs コマンド: バックグラウンドジョブの一覧を表示する。
+    /// 終わっていたジョブはここで回収して Done と表示し、一覧から消す。
+    pub(super) fn cmd_jobs(&self) {
+        let mut jobs = self.jobs.borrow_mut();
+        for (job, exit_code) in jobs.reap_finished() {
+            kprintln!("[{}]  Done({})  {}", job.number, exit_code, job.command);
+        }
+        for job in jobs.iter() {
+            kprintln!("[{}]  Running  {}  (task {})", job.number, job.command, job.task_id);
+        }
+    }
+
+    /// fg コマンド: ジョブの終了を待ち、終了コードを表示する。
+    pub(super) fn cmd_fg(&self, args: &str) {
+        let Some(number) = super::jobs::parse_job_spec(args) else {
+            kprintln!("Usage: fg <n> | fg %<n>");
+            return;
+        };
+        // 待っているあいだは借用を持たないよう、一覧から外してから待つ
+        let Some(job) = self.jobs.borrow_mut().remove(number) else {
+            kprintln!("fg: %{}: no such job", number);
+            return;
+        };
+        kprintln!("{}", job.command);
+        match scheduler::wait_for_child(job.task_id, 0) {
+            Ok(exit_code) => kprintln!("[{}]  Done({})  {}", job.number, exit_code, job.command),
+            Err(e) => kprintln!("fg: %{}: {:?}", job.number, e),
+        }
+    }
+
+    /// 終わったバックグラウンドジョブを回収して知らせる。プロンプトを出す前に呼ぶ。
+    pub(super) fn report_finished_jobs(&self) {
+        for (job, exit_code) in self.jobs.borrow_mut().reap_finished() {
+            kprintln!("[{}]  Done({})  {}", job.number, exit_code, job.command);
+        }
+    }
+
+    /// export コマンド: シェルの環境変数を設定する。
+    /// シェル（このタスク）の環境変数は run / spawn で起動するプログラムに引き継がれる。
+    /// 引数では `$KEY` が展開されるので、`export PATH=$PATH:/host` のようにも書ける。
+    pub(super) fn cmd_export(&self, args: &str) {
+        if args.trim().is_empty() {
+            kprintln!("Usage: export KEY=VALUE...");
+            return;
+        }
+        for pair in args.split_whitespace() {
+            let valid = pair.split_once('=').filter(|(key, _)| {
+                !key.is_empty()
+                    && !key.starts_with(|c: char| c.is_ascii_digit())
+                    && key.chars().all(super::is_env_name_char)
+            });
+            match valid {
+                Some((key, value)) => scheduler::set_env_var(key, value),
+                None => kprintln!("export: invalid assignment: {}", pair),
+            }
+        }
+    }
+
+    /// env コマンド: シェルの環境変数を "KEY=VALUE" の形で一覧表示する。
+    /// SYS_LISTENV と同じ list_env_vars() の内容をそのまま出す。
+    pub(super) fn cmd_env(&self) {
+        kprint!("{}", scheduler::list_env_vars());
+    }
+
+    /// unset コマンド: シェルの環境変数を削除する。
+    pub(super) fn cmd_unset(&self, args: &str) {
+        if args.trim().is_empty() {
+            kprintln!("Usage: unset KEY...");
+            return;
+        }
+        for key in args.split_whitespace() {
+            scheduler::remove_env_var(key);
+        }
+    }
+
+    /// clip コマンド: 引数がなければクリップボードの中身を出し、あればそれで置き換える。
+    /// ED の y/x やユーザープログラムとのあいだで、SYS_CLIPBOARD_* と同じバッファを共有する。
+    pub(super) fn cmd_clip(&self, args: &str) {
+        if args.is_empty() {
+            let data = crate::clipboard::contents();
+            let text = alloc::string::String::from_utf8_lossy(&data);
+            if text.ends_with('\n') || text.is_empty() {
+                kprint!("{}", text);
+            } else {
+                kprintln!("{}", text);
+            }
+            return;
+        }
+        if crate::clipboard::set(args.as_bytes()).is_err() {
+            kprintln!("clip: too large (max {} bytes)", crate::clipboard::CLIPBOARD_MAX);
+        }
     }
 
     /// shutdown コマンド: ACPI S5 シャットダウンで電源を切る。
     /// PM1a_CNT レジスタに SLP_TYPa と SLP_EN を書き込んで S5 ステートに遷移する。
+    /// 電源を切る前に TCP 接続へ FIN を送り、相手に接続を閉じさせる。
     pub(super) fn cmd_shutdown(&self) {
         kprintln!("Shutting down...");
+        crate::netstack::shutdown();
         crate::acpi::acpi_shutdown();
     }
 
     /// reboot コマンド: ACPI リセットでシステムを再起動する。
     /// FADT reset register → 8042 キーボードコントローラ → トリプルフォルトの 3 段フォールバック。
+    /// shutdown と同じく、先に TCP 接続を閉じておく。
     pub(super) fn cmd_reboot(&self) {
         kprintln!("Rebooting...");
+        crate::netstack::shutdown();
         crate::acpi::acpi_reboot();
     }
 
@@ -917,3 +2287,308 @@ impl super::Shell {
         kprintln!("Start QEMU with: -device isa-debug-exit,iobase=0xf4,iosize=0x04");
     }
 }
+
+/// HTTP 応答をヘッダと本文に分けたもの
+pub(super) struct HttpResponse<'a> {
+    /// ステータス行（"HTTP/1.0 200 OK" など）
+    pub(super) status: &'a str,
+    /// Content-Length ヘッダの値（なければ接続が閉じるまでが本文）
+    pub(super) content_length: Option<usize>,
+    /// ヘッダの後ろに続くバイト列（受信途中なら途中まで）
+    pub(super) body: &'a [u8],
+}
+
+/// 受信した HTTP 応答を解析する。ヘッダの終わり（空行）がまだ来ていなければ None。
+pub(super) fn parse_http_response(response: &[u8]) -> Option<HttpResponse<'_>> {
+    let header_end = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let header = core::str::from_utf8(&response[..header_end]).ok()?;
+    let mut lines = header.split("\r\n");
+    let status = lines.next()?;
+    if !status.starts_with("HTTP/") {
+        return None;
+    }
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("Content-Length"))
+        .and_then(|(_, value)| value.trim().parse().ok());
+    Some(HttpResponse { status, content_length, body: &response[header_end + 4..] })
+}
+
+/// nc コマンドで 1 キー入力を処理した結果
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum NcKey {
+    /// 入力中の行に文字を足した（画面にエコーする）
+    Echo(char),
+    /// 入力中の行から 1 文字消した
+    Erase,
+    /// Enter で行が確定した（末尾に "\n" を付けた送信データ）
+    Send(alloc::string::String),
+    /// ESC: 接続を終える
+    Quit,
+    /// 何もしない（矢印キーなど）
+    Ignore,
+}
+
+/// nc コマンドのキー入力を 1 文字処理する。
+///
+/// 矢印キーは "\x1b[" + 文字の 3 文字がまとめてキューに入るので、ESC の直後に '[' が
+/// 続いていたら矢印キーとして読み捨てる。ESC 単体のときだけ Quit になる。
+pub(super) fn nc_feed_key(line: &mut alloc::string::String, c: char) -> NcKey {
+    match c {
+        '\x1b' => {
+            if crate::interrupts::get_key() == Some('[') {
+                let _ = crate::interrupts::get_key();
+                NcKey::Ignore
+            } else {
+                NcKey::Quit
+            }
+        }
+        '\n' | '\r' => {
+            let mut data = core::mem::take(line);
+            data.push('\n');
+            NcKey::Send(data)
+        }
+        '\x08' | '\x7f' => {
+            if line.pop().is_some() { NcKey::Erase } else { NcKey::Ignore }
+        }
+        c if !c.is_control() => {
+            line.push(c);
+            NcKey::Echo(c)
+        }
+        _ => NcKey::Ignore,
+    }
+}
+
+/// "a.b.c.d" 形式の IPv4 アドレスをパースする
+fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut ip = [0u8; 4];
+    let mut parts = s.split('.');
+    for octet in ip.iter_mut() {
+        *octet = parts.next()?.parse().ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(ip)
+}
+
+/// "a.b.c.d/len" 形式のネットワークをパースする（"/len" 省略時は /32）
+fn parse_cidr(s: &str) -> Option<([u8; 4], u8)> {
+    let (ip, len) = match s.split_once('/') {
+        Some((ip, len)) => (ip, len.parse().ok()?),
+        None => (s, 32),
+    };
+    if len > 32 {
+        return None;
+    }
+    Some((parse_ipv4(ip)?, len))
+}
+
+/// "aa:bb:cc:dd:ee:ff" 形式の MAC アドレスをパースする
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut parts = s.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(mac)
+}
+
+/// df の出力行（ヘッダ + マウントごとに 1 行）を作る。
+///
+/// 列は「マウントポイント、種類、全体、使用中、空き（いずれもバイト）、使用率」。
+/// 容量を持たないファイルシステム（procfs など）は数値の列を "-" にする。
+/// selftest が出力を解釈して確かめられるよう、表示とは分けている。
+pub(super) fn df_lines() -> Vec<alloc::string::String> {
+    use alloc::format;
+
+    let mut lines = Vec::new();
+    lines.push(format!(
+        "{:<10} {:<8} {:>14} {:>14} {:>14} {:>5}",
+        "Mounted", "Type", "Total", "Used", "Free", "Use%"
+    ));
+    for mount in crate::vfs::mounts() {
+        match crate::vfs::fs_space(&mount) {
+            Ok((name, Some(space))) => {
+                let used = space.used_bytes();
+                // df と同じく切り上げる（少しでも使っていれば 0% にしない）
+                let percent = if space.total_bytes == 0 {
+                    0
+                } else {
+                    (used * 100).div_ceil(space.total_bytes)
+                };
+                lines.push(format!(
+                    "{:<10} {:<8} {:>14} {:>14} {:>14} {:>4}%",
+                    mount, name, space.total_bytes, used, space.free_bytes, percent
+                ));
+            }
+            Ok((name, None)) => {
+                lines.push(format!("{:<10} {:<8} {:>14} {:>14} {:>14} {:>5}", mount, name, "-", "-", "-", "-"));
+            }
+            Err(e) => {
+                lines.push(format!("{:<10} error: {:?}", mount, e));
+            }
+        }
+    }
+    lines
+}
+
+/// ZIP アーカイブを dest_dir の下に展開し、エントリごとの結果を 1 行ずつ返す。
+///
+/// エントリ名は zip-core の is_safe_path で確かめ、"../" や絶対パスで
+/// dest_dir の外を指すものは書き出さずに skipped とする。
+/// 1 つのエントリが壊れていても（CRC32 の不一致など）残りは展開する。
+/// アーカイブそのものが読めないときだけ Err を返す。
+/// selftest が結果を確かめられるよう、表示とは分けている。
+pub(super) fn unzip_lines(
+    archive: &[u8],
+    dest_dir: &str,
+) -> Result<Vec<alloc::string::String>, sabos_zip_core::ZipError> {
+    use alloc::format;
+
+    let entries = sabos_zip_core::parse_zip(archive)?;
+    let dest_dir = dest_dir.trim_end_matches('/');
+    let mut lines = Vec::new();
+    if let Err(e) = create_dirs(dest_dir) {
+        lines.push(format!("  error: {}: {:?}", dest_dir, e));
+        return Ok(lines);
+    }
+
+    for entry in &entries {
+        let Some(name) = entry.name_str().filter(|n| sabos_zip_core::is_safe_path(n)) else {
+            lines.push(format!("  skipped: {} (unsafe path)", alloc::string::String::from_utf8_lossy(entry.name)));
+            continue;
+        };
+        let path = format!("{}/{}", dest_dir, name.trim_end_matches('/'));
+        if entry.is_dir() {
+            match create_dirs(&path) {
+                Ok(()) => lines.push(format!("   creating: {}/", path)),
+                Err(e) => lines.push(format!("  error: {}: {:?}", path, e)),
+            }
+            continue;
+        }
+
+        // 親ディレクトリのエントリがアーカイブに入っていないこともあるので、先に作っておく
+        if let Some((parent, _)) = path.rsplit_once('/')
+            && let Err(e) = create_dirs(parent)
+        {
+            lines.push(format!("  error: {}: {:?}", parent, e));
+            continue;
+        }
+        let contents = match sabos_zip_core::extract(archive, entry) {
+            Ok(contents) => contents,
+            Err(e) => {
+                lines.push(format!("  error: {}: {:?}", name, e));
+                continue;
+            }
+        };
+        if crate::vfs::node_kind(&path) == Ok(crate::vfs::VfsNodeKind::File) {
+            let _ = crate::vfs::delete_file(&path);
+        }
+        match crate::vfs::create_file(&path, &contents) {
+            Ok(()) => lines.push(format!("  inflating: {} ({} bytes)", path, contents.len())),
+            Err(e) => lines.push(format!("  error: {}: {:?}", path, e)),
+        }
+    }
+    Ok(lines)
+}
+
+/// path とその途中のディレクトリを、なければ作る（mkdir -p）
+fn create_dirs(path: &str) -> Result<(), crate::vfs::VfsError> {
+    let mut current = alloc::string::String::new();
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        current.push('/');
+        current.push_str(component);
+        match crate::vfs::node_kind(&current) {
+            Ok(crate::vfs::VfsNodeKind::Directory) => {}
+            Ok(crate::vfs::VfsNodeKind::File) => return Err(crate::vfs::VfsError::NotADirectory),
+            Err(_) => crate::vfs::create_dir(&current)?,
+        }
+    }
+    Ok(())
+}
+
+/// hexdump の 1 行あたりの既定バイト数
+pub(super) const HEXDUMP_DEFAULT_WIDTH: usize = 16;
+
+/// hexdump の 1 行あたりの最大バイト数（画面幅に収まる程度）
+const HEXDUMP_MAX_WIDTH: usize = 32;
+
+/// バイト列を「オフセット: 16 進 |ASCII|」形式の行にする。
+///
+/// base_offset は bytes[0] のファイル内での位置で、行頭のオフセット表示に使う。
+/// 最後の行が width に満たなくても ASCII 欄の位置がそろうよう空白で埋める。
+pub(super) fn hexdump_lines(bytes: &[u8], base_offset: usize, width: usize) -> Vec<alloc::string::String> {
+    use core::fmt::Write;
+
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for (row, chunk) in bytes.chunks(width).enumerate() {
+        let mut line = alloc::string::String::new();
+        let _ = write!(line, "{:08x}: ", base_offset + row * width);
+        for b in chunk {
+            let _ = write!(line, "{:02x} ", b);
+        }
+        for _ in chunk.len()..width {
+            line.push_str("   ");
+        }
+        line.push_str(" |");
+        for &b in chunk {
+            line.push(if (0x20..0x7F).contains(&b) { b as char } else { '.' });
+        }
+        line.push('|');
+        lines.push(line);
+    }
+    lines
+}
+
+/// run / spawn で読み込む実行ファイルの大きさの上限（ヒープに対する割合の分母）
+///
+/// ELF はパースとロードのためにファイル全体をヒープに置くので、ヒープの 1/4 を超える
+/// ファイルは読み込む前に断る（途中で OOM panic するより、理由の分かるエラーにする）。
+const EXEC_MAX_HEAP_FRACTION: u64 = 4;
+
+/// run / spawn 用に実行ファイルを丸ごと読み込む。失敗したらエラーを表示して None を返す
+pub(super) fn read_executable(filename: &str) -> Option<Vec<u8>> {
+    let limit = (crate::allocator::heap_size() / EXEC_MAX_HEAP_FRACTION) as usize;
+    let result = match crate::vfs::file_size(filename) {
+        Ok(size) if size > limit => {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("Error: {} is too large to load ({} bytes, limit {} bytes)", filename, size, limit);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            return None;
+        }
+        Ok(_) => crate::vfs::read_file(filename),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(data) => Some(data),
+        Err(e) => {
+            framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
+            kprintln!("Error reading file: {:?}", e);
+            framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+            None
+        }
+    }
+}
+
+/// 10 進数または 0x 付きの 16 進数を解釈する
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// タスクの状態の表示名（ps / top 共通）
+fn task_state_str(state: scheduler::TaskState) -> &'static str {
+    match state {
+        scheduler::TaskState::Ready => "Ready",
+        scheduler::TaskState::Running => "Running",
+        scheduler::TaskState::Sleeping(_) => "Sleeping",
+        scheduler::TaskState::Finished => "Finished",
+    }
+}
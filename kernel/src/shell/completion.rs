@@ -0,0 +1,143 @@
+// shell/completion.rs — Tab 補完
+//
+// 行の先頭のトークンはコマンド名、それ以降のトークンはファイルパスとして補完する。
+// 候補が 1 つならそれで確定し、複数あれば候補を一覧表示して共通部分まで補完する。
+//
+// FAT のファイル名は大文字で格納されていて、VFS の検索も大文字小文字を区別しないので、
+// 照合は ASCII の大文字小文字を無視して行い、補完結果は実際のエントリ名の綴りに置き換える。
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{kprint, kprintln};
+
+use super::COMMANDS;
+
+/// 補完の候補
+pub(super) struct Completion {
+    /// 補完対象のトークンが行のどこから始まるか（バイト位置）
+    pub(super) token_start: usize,
+    /// トークンを置き換える候補（パスならディレクトリ部分込み。ディレクトリは末尾 '/' 付き）
+    pub(super) candidates: Vec<String>,
+    /// コマンド名の補完かどうか
+    pub(super) is_command: bool,
+}
+
+impl super::Shell {
+    /// Tab キー: カーソル直前のトークンを補完する。
+    pub(super) fn complete(&mut self) {
+        let Completion { token_start, candidates, is_command } = self.completion_candidates();
+        if candidates.is_empty() {
+            return;
+        }
+
+        let common = common_prefix(&candidates);
+        let mut replacement = String::from(common);
+        // 候補が 1 つに決まったら区切りの空白も入れる（ディレクトリは中を続けて補完できるよう入れない）
+        if candidates.len() == 1 && (is_command || !replacement.ends_with('/')) {
+            replacement.push(' ');
+        }
+
+        // 共通部分が今のトークンより短くなる（大文字小文字だけ違う候補が並ぶ）ときは書き換えない
+        let cursor = self.editor.cursor();
+        if replacement.len() >= cursor - token_start {
+            self.editor.replace_range(token_start..cursor, &replacement);
+        }
+
+        if candidates.len() > 1 {
+            // 候補を一覧表示してから、プロンプトと入力中の行を出し直す
+            kprintln!();
+            for c in &candidates {
+                // パスはディレクトリ部分を省いて名前だけ見せる
+                let name = c.trim_end_matches('/').rsplit('/').next().unwrap_or(c);
+                let suffix = if c.ends_with('/') && !is_command { "/" } else { "" };
+                kprint!("{}{}  ", name, suffix);
+            }
+            kprintln!();
+        }
+        self.redraw_line();
+    }
+
+    /// カーソル直前のトークンに対する補完候補を集める。
+    pub(super) fn completion_candidates(&self) -> Completion {
+        let before = &self.editor.line()[..self.editor.cursor()];
+        let token_start = before.rfind(' ').map(|i| i + 1).unwrap_or(0);
+        let token = &before[token_start..];
+        let is_command = before[..token_start].trim().is_empty();
+
+        let mut candidates: Vec<String> = if is_command {
+            COMMANDS
+                .iter()
+                .filter(|name| starts_with_ignore_case(name, token))
+                .map(|name| String::from(*name))
+                .collect()
+        } else {
+            path_candidates(self.cwd(), token)
+        };
+        candidates.sort();
+        candidates.dedup();
+
+        Completion { token_start, candidates, is_command }
+    }
+
+    /// 相対パスの起点になるディレクトリ。
+    ///
+    /// カーネルシェルにはまだ cd がなく、相対パスは vfs::normalize_path と同じく
+    /// ルートからとして扱う。カレントディレクトリを持つようになったらここを差し替える。
+    fn cwd(&self) -> &str {
+        "/"
+    }
+}
+
+/// パスのトークンを補完する候補を返す。
+///
+/// "SUB/he" なら cwd/SUB を一覧し、"he" で始まるエントリを "SUB/HELLO.TXT" の形で返す。
+/// トークンが '/' で始まれば絶対パスとしてルートから探す。
+fn path_candidates(cwd: &str, token: &str) -> Vec<String> {
+    let (dir_part, name_prefix) = match token.rfind('/') {
+        Some(i) => (&token[..=i], &token[i + 1..]),
+        None => ("", token),
+    };
+    let dir = if dir_part.starts_with('/') {
+        String::from(dir_part)
+    } else {
+        alloc::format!("{}/{}", cwd.trim_end_matches('/'), dir_part)
+    };
+
+    let Ok(entries) = crate::vfs::list_dir(&dir) else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter(|e| e.name != "." && e.name != "..")
+        .filter(|e| starts_with_ignore_case(&e.name, name_prefix))
+        .map(|e| {
+            let slash = if e.kind == crate::vfs::VfsNodeKind::Directory { "/" } else { "" };
+            alloc::format!("{}{}{}", dir_part, e.name, slash)
+        })
+        .collect()
+}
+
+/// ASCII の大文字小文字を無視した前方一致
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// 候補すべてに共通する先頭部分（大文字小文字は無視し、綴りは先頭の候補に合わせる）
+fn common_prefix(candidates: &[String]) -> &str {
+    let first = &candidates[0];
+    let mut len = first.len();
+    for c in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(c.chars())
+            .take_while(|((_, a), b)| a.eq_ignore_ascii_case(b))
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    &first[..len]
+}
@@ -0,0 +1,72 @@
+// shell/jobs.rs — バックグラウンドジョブ（`cmd &`）の管理
+//
+// 行末に `&` を付けて起動したユーザープログラムを、シェルがジョブ番号つきで覚えておく。
+// jobs で一覧、fg <n> で終了を待ち、kill %n で終了させる。
+//
+// ジョブのタスクはシェル（このタスク）の子として spawn するので、
+// 終わったかどうかは waitpid（WNOHANG）で調べ、終わっていればその場で回収する。
+// 回収しないまま放っておくと Finished のタスクがスケジューラに残り続ける。
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::scheduler;
+
+/// バックグラウンドで動かしているジョブ
+pub(super) struct Job {
+    /// ジョブ番号（1 から。%n で指定する）
+    pub number: usize,
+    /// ジョブのタスク ID
+    pub task_id: u64,
+    /// 起動したコマンド行（`&` は除く）
+    pub command: String,
+}
+
+/// シェルが持つジョブの一覧
+#[derive(Default)]
+pub(super) struct JobTable {
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    /// ジョブを登録してジョブ番号を返す。番号は今あるジョブの最大 + 1。
+    pub fn add(&mut self, task_id: u64, command: &str) -> usize {
+        let number = self.jobs.iter().map(|j| j.number).max().unwrap_or(0) + 1;
+        self.jobs.push(Job { number, task_id, command: String::from(command) });
+        number
+    }
+
+    pub fn get(&self, number: usize) -> Option<&Job> {
+        self.jobs.iter().find(|j| j.number == number)
+    }
+
+    pub fn remove(&mut self, number: usize) -> Option<Job> {
+        let pos = self.jobs.iter().position(|j| j.number == number)?;
+        Some(self.jobs.remove(pos))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    /// 終わったジョブを回収して、終了コードと一緒に返す（ジョブ番号の順）
+    pub fn reap_finished(&mut self) -> Vec<(Job, i32)> {
+        let mut finished = Vec::new();
+        let mut i = 0;
+        while i < self.jobs.len() {
+            match scheduler::waitpid(self.jobs[i].task_id, sabos_syscall::WNOHANG) {
+                Ok((0, _)) => i += 1,
+                Ok((_, exit_code)) => finished.push((self.jobs.remove(i), exit_code)),
+                // 子でなくなっている（誰かが先に回収した）なら、もう追えないので消す
+                Err(_) => finished.push((self.jobs.remove(i), -1)),
+            }
+        }
+        finished
+    }
+}
+
+/// ジョブの指定（`%n` または `n`）を番号にする
+pub(super) fn parse_job_spec(spec: &str) -> Option<usize> {
+    let spec = spec.trim();
+    spec.strip_prefix('%').unwrap_or(spec).parse().ok()
+}
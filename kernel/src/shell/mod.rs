@@ -3,23 +3,62 @@
 // キーボードから受け取った文字を行バッファに溜めて、
 // Enter で「コマンド」として解釈・実行する。
 // 簡易的なコマンドラインインターフェースを提供する。
+//
+// 矢印キーは ANSI エスケープシーケンス（ESC [ A/B/C/D）として届く。
+// シリアル端末が送ってくる形式にそろえ、PS/2 キーボードの矢印キーも
+// 割り込みハンドラで同じシーケンスに変換しているので、どちらの入力でも同じに動く。
 
 mod commands;
+mod completion;
+mod jobs;
+mod pipeline;
 mod selftest;
 
+use core::cell::RefCell;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use sabos_line_editor::{Event, LineEditor, RenderSink};
+
 use crate::framebuffer;
 use crate::{kprint, kprintln};
 
+/// シェルのコマンド名の一覧（Tab 補完で使う）。
+/// execute_command() の match に足したらここにも足すこと。
+const COMMANDS: &[&str] = &[
+    "help", "clear", "mem", "page", "ps", "kill", "top", "dmesg", "loglevel", "echo", "usermode", "usertest", "isolate", "elf", "lspci",
+    "ahci", "nvme", "blkread", "blkwrite", "ls", "cat", "hexdump", "write", "rm", "cp", "mv", "unzip", "df", "fsck", "replace", "run", "spawn", "ip",
+    "ifconfig", "linkstatus", "arp", "route", "nc", "http", "selftest", "ipc_bench", "blit_bench", "beep", "play", "panic", "addr2line",
+    "time", "export", "env", "unset", "clip", "jobs", "fg", "shutdown", "reboot", "halt", "exit_qemu", "input",
+];
+
+/// 覚えておくコマンド履歴の件数。古いものから捨てる。
+const HISTORY_MAX: usize = 32;
+
+/// 行エディタの出力先。kprint でフレームバッファとシリアルの両方に出す
+struct ConsoleSink;
+
+impl RenderSink for ConsoleSink {
+    fn write(&mut self, s: &str) {
+        kprint!("{}", s);
+    }
+
+    fn prompt(&mut self) {
+        framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
+        kprint!("sabos> ");
+        framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+    }
+}
+
 /// シェルの状態を管理する構造体。
 pub struct Shell {
-    /// 現在入力中の行バッファ
-    line_buffer: String,
+    /// 入力中の行・カーソル・コマンド履歴（ED.ELF と共通の行エディタ）
+    editor: LineEditor,
     /// メモリ情報（起動時に取得した値を保持）
     usable_mib: u64,
     usable_pages: u64,
+    /// `cmd &` で起動したバックグラウンドジョブ（コマンドは &self で動くので RefCell）
+    jobs: RefCell<jobs::JobTable>,
 }
 
 impl Shell {
@@ -27,55 +66,61 @@ impl Shell {
     /// メモリ情報は起動時にしか取得できないので、ここで受け取って保持する。
     pub fn new(usable_mib: u64, usable_pages: u64) -> Self {
         Self {
-            line_buffer: String::new(),
+            editor: LineEditor::new(HISTORY_MAX),
             usable_mib,
             usable_pages,
+            jobs: RefCell::new(jobs::JobTable::default()),
         }
     }
 
     /// プロンプトを表示する。
     pub fn print_prompt(&self) {
-        framebuffer::set_global_colors((0, 255, 0), (0, 0, 128));
-        kprint!("sabos> ");
-        framebuffer::set_global_colors((255, 255, 255), (0, 0, 128));
+        ConsoleSink.prompt();
     }
 
     /// キーボードから1文字受け取って処理する。
-    /// Enter でコマンド実行、Backspace で1文字削除、
-    /// それ以外は行バッファに追加してエコーバックする。
+    /// 行の編集は edit_line() に任せ、Enter で確定した行をコマンドとして実行する。
     pub fn handle_char(&mut self, c: char) {
-        match c {
-            // Enter: コマンドを実行
-            '\n' => {
-                kprintln!();
-                self.execute_command();
-                self.line_buffer.clear();
-                self.print_prompt();
-            }
-            // Backspace (0x08): 1文字削除
-            '\x08' => {
-                if !self.line_buffer.is_empty() {
-                    self.line_buffer.pop();
-                    // 画面上のカーソルを1文字戻して、その位置を背景色で塗りつぶす。
-                    // '\x08' は framebuffer.rs でバックスペース処理される。
-                    kprint!("\x08");
-                }
-            }
-            // Tab: 無視（将来的にはタブ補完）
-            '\t' => {}
-            // 表示可能な文字: バッファに追加してエコー
-            c if !c.is_control() => {
-                self.line_buffer.push(c);
-                kprint!("{}", c);
+        if let Some(line) = self.edit_line(c) {
+            kprintln!();
+            self.execute_command(&line);
+            self.report_finished_jobs();
+            self.print_prompt();
+        }
+    }
+
+    /// 1 文字ぶん行を編集する。Enter で行が確定したらその内容を返す。
+    ///
+    /// Backspace・左右キー・上下キーの履歴は LineEditor に任せ、Tab で補完する。
+    fn edit_line(&mut self, c: char) -> Option<String> {
+        match self.editor.feed(c, &mut ConsoleSink) {
+            Event::Submit(line) => Some(line),
+            Event::Tab => {
+                self.complete();
+                None
             }
-            // その他の制御文字: 無視
-            _ => {}
+            Event::Pending => None,
         }
     }
 
+    /// プロンプトから行全体を描き直す
+    fn redraw_line(&self) {
+        self.editor.redraw(&mut ConsoleSink);
+    }
+
     /// 行バッファの内容をコマンドとして解釈・実行する。
-    fn execute_command(&self) {
-        let cmd = self.line_buffer.trim();
+    /// 先に `$KEY` を環境変数の値に置き換える。
+    fn execute_command(&self, line: &str) {
+        let line = expand_env_vars(line);
+        self.dispatch_command(&line);
+    }
+
+    /// 展開済みの行をコマンド名と引数に分けて、対応するコマンドを実行する。
+    ///
+    /// time のようにコマンドを入れ子で実行するときはこちらを呼ぶ
+    /// （`$` を含む値が二重に展開されないように）。
+    fn dispatch_command(&self, line: &str) {
+        let cmd = line.trim();
         if cmd.is_empty() {
             return;
         }
@@ -85,36 +130,77 @@ impl Shell {
         let command = parts[0];
         let args = if parts.len() > 1 { parts[1] } else { "" };
 
+        // 行末の `&` はバックグラウンドジョブとして起動する
+        if let Some(rest) = cmd.strip_suffix('&') {
+            self.cmd_background(rest.trim());
+            return;
+        }
+
+        // `|` を含む行はパイプラインとして実行する（time はパイプライン全体を測る）
+        if cmd.contains('|') && command != "time" {
+            self.cmd_pipeline(cmd);
+            return;
+        }
+
         match command {
             "help" => self.cmd_help(),
             "clear" => self.cmd_clear(),
             "mem" => self.cmd_mem(),
             "page" => self.cmd_page(args),
             "ps" => self.cmd_ps(),
+            "kill" => self.cmd_kill(args),
+            "top" => self.cmd_top(),
+            "dmesg" => self.cmd_dmesg(args),
+            "loglevel" => self.cmd_loglevel(args),
             "echo" => self.cmd_echo(args),
             "usermode" => self.cmd_usermode(),
             "usertest" => self.cmd_usertest(),
             "isolate" => self.cmd_isolate(),
             "elf" => self.cmd_elf(),
-            "lspci" => self.cmd_lspci(),
+            "lspci" => self.cmd_lspci(args),
+            "ahci" => self.cmd_ahci(),
+            "nvme" => self.cmd_nvme(),
             "blkread" => self.cmd_blkread(args),
             "blkwrite" => self.cmd_blkwrite(args),
             "ls" => self.cmd_ls(args),
             "cat" => self.cmd_cat(args),
+            "hexdump" => self.cmd_hexdump(args),
             "write" => self.cmd_write(args),
             "rm" => self.cmd_rm(args),
+            "cp" => self.cmd_cp(args),
+            "mv" => self.cmd_mv(args),
+            "unzip" => self.cmd_unzip(args),
+            "df" => self.cmd_df(),
+            "fsck" => self.cmd_fsck(args),
+            "replace" => self.cmd_replace(args),
             "run" => self.cmd_run(args),
             "spawn" => self.cmd_spawn(args),
             "ip" => self.cmd_ip(),
             "linkstatus" => self.cmd_linkstatus(),
+            "arp" => self.cmd_arp(args),
+            "route" => self.cmd_route(args),
+            "ifconfig" => self.cmd_ifconfig(args),
+            "nc" => self.cmd_nc(args),
+            "http" => self.cmd_http(args),
             "selftest" => self.cmd_selftest(args),
             "ipc_bench" => self.cmd_ipc_bench(args),
+            "blit_bench" => self.cmd_blit_bench(args),
             "beep" => self.cmd_beep(args),
-            "panic" => self.cmd_panic(),
+            "play" => self.cmd_play(args),
+            "panic" => self.cmd_panic(args),
+            "addr2line" => self.cmd_addr2line(args),
+            "time" => self.cmd_time(args),
+            "export" => self.cmd_export(args),
+            "env" => self.cmd_env(),
+            "unset" => self.cmd_unset(args),
+            "clip" => self.cmd_clip(args),
+            "jobs" => self.cmd_jobs(),
+            "fg" => self.cmd_fg(args),
             "shutdown" => self.cmd_shutdown(),
             "reboot" => self.cmd_reboot(),
             "halt" => self.cmd_halt(),
             "exit_qemu" => self.cmd_exit_qemu(args),
+            "input" => self.cmd_input(args),
             _ => {
                 framebuffer::set_global_colors((255, 100, 100), (0, 0, 128));
                 kprintln!("Unknown command: {}", command);
@@ -125,6 +211,32 @@ impl Shell {
     }
 }
 
+/// 環境変数の名前に使える文字か（英数字と _）
+fn is_env_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// 行の中の `$KEY` を、シェル（今のタスク）の環境変数 KEY の値に置き換える。
+///
+/// 設定されていない変数は空文字列になる。名前が続かない `$` はそのまま残す。
+fn expand_env_vars(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let name_len = after.find(|c: char| !is_env_name_char(c)).unwrap_or(after.len());
+        if name_len == 0 {
+            out.push('$');
+        } else if let Some(value) = crate::scheduler::get_env_var(&after[..name_len]) {
+            out.push_str(&value);
+        }
+        rest = &after[name_len..];
+    }
+    out.push_str(rest);
+    out
+}
+
 /// rdtsc 命令で TSC (Time Stamp Counter) を読み取る。
 ///
 /// IPC ベンチマーク等のサイクル計測で使用する。
@@ -138,6 +250,41 @@ fn rdtsc() -> u64 {
     ((hi as u64) << 32) | (lo as u64)
 }
 
+/// TSC が 1ms に進むカウント数。最初に tsc_per_ms() を呼んだときに測る（0 = まだ測っていない）。
+static TSC_PER_MS: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+/// TSC の周波数を測るときに数える PIT のティック数（約 110ms）
+const TSC_CALIBRATE_TICKS: u64 = 2;
+
+/// TSC が 1ms に進むカウント数を返す。
+///
+/// PIT のティック（約 55ms）では短いコマンドの時間が測れないので、time コマンドは TSC で測る。
+/// TSC の周波数は CPU ごとに違うため、初回だけティックの境目から
+/// TSC_CALIBRATE_TICKS ティックぶんの TSC の進みを数えて求める。
+fn tsc_per_ms() -> u64 {
+    use core::sync::atomic::Ordering;
+
+    let cached = TSC_PER_MS.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+    let ticks = || crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    // ティックの途中から数えると短く出るので、次のティックの直後から測り始める
+    let t0 = ticks();
+    while ticks() == t0 {
+        x86_64::instructions::hlt();
+    }
+    let start_tick = ticks();
+    let start = rdtsc();
+    while ticks() < start_tick + TSC_CALIBRATE_TICKS {
+        x86_64::instructions::hlt();
+    }
+    let cycles = rdtsc().wrapping_sub(start);
+    let per_ms = (cycles * 182 / (TSC_CALIBRATE_TICKS * 10000)).max(1);
+    TSC_PER_MS.store(per_ms, Ordering::Relaxed);
+    per_ms
+}
+
 /// カーネル側から selftest を実行するための公開関数。
 ///
 /// syscall から呼べるように、最小限の Shell を生成して selftest を実行する。
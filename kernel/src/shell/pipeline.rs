@@ -0,0 +1,235 @@
+// shell/pipeline.rs — パイプライン（cmd1 | cmd2 | ...）
+//
+// `|` で区切った各段をユーザープログラムとして spawn し、隣り合う段の stdout と stdin を
+// パイプでつなぐ（scheduler::spawn_user_redirected）。最後の段の stdout もパイプにして
+// シェルが読み取り、1 行ずつ画面に出す。
+//
+// シェルの組み込みコマンドはタスクではないので、両端でだけ使える:
+// - 先頭: echo / cat。出力を最初の段の stdin に流し込む。
+// - 末尾: grep。最後の段の出力から、パターンを含む行だけを出す。
+// 途中の段に組み込みコマンドは置けない。
+//
+// 子の stdin/stdout には複製したハンドルを渡し、シェルの手元のものはすぐ閉じる
+// （SYS_SPAWN_REDIRECTED と同じ）。こうしておくと、書き込む側の子が終了した時点で
+// 書き込み端がすべて閉じ、読む側に EOF が届く。
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use x86_64::registers::control::Cr3;
+
+use crate::handle::{self, Handle};
+use crate::scheduler;
+use crate::user_ptr::SyscallError;
+
+/// 段をコマンド名と引数に分ける
+fn split_command(stage: &str) -> (&str, &str) {
+    match stage.split_once(' ') {
+        Some((command, args)) => (command, args.trim()),
+        None => (stage, ""),
+    }
+}
+
+/// 先頭に置ける組み込みコマンドなら、その出力を返す
+fn producer_output(stage: &str) -> Option<Result<Vec<u8>, String>> {
+    let (command, args) = split_command(stage);
+    match command {
+        "echo" => {
+            let mut out = Vec::from(args.as_bytes());
+            out.push(b'\n');
+            Some(Ok(out))
+        }
+        "cat" => Some(crate::vfs::read_file(args).map_err(|e| format!("cat: {}: {:?}", args, e))),
+        _ => None,
+    }
+}
+
+/// 末尾に置ける組み込みコマンド
+enum Consumer<'a> {
+    /// パターンを含む行だけを出す
+    Grep(&'a str),
+}
+
+impl Consumer<'_> {
+    fn parse(stage: &str) -> Option<Consumer<'_>> {
+        match split_command(stage) {
+            ("grep", pattern) => Some(Consumer::Grep(pattern)),
+            _ => None,
+        }
+    }
+
+    fn accepts(&self, line: &str) -> bool {
+        match self {
+            Consumer::Grep(pattern) => line.contains(pattern),
+        }
+    }
+}
+
+/// 最後の段の出力を行に切り分け、末尾の組み込みコマンドで絞り込んでから out に渡す
+struct LineSink<'a, 'b> {
+    pending: Vec<u8>,
+    consumer: Option<Consumer<'a>>,
+    out: &'b mut dyn FnMut(&str),
+}
+
+impl LineSink<'_, '_> {
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        // 行の区切りは必ず 1 バイトの '\n' なので、UTF-8 の文字の途中で切れることはない
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            self.emit(&line);
+        }
+    }
+
+    /// 改行で終わっていない最後の行を出す
+    fn finish(&mut self) {
+        if !self.pending.is_empty() {
+            let mut line = core::mem::take(&mut self.pending);
+            line.push(b'\n');
+            self.emit(&line);
+        }
+    }
+
+    fn emit(&mut self, line: &[u8]) {
+        let text = String::from_utf8_lossy(line);
+        if self.consumer.as_ref().is_none_or(|c| c.accepts(text.trim_end_matches('\n'))) {
+            (self.out)(&text);
+        }
+    }
+}
+
+/// パイプラインを実行し、最後の段の出力を 1 行ずつ out に渡す。
+/// すべての段が終わるまで待つ。
+pub(super) fn run(line: &str, out: &mut dyn FnMut(&str)) -> Result<(), String> {
+    let stages: Vec<&str> = line.split('|').map(str::trim).collect();
+    if stages.len() < 2 || stages.iter().any(|s| s.is_empty()) {
+        return Err(String::from("invalid pipeline"));
+    }
+
+    let mut programs = &stages[..];
+    let input = match producer_output(programs[0]) {
+        Some(result) => {
+            programs = &programs[1..];
+            Some(result?)
+        }
+        None => None,
+    };
+    let consumer = match programs.last().and_then(|s| Consumer::parse(s)) {
+        Some(consumer) => {
+            programs = &programs[..programs.len() - 1];
+            Some(consumer)
+        }
+        None => None,
+    };
+    let mut sink = LineSink { pending: Vec::new(), consumer, out };
+
+    // 組み込みコマンドだけのパイプライン（echo hi | grep hi）はタスクを作らずに済ませる
+    if programs.is_empty() {
+        sink.push(&input.unwrap_or_default());
+        sink.finish();
+        return Ok(());
+    }
+
+    // 途中の段で読み込みに失敗して前の段だけが走り出さないよう、先に全部読み込んでおく
+    let mut loaded: Vec<(Vec<&str>, Vec<u8>)> = Vec::with_capacity(programs.len());
+    for stage in programs {
+        let (command, _) = split_command(stage);
+        if producer_output(stage).is_some() || Consumer::parse(stage).is_some() {
+            return Err(format!("{}: built-in commands can only be used at the ends of a pipeline", command));
+        }
+        let elf = super::commands::read_executable(command)
+            .ok_or_else(|| format!("{}: failed to load", command))?;
+        loaded.push((stage.split_whitespace().collect(), elf));
+    }
+
+    // 先頭が組み込みコマンドなら、その出力を流し込むパイプを用意する
+    let input_pipe = input.as_ref().map(|_| handle::create_pipe_handles());
+    let mut stdin = input_pipe.map(|(read, _)| read);
+    let mut task_ids = Vec::with_capacity(loaded.len());
+    let mut spawn_error = None;
+    for (args, elf) in &loaded {
+        let (read, write) = handle::create_pipe_handles();
+        let result = spawn_program(args, elf, stdin, Some(write));
+        // 子には複製を渡したので、手元のハンドルは閉じる
+        if let Some(h) = stdin {
+            let _ = handle::close(&h);
+        }
+        let _ = handle::close(&write);
+        stdin = Some(read);
+        match result {
+            Ok(task_id) => task_ids.push(task_id),
+            Err(e) => {
+                // 残りの段は起動しない。起動済みの段は読み取り端が閉じて BrokenPipe になる
+                spawn_error = Some(e);
+                break;
+            }
+        }
+    }
+
+    if let (Some((_, write)), Some(data)) = (input_pipe, input) {
+        let _ = handle::write(&write, &data);
+        let _ = handle::close(&write);
+    }
+
+    // 最後の段の stdout を EOF まで読む
+    if let Some(output) = stdin {
+        let mut buf = [0u8; 512];
+        loop {
+            match handle::read(&output, &mut buf) {
+                Ok(0) => break,
+                Ok(n) => sink.push(&buf[..n]),
+                Err(SyscallError::WouldBlock) => scheduler::yield_now(),
+                Err(_) => break,
+            }
+        }
+        let _ = handle::close(&output);
+    }
+    sink.finish();
+
+    for task_id in task_ids {
+        let _ = scheduler::wait_for_child(task_id, 0);
+    }
+    spawn_error.map_or(Ok(()), Err)
+}
+
+/// ユーザープログラムを stdin / stdout をつないで spawn し、タスク ID を返す。
+///
+/// ハンドルは複製して子に渡すので、呼び出し元の手元のものはそのまま残る。
+/// None ならコンソールにつながる。パイプラインの各段とバックグラウンドジョブ（jobs.rs）で使う。
+pub(super) fn spawn_program(
+    args: &[&str],
+    elf: &[u8],
+    stdin: Option<Handle>,
+    stdout: Option<Handle>,
+) -> Result<u64, String> {
+    let name = args[0].rsplit('/').next().unwrap_or(args[0]);
+    let close_all = |handles: &[Option<Handle>]| {
+        for h in handles.iter().flatten() {
+            let _ = handle::close(h);
+        }
+    };
+    let child_stdin = stdin.map(|h| handle::duplicate_handle(&h)).transpose();
+    let child_stdout = stdout.map(|h| handle::duplicate_handle(&h)).transpose();
+    let (child_stdin, child_stdout) = match (child_stdin, child_stdout) {
+        (Ok(i), Ok(o)) => (i, o),
+        (i, o) => {
+            close_all(&[i.ok().flatten(), o.ok().flatten()]);
+            return Err(format!("{}: failed to duplicate handle", args[0]));
+        }
+    };
+
+    // selftest が syscall 経由で動いているときはユーザーのページテーブルのままなので、
+    // カーネルのページテーブルに切り替えて spawn する
+    let (current_cr3, current_flags) = Cr3::read();
+    unsafe {
+        crate::paging::switch_to_kernel_page_table();
+    }
+    let result = scheduler::spawn_user_redirected(name, elf, args, child_stdin, child_stdout, None, None);
+    unsafe { Cr3::write(current_cr3, current_flags); }
+
+    result.map_err(|e| {
+        close_all(&[child_stdin, child_stdout]);
+        format!("{}: {}", args[0], e)
+    })
+}
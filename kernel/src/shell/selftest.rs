@@ -72,6 +72,9 @@ impl super::Shell {
             // 3. PCI 列挙のテスト
             run_test("pci_enum", this.test_pci_enum());
 
+            // 3.1. PCI BAR プローブのテスト
+            run_test("pci_bar_probe", this.test_pci_bar_probe());
+
             // 4. procfs のテスト
             run_test("procfs", this.test_procfs());
 
@@ -81,14 +84,31 @@ impl super::Shell {
             // 6. フレームバッファ情報のテスト
             run_test("framebuffer_info", this.test_framebuffer_info());
 
+            // 6.1. ダブルバッファ（present まで画面に出ない）のテスト
+            run_test("framebuffer_double_buffer", this.test_framebuffer_double_buffer());
+            run_test("framebuffer_blit", this.test_framebuffer_blit());
+            run_test("framebuffer_blit_alpha", this.test_framebuffer_blit_alpha());
+            run_test("framebuffer_clip", this.test_framebuffer_clip());
+            run_test("framebuffer_text_scale", this.test_framebuffer_text_scale());
+            run_test("framebuffer_logical_size", this.test_framebuffer_logical_size());
+            run_test("framebuffer_ansi", this.test_framebuffer_ansi());
+            run_test("framebuffer_utf8", this.test_framebuffer_utf8());
+            run_test("mouse_cursor", this.test_mouse_cursor());
+
             // 6.5. マウス初期化のテスト
             run_test("mouse", this.test_mouse());
+            run_test("mouse_wheel", this.test_mouse_wheel());
 
             // 7. ハンドル open/read のテスト
             run_test("handle_open", this.test_handle_open_read());
 
             // 8. スケジューラのテスト
             run_test("scheduler", this.test_scheduler());
+            run_test("shell_kill", this.test_shell_kill());
+            // 割り込みからの wake_task()（SCHEDULER のロック中は起床キューに回す）
+            run_test("wake_queue_stress", this.test_wake_queue_stress());
+            // 期限つきの待機（park_with_timeout が TimedOut / Woken を返す）
+            run_test("park_timeout", this.test_park_timeout());
 
             // 9. ブロックデバイス syscalls のテスト
             run_test("block_syscall", this.test_block_syscall());
@@ -99,35 +119,94 @@ impl super::Shell {
             // 11. 型安全 IPC のテスト
             run_test("ipc_typed", this.test_ipc_typed());
 
+            // IPC で知らせるタイマー（SYS_TIMER_CREATE / CANCEL）のテスト
+            run_test("ipc_timer", this.test_ipc_timer());
+
             // 11.5. 文字列置換ユーティリティのテスト
             run_test("textutil_replace", this.test_textutil_replace());
 
             // 11.7. 文字列検索ユーティリティのテスト
             run_test("textutil_contains", this.test_textutil_contains());
 
+            // 11.8. シェルの行編集（履歴・矢印キー）のテスト
+            run_test("shell_line_edit", this.test_shell_line_edit());
+            run_test("shell_tab_complete", this.test_shell_tab_complete());
+
             // 11.6. exec のテスト（EXIT0.ELF を同期実行）
             run_test("exec_exit0", this.test_exec_exit0());
 
             // 11.65. argc/argv/envp の受け渡しテスト
             run_test("exec_args", this.test_exec_with_args());
 
+            // 環境変数の継承テスト（spawn 前に設定した値が子に見えること）
+            run_test("env_inherit", this.test_env_inherit());
+
+            // spawn 時の環境変数・カレントディレクトリの指定（std の Command::env / current_dir）
+            run_test("spawn_env_cwd", this.test_spawn_env_cwd());
+
+            // シェルの export / env / unset と $KEY の展開のテスト
+            run_test("shell_env", this.test_shell_env());
+
+            // パイプライン（echo | EXIT0.ELF upper | grep）のテスト
+            run_test("shell_pipeline", this.test_shell_pipeline());
+
+            // バックグラウンドジョブ（&, jobs, fg）のテスト
+            run_test("shell_jobs", this.test_shell_jobs());
+
+            // クリップボード（set / get と clip コマンド）のテスト
+            run_test("clipboard", this.test_clipboard());
+
             // 11.8. kill のテスト（自分自身の kill が拒否されること）
             run_test("kill_self_reject", this.test_kill_self_reject());
 
+            // 11.8b. SIG_TERM のテスト（保留フラグが立つだけで即座には終了しないこと）
+            run_test("kill_sigterm", this.test_kill_sigterm());
+
             // 11.9. clock_monotonic のテスト
             run_test("clock_monotonic", this.test_clock_monotonic());
 
             // 11.10. clock_realtime のテスト（CMOS RTC）
             run_test("clock_realtime", this.test_clock_realtime());
 
+            // time コマンド（TSC でコマンドの実行時間を測る）のテスト
+            run_test("time_command", this.test_time_command());
+
             // 11.11. getrandom のテスト
             run_test("getrandom", this.test_getrandom());
+            run_test("getrandom_pool", crate::random::test_entropy_pool());
 
             // 11.11. mmap のテスト（匿名ページの動的マッピング）
             run_test("mmap", this.test_mmap());
+            run_test("mmap_file", this.test_mmap_file());
+            run_test("mmap_file_user", this.test_mmap_file_user());
+            run_test("mprotect", this.test_mprotect());
+            run_test("stack_guard", this.test_stack_guard());
+            run_test("fault_diag", this.test_fault_diag());
+            run_test("task_mem_info", this.test_task_mem_info());
+
+            // スケジューラの統計（SYS_SCHED_STATS）と優先度のテスト
+            run_test("sched_stats", this.test_sched_stats());
 
             // procfs maps テスト
             run_test("procfs_maps", this.test_procfs_maps());
+            // procfs /proc/<pid>/ テスト
+            run_test("procfs_pid", this.test_procfs_pid());
+            // /proc/kmsg（カーネルログのリングバッファ）テスト
+            run_test("procfs_kmsg", this.test_procfs_kmsg());
+            // ログレベル（klog!）の切り替えテスト
+            run_test("klog_levels", this.test_klog_levels());
+            // ロック順序の検査（debug-locks フィーチャ有効時のみ。逆順の lock() が見つかること）
+            #[cfg(feature = "debug-locks")]
+            run_test("lock_order", crate::lock_order::test_lock_order());
+            // パニック時のバックトレース（フレームポインタの鎖を辿る）テスト
+            run_test("backtrace", this.test_backtrace());
+            // 埋め込んだシンボルテーブルでアドレスを関数名に直せるか
+            run_test("symbols", this.test_symbols());
+            // SMP: AP が起動してアイドルループに入ったか（QEMU は -smp 2 で起動する）
+            run_test("smp_ap_idle", this.test_smp_ap_idle());
+            // CPU ごとのデータ（GS ベースで引く PerCpu）のテスト
+            run_test("percpu", this.test_percpu());
+            run_test("percpu_user_gs", this.test_percpu_user_gs());
 
             // VMA 管理のテスト（4項目）
             run_test("vma_insert", this.test_vma_insert());
@@ -138,12 +217,18 @@ impl super::Shell {
             // 11.12. AC97 オーディオコントローラの検出テスト
             run_test("ac97_detect", this.test_ac97_detect());
 
+            // 11.12b. AC97 の PCM ストリーミング再生テスト
+            run_test("ac97_pcm_stream", this.test_ac97_pcm_stream());
+
             // 11.13. Futex のテスト
             run_test("futex", this.test_futex());
 
             // 11.14. スレッド構造体のテスト
             run_test("thread_struct", this.test_thread_struct());
 
+            // 11.14b. スレッドごとの TLS ポインタのテスト
+            run_test("thread_tls", this.test_thread_tls());
+
             // 11.15. IPC cancel のテスト
             run_test("ipc_cancel", this.test_ipc_cancel());
 
@@ -159,9 +244,15 @@ impl super::Shell {
             // 11.17. パイプのテスト
             run_test("pipe", crate::pipe::test_pipe());
 
+            // 子プロセスの終了・kill でパイプの読み手に EOF が届くこと
+            run_test("pipe_child_eof", this.test_pipe_child_eof());
+
             // 11.18. waitpid のテスト（spawn → waitpid で task_id と exit_code を検証）
             run_test("waitpid", this.test_waitpid());
 
+            // 11.18b. 終了コード取得のテスト（exit(3) した子の終了コードを SYS_WAIT で回収）
+            run_test("wait_exit_code", this.test_wait_exit_code());
+
             // 11.19. ACPI テーブル検出のテスト（APIC 情報が取得できること）
             run_test("acpi_detect", crate::acpi::get_apic_info().is_some());
 
@@ -192,20 +283,43 @@ impl super::Shell {
             // 11.6. AHCI セクタ読み取りテスト
             run_test("ahci_read", this.test_ahci_read());
 
+            // 11.6b. AHCI ポート走査テスト（PI の全ポートを調べ、ディスクをすべて登録していること）
+            run_test("ahci_ports", this.test_ahci_ports());
+
             // 11.7. NVMe コントローラ検出テスト
             run_test("nvme_detect", this.test_nvme_detect());
 
             // 11.8. NVMe セクタ読み取りテスト
             run_test("nvme_read", this.test_nvme_read());
 
+            // 11.9. NVMe Identify テスト（ネームスペース 1 の容量が取れること）
+            run_test("nvme_identify", this.test_nvme_identify());
+
             // 12. virtio-blk のテスト
             run_test("virtio_blk", this.test_virtio_blk());
+            // 12.1. blockdev レジストリ（検出した全ディスクに通し番号が付き、セクタ 0 が読めること）
+            run_test("blockdev_registry", this.test_blockdev_registry());
+            run_test("msix_irq", this.test_msix_irq());
 
             // 13. FAT32 のテスト
             run_test("fat32", this.test_fat32());
 
             // 13.5. FAT32 空き容量のテスト
             run_test("fat32_space", this.test_fat32_space());
+            run_test("df", this.test_df());
+            run_test("vfs_copy", this.test_vfs_copy());
+            run_test("vfs_move", this.test_vfs_move());
+            // 13.51. VFS のパス検索キャッシュ（2 回目の読み込みでディレクトリを辿らないこと）
+            run_test("vfs_dentry_cache", this.test_vfs_dentry_cache());
+            run_test("vfs_remove_dir_all", this.test_vfs_remove_dir_all());
+            // 13.52. ファイル全体を読み込まずに少しずつ読む（cat の表示経路）
+            run_test("vfs_read_chunked", this.test_vfs_read_chunked());
+            run_test("hexdump", this.test_hexdump());
+            // 13.53. ZIP の展開（CRC32 の検証と、展開先の外を指す名前の拒否）
+            run_test("unzip", this.test_unzip());
+
+            // 13.55. replace コマンド（ファイル中の文字列置換）のテスト
+            run_test("shell_replace", this.test_shell_replace());
 
             // 13.6. コンソールエディタ (ED.ELF) の存在確認
             run_test("console_editor_elf", this.test_console_editor_elf());
@@ -222,6 +336,9 @@ impl super::Shell {
             // 13.10. ハンドル経由のファイル書き込みテスト
             run_test("handle_write", this.test_handle_write());
 
+            // 13.105. close 前の fsync で書き込みがディスクに届くテスト
+            run_test("handle_fsync", this.test_handle_fsync());
+
             // 13.11. ハンドル経由のシークテスト
             run_test("handle_seek", this.test_handle_seek());
 
@@ -241,16 +358,73 @@ impl super::Shell {
         let run_net = |this: &Self, run_test: &mut dyn FnMut(&str, bool)| {
             // 13.99. DHCP 設定テスト（DHCP で IP が取得されていること）
             run_test("dhcp_config", this.test_dhcp_config());
+            run_test("static_ip_config", this.test_static_ip_config());
             // 14. ARP 解決テスト（ゲートウェイの MAC が解決できること）
             run_test("arp_resolve", this.test_arp_resolve());
+            // 14.0a. フレームキャプチャ（送った ARP リクエストと受けたリプライが読めること）
+            run_test("net_capture", crate::netstack::test_capture());
+            // 14.0b. 静的 ARP エントリが期限切れ処理で消えず、動的エントリは消えること
+            run_test("arp_static_expiry", crate::netstack::test_arp_static_expiry());
+            // 14.0c. ルーティングテーブル（表示の整形、最長一致、追加→削除で元に戻ること）
+            run_test("route_table", this.test_route_table());
+            // 14.0d. IP を設定すると ARP Probe と Gratuitous ARP が送られること
+            run_test("gratuitous_arp", crate::netstack::test_gratuitous_arp());
             // 14.1. ネットワーク DNS テスト（カーネル内 netstack 直接呼び出し）
             run_test("network_dns", this.test_network_dns());
+            // 14.1b. DNS が UDP ソケット API 経由で動くこと（ソケットを漏らさず、他ソケットに混入しない）
+            run_test("dns_socket_path", this.test_dns_socket_path());
+            // 14.1c. DNS レスポンスがトランザクション ID で正しい待機者に振り分けられること
+            run_test("dns_routing", crate::netstack::test_dns_routing());
+            // 14.1d. 2 タスクから同時に別々の名前を引いても、それぞれ答えが返ること
+            run_test("dns_concurrent", this.test_dns_concurrent());
             // 14.2. TCP ISN ランダム化テスト（2 つの接続の ISN が異なること）
             run_test("tcp_isn_random", this.test_tcp_isn_random());
             // 14.3. TCP 再送タイマーテスト（UnackedPacket の記録・クリアが正しく動くこと）
             run_test("tcp_retransmit", this.test_tcp_retransmit());
+            run_test("shell_nc_input", this.test_shell_nc_input());
+            run_test("http_response_parse", this.test_http_response_parse());
+            // 14.3b. シャットダウン時の後始末（各接続に FIN を送って FinWait1 に進むこと）
+            run_test("tcp_drain", crate::netstack::test_tcp_drain());
+            // 14.3c. half-close（SHUT_WR の後も受信でき、送信はエラーになること）
+            run_test("tcp_half_close", crate::netstack::test_tcp_half_close());
+            run_test("tcp_connect6", crate::netstack::test_tcp_connect6());
+            // SLIRP のホスト (fec0::2 → ホストの ::1) と IPv6 で TCP エコー
+            run_test("tcp6_host_echo", crate::netstack::test_tcp6_host_echo());
+            // 14.3d. ウィンドウスケール（65535 バイトを超えて読まずに受け取れること）
+            run_test("tcp_window_scale", crate::netstack::test_tcp_window_scale());
+            // 14.3e. Nagle と遅延 ACK（確認待ちの間の小さい送信がまとまり、NODELAY ならすぐ届くこと）
+            run_test("tcp_nagle", crate::netstack::test_tcp_nagle());
+            // 14.3f. ソケットオプション（SO_RCVTIMEO の読み戻しと受信タイムアウト、SO_REUSEADDR）
+            run_test("sockopt", crate::netstack::test_sockopt());
+            // 14.3g. SO_LINGER の強制切断（RST で接続がすぐ消え、同じポートを使い直せること）
+            run_test("tcp_linger_abort", crate::netstack::test_tcp_linger_abort());
+            // 14.3h. listen の backlog（あふれた接続要求は RST で断られ、accept で枠が空くこと）
+            run_test("tcp_backlog", crate::netstack::test_tcp_backlog());
+            // 14.3i. SYS_SENDFILE（ファイルハンドルの内容がループバックの相手にそのまま届くこと）
+            run_test("sendfile", this.test_sendfile());
+            // 14.3g. 127.0.0.1 のループバック（NIC を通さずに TCP / UDP がやりとりできること）
+            run_test("tcp_loopback", crate::netstack::test_tcp_loopback());
+            run_test("udp_loopback", crate::netstack::test_udp_loopback());
+            // 14.3h. IPv4 のフラグメント化と再構築（MTU を超える UDP がループバックで元どおり届くこと）
+            run_test("ipv4_fragmentation", crate::netstack::test_ipv4_fragmentation());
+            // 14.3j. 重なったフラグメントは組ごと捨てる（オフセットをずらして溜め込ませない）
+            run_test("ipv4_fragment_overlap", crate::netstack::test_ipv4_fragment_overlap());
             // 14.4. IPv6 スタックテスト（偽パケット注入で ICMPv6 Echo Reply 処理を検証）
             run_test("ipv6_stack", this.test_ipv6_stack());
+            // 14.5. SLAAC のアドレス生成テスト（MAC → EUI-64 IID、プレフィックス + IID）
+            run_test("slaac_eui64", this.test_slaac_eui64());
+            // 14.5b. 送信元がリンクローカルでない RA や hop limit が 255 でない RA を無視すること
+            run_test("slaac_ra_validation", this.test_slaac_ra_validation());
+            // 14.6. NDP 近隣キャッシュのテスト（NA 注入で学習し、送信時の解決に使われること）
+            run_test("ndp_cache", this.test_ndp_cache());
+            // 14.6b. NDP 近隣エントリの期限切れ（約 5 分を過ぎたら引けなくなること）
+            run_test("ndp_expiry", crate::netstack::test_ndp_expiry());
+            // 14.7. IPv6 上の UDP テスト（チェックサム付きデータグラムの組み立て・検証と受信経路）
+            run_test("udp_ipv6", this.test_udp_ipv6());
+            // 14.8. 受信リングのあふれ検出と回復（net_poller を止めてフレームを浴びせる）
+            run_test("net_rx_overflow", this.test_net_rx_overflow());
+            // 14.9. net_poller のウォッチドッグ（止まったことを検出して NIC のリングを作り直すこと）
+            run_test("net_watchdog", crate::netstack::test_net_watchdog());
         };
 
         let run_gui = |this: &Self, run_test: &mut dyn FnMut(&str, bool)| {
@@ -267,6 +441,8 @@ impl super::Shell {
             run_test("httpd_service", this.test_httpd_service());
             // 17.5. ルートディレクトリ一覧が取得できることを確認
             run_test("vfs_dirlist", this.test_vfs_dirlist());
+            // 17.6. ディレクトリ一覧の HTML にリンクが並び、名前がエスケープされることを確認
+            run_test("vfs_dirlist_html", this.test_vfs_dirlist_html());
         };
         let run_base = |this: &Self, run_test: &mut dyn FnMut(&str, bool)| {
             run_core(this, run_test);
@@ -407,8 +583,39 @@ impl super::Shell {
             }
         }
 
-        // drop されてメモリが解放されることを期待（明示的なチェックは難しいので省略）
-        true
+        drop(blocks);
+
+        // 大オブジェクト領域で同じ churn を起こし、断片化の統計が筋の通った動きをするか見る。
+        // 他のタスクが途中でヒープを使うと数字がずれるので、割り込みを止めて測る。
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            const SIZE: usize = 8192;
+            let sane = |s: &crate::slab_allocator::HeapStats| {
+                s.used_bytes <= s.total_bytes
+                    && s.largest_free_block <= s.total_bytes - s.used_bytes
+                    && s.free_block_count >= 1
+            };
+            let before = crate::allocator::heap_stats();
+            let mut large: Vec<Vec<u8>> = (0..16).map(|_| vec![0u8; SIZE]).collect();
+            let filled = crate::allocator::heap_stats();
+
+            // 1 つおきに解放して穴を作る。解放しかしていないので最大の空きブロックは縮まない
+            for block in large.iter_mut().step_by(2) {
+                *block = Vec::new();
+            }
+            let holed = crate::allocator::heap_stats();
+            drop(large);
+            let freed = crate::allocator::heap_stats();
+
+            sane(&before)
+                && sane(&filled)
+                && sane(&holed)
+                && sane(&freed)
+                && filled.used_bytes >= before.used_bytes + 16 * SIZE
+                && holed.used_bytes + 8 * SIZE <= filled.used_bytes
+                && freed.used_bytes + 16 * SIZE <= filled.used_bytes
+                && holed.largest_free_block >= filled.largest_free_block
+                && freed.largest_free_block >= holed.largest_free_block
+        })
     }
 
     /// メモリマッピングの整合性テスト
@@ -521,6 +728,147 @@ impl super::Shell {
         false
     }
 
+    /// kill コマンドのテスト
+    ///
+    /// 回り続けるタスクを spawn して kill し、ps の active に数えられない
+    /// （Finished になる）ことを確認する。task 0 とシェル自身の kill は拒否される。
+    fn test_shell_kill(&self) -> bool {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        // kill に失敗したときにタスクを回しっぱなしにしないための停止フラグ
+        static STOP: AtomicBool = AtomicBool::new(false);
+        STOP.store(false, Ordering::SeqCst);
+
+        fn busy_task() {
+            while !STOP.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+        }
+
+        scheduler::spawn("selftest_busy", busy_task);
+        // 一度 CPU を譲って、タスクが実際に走り出してから kill する
+        scheduler::yield_now();
+
+        let find = || {
+            scheduler::task_list()
+                .into_iter()
+                .rev()
+                .find(|t| t.name == "selftest_busy")
+        };
+        let Some(task) = find() else {
+            return false;
+        };
+        let running = task.state != scheduler::TaskState::Finished;
+
+        self.cmd_kill(&alloc::format!("{}", task.id));
+        let killed = find().is_some_and(|t| t.id == task.id && t.state == scheduler::TaskState::Finished);
+        STOP.store(true, Ordering::SeqCst);
+
+        // task 0 と自分自身は kill できない
+        self.cmd_kill("0");
+        self.cmd_kill(&alloc::format!("{}", scheduler::current_task_id()));
+        let self_alive = scheduler::task_list()
+            .iter()
+            .any(|t| t.id == 0 && t.state != scheduler::TaskState::Finished);
+
+        running && killed && self_alive
+    }
+
+    /// 割り込みコンテキストからの起床のストレステスト
+    ///
+    /// 期限なしで眠り続けるタスクをタイマー割り込みのたびに wake_task() で起こしながら、
+    /// こちらは SCHEDULER のロックを取り続けて割り込みとロックをぶつける。
+    /// ロック中の起床は起床キューに回るので、デッドロックせずに起床が届き続けることを確認する。
+    fn test_wake_queue_stress(&self) -> bool {
+        use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+        static WORKER_ID: AtomicU64 = AtomicU64::new(u64::MAX);
+        static WAKES: AtomicU64 = AtomicU64::new(0);
+        static STOP: AtomicBool = AtomicBool::new(false);
+        WORKER_ID.store(u64::MAX, Ordering::SeqCst);
+        WAKES.store(0, Ordering::SeqCst);
+        STOP.store(false, Ordering::SeqCst);
+
+        fn worker() {
+            WORKER_ID.store(scheduler::current_task_id(), Ordering::SeqCst);
+            while !STOP.load(Ordering::SeqCst) {
+                scheduler::sleep_until(u64::MAX);
+                WAKES.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        scheduler::spawn("selftest_waker", worker);
+        for _ in 0..100 {
+            if WORKER_ID.load(Ordering::SeqCst) != u64::MAX {
+                break;
+            }
+            scheduler::yield_now();
+        }
+        let id = WORKER_ID.load(Ordering::SeqCst);
+        if id == u64::MAX {
+            return false;
+        }
+
+        let ticks = || crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+        let queued_before = scheduler::wakes_queued();
+        set_timer_wake_target(Some(id));
+
+        // 10 ティックのあいだ、task_list() で SCHEDULER のロックを取り続ける
+        let start = ticks();
+        while ticks() - start < 10 {
+            core::hint::black_box(scheduler::task_list());
+        }
+        let wakes = WAKES.load(Ordering::SeqCst);
+
+        // 止める。STOP を見る前に眠り直しても次のティックで起こされるよう、
+        // タスクが終わるまでタイマーからの起床は続ける
+        STOP.store(true, Ordering::SeqCst);
+        let finished = (0..200).any(|_| {
+            scheduler::yield_now();
+            scheduler::task_list()
+                .iter()
+                .any(|t| t.id == id && t.state == scheduler::TaskState::Finished)
+        });
+        set_timer_wake_target(None);
+
+        crate::serial_println!(
+            "wake_queue_stress: {} wakes, {} queued from interrupts",
+            wakes,
+            scheduler::wakes_queued() - queued_before
+        );
+        wakes >= 3 && finished
+    }
+
+    /// park_with_timeout(100) が誰にも起こされなければ TimedOut を返し、
+    /// 100ms 分のティック（ms_to_ticks(100)）からあまり遅れずに戻ることを確認する。
+    /// タイマー割り込みから起こしてもらえば、長い期限でも Woken ですぐ戻ることも確認する。
+    fn test_park_timeout(&self) -> bool {
+        use core::sync::atomic::Ordering;
+        use scheduler::WakeReason;
+
+        let ticks = || crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+        let expected = scheduler::ms_to_ticks(100);
+
+        let start = ticks();
+        let reason = scheduler::park_with_timeout(100);
+        let timeout_ticks = ticks() - start;
+        let timed_out = reason == WakeReason::TimedOut
+            && (expected..=expected + 2).contains(&timeout_ticks);
+
+        set_timer_wake_target(Some(scheduler::current_task_id()));
+        let start = ticks();
+        let reason = scheduler::park_with_timeout(10_000);
+        let woken_ticks = ticks() - start;
+        set_timer_wake_target(None);
+        let woken = reason == WakeReason::Woken && woken_ticks <= 3;
+
+        crate::serial_println!(
+            "park_timeout: timed out after {} ticks (expected {}), woken after {} ticks",
+            timeout_ticks, expected, woken_ticks
+        );
+        timed_out && woken
+    }
+
     /// exec のテスト
     /// EXIT0.ELF を同期実行し、正常終了することを確認する
     fn test_exec_exit0(&self) -> bool {
@@ -541,6 +889,256 @@ impl super::Shell {
         )
     }
 
+    /// 環境変数の継承テスト
+    ///
+    /// シェル（自分のタスク）に FOO=bar を設定して EXIT0.ELF を spawn し、
+    /// 子が envp から FOO=bar を読めたこと（終了コード 0）を確認する。
+    /// FOO を消してから spawn した子には見えないこと（終了コード 2）も確かめる。
+    fn test_env_inherit(&self) -> bool {
+        use crate::syscall::{exec_spawn_with_args_for_test, wait_for_test};
+
+        let args = ["/EXIT0.ELF", "getenv", "FOO", "bar"];
+        let run = || match exec_spawn_with_args_for_test("/EXIT0.ELF", &args) {
+            Ok(task_id) => wait_for_test(task_id, 5000),
+            Err(_) => u64::MAX,
+        };
+
+        crate::scheduler::set_env_var("FOO", "bar");
+        let inherited = run();
+        crate::scheduler::remove_env_var("FOO");
+        let removed = run();
+        if inherited != 0 || removed != 2 {
+            kprintln!("    exit codes: with FOO={} without FOO={}", inherited, removed);
+        }
+        inherited == 0 && removed == 2
+    }
+
+    /// 子プロセスが書いて終わったパイプを親が最後まで読めるかのテスト
+    ///
+    /// EXIT0.ELF の stdout をパイプにして終了を待ち、出力をすべて読んだ後は
+    /// WouldBlock ではなく 0（EOF）が返ること、もう一度読んでも 0 のままであることを確かめる。
+    /// kill された子でも、生きている間は WouldBlock、kill の後は EOF になることを確かめる。
+    fn test_pipe_child_eof(&self) -> bool {
+        use crate::handle::{self, Handle};
+        use crate::syscall::wait_for_test;
+        use crate::user_ptr::SyscallError;
+
+        let elf = match crate::vfs::read_file("/EXIT0.ELF") {
+            Ok(elf) => elf,
+            Err(_) => return false,
+        };
+        // stdout をパイプにして spawn し、親の手元の write 端は閉じる
+        let spawn = |args: &[&str]| -> Option<(u64, Handle)> {
+            let (reader, writer) = handle::create_pipe_handles();
+            let spawned = super::pipeline::spawn_program(args, &elf, None, Some(writer));
+            let _ = handle::close(&writer);
+            match spawned {
+                Ok(task_id) => Some((task_id, reader)),
+                Err(_) => {
+                    let _ = handle::close(&reader);
+                    None
+                }
+            }
+        };
+
+        // 1. 書いてから exit する子: 全部読めて、その後は EOF が続く
+        let Some((task_id, reader)) = spawn(&["/EXIT0.ELF"]) else {
+            return false;
+        };
+        let exit_code = wait_for_test(task_id, 5000);
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4];
+        let drained = loop {
+            match handle::read(&reader, &mut buf) {
+                Ok(0) => break true,
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                // 子はもう終わっているので、待たされるのはおかしい
+                Err(_) => break false,
+            }
+        };
+        let eof_again = handle::read(&reader, &mut buf) == Ok(0);
+        let _ = handle::close(&reader);
+        if exit_code != 0 || !drained || !eof_again || data != b"exit0: ok\n" {
+            kprintln!(
+                "    exit: code={} drained={} eof_again={} data={:?}",
+                exit_code, drained, eof_again, core::str::from_utf8(&data)
+            );
+            return false;
+        }
+
+        // 2. kill される子: 生きている間は WouldBlock、kill の後は EOF
+        let Some((task_id, reader)) = spawn(&["/EXIT0.ELF", "sleep", "10000"]) else {
+            return false;
+        };
+        let before_kill = handle::read(&reader, &mut buf);
+        let killed = crate::scheduler::kill_task(task_id).is_ok();
+        wait_for_test(task_id, 1000);
+        let after_kill = handle::read(&reader, &mut buf);
+        let _ = handle::close(&reader);
+        let ok = before_kill == Err(SyscallError::WouldBlock) && killed && after_kill == Ok(0);
+        if !ok {
+            kprintln!("    kill: before={:?} killed={} after={:?}", before_kill, killed, after_kill);
+        }
+        ok
+    }
+
+    /// spawn 時の環境変数・カレントディレクトリ指定のテスト
+    ///
+    /// 指定した環境変数だけが子に見えること（親の FOO は見えない）、
+    /// 指定したカレントディレクトリで子が始まり、指定しなければ親の "/" を継承することを確かめる。
+    fn test_spawn_env_cwd(&self) -> bool {
+        use crate::syscall::{exec_spawn_with_env_for_test, wait_for_test};
+        use alloc::vec;
+
+        let run = |args: &[&str], env: Option<Vec<(String, String)>>, cwd: Option<&str>| {
+            match exec_spawn_with_env_for_test("/EXIT0.ELF", args, env, cwd.map(String::from)) {
+                Ok(task_id) => wait_for_test(task_id, 5000),
+                Err(_) => u64::MAX,
+            }
+        };
+        let env = || Some(vec![(String::from("SPAWN_ENV"), String::from("override"))]);
+
+        crate::scheduler::set_env_var("FOO", "bar");
+        let overridden = run(&["/EXIT0.ELF", "getenv", "SPAWN_ENV", "override"], env(), None);
+        let replaced = run(&["/EXIT0.ELF", "getenv", "FOO", "bar"], env(), None);
+        crate::scheduler::remove_env_var("FOO");
+        let with_cwd = run(&["/EXIT0.ELF", "getcwd", "/proc"], None, Some("/proc"));
+        let inherited_cwd = run(&["/EXIT0.ELF", "getcwd", "/"], None, None);
+
+        let ok = overridden == 0 && replaced == 2 && with_cwd == 0 && inherited_cwd == 0;
+        if !ok {
+            kprintln!(
+                "    exit codes: env={} replaced={} cwd={} inherited_cwd={}",
+                overridden, replaced, with_cwd, inherited_cwd
+            );
+        }
+        ok
+    }
+
+    /// シェルの export / env / unset のテスト
+    ///
+    /// export した変数が env の一覧に出て、`echo $KEY` で値に展開されること、
+    /// unset すると env の一覧から消えることを確認する。
+    /// コマンドの出力は kmsg に残るので、直前に echo した目印より後ろだけを見る。
+    fn test_shell_env(&self) -> bool {
+        let tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+        let value = alloc::format!("shell-env-{}", tick);
+        let entry = alloc::format!("SELFTEST_ENV={}", value);
+
+        self.execute_command(&alloc::format!("export {}", entry));
+        let listed = self.command_output("env");
+        let echoed = self.command_output("echo [$SELFTEST_ENV]");
+        self.execute_command("unset SELFTEST_ENV");
+        let after_unset = self.command_output("env");
+
+        listed.lines().any(|l| l == entry)
+            && echoed.contains(&alloc::format!("[{}]", value))
+            && !after_unset.contains("SELFTEST_ENV=")
+            && crate::scheduler::get_env_var("SELFTEST_ENV").is_none()
+    }
+
+    /// クリップボードのテスト
+    ///
+    /// set した内容が get でそのまま読み戻せること、バッファが足りないと中身の長さを
+    /// 返してコピーしないこと、上限を超える set は拒否して中身を変えないことを確認する。
+    /// 最後に clip コマンドで置き換えた内容が get から見えることも確かめる。
+    fn test_clipboard(&self) -> bool {
+        use crate::clipboard;
+
+        let saved = clipboard::contents();
+        let text = "clipboard \u{3042}\n2nd line";
+        let mut buf = [0u8; 64];
+        let roundtrip = clipboard::set(text.as_bytes()).is_ok()
+            && clipboard::get(&mut buf) == Ok(text.len())
+            && &buf[..text.len()] == text.as_bytes();
+
+        let mut small = [0u8; 4];
+        let too_small = clipboard::get(&mut small) == Err(text.len()) && small == [0; 4];
+
+        let huge = alloc::vec![b'x'; clipboard::CLIPBOARD_MAX + 1];
+        let rejected = clipboard::set(&huge).is_err() && clipboard::contents() == text.as_bytes();
+
+        self.execute_command("clip from shell");
+        let from_shell = clipboard::contents() == b"from shell"
+            && self.command_output("clip").contains("from shell");
+
+        let _ = clipboard::set(&saved);
+        if !(roundtrip && too_small && rejected && from_shell) {
+            kprintln!("    roundtrip={} too_small={} rejected={} from_shell={}", roundtrip, too_small, rejected, from_shell);
+        }
+        roundtrip && too_small && rejected && from_shell
+    }
+
+    /// シェルのコマンドを実行し、その出力を返す（selftest 用）。
+    ///
+    /// コマンドの出力は kmsg に残るので、直前に目印を echo しておき、
+    /// 目印より後ろを切り出す。目印は呼ぶたびに変える。
+    fn command_output(&self, cmd: &str) -> String {
+        static STEP: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+        let step = STEP.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        let tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+        let marker = alloc::format!("selftest-output-marker-{}-{}", tick, step);
+        self.execute_command(&alloc::format!("echo {}", marker));
+        self.execute_command(cmd);
+        let log = crate::kmsg::snapshot();
+        let text = String::from_utf8_lossy(&log);
+        text.rfind(&marker).map(|pos| String::from(&text[pos + marker.len()..])).unwrap_or_default()
+    }
+
+    /// バックグラウンドジョブのテスト
+    ///
+    /// EXIT0.ELF sleep を `&` 付きで起動し、すぐに戻ってきてジョブとして動いていること、
+    /// jobs の一覧に Running で出ることを確認する。fg で終了まで待つと、
+    /// 一覧から消えてタスクも回収されていること、jobs にも出なくなることを確かめる。
+    fn test_shell_jobs(&self) -> bool {
+        self.execute_command("/EXIT0.ELF sleep 300 &");
+        let job = self.jobs.borrow().iter().last().map(|j| (j.number, j.task_id));
+        let Some((number, task_id)) = job else {
+            kprintln!("    no job was started");
+            return false;
+        };
+        let running = crate::scheduler::task_exists(task_id);
+        let listed = self.command_output("jobs");
+        let running_line = alloc::format!("[{}]  Running  /EXIT0.ELF sleep 300", number);
+
+        self.execute_command(&alloc::format!("fg %{}", number));
+        let after_fg = self.command_output("jobs");
+        let job_line = alloc::format!("[{}]", number);
+
+        running
+            && listed.contains(&running_line)
+            && self.jobs.borrow().get(number).is_none()
+            && !crate::scheduler::task_exists(task_id)
+            && !after_fg.contains(&job_line)
+    }
+
+    /// パイプラインのテスト
+    ///
+    /// 組み込みの echo の出力を EXIT0.ELF upper（stdin を大文字にして stdout に書く）に流し、
+    /// 最後の段の出力が大文字になって返ってくることを確認する。
+    /// ユーザープログラムを 2 段つないだ場合、末尾の組み込み grep で絞り込む場合、
+    /// 組み込みコマンドだけの場合も確かめる。
+    fn test_shell_pipeline(&self) -> bool {
+        let run = |line: &str| {
+            let mut output = String::new();
+            let result = super::pipeline::run(line, &mut |text| output.push_str(text));
+            if let Err(e) = &result {
+                kprintln!("    {}: {}", line, e);
+            }
+            result.ok().map(|_| output)
+        };
+
+        let single = run("echo hello pipe | /EXIT0.ELF upper");
+        let chained = run("echo one | /EXIT0.ELF upper | /EXIT0.ELF upper | grep ONE");
+        let filtered = run("echo nope | /EXIT0.ELF upper | grep YES");
+        let builtin_only = run("echo abc | grep b");
+        single.as_deref() == Some("HELLO PIPE\n")
+            && chained.as_deref() == Some("ONE\n")
+            && filtered.as_deref() == Some("")
+            && builtin_only.as_deref() == Some("abc\n")
+            && run("echo x | grep x | /EXIT0.ELF upper").is_none()
+    }
+
     /// PCI 列挙のテスト
     /// バス 0 に 1 つ以上のデバイスが存在することを確認する
     fn test_pci_enum(&self) -> bool {
@@ -564,6 +1162,39 @@ impl super::Shell {
         true
     }
 
+    /// PCI BAR プローブのテスト
+    ///
+    /// virtio-blk の BAR0 をデコードしてベースアドレスとサイズが 0 でないことを確認する。
+    /// プローブで BAR に 0xFFFFFFFF を書くので、終了後に元の値へ戻っていることも確認する
+    /// （戻っていないと後続の virtio_blk テストが壊れたアドレスを叩くことになる）。
+    /// SYS_PCI_READ_BAR が返す起動時のキャッシュも同じ値になっていることを確認する。
+    fn test_pci_bar_probe(&self) -> bool {
+        let dev = match crate::pci::find_all_virtio_blk().into_iter().next() {
+            Some(dev) => dev,
+            None => return false,
+        };
+        let before = crate::pci::read_bar(dev.bus, dev.device, dev.function, 0);
+        let bar = crate::pci::probe_bar(dev.bus, dev.device, dev.function, 0);
+        let after = crate::pci::read_bar(dev.bus, dev.device, dev.function, 0);
+        if before != after {
+            return false;
+        }
+
+        let cached = crate::pci::cached_bar(dev.bus, dev.device, dev.function, 0);
+        match (bar, cached) {
+            // legacy virtio は BAR0 が I/O ポート
+            (Some(bar), Some(cached)) => {
+                bar.base != 0
+                    && bar.size != 0
+                    && bar.kind == crate::pci::BarKind::Io
+                    && cached.base == bar.base
+                    && cached.size == bar.size
+                    && cached.kind == bar.kind
+            }
+            _ => false,
+        }
+    }
+
     /// procfs のテスト（VFS 経由）
     /// /proc の一覧と、/proc/meminfo / /proc/tasks が読めることを確認
     fn test_procfs(&self) -> bool {
@@ -628,10 +1259,10 @@ impl super::Shell {
             return false;
         }
         let blit_buf = [255u8, 255u8, 0u8, 0u8];
-        if crate::framebuffer::draw_blit_global(0, 0, 1, 1, &blit_buf).is_err() {
+        if crate::framebuffer::draw_blit_global(0, 0, 1, 1, &blit_buf, crate::framebuffer::BlitFormat::Native, false).is_err() {
             return false;
         }
-        if crate::framebuffer::draw_text_global(0, 0, (255, 255, 255), (0, 0, 0), "GUI").is_err() {
+        if crate::framebuffer::draw_text_global(0, 0, (255, 255, 255), (0, 0, 0), "GUI", 1).is_err() {
             return false;
         }
 
@@ -645,121 +1276,512 @@ impl super::Shell {
         if crate::framebuffer::draw_line_global(width, 0, width + 1, 1, 0, 0, 0).is_ok() {
             return false;
         }
-        if crate::framebuffer::draw_blit_global(0, 0, 2, 2, &blit_buf).is_ok() {
+        if crate::framebuffer::draw_blit_global(0, 0, 2, 2, &blit_buf, crate::framebuffer::BlitFormat::Native, false).is_ok() {
             return false;
         }
 
         true
     }
 
-    /// フレームバッファ情報のテスト
-    fn test_framebuffer_info(&self) -> bool {
-        let Some(info) = crate::framebuffer::screen_info() else {
+    /// ダブルバッファのテスト
+    ///
+    /// ダブルバッファモード中の描画は MMIO に届かず、present() で初めて
+    /// 画面に反映されることを、ハードウェア側のピクセルを読んで確かめる。
+    fn test_framebuffer_double_buffer(&self) -> bool {
+        let task_id = crate::scheduler::current_task_id();
+        let Some(before) = crate::framebuffer::read_screen_pixel(0, 0) else {
             return false;
         };
-        if info.width == 0 || info.height == 0 {
-            return false;
-        }
-        if info.stride < info.width {
+        if crate::framebuffer::set_double_buffering(task_id, true).is_err() {
             return false;
         }
-        if info.bytes_per_pixel != 4 {
-            return false;
-        }
-        info.pixel_format != 0
-    }
 
-    /// マウス初期化のテスト
-    /// PS/2 マウスが初期化できているかだけを確認する。
-    fn test_mouse(&self) -> bool {
-        crate::mouse::is_initialized()
+        let ok = (|| {
+            if !crate::framebuffer::is_double_buffered() {
+                return false;
+            }
+            // 他のタスクは横取りできない
+            if crate::framebuffer::set_double_buffering(u64::MAX, true)
+                != Err(crate::framebuffer::DrawError::Busy)
+            {
+                return false;
+            }
+            // 全チャンネルを反転した色なら、RGB/BGR どちらでも中央のバイトは必ず変わる
+            if crate::framebuffer::draw_pixel_global(0, 0, !before[0], !before[1], !before[2]).is_err() {
+                return false;
+            }
+            if crate::framebuffer::read_screen_pixel(0, 0) != Some(before) {
+                return false;
+            }
+            if crate::framebuffer::present().is_err() {
+                return false;
+            }
+            crate::framebuffer::read_screen_pixel(0, 0).is_some_and(|after| after != before)
+        })();
+
+        // 失敗しても直接モードに戻しておく（以降の出力が画面に出なくなるため）
+        let restored = crate::framebuffer::set_double_buffering(task_id, false).is_ok()
+            && !crate::framebuffer::is_double_buffered();
+        ok && restored
     }
 
-    /// ハンドル open/read のテスト
-    /// /proc/meminfo と HELLO.TXT を open して読めることを確認
-    fn test_handle_open_read(&self) -> bool {
-        use crate::handle::HANDLE_RIGHT_READ;
+    /// blit の高速パス・低速パスと、画面端でのクリップのテスト
+    ///
+    /// 直接モードなので描画はすぐ MMIO に届く。画面右下の 1 ピクセルに
+    /// draw_pixel_global で描いた結果を「正解」として、blit の結果と比べる。
+    fn test_framebuffer_blit(&self) -> bool {
+        use crate::framebuffer::BlitFormat;
 
-        // /proc/meminfo を open
-        let handle = match crate::syscall::open_path_to_handle("/proc/meminfo", HANDLE_RIGHT_READ) {
-            Ok(h) => h,
-            Err(_) => return false,
+        let Some((width, height)) = crate::framebuffer::screen_size() else {
+            return false;
         };
+        let (cx, cy) = (width - 1, height - 1);
+        let (r, g, b) = (0x12u8, 0x34u8, 0x56u8);
 
-        let mem = match self.read_all_handle(&handle) {
-            Ok(v) => v,
-            Err(_) => {
-                let _ = crate::handle::close(&handle);
-                return false;
-            }
+        if crate::framebuffer::draw_pixel_global(cx, cy, r, g, b).is_err() {
+            return false;
+        }
+        let Some(expected) = crate::framebuffer::read_screen_pixel(cx, cy) else {
+            return false;
         };
-        let _ = crate::handle::close(&handle);
 
-        if !mem.windows(b"\"total_frames\"".len()).any(|w| w == b"\"total_frames\"") {
+        // 低速パス: RGBX からの変換結果が draw_pixel と一致する。
+        // 2x2 を右下隅に置くので、画面内に残るのは左上の 1 ピクセルだけ
+        let rgbx = [r, g, b, 0, 0xFF, 0xFF, 0xFF, 0, 0xFF, 0xFF, 0xFF, 0, 0xFF, 0xFF, 0xFF, 0];
+        if crate::framebuffer::draw_pixel_global(cx, cy, 0, 0, 0).is_err() {
+            return false;
+        }
+        if crate::framebuffer::draw_blit_global(cx, cy, 2, 2, &rgbx, BlitFormat::Rgbx, false).is_err() {
+            return false;
+        }
+        if crate::framebuffer::read_screen_pixel(cx, cy) != Some(expected) {
             return false;
         }
 
-        // HELLO.TXT を open
-        let handle = match crate::syscall::open_path_to_handle("HELLO.TXT", HANDLE_RIGHT_READ) {
-            Ok(h) => h,
-            Err(_) => return false,
-        };
-
-        let hello = match self.read_all_handle(&handle) {
-            Ok(v) => v,
-            Err(_) => {
-                let _ = crate::handle::close(&handle);
-                return false;
-            }
-        };
-        let _ = crate::handle::close(&handle);
+        // 高速パス: ハードウェアの並びのバイト列がそのまま画面に出る
+        let mut native = [0u8; 16];
+        native[..4].copy_from_slice(&expected);
+        if crate::framebuffer::draw_pixel_global(cx, cy, 0, 0, 0).is_err() {
+            return false;
+        }
+        if crate::framebuffer::draw_blit_global(cx, cy, 2, 2, &native, BlitFormat::Native, false).is_err() {
+            return false;
+        }
+        if crate::framebuffer::read_screen_pixel(cx, cy) != Some(expected) {
+            return false;
+        }
 
-        hello.starts_with(b"Hello from FAT32!")
+        // 左上が画面外なら描けない
+        crate::framebuffer::draw_blit_global(width, 0, 1, 1, &native, BlitFormat::Native, false)
+            == Err(crate::framebuffer::DrawError::OutOfBounds)
     }
 
-    /// ハンドル経由のファイル書き込みテスト
+    /// アルファ付き blit のテスト
     ///
-    /// 1. WRITE 権限付きで新規ファイルを open
-    /// 2. "hello" を書き込み
-    /// 3. close（FAT32 にフラッシュ）
-    /// 4. READ で再度 open
-    /// 5. 読み返して "hello" であることを確認
-    /// 6. close
-    /// 7. クリーンアップ（ファイル削除）
-    fn test_handle_write(&self) -> bool {
-        use crate::handle::{HANDLE_RIGHT_READ, HANDLE_RIGHT_WRITE, HANDLE_RIGHT_STAT, HANDLE_RIGHT_SEEK};
+    /// 青い背景に 50% の赤い正方形を重ね、合成結果が (128, 0, 127) になることを確かめる。
+    /// 正方形は右下隅からはみ出させ、完全に透明なピクセルも混ぜておく。
+    fn test_framebuffer_blit_alpha(&self) -> bool {
+        use crate::framebuffer::BlitFormat;
 
-        let test_path = "/HWTEST.TXT";
-        let test_data = b"hello";
-        let write_rights = HANDLE_RIGHT_WRITE | HANDLE_RIGHT_READ | HANDLE_RIGHT_STAT | HANDLE_RIGHT_SEEK;
+        let Some((width, height)) = crate::framebuffer::screen_size() else {
+            return false;
+        };
+        let (cx, cy) = (width - 2, height - 2);
 
-        // 1. WRITE 権限付きで新規ファイルを open
-        let handle = match crate::syscall::open_path_to_handle(test_path, write_rights) {
-            Ok(h) => h,
-            Err(_) => return false,
+        // 期待値: 赤 255 * 128/255 + 青の背景 0 → 128、青 0 + 255 * 127/255 → 127
+        if crate::framebuffer::draw_pixel_global(cx, cy, 128, 0, 127).is_err() {
+            return false;
+        }
+        let Some(blended) = crate::framebuffer::read_screen_pixel(cx, cy) else {
+            return false;
+        };
+        if crate::framebuffer::draw_rect_global(cx, cy, 2, 2, 0, 0, 255).is_err() {
+            return false;
+        }
+        let Some(blue) = crate::framebuffer::read_screen_pixel(cx, cy) else {
+            return false;
         };
 
-        // 2. "hello" を書き込み
-        if crate::handle::write(&handle, test_data).is_err() {
-            let _ = crate::handle::close(&handle);
+        // 4x4 の RGBA。(1, 0) だけ完全に透明、それ以外は 50% の赤
+        let mut square = [0u8; 4 * 4 * 4];
+        for (i, px) in square.chunks_exact_mut(4).enumerate() {
+            let a = if i == 1 { 0 } else { 128 };
+            px.copy_from_slice(&[255, 0, 0, a]);
+        }
+        if crate::framebuffer::draw_blit_global(cx, cy, 4, 4, &square, BlitFormat::Rgbx, true).is_err() {
             return false;
         }
 
-        // 3. close（FAT32 にフラッシュ）
-        if crate::handle::close(&handle).is_err() {
+        crate::framebuffer::read_screen_pixel(cx, cy) == Some(blended)
+            && crate::framebuffer::read_screen_pixel(cx + 1, cy) == Some(blue)
+            && crate::framebuffer::read_screen_pixel(cx + 1, cy + 1) == Some(blended)
+    }
+
+    /// クリップ領域のテスト
+    ///
+    /// 画面左上 4x4 をクリップ領域にして、(2, 2) から 4x4 の矩形と blit を描く。
+    /// クリップ内の (2..4, 2..4) だけが塗られ、外の (4, 4) や (1, 1) は変わらないことを確かめる。
+    fn test_framebuffer_clip(&self) -> bool {
+        use crate::framebuffer::{read_screen_pixel, BlitFormat};
+
+        // 背景を黒に揃えてから基準の色を読み取る
+        if crate::framebuffer::draw_rect_global(0, 0, 8, 8, 0, 0, 0).is_err() {
+            return false;
+        }
+        let (Some(black), Some(inside_before)) = (read_screen_pixel(4, 4), read_screen_pixel(3, 3)) else {
+            return false;
+        };
+        if crate::framebuffer::set_clip(0, 0, 4, 4).is_err() {
             return false;
         }
 
-        // 4. READ で再度 open
-        let handle = match crate::syscall::open_path_to_handle(test_path, HANDLE_RIGHT_READ) {
-            Ok(h) => h,
-            Err(_) => {
-                // クリーンアップ
-                let mut fat32 = crate::fat32::Fat32::new().unwrap();
-                let _ = fat32.delete_file(test_path);
+        let ok = (|| {
+            // 矩形: クリップ外にはみ出す部分は切り捨てられる
+            if crate::framebuffer::draw_rect_global(2, 2, 4, 4, 255, 255, 255).is_err() {
                 return false;
             }
-        };
+            let Some(white) = read_screen_pixel(3, 3) else {
+                return false;
+            };
+            if white == inside_before || read_screen_pixel(4, 4) != Some(black)
+                || read_screen_pixel(1, 1) != Some(black)
+            {
+                return false;
+            }
+
+            // blit: 同じくクリップ内の 2x2 だけが描かれる
+            if crate::framebuffer::draw_rect_global(0, 0, 4, 4, 0, 0, 0).is_err() {
+                return false;
+            }
+            let buf = [0xFFu8; 4 * 4 * 4];
+            if crate::framebuffer::draw_blit_global(2, 2, 4, 4, &buf, BlitFormat::Rgbx, false).is_err() {
+                return false;
+            }
+            if read_screen_pixel(2, 2) != Some(white) || read_screen_pixel(2, 4) != Some(black) {
+                return false;
+            }
+
+            // 完全にクリップ外の描画は何もせず成功する
+            crate::framebuffer::draw_pixel_global(6, 6, 255, 255, 255).is_ok()
+                && read_screen_pixel(6, 6) == Some(black)
+        })();
+
+        crate::framebuffer::clear_clip();
+        ok
+    }
+
+    /// 論理解像度のテスト
+    ///
+    /// 画面の半分の論理解像度を設定すると拡大率が 2 になり、論理座標 (1, 1) の 2x2 の矩形が
+    /// 画面では 4x4 で描かれることを確認する。screen_info() が論理解像度を返すこと、
+    /// 論理解像度の外には描けないこと、起動時に記録した画面モードに今の解像度が
+    /// 含まれることも確かめる。
+    fn test_framebuffer_logical_size(&self) -> bool {
+        use crate::framebuffer::{draw_rect_global, list_modes, read_screen_pixel, screen_info, screen_size, set_logical_size, MAX_FB_MODES};
+        use sabos_syscall::FbMode;
+
+        let Some((width, height)) = screen_size() else {
+            return false;
+        };
+        let mut modes = [FbMode::default(); MAX_FB_MODES];
+        let n = list_modes(&mut modes);
+        let has_current = modes[..n].contains(&FbMode { width: width as u32, height: height as u32 });
+
+        let (lw, lh) = (width / 2, height / 2);
+        if set_logical_size(lw, lh) != Ok(2) {
+            let _ = set_logical_size(0, 0);
+            return false;
+        }
+        // ビューポートは画面中央（奇数の解像度なら 1 ピクセルずれる）
+        let (ox, oy) = ((width - lw * 2) / 2, (height - lh * 2) / 2);
+
+        let ok = (|| {
+            let info_ok = screen_info().is_some_and(|i| i.width as usize == lw && i.height as usize == lh);
+            if draw_rect_global(0, 0, 4, 4, 0, 0, 0).is_err() || draw_rect_global(1, 1, 2, 2, 255, 255, 255).is_err() {
+                return false;
+            }
+            let (Some(black), Some(white)) = (read_screen_pixel(ox, oy), read_screen_pixel(ox + 2, oy + 2)) else {
+                return false;
+            };
+            info_ok
+                && white != black
+                && read_screen_pixel(ox + 5, oy + 5) == Some(white)
+                && read_screen_pixel(ox + 6, oy + 6) == Some(black)
+                && read_screen_pixel(ox + 1, oy + 5) == Some(black)
+                && draw_rect_global(lw, 0, 1, 1, 255, 255, 255).is_err()
+        })();
+
+        let reset = set_logical_size(0, 0) == Ok(1)
+            && screen_info().is_some_and(|i| i.width as usize == width && i.height as usize == height);
+        has_current && ok && reset
+    }
+
+    /// 拡大文字描画のテスト
+    ///
+    /// "HI" を 1 倍と 2 倍で描き、返ってくる幅が 16 → 32 と倍になることを確かめる。
+    /// 'H' のグリフの 1 行目は 0x33（左から 2 ピクセル点灯、2 ピクセル消灯）なので、
+    /// 2 倍では x = 0..4 が前景色、x = 4..8 が背景色になる。
+    /// 8 行目は空なので y = 14 は背景色。
+    fn test_framebuffer_text_scale(&self) -> bool {
+        use crate::framebuffer::{draw_text_global, read_screen_pixel};
+
+        let white = (255, 255, 255);
+        let black = (0, 0, 0);
+        if draw_text_global(0, 0, white, black, "HI", 1) != Ok(16) {
+            return false;
+        }
+        if draw_text_global(0, 0, white, black, "HI", 2) != Ok(32) {
+            return false;
+        }
+        let (Some(fg), Some(bg)) = (read_screen_pixel(0, 0), read_screen_pixel(0, 14)) else {
+            return false;
+        };
+        if fg == bg || read_screen_pixel(3, 1) != Some(fg) || read_screen_pixel(4, 0) != Some(bg) {
+            return false;
+        }
+
+        // 複数行なら最も長い行の幅、拡大率 0 や上限超えはエラー
+        draw_text_global(0, 0, white, black, "A\nABC", 1) == Ok(24)
+            && draw_text_global(0, 0, white, black, "HI", 0).is_err()
+            && draw_text_global(0, 0, white, black, "HI", crate::framebuffer::MAX_TEXT_SCALE + 1).is_err()
+    }
+
+    /// コンソール出力の ANSI エスケープシーケンス（SGR）のテスト
+    ///
+    /// "\x1b[32m" で前景色が緑になり、"ok\x1b[0m" でもとの色に戻ることを確認する。
+    /// kprint! の呼び出しをまたいで分断されたシーケンスと、
+    /// 対応していないシーケンス（読み飛ばされて色が変わらない）も確かめる。
+    fn test_framebuffer_ansi(&self) -> bool {
+        use crate::framebuffer::{global_colors, ANSI_COLORS};
+
+        let Some((fg0, bg0)) = global_colors() else {
+            return false;
+        };
+        kprint!("\x1b[32m");
+        if global_colors() != Some((ANSI_COLORS[2], bg0)) {
+            kprint!("\x1b[0m");
+            return false;
+        }
+        kprint!("ok\x1b[0m");
+        if global_colors() != Some((fg0, bg0)) {
+            return false;
+        }
+
+        // "ESC[" と "41m" が別々の kprint! に分かれても背景色が変わる
+        kprint!("\x1b[");
+        kprint!("41m");
+        let split_ok = global_colors() == Some((fg0, ANSI_COLORS[1]));
+        kprint!("\x1b[0m");
+
+        // private マーカー付き・未知の終端文字は無視される
+        kprint!("\x1b[?25l\x1b[31;99z\n");
+        split_ok && global_colors() == Some((fg0, bg0))
+    }
+
+    /// コンソール出力の UTF-8 のテスト
+    ///
+    /// 2 バイトの "é"（C3 A9）を 1 バイトずつ別の write に分けて出しても、
+    /// カーソルが 1 セルだけ進むことを確認する。不正なバイトも 1 セルの置換文字になる。
+    /// Latin-1 補助と罫線はフォントにあり、U+FFFD は置換グリフで描かれることも確かめる。
+    fn test_framebuffer_utf8(&self) -> bool {
+        use crate::framebuffer::{glyph_for, screen_size, text_cursor, REPLACEMENT_GLYPH};
+        use crate::syscall::write_console_bytes;
+
+        let glyphs_ok = glyph_for('é') != REPLACEMENT_GLYPH
+            && glyph_for('é') != glyph_for('?')
+            && glyph_for('─') != REPLACEMENT_GLYPH
+            && glyph_for('\u{FFFD}') == REPLACEMENT_GLYPH;
+
+        let (Some((width, _)), Some((col, _))) = (screen_size(), text_cursor()) else {
+            return false;
+        };
+        // 行末の折り返しでセルの数え方が変わらないよう、残りが少なければ改行しておく
+        if col + 4 >= width / 8 {
+            kprintln!();
+        }
+        let Some((start, row)) = text_cursor() else {
+            return false;
+        };
+        write_console_bytes(b"\xC3");
+        let pending_ok = text_cursor() == Some((start, row));
+        write_console_bytes(b"\xA9");
+        let one_cell = text_cursor() == Some((start + 1, row));
+        write_console_bytes(b"\xFF");
+        let replaced = text_cursor() == Some((start + 2, row));
+        kprintln!();
+
+        glyphs_ok && pending_ok && one_cell && replaced
+    }
+
+    /// カーネル描画のマウスカーソルのテスト
+    ///
+    /// (20, 20) にカーソルを置いて表示・非表示を切り替え、画面（MMIO）を読んで確かめる。
+    /// 既定の矢印は左上 (0, 0) が黒い縁、(1, 2) が白い塗り。
+    /// 表示中にカーソルの下へ矩形を描いてもカーソルは上に残り、
+    /// 隠すと矩形の色が出てくる（描画内容を壊していない）ことも確認する。
+    fn test_mouse_cursor(&self) -> bool {
+        use crate::framebuffer::{cursor_visible, draw_rect_global, move_cursor, read_screen_pixel, set_cursor_visible};
+
+        let was_visible = cursor_visible();
+        set_cursor_visible(false);
+        if draw_rect_global(16, 16, 32, 32, 0, 128, 0).is_err() {
+            return false;
+        }
+        let Some(green) = read_screen_pixel(21, 22) else {
+            return false;
+        };
+
+        move_cursor(20, 20);
+        set_cursor_visible(true);
+        let shown = cursor_visible()
+            && read_screen_pixel(20, 20).is_some_and(|p| p != green)
+            && read_screen_pixel(21, 22).is_some_and(|p| p != green)
+            && read_screen_pixel(40, 40) == Some(green);
+
+        // カーソルの下に描いても、カーソルは一番上に描き直される
+        let _ = draw_rect_global(16, 16, 32, 32, 128, 0, 0);
+        let red = read_screen_pixel(40, 40);
+        let on_top = red.is_some() && red != Some(green) && read_screen_pixel(20, 20) != red;
+
+        set_cursor_visible(false);
+        let hidden = !cursor_visible()
+            && read_screen_pixel(20, 20) == red
+            && read_screen_pixel(21, 22) == red;
+
+        set_cursor_visible(was_visible);
+        shown && on_top && hidden
+    }
+
+    /// フレームバッファ情報のテスト
+    fn test_framebuffer_info(&self) -> bool {
+        let Some(info) = crate::framebuffer::screen_info() else {
+            return false;
+        };
+        if info.width == 0 || info.height == 0 {
+            return false;
+        }
+        if info.stride < info.width {
+            return false;
+        }
+        if info.bytes_per_pixel != 4 {
+            return false;
+        }
+        info.pixel_format != 0
+    }
+
+    /// マウス初期化のテスト
+    /// PS/2 マウスが初期化できているかだけを確認する。
+    fn test_mouse(&self) -> bool {
+        crate::mouse::is_initialized()
+    }
+
+    /// マウスのホイール・拡張ボタンのテスト
+    ///
+    /// QEMU の PS/2 マウスは IntelliMouse 拡張に対応しているので、初期化後の
+    /// デバイス ID は 3（ホイール）か 4（ホイール + 5 ボタン）になっているはず。
+    /// 合わせて 4 バイトパケットの解釈も確認する。
+    fn test_mouse_wheel(&self) -> bool {
+        use crate::mouse::{decode_packet, DEVICE_ID_FIVE_BUTTON, DEVICE_ID_STANDARD, DEVICE_ID_WHEEL};
+
+        let id = crate::mouse::device_id();
+        if !crate::mouse::is_initialized() || (id != DEVICE_ID_WHEEL && id != DEVICE_ID_FIVE_BUTTON) {
+            return false;
+        }
+
+        // 標準: 4 バイト目は無視。左ボタン、右へ 5、上へ 3（画面座標では -3）
+        let std_ok = decode_packet(&[0x09, 5, 3, 0xFF], DEVICE_ID_STANDARD) == (5, -3, 0x01, 0);
+        // ID 3: 4 バイト目 0xFF（-1 = 奥へ 1 ノッチ）は上スクロール +1
+        let wheel_ok = decode_packet(&[0x08, 0, 0, 0xFF], DEVICE_ID_WHEEL) == (0, 0, 0, 1);
+        // ID 4: 下位 4bit 0x1（手前へ 1）は -1、bit4 / bit5 は 4 / 5 番目のボタン
+        let five_ok = decode_packet(&[0x08, 0, 0, 0x31], DEVICE_ID_FIVE_BUTTON) == (0, 0, 0x18, -1);
+        std_ok && wheel_ok && five_ok
+    }
+
+    /// ハンドル open/read のテスト
+    /// /proc/meminfo と HELLO.TXT を open して読めることを確認
+    fn test_handle_open_read(&self) -> bool {
+        use crate::handle::HANDLE_RIGHT_READ;
+
+        // /proc/meminfo を open
+        let handle = match crate::syscall::open_path_to_handle("/proc/meminfo", HANDLE_RIGHT_READ) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+
+        let mem = match self.read_all_handle(&handle) {
+            Ok(v) => v,
+            Err(_) => {
+                let _ = crate::handle::close(&handle);
+                return false;
+            }
+        };
+        let _ = crate::handle::close(&handle);
+
+        if !mem.windows(b"\"total_frames\"".len()).any(|w| w == b"\"total_frames\"") {
+            return false;
+        }
+
+        // HELLO.TXT を open
+        let handle = match crate::syscall::open_path_to_handle("HELLO.TXT", HANDLE_RIGHT_READ) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+
+        let hello = match self.read_all_handle(&handle) {
+            Ok(v) => v,
+            Err(_) => {
+                let _ = crate::handle::close(&handle);
+                return false;
+            }
+        };
+        let _ = crate::handle::close(&handle);
+
+        hello.starts_with(b"Hello from FAT32!")
+    }
+
+    /// ハンドル経由のファイル書き込みテスト
+    ///
+    /// 1. WRITE 権限付きで新規ファイルを open
+    /// 2. "hello" を書き込み
+    /// 3. close（FAT32 にフラッシュ）
+    /// 4. READ で再度 open
+    /// 5. 読み返して "hello" であることを確認
+    /// 6. close
+    /// 7. クリーンアップ（ファイル削除）
+    fn test_handle_write(&self) -> bool {
+        use crate::handle::{HANDLE_RIGHT_READ, HANDLE_RIGHT_WRITE, HANDLE_RIGHT_STAT, HANDLE_RIGHT_SEEK};
+
+        let test_path = "/HWTEST.TXT";
+        let test_data = b"hello";
+        let write_rights = HANDLE_RIGHT_WRITE | HANDLE_RIGHT_READ | HANDLE_RIGHT_STAT | HANDLE_RIGHT_SEEK;
+
+        // 1. WRITE 権限付きで新規ファイルを open
+        let handle = match crate::syscall::open_path_to_handle(test_path, write_rights) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+
+        // 2. "hello" を書き込み
+        if crate::handle::write(&handle, test_data).is_err() {
+            let _ = crate::handle::close(&handle);
+            return false;
+        }
+
+        // 3. close（FAT32 にフラッシュ）
+        if crate::handle::close(&handle).is_err() {
+            return false;
+        }
+
+        // 4. READ で再度 open
+        let handle = match crate::syscall::open_path_to_handle(test_path, HANDLE_RIGHT_READ) {
+            Ok(h) => h,
+            Err(_) => {
+                // クリーンアップ
+                let mut fat32 = crate::fat32::Fat32::new().unwrap();
+                let _ = fat32.delete_file(test_path);
+                return false;
+            }
+        };
 
         // 5. 読み返して "hello" であることを確認
         let data = match self.read_all_handle(&handle) {
@@ -780,6 +1802,45 @@ impl super::Shell {
         data == test_data
     }
 
+    /// fsync のテスト
+    ///
+    /// 書き込んだハンドルを close せずに fsync し、新しい Fat32 インスタンスから
+    /// 読んで内容が届いていることを確かめる。fsync 後の書き込みはもう一度 fsync すると届くこと、
+    /// パイプのハンドルでは何もせず成功することも確かめる。
+    fn test_handle_fsync(&self) -> bool {
+        use crate::handle::HANDLE_RIGHTS_FILE_RW;
+
+        let test_path = "/FSYNC.TXT";
+        let handle = match crate::syscall::open_path_to_handle(test_path, HANDLE_RIGHTS_FILE_RW) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+        let read_back = || crate::fat32::Fat32::new().ok().and_then(|mut fs| fs.read_file(test_path).ok());
+
+        let _ = crate::handle::write(&handle, b"first");
+        let first_synced = crate::handle::fsync(&handle).is_ok();
+        let after_first = read_back();
+        let _ = crate::handle::write(&handle, b" second");
+        let second_synced = crate::handle::fsync(&handle).is_ok();
+        let after_second = read_back();
+        let _ = crate::handle::close(&handle);
+
+        let (reader, writer) = crate::handle::create_pipe_handles();
+        let pipe_synced = crate::handle::fsync(&writer).is_ok() && crate::handle::fsync(&reader).is_ok();
+        let _ = crate::handle::close(&writer);
+        let _ = crate::handle::close(&reader);
+
+        if let Ok(mut fat32) = crate::fat32::Fat32::new() {
+            let _ = fat32.delete_file(test_path);
+        }
+
+        first_synced
+            && second_synced
+            && pipe_synced
+            && after_first.as_deref() == Some(&b"first"[..])
+            && after_second.as_deref() == Some(&b"first second"[..])
+    }
+
     /// ハンドル経由のシークテスト
     ///
     /// 1. HELLO.TXT を READ + SEEK + STAT 権限で open
@@ -1059,7 +2120,7 @@ impl super::Shell {
         while crate::ipc::try_recv(task_id).is_some() {}
 
         let data = b"ping";
-        if crate::ipc::send(task_id, task_id, data.to_vec()).is_err() {
+        if crate::ipc::send(task_id, task_id, data).is_err() {
             return false;
         }
 
@@ -1068,7 +2129,7 @@ impl super::Shell {
             Err(_) => return false,
         };
 
-        msg.data == data
+        msg.data[..] == data[..]
     }
 
     /// 型安全 IPC のテスト
@@ -1094,6 +2155,66 @@ impl super::Shell {
         msg.sender == task_id && msg.data == data
     }
 
+    /// IPC で知らせるタイマーのテスト
+    ///
+    /// 100ms のワンショットタイマーを作り、TIMER_IPC_SENDER からのメッセージが
+    /// おおよそ 100ms 後（ティックの粒度で 1〜10 ティック）に届くこと、中身がそのタイマーの
+    /// ID であること、発火後にタイマーが消えていることを確認する。
+    /// 止めたタイマーからは届かないこと、1 タスクあたりの上限を超えて作れないことも確かめる。
+    fn test_ipc_timer(&self) -> bool {
+        use crate::timer;
+        use core::sync::atomic::Ordering;
+        use sabos_syscall::TIMER_IPC_SENDER;
+
+        let task_id = crate::scheduler::current_task_id();
+        while crate::ipc::try_recv(task_id).is_some() {}
+        let ticks = || crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+
+        let start = ticks();
+        let id = match timer::create(task_id, 100, false) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+        let msg = crate::ipc::recv_from(task_id, TIMER_IPC_SENDER, 2000);
+        let elapsed = ticks() - start;
+        let fired = match msg {
+            Ok(msg) => {
+                msg.data.len() == 16
+                    && msg.data[..8] == id.to_le_bytes()
+                    && msg.data[8..] == 1u64.to_le_bytes()
+            }
+            Err(_) => false,
+        };
+        let on_time = (1..=10).contains(&elapsed);
+        let removed = timer::count_for(task_id) == 0;
+
+        // 周期タイマーを止めたら、それ以降は届かない
+        let cancelled = match timer::create(task_id, 50, true) {
+            Ok(id) => {
+                timer::cancel(task_id, id).is_ok()
+                    && timer::cancel(task_id, id).is_err()
+                    && crate::ipc::recv_from(task_id, TIMER_IPC_SENDER, 300).is_err()
+            }
+            Err(_) => false,
+        };
+
+        // 上限まで作れて、その次は断られる
+        let mut ids = Vec::new();
+        while let Ok(id) = timer::create(task_id, 60_000, false) {
+            ids.push(id);
+        }
+        let limited = ids.len() == timer::MAX_TIMERS_PER_TASK;
+        timer::cleanup_task(task_id);
+
+        if !(fired && on_time && removed && cancelled && limited) {
+            kprintln!(
+                "    fired={} elapsed={} ticks removed={} cancelled={} created={}",
+                fired, elapsed, removed, cancelled, ids.len()
+            );
+        }
+        fired && on_time && removed && cancelled && limited
+    }
+
     /// IPC cancel のテスト
     ///
     /// cancel_recv が正しく Cancelled エラーを返すことを確認する。
@@ -1176,6 +2297,131 @@ impl super::Shell {
         changed && out == "heLLo"
     }
 
+    /// シェルの行編集のテスト
+    ///
+    /// 使い捨ての Shell に文字を流し込み、コマンドは実行せずに edit_line() の結果を見る。
+    /// 上下キーで履歴を辿れること、左右キーで行の途中に挿入・削除できること、
+    /// 履歴が HISTORY_MAX 件で古いものから捨てられることを確認する。
+    fn test_shell_line_edit(&self) -> bool {
+        use super::{Shell, HISTORY_MAX};
+
+        fn feed(sh: &mut Shell, keys: &str) -> Option<String> {
+            let mut done = None;
+            for c in keys.chars() {
+                if let Some(line) = sh.edit_line(c) {
+                    done = Some(line);
+                }
+            }
+            done
+        }
+        const UP: &str = "\x1b[A";
+        const DOWN: &str = "\x1b[B";
+        const LEFT: &str = "\x1b[D";
+
+        let mut sh = Shell::new(0, 0);
+        let mut ok = feed(&mut sh, "ls\n").as_deref() == Some("ls")
+            && feed(&mut sh, "pwd\n").as_deref() == Some("pwd")
+            // 直前と同じ行と空行は履歴に積まない
+            && feed(&mut sh, "pwd\n").is_some()
+            && feed(&mut sh, "\n").is_some()
+            && sh.editor.history().len() == 2;
+
+        // 入力途中で上キー → 新しい順に辿り、一番古いところで止まる。下キーで元の入力に戻る
+        feed(&mut sh, "ec");
+        feed(&mut sh, UP);
+        ok &= sh.editor.line() == "pwd";
+        feed(&mut sh, UP);
+        feed(&mut sh, UP);
+        ok &= sh.editor.line() == "ls";
+        feed(&mut sh, DOWN);
+        ok &= sh.editor.line() == "pwd";
+        feed(&mut sh, DOWN);
+        ok &= sh.editor.line() == "ec" && sh.editor.cursor() == 2;
+
+        // 左キーで戻って途中に挿入・削除する（ESC [ 1 ; 5 D のような修飾付きも左キー扱い）
+        feed(&mut sh, "ho");
+        feed(&mut sh, LEFT);
+        feed(&mut sh, "\x1b[1;5D");
+        feed(&mut sh, "X\x08Y");
+        ok &= sh.editor.line() == "ecYho" && sh.editor.cursor() == 3;
+        feed(&mut sh, "\x1b[C\x1b[C");
+        ok &= sh.editor.cursor() == 5;
+        ok &= feed(&mut sh, "\n").as_deref() == Some("ecYho") && sh.editor.history().len() == 3;
+
+        // 件数の上限を超えたら古いものから捨てる
+        for i in 0..HISTORY_MAX + 5 {
+            feed(&mut sh, &alloc::format!("cmd{}\n", i));
+        }
+        ok &= sh.editor.history().len() == HISTORY_MAX
+            && sh.editor.history().front().map(String::as_str) == Some("cmd5");
+
+        kprintln!();
+        ok
+    }
+
+    /// シェルの Tab 補完のテスト
+    ///
+    /// 候補が 1 つのコマンドは空白付きで確定し、複数なら共通部分まで補完する。
+    /// 空行では全コマンドが候補になる。ファイルパスは大文字小文字を無視して
+    /// ルートの HELLO.TXT に補完されること（相対・絶対の両方）を確認する。
+    fn test_shell_tab_complete(&self) -> bool {
+        use super::{Shell, COMMANDS};
+
+        fn complete(keys: &str) -> Shell {
+            let mut sh = Shell::new(0, 0);
+            for c in keys.chars() {
+                sh.edit_line(c);
+            }
+            sh.edit_line('\t');
+            sh
+        }
+
+        let ok = complete("sel").editor.line() == "selftest "
+            && complete("").completion_candidates().candidates.len() == COMMANDS.len()
+            && {
+                let sh = complete("bl");
+                sh.editor.line() == "bl" && sh.completion_candidates().candidates.len() == 3
+            }
+            && complete("blkw").editor.line() == "blkwrite "
+            && complete("cat hello.t").editor.line() == "cat HELLO.TXT "
+            && complete("cat /hello.t").editor.line() == "cat /HELLO.TXT "
+            && complete("cat /no_such_dir/x").editor.line() == "cat /no_such_dir/x";
+
+        kprintln!();
+        ok
+    }
+
+    /// replace コマンドのテスト
+    ///
+    /// ファイルを作って replace を実行し、ディスク上の内容を読み直して確かめる。
+    /// -i なしでは書き換わらないこと、-g なしでは各行の最初の一致だけ、
+    /// -g ありでは行内のすべての一致が置換されることを確認する。
+    fn test_shell_replace(&self) -> bool {
+        let path = "/RPLTEST.TXT";
+        let _ = crate::vfs::delete_file(path);
+        if crate::vfs::create_file(path, b"foo bar foo\nbaz foo\n").is_err() {
+            return false;
+        }
+        let read = || crate::vfs::read_file(path).unwrap_or_default();
+
+        // プレビューだけではディスクの内容は変わらない
+        self.cmd_replace("foo qux /RPLTEST.TXT");
+        let mut ok = read() == b"foo bar foo\nbaz foo\n";
+
+        self.cmd_replace("-i foo qux /RPLTEST.TXT");
+        ok &= read() == b"qux bar foo\nbaz qux\n";
+
+        self.cmd_replace("-i -g o 0 /RPLTEST.TXT");
+        ok &= read() == b"qux bar f00\nbaz qux\n";
+
+        // 一致しなければファイルはそのまま
+        self.cmd_replace("-i -g nothing x /RPLTEST.TXT");
+        ok &= read() == b"qux bar f00\nbaz qux\n";
+
+        let _ = crate::vfs::delete_file(path);
+        ok
+    }
+
     /// textutil の contains_literal テスト
     fn test_textutil_contains(&self) -> bool {
         // 通常マッチ
@@ -1210,6 +2456,58 @@ impl super::Shell {
         crate::scheduler::kill_task(my_id).is_err()
     }
 
+    /// SIG_TERM のテスト
+    ///
+    /// SIG_TERM を受けたタスクはすぐには殺されず、保留フラグが立つだけであることを確認する。
+    /// テスト用タスクはフラグをポーリングし、気づいたら自分で終了する
+    /// （サービスが後始末してから終わる流れの最小版）。
+    fn test_kill_sigterm(&self) -> bool {
+        use core::sync::atomic::{AtomicBool, Ordering};
+        use crate::scheduler;
+        use sabos_syscall::SIG_TERM;
+
+        static SIGTERM_SEEN: AtomicBool = AtomicBool::new(false);
+        SIGTERM_SEEN.store(false, Ordering::SeqCst);
+
+        fn sigterm_task() {
+            // 最大 ~5 秒待つ（テストが失敗してもタスクが残り続けないように）
+            for _ in 0..100 {
+                if scheduler::take_pending_signals() & (1 << SIG_TERM) != 0 {
+                    SIGTERM_SEEN.store(true, Ordering::SeqCst);
+                    return;
+                }
+                scheduler::sleep_ms(50);
+            }
+        }
+
+        scheduler::spawn("selftest_sigterm", sigterm_task);
+        let task_id = match scheduler::find_task_id_by_name("selftest_sigterm") {
+            Some(id) => id,
+            None => return false,
+        };
+
+        // 送信直後の状態をプリエンプションなしで確認する。
+        // 割り込みを止めないと、確認前に子タスクがフラグを消費して終了しうる。
+        let (sent, alive, pending) = x86_64::instructions::interrupts::without_interrupts(|| {
+            let sent = scheduler::send_signal(task_id, SIG_TERM).is_ok();
+            let alive = scheduler::task_exists(task_id);
+            let pending = scheduler::peek_pending_signals(task_id).unwrap_or(0);
+            (sent, alive, pending)
+        });
+        if !sent || !alive || pending & (1 << SIG_TERM) == 0 {
+            return false;
+        }
+
+        // タスクがフラグに気づいて自発的に終了するのを待つ
+        for _ in 0..100 {
+            if !scheduler::task_exists(task_id) {
+                return SIGTERM_SEEN.load(Ordering::SeqCst);
+            }
+            scheduler::sleep_ms(10);
+        }
+        false
+    }
+
     /// SYS_CLOCK_MONOTONIC のテスト
     /// 起動からの経過時間が 0 より大きいことを確認する。
     /// また、2回呼んで2回目が1回目以上であること（単調増加）を確認する。
@@ -1237,6 +2535,15 @@ impl super::Shell {
         secs >= 1577836800 && secs < 4102444800
     }
 
+    /// time コマンドで `echo hi` を測り、かかった時間が小さな値（1 秒未満）になることを確認する。
+    /// 1 回目は TSC の周波数を測るが、その時間は計測に含めないので 2 回とも小さいはず。
+    fn test_time_command(&self) -> bool {
+        let first = self.time_command("echo hi");
+        let second = self.time_command("echo hi");
+        kprintln!("    echo hi: {} us, {} us", first, second);
+        first < 1_000_000 && second < 1_000_000
+    }
+
     /// SYS_GETRANDOM のテスト
     /// RDRAND 命令でランダムバイトが生成されることを確認する。
     /// 8 バイトを生成して、全てゼロでないことを確認する。
@@ -1323,12 +2630,278 @@ impl super::Shell {
         written_ok && unmap_ok
     }
 
+    /// ファイルマッピングのテスト
+    ///
+    /// HELLO.TXT をプロセスのページテーブルにマッピングし、
+    /// そのページテーブルで仮想アドレスを辿った先にファイルの中身があること、
+    /// ファイル末尾より後ろがゼロで埋まっていることを確認する。
+    /// あわせて MAP_FIXED の判定に使う VmaList::is_range_free も確認する。
+    fn test_mmap_file(&self) -> bool {
+        use crate::handle::HANDLE_RIGHT_READ;
+        use crate::vma::{Vma, VmaKind, VmaList, VmaProt};
+        use x86_64::VirtAddr;
+
+        let handle = match crate::syscall::open_path_to_handle("HELLO.TXT", HANDLE_RIGHT_READ) {
+            Ok(h) => h,
+            Err(_) => return false,
+        };
+        let data = crate::handle::read_range(&handle, 0, 4096);
+        let _ = crate::handle::close(&handle);
+        let data = match data {
+            Ok(d) if !d.is_empty() && d.len() < 4096 => d,
+            _ => return false,
+        };
+
+        let l4_frame = crate::paging::create_process_page_table();
+        let virt_addr = VirtAddr::new(0x100_0000_0000);
+        let allocated = crate::paging::map_file_pages_in_process(l4_frame, virt_addr, 1, false, &data);
+
+        // マッピングした仮想アドレスを辿って、物理フレーム上の中身を読む
+        let mapped_ok = match crate::paging::translate_in_process(l4_frame, virt_addr) {
+            Some(phys) if allocated.len() == 1 => {
+                let page = unsafe { core::slice::from_raw_parts(phys.as_u64() as *const u8, 4096) };
+                page.starts_with(b"Hello from FAT32!")
+                    && page[..data.len()] == data[..]
+                    && page[data.len()..].iter().all(|&b| b == 0)
+            }
+            _ => false,
+        };
+
+        crate::paging::unmap_pages_in_process(l4_frame, virt_addr, 1);
+        crate::paging::destroy_process_page_table(l4_frame);
+
+        // MAP_FIXED: 既存の VMA と重なる範囲は空いていない、隣接は空いている
+        let mut list = VmaList::new();
+        let _ = list.insert(Vma {
+            start: 0x100_0000_1000,
+            end: 0x100_0000_3000,
+            prot: VmaProt::read_only(),
+            kind: VmaKind::File,
+            name: alloc::string::String::from("HELLO.TXT"),
+        });
+        let fixed_ok = !list.is_range_free(0x100_0000_2000, 0x100_0000_4000)
+            && !list.is_range_free(0x100_0000_0000, 0x100_0000_4000)
+            && list.is_range_free(0x100_0000_0000, 0x100_0000_1000)
+            && list.is_range_free(0x100_0000_3000, 0x100_0000_5000);
+
+        mapped_ok && fixed_ok
+    }
+
+    /// ユーザー空間からのファイル版 mmap のテスト
+    ///
+    /// EXIT0.ELF を "mmapfile" 付きで spawn する。子は /HELLO.TXT を mmap_file で
+    /// マッピングし、read で読んだ中身と一致すれば終了コード 0 で終わる
+    /// （2 = syscall の失敗、3 = マッピングの中身が違う）。
+    fn test_mmap_file_user(&self) -> bool {
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "mmapfile"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        (ret as i64) >= 0 && ret as u32 as i32 == 0
+    }
+
+    /// mprotect のテスト
+    ///
+    /// EXIT0.ELF を "mprotect" 付きで spawn する。子は書き込んだページを
+    /// 読み取り専用にしてから書き込むので、usertest と同じように
+    /// ページフォルトで安全に強制終了され、終了コードが -1 になるはず。
+    /// （2 = syscall の失敗、3 = 読み取り専用のはずのページに書けてしまった）
+    fn test_mprotect(&self) -> bool {
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "mprotect"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        (ret as i64) >= 0 && ret as u32 as i32 == -1
+    }
+
+    /// ユーザースタックのガードページのテスト
+    ///
+    /// EXIT0.ELF を "overflow" 付きで spawn する。子は深い再帰でスタックを
+    /// 使い切るので、スタック直下のガードページでページフォルトが起き、
+    /// 終了コード -1 で安全に終了するはず（3 = 再帰から戻ってきてしまった）。
+    /// 子が終わった後もこのタスク（シェル側）が動き続けていること自体が
+    /// 「制御がシェルに戻る」ことの確認になる。
+    fn test_stack_guard(&self) -> bool {
+        // ガードページの範囲判定（スタックの最下位ページは含まない）
+        if !crate::usermode::is_stack_guard_addr(0x2000000 - 8)
+            || crate::usermode::is_stack_guard_addr(0x2000000)
+        {
+            return false;
+        }
+
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "overflow"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        (ret as i64) >= 0 && ret as u32 as i32 == -1
+    }
+
+    /// 例外の診断表示のテスト
+    ///
+    /// EXIT0.ELF を "wildwrite" 付きで spawn する。子はマップされていないアドレスに
+    /// 書き込むので、終了コード -1 で終わり、カーネルログに
+    /// "user write to non-present page" という分類とタスク名が残っているはず。
+    fn test_fault_diag(&self) -> bool {
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "wildwrite"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        let log = crate::kmsg::snapshot();
+        let text = String::from_utf8_lossy(&log);
+        let task_line = alloc::format!("  Task: {} (", task_id);
+        (ret as i64) >= 0
+            && ret as u32 as i32 == -1
+            && text.contains("Page fault in user mode: user write to non-present page")
+            && text.contains("Accessed address (CR2): 0x500000000000")
+            && text.contains(&task_line)
+    }
+
+    /// プロセスごとのメモリ使用量（SYS_GET_TASK_INFO）のテスト
+    ///
+    /// EXIT0.ELF を "meminfo" 付きで spawn する。子は 10 ページを mmap して
+    /// mmap_frames がちょうど 10 増えること、munmap で元に戻ることを確認し、
+    /// 成功なら終了コード 0 で終わる（2 = syscall 失敗, 3 / 4 = 数が合わない）。
+    fn test_task_mem_info(&self) -> bool {
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "meminfo"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        (ret as i64) >= 0 && ret as u32 as i32 == 0
+    }
+
+    /// スケジューラの統計と優先度のテスト
+    ///
+    /// yield し続けるタスクを spawn し、こちらも 5 回 yield すると、コンテキストスイッチと
+    /// yield による切り替えの回数がそれぞれ 5 以上増えることを確認する。カーネルからは
+    /// 相手の優先度を変えられ、範囲外の値は断られることも確かめる。
+    /// 最後に EXIT0.ELF を "sched" 付きで spawn し、ユーザーから見た統計と優先度の制限を試す
+    /// （終了コード 0 で成功。2 = 統計が読めない, 3 = 優先度の結果が違う）。
+    fn test_sched_stats(&self) -> bool {
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        static STOP: AtomicBool = AtomicBool::new(false);
+        STOP.store(false, Ordering::SeqCst);
+
+        fn yield_task() {
+            while !STOP.load(Ordering::SeqCst) {
+                scheduler::yield_now();
+            }
+        }
+
+        const YIELDS: u64 = 5;
+        scheduler::spawn("selftest_yield", yield_task);
+        let before = scheduler::sched_stats();
+        for _ in 0..YIELDS {
+            scheduler::yield_now();
+        }
+        let after = scheduler::sched_stats();
+
+        let helper = scheduler::task_list()
+            .into_iter()
+            .rev()
+            .find(|t| t.name == "selftest_yield")
+            .map(|t| t.id);
+        let me = scheduler::current_task_id();
+        let priority = helper.is_some_and(|id| {
+            scheduler::set_priority(me, id, sabos_syscall::PRIORITY_MIN).is_ok()
+                && scheduler::get_priority(id) == Some(sabos_syscall::PRIORITY_MIN)
+                && scheduler::set_priority(me, id, sabos_syscall::PRIORITY_MAX + 1).is_err()
+        });
+        STOP.store(true, Ordering::SeqCst);
+
+        let switched = after.context_switches >= before.context_switches + YIELDS
+            && after.yield_switches >= before.yield_switches + YIELDS;
+
+        let me_arg = alloc::format!("{}", me);
+        let user = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "sched", &me_arg],
+        ) {
+            Ok(task_id) => crate::syscall::wait_for_test(task_id, 5000),
+            Err(_) => u64::MAX,
+        };
+
+        if !(switched && priority && user == 0) {
+            kprintln!(
+                "    switches {} -> {} (yield {} -> {}) priority={} user exit={}",
+                before.context_switches, after.context_switches,
+                before.yield_switches, after.yield_switches, priority, user as i64
+            );
+        }
+        switched && priority && user == 0
+    }
+
     /// AC97 オーディオコントローラの検出テスト。
     /// AC97 ドライバが正常に初期化されていることを確認する。
     fn test_ac97_detect(&self) -> bool {
         crate::ac97::is_available()
     }
 
+    /// AC97 の PCM ストリーミング再生テスト。
+    /// BD 2 つにまたがる短いランプ波（ノコギリ波）をキューに入れ、
+    /// DMA が全フレームを消費した（consumed が queued に追いついた）と
+    /// ドライバが報告することを確認する。
+    fn test_ac97_pcm_stream(&self) -> bool {
+        // 1 BD は 4096 フレームなので、6000 フレームで 2 つ目の BD まで使う（48kHz で約 125ms）
+        const FRAMES: usize = 6000;
+        let mut pcm = Vec::with_capacity(FRAMES * 4);
+        for i in 0..FRAMES {
+            let v = (((i % 256) as i16) - 128) * 64;
+            pcm.extend_from_slice(&v.to_le_bytes()); // 左
+            pcm.extend_from_slice(&v.to_le_bytes()); // 右
+        }
+
+        let before = {
+            let mut ac97 = crate::ac97::AC97.lock();
+            let Some(driver) = ac97.as_mut() else {
+                return false;
+            };
+            if !driver.set_pcm_rate(crate::ac97::PCM_MAX_RATE) {
+                return false;
+            }
+            let before = driver.pcm_stats().consumed;
+            if driver.queue_pcm(&pcm) != pcm.len() {
+                return false;
+            }
+            before
+        };
+
+        // 再生時間の 10 倍程度まで待つ（audiodev=none でも QEMU は実時間で消費する）
+        for _ in 0..25 {
+            crate::scheduler::sleep_ms(50);
+            let stats = crate::ac97::AC97.lock().as_mut().map(|d| d.pcm_stats());
+            if stats.is_some_and(|s| s.consumed - before >= FRAMES as u64 && s.consumed == s.queued && s.in_flight == 0) {
+                return true;
+            }
+        }
+        false
+    }
+
     /// e1000e NIC の検出テスト。
     /// QEMU に `-device e1000e` を追加した場合、
     /// e1000e ドライバが正常に初期化されていることを確認する。
@@ -1455,6 +3028,38 @@ impl super::Shell {
         true
     }
 
+    /// TLS ポインタ（SYS_THREAD_SET_TLS / GET_TLS）のテスト
+    ///
+    /// 新しいタスクは 0 から始まり、別のタスクが設定しても自分の値は変わらないことを確かめる。
+    fn test_thread_tls(&self) -> bool {
+        use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+        static HELPER_SAW: AtomicU64 = AtomicU64::new(u64::MAX);
+        static HELPER_DONE: AtomicBool = AtomicBool::new(false);
+        HELPER_SAW.store(u64::MAX, Ordering::SeqCst);
+        HELPER_DONE.store(false, Ordering::SeqCst);
+
+        fn tls_task() {
+            HELPER_SAW.store(scheduler::current_tls_pointer(), Ordering::SeqCst);
+            scheduler::set_current_tls_pointer(0x2000);
+            HELPER_DONE.store(true, Ordering::SeqCst);
+        }
+
+        let saved = scheduler::current_tls_pointer();
+        scheduler::set_current_tls_pointer(0x1000);
+        scheduler::spawn("selftest_tls", tls_task);
+        for _ in 0..100 {
+            if HELPER_DONE.load(Ordering::SeqCst) {
+                break;
+            }
+            scheduler::yield_now();
+        }
+        let mine = scheduler::current_tls_pointer();
+        scheduler::set_current_tls_pointer(saved);
+
+        HELPER_DONE.load(Ordering::SeqCst) && HELPER_SAW.load(Ordering::SeqCst) == 0 && mine == 0x1000
+    }
+
     /// Handle から EOF まで読み取る
     fn read_all_handle(&self, handle: &crate::handle::Handle) -> Result<Vec<u8>, crate::user_ptr::SyscallError> {
         use alloc::vec::Vec;
@@ -1501,6 +3106,33 @@ impl super::Shell {
         }
     }
 
+    /// AHCI のポート走査テスト。
+    /// 各コントローラについて、走査結果のポートが PI レジスタのビットと一致する
+    /// （個数が popcount と等しく、ポート番号がすべて PI で立っている）ことと、
+    /// Disk になったポートの数が AHCI_DEVICES の数と一致し、各ディスクの
+    /// モデル名と容量が取れていることを確認する。
+    fn test_ahci_ports(&self) -> bool {
+        let controllers = crate::ahci::AHCI_CONTROLLERS.lock();
+        if controllers.is_empty() {
+            return false;
+        }
+        let ports_ok = controllers.iter().all(|c| {
+            c.ports.len() == c.ports_implemented.count_ones() as usize
+                && c.ports.iter().all(|p| c.ports_implemented & (1 << p.port) != 0)
+        });
+        let disk_ports = controllers
+            .iter()
+            .flat_map(|c| c.ports.iter())
+            .filter(|p| p.status == crate::ahci::AhciPortStatus::Disk)
+            .count();
+        drop(controllers);
+
+        let devs = crate::ahci::AHCI_DEVICES.lock();
+        let disks_ok = disk_ports == devs.len()
+            && devs.iter().all(|d| d.capacity() > 0 && !d.model().is_empty() && d.port_index() < 32);
+        ports_ok && disks_ok
+    }
+
     /// NVMe コントローラが PCI で検出できることを確認するテスト。
     /// QEMU に `-device nvme` が設定されていれば、
     /// PCI バスに class=0x01/subclass=0x08/prog_if=0x02 のデバイスが見つかるはず。
@@ -1527,6 +3159,21 @@ impl super::Shell {
         }
     }
 
+    /// NVMe の Identify コマンドのテスト。
+    /// Identify Controller でモデル名が取れ、Identify Namespace でネームスペース 1 の
+    /// 容量（論理ブロック数）とブロックサイズが 0 でないことを確認する。
+    /// NVMe デバイスがない環境では成功扱い。
+    fn test_nvme_identify(&self) -> bool {
+        if crate::nvme::device_count() == 0 {
+            return true;
+        }
+        let ctrl_ok = crate::nvme::identify_controller()
+            .is_ok_and(|ctrl| !ctrl.model.is_empty() && ctrl.namespace_count >= 1);
+        let ns_ok = crate::nvme::identify_namespace(1)
+            .is_ok_and(|ns| ns.nsid == 1 && ns.size_lbas > 0 && ns.capacity_lbas > 0 && ns.block_size >= 512);
+        ctrl_ok && ns_ok
+    }
+
     fn test_virtio_blk(&self) -> bool {
         let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
         if let Some(d) = devs.get_mut(0) {
@@ -1542,6 +3189,62 @@ impl super::Shell {
         }
     }
 
+    /// blockdev レジストリのテスト。
+    /// virtio-blk / AHCI / NVMe で検出したディスクがすべて登録され、
+    /// 通し番号が 0 から順に並んで先頭が disk.img (virtio-blk 0) であることと、
+    /// 各デバイスを BlockDevice として開いてセクタ 0 が読めることを確認する。
+    fn test_blockdev_registry(&self) -> bool {
+        use crate::blockdev::{BlockBackend, BlockDevice};
+
+        let devices = crate::blockdev::devices();
+        let expected = crate::virtio_blk::device_count() + crate::ahci::device_count() + crate::nvme::device_count();
+        if devices.len() != expected
+            || devices.first().map(|d| d.backend) != Some(BlockBackend::VirtioBlk(0))
+        {
+            return false;
+        }
+        let all_readable = devices.iter().enumerate().all(|(i, info)| {
+            let mut buf = [0u8; 512];
+            crate::serial_println!("blockdev_registry: [{}] {:?} {} sectors", info.index, info.backend, info.sectors);
+            info.index == i
+                && info.sectors > 0
+                && crate::blockdev::open(i).is_ok_and(|mut dev| dev.read_sector(0, &mut buf).is_ok())
+        });
+        all_readable && crate::blockdev::open(devices.len()).is_err()
+    }
+
+    /// MSI-X 割り込みのテスト
+    /// APIC モードなら virtio-blk が MSI-X を使っており、セクタを読むと
+    /// 完了割り込みが届いて msix_irq_count() が増えることを確認する。
+    /// Legacy PIC モードでは MSI-X を使わないので成功扱い。
+    fn test_msix_irq(&self) -> bool {
+        if !crate::apic::is_apic_active() {
+            return true;
+        }
+        let before = crate::virtio_blk::msix_irq_count();
+        {
+            let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
+            let Some(d) = devs.get_mut(0) else {
+                return false;
+            };
+            if !d.uses_msix() {
+                return false;
+            }
+            let mut buf = [0u8; 512];
+            if d.read_sector(0, &mut buf).is_err() {
+                return false;
+            }
+        }
+        // 割り込みの配送は完了のポーリングより遅れることがあるので少し待つ
+        for _ in 0..1_000_000 {
+            if crate::virtio_blk::msix_irq_count() > before {
+                return true;
+            }
+            core::hint::spin_loop();
+        }
+        false
+    }
+
     /// FAT32 のテスト
     /// HELLO.TXT ファイルを読み取り、内容が "Hello from FAT32!" で始まるか確認
     fn test_fat32(&self) -> bool {
@@ -1558,23 +3261,296 @@ impl super::Shell {
         }
     }
 
-    /// FAT32 の空き容量テスト
-    /// 総クラスタ数と空きクラスタ数の整合性を確認する。
-    fn test_fat32_space(&self) -> bool {
-        let mut fs = match crate::fat32::Fat32::new() {
-            Ok(f) => f,
-            Err(_) => return false,
+    /// FAT32 の空き容量テスト
+    /// 総クラスタ数と空きクラスタ数の整合性を確認する。
+    fn test_fat32_space(&self) -> bool {
+        let mut fs = match crate::fat32::Fat32::new() {
+            Ok(f) => f,
+            Err(_) => return false,
+        };
+        let total = fs.total_clusters();
+        let free = match fs.free_clusters() {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+        if total == 0 || free > total {
+            return false;
+        }
+        let used = total - free;
+        used > 0
+    }
+
+    /// df の出力のテスト
+    /// "/" の行を取り出して数値を解釈し、空き ≤ 全体、使用中 + 空き = 全体を確認する。
+    fn test_df(&self) -> bool {
+        let lines = super::commands::df_lines();
+        let Some(root) = lines.iter().find(|l| l.split_whitespace().next() == Some("/")) else {
+            return false;
+        };
+        let nums: Vec<u64> = root
+            .split_whitespace()
+            .skip(2)
+            .take(3)
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        let [total, used, free] = nums[..] else {
+            return false;
+        };
+        total > 0 && free <= total && used + free == total && root.ends_with('%')
+    }
+
+    /// vfs::copy_file のテスト
+    ///
+    /// /HELLO.TXT を /HELLO2.TXT にコピーして中身を比べる。
+    /// コピー先がディレクトリならその中に同じ名前で置かれること、
+    /// 自分自身への上書きとディレクトリへの上書きは拒否されることも確認する。
+    fn test_vfs_copy(&self) -> bool {
+        let Ok(original) = crate::vfs::read_file("/HELLO.TXT") else {
+            return false;
+        };
+        let _ = crate::vfs::delete_file("/HELLO2.TXT");
+
+        let copied = crate::vfs::copy_file("/HELLO.TXT", "/HELLO2.TXT")
+            .is_ok_and(|(dest, size)| dest == "/HELLO2.TXT" && size == original.len())
+            && crate::vfs::read_file("/HELLO2.TXT").is_ok_and(|data| data == original);
+        // 既存ファイルへのコピーは上書き
+        let overwritten = crate::vfs::copy_file("/HELLO.TXT", "/HELLO2.TXT").is_ok();
+        let _ = crate::vfs::delete_file("/HELLO2.TXT");
+
+        // ディレクトリを指定するとその中へ
+        let _ = crate::vfs::create_dir("/CPDIR");
+        let into_dir = crate::vfs::copy_file("/HELLO.TXT", "/CPDIR")
+            .is_ok_and(|(dest, _)| dest == "/CPDIR/HELLO.TXT")
+            && crate::vfs::read_file("/CPDIR/HELLO.TXT").is_ok_and(|data| data == original);
+        let _ = crate::vfs::delete_file("/CPDIR/HELLO.TXT");
+        let _ = crate::vfs::delete_dir("/CPDIR");
+
+        let rejected = crate::vfs::copy_file("/HELLO.TXT", "/HELLO.TXT").is_err()
+            && crate::vfs::copy_file("/HELLO.TXT", "/proc").is_err()
+            && crate::vfs::read_file("/HELLO.TXT").is_ok_and(|data| data == original);
+
+        copied && overwritten && into_dir && rejected
+    }
+
+    /// vfs::move_path のテスト
+    ///
+    /// / と /SUBDIR の間でファイルを移動し、元の場所から消えて移動先に現れることを確かめる。
+    /// ディレクトリの移動（中身ごと）と、ディレクトリを既存ファイルの上へ移せないことも確認する。
+    fn test_vfs_move(&self) -> bool {
+        use crate::vfs::{create_dir, create_file, delete_dir, delete_file, move_path, read_file};
+
+        let data = b"move test";
+        let created_subdir = create_dir("/SUBDIR").is_ok();
+        let _ = delete_file("/MVTEST.TXT");
+        let _ = delete_file("/SUBDIR/MVTEST.TXT");
+        if create_file("/MVTEST.TXT", data).is_err() {
+            return false;
+        }
+
+        // / → /SUBDIR（ディレクトリを指定するとその中へ同じ名前で）
+        let into_subdir = move_path("/MVTEST.TXT", "/SUBDIR").is_ok_and(|dest| dest == "/SUBDIR/MVTEST.TXT")
+            && read_file("/MVTEST.TXT").is_err()
+            && read_file("/SUBDIR/MVTEST.TXT").is_ok_and(|d| d == data);
+
+        // /SUBDIR → /（名前も変える）
+        let back_to_root = move_path("/SUBDIR/MVTEST.TXT", "/MVTEST2.TXT").is_ok()
+            && read_file("/SUBDIR/MVTEST.TXT").is_err()
+            && read_file("/MVTEST2.TXT").is_ok_and(|d| d == data);
+
+        // ディレクトリを中身ごと /SUBDIR へ移し、移動先で中身が読めること
+        let _ = create_dir("/MVDIR");
+        let _ = create_file("/MVDIR/IN.TXT", data);
+        let dir_moved = move_path("/MVDIR", "/SUBDIR").is_ok()
+            && read_file("/SUBDIR/MVDIR/IN.TXT").is_ok_and(|d| d == data)
+            && crate::vfs::node_kind("/MVDIR").is_err();
+
+        // ディレクトリを既存ファイルの上へは移せない
+        let _ = create_dir("/MVDIR2");
+        let dir_onto_file_rejected = move_path("/MVDIR2", "/MVTEST2.TXT").is_err()
+            && read_file("/MVTEST2.TXT").is_ok_and(|d| d == data);
+
+        let _ = delete_dir("/MVDIR2");
+        let _ = delete_file("/SUBDIR/MVDIR/IN.TXT");
+        let _ = delete_dir("/SUBDIR/MVDIR");
+        let _ = delete_file("/MVTEST2.TXT");
+        if created_subdir {
+            let _ = delete_dir("/SUBDIR");
+        }
+
+        into_subdir && back_to_root && dir_moved && dir_onto_file_rejected
+    }
+
+    /// VFS の dentry キャッシュのテスト
+    ///
+    /// 同じファイルを 2 回読み、2 回目はパス検索（DENTRY_WALKS）が増えずにヒットすることを確かめる。
+    /// 削除して作り直すと古い位置を読まないこと、procfs はキャッシュを通らないことも確認する。
+    fn test_vfs_dentry_cache(&self) -> bool {
+        use crate::vfs::{create_file, delete_file, dentry_cache_stats, read_file};
+
+        let _ = delete_file("/DCTEST.TXT");
+        if create_file("/DCTEST.TXT", b"first").is_err() {
+            return false;
+        }
+        let first_read = read_file("/DCTEST.TXT").is_ok_and(|d| d == b"first");
+        let before = dentry_cache_stats();
+        let second_read = read_file("/DCTEST.TXT").is_ok_and(|d| d == b"first");
+        let after = dentry_cache_stats();
+        let cached = after.walks == before.walks && after.hits == before.hits + 1;
+
+        // 作り直したら新しい中身が読める（削除でキャッシュが捨てられている）
+        let _ = delete_file("/DCTEST.TXT");
+        let _ = create_file("/DCTEST.TXT", b"second, longer");
+        let invalidated = read_file("/DCTEST.TXT").is_ok_and(|d| d == b"second, longer");
+        let _ = delete_file("/DCTEST.TXT");
+        let gone = read_file("/DCTEST.TXT").is_err();
+
+        // procfs は動的なのでキャッシュに載らない
+        let before = dentry_cache_stats();
+        let _ = read_file("/proc/meminfo");
+        let _ = read_file("/proc/meminfo");
+        let after = dentry_cache_stats();
+        let proc_bypassed = after.walks == before.walks && after.hits == before.hits;
+
+        first_read && second_read && cached && invalidated && gone && proc_bypassed
+    }
+
+    /// vfs::remove_dir_all のテスト
+    ///
+    /// /T/A/B にファイルを置いた木を作り、/T ごと消えることを確かめる。
+    /// ファイルやマウントポイントを指定したときは何も消さずにエラーになること。
+    fn test_vfs_remove_dir_all(&self) -> bool {
+        use crate::vfs::{create_dir, create_file, node_kind, remove_dir_all};
+
+        let _ = remove_dir_all("/T");
+        let built = create_dir("/T").is_ok()
+            && create_dir("/T/A").is_ok()
+            && create_dir("/T/A/B").is_ok()
+            && create_file("/T/ROOT.TXT", b"root").is_ok()
+            && create_file("/T/A/MID.TXT", b"mid").is_ok()
+            && create_file("/T/A/B/LEAF1.TXT", b"leaf1").is_ok()
+            && create_file("/T/A/B/LEAF2.TXT", b"leaf2").is_ok();
+        if !built {
+            let _ = remove_dir_all("/T");
+            return false;
+        }
+
+        let file_rejected = remove_dir_all("/T/ROOT.TXT").is_err()
+            && node_kind("/T/ROOT.TXT") == Ok(crate::vfs::VfsNodeKind::File);
+        let mount_rejected = remove_dir_all("/").is_err() && remove_dir_all("/proc").is_err();
+
+        // ファイル 4 個 + ディレクトリ 3 個
+        let removed = remove_dir_all("/T") == Ok(7);
+        let gone = node_kind("/T").is_err() && node_kind("/T/A/B/LEAF1.TXT").is_err();
+
+        file_rejected && mount_rejected && removed && gone
+    }
+
+    /// vfs::read_file_chunked のテスト
+    ///
+    /// 256 KiB のテキストファイルを cat と同じ経路（チャンク → UTF-8 デコーダ）で読み、
+    /// 行数とバイト数が合うこと、読んでいる間のヒープ使用量がファイルの大きさに比例せず
+    /// 小さいまま（64 KiB 未満の増加）であることを確かめる。
+    fn test_vfs_read_chunked(&self) -> bool {
+        const LINES: usize = 16384;
+        const PATH: &str = "/BIGCAT.TXT";
+
+        // 16 バイトの行を 16384 行並べて 256 KiB にする
+        let mut content = Vec::with_capacity(LINES * 16);
+        for i in 0..LINES {
+            content.extend_from_slice(alloc::format!("line {:05} okay\n", i).as_bytes());
+        }
+        let size = content.len();
+        let _ = crate::vfs::delete_file(PATH);
+        let created = crate::vfs::create_file(PATH, &content).is_ok();
+        drop(content);
+        if !created {
+            return false;
+        }
+
+        let baseline = crate::allocator::heap_stats().used_bytes;
+        let mut peak = baseline;
+        let mut decoder = sabos_textutil::Utf8ChunkDecoder::new();
+        let mut lines = 0;
+        let mut max_chunk = 0;
+        let result = crate::vfs::read_file_chunked(PATH, |chunk| {
+            max_chunk = max_chunk.max(chunk.len());
+            decoder.push(chunk, &mut |text| lines += text.matches('\n').count());
+            peak = peak.max(crate::allocator::heap_stats().used_bytes);
+            Ok(())
+        });
+        let _ = crate::vfs::delete_file(PATH);
+
+        let read_ok = result == Ok(size) && lines == LINES && max_chunk <= 4096;
+        let small_heap = peak.saturating_sub(baseline) < 64 * 1024;
+        if !small_heap {
+            crate::kprintln!("    heap grew by {} bytes while reading {} bytes", peak - baseline, size);
+        }
+
+        // コールバックのエラーで途中でやめられる
+        let aborted = crate::vfs::read_file_chunked("/HELLO.TXT", |_| Err(crate::vfs::VfsError::IoError))
+            == Err(crate::vfs::VfsError::IoError);
+
+        read_ok && small_heap && aborted
+    }
+
+    /// hexdump の整形のテスト
+    ///
+    /// ELF ファイルの先頭 16 バイトをダンプしてマジック (7f 45 4c 46) と
+    /// ASCII 欄の "ELF" が出ること、途中のオフセットや半端な行も崩れないことを確認する。
+    fn test_hexdump(&self) -> bool {
+        use super::commands::{hexdump_lines, HEXDUMP_DEFAULT_WIDTH};
+
+        let Ok(elf) = crate::vfs::read_file("/EXIT0.ELF") else {
+            return false;
+        };
+        if elf.len() < 16 {
+            return false;
+        }
+        let lines = hexdump_lines(&elf[..16], 0, HEXDUMP_DEFAULT_WIDTH);
+        let magic_ok = lines.len() == 1
+            && lines[0].starts_with("00000000: 7f 45 4c 46 ")
+            && lines[0].contains("|.ELF");
+
+        // オフセット 0x1 から 3 バイト、1 行 2 バイト → 2 行目は 1 バイトで空白埋め
+        let partial = hexdump_lines(&elf[1..4], 1, 2);
+        let partial_ok = partial.len() == 2
+            && partial[0] == "00000001: 45 4c  |EL|"
+            && partial[1] == "00000003: 46     |F|";
+
+        magic_ok && partial_ok
+    }
+
+    /// unzip_lines で ZIP を展開できることを確認する。
+    ///
+    /// zip-core のユニットテストと同じアーカイブを使う。two.zip は無圧縮の HELLO.TXT と
+    /// deflate の DOCS/README.TXT（ディレクトリのエントリなし）、evil.zip は "../EVIL.TXT" だけを含む。
+    fn test_unzip(&self) -> bool {
+        use super::commands::unzip_lines;
+
+        const TWO_ZIP: &[u8] = include_bytes!("../../../libs/zip-core/testdata/two.zip");
+        const EVIL_ZIP: &[u8] = include_bytes!("../../../libs/zip-core/testdata/evil.zip");
+        const DEST: &str = "/UNZIPT";
+
+        let _ = crate::vfs::remove_dir_all(DEST);
+        let extracted = match unzip_lines(TWO_ZIP, DEST) {
+            Ok(lines) => lines.len() == 2 && lines.iter().all(|l| l.contains("inflating:")),
+            Err(_) => false,
         };
-        let total = fs.total_clusters();
-        let free = match fs.free_clusters() {
-            Ok(v) => v,
-            Err(_) => return false,
+        let hello_ok = crate::vfs::read_file("/UNZIPT/HELLO.TXT").is_ok_and(|d| d == b"Hello, SABOS!\n");
+        let readme_ok = crate::vfs::read_file("/UNZIPT/DOCS/README.TXT")
+            .is_ok_and(|d| d.len() == 1690 && d.starts_with(b"line 0: SABOS zip-core test data\n"));
+
+        // 展開先の外（ルート直下）には書き出さない
+        let evil_skipped = match unzip_lines(EVIL_ZIP, DEST) {
+            Ok(lines) => lines.len() == 1 && lines[0].contains("skipped: ../EVIL.TXT"),
+            Err(_) => false,
         };
-        if total == 0 || free > total {
-            return false;
-        }
-        let used = total - free;
-        used > 0
+        let evil_absent = crate::vfs::node_kind("/EVIL.TXT").is_err();
+
+        let not_zip = unzip_lines(b"not a zip file", DEST).is_err();
+
+        let _ = crate::vfs::remove_dir_all(DEST);
+        extracted && hello_ok && readme_ok && evil_skipped && evil_absent && not_zip
     }
 
     /// file_write syscall のテスト。
@@ -1699,6 +3675,40 @@ impl super::Shell {
         data[0] == 0x7F && data[1] == b'E' && data[2] == b'L' && data[3] == b'F'
     }
 
+    /// 静的 IP 設定のテスト
+    ///
+    /// 0.0.0.0 / ループバック / 不正なマスクは拒否され、設定が変わらないこと、
+    /// 正しい設定なら get_my_ip() に反映されて DHCP が止まり、
+    /// 送信する ARP Request の送信元 IP（spa）も新しいアドレスになることを確認する。
+    /// 終わったら元の設定と DHCP の状態に戻す。
+    fn test_static_ip_config(&self) -> bool {
+        use crate::net_config::*;
+
+        let saved = (get_my_ip(), get_gateway_ip(), get_dns_server_ip(), get_subnet_mask(), is_dhcp_enabled());
+        let (old_ip, gateway, dns, mask, dhcp) = saved;
+        let new_ip = [10, 0, 2, 20];
+
+        let rejected = set_static_config([0, 0, 0, 0], mask, gateway, dns).is_err()
+            && set_static_config([127, 0, 0, 1], mask, gateway, dns).is_err()
+            && set_static_config(new_ip, [255, 0, 255, 0], gateway, dns).is_err()
+            && get_my_ip() == old_ip
+            && is_dhcp_enabled() == dhcp;
+
+        let applied = set_static_config(new_ip, mask, gateway, dns).is_ok()
+            && get_my_ip() == new_ip
+            && !is_dhcp_enabled()
+            && crate::netstack::dhcp_discover().is_err()
+            && get_my_ip() == new_ip;
+        // ARP パケットの送信元 IP は Ethernet ヘッダー (14) + 14 バイト目から
+        let arp = crate::netstack::build_arp_request(gateway);
+        let arp_source = arp.len() == 42 && arp[28..32] == new_ip;
+
+        set_config(old_ip, gateway, dns, mask);
+        set_dhcp_enabled(dhcp);
+
+        rejected && applied && arp_source && get_my_ip() == old_ip
+    }
+
     /// DHCP 設定テスト: DHCP で IP アドレスが取得されていることを確認する。
     ///
     /// init() 時に dhcp_discover() が実行され、IP が設定されているはず。
@@ -1709,6 +3719,51 @@ impl super::Shell {
         ip != [0, 0, 0, 0]
     }
 
+    /// ルーティングテーブルのテスト。
+    ///
+    /// 以下を確認する:
+    /// - 直結ルートとデフォルトルートが DHCP の設定から作られ、整形結果が期待どおり
+    /// - 静的ルートを足すと最長一致でそのゲートウェイが選ばれる
+    /// - 追加 → 削除でテーブルが元どおりに戻る
+    fn test_route_table(&self) -> bool {
+        use crate::net_config::{add_route, del_route, format_route, route_lookup, routes, Route, RouteKind};
+
+        let original = routes();
+        let Some(connected) = original.iter().find(|r| r.kind == RouteKind::Connected) else {
+            return false;
+        };
+        // 自分のサブネット内の宛先は直結ルートに当たり、次ホップは宛先そのもの
+        let my_ip = crate::net_config::get_my_ip();
+        if route_lookup(&my_ip) != Some(my_ip) || connected.gateway.is_some() {
+            return false;
+        }
+
+        let line = format_route(&Route {
+            dest: [192, 0, 2, 0],
+            prefix_len: 24,
+            gateway: Some([10, 0, 2, 2]),
+            kind: RouteKind::Static,
+        });
+        if line != "192.0.2.0/24       10.0.2.2         eth0   static" {
+            return false;
+        }
+
+        // ホスト部付きで渡しても正規化されて登録される
+        let gw = [10, 0, 2, 99];
+        if add_route([192, 0, 2, 77], 24, Some(gw)).is_err() {
+            return false;
+        }
+        let added = routes();
+        let lookup_ok = route_lookup(&[192, 0, 2, 5]) == Some(gw)
+            && added.len() == original.len() + 1
+            && added.iter().any(|r| r.dest == [192, 0, 2, 0] && r.kind == RouteKind::Static)
+            // 重複は拒否される
+            && add_route([192, 0, 2, 0], 24, None).is_err();
+
+        let deleted = del_route([192, 0, 2, 0], 24);
+        lookup_ok && deleted && routes() == original
+    }
+
     /// ARP 解決テスト: ゲートウェイの MAC アドレスが解決できることを確認する。
     /// resolve_mac() が ARP Request を送信し、QEMU SLIRP からの ARP Reply を
     /// 受信してキャッシュに登録し、MAC アドレスを返すフローをテストする。
@@ -1722,6 +3777,81 @@ impl super::Shell {
         }
     }
 
+    /// 受信リングのあふれテスト
+    ///
+    /// virtio-net のロックを握って net_poller が受信できない状態にし、
+    /// ゲートウェイへ ARP Request を受信バッファ数より多く送って ARP Reply を浴びる。
+    /// ロックを離した後に以下を確認する:
+    /// 1. overflows が増えていること（あふれを検出した）
+    /// 2. recoveries が増えていること（リングを立て直した）
+    /// 3. その後も受信できること（packets が増える）
+    fn test_net_rx_overflow(&self) -> bool {
+        use crate::netstack;
+        use core::sync::atomic::Ordering;
+
+        let Some((_, before)) = netstack::nic_rx_stats() else {
+            return false;
+        };
+
+        // ゲートウェイ宛ての ARP Request（Ethernet + ARP）
+        let my_mac = netstack::get_my_mac();
+        let mut arp_request: Vec<u8> = Vec::with_capacity(42);
+        arp_request.extend_from_slice(&[0xFF; 6]);
+        arp_request.extend_from_slice(&my_mac);
+        arp_request.extend_from_slice(&0x0806u16.to_be_bytes());
+        arp_request.extend_from_slice(&[0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x01]);
+        arp_request.extend_from_slice(&my_mac);
+        arp_request.extend_from_slice(&crate::net_config::get_my_ip());
+        arp_request.extend_from_slice(&[0; 6]);
+        arp_request.extend_from_slice(&crate::net_config::get_gateway_ip());
+
+        {
+            let mut drv = crate::virtio_net::VIRTIO_NET.lock();
+            let Some(d) = drv.as_mut() else {
+                return false;
+            };
+            for _ in 0..48 {
+                let _ = d.send_packet(&arp_request);
+            }
+            // ロックを握ったまま QEMU に応答を返す時間を与える（約 4 ティック）
+            let start = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+            while crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed) < start + 4 {
+                x86_64::instructions::interrupts::enable_and_hlt();
+            }
+        }
+
+        // net_poller が取り出して立て直すのを待つ
+        let mut recovered = None;
+        for _ in 0..40 {
+            crate::scheduler::sleep_ms(50);
+            if let Some((_, now)) = netstack::nic_rx_stats().filter(|(_, s)| s.recoveries > before.recoveries) {
+                recovered = Some(now);
+                break;
+            }
+        }
+        let Some(after) = recovered else {
+            return false;
+        };
+
+        // 回復後も受信できること
+        {
+            let mut drv = crate::virtio_net::VIRTIO_NET.lock();
+            if let Some(d) = drv.as_mut() {
+                let _ = d.send_packet(&arp_request);
+            }
+        }
+        let mut still_receives = false;
+        for _ in 0..40 {
+            crate::scheduler::sleep_ms(50);
+            if netstack::nic_rx_stats().is_some_and(|(_, s)| s.packets > after.packets) {
+                still_receives = true;
+                break;
+            }
+        }
+
+        after.overflows > before.overflows && still_receives
+    }
+
     fn test_network_dns(&self) -> bool {
         match crate::netstack::dns_lookup("example.com") {
             Ok(ip) => ip != [0, 0, 0, 0],
@@ -1729,15 +3859,182 @@ impl super::Shell {
         }
     }
 
+    /// DNS がユーザー空間と同じ UDP ソケット経路で解決されることを確認する。
+    ///
+    /// 別の UDP ソケットを bind した状態で dns_lookup を行い、
+    /// 1. 解決に成功すること
+    /// 2. DNS レスポンスが無関係なソケットのキューに入らないこと
+    /// 3. lookup のたびにソケットを増やさないこと（リゾルバのソケットは共有）
+    /// をチェックする。
+    fn test_dns_socket_path(&self) -> bool {
+        use crate::netstack::{udp_bind, udp_close, udp_recv_from, udp_socket_count};
+
+        // リゾルバの共有ソケットを確実に作っておく
+        let _ = crate::netstack::dns_lookup("example.com");
+
+        let other = match udp_bind(0) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+        let before = udp_socket_count();
+
+        let resolved = matches!(
+            crate::netstack::dns_lookup("example.com"),
+            Ok(ip) if ip != [0, 0, 0, 0]
+        );
+        let no_leak = udp_socket_count() == before;
+        // 無関係なソケットには何も届いていないはず（短いタイムアウトで確認）
+        let not_mixed = udp_recv_from(other, 100).is_err();
+
+        let _ = udp_close(other);
+        resolved && no_leak && not_mixed
+    }
+
+    /// 2 つのカーネルタスクから同時に dns_lookup し、両方が解決できることを確認する。
+    ///
+    /// 共有ソケット上でレスポンスがトランザクション ID ごとに振り分けられるので、
+    /// 片方の答えを横取りしたり、取りこぼしてタイムアウトしたりしないはず。
+    /// 振り分け自体の正しさは dns_routing テストで決定的に確認している。
+    fn test_dns_concurrent(&self) -> bool {
+        use core::sync::atomic::{AtomicU8, Ordering};
+
+        // 0 = 実行中, 1 = 成功, 2 = 失敗
+        static RESULT_A: AtomicU8 = AtomicU8::new(0);
+        static RESULT_B: AtomicU8 = AtomicU8::new(0);
+        RESULT_A.store(0, Ordering::SeqCst);
+        RESULT_B.store(0, Ordering::SeqCst);
+
+        fn lookup_into(name: &str, result: &AtomicU8) {
+            let ok = matches!(crate::netstack::dns_lookup(name), Ok(ip) if ip != [0, 0, 0, 0]);
+            result.store(if ok { 1 } else { 2 }, Ordering::SeqCst);
+        }
+        fn task_a() {
+            lookup_into("example.com", &RESULT_A);
+        }
+        fn task_b() {
+            lookup_into("example.org", &RESULT_B);
+        }
+
+        scheduler::spawn("selftest_dns_a", task_a);
+        scheduler::spawn("selftest_dns_b", task_b);
+
+        // 両方終わるまで待つ（リトライ込みで最大 ~12 秒）
+        for _ in 0..240 {
+            let a = RESULT_A.load(Ordering::SeqCst);
+            let b = RESULT_B.load(Ordering::SeqCst);
+            if a != 0 && b != 0 {
+                return a == 1 && b == 1;
+            }
+            scheduler::sleep_ms(50);
+        }
+        false
+    }
+
     /// TCP ISN ランダム化のテスト。
     /// 2 つの接続を作成し、ISN が異なることを確認する。
     /// RDRAND でランダム化しているので、2 つの ISN が一致する確率は 1/2^32。
     fn test_tcp_isn_random(&self) -> bool {
-        let conn1 = crate::netstack::TcpConnection::new(9990, 40000, [10, 0, 2, 2], 80);
-        let conn2 = crate::netstack::TcpConnection::new(9991, 40001, [10, 0, 2, 2], 80);
+        let conn1 = crate::netstack::TcpConnection::new(9990, 40000, crate::netstack::IpAddr::V4([10, 0, 2, 2]), 80);
+        let conn2 = crate::netstack::TcpConnection::new(9991, 40001, crate::netstack::IpAddr::V4([10, 0, 2, 2]), 80);
         conn1.seq_num != conn2.seq_num
     }
 
+    /// http コマンドの応答解析のテスト
+    ///
+    /// SLIRP 越しに届く HTTP サーバがないので、組み立てた応答で
+    /// ステータス行・Content-Length・本文の切り出しと、ヘッダ途中の扱いを確認する。
+    fn test_http_response_parse(&self) -> bool {
+        use super::commands::parse_http_response;
+
+        let resp = b"HTTP/1.0 200 OK\r\nServer: t\r\ncontent-length: 5\r\n\r\nhello";
+        let full = parse_http_response(resp).is_some_and(|r| {
+            r.status == "HTTP/1.0 200 OK" && r.content_length == Some(5) && r.body == b"hello"
+        });
+        // Content-Length なし: 接続が閉じるまでが本文
+        let no_len = parse_http_response(b"HTTP/1.1 404 Not Found\r\n\r\nnope")
+            .is_some_and(|r| r.content_length.is_none() && r.body == b"nope");
+        // ヘッダがまだ終わっていない・HTTP ではない
+        let partial = parse_http_response(b"HTTP/1.0 200 OK\r\nContent-Le").is_none();
+        let garbage = parse_http_response(b"SSH-2.0-x\r\n\r\n").is_none();
+
+        full && no_len && partial && garbage
+    }
+
+    /// nc コマンドのキー入力処理のテスト
+    ///
+    /// 文字を溜めて Enter で "\n" 付きの送信データになること、Backspace で消せること、
+    /// 制御文字は無視されること、ESC 単体で終わることを確認する。
+    /// 接続先がいらない部分だけを見る（引数が不正なときに接続しないことも確認）。
+    fn test_shell_nc_input(&self) -> bool {
+        use super::commands::{nc_feed_key, NcKey};
+
+        let mut line = String::new();
+        let typed = "hix".chars().all(|c| nc_feed_key(&mut line, c) == NcKey::Echo(c));
+        let erased = nc_feed_key(&mut line, '\x08') == NcKey::Erase;
+        let ignored = nc_feed_key(&mut line, '\x01') == NcKey::Ignore;
+        let sent = nc_feed_key(&mut line, '\n') == NcKey::Send(String::from("hi\n"));
+        let cleared = line.is_empty() && nc_feed_key(&mut line, '\x08') == NcKey::Ignore;
+        let quit = nc_feed_key(&mut line, '\x1b') == NcKey::Quit;
+
+        // 引数が不正なら使い方かエラーを出して戻ってくる（接続は試みない）
+        self.cmd_nc("");
+        self.cmd_nc("10.0.2");
+        self.cmd_nc("10.0.2.2 99999");
+
+        typed && erased && ignored && sent && cleared && quit
+    }
+
+    /// SYS_SENDFILE のテスト
+    ///
+    /// 数セグメントにまたがる内容のファイルハンドルを 127.0.0.1 の接続へ sendfile で送り、
+    /// 受け手で 1 バイトずつ一致することを確かめる。
+    /// 途中から末尾を越える count で送ると、末尾までの分だけ送って送ったバイト数を返すことと、
+    /// READ 権限のないハンドルがエラーになることも確認する。
+    fn test_sendfile(&self) -> bool {
+        use crate::handle::{HANDLE_RIGHT_READ, HANDLE_RIGHT_STAT};
+
+        const PORT: u16 = 40140;
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 + i / 256) as u8).collect();
+        let handle = crate::handle::create_handle(data.clone(), HANDLE_RIGHT_READ);
+        let no_read = crate::handle::create_handle(data.clone(), HANDLE_RIGHT_STAT);
+
+        // count バイト届くまで受け取る（最後の短いセグメントは Nagle で ACK 待ちになることがある）
+        let recv_exact = |conn_id: u32, count: usize| {
+            let mut received = Vec::new();
+            while received.len() < count {
+                match crate::netstack::tcp_recv(conn_id, 1000) {
+                    Ok(chunk) => received.extend_from_slice(&chunk),
+                    Err(_) => break,
+                }
+            }
+            received
+        };
+
+        let _ = crate::netstack::tcp_listen(PORT, 0);
+        let client = crate::netstack::tcp_connect(crate::netstack::LOOPBACK_IP, PORT);
+        let server = crate::netstack::tcp_try_accept(PORT);
+        crate::netstack::tcp_unlisten(PORT);
+
+        let ok = match (client, server) {
+            (Ok(client), Some(server)) => {
+                let whole = crate::syscall::sendfile(client, &handle, 0, data.len());
+                let whole_ok = whole == Ok(data.len()) && recv_exact(server, data.len()) == data;
+                let tail = crate::syscall::sendfile(client, &handle, 4000, 10_000);
+                let tail_ok = tail == Ok(1000) && recv_exact(server, 1000) == data[4000..];
+                let denied = crate::syscall::sendfile(client, &no_read, 0, 100).is_err();
+                whole_ok && tail_ok && denied
+            }
+            _ => false,
+        };
+
+        for id in [client.ok(), server].into_iter().flatten() {
+            let _ = crate::netstack::tcp_close(id);
+        }
+        let _ = crate::handle::close(&handle);
+        let _ = crate::handle::close(&no_read);
+        ok
+    }
+
     /// TCP 再送タイマーテスト
     ///
     /// TcpConnection の unacked_packet フィールドが正しく初期化・設定・クリアされることを確認する。
@@ -1745,7 +4042,7 @@ impl super::Shell {
         use crate::netstack::{TcpConnection, UnackedPacket};
 
         // 1. 初期状態では unacked_packet は None
-        let mut conn = TcpConnection::new(9980, 40010, [10, 0, 2, 2], 80);
+        let mut conn = TcpConnection::new(9980, 40010, crate::netstack::IpAddr::V4([10, 0, 2, 2]), 80);
         if conn.unacked_packet.is_some() {
             return false;
         }
@@ -1775,6 +4072,194 @@ impl super::Shell {
         conn.unacked_packet.is_none()
     }
 
+    /// SLAAC のアドレス生成テスト
+    ///
+    /// QEMU のデフォルト MAC 52:54:00:12:34:56 から修正 EUI-64 の
+    /// インターフェース ID 5054:00ff:fe12:3456 が作られ、
+    /// SLIRP が広告するプレフィックス fec0::/64 と連結して
+    /// fec0::5054:ff:fe12:3456 になることを確認する。
+    fn test_slaac_eui64(&self) -> bool {
+        use crate::netstack::{eui64_interface_id, format_ipv6, slaac_address};
+
+        let mac = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+        let iid = eui64_interface_id(&mac);
+        // U/L ビット（0x02）が反転し、真ん中に ff:fe が入る
+        if iid != [0x50, 0x54, 0x00, 0xff, 0xfe, 0x12, 0x34, 0x56] {
+            return false;
+        }
+
+        // プレフィックスの下位 64bit は捨てられ、IID で置き換わる
+        let prefix = [0xfe, 0xc0, 0,0,0,0,0,0, 0xde,0xad,0xbe,0xef,0,0,0,0];
+        let addr = slaac_address(&prefix, &iid);
+        if addr != [0xfe, 0xc0, 0,0,0,0,0,0, 0x50,0x54,0x00,0xff,0xfe,0x12,0x34,0x56] {
+            return false;
+        }
+
+        format_ipv6(&addr) == "fec0::5054:ff:fe12:3456"
+            && format_ipv6(&crate::netstack::MY_IPV6) == "fec0::15"
+    }
+
+    /// RA の検証のテスト（RFC 4861 §6.1.2）
+    ///
+    /// 送信元がリンクローカルでない RA と、hop limit が 255 でない RA を
+    /// handle_packet() に注入し、どちらもアドレス・ルーターを変えず、
+    /// 送信元の MAC も学習しないことを確認する。
+    /// プレフィックスはドキュメント用の 2001:db8:1::/64 にしておく。
+    /// 受信側は ICMPv6 チェックサムを検証しないので 0 のまま送る。
+    fn test_slaac_ra_validation(&self) -> bool {
+        use crate::netstack;
+
+        let router_mac: [u8; 6] = [0x52, 0x55, 0x00, 0x00, 0x0a, 0x01];
+        let global_src: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 1, 0,0, 0,0,0,0,0,0,0, 1];
+        let link_local_src: [u8; 16] = [0xfe, 0x80, 0,0,0,0,0,0, 0,0,0,0,0,0,0x0a, 0x01];
+        let prefix: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 1, 0,0, 0,0,0,0,0,0,0,0];
+
+        // ICMPv6 Router Advertisement: type=134, router lifetime=1800s,
+        // Source LL Address と Prefix Information（/64, L|A, 有効期間あり）を付ける
+        let mut icmpv6_data: Vec<u8> = Vec::new();
+        icmpv6_data.extend_from_slice(&[134, 0, 0, 0, 64, 0]);
+        icmpv6_data.extend_from_slice(&1800u16.to_be_bytes());
+        icmpv6_data.extend_from_slice(&[0; 8]);
+        icmpv6_data.extend_from_slice(&[1, 1]);
+        icmpv6_data.extend_from_slice(&router_mac);
+        icmpv6_data.extend_from_slice(&[3, 4, 64, 0xc0]);
+        icmpv6_data.extend_from_slice(&86400u32.to_be_bytes());
+        icmpv6_data.extend_from_slice(&14400u32.to_be_bytes());
+        icmpv6_data.extend_from_slice(&[0; 4]);
+        icmpv6_data.extend_from_slice(&prefix);
+
+        let build = |src: &[u8; 16], hop_limit: u8| {
+            let mut packet: Vec<u8> = Vec::new();
+            packet.extend_from_slice(&[0x33, 0x33, 0, 0, 0, 1]);
+            packet.extend_from_slice(&router_mac);
+            packet.extend_from_slice(&0x86DDu16.to_be_bytes());
+            packet.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+            packet.extend_from_slice(&(icmpv6_data.len() as u16).to_be_bytes());
+            packet.push(58); // next_header = ICMPv6
+            packet.push(hop_limit);
+            packet.extend_from_slice(src);
+            packet.extend_from_slice(&[0xff, 0x02, 0,0,0,0,0,0, 0,0,0,0,0,0,0, 1]);
+            packet.extend_from_slice(&icmpv6_data);
+            packet
+        };
+
+        let addr_before = crate::net_config::get_my_ipv6();
+        let router_before = crate::net_config::get_ipv6_router();
+
+        netstack::handle_packet(&build(&global_src, 255));
+        netstack::handle_packet(&build(&link_local_src, 64));
+
+        crate::net_config::get_my_ipv6() == addr_before
+            && crate::net_config::get_ipv6_router() == router_before
+            && netstack::ndp_lookup(&global_src).is_none()
+            && netstack::ndp_lookup(&link_local_src).is_none()
+    }
+
+    /// NDP 近隣キャッシュのテスト
+    ///
+    /// 偽の Neighbor Advertisement を handle_packet() に注入し、
+    /// Target Link-Layer Address の MAC が近隣キャッシュに入ることを確認する。
+    /// その後の resolve_neighbor() は NS を送らずキャッシュから即座に解決できるはず。
+    /// 受信側は ICMPv6 チェックサムを検証しないので 0 のまま送る。
+    fn test_ndp_cache(&self) -> bool {
+        use crate::netstack;
+
+        // どのプレフィックスでも直接届くようにリンクローカルの相手にする
+        let target: [u8; 16] = [0xfe, 0x80, 0,0,0,0,0,0, 0x50,0x55,0x00,0xff,0xfe,0x00,0x12,0x34];
+        let neighbor_mac: [u8; 6] = [0x52, 0x55, 0x00, 0x00, 0x12, 0x34];
+        if netstack::ndp_lookup(&target).is_some() {
+            return false;
+        }
+
+        // ICMPv6 Neighbor Advertisement: type=136, flags=S|O, target, Target LL Address
+        let mut icmpv6_data: Vec<u8> = Vec::new();
+        icmpv6_data.extend_from_slice(&[136, 0, 0, 0]);
+        icmpv6_data.extend_from_slice(&[0x60, 0, 0, 0]);
+        icmpv6_data.extend_from_slice(&target);
+        icmpv6_data.extend_from_slice(&[2, 1]);
+        icmpv6_data.extend_from_slice(&neighbor_mac);
+
+        let mut packet: Vec<u8> = Vec::new();
+        packet.extend_from_slice(&netstack::get_my_mac());
+        packet.extend_from_slice(&neighbor_mac);
+        packet.extend_from_slice(&0x86DDu16.to_be_bytes());
+        packet.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(&(icmpv6_data.len() as u16).to_be_bytes());
+        packet.push(58);  // next_header = ICMPv6
+        packet.push(255); // NDP は hop limit 255 でないと捨てられる
+        packet.extend_from_slice(&target);
+        packet.extend_from_slice(&crate::net_config::get_my_ipv6());
+        packet.extend_from_slice(&icmpv6_data);
+
+        netstack::handle_packet(&packet);
+
+        netstack::ndp_lookup(&target) == Some(neighbor_mac)
+            && netstack::resolve_neighbor(&target) == Ok(neighbor_mac)
+    }
+
+    /// IPv6 上の UDP テスト
+    ///
+    /// 1. build_udp_datagram_v6 のチェックサムが疑似ヘッダー込みで正しいこと
+    /// 2. parse_udp_datagram_v6 が壊れたデータグラムとチェックサム 0 を拒否すること
+    /// 3. IPv6 フレームを注入すると、バインドしたソケットに IPv6 の送信元付きで届くこと
+    fn test_udp_ipv6(&self) -> bool {
+        use crate::netstack::{self, IpAddr};
+
+        let peer: [u8; 16] = [0xfe, 0x80, 0,0,0,0,0,0, 0x50,0x55,0x00,0xff,0xfe,0x00,0x56,0x78];
+        let my_ip = crate::net_config::get_my_ipv6();
+        let payload = b"hello udp6";
+
+        let sock = match netstack::udp_bind(0) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+        let port = match netstack::udp_local_port(sock) {
+            Ok(p) => p,
+            Err(_) => {
+                let _ = netstack::udp_close(sock);
+                return false;
+            }
+        };
+
+        let datagram = netstack::build_udp_datagram_v6(&peer, &my_ip, 5353, port, payload);
+        let checksum_ok = datagram.len() == 8 + payload.len()
+            && datagram[6..8] != [0, 0]
+            && netstack::parse_udp_datagram_v6(&peer, &my_ip, &datagram)
+                == Some((5353, port, &payload[..]));
+
+        // 1 バイト壊すとチェックサムが合わなくなる
+        let mut corrupted = datagram.clone();
+        corrupted[8] ^= 0x01;
+        // IPv6 ではチェックサム 0（省略）は不正
+        let mut no_checksum = datagram.clone();
+        no_checksum[6] = 0;
+        no_checksum[7] = 0;
+        let rejects_bad = netstack::parse_udp_datagram_v6(&peer, &my_ip, &corrupted).is_none()
+            && netstack::parse_udp_datagram_v6(&peer, &my_ip, &no_checksum).is_none();
+
+        // Ethernet + IPv6 ヘッダーを付けて受信経路に流す
+        let mut packet: Vec<u8> = Vec::new();
+        packet.extend_from_slice(&netstack::get_my_mac());
+        packet.extend_from_slice(&[0x52, 0x55, 0x00, 0x00, 0x56, 0x78]);
+        packet.extend_from_slice(&0x86DDu16.to_be_bytes());
+        packet.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+        packet.extend_from_slice(&(datagram.len() as u16).to_be_bytes());
+        packet.push(17); // next_header = UDP
+        packet.push(64);
+        packet.extend_from_slice(&peer);
+        packet.extend_from_slice(&my_ip);
+        packet.extend_from_slice(&datagram);
+        netstack::handle_packet(&packet);
+
+        let received = matches!(
+            netstack::udp_recv_from(sock, 100),
+            Ok((IpAddr::V6(src), 5353, ref data)) if src == peer && data.as_slice() == payload
+        );
+
+        let _ = netstack::udp_close(sock);
+        checksum_ok && rejects_bad && received
+    }
+
     /// IPv6 スタックテスト
     ///
     /// 偽の ICMPv6 Echo Reply パケットを構築して handle_packet() に注入し、
@@ -1793,8 +4278,8 @@ impl super::Shell {
         let dummy_src_mac: [u8; 6] = [0x52, 0x55, 0x00, 0xAA, 0xBB, 0xCC];
         // 送信元: fec0::2 (QEMU ゲートウェイ)
         let src_ipv6: [u8; 16] = [0xfe, 0xc0, 0,0,0,0,0,0, 0,0,0,0,0,0,0, 0x02];
-        // 宛先: fec0::15 (MY_IPV6)
-        let dst_ipv6: [u8; 16] = netstack::MY_IPV6;
+        // 宛先: 自分の IPv6 アドレス（SLAAC で設定済み、RA がなければ fec0::15）
+        let dst_ipv6: [u8; 16] = crate::net_config::get_my_ipv6();
 
         // ICMPv6 Echo Reply ペイロード: type=129, code=0, checksum(2), id=0x1234, seq=1, data=DEADBEEF
         let id: u16 = 0x1234;
@@ -1923,7 +4408,7 @@ impl super::Shell {
             req[8..8 + payload.len()].copy_from_slice(payload);
 
             for _ in 0..2 {
-                if crate::ipc::send(sender, gui_id, req[..header_len + payload.len()].to_vec()).is_err() {
+                if crate::ipc::send(sender, gui_id, &req[..header_len + payload.len()]).is_err() {
                     return false;
                 }
                 crate::scheduler::sleep_ms(10);
@@ -2014,7 +4499,7 @@ impl super::Shell {
         req[4..8].copy_from_slice(&(payload_text.len() as u32).to_le_bytes());
         req[8..8 + payload_text.len()].copy_from_slice(&payload_text);
 
-        if crate::ipc::send(sender, gui_id, req[..header_len + payload_text.len()].to_vec()).is_err() {
+        if crate::ipc::send(sender, gui_id, &req[..header_len + payload_text.len()]).is_err() {
             return false;
         }
 
@@ -2041,7 +4526,7 @@ impl super::Shell {
         req[0..4].copy_from_slice(&opcode_mouse.to_le_bytes());
         req[4..8].copy_from_slice(&0u32.to_le_bytes());
 
-        if crate::ipc::send(sender, gui_id, req[..header_len].to_vec()).is_err() {
+        if crate::ipc::send(sender, gui_id, &req[..header_len]).is_err() {
             return false;
         }
 
@@ -2073,7 +4558,7 @@ impl super::Shell {
             req[4..8].copy_from_slice(&(payload.len() as u32).to_le_bytes());
             req[8..8 + payload.len()].copy_from_slice(payload);
             for _ in 0..2 {
-                if crate::ipc::send(sender, gui_id, req[..header_len + payload.len()].to_vec()).is_err() {
+                if crate::ipc::send(sender, gui_id, &req[..header_len + payload.len()]).is_err() {
                     return None;
                 }
                 crate::scheduler::sleep_ms(10);
@@ -2226,6 +4711,25 @@ impl super::Shell {
         true
     }
 
+    /// 終了コード取得のテスト
+    ///
+    /// EXIT0.ELF を "exit 3" 付きで spawn し、SYS_WAIT の戻り値が
+    /// 子プロセスの渡した終了コード 3 になっていることを確認する。
+    /// 以前は SYS_EXIT が rdi を無視していたので常に 0 が返っていた。
+    fn test_wait_exit_code(&self) -> bool {
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "exit", "3"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        // 戻り値は i32 を u32 として詰めた値なので、元の符号付き値に戻して比較する
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        (ret as i64) >= 0 && ret as u32 as i32 == 3
+    }
+
     /// ルートディレクトリのエントリ一覧を取得し、
     /// HELLO.TXT が含まれることを確認する。
     fn test_vfs_dirlist(&self) -> bool {
@@ -2249,6 +4753,23 @@ impl super::Shell {
         text.contains("HELLO.TXT")
     }
 
+    /// vfs::list_dir_html で "/" の一覧に HELLO.TXT へのリンクがあることと、
+    /// HTML の特殊文字を含む名前がエスケープされることを確認する。
+    /// FAT32 には "<" を含む名前を作れないので、後者は生の一覧を直接渡して確かめる。
+    fn test_vfs_dirlist_html(&self) -> bool {
+        let html = match crate::vfs::list_dir_html("/") {
+            Ok(html) => html,
+            Err(_) => return false,
+        };
+        if !html.contains("<a href=\"/HELLO.TXT\">HELLO.TXT</a>") {
+            return false;
+        }
+
+        let crafted = sabos_textutil::dir_listing_html("/", "<b>\"x\"&.TXT\n");
+        crafted.contains("<a href=\"/&lt;b&gt;&quot;x&quot;&amp;.TXT\">&lt;b&gt;&quot;x&quot;&amp;.TXT</a>")
+            && !crafted.contains("<b>")
+    }
+
     /// /proc/maps が読めて、JSON に "processes" キーが含まれることを確認する。
     /// 実行中のユーザープロセスの VMA 情報が取得できる。
     fn test_procfs_maps(&self) -> bool {
@@ -2278,6 +4799,230 @@ impl super::Shell {
         text.contains("\"processes\"") && text.contains("\"vmas\"")
     }
 
+    /// serial_println! と klog!(Warn, ..) で書いた目印が /proc/kmsg から
+    /// 1 行ずつの JSON として読み返せることを確認する。
+    /// 目印には現在のティック数を入れて、以前の selftest の出力と区別する。
+    fn test_procfs_kmsg(&self) -> bool {
+        let tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+        let marker = alloc::format!("kmsg-selftest-marker-{}", tick);
+        crate::serial_println!("{}", marker);
+        crate::klog!(Warn, Console, "{}-warn", marker);
+
+        let listed = crate::vfs::list_dir("/proc")
+            .is_ok_and(|entries| entries.iter().any(|e| e.name == "kmsg"));
+        let log = match crate::vfs::read_file("/proc/kmsg") {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        let text = match core::str::from_utf8(&log) {
+            Ok(text) => text,
+            Err(_) => return false,
+        };
+        let info_line = alloc::format!("\"level\":\"info\",\"msg\":\"{}\"}}", marker);
+        let warn_line = alloc::format!("\"level\":\"warn\",\"msg\":\"[console] WARN: {}-warn\"}}", marker);
+        listed
+            && text.starts_with("{\"lines\":[")
+            && text.ends_with("]}\n")
+            && text.contains(&info_line)
+            && text.contains(&warn_line)
+    }
+
+    /// net を trace にすると net_trace 相当のログ（klog!(Trace, Net, ..)）が /proc/kmsg に出て、
+    /// debug に戻すと出なくなること、出ないときは引数をフォーマットしないことを確認する。
+    fn test_klog_levels(&self) -> bool {
+        use crate::klog::{self, LogLevel, Subsystem};
+
+        /// フォーマットされた回数を数える
+        struct Counted<'a>(&'a core::cell::Cell<u32>);
+        impl core::fmt::Display for Counted<'_> {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                self.0.set(self.0.get() + 1);
+                f.write_str("counted")
+            }
+        }
+
+        let tick = crate::interrupts::TIMER_TICK_COUNT.load(core::sync::atomic::Ordering::Relaxed);
+        let shown = alloc::format!("klog-trace-shown-{}", tick);
+        let hidden = alloc::format!("klog-trace-hidden-{}", tick);
+        let formats = core::cell::Cell::new(0);
+
+        let prev = klog::set_level(Subsystem::Net, LogLevel::Trace);
+        crate::klog!(Trace, Net, "{} {}", shown, Counted(&formats));
+        klog::set_level(Subsystem::Net, LogLevel::Debug);
+        crate::klog!(Trace, Net, "{} {}", hidden, Counted(&formats));
+        klog::set_level(Subsystem::Net, prev);
+
+        let log = crate::kmsg::snapshot();
+        let text = alloc::string::String::from_utf8_lossy(&log);
+        let trace_line = alloc::format!("[net-trace] {} counted", shown);
+        text.contains(&trace_line)
+            && !text.contains(&hidden)
+            && formats.get() == 1
+            && klog::level(Subsystem::Net) == prev
+            && LogLevel::from_name("TRACE") == Some(LogLevel::Trace)
+            && Subsystem::from_name("sched") == Some(Subsystem::Sched)
+    }
+
+    /// 決まった深さまで再帰した先からバックトレースを取り、
+    /// 再帰した回数ぶんのフレームが再帰関数の中を指していることを確認する。
+    /// パニックハンドラと同じ write_backtrace() の出力をシリアルにも出して、行数も確かめる。
+    /// （本当にパニックさせると戻ってこられないので、シェルの `panic <depth>` で目視する）
+    fn test_backtrace(&self) -> bool {
+        use crate::backtrace::{self, MAX_FRAMES};
+
+        const DEPTH: usize = 5;
+
+        #[inline(never)]
+        fn recurse(depth: usize, frames: &mut [u64; MAX_FRAMES], text: &mut String) -> usize {
+            if depth > 0 {
+                // 戻り値を使って末尾呼び出しの最適化を防ぐ
+                return core::hint::black_box(recurse(depth - 1, frames, text));
+            }
+            let rbp = backtrace::current_rbp();
+            unsafe {
+                backtrace::walk(rbp, |i, addr| frames[i] = addr);
+                backtrace::write_backtrace(text, rbp)
+            }
+        }
+
+        let mut frames = [0u64; MAX_FRAMES];
+        let mut text = String::new();
+        let depth = recurse(DEPTH, &mut frames, &mut text);
+        crate::serial_print!("{}", text);
+
+        // 戻りアドレスは呼び出し命令の直後なので、関数の先頭から少し後ろにある
+        let start = recurse as *const () as u64;
+        let in_recurse = |addr: u64| (start..start + 0x400).contains(&addr);
+        let lines = text.lines().filter(|l| l.trim_start().starts_with('#')).count();
+        depth > DEPTH
+            && frames[..DEPTH].iter().all(|&a| in_recurse(a))
+            && !in_recurse(frames[DEPTH])
+            && lines == depth
+    }
+
+    /// 既知のカーネル関数のアドレスが、埋め込んだシンボルテーブルでその関数名に戻ることを確認する。
+    /// 関数の途中のアドレスはオフセット付きで同じ関数に、カーネルの外のアドレスは None になること。
+    /// テーブルは make build（scripts/build-kernel.sh）で埋め込まれる。
+    fn test_symbols(&self) -> bool {
+        use crate::symbols;
+
+        if !symbols::available() {
+            kprintln!("    no symbol table embedded (build with scripts/build-kernel.sh)");
+            return false;
+        }
+        let start = crate::scheduler::current_task_id as *const () as u64;
+        let name = "sabos::scheduler::current_task_id";
+        let exact = symbols::resolve(start);
+        let inside = symbols::resolve(start + 1);
+        if exact.is_none_or(|(n, _)| n != name) {
+            kprintln!("    {:#x} resolved to {:?}", start, exact);
+        }
+        exact == Some((name, 0))
+            && inside == Some((name, 1))
+            && symbols::resolve(0).is_none()
+            && symbols::count() > 100
+    }
+
+    /// 起動時に AP が 1 つ以上立ち上がり、アイドルループに入ったことを確認する。
+    /// AP が自分で立てる CPU ごとのフラグを BSP から読む。
+    fn test_smp_ap_idle(&self) -> bool {
+        let online = crate::smp::online_cpus();
+        (1..online).all(crate::smp::is_ap_idle) && online >= 2
+    }
+
+    /// シェルが動いている BSP では current_cpu_id() が 0 になり、
+    /// CPU ごとのブロックに書いた値がそのまま読み戻せることを確認する。
+    fn test_percpu(&self) -> bool {
+        use x86_64::instructions::interrupts;
+
+        let cpu = crate::percpu::current();
+        let my_id = crate::scheduler::current_task_id();
+        // 書き換えているあいだにタスクが切り替わらないよう割り込みを止める
+        let round_trip = interrupts::without_interrupts(|| {
+            cpu.set_current_task_id(0xC0FFEE);
+            let read = crate::scheduler::current_task_id();
+            cpu.set_current_task_id(my_id);
+            read == 0xC0FFEE
+        });
+        crate::percpu::current_cpu_id() == 0
+            && round_trip
+            && crate::scheduler::current_task_id() == my_id
+            && crate::scheduler::try_with_current_task(|t| t.id) == Some(my_id)
+    }
+
+    /// ユーザープログラムが GS を書き換えてもカーネルが動き続けることを確認する。
+    ///
+    /// EXIT0.ELF を "gsclobber" 付きで spawn する。子は GS ベースを 0 にしてから
+    /// タイマー割り込みと syscall を受けるので、入口で swapgs していなければ
+    /// カーネルが gs:[0] を読んで落ちる。終了コード 0 で戻り、このタスクの
+    /// CPU ごとのブロックも元のままなら OK（3 = 子の GS セレクタが変わっていた）。
+    fn test_percpu_user_gs(&self) -> bool {
+        let task_id = match crate::syscall::exec_spawn_with_args_for_test(
+            "/EXIT0.ELF",
+            &["/EXIT0.ELF", "gsclobber"],
+        ) {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+
+        let ret = crate::syscall::wait_for_test(task_id, 0);
+        (ret as i64) >= 0
+            && ret as u32 as i32 == 0
+            && crate::percpu::current_cpu_id() == 0
+            && crate::scheduler::try_with_current_task(|t| t.id)
+                == Some(crate::scheduler::current_task_id())
+    }
+
+    /// /proc/<self>/status が読めて、自分のタスク名が含まれることを確認する。
+    /// また /proc の一覧に自分の pid ディレクトリが並び、
+    /// /proc/<self>/ に status / cmdline / maps があることも確認する。
+    fn test_procfs_pid(&self) -> bool {
+        let my_id = crate::scheduler::current_task_id();
+        let my_name = match crate::scheduler::task_detail(my_id) {
+            Some(d) => d.name,
+            None => return false,
+        };
+        let pid_str = alloc::format!("{}", my_id);
+
+        // /proc の一覧に数値ディレクトリとして出てくること
+        let entries = match crate::vfs::list_dir("/proc") {
+            Ok(entries) => entries,
+            Err(_) => return false,
+        };
+        if !entries.iter().any(|e| e.name == pid_str && e.kind == crate::vfs::VfsNodeKind::Directory) {
+            return false;
+        }
+
+        // /proc/<self>/ の中身
+        let dir = alloc::format!("/proc/{}", my_id);
+        let files = match crate::vfs::list_dir(&dir) {
+            Ok(files) => files,
+            Err(_) => return false,
+        };
+        for name in ["status", "cmdline", "maps"] {
+            if !files.iter().any(|e| e.name == name) {
+                return false;
+            }
+        }
+
+        // /proc/<self>/status にタスク名が含まれること
+        let status = match crate::vfs::read_file(&alloc::format!("{}/status", dir)) {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        let status = match core::str::from_utf8(&status) {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        let expected_name = alloc::format!("\"name\":\"{}\"", my_name);
+        if !status.contains(&expected_name) || !status.contains("\"state\"") {
+            return false;
+        }
+
+        // 存在しない pid は NotFound になること
+        crate::vfs::read_file("/proc/999999/status").is_err()
+    }
+
     // =================================================================
     // VMA 管理のテスト
     // =================================================================
@@ -2475,3 +5220,23 @@ impl super::Shell {
     }
 
 }
+
+/// タイマー割り込みから wake_task() で起こすタスク（wake_target_on_tick 用）
+static TIMER_WAKE_TARGET: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+/// タイマーフック: TIMER_WAKE_TARGET のタスクを割り込みコンテキストから起こす
+fn wake_target_on_tick() {
+    scheduler::wake_task(TIMER_WAKE_TARGET.load(core::sync::atomic::Ordering::Relaxed));
+}
+
+/// タイマー割り込みのたびに task_id を wake_task() で起こすようにする（None で解除）。
+/// 割り込みコンテキストからの起床を試すために使う。
+fn set_timer_wake_target(task_id: Option<u64>) {
+    match task_id {
+        Some(id) => {
+            TIMER_WAKE_TARGET.store(id, core::sync::atomic::Ordering::Relaxed);
+            crate::interrupts::set_timer_hook(Some(wake_target_on_tick));
+        }
+        None => crate::interrupts::set_timer_hook(None),
+    }
+}
@@ -14,7 +14,10 @@
 // - どちらも O(1) で動作する
 //
 // 大オブジェクト（> 2048B）は first-fit + バンプのハイブリッド方式で管理。
+//
+// これとは別に、小さい IPC メッセージ専用のキャッシュ（MsgCache / MsgBuf）も置いている。
 
+use alloc::vec::Vec;
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr;
 use spin::Mutex;
@@ -534,6 +537,58 @@ impl SlabAllocator {
     }
 }
 
+/// ヒープの使用状況と断片化の統計（LockedSlabAllocator::stats() の結果）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeapStats {
+    /// ヒープ全体のバイト数（スラブ + 大オブジェクト領域）
+    pub total_bytes: usize,
+    /// 使用中のバイト数（スラブの確保中スロット + 大オブジェクト領域の使用中ブロック）
+    pub used_bytes: usize,
+    /// 大オブジェクト領域で一番大きい空きブロックのバイト数（ヘッダ込み）。
+    /// これより大きい確保はバンプ領域の残りがなければ失敗する
+    pub largest_free_block: usize,
+    /// 大オブジェクト領域の空きブロック数（フリーリスト + バンプポインタ以降の未使用領域）。
+    /// 空き容量が同じでもこれが多いほど断片化している
+    pub free_block_count: usize,
+}
+
+impl SlabAllocator {
+    /// 使用状況を集計する。
+    /// スラブは確保中スロット数を持っているので O(1)、大オブジェクト領域は
+    /// フリーリストを 1 回たどるだけなので O(空きブロック数)。
+    fn stats(&self) -> HeapStats {
+        let mut total_bytes = 0;
+        let mut used_bytes = 0;
+        for slab in &self.slabs {
+            total_bytes += slab.region_end - slab.region_start;
+            used_bytes += slab.allocated_count * slab.slot_size;
+        }
+
+        let large = &self.large;
+        total_bytes += large.region_end - large.region_start;
+        let tail = large.region_end - large.next_uninit;
+        let mut free_bytes = 0;
+        let mut largest_free_block = tail;
+        let mut free_block_count = if tail > 0 { 1 } else { 0 };
+        let mut current = large.free_list;
+        while !current.is_null() {
+            let size = unsafe { (*current).size };
+            free_bytes += size;
+            largest_free_block = largest_free_block.max(size);
+            free_block_count += 1;
+            current = unsafe { (*current).next };
+        }
+        used_bytes += large.next_uninit - large.region_start - free_bytes;
+
+        HeapStats {
+            total_bytes,
+            used_bytes,
+            largest_free_block,
+            free_block_count,
+        }
+    }
+}
+
 // =============================================================================
 // LockedSlabAllocator — spin::Mutex で包んだグローバルアロケータ
 // =============================================================================
@@ -565,6 +620,11 @@ impl LockedSlabAllocator {
         *inner = Some(SlabAllocator::new(heap_start, heap_size));
     }
 
+    /// ヒープの使用状況と断片化の統計を返す。init 前は None。
+    pub fn stats(&self) -> Option<HeapStats> {
+        self.inner.lock().as_ref().map(|allocator| allocator.stats())
+    }
+
     /// ヒープ使用状況をシリアルに出力する（デバッグ用）。
     ///
     /// 各スラブと大オブジェクト領域の使用状況を表示する。
@@ -597,6 +657,14 @@ impl LockedSlabAllocator {
                 bump_used, total, free_bytes, free_blocks
             );
         }
+        drop(inner);
+        // IPC メッセージ用キャッシュ（チャンクはグローバルヒープの large から借りている）
+        for cache in MSG_CACHES.lock().iter() {
+            crate::serial_println!(
+                "[heap] msg_cache size={:>3} alloc={:>5} chunks={} reused={}",
+                cache.slot_size, cache.allocated_count, cache.chunk_count, cache.reuse_count
+            );
+        }
     }
 }
 
@@ -642,6 +710,182 @@ unsafe impl GlobalAlloc for LockedSlabAllocator {
 // SlabAllocator は生ポインタを含むが、Mutex で排他制御しているので安全。
 unsafe impl Send for SlabAllocator {}
 
+// =============================================================================
+// MsgCache — 小さい IPC メッセージ専用のスラブキャッシュ
+// =============================================================================
+//
+// IPC はメッセージごとに送信データをコピーしたバッファを作る。GUI や selftest は
+// 数十バイトのメッセージを大量に送るので、毎回グローバルアロケータを通すと
+// ロックの取り合いとサイズクラス判定のコストがかさむ。
+//
+// そこで 16/32/64/128 バイトの専用キャッシュを用意する。各キャッシュは
+// グローバルヒープから 4 KiB のチャンクを借りてスロットに切り分け、
+// 解放されたスロットはフリーリストに戻して次のメッセージで使い回す。
+// チャンクはヒープに返さない（最大使用量ぶんだけ持ち続ける）ので、
+// 定常状態ではグローバルアロケータに一切触らない。
+
+/// IPC メッセージ用キャッシュのサイズクラス（バイト単位）
+const MSG_CACHE_SIZES: [usize; 4] = [16, 32, 64, 128];
+
+/// キャッシュがグローバルヒープから一度に借りるチャンクのサイズ
+const MSG_CACHE_CHUNK_SIZE: usize = 4096;
+
+/// 1 つのサイズクラスのキャッシュ。
+/// Slab と同じくフリーリスト + バンプポインタだが、領域は固定ではなく
+/// 足りなくなったらチャンク単位でヒープから継ぎ足す。
+struct MsgCache {
+    /// スロットのサイズ（バイト）
+    slot_size: usize,
+    /// 解放済みスロットのフリーリスト
+    free_list: *mut FreeNode,
+    /// 現在のチャンクの未使用部分の先頭（バンプポインタ）
+    next_uninit: usize,
+    /// 現在のチャンクの末尾（排他的）
+    chunk_end: usize,
+    /// 確保中のスロット数
+    allocated_count: usize,
+    /// ヒープから借りたチャンク数
+    chunk_count: usize,
+    /// フリーリストから使い回した回数
+    reuse_count: usize,
+}
+
+impl MsgCache {
+    const fn new(slot_size: usize) -> Self {
+        MsgCache {
+            slot_size,
+            free_list: ptr::null_mut(),
+            next_uninit: 0,
+            chunk_end: 0,
+            allocated_count: 0,
+            chunk_count: 0,
+            reuse_count: 0,
+        }
+    }
+
+    /// スロットを 1 つ確保する。チャンクを借りられなければ null。
+    fn alloc(&mut self) -> *mut u8 {
+        if !self.free_list.is_null() {
+            let node = self.free_list;
+            unsafe {
+                self.free_list = (*node).next;
+            }
+            self.allocated_count += 1;
+            self.reuse_count += 1;
+            return node as *mut u8;
+        }
+
+        if self.next_uninit + self.slot_size > self.chunk_end {
+            // チャンクの使い残し（slot_size 未満）は捨てる。4096 は全クラスで割り切れるので出ない
+            let layout = Layout::from_size_align(MSG_CACHE_CHUNK_SIZE, self.slot_size).unwrap();
+            let chunk = unsafe { alloc::alloc::alloc(layout) };
+            if chunk.is_null() {
+                return ptr::null_mut();
+            }
+            self.next_uninit = chunk as usize;
+            self.chunk_end = chunk as usize + MSG_CACHE_CHUNK_SIZE;
+            self.chunk_count += 1;
+        }
+
+        let ptr = self.next_uninit as *mut u8;
+        self.next_uninit += self.slot_size;
+        self.allocated_count += 1;
+        ptr
+    }
+
+    /// スロットをフリーリストに戻す。
+    ///
+    /// # Safety
+    /// - `ptr` はこのキャッシュから確保されたポインタであること
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        let node = ptr as *mut FreeNode;
+        unsafe {
+            (*node).next = self.free_list;
+        }
+        self.free_list = node;
+        self.allocated_count -= 1;
+    }
+}
+
+// MsgCache は生ポインタを含むが、MSG_CACHES の Mutex で排他制御しているので安全
+unsafe impl Send for MsgCache {}
+
+static MSG_CACHES: Mutex<[MsgCache; 4]> = Mutex::new([
+    MsgCache::new(16),
+    MsgCache::new(32),
+    MsgCache::new(64),
+    MsgCache::new(128),
+]);
+
+/// IPC メッセージの中身を持つバイト列。
+///
+/// 128 バイト以下なら MsgCache のスロット、それより大きければ通常の Vec に置く。
+/// Deref で &[u8] として読めるので、受け取る側は Vec<u8> と同じように扱える。
+pub struct MsgBuf {
+    repr: MsgBufRepr,
+}
+
+enum MsgBufRepr {
+    /// MsgCache のスロット。class は MSG_CACHE_SIZES のインデックス
+    Cached { ptr: *mut u8, len: usize, class: usize },
+    /// キャッシュに収まらない（またはキャッシュが確保に失敗した）場合
+    Heap(Vec<u8>),
+}
+
+impl MsgBuf {
+    /// data をコピーしたバッファを作る
+    pub fn from_slice(data: &[u8]) -> Self {
+        if let Some(class) = MSG_CACHE_SIZES.iter().position(|&size| !data.is_empty() && data.len() <= size) {
+            let ptr = MSG_CACHES.lock()[class].alloc();
+            if !ptr.is_null() {
+                unsafe { ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len()) };
+                return MsgBuf { repr: MsgBufRepr::Cached { ptr, len: data.len(), class } };
+            }
+        }
+        MsgBuf { repr: MsgBufRepr::Heap(data.to_vec()) }
+    }
+
+    /// MsgCache のスロットに置かれているか（テスト・統計用）
+    pub fn is_cached(&self) -> bool {
+        matches!(self.repr, MsgBufRepr::Cached { .. })
+    }
+}
+
+impl core::ops::Deref for MsgBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.repr {
+            MsgBufRepr::Cached { ptr, len, .. } => unsafe { core::slice::from_raw_parts(*ptr, *len) },
+            MsgBufRepr::Heap(v) => v,
+        }
+    }
+}
+
+impl Drop for MsgBuf {
+    fn drop(&mut self) {
+        if let MsgBufRepr::Cached { ptr, class, .. } = self.repr {
+            unsafe { MSG_CACHES.lock()[class].dealloc(ptr) };
+        }
+    }
+}
+
+impl Clone for MsgBuf {
+    fn clone(&self) -> Self {
+        MsgBuf::from_slice(self)
+    }
+}
+
+impl core::fmt::Debug for MsgBuf {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+// スロットは MsgBuf が排他的に所有しているので、タスク間で受け渡して問題ない
+unsafe impl Send for MsgBuf {}
+unsafe impl Sync for MsgBuf {}
+
 // =============================================================================
 // ユーティリティ関数
 // =============================================================================
@@ -665,6 +909,7 @@ fn align_up(addr: usize, align: usize) -> usize {
 /// 2. アライメント要件のある確保
 /// 3. 大オブジェクトの確保・解放
 /// 4. 全サイズクラスの混合ストレステスト
+/// 5. IPC メッセージ用キャッシュ（16/32/64/128B）の確保とフリーリストの再利用
 pub fn test_slab_allocator() -> bool {
     use alloc::boxed::Box;
     use alloc::vec;
@@ -814,5 +1059,52 @@ pub fn test_slab_allocator() -> bool {
         }
     }
 
+    // === テスト 5: IPC メッセージ用キャッシュ ===
+    // 各サイズクラスちょうどの長さで確保し、解放直後に同じ長さで確保すると
+    // フリーリストの先頭（= いま解放したスロット）が返ることを確認する。
+    // 129 バイト以上と空のメッセージはヒープ（Vec）に置かれる。
+    {
+        for &size in &MSG_CACHE_SIZES {
+            let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+            // 間に他のタスクがキャッシュを使うとスロットがずれるので割り込みを止める
+            let ok = x86_64::instructions::interrupts::without_interrupts(|| {
+                let first = MsgBuf::from_slice(&data);
+                if !first.is_cached() || first[..] != data[..] || !(first.as_ptr() as usize).is_multiple_of(size) {
+                    crate::serial_println!("[slab_test] msg cache: bad first alloc (size={})", size);
+                    return false;
+                }
+                let first_ptr = first.as_ptr();
+                drop(first);
+                let second = MsgBuf::from_slice(&data[..size - 1]);
+                if second.as_ptr() != first_ptr || second[..] != data[..size - 1] {
+                    crate::serial_println!("[slab_test] msg cache: slot not reused (size={})", size);
+                    return false;
+                }
+                // clone は別のスロットになる
+                let cloned = second.clone();
+                cloned.is_cached() && cloned.as_ptr() != second.as_ptr() && cloned[..] == second[..]
+            });
+            if !ok {
+                crate::serial_println!("[slab_test] msg cache: check failed (size={})", size);
+                return false;
+            }
+        }
+
+        // チャンクをまたぐ数を確保しても中身が混ざらない
+        let bufs: Vec<MsgBuf> = (0..300u32).map(|i| MsgBuf::from_slice(&i.to_le_bytes())).collect();
+        for (i, b) in bufs.iter().enumerate() {
+            if !b.is_cached() || b[..] != (i as u32).to_le_bytes() {
+                crate::serial_println!("[slab_test] msg cache: data mismatch at {}", i);
+                return false;
+            }
+        }
+        drop(bufs);
+
+        if MsgBuf::from_slice(&[0u8; 129]).is_cached() || MsgBuf::from_slice(&[]).is_cached() {
+            crate::serial_println!("[slab_test] msg cache: unexpected cache use");
+            return false;
+        }
+    }
+
     true
 }
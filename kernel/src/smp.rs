@@ -0,0 +1,381 @@
+// smp.rs — AP (Application Processor) の起動
+//
+// これまでは BSP（電源投入時に動いている CPU）だけで動いていて、
+// ACPI の MADT に載っている他のコア（AP）は一度も起こしていなかった。
+// ここでは AP を起動して、それぞれ専用の GDT/TSS とスタックを持たせ、
+// 何もしないアイドルループ（hlt）で待たせる。マルチコアスケジューラの土台。
+//
+// AP は INIT-SIPI-SIPI で起きると、16 ビットのリアルモードで
+// 「SIPI のベクタ × 4KiB」の物理アドレスから実行を始める。
+// そこで 1MiB 未満の空きページにトランポリン（下の global_asm!）をコピーしておき、
+//
+//   リアルモード → 32 ビットプロテクトモード → ロングモード（BSP と同じ CR3）
+//
+// と切り替えてから、Rust の ap_entry() に飛ぶ。
+// トランポリンは 1 つしかないので、AP は 1 つずつ順番に起こす
+// （前の AP が ap_entry() まで来たのを確かめてから次を起こす）。
+//
+// IDT は全 CPU で同じものを使う。TSS は IST のスタックや rsp0 を CPU ごとに
+// 持つ必要があるので、GDT ごと CPU ごとに作る（gdt::CpuGdt）。
+// GS ベースは CPU ごとのブロック（percpu.rs）に向ける。
+// AP はまだロックを取らない（lock_order の「持っているロック」は CPU 1 つ分しかない）。
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use uefi::mem::memory_map::{MemoryMap, MemoryMapOwned, MemoryType};
+use x86_64::VirtAddr;
+
+use crate::kprintln;
+
+/// 扱う CPU の最大数（BSP を含む）
+pub const MAX_CPUS: usize = 16;
+
+/// AP ごとのカーネルスタックのサイズ
+const AP_STACK_SIZE: usize = 4096 * 4;
+
+/// SIPI を送ってから AP が ap_entry() に着くまで待つティック数（約 0.3 秒）
+const AP_START_TICKS: u64 = 5;
+
+/// CPU 番号（BSP = 0、AP は起動した順に 1, 2, ...）ごとの「アイドルループに入った」フラグ。
+/// AP が自分で立て、BSP が読む。
+static AP_IDLE: [AtomicBool; MAX_CPUS] = [const { AtomicBool::new(false) }; MAX_CPUS];
+
+/// 動いている CPU の数（BSP を含む）
+static ONLINE_CPUS: AtomicUsize = AtomicUsize::new(1);
+
+// =================================================================
+// トランポリン
+// =================================================================
+//
+// 1MiB 未満のページにコピーして使うので、位置に依存しない書き方をする。
+// リアルモードでは CS = ページ >> 4 で始まるので DS に同じ値を入れ、
+// ページ先頭からのオフセットでデータを読む。32 ビット以降は ebx/rbx に
+// ページの物理アドレスを入れておき、[rbx + オフセット] で読む。
+// 末尾のデータ領域（ap_tramp_*）はコピーした後にカーネルが書き込む。
+global_asm!(
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_end",
+    ".global ap_tramp_pm",
+    ".global ap_tramp_lm",
+    ".global ap_tramp_gdt",
+    ".global ap_tramp_gdt_base",
+    ".global ap_tramp_pm_ptr",
+    ".global ap_tramp_lm_ptr",
+    ".global ap_tramp_cr0",
+    ".global ap_tramp_cr3",
+    ".global ap_tramp_cr4",
+    ".global ap_tramp_efer",
+    ".global ap_tramp_stack",
+    ".global ap_tramp_entry",
+    ".global ap_tramp_arg",
+    ".code16",
+    "ap_trampoline_start:",
+    "cli",
+    "cld",
+    "mov ax, cs",
+    "mov ds, ax",
+    // ebx = このページの物理アドレス（モードを切り替えても残る）
+    "xor ebx, ebx",
+    "mov bx, ax",
+    "shl ebx, 4",
+    "lgdt [AP_TRAMP_OFF_GDTR]",
+    "mov eax, cr0",
+    "or eax, 1",
+    "mov cr0, eax",
+    // 32 ビットコードセグメント (0x08) へ far jump。ページが 64KiB を超える位置にあっても
+    // 届くよう、オペランドサイズプレフィックス (0x66) で 16:32 のポインタを読ませる
+    // （LLVM の Intel 構文ではこの形を書けないのでバイトで置く）
+    ".byte 0x66",
+    "ljmp dword ptr [AP_TRAMP_OFF_PM_PTR]",
+
+    ".code32",
+    "ap_tramp_pm:",
+    "mov ax, 0x10",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    // BSP と同じ CR4（PAE を含む）→ CR3 → EFER（LME / NXE）→ CR0（PG）の順に設定する
+    "mov eax, [ebx + AP_TRAMP_OFF_CR4]",
+    "mov cr4, eax",
+    "mov eax, [ebx + AP_TRAMP_OFF_CR3]",
+    "mov cr3, eax",
+    "mov ecx, 0xC0000080",
+    "mov eax, [ebx + AP_TRAMP_OFF_EFER]",
+    "xor edx, edx",
+    "wrmsr",
+    "mov eax, [ebx + AP_TRAMP_OFF_CR0]",
+    "mov cr0, eax",
+    // 64 ビットコードセグメント (0x18) へ far jump するとロングモードに入る
+    "ljmp dword ptr [ebx + AP_TRAMP_OFF_LM_PTR]",
+
+    ".code64",
+    "ap_tramp_lm:",
+    // 上位 32 ビットを 0 にしておく
+    "mov ebx, ebx",
+    "mov rsp, [rbx + AP_TRAMP_OFF_STACK]",
+    "mov rdi, [rbx + AP_TRAMP_OFF_ARG]",
+    "mov rax, [rbx + AP_TRAMP_OFF_ENTRY]",
+    // バックトレースがここで止まるように rbp = 0 にする
+    "xor ebp, ebp",
+    "call rax",
+    "2:",
+    "hlt",
+    "jmp 2b",
+
+    // --- データ領域 ---
+    ".balign 8",
+    "ap_tramp_gdt:",
+    ".quad 0",
+    ".quad 0x00CF9A000000FFFF", // 0x08: 32 ビットコード
+    ".quad 0x00CF92000000FFFF", // 0x10: 32 ビットデータ
+    ".quad 0x00AF9A000000FFFF", // 0x18: 64 ビットコード
+    "ap_tramp_gdtr:",
+    ".word 31",
+    "ap_tramp_gdt_base: .long 0",
+    ".balign 4",
+    "ap_tramp_pm_ptr:",
+    ".long 0", // ap_tramp_pm の物理アドレス
+    ".word 0x08",
+    ".balign 4",
+    "ap_tramp_lm_ptr:",
+    ".long 0", // ap_tramp_lm の物理アドレス
+    ".word 0x18",
+    ".balign 8",
+    "ap_tramp_cr0: .quad 0",
+    "ap_tramp_cr3: .quad 0",
+    "ap_tramp_cr4: .quad 0",
+    "ap_tramp_efer: .quad 0",
+    "ap_tramp_stack: .quad 0",
+    "ap_tramp_entry: .quad 0",
+    "ap_tramp_arg: .quad 0",
+    "ap_trampoline_end:",
+    // トランポリン先頭からのオフセット（Intel 構文のメモリオペランドには
+    // ラベルの差を直接書けないので、定数にしておく）
+    ".set AP_TRAMP_OFF_GDTR, ap_tramp_gdtr - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_PM_PTR, ap_tramp_pm_ptr - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_CR4, ap_tramp_cr4 - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_CR3, ap_tramp_cr3 - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_EFER, ap_tramp_efer - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_CR0, ap_tramp_cr0 - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_LM_PTR, ap_tramp_lm_ptr - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_STACK, ap_tramp_stack - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_ARG, ap_tramp_arg - ap_trampoline_start",
+    ".set AP_TRAMP_OFF_ENTRY, ap_tramp_entry - ap_trampoline_start",
+);
+
+unsafe extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_end: u8;
+    static ap_tramp_pm: u8;
+    static ap_tramp_lm: u8;
+    static ap_tramp_gdt: u8;
+    static ap_tramp_gdt_base: u8;
+    static ap_tramp_pm_ptr: u8;
+    static ap_tramp_lm_ptr: u8;
+    static ap_tramp_cr0: u8;
+    static ap_tramp_cr3: u8;
+    static ap_tramp_cr4: u8;
+    static ap_tramp_efer: u8;
+    static ap_tramp_stack: u8;
+    static ap_tramp_entry: u8;
+    static ap_tramp_arg: u8;
+}
+
+/// 1MiB 未満にコピーしたトランポリン
+struct Trampoline {
+    /// コピー先のページの物理アドレス（= 仮想アドレス）
+    page: u64,
+}
+
+impl Trampoline {
+    /// トランポリンの大きさ（バイト）
+    fn len() -> usize {
+        (&raw const ap_trampoline_end) as usize - (&raw const ap_trampoline_start) as usize
+    }
+
+    /// トランポリン内のシンボルの、コピー先での物理アドレス
+    fn addr_of(&self, symbol: *const u8) -> u64 {
+        self.page + (symbol as u64 - (&raw const ap_trampoline_start) as u64)
+    }
+
+    fn write<T>(&self, symbol: *const u8, value: T) {
+        unsafe { core::ptr::write_volatile(self.addr_of(symbol) as *mut T, value) };
+    }
+
+    /// トランポリンを page にコピーし、どの AP でも同じ値を書き込む
+    ///
+    /// # Safety
+    /// page は 1MiB 未満の、他で使われていない 4KiB ページであること。
+    unsafe fn install(page: u64) -> Self {
+        use x86_64::registers::control::{Cr0, Cr4, Cr4Flags};
+        use x86_64::registers::model_specific::Efer;
+
+        let start = &raw const ap_trampoline_start;
+        unsafe { core::ptr::copy_nonoverlapping(start, page as *mut u8, Self::len()) };
+
+        let t = Trampoline { page };
+        t.write(&raw const ap_tramp_gdt_base, t.addr_of(&raw const ap_tramp_gdt) as u32);
+        t.write(&raw const ap_tramp_pm_ptr, t.addr_of(&raw const ap_tramp_pm) as u32);
+        t.write(&raw const ap_tramp_lm_ptr, t.addr_of(&raw const ap_tramp_lm) as u32);
+        t.write(&raw const ap_tramp_cr0, Cr0::read_raw());
+        t.write(&raw const ap_tramp_cr3, crate::paging::kernel_cr3().as_u64());
+        // PCIDE はロングモードに入ってからでないと立てられないので落とす
+        let cr4 = (Cr4::read() | Cr4Flags::PHYSICAL_ADDRESS_EXTENSION) - Cr4Flags::PCID;
+        t.write(&raw const ap_tramp_cr4, cr4.bits());
+        // LMA（ビット 10）は CPU が立てるビットなので落としておく
+        t.write(&raw const ap_tramp_efer, Efer::read_raw() & !(1 << 10));
+        t.write(&raw const ap_tramp_entry, ap_entry as *const () as u64);
+        t
+    }
+
+    /// SIPI に載せるベクタ（ページ番号）
+    fn vector(&self) -> u8 {
+        (self.page >> 12) as u8
+    }
+}
+
+// =================================================================
+// AP 側
+// =================================================================
+
+/// ap_entry() に渡す、AP ごとの情報。BSP が用意してリークする。
+struct ApContext {
+    /// CPU 番号
+    cpu: usize,
+    gdt: &'static crate::gdt::CpuGdt,
+}
+
+/// AP がトランポリンから最初に来る関数。
+/// トランポリンは System V の呼び出し規約で rdi に引数を入れて call する。
+extern "sysv64" fn ap_entry(ctx: &'static ApContext) -> ! {
+    ctx.gdt.load();
+    crate::percpu::init(ctx.cpu, ctx.gdt.tss());
+    crate::interrupts::init_ap();
+    AP_IDLE[ctx.cpu].store(true, Ordering::Release);
+
+    // アイドルループ。割り込みは受けないので、INIT か NMI が来るまで止まったまま。
+    loop {
+        x86_64::instructions::interrupts::disable();
+        x86_64::instructions::hlt();
+    }
+}
+
+// =================================================================
+// BSP 側
+// =================================================================
+
+/// 1MiB 未満の空きページ（CONVENTIONAL）を 1 つ探す。
+/// ページ 0 はリアルモードの割り込みベクタテーブルなので使わない。
+fn find_trampoline_page(memory_map: &MemoryMapOwned) -> Option<u64> {
+    memory_map
+        .entries()
+        .filter(|d| d.ty == MemoryType::CONVENTIONAL)
+        .find_map(|d| {
+            let start = d.phys_start.max(0x1000);
+            let end = (d.phys_start + d.page_count * 4096).min(0x10_0000);
+            (start + 4096 <= end).then_some(start)
+        })
+}
+
+/// cpu の AP がアイドルループに入るまで最大 ticks ティック待つ。
+///
+/// 起動中はまだ他のタスクがいないので sleep_ms() ではなく hlt で待つ。
+fn wait_for_ap(cpu: usize, ticks: u64) -> bool {
+    let ticks_now = || crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    let until = ticks_now() + ticks;
+    while !AP_IDLE[cpu].load(Ordering::Acquire) {
+        if ticks_now() >= until {
+            return false;
+        }
+        x86_64::instructions::hlt();
+    }
+    true
+}
+
+/// APIC ID が apic_id の AP を CPU 番号 cpu として起動する
+fn start_ap(t: &Trampoline, cpu: usize, apic_id: u32) -> bool {
+    let stack = Box::leak(vec![0u8; AP_STACK_SIZE].into_boxed_slice());
+    let stack_top = (stack.as_ptr() as u64 + AP_STACK_SIZE as u64) & !0xF;
+    let ctx: &'static ApContext = Box::leak(Box::new(ApContext {
+        cpu,
+        gdt: crate::gdt::alloc_cpu_gdt(),
+    }));
+    t.write(&raw const ap_tramp_stack, stack_top);
+    t.write(&raw const ap_tramp_arg, ctx as *const ApContext as u64);
+
+    // INIT → 10ms 以上待つ → SIPI → 来なければもう一度 SIPI
+    crate::apic::send_init_ipi(apic_id);
+    wait_for_ap(cpu, 1);
+    for _ in 0..2 {
+        crate::apic::send_startup_ipi(apic_id, t.vector());
+        if wait_for_ap(cpu, AP_START_TICKS) {
+            return true;
+        }
+    }
+    false
+}
+
+/// ACPI の MADT に載っている AP を起動して、アイドルループで待たせる。
+///
+/// APIC の初期化（apic::init()）の後、タスクを spawn する前に呼ぶ。
+pub fn init(memory_map: &MemoryMapOwned) {
+    let info = match crate::acpi::get_apic_info() {
+        Some(info) if crate::apic::is_apic_active() => info,
+        _ => {
+            kprintln!("SMP: APIC not active, running on 1 CPU");
+            return;
+        }
+    };
+    let ap_ids = &info.ap_apic_ids;
+    if ap_ids.is_empty() {
+        kprintln!("SMP: no application processors, running on 1 CPU");
+        return;
+    }
+    // MADT の BSP が今動いている CPU と違うと、AP の一覧に自分が紛れ込んでいるかもしれない。
+    // 自分に INIT を送ると止まってしまうので、そのときは AP を起こさない。
+    let bsp_apic_id = crate::apic::local_apic_id();
+    if bsp_apic_id != Some(info.bsp_apic_id) {
+        kprintln!("SMP: BSP APIC ID {:?} does not match MADT ({}), running on 1 CPU",
+            bsp_apic_id, info.bsp_apic_id);
+        return;
+    }
+    // トランポリンは 32 ビットモードで CR3 を読み込むので 4GiB 未満でないといけない
+    if crate::paging::kernel_cr3().as_u64() >= 1 << 32 {
+        kprintln!("SMP: kernel page table is above 4GiB, not starting APs");
+        return;
+    }
+    let Some(page) = find_trampoline_page(memory_map) else {
+        kprintln!("SMP: no free page below 1MiB for the AP trampoline");
+        return;
+    };
+    if Trampoline::len() > 4096 || !crate::paging::make_identity_page_executable(VirtAddr::new(page)) {
+        kprintln!("SMP: cannot prepare the AP trampoline at {:#x}", page);
+        return;
+    }
+    let trampoline = unsafe { Trampoline::install(page) };
+
+    for &apic_id in ap_ids.iter().take(MAX_CPUS - 1) {
+        let cpu = ONLINE_CPUS.load(Ordering::Relaxed);
+        if !start_ap(&trampoline, cpu, apic_id) {
+            // 遅れて起きた AP が書き換え途中のトランポリンを読まないよう、ここでやめる
+            kprintln!("SMP: AP with APIC ID {} did not start", apic_id);
+            break;
+        }
+        ONLINE_CPUS.store(cpu + 1, Ordering::Relaxed);
+    }
+
+    let online = ONLINE_CPUS.load(Ordering::Relaxed);
+    kprintln!("SMP: {} CPUs online ({} APs started)", online, online - 1);
+}
+
+/// 動いている CPU の数（BSP を含む）
+pub fn online_cpus() -> usize {
+    ONLINE_CPUS.load(Ordering::Relaxed)
+}
+
+/// CPU 番号 cpu の AP がアイドルループに入っているか
+pub fn is_ap_idle(cpu: usize) -> bool {
+    cpu < MAX_CPUS && AP_IDLE[cpu].load(Ordering::Acquire)
+}
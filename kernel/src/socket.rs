@@ -0,0 +1,527 @@
+// socket.rs — 統一ソケット層
+//
+// SYS_NET_TCP_* / SYS_NET_UDP_* はプロトコルごとに別の ID（conn_id / socket_id）を
+// 扱うため、ユーザー側は TCP と UDP で別々のコードを書く必要があった。
+// このモジュールは POSIX の socket/bind/connect/listen/accept/send/recv に似た
+// 共通の操作を提供し、内部で netstack の TCP / UDP 実装に振り分ける。
+//
+// ソケットはハンドル（handle.rs の HandleKind::Socket）として公開されるので、
+// SYS_HANDLE_READ / WRITE / CLOSE でも読み書き・クローズできる。
+//
+// ## 状態遷移
+//
+// - TCP: Unbound → (bind) → Bound → (listen) → Listening
+//        Unbound / Bound → (connect) → Connected
+//        Listening → (accept) → 新しいソケットが Connected で生まれる
+// - UDP: Unbound → (bind / connect / send) → Udp
+//
+// ## ロックについて
+//
+// netstack の connect / recv / accept は待ちに入るので、SOCKET_TABLE のロックを
+// 持ったまま呼ばない。必要な情報をコピーしてからロックを外して呼び、
+// 結果を反映するときにもう一度ロックを取る（pipe.rs / handle.rs と同じ方針）。
+
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+use crate::netstack::IpAddr;
+use crate::user_ptr::SyscallError;
+
+/// ソケットの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    /// ストリーム（TCP）
+    Stream,
+    /// データグラム（UDP）
+    Datagram,
+}
+
+/// ソケットの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocketState {
+    /// 作成直後
+    Unbound,
+    /// TCP: ローカルポートだけ決まっている（listen 待ち）
+    Bound(u16),
+    /// TCP: 指定ポートで待ち受け中
+    Listening(u16),
+    /// TCP: 接続済み（netstack の conn_id）
+    Connected(u32),
+    /// UDP: netstack の UDP ソケットにバインド済み（netstack の socket_id）
+    Udp(u32),
+}
+
+/// ソケット 1 つ分の情報
+struct Socket {
+    /// アドレスファミリー（AF_INET / AF_INET6）
+    family: u64,
+    /// ソケットの種類
+    ty: SocketType,
+    /// 現在の状態
+    state: SocketState,
+    /// UDP: connect() で設定された既定の宛先
+    peer: Option<(IpAddr, u16)>,
+    /// TCP: 前回の recv でユーザーのバッファに入りきらなかった受信データ。
+    /// netstack の tcp_recv は受信バッファを丸ごと返すので、余りをここに取っておく。
+    pending: Vec<u8>,
+    /// このソケットを指しているハンドルの数（ハンドル複製で増える）
+    refs: usize,
+    /// ソケットオプション（SYS_SETSOCKOPT で設定した値）
+    options: SocketOptions,
+}
+
+/// ソケットオプション
+///
+/// 接続前・bind 前に設定されたものは、netstack の TCP 接続 / UDP ソケットが
+/// できた時点で反映する。
+#[derive(Clone, Copy, Default)]
+struct SocketOptions {
+    /// TCP: TCP_NODELAY
+    nodelay: bool,
+    /// SO_RCVTIMEO（ミリ秒、0 = 設定なし）。SOCKET_WAIT_FOREVER の受信をこの時間で打ち切る
+    recv_timeout_ms: u64,
+    /// SO_REUSEADDR。UDP は bind 時に netstack に渡す
+    reuse_addr: bool,
+    /// TCP: SO_LINGER（linger 時間 0）。close で RST を送る
+    linger_abort: bool,
+}
+
+lazy_static! {
+    /// グローバルソケットテーブル
+    ///
+    /// 各エントリは Option<Socket> で、None は空きスロット。
+    /// socket_id はこの Vec のインデックス。
+    static ref SOCKET_TABLE: Mutex<Vec<Option<Socket>>> = Mutex::new(Vec::new());
+}
+
+/// 新しいソケットを作成し、socket_id を返す
+///
+/// proto は 0（種類から自動選択）か、種類に合ったプロトコル番号だけを受け付ける。
+/// TCP も UDP も AF_INET / AF_INET6 の両方を作れる（IPv4 / IPv6 は connect の宛先で決まる）。
+pub fn create(family: u64, ty: u64, proto: u64) -> Result<usize, SyscallError> {
+    use sabos_syscall::{AF_INET, AF_INET6, IPPROTO_TCP, IPPROTO_UDP, SOCK_DGRAM, SOCK_STREAM};
+
+    if family != AF_INET && family != AF_INET6 {
+        return Err(SyscallError::NotSupported);
+    }
+    let ty = match (ty, proto) {
+        (SOCK_STREAM, 0) | (SOCK_STREAM, IPPROTO_TCP) => SocketType::Stream,
+        (SOCK_DGRAM, 0) | (SOCK_DGRAM, IPPROTO_UDP) => SocketType::Datagram,
+        (SOCK_STREAM, _) | (SOCK_DGRAM, _) => return Err(SyscallError::InvalidArgument),
+        _ => return Err(SyscallError::NotSupported),
+    };
+    Ok(insert(Socket {
+        family,
+        ty,
+        state: SocketState::Unbound,
+        peer: None,
+        pending: Vec::new(),
+        refs: 1,
+        options: SocketOptions::default(),
+    }))
+}
+
+/// ソケットを参照するハンドルが増えたときに呼ぶ（duplicate_handle 等）
+pub fn add_ref(socket_id: usize) {
+    let mut table = SOCKET_TABLE.lock();
+    if let Some(Some(sock)) = table.get_mut(socket_id) {
+        sock.refs += 1;
+    }
+}
+
+/// ソケットをローカルアドレスに割り当てる
+///
+/// IP アドレスは無視する（SABOS のインターフェースは 1 つだけ）。
+/// UDP は port = 0 ならエフェメラルポートが割り当てられる。
+/// 実際に割り当てたポート番号を返す。
+pub fn bind(socket_id: usize, port: u16) -> Result<u16, SyscallError> {
+    let ty = with_socket(socket_id, |sock| {
+        if sock.state != SocketState::Unbound {
+            return Err(SyscallError::InvalidArgument);
+        }
+        Ok(sock.ty)
+    })?;
+
+    match ty {
+        SocketType::Stream => {
+            // TCP の待ち受けポートは自動割り当てしない
+            if port == 0 {
+                return Err(SyscallError::InvalidArgument);
+            }
+            with_socket(socket_id, |sock| {
+                sock.state = SocketState::Bound(port);
+                Ok(port)
+            })
+        }
+        SocketType::Datagram => {
+            let udp_id = bind_udp(socket_id, port)?;
+            crate::netstack::udp_local_port(udp_id).map_err(|_| SyscallError::Other)
+        }
+    }
+}
+
+/// 接続先を設定する
+///
+/// TCP は 3-way ハンドシェイクを行い、完了するまで待つ。
+/// UDP は既定の宛先を覚えるだけ（未バインドならエフェメラルポートにバインドする）。
+pub fn connect(socket_id: usize, ip: IpAddr, port: u16) -> Result<(), SyscallError> {
+    let (ty, state) = with_socket(socket_id, |sock| Ok((sock.ty, sock.state)))?;
+
+    match ty {
+        SocketType::Stream => {
+            if !matches!(state, SocketState::Unbound | SocketState::Bound(_)) {
+                return Err(SyscallError::InvalidArgument);
+            }
+            let conn_id = match ip {
+                IpAddr::V4(v4) => crate::netstack::tcp_connect(v4, port),
+                IpAddr::V6(v6) => crate::netstack::tcp_connect6(v6, port),
+            }
+            .map_err(|_| SyscallError::Other)?;
+            let options = with_socket(socket_id, |sock| {
+                sock.state = SocketState::Connected(conn_id);
+                Ok(sock.options)
+            })?;
+            apply_tcp_options(conn_id, options);
+            Ok(())
+        }
+        SocketType::Datagram => {
+            ensure_udp_bound(socket_id)?;
+            with_socket(socket_id, |sock| {
+                sock.peer = Some((ip, port));
+                Ok(())
+            })
+        }
+    }
+}
+
+/// TCP の待ち受けを開始する
+///
+/// backlog は accept 待ちの接続の上限（0 なら既定値）。あふれた接続要求は RST で断る。
+pub fn listen(socket_id: usize, backlog: usize) -> Result<(), SyscallError> {
+    let port = with_socket(socket_id, |sock| match sock.state {
+        SocketState::Bound(port) => Ok(port),
+        // 二重 listen は backlog だけ変える
+        SocketState::Listening(port) => Ok(port),
+        _ => Err(SyscallError::InvalidArgument),
+    })?;
+    crate::netstack::tcp_listen(port, backlog).map_err(|_| SyscallError::Other)?;
+    with_socket(socket_id, |sock| {
+        sock.state = SocketState::Listening(port);
+        Ok(())
+    })
+}
+
+/// 接続を受け入れ、接続済みの新しいソケットと相手のアドレスを返す
+///
+/// timeout_ms = 0 ならノンブロッキング（接続がなければ WouldBlock）。
+/// 待ち受けソケットのオプション（TCP_NODELAY / SO_RCVTIMEO 等）は新しいソケットに引き継ぐ。
+pub fn accept(socket_id: usize, timeout_ms: u64) -> Result<(usize, IpAddr, u16), SyscallError> {
+    let (family, port, options) = with_socket(socket_id, |sock| match sock.state {
+        SocketState::Listening(port) => Ok((sock.family, port, sock.options)),
+        _ => Err(SyscallError::InvalidArgument),
+    })?;
+
+    let conn_id = if timeout_ms == 0 {
+        crate::netstack::tcp_try_accept(port).ok_or(SyscallError::WouldBlock)?
+    } else {
+        crate::netstack::tcp_accept(timeout_ms, port).map_err(|_| SyscallError::Timeout)?
+    };
+    let (peer_ip, peer_port) = crate::netstack::tcp_peer_addr(conn_id).unwrap_or((IpAddr::V4([0; 4]), 0));
+    apply_tcp_options(conn_id, options);
+
+    let new_id = insert(Socket {
+        family,
+        ty: SocketType::Stream,
+        state: SocketState::Connected(conn_id),
+        peer: None,
+        pending: Vec::new(),
+        refs: 1,
+        options,
+    });
+    Ok((new_id, peer_ip, peer_port))
+}
+
+/// データを送信し、送信したバイト数を返す
+///
+/// dst が None なら接続先（TCP の相手 / UDP の connect 先）に送る。
+/// TCP では dst は無視する。UDP が未バインドならエフェメラルポートにバインドする。
+pub fn send(socket_id: usize, data: &[u8], dst: Option<(IpAddr, u16)>) -> Result<usize, SyscallError> {
+    let (ty, state, peer) = with_socket(socket_id, |sock| Ok((sock.ty, sock.state, sock.peer)))?;
+
+    match ty {
+        SocketType::Stream => {
+            let SocketState::Connected(conn_id) = state else {
+                return Err(SyscallError::InvalidArgument);
+            };
+            crate::netstack::tcp_send(conn_id, data).map_err(|_| SyscallError::Other)?;
+        }
+        SocketType::Datagram => {
+            let (ip, port) = dst.or(peer).ok_or(SyscallError::InvalidArgument)?;
+            let udp_id = ensure_udp_bound(socket_id)?;
+            crate::netstack::udp_send_to(udp_id, ip, port, data).map_err(|_| SyscallError::Other)?;
+        }
+    }
+    Ok(data.len())
+}
+
+/// データを受信し、(受信バイト数, 送信元) を返す
+///
+/// - timeout_ms = 0: ノンブロッキング（データがなければ WouldBlock）
+/// - それ以外: 最大 timeout_ms 待ち、来なければ Timeout
+///   （SOCKET_WAIT_FOREVER でも SO_RCVTIMEO が設定されていればその時間で打ち切る）
+///
+/// TCP は相手が FIN を送っていれば 0（EOF）を返す。送信元は接続相手。
+/// UDP は 1 データグラムずつ返し、buf に入りきらない部分は捨てる。
+pub fn recv(socket_id: usize, buf: &mut [u8], timeout_ms: u64) -> Result<(usize, Option<(IpAddr, u16)>), SyscallError> {
+    // 前回の余りがあればそれを先に返す
+    let (ty, state, pending, recv_timeout_ms) = with_socket(socket_id, |sock| {
+        Ok((sock.ty, sock.state, take_pending(sock, buf), sock.options.recv_timeout_ms))
+    })?;
+    if let Some(n) = pending {
+        let peer = match state {
+            SocketState::Connected(conn_id) => tcp_peer(conn_id),
+            _ => None,
+        };
+        return Ok((n, peer));
+    }
+    let timeout_ms = if timeout_ms == sabos_syscall::SOCKET_WAIT_FOREVER && recv_timeout_ms != 0 {
+        recv_timeout_ms
+    } else {
+        timeout_ms
+    };
+
+    match ty {
+        SocketType::Stream => {
+            let SocketState::Connected(conn_id) = state else {
+                return Err(SyscallError::InvalidArgument);
+            };
+            let result = if timeout_ms == 0 {
+                crate::netstack::tcp_try_recv(conn_id)
+                    .and_then(|data| data.ok_or("would block"))
+            } else {
+                crate::netstack::tcp_recv(conn_id, timeout_ms)
+            };
+            let data = match result {
+                Ok(data) => data,
+                Err("connection closed") => return Ok((0, tcp_peer(conn_id))),
+                Err("would block") => return Err(SyscallError::WouldBlock),
+                Err("timeout") => return Err(SyscallError::Timeout),
+                Err(_) => return Err(SyscallError::Other),
+            };
+            let n = data.len().min(buf.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            if n < data.len() {
+                with_socket(socket_id, |sock| {
+                    sock.pending.extend_from_slice(&data[n..]);
+                    Ok(())
+                })?;
+            }
+            Ok((n, tcp_peer(conn_id)))
+        }
+        SocketType::Datagram => {
+            let SocketState::Udp(udp_id) = state else {
+                // 一度も bind / send していない UDP ソケットには何も届かない
+                return Err(SyscallError::InvalidArgument);
+            };
+            let (src_ip, src_port, data) = if timeout_ms == 0 {
+                crate::netstack::udp_try_recv_from(udp_id)
+                    .map_err(|_| SyscallError::Other)?
+                    .ok_or(SyscallError::WouldBlock)?
+            } else {
+                crate::netstack::udp_recv_from(udp_id, timeout_ms).map_err(|e| match e {
+                    "timeout" => SyscallError::Timeout,
+                    _ => SyscallError::Other,
+                })?
+            };
+            let n = data.len().min(buf.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            Ok((n, Some((src_ip, src_port))))
+        }
+    }
+}
+
+/// ソケットオプションを設定する
+///
+/// TCP_NODELAY（level = IPPROTO_TCP）、SO_RCVTIMEO / SO_REUSEADDR / SO_LINGER（level = SOL_SOCKET）に対応する。
+/// 知らないオプションは NotSupported、UDP ソケットへの TCP オプションは InvalidArgument。
+pub fn setsockopt(socket_id: usize, level: u64, optname: u64, value: u64) -> Result<(), SyscallError> {
+    use sabos_syscall::{IPPROTO_TCP, SOL_SOCKET, SO_LINGER, SO_RCVTIMEO, SO_REUSEADDR, TCP_NODELAY};
+
+    let state = with_socket(socket_id, |sock| {
+        match (level, optname) {
+            (IPPROTO_TCP, TCP_NODELAY) => {
+                if sock.ty != SocketType::Stream {
+                    return Err(SyscallError::InvalidArgument);
+                }
+                sock.options.nodelay = value != 0;
+            }
+            (SOL_SOCKET, SO_RCVTIMEO) => sock.options.recv_timeout_ms = value,
+            (SOL_SOCKET, SO_REUSEADDR) => sock.options.reuse_addr = value != 0,
+            (SOL_SOCKET, SO_LINGER) => {
+                if sock.ty != SocketType::Stream {
+                    return Err(SyscallError::InvalidArgument);
+                }
+                sock.options.linger_abort = value != 0;
+            }
+            _ => return Err(SyscallError::NotSupported),
+        }
+        Ok(sock.state)
+    })?;
+
+    // netstack の接続 / ソケットができていればそちらにも反映する
+    match state {
+        SocketState::Connected(net_id) | SocketState::Udp(net_id) => {
+            crate::netstack::set_socket_option(net_id, level, optname, value).map_err(sockopt_error)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// ソケットオプションの現在値を取得する（フラグは 0 / 1 で返す）
+///
+/// エラーは setsockopt と同じ。
+pub fn getsockopt(socket_id: usize, level: u64, optname: u64) -> Result<u64, SyscallError> {
+    use sabos_syscall::{IPPROTO_TCP, SOL_SOCKET, SO_LINGER, SO_RCVTIMEO, SO_REUSEADDR, TCP_NODELAY};
+
+    with_socket(socket_id, |sock| match (level, optname) {
+        (IPPROTO_TCP, TCP_NODELAY) if sock.ty == SocketType::Stream => Ok(sock.options.nodelay as u64),
+        (IPPROTO_TCP, TCP_NODELAY) => Err(SyscallError::InvalidArgument),
+        (SOL_SOCKET, SO_RCVTIMEO) => Ok(sock.options.recv_timeout_ms),
+        (SOL_SOCKET, SO_REUSEADDR) => Ok(sock.options.reuse_addr as u64),
+        (SOL_SOCKET, SO_LINGER) if sock.ty == SocketType::Stream => Ok(sock.options.linger_abort as u64),
+        (SOL_SOCKET, SO_LINGER) => Err(SyscallError::InvalidArgument),
+        _ => Err(SyscallError::NotSupported),
+    })
+}
+
+/// netstack のソケットオプション操作のエラーを SyscallError に変換する
+pub fn sockopt_error(e: &str) -> SyscallError {
+    match e {
+        "unsupported option" => SyscallError::NotSupported,
+        "invalid option" => SyscallError::InvalidArgument,
+        "no such socket" => SyscallError::InvalidHandle,
+        _ => SyscallError::Other,
+    }
+}
+
+/// 接続ができた TCP に、接続前に設定されていたオプションを反映する
+fn apply_tcp_options(conn_id: u32, options: SocketOptions) {
+    use sabos_syscall::{SOL_SOCKET, SO_LINGER, SO_RCVTIMEO};
+
+    if options.nodelay {
+        let _ = crate::netstack::tcp_set_nodelay(conn_id, true);
+    }
+    if options.recv_timeout_ms != 0 {
+        let _ = crate::netstack::set_socket_option(conn_id, SOL_SOCKET, SO_RCVTIMEO, options.recv_timeout_ms);
+    }
+    if options.linger_abort {
+        let _ = crate::netstack::set_socket_option(conn_id, SOL_SOCKET, SO_LINGER, 1);
+    }
+}
+
+/// ハンドルが 1 つ閉じられたときに呼ぶ
+///
+/// 参照カウントが 0 になったら netstack 側の資源（TCP 接続 / 待ち受けポート /
+/// UDP ソケット）を解放してテーブルから消す。
+pub fn close(socket_id: usize) {
+    let state = {
+        let mut table = SOCKET_TABLE.lock();
+        let Some(Some(sock)) = table.get_mut(socket_id) else {
+            return;
+        };
+        sock.refs -= 1;
+        if sock.refs > 0 {
+            return;
+        }
+        let state = sock.state;
+        table[socket_id] = None;
+        state
+    };
+
+    match state {
+        SocketState::Connected(conn_id) => {
+            let _ = crate::netstack::tcp_close(conn_id);
+        }
+        SocketState::Listening(port) => crate::netstack::tcp_unlisten(port),
+        SocketState::Udp(udp_id) => {
+            let _ = crate::netstack::udp_close(udp_id);
+        }
+        SocketState::Unbound | SocketState::Bound(_) => {}
+    }
+}
+
+// =================================================================
+// 内部ヘルパー
+// =================================================================
+
+/// 空きスロットにソケットを入れて socket_id を返す
+fn insert(sock: Socket) -> usize {
+    let mut table = SOCKET_TABLE.lock();
+    if let Some(i) = table.iter().position(|slot| slot.is_none()) {
+        table[i] = Some(sock);
+        return i;
+    }
+    table.push(Some(sock));
+    table.len() - 1
+}
+
+/// ロックを取ってソケットに対する操作を行う
+fn with_socket<T>(
+    socket_id: usize,
+    f: impl FnOnce(&mut Socket) -> Result<T, SyscallError>,
+) -> Result<T, SyscallError> {
+    let mut table = SOCKET_TABLE.lock();
+    match table.get_mut(socket_id) {
+        Some(Some(sock)) => f(sock),
+        _ => Err(SyscallError::InvalidHandle),
+    }
+}
+
+/// pending に残っているデータを buf に移す。なければ None
+fn take_pending(sock: &mut Socket, buf: &mut [u8]) -> Option<usize> {
+    if sock.pending.is_empty() {
+        return None;
+    }
+    let n = sock.pending.len().min(buf.len());
+    buf[..n].copy_from_slice(&sock.pending[..n]);
+    sock.pending.drain(..n);
+    Some(n)
+}
+
+/// TCP 接続の相手アドレス
+fn tcp_peer(conn_id: u32) -> Option<(IpAddr, u16)> {
+    crate::netstack::tcp_peer_addr(conn_id)
+}
+
+/// UDP ソケットを netstack にバインドして Udp 状態にする
+fn bind_udp(socket_id: usize, port: u16) -> Result<u32, SyscallError> {
+    use sabos_syscall::{SOL_SOCKET, SO_RCVTIMEO};
+
+    let options = with_socket(socket_id, |sock| Ok(sock.options))?;
+    let udp_id = crate::netstack::udp_bind_reuse(port, options.reuse_addr).map_err(|_| SyscallError::Other)?;
+    if options.recv_timeout_ms != 0 {
+        let _ = crate::netstack::set_socket_option(udp_id, SOL_SOCKET, SO_RCVTIMEO, options.recv_timeout_ms);
+    }
+    let result = with_socket(socket_id, |sock| {
+        if sock.state != SocketState::Unbound {
+            return Err(SyscallError::InvalidArgument);
+        }
+        sock.state = SocketState::Udp(udp_id);
+        Ok(udp_id)
+    });
+    if result.is_err() {
+        // 別のスレッドが先にバインドした等。取ったポートは返しておく
+        let _ = crate::netstack::udp_close(udp_id);
+    }
+    result
+}
+
+/// UDP ソケットがバインド済みならその ID を、未バインドならエフェメラルポートにバインドして返す
+fn ensure_udp_bound(socket_id: usize) -> Result<u32, SyscallError> {
+    match with_socket(socket_id, |sock| Ok(sock.state))? {
+        SocketState::Udp(udp_id) => Ok(udp_id),
+        SocketState::Unbound => bind_udp(socket_id, 0),
+        _ => Err(SyscallError::InvalidArgument),
+    }
+}
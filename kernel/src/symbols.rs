@@ -0,0 +1,111 @@
+// symbols.rs — カーネル自身のシンボルテーブル
+//
+// バックトレースに並ぶ戻りアドレスだけでは、どの関数の中なのかを知るのに
+// ホストで PDB を引く手間がかかる。そこでビルド時にカーネルの関数名と RVA の表を作って
+// イメージに埋め込み（build.rs, scripts/gen-kernel-symbols.py）、カーネルの中で名前に直す。
+//
+// テーブルは任意。scripts/build-kernel.sh（make build）を通さずに cargo build しただけなら空で、
+// resolve() は常に None を返す。
+//
+// パニックハンドラからも呼ぶので、ヒープもロックも使わない。
+// テーブルは RVA の昇順に並んでいるので、二分探索で「addr 以下で最も近い関数」を探す。
+// PDB の公開シンボルには関数の大きさが無いので、次の関数の先頭までを前の関数の中とみなす。
+//
+// 形式（すべてリトルエンディアン）:
+//   ヘッダ   magic "KSYM", エントリ数 u32, .text の終わりの RVA u32
+//   エントリ RVA u32, 名前のオフセット u32, 名前の長さ u32
+//   名前     UTF-8 の文字列を並べたもの
+
+/// 埋め込まれたシンボルテーブル（空なら無し）
+static TABLE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/symbols.bin"));
+
+const MAGIC: &[u8; 4] = b"KSYM";
+const HEADER_SIZE: usize = 12;
+const ENTRY_SIZE: usize = 12;
+
+unsafe extern "C" {
+    /// リンカが用意する、ロードされたイメージの先頭（PE の ImageBase）
+    static __ImageBase: u8;
+}
+
+/// テーブルを読むためのビュー
+struct Table {
+    data: &'static [u8],
+    count: usize,
+    text_end: u64,
+}
+
+impl Table {
+    /// ヘッダを確かめて返す。テーブルが無いか壊れていれば None。
+    fn get() -> Option<Self> {
+        // ビルドの 1 回目（空のテーブル）と 2 回目でコードが変わらないよう、
+        // テーブルの長さを定数として畳み込ませない
+        let data: &'static [u8] = core::hint::black_box(TABLE);
+        if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+            return None;
+        }
+        let count = read_u32(data, 4)? as usize;
+        let text_end = read_u32(data, 8)? as u64;
+        if data.len() < HEADER_SIZE + count * ENTRY_SIZE {
+            return None;
+        }
+        Some(Self { data, count, text_end })
+    }
+
+    fn rva(&self, i: usize) -> u64 {
+        read_u32(self.data, HEADER_SIZE + i * ENTRY_SIZE).unwrap_or(0) as u64
+    }
+
+    fn name(&self, i: usize) -> &'static str {
+        let entry = HEADER_SIZE + i * ENTRY_SIZE;
+        let strings = HEADER_SIZE + self.count * ENTRY_SIZE;
+        let name = (|| {
+            let start = strings + read_u32(self.data, entry + 4)? as usize;
+            let len = read_u32(self.data, entry + 8)? as usize;
+            core::str::from_utf8(self.data.get(start..start + len)?).ok()
+        })();
+        name.unwrap_or("?")
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// カーネルイメージがロードされた先頭アドレス
+fn image_base() -> u64 {
+    (&raw const __ImageBase) as u64
+}
+
+/// シンボルテーブルが埋め込まれているか
+pub fn available() -> bool {
+    Table::get().is_some()
+}
+
+/// 埋め込まれている関数の数
+pub fn count() -> usize {
+    Table::get().map_or(0, |t| t.count)
+}
+
+/// addr を含む関数の名前と、関数の先頭からのオフセットを返す。
+///
+/// テーブルが無いとき、addr がカーネルの .text の外のときは None。
+pub fn resolve(addr: u64) -> Option<(&'static str, usize)> {
+    let table = Table::get()?;
+    let rva = addr.checked_sub(image_base())?;
+    if table.count == 0 || rva < table.rva(0) || rva >= table.text_end {
+        return None;
+    }
+    // rva(i) <= rva となる最後の i を探す
+    let (mut lo, mut hi) = (0, table.count);
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if table.rva(mid) <= rva {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some((table.name(lo), (rva - table.rva(lo)) as usize))
+}
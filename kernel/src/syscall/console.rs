@@ -5,10 +5,12 @@
 
 use alloc::string::String;
 use alloc::vec::Vec;
+use sabos_textutil::Utf8ChunkDecoder;
+use spin::Mutex;
 use crate::user_ptr::SyscallError;
 use x86_64::registers::control::Cr3;
 use super::{user_slice_from_args, user_ptr_from_arg};
-use super::process::parse_args_buffer;
+use super::process::{parse_args_buffer, resolve_dir_path};
 
 /// SYS_READ: コンソールから読み取り（フォーカス対応版）
 ///
@@ -143,15 +145,26 @@ pub(crate) fn sys_write(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
         };
     }
 
-    // UTF-8 として解釈してカーネルコンソールに出力
-    // as_str_lossy() は不正な UTF-8 を "<invalid utf-8>" に置換
-    let s = user_slice.as_str_lossy();
-    crate::kprint!("{}", s);
+    write_console_bytes(user_slice.as_slice());
 
     // 書き込んだバイト数を返す
     Ok(len as u64)
 }
 
+/// コンソールに出すバイト列の UTF-8 復号器。
+///
+/// 1 バイトずつ write するプログラムもあるので、write の区切りで多バイト文字が切れても
+/// 書きかけの断片を次の write まで持ち越し、1 文字（1 セル）として描く。
+/// 不正なバイト列は U+FFFD（画面では置換グリフ）になる。
+/// 全タスクで共有するため、複数のタスクが同時に文字の途中まで書くと混ざるが、
+/// その場合も U+FFFD になるだけで画面が崩れることはない。
+static CONSOLE_DECODER: Mutex<Utf8ChunkDecoder> = Mutex::new(Utf8ChunkDecoder::new());
+
+/// バイト列を UTF-8 として復号してカーネルコンソールに出力する
+pub(crate) fn write_console_bytes(bytes: &[u8]) {
+    CONSOLE_DECODER.lock().push(bytes, &mut |s| crate::kprint!("{}", s));
+}
+
 /// SYS_PIPE: パイプを作成する
 ///
 /// 引数:
@@ -192,6 +205,7 @@ pub(crate) fn sys_spawn_redirected(arg1: u64) -> Result<u64, SyscallError> {
     ///
     /// ユーザー空間で構築してポインタを渡す。
     /// handle_id が u64::MAX の場合はリダイレクトなし（コンソール直結）。
+    /// env_ptr が 0 なら環境変数を、cwd_len が 0 ならカレントディレクトリを親から継承する。
     #[repr(C)]
     #[derive(Clone, Copy)]
     struct SpawnRedirectArgs {
@@ -203,6 +217,10 @@ pub(crate) fn sys_spawn_redirected(arg1: u64) -> Result<u64, SyscallError> {
         stdin_handle_token: u64,
         stdout_handle_id: u64,    // u64::MAX = リダイレクトなし
         stdout_handle_token: u64,
+        env_ptr: u64,             // 0 = 継承。引数バッファと同じ形式で "KEY=VALUE" を並べた環境変数すべて
+        env_len: u64,
+        cwd_ptr: u64,
+        cwd_len: u64,             // 0 = 継承
     }
 
     // ユーザー空間から構造体を読み取り
@@ -220,6 +238,27 @@ pub(crate) fn sys_spawn_redirected(arg1: u64) -> Result<u64, SyscallError> {
     // 追加引数をパース
     let extra_args = parse_args_buffer(args.args_ptr, args.args_len)?;
 
+    // 環境変数の指定（std の Command::env / env_clear）。"=" のないエントリは不正
+    let env_vars = if args.env_ptr != 0 {
+        let mut vars = Vec::new();
+        for entry in parse_args_buffer(args.env_ptr, args.env_len)? {
+            let (key, value) = entry.split_once('=').ok_or(SyscallError::InvalidArgument)?;
+            vars.push((String::from(key), String::from(value)));
+        }
+        Some(vars)
+    } else {
+        None
+    };
+
+    // 作業ディレクトリの指定（std の Command::current_dir）
+    let cwd = if args.cwd_len != 0 {
+        let cwd_slice = user_slice_from_args(args.cwd_ptr, args.cwd_len)?;
+        let cwd = cwd_slice.as_str().map_err(|_| SyscallError::InvalidUtf8)?;
+        Some(resolve_dir_path(cwd)?)
+    } else {
+        None
+    };
+
     // VFS 経由でファイルを読み込む
     let elf_data = crate::vfs::read_file(path).map_err(crate::vfs::vfs_error_to_syscall)?;
 
@@ -275,7 +314,7 @@ pub(crate) fn sys_spawn_redirected(arg1: u64) -> Result<u64, SyscallError> {
         crate::paging::switch_to_kernel_page_table();
     }
     let task_id = match crate::scheduler::spawn_user_redirected(
-        &process_name, &elf_data, &args_vec, child_stdin, child_stdout,
+        &process_name, &elf_data, &args_vec, child_stdin, child_stdout, env_vars, cwd,
     ) {
         Ok(id) => id,
         Err(_) => {
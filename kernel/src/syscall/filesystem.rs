@@ -1,6 +1,6 @@
 // syscall/filesystem.rs — ファイルシステム関連システムコール
 //
-// SYS_FILE_DELETE/WRITE, SYS_DIR_CREATE/REMOVE/LIST,
+// SYS_FILE_DELETE/WRITE, SYS_DIR_CREATE/REMOVE/REMOVE_ALL/LIST,
 // SYS_FS_STAT, list_dir_to_buffer, open_path_to_handle
 
 use alloc::format;
@@ -99,6 +99,23 @@ pub(crate) fn sys_dir_remove(arg1: u64, arg2: u64) -> Result<u64, SyscallError>
     Ok(0)
 }
 
+/// SYS_DIR_REMOVE_ALL: ディレクトリを中身ごと削除（std::fs::remove_dir_all 用）
+///
+/// 引数:
+///   arg1 — パスのポインタ（ユーザー空間）
+///   arg2 — パスの長さ
+///
+/// 戻り値:
+///   削除したエントリの数（成功時）
+///   負の値（エラー時）
+pub(crate) fn sys_dir_remove_all(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let path_slice = user_slice_from_args(arg1, arg2)?;
+    let path = path_slice.as_str().map_err(|_| SyscallError::InvalidUtf8)?;
+
+    let removed = crate::vfs::remove_dir_all(path).map_err(crate::vfs::vfs_error_to_syscall)?;
+    Ok(removed as u64)
+}
+
 /// SYS_FS_STAT: ファイルシステム統計情報を取得
 ///
 /// JSON 形式でファイルシステムの使用状況をバッファに書き込む。
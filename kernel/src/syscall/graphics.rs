@@ -1,6 +1,8 @@
 // syscall/graphics.rs — グラフィックス関連システムコール
 //
-// SYS_GET_FB_INFO, SYS_MOUSE_READ, SYS_DRAW_PIXEL/RECT/LINE/BLIT/TEXT
+// SYS_GET_FB_INFO, SYS_MOUSE_READ, SYS_DRAW_PIXEL/RECT/LINE/BLIT/TEXT,
+// SYS_FB_SET_DOUBLE_BUFFER, SYS_FB_PRESENT, SYS_FB_SET_CLIP, SYS_SET_CURSOR,
+// SYS_FB_LIST_MODES, SYS_FB_SET_LOGICAL_SIZE
 
 use crate::user_ptr::{UserSlice, SyscallError};
 use super::user_slice_from_args;
@@ -159,15 +161,25 @@ pub(crate) fn sys_draw_line(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, Sysc
     }
 }
 
-/// SYS_DRAW_BLIT: 画像（RGBX）を描画する
+/// SYS_DRAW_BLIT: 画像（4 bytes/pixel）を描画する
 ///
 /// 引数:
-///   arg1 — x 座標
+///   arg1 — 下位 32bit = x 座標、上位 32bit = ピクセル並び（BLIT_FORMAT_*）| フラグ（BLIT_FLAG_*）
 ///   arg2 — y 座標
 ///   arg3 — width/height packed（上位 32bit = w, 下位 32bit = h）
 ///   arg4 — バッファポインタ（ユーザー空間）
 pub(crate) fn sys_draw_blit(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
-    let x = usize::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    let mode = arg1 >> 32;
+    if mode & !(0xFF | sabos_syscall::BLIT_FLAG_ALPHA) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let alpha = mode & sabos_syscall::BLIT_FLAG_ALPHA != 0;
+    let format = match mode & 0xFF {
+        sabos_syscall::BLIT_FORMAT_NATIVE => crate::framebuffer::BlitFormat::Native,
+        sabos_syscall::BLIT_FORMAT_RGBX => crate::framebuffer::BlitFormat::Rgbx,
+        _ => return Err(SyscallError::InvalidArgument),
+    };
+    let x = usize::try_from(arg1 & 0xFFFF_FFFF).map_err(|_| SyscallError::InvalidArgument)?;
     let y = usize::try_from(arg2).map_err(|_| SyscallError::InvalidArgument)?;
 
     let w = (arg3 >> 32) as u32;
@@ -180,7 +192,7 @@ pub(crate) fn sys_draw_blit(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Resul
     let buf_slice = UserSlice::<u8>::from_raw(arg4, byte_len)?;
     let buf = buf_slice.as_slice();
 
-    match crate::framebuffer::draw_blit_global(x, y, w, h, buf) {
+    match crate::framebuffer::draw_blit_global(x, y, w, h, buf, format, alpha) {
         Ok(()) => Ok(0),
         Err(crate::framebuffer::DrawError::NotInitialized) => Err(SyscallError::Other),
         Err(_) => Err(SyscallError::InvalidArgument),
@@ -192,14 +204,18 @@ pub(crate) fn sys_draw_blit(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Resul
 /// 引数:
 ///   arg1 — x/y packed（上位 32bit = x, 下位 32bit = y）
 ///   arg2 — fg/bg packed（上位 32bit = fg, 下位 32bit = bg）
+///          最上位 8bit（fg の使われていない上位バイト）は拡大率。0 は 1 倍として扱う
 ///   arg3 — 文字列ポインタ（ユーザー空間）
 ///   arg4 — 文字列長
+///
+/// 戻り値: 描いた文字列の幅（ピクセル、複数行なら最も長い行）
 pub(crate) fn sys_draw_text(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
     let x = (arg1 >> 32) as u32;
     let y = (arg1 & 0xFFFF_FFFF) as u32;
     let x = usize::try_from(x).map_err(|_| SyscallError::InvalidArgument)?;
     let y = usize::try_from(y).map_err(|_| SyscallError::InvalidArgument)?;
 
+    let scale = ((arg2 >> 56) as usize).max(1);
     let fg = (arg2 >> 32) as u32;
     let bg = (arg2 & 0xFFFF_FFFF) as u32;
     let fg = (
@@ -216,9 +232,147 @@ pub(crate) fn sys_draw_text(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Resul
     let text_slice = user_slice_from_args(arg3, arg4)?;
     let text = text_slice.as_str().map_err(|_| SyscallError::InvalidUtf8)?;
 
-    match crate::framebuffer::draw_text_global(x, y, fg, bg, text) {
+    match crate::framebuffer::draw_text_global(x, y, fg, bg, text, scale) {
+        Ok(width) => Ok(width as u64),
+        Err(crate::framebuffer::DrawError::NotInitialized) => Err(SyscallError::Other),
+        Err(_) => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// SYS_FB_SET_DOUBLE_BUFFER: ダブルバッファモードを切り替える
+///
+/// 引数:
+///   arg1 — 0 以外で有効、0 で無効（溜まっている描画を転送して直接モードに戻る）
+///
+/// 有効にしている間、SYS_DRAW_* はバックバッファに描くだけになり、
+/// SYS_FB_PRESENT を呼ぶまで画面に出ない。
+/// 他のタスクが有効にしている場合は PermissionDenied。
+/// 有効にしたタスクが終了すると自動的に直接モードに戻る。
+pub(crate) fn sys_fb_set_double_buffer(arg1: u64) -> Result<u64, SyscallError> {
+    let task_id = crate::scheduler::current_task_id();
+    match crate::framebuffer::set_double_buffering(task_id, arg1 != 0) {
+        Ok(()) => Ok(0),
+        Err(crate::framebuffer::DrawError::Busy) => Err(SyscallError::PermissionDenied),
+        Err(_) => Err(SyscallError::Other),
+    }
+}
+
+/// SYS_FB_PRESENT: バックバッファの変更を画面に一括転送する
+///
+/// 前回の present 以降に描いた領域の bounding box を 1 回で転送する。
+/// 直接モードでは描画ごとに転送済みなので何もしない。
+pub(crate) fn sys_fb_present() -> Result<u64, SyscallError> {
+    match crate::framebuffer::present() {
+        Ok(()) => Ok(0),
+        Err(_) => Err(SyscallError::Other),
+    }
+}
+
+/// SYS_FB_SET_CLIP: クリップ領域（シザー矩形）を設定する
+///
+/// 引数:
+///   arg1 — x 座標
+///   arg2 — y 座標
+///   arg3 — 幅
+///   arg4 — 高さ
+///
+/// 以降の描画システムコールは矩形の外に描かなくなる。
+/// w = h = 0 ならクリップ領域を解除する。
+pub(crate) fn sys_fb_set_clip(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    if arg3 == 0 && arg4 == 0 {
+        crate::framebuffer::clear_clip();
+        return Ok(0);
+    }
+    let x = usize::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    let y = usize::try_from(arg2).map_err(|_| SyscallError::InvalidArgument)?;
+    let w = usize::try_from(arg3).map_err(|_| SyscallError::InvalidArgument)?;
+    let h = usize::try_from(arg4).map_err(|_| SyscallError::InvalidArgument)?;
+    match crate::framebuffer::set_clip(x, y, w, h) {
         Ok(()) => Ok(0),
         Err(crate::framebuffer::DrawError::NotInitialized) => Err(SyscallError::Other),
         Err(_) => Err(SyscallError::InvalidArgument),
     }
 }
+
+/// SYS_SET_CURSOR: カーネルが描くマウスカーソルを設定する
+///
+/// 引数:
+///   arg1 — カーソル画像（R, G, B, A の 4 bytes/pixel、ユーザー空間）。0 なら既定の矢印
+///   arg2 — 画像の幅（1〜CURSOR_MAX_SIZE）
+///   arg3 — 画像の高さ（1〜CURSOR_MAX_SIZE）
+///   arg4 — ホットスポット packed（上位 32bit = x, 下位 32bit = y）
+///
+/// w = h = 0 ならカーソルを隠す。それ以外は画像を設定して表示する。
+/// カーソルは画面にだけ重ねて描くので、アプリの描画内容は壊さない。
+pub(crate) fn sys_set_cursor(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    if arg2 == 0 && arg3 == 0 {
+        crate::framebuffer::set_cursor_visible(false);
+        return Ok(0);
+    }
+
+    let w = usize::try_from(arg2).map_err(|_| SyscallError::InvalidArgument)?;
+    let h = usize::try_from(arg3).map_err(|_| SyscallError::InvalidArgument)?;
+    let hot_x = (arg4 >> 32) as usize;
+    let hot_y = (arg4 & 0xFFFF_FFFF) as usize;
+
+    let result = if arg1 == 0 {
+        crate::framebuffer::set_cursor_image(None, 0, 0, 0, 0)
+    } else {
+        // 巨大な w * h でオーバーフローしないよう、先に上限で弾く
+        let max = crate::framebuffer::CURSOR_MAX_SIZE;
+        if w > max || h > max {
+            return Err(SyscallError::InvalidArgument);
+        }
+        let image = user_slice_from_args(arg1, (w * h * 4) as u64)?;
+        crate::framebuffer::set_cursor_image(Some(image.as_slice()), w, h, hot_x, hot_y)
+    };
+    match result {
+        Ok(()) => {
+            crate::framebuffer::set_cursor_visible(true);
+            Ok(0)
+        }
+        Err(crate::framebuffer::DrawError::NotInitialized) => Err(SyscallError::Other),
+        Err(_) => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// SYS_FB_LIST_MODES: 起動時に GOP が対応していた画面モード（解像度）の一覧を取得する
+///
+/// 引数:
+///   arg1 — 書き込み先バッファ（FbMode の配列、ユーザー空間）
+///   arg2 — バッファ長（バイト）
+///
+/// 戻り値:
+///   書き込んだ FbMode の数（バッファに入る分まで）
+///
+/// ExitBootServices の後は GOP が使えないので、ここに並ぶモードへ切り替えることはできない。
+/// 低い解像度で描きたいときは SYS_FB_SET_LOGICAL_SIZE を使う。
+pub(crate) fn sys_fb_list_modes(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let count = usize::try_from(arg2).map_err(|_| SyscallError::InvalidArgument)?
+        / core::mem::size_of::<sabos_syscall::FbMode>();
+    let buf = UserSlice::<sabos_syscall::FbMode>::from_raw(arg1, count)?;
+    Ok(crate::framebuffer::list_modes(buf.as_mut_slice()) as u64)
+}
+
+/// SYS_FB_SET_LOGICAL_SIZE: 論理解像度を設定する
+///
+/// 引数:
+///   arg1 — 論理解像度の幅
+///   arg2 — 論理解像度の高さ
+///
+/// 戻り値:
+///   拡大率（論理 1 ピクセルが画面の何ピクセル四方になるか）
+///
+/// 以降の SYS_DRAW_* と SYS_FB_SET_CLIP の座標は論理座標になり、画面に収まる最大の
+/// 整数倍に拡大して画面中央に描く。SYS_GET_FB_INFO の幅・高さも論理解像度になる。
+/// マウスの座標は画面のピクセル座標のまま。w = h = 0 なら解除する。
+/// 設定したタスクが終了すると自動的に解除される。
+pub(crate) fn sys_fb_set_logical_size(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let w = usize::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    let h = usize::try_from(arg2).map_err(|_| SyscallError::InvalidArgument)?;
+    match crate::framebuffer::set_logical_size(w, h) {
+        Ok(scale) => Ok(scale as u64),
+        Err(crate::framebuffer::DrawError::NotInitialized) => Err(SyscallError::Other),
+        Err(_) => Err(SyscallError::InvalidArgument),
+    }
+}
@@ -372,6 +372,27 @@ pub(crate) fn sys_handle_unlink(arg1: u64, arg2: u64, arg3: u64) -> Result<u64,
     Ok(0)
 }
 
+/// SYS_FSYNC: ハンドルに書き込んだ内容をディスクに書き戻す
+///
+/// 引数:
+///   arg1 — Handle のポインタ（ユーザー空間）
+///
+/// 戻り値:
+///   0（成功時）
+///   負の値（エラー時）
+///
+/// ファイルは close を待たずに書き戻す。パイプ・ソケット・procfs など
+/// 書き戻すものがないハンドルでは何もせず 0 を返す。
+pub(crate) fn sys_fsync(arg1: u64) -> Result<u64, SyscallError> {
+    use crate::handle::Handle;
+
+    let handle_ptr = user_ptr_from_arg::<Handle>(arg1)?;
+    let handle = handle_ptr.read();
+
+    crate::handle::fsync(&handle)?;
+    Ok(0)
+}
+
 /// SYS_HANDLE_MKDIR: ディレクトリハンドル内にサブディレクトリを作成
 ///
 /// 引数:
@@ -1,8 +1,9 @@
 // syscall/ipc.rs — IPC・ブロックデバイス関連システムコール
 //
 // SYS_IPC_SEND/RECV/RECV_FROM/CANCEL/SEND_HANDLE/RECV_HANDLE,
-// SYS_BLOCK_READ/WRITE
+// SYS_TIMER_CREATE/CANCEL, SYS_BLOCK_READ/WRITE
 
+use crate::blockdev::BlockDevice;
 use crate::user_ptr::SyscallError;
 use super::{user_slice_from_args, user_ptr_from_arg};
 
@@ -12,7 +13,7 @@ use super::{user_slice_from_args, user_ptr_from_arg};
 ///   arg1 — セクタ番号
 ///   arg2 — バッファのポインタ（ユーザー空間）
 ///   arg3 — バッファの長さ（512 バイト固定）
-///   arg4 — デバイスインデックス（0 = disk.img, 1 = hostfs.img, ...）
+///   arg4 — blockdev レジストリの通し番号（0 = disk.img, 1 = hostfs.img, ...）
 ///
 /// 戻り値:
 ///   読み取ったバイト数（成功時）
@@ -27,8 +28,7 @@ pub(crate) fn sys_block_read(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Resu
     let buf_slice = user_slice_from_args(arg2, arg3)?;
     let buf = buf_slice.as_mut_slice();
 
-    let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
-    let drv = devs.get_mut(dev_index).ok_or(SyscallError::Other)?;
+    let mut drv = crate::blockdev::open(dev_index).map_err(|_| SyscallError::Other)?;
     // ユーザー空間のバッファは物理アドレスではないため、
     // DMA 先に直接渡すと壊れる。カーネルバッファに読み取ってから
     // ユーザー空間にコピーする。
@@ -44,7 +44,7 @@ pub(crate) fn sys_block_read(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Resu
 ///   arg1 — セクタ番号
 ///   arg2 — バッファのポインタ（ユーザー空間）
 ///   arg3 — バッファの長さ（512 バイト固定）
-///   arg4 — デバイスインデックス（0 = disk.img, 1 = hostfs.img, ...）
+///   arg4 — blockdev レジストリの通し番号（0 = disk.img, 1 = hostfs.img, ...）
 ///
 /// 戻り値:
 ///   書き込んだバイト数（成功時）
@@ -59,12 +59,14 @@ pub(crate) fn sys_block_write(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Res
     let buf_slice = user_slice_from_args(arg2, arg3)?;
     let buf = buf_slice.as_slice();
 
-    let mut devs = crate::virtio_blk::VIRTIO_BLKS.lock();
-    let drv = devs.get_mut(dev_index).ok_or(SyscallError::Other)?;
+    let mut drv = crate::blockdev::open(dev_index).map_err(|_| SyscallError::Other)?;
     // DMA 先は物理アドレス前提なので、カーネルバッファにコピーしてから書き込む。
     let mut kernel_buf = [0u8; 512];
     kernel_buf.copy_from_slice(buf);
     drv.write_sector(arg1, &kernel_buf).map_err(|_| SyscallError::Other)?;
+    // ユーザー空間の FAT32 ドライバがディレクトリを書き換えたかもしれないので、
+    // VFS のパス検索キャッシュは信用できなくなる
+    crate::vfs::invalidate_dentry_cache();
     Ok(len as u64)
 }
 
@@ -83,7 +85,7 @@ pub(crate) fn sys_ipc_send(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, Sysca
     let buf = buf_slice.as_slice();
 
     let sender = crate::scheduler::current_task_id();
-    crate::ipc::send(sender, arg1, buf.to_vec())?;
+    crate::ipc::send(sender, arg1, buf)?;
     Ok(0)
 }
 
@@ -215,3 +217,35 @@ pub(crate) fn sys_ipc_recv_handle(arg1: u64, arg2: u64, arg3: u64, arg4: u64) ->
 
     Ok(copy_len as u64)
 }
+
+/// SYS_TIMER_CREATE: 期限が来たら IPC メッセージで知らせるタイマーを作る
+///
+/// 引数:
+///   arg1 — 期限までの時間 (ms)。周期タイマーなら周期。タイマーティック（約 55ms）に切り上げる
+///   arg2 — 0 = 1 回だけ、1 = 周期
+///
+/// 戻り値:
+///   タイマー ID（成功時）
+///   InvalidArgument（arg1 が 0、arg2 が 0/1 以外）
+///   Other（1 タスクあたりの上限 MAX_TIMERS_PER_TASK に達している）
+pub(crate) fn sys_timer_create(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let periodic = match arg2 {
+        0 => false,
+        1 => true,
+        _ => return Err(SyscallError::InvalidArgument),
+    };
+    crate::timer::create(crate::scheduler::current_task_id(), arg1, periodic)
+}
+
+/// SYS_TIMER_CANCEL: タイマーを止める
+///
+/// 引数:
+///   arg1 — SYS_TIMER_CREATE が返したタイマー ID
+///
+/// 戻り値:
+///   0（成功時）
+///   InvalidArgument（自分のタイマーでない、もう止まっている）
+pub(crate) fn sys_timer_cancel(arg1: u64) -> Result<u64, SyscallError> {
+    crate::timer::cancel(crate::scheduler::current_task_id(), arg1)?;
+    Ok(0)
+}
@@ -1,7 +1,8 @@
 // syscall/misc.rs — その他のシステムコール
 //
 // SYS_SELFTEST, SYS_HALT, SYS_MMAP/MUNMAP, SYS_GETRANDOM,
-// SYS_SOUND_PLAY, SYS_THREAD_CREATE/EXIT/JOIN, SYS_FUTEX
+// SYS_SOUND_PLAY/PLAY_PCM, SYS_THREAD_CREATE/EXIT/JOIN, SYS_THREAD_SET_TLS/GET_TLS, SYS_FUTEX,
+// SYS_CLIPBOARD_SET/GET
 
 use crate::user_ptr::SyscallError;
 use super::user_slice_from_args;
@@ -42,10 +43,11 @@ pub(crate) fn sys_halt() -> Result<u64, SyscallError> {
 // SYS_GETRANDOM: ランダムバイト生成
 // =================================================================
 
-/// SYS_GETRANDOM: RDRAND 命令でランダムバイトを生成
+/// SYS_GETRANDOM: カーネルのエントロピープールからランダムバイトを生成
 ///
-/// x86_64 の RDRAND 命令を使って暗号学的に安全なランダムバイトを生成する。
-/// RDRAND はハードウェア乱数生成器 (DRNG) を使うため、ソフトウェア PRNG より安全。
+/// ChaCha20 ベースのプール（crate::random）から取り出す。
+/// RDRAND が使える CPU ではそれも混ぜるが、使えなくても失敗せず、
+/// 予測できる値（全ゼロなど）を返すこともない。
 ///
 /// 引数:
 ///   arg1 — バッファのポインタ（ユーザー空間）
@@ -55,58 +57,20 @@ pub(crate) fn sys_halt() -> Result<u64, SyscallError> {
 pub(crate) fn sys_getrandom(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
     let buf_slice = user_slice_from_args(arg1, arg2)?;
     let buf = buf_slice.as_mut_slice();
-    let len = buf.len();
-
-    // 8 バイトずつ RDRAND で生成し、バッファに書き込む
-    let mut offset = 0;
-    while offset < len {
-        let random_value: u64 = rdrand64()?;
-        let bytes = random_value.to_le_bytes();
-        let remaining = len - offset;
-        let to_copy = remaining.min(8);
-        buf[offset..offset + to_copy].copy_from_slice(&bytes[..to_copy]);
-        offset += to_copy;
-    }
-
-    Ok(len as u64)
-}
-
-/// RDRAND 命令で 64 ビットのランダム値を取得する。
-///
-/// RDRAND が失敗する場合（エントロピー枯渇など）は最大 10 回リトライする。
-/// それでも失敗した場合はエラーを返す。
-fn rdrand64() -> Result<u64, SyscallError> {
-    for _ in 0..10 {
-        let mut value: u64;
-        let success: u8;
-        unsafe {
-            core::arch::asm!(
-                "rdrand {val}",
-                "setc {ok}",
-                val = out(reg) value,
-                ok = out(reg_byte) success,
-            );
-        }
-        if success != 0 {
-            return Ok(value);
-        }
-    }
-    // RDRAND が 10 回連続で失敗した場合（通常は起こらない）
-    Err(SyscallError::NotSupported)
+    crate::random::fill_bytes(buf);
+    Ok(buf.len() as u64)
 }
 
-// SYS_MMAP / SYS_MUNMAP: 匿名ページの動的マッピング/解除
+// SYS_MMAP / SYS_MUNMAP: ページの動的マッピング/解除
 //
 // ユーザー空間から動的にメモリを確保するためのシステムコール。
-// POSIX の mmap(MAP_ANONYMOUS) に相当するが、ファイルマッピングは未対応。
-// std の GlobalAlloc や、ユーザー空間のヒープ拡張に使う。
+// POSIX の mmap(MAP_ANONYMOUS) と、MAP_PRIVATE なファイルマッピングに相当する。
+// std の GlobalAlloc や、ユーザー空間のヒープ拡張、ファイルのメモリ読み込みに使う。
 
-/// MMAP_PROT_READ: 読み取り可能
-const MMAP_PROT_READ: u64 = 0x1;
-/// MMAP_PROT_WRITE: 書き込み可能
-const MMAP_PROT_WRITE: u64 = 0x2;
-/// MMAP_FLAG_ANONYMOUS: 匿名マッピング（ファイルに紐付かない）
-const MMAP_FLAG_ANONYMOUS: u64 = 0x1;
+use sabos_syscall::{
+    MMAP_FLAG_ANONYMOUS, MMAP_FLAG_FILE, MMAP_FLAG_FIXED, MMAP_PROT_EXEC, MMAP_PROT_READ,
+    MMAP_PROT_WRITE,
+};
 
 /// mmap 用の仮想アドレスの下限。
 /// ELF の LOAD セグメント、ユーザースタック (0x2000000)、
@@ -121,21 +85,25 @@ const MMAP_VADDR_BASE: u64 = 0x100_0000_0000; // 1 TiB
 /// mmap 領域の上限。
 const MMAP_VADDR_LIMIT: u64 = 0x200_0000_0000; // 2 TiB
 
-/// SYS_MMAP: ユーザー空間に匿名ページをマッピングする。
+/// SYS_MMAP: ユーザー空間にページをマッピングする。
 ///
 /// VMA リストで空き仮想アドレス領域を管理する。
 /// 旧実装ではページテーブルを O(n) 走査していたが、
 /// VMA ベースでは VMA 数（数十程度）に対する O(n) で済む。
 ///
 /// 引数:
-/// - arg1 (addr_hint): マッピング先仮想アドレスのヒント（0 ならカーネルが決定）
+/// - arg1 (addr_hint): マッピング先仮想アドレスのヒント（0 ならカーネルが決定）。
+///   MMAP_FLAG_FILE のときは MmapFileArgs 構造体のポインタ
 /// - arg2 (len): マッピングサイズ（バイト、4KiB にアラインされる）
 /// - arg3 (prot): プロテクションフラグ（PROT_READ | PROT_WRITE）
-/// - arg4 (flags): マッピングフラグ（MAP_ANONYMOUS のみ対応）
+/// - arg4 (flags): マッピングフラグ（MAP_ANONYMOUS / MAP_FILE のどちらか + MAP_FIXED）
+///
+/// addr_hint の扱い:
+/// - MAP_FIXED なし: ヒントの範囲が空いていればそこに、埋まっていれば別の空き領域に置く
+/// - MAP_FIXED あり: ヒントは 4KiB アライン必須。範囲が埋まっていれば InvalidAddress
 ///
 /// 戻り値: マッピングされた仮想アドレス
 pub(crate) fn sys_mmap(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
-    let addr_hint = arg1;
     let len = arg2;
     let prot = arg3;
     let flags = arg4;
@@ -145,10 +113,15 @@ pub(crate) fn sys_mmap(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64
         return Err(SyscallError::InvalidArgument);
     }
 
-    // 現状は匿名マッピングのみ対応
-    if (flags & MMAP_FLAG_ANONYMOUS) == 0 {
+    // 匿名かファイルのどちらか一方だけを指定する
+    let is_file = (flags & MMAP_FLAG_FILE) != 0;
+    if is_file == ((flags & MMAP_FLAG_ANONYMOUS) != 0) {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if (flags & !(MMAP_FLAG_ANONYMOUS | MMAP_FLAG_FILE | MMAP_FLAG_FIXED)) != 0 {
         return Err(SyscallError::NotSupported);
     }
+    let fixed = (flags & MMAP_FLAG_FIXED) != 0;
 
     // prot の検証（最低限 READ は必要）
     if (prot & MMAP_PROT_READ) == 0 {
@@ -161,37 +134,80 @@ pub(crate) fn sys_mmap(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64
     let num_pages = ((len + 4095) / 4096) as usize;
     let aligned_size = num_pages as u64 * 4096;
 
+    // ファイルマッピングなら引数構造体を読み、マッピングする内容を先に取り出しておく
+    // （ページを確保してから失敗すると後始末が面倒なので）
+    let (addr_hint, file) = if is_file {
+        let args = super::user_ptr_from_arg::<sabos_syscall::MmapFileArgs>(arg1)?.read();
+        if (args.offset & 0xFFF) != 0 {
+            return Err(SyscallError::MisalignedPointer);
+        }
+        let handle = crate::handle::Handle { id: args.handle_id, token: args.handle_token };
+        let data = crate::handle::read_range(&handle, args.offset as usize, aligned_size as usize)?;
+        let path = crate::handle::get_path(&handle)?;
+        (args.addr_hint, Some((data, path)))
+    } else {
+        (arg1, None)
+    };
+
     // 現在のプロセスの L4 ページテーブルフレームを取得
     let l4_frame = crate::scheduler::current_task_page_table_frame()
         .ok_or(SyscallError::NotSupported)?; // カーネルタスクでは mmap 不可
 
     // マッピング先の仮想アドレスを決定する
-    let virt_addr = if addr_hint != 0 {
-        // ユーザーが指定したアドレスを使う（4KiB アラインに切り上げ）
-        let aligned = (addr_hint + 4095) & !4095;
-        if aligned < MMAP_VADDR_BASE || aligned + aligned_size > MMAP_VADDR_LIMIT {
+    let virt_addr = if fixed {
+        // ヒントどおりの場所でなければ意味がないので、アラインの切り上げもしない
+        if (addr_hint & 0xFFF) != 0 {
+            return Err(SyscallError::MisalignedPointer);
+        }
+        if addr_hint < MMAP_VADDR_BASE || addr_hint.saturating_add(aligned_size) > MMAP_VADDR_LIMIT {
+            return Err(SyscallError::InvalidAddress);
+        }
+        if !crate::scheduler::vma_range_is_free_in_current(addr_hint, addr_hint + aligned_size) {
             return Err(SyscallError::InvalidAddress);
         }
-        aligned
+        addr_hint
     } else {
-        // VMA リストで空き領域を探す（旧実装: ページテーブル走査 → 新実装: VMA gap 走査）
-        crate::scheduler::find_free_vma_region(aligned_size, MMAP_VADDR_BASE, MMAP_VADDR_LIMIT)
-            .ok_or(SyscallError::Other)?
+        // ヒントの範囲が使えればそこを使う（4KiB アラインに切り上げ）
+        let aligned = addr_hint.saturating_add(4095) & !4095;
+        let hint_usable = addr_hint != 0
+            && aligned >= MMAP_VADDR_BASE
+            && aligned.saturating_add(aligned_size) <= MMAP_VADDR_LIMIT
+            && crate::scheduler::vma_range_is_free_in_current(aligned, aligned + aligned_size);
+        if hint_usable {
+            aligned
+        } else {
+            // VMA リストで空き領域を探す（旧実装: ページテーブル走査 → 新実装: VMA gap 走査）
+            crate::scheduler::find_free_vma_region(aligned_size, MMAP_VADDR_BASE, MMAP_VADDR_LIMIT)
+                .ok_or(SyscallError::Other)?
+        }
     };
 
     // ページをマッピング
-    let allocated = crate::paging::map_anonymous_pages_in_process(
-        l4_frame,
-        x86_64::VirtAddr::new(virt_addr),
-        num_pages,
-        writable,
-    );
+    let allocated = match &file {
+        Some((data, _)) => crate::paging::map_file_pages_in_process(
+            l4_frame,
+            x86_64::VirtAddr::new(virt_addr),
+            num_pages,
+            writable,
+            data,
+        ),
+        None => crate::paging::map_anonymous_pages_in_process(
+            l4_frame,
+            x86_64::VirtAddr::new(virt_addr),
+            num_pages,
+            writable,
+        ),
+    };
 
     // 確保したフレームをプロセスの allocated_frames に追加
     // （プロセス終了時に自動で解放される）
     crate::scheduler::add_mmap_frames_to_current(&allocated);
 
     // VMA を登録（空き領域管理と /proc/maps 表示用）
+    let (kind, name) = match file {
+        Some((_, path)) => (crate::vma::VmaKind::File, path),
+        None => (crate::vma::VmaKind::Anonymous, alloc::string::String::from("[anon]")),
+    };
     let _ = crate::scheduler::add_vma_to_current(crate::vma::Vma {
         start: virt_addr,
         end: virt_addr + aligned_size,
@@ -200,8 +216,8 @@ pub(crate) fn sys_mmap(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64
             write: writable,
             execute: false,
         },
-        kind: crate::vma::VmaKind::Anonymous,
-        name: alloc::string::String::from("[anon]"),
+        kind,
+        name,
     });
 
     Ok(virt_addr)
@@ -256,6 +272,72 @@ pub(crate) fn sys_munmap(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
     Ok(0)
 }
 
+/// SYS_MPROTECT: マッピング済みページの保護属性を変更する。
+///
+/// JIT（書いてから実行可能にする）やガードページ（アクセス不可にする）のためのもの。
+/// 範囲は隙間なく VMA で覆われていて、すべてのページがマッピング済みである必要がある。
+/// 書き込みと実行は同時に許可しない（W^X）。
+///
+/// 引数:
+/// - arg1 (addr): 先頭アドレス（4KiB アライン必須）
+/// - arg2 (len): バイト数（4KiB に切り上げ）
+/// - arg3 (prot): PROT_READ | PROT_WRITE | PROT_EXEC の組み合わせ（0 = PROT_NONE）
+///
+/// 戻り値: 0（成功）
+pub(crate) fn sys_mprotect(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, SyscallError> {
+    let addr = arg1;
+    let len = arg2;
+    let prot = arg3;
+
+    if (addr & 0xFFF) != 0 {
+        return Err(SyscallError::MisalignedPointer);
+    }
+    if len == 0 || (prot & !(MMAP_PROT_READ | MMAP_PROT_WRITE | MMAP_PROT_EXEC)) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    if (prot & MMAP_PROT_WRITE) != 0 && (prot & MMAP_PROT_EXEC) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let num_pages = len.div_ceil(4096);
+    let end = num_pages
+        .checked_mul(4096)
+        .and_then(|size| addr.checked_add(size))
+        .ok_or(SyscallError::InvalidAddress)?;
+    if end > 0x0000_8000_0000_0000 {
+        return Err(SyscallError::InvalidAddress);
+    }
+
+    let l4_frame = crate::scheduler::current_task_page_table_frame()
+        .ok_or(SyscallError::NotSupported)?;
+
+    let vma_prot = crate::vma::VmaProt {
+        read: (prot & MMAP_PROT_READ) != 0,
+        write: (prot & MMAP_PROT_WRITE) != 0,
+        execute: (prot & MMAP_PROT_EXEC) != 0,
+    };
+
+    // 先に VMA で「このプロセスの領域か」を確認してから、ページテーブルを書き換える。
+    // ページテーブル側も全ページがマッピング済みでなければ何も変えないので、
+    // 失敗したときに VMA とページテーブルの権限が食い違うことはない。
+    if !crate::scheduler::vma_range_is_covered_in_current(addr, end) {
+        return Err(SyscallError::InvalidAddress);
+    }
+    if !crate::paging::protect_pages_in_process(
+        l4_frame,
+        x86_64::VirtAddr::new(addr),
+        num_pages as usize,
+        vma_prot.read,
+        vma_prot.write,
+        vma_prot.execute,
+    ) {
+        return Err(SyscallError::InvalidAddress);
+    }
+    crate::scheduler::protect_vma_range_in_current(addr, end, vma_prot);
+
+    Ok(0)
+}
+
 // =================================================================
 // サウンド関連
 // =================================================================
@@ -292,6 +374,53 @@ pub(crate) fn sys_sound_play(arg1: u64, arg2: u64) -> Result<u64, SyscallError>
     }
 }
 
+/// SYS_SOUND_PLAY_PCM: ユーザーバッファの 16bit stereo PCM を AC97 で再生する。
+///
+/// # 引数
+/// - arg1 (buf_ptr): PCM データ（16bit signed LE, L/R 交互）のポインタ
+/// - arg2 (len): バイト数。4 の倍数（フレーム境界）であること。
+/// - arg3 (sample_rate): サンプルレート (Hz)。8000〜48000 の範囲。
+/// - arg4 (flags): SOUND_PCM_NONBLOCK を立てると再生完了を待たない
+///
+/// # 戻り値
+/// - キューに入れたバイト数。ブロッキング時は常に len（全て再生し終えてから戻る）。
+///   ノンブロッキング時は BDL リングに空きがあった分だけで、0 のこともある。
+/// - エラー: InvalidArgument (範囲外), NotSupported (AC97 未検出),
+///   WouldBlock (ノンブロッキングで、別レートの再生がまだ残っている)
+///
+/// 1 つの BD に収まらない長さのデータは、DMA が BD を消費するたびに
+/// 空いたエントリへ詰め直して最後まで流す。
+pub(crate) fn sys_sound_play_pcm(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    use crate::ac97::PcmError;
+    use sabos_syscall::SOUND_PCM_NONBLOCK;
+
+    let rate = u32::try_from(arg3).map_err(|_| SyscallError::InvalidArgument)?;
+    if !arg2.is_multiple_of(4) {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let nonblock = arg4 & SOUND_PCM_NONBLOCK != 0;
+    if arg2 == 0 {
+        return Ok(0);
+    }
+    let user_buf = user_slice_from_args(arg1, arg2)?;
+    let data = user_buf.as_slice();
+
+    let to_syscall_error = |e: PcmError| match e {
+        PcmError::NotAvailable => SyscallError::NotSupported,
+        PcmError::UnsupportedRate => SyscallError::InvalidArgument,
+        PcmError::Busy => SyscallError::WouldBlock,
+    };
+
+    if nonblock {
+        let written = crate::ac97::try_queue_pcm(data, rate).map_err(to_syscall_error)?;
+        return Ok(written as u64);
+    }
+
+    crate::ac97::queue_pcm_all(data, rate).map_err(to_syscall_error)?;
+    crate::ac97::wait_pcm_drain();
+    Ok(data.len() as u64)
+}
+
 /// SYS_THREAD_CREATE: 同一プロセス内で新しいスレッドを作成する
 ///
 /// 引数:
@@ -347,6 +476,26 @@ pub(crate) fn sys_thread_join(arg1: u64, arg2: u64) -> Result<u64, SyscallError>
     }
 }
 
+/// SYS_THREAD_SET_TLS: 現在のスレッドの TLS ポインタを設定する
+///
+/// 引数:
+///   arg1 — TLS ポインタ（カーネルは中身を解釈しない。0 で未設定に戻す）
+///
+/// 戻り値:
+///   0（成功時）
+pub(crate) fn sys_thread_set_tls(arg1: u64) -> Result<u64, SyscallError> {
+    crate::scheduler::set_current_tls_pointer(arg1);
+    Ok(0)
+}
+
+/// SYS_THREAD_GET_TLS: 現在のスレッドの TLS ポインタを返す
+///
+/// 戻り値:
+///   SYS_THREAD_SET_TLS で設定した値（未設定なら 0）
+pub(crate) fn sys_thread_get_tls() -> Result<u64, SyscallError> {
+    Ok(crate::scheduler::current_tls_pointer())
+}
+
 /// SYS_FUTEX: Futex 操作（ユーザー空間同期プリミティブの基盤）
 ///
 /// 引数:
@@ -374,3 +523,38 @@ pub(crate) fn sys_futex(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u6
         _ => Err(SyscallError::InvalidArgument),
     }
 }
+
+// =================================================================
+// クリップボード関連
+// =================================================================
+
+/// SYS_CLIPBOARD_SET: クリップボードの中身を置き換える
+///
+/// 引数:
+///   arg1 — データのポインタ（ユーザー空間）
+///   arg2 — データの長さ（CLIPBOARD_MAX まで。0 なら空にする）
+///
+/// 戻り値:
+///   0（成功時）
+///   InvalidArgument（CLIPBOARD_MAX を超える。中身は変わらない）
+pub(crate) fn sys_clipboard_set(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let data = user_slice_from_args(arg1, arg2)?;
+    crate::clipboard::set(data.as_slice()).map_err(|_| SyscallError::InvalidArgument)?;
+    Ok(0)
+}
+
+/// SYS_CLIPBOARD_GET: クリップボードの中身を読む
+///
+/// 引数:
+///   arg1 — バッファのポインタ（ユーザー空間）
+///   arg2 — バッファの長さ
+///
+/// 戻り値:
+///   中身のバイト数（成功時）
+///   BufferOverflow（バッファが足りない。何もコピーしない）
+pub(crate) fn sys_clipboard_get(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let buf = user_slice_from_args(arg1, arg2)?;
+    crate::clipboard::get(buf.as_mut_slice())
+        .map(|len| len as u64)
+        .map_err(|_| SyscallError::BufferOverflow)
+}
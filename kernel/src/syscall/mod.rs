@@ -50,10 +50,12 @@ use crate::user_ptr::{UserPtr, UserSlice, SyscallError};
 pub use sabos_syscall::*;
 
 // 外部から参照される公開 API を re-export
-pub use process::{exec_for_test, exec_spawn_for_test, exec_with_args_for_test};
+pub use process::{exec_for_test, exec_spawn_for_test, exec_spawn_with_args_for_test, exec_spawn_with_env_for_test, exec_with_args_for_test, wait_for_test};
 pub use filesystem::list_dir_to_buffer_for_test;
 pub(crate) use handle::open_path_to_handle;
 pub(crate) use ipc::sys_block_read;
+pub(crate) use console::write_console_bytes;
+pub(crate) use network::sendfile;
 
 // =================================================================
 // アセンブリエントリポイント
@@ -66,12 +68,21 @@ pub(crate) use ipc::sys_block_read;
 //
 // ハンドラ側では汎用レジスタを保存し、Rust 関数を呼び、
 // レジスタを復帰して iretq でユーザーモードに戻る。
+//
+// Ring 3 から来たときは入口と出口で swapgs し、カーネルにいる間だけ
+// GS ベースを CPU ごとのブロックに向ける（percpu.rs 参照）。
+// デモ用のカーネル内 int 0x80 もあるので、保存された CS の RPL で判定する。
 
 global_asm!(
     ".global syscall_handler_asm",
     "syscall_handler_asm:",
     // 割り込みを無効化（カーネル内の再入を防ぐ）
     "cli",
+    // Ring 3 からなら GS ベースをカーネルのものに切り替える（[rsp + 8] が CS）
+    "test qword ptr [rsp + 8], 3",
+    "jz 2f",
+    "swapgs",
+    "2:",
 
     // --- 汎用レジスタの保存 ---
     // int 0x80 で CPU が自動保存するのは SS/RSP/RFLAGS/CS/RIP のみ。
@@ -140,6 +151,12 @@ global_asm!(
     "pop r10",
     "pop r11",
 
+    // Ring 3 に戻るなら GS ベースをユーザーのものに戻す
+    "test qword ptr [rsp + 8], 3",
+    "jz 3f",
+    "swapgs",
+    "3:",
+
     // --- iretq でユーザーモードに復帰 ---
     // CPU が自動的に push した SS/RSP/RFLAGS/CS/RIP を pop して
     // Ring 3 の実行を再開する。
@@ -216,7 +233,11 @@ impl<'a> core::fmt::Write for SliceWriter<'a> {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         let bytes = s.as_bytes();
         let remaining = self.buf.len() - self.pos;
-        let to_write = core::cmp::min(bytes.len(), remaining);
+        let mut to_write = core::cmp::min(bytes.len(), remaining);
+        // 多バイト文字の途中で切らない（読んだ側で壊れた文字にならないように）
+        while !s.is_char_boundary(to_write) {
+            to_write -= 1;
+        }
         self.buf[self.pos..self.pos + to_write].copy_from_slice(&bytes[..to_write]);
         self.pos += to_write;
         Ok(())
@@ -277,17 +298,21 @@ fn dispatch_inner(nr: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result
         SYS_DIR_REMOVE => filesystem::sys_dir_remove(arg1, arg2),
         SYS_FS_STAT => filesystem::sys_fs_stat(arg1, arg2),
         // SYS_FS_REGISTER(18) は削除済み（モノリシック化により不要）
+        SYS_DIR_REMOVE_ALL => filesystem::sys_dir_remove_all(arg1, arg2),
         // システム情報
         SYS_GET_MEM_INFO => sysinfo::sys_get_mem_info(arg1, arg2),
         SYS_GET_TASK_LIST => sysinfo::sys_get_task_list(arg1, arg2),
         SYS_GET_NET_INFO => sysinfo::sys_get_net_info(arg1, arg2),
         SYS_PCI_CONFIG_READ => sysinfo::sys_pci_config_read(arg1, arg2, arg3, arg4),
+        SYS_PCI_READ_BAR => sysinfo::sys_pci_read_bar(arg1, arg2, arg3),
         SYS_GET_FB_INFO => graphics::sys_get_fb_info(arg1, arg2),
         SYS_MOUSE_READ => graphics::sys_mouse_read(arg1, arg2),
         SYS_CLOCK_MONOTONIC => sysinfo::sys_clock_monotonic(),
         SYS_GETRANDOM => misc::sys_getrandom(arg1, arg2),
         SYS_MMAP => misc::sys_mmap(arg1, arg2, arg3, arg4),
         SYS_MUNMAP => misc::sys_munmap(arg1, arg2),
+        SYS_MPROTECT => misc::sys_mprotect(arg1, arg2, arg3),
+        SYS_GET_TASK_INFO => sysinfo::sys_get_task_info(arg1, arg2),
         // プロセス管理
         SYS_EXEC => process::sys_exec(arg1, arg2, arg3, arg4),
         SYS_SPAWN => process::sys_spawn(arg1, arg2, arg3, arg4),
@@ -296,10 +321,17 @@ fn dispatch_inner(nr: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result
         SYS_WAIT => process::sys_wait(arg1, arg2),
         SYS_WAITPID => process::sys_waitpid(arg1, arg2, arg3),
         SYS_GETPID => process::sys_getpid(),
-        SYS_KILL => process::sys_kill(arg1),
+        SYS_KILL => process::sys_kill(arg1, arg2),
+        SYS_SIGPENDING => process::sys_sigpending(),
+        // スケジューラ
+        SYS_SCHED_STATS => sysinfo::sys_sched_stats(arg1, arg2),
+        SYS_SETPRIORITY => process::sys_setpriority(arg1, arg2),
+        SYS_GETPRIORITY => process::sys_getpriority(arg1),
         SYS_GETENV => process::sys_getenv(arg1, arg2, arg3, arg4),
         SYS_SETENV => process::sys_setenv(arg1, arg2, arg3, arg4),
         SYS_LISTENV => process::sys_listenv(arg1, arg2),
+        SYS_GETCWD => process::sys_getcwd(arg1, arg2),
+        SYS_CHDIR => process::sys_chdir(arg1, arg2),
         // ネットワーク（カーネル内ネットワークスタック）
         SYS_NET_DNS_LOOKUP => network::sys_net_dns_lookup(arg1, arg2, arg3),
         SYS_NET_TCP_CONNECT => network::sys_net_tcp_connect(arg1, arg2),
@@ -309,13 +341,32 @@ fn dispatch_inner(nr: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result
         SYS_NET_SEND_FRAME => network::sys_net_send_frame(arg1, arg2),
         SYS_NET_RECV_FRAME => network::sys_net_recv_frame(arg1, arg2, arg3),
         SYS_NET_GET_MAC => network::sys_net_get_mac(arg1, arg2),
-        SYS_NET_TCP_LISTEN => network::sys_net_tcp_listen(arg1),
-        SYS_NET_TCP_ACCEPT => network::sys_net_tcp_accept(arg1, arg2),
+        SYS_NET_SET_CONFIG => network::sys_net_set_config(arg1),
+        SYS_NET_CAPTURE_OPEN => network::sys_net_capture_open(arg1, arg2),
+        SYS_NET_TCP_LISTEN => network::sys_net_tcp_listen(arg1, arg2),
+        SYS_NET_TCP_ACCEPT => network::sys_net_tcp_accept(arg1, arg2, arg3),
         SYS_NET_UDP_BIND => network::sys_net_udp_bind(arg1),
         SYS_NET_UDP_SEND_TO => network::sys_net_udp_send_to(arg1),
         SYS_NET_UDP_RECV_FROM => network::sys_net_udp_recv_from(arg1),
         SYS_NET_UDP_CLOSE => network::sys_net_udp_close(arg1),
         SYS_NET_PING6 => network::sys_net_ping6(arg1, arg2, arg3),
+        SYS_NET_UDP_SEND_TO6 => network::sys_net_udp_send_to6(arg1),
+        SYS_NET_UDP_RECV_FROM6 => network::sys_net_udp_recv_from6(arg1),
+        SYS_NET_TCP_SHUTDOWN => network::sys_net_tcp_shutdown(arg1, arg2),
+        SYS_SOCKET => network::sys_socket(arg1, arg2, arg3, arg4),
+        SYS_BIND => network::sys_bind(arg1, arg2),
+        SYS_CONNECT => network::sys_connect(arg1, arg2),
+        SYS_LISTEN => network::sys_listen(arg1, arg2),
+        SYS_ACCEPT => network::sys_accept(arg1, arg2, arg3, arg4),
+        SYS_SEND => network::sys_send(arg1, arg2, arg3, arg4),
+        SYS_RECV => network::sys_recv(arg1, arg2, arg3, arg4),
+        SYS_RECV_FROM => network::sys_recv_from(arg1, arg2, arg3, arg4),
+        SYS_SETSOCKOPT => network::sys_setsockopt(arg1, arg2, arg3, arg4),
+        SYS_GETSOCKOPT => network::sys_getsockopt(arg1, arg2, arg3, arg4),
+        SYS_NET_SETSOCKOPT => network::sys_net_setsockopt(arg1, arg2, arg3, arg4),
+        SYS_NET_GETSOCKOPT => network::sys_net_getsockopt(arg1, arg2, arg3, arg4),
+        SYS_NET_TCP_LOCAL_ADDR => network::sys_net_tcp_local_addr(arg1, arg2),
+        SYS_SENDFILE => network::sys_sendfile(arg1, arg2, arg3, arg4),
         // ハンドル
         SYS_OPEN => handle::sys_open(arg1, arg2, arg3, arg4),
         SYS_HANDLE_READ => handle::sys_handle_read(arg1, arg2, arg3),
@@ -330,6 +381,7 @@ fn dispatch_inner(nr: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result
         SYS_HANDLE_CREATE_FILE => handle::sys_handle_create_file(arg1, arg2, arg3, arg4),
         SYS_HANDLE_UNLINK => handle::sys_handle_unlink(arg1, arg2, arg3),
         SYS_HANDLE_MKDIR => handle::sys_handle_mkdir(arg1, arg2, arg3),
+        SYS_FSYNC => handle::sys_fsync(arg1),
         // ブロックデバイス
         SYS_BLOCK_READ => ipc::sys_block_read(arg1, arg2, arg3, arg4),
         SYS_BLOCK_WRITE => ipc::sys_block_write(arg1, arg2, arg3, arg4),
@@ -340,14 +392,23 @@ fn dispatch_inner(nr: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result
         SYS_IPC_CANCEL => ipc::sys_ipc_cancel(arg1),
         SYS_IPC_SEND_HANDLE => ipc::sys_ipc_send_handle(arg1, arg2, arg3, arg4),
         SYS_IPC_RECV_HANDLE => ipc::sys_ipc_recv_handle(arg1, arg2, arg3, arg4),
+        // タイマー
+        SYS_TIMER_CREATE => ipc::sys_timer_create(arg1, arg2),
+        SYS_TIMER_CANCEL => ipc::sys_timer_cancel(arg1),
         // サウンド
         SYS_SOUND_PLAY => misc::sys_sound_play(arg1, arg2),
+        SYS_SOUND_PLAY_PCM => misc::sys_sound_play_pcm(arg1, arg2, arg3, arg4),
         // スレッド
         SYS_THREAD_CREATE => misc::sys_thread_create(arg1, arg2, arg3),
         SYS_THREAD_EXIT => misc::sys_thread_exit(arg1),
         SYS_THREAD_JOIN => misc::sys_thread_join(arg1, arg2),
+        SYS_THREAD_SET_TLS => misc::sys_thread_set_tls(arg1),
+        SYS_THREAD_GET_TLS => misc::sys_thread_get_tls(),
         // Futex
         SYS_FUTEX => misc::sys_futex(arg1, arg2, arg3, arg4),
+        // クリップボード
+        SYS_CLIPBOARD_SET => misc::sys_clipboard_set(arg1, arg2),
+        SYS_CLIPBOARD_GET => misc::sys_clipboard_get(arg1, arg2),
         // 時刻
         SYS_CLOCK_REALTIME => sysinfo::sys_clock_realtime(),
         // システム制御
@@ -356,13 +417,22 @@ fn dispatch_inner(nr: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result
         SYS_DRAW_LINE => graphics::sys_draw_line(arg1, arg2, arg3),
         SYS_DRAW_BLIT => graphics::sys_draw_blit(arg1, arg2, arg3, arg4),
         SYS_DRAW_TEXT => graphics::sys_draw_text(arg1, arg2, arg3, arg4),
+        SYS_FB_SET_DOUBLE_BUFFER => graphics::sys_fb_set_double_buffer(arg1),
+        SYS_FB_PRESENT => graphics::sys_fb_present(),
+        SYS_FB_SET_CLIP => graphics::sys_fb_set_clip(arg1, arg2, arg3, arg4),
+        SYS_SET_CURSOR => graphics::sys_set_cursor(arg1, arg2, arg3, arg4),
+        SYS_FB_LIST_MODES => graphics::sys_fb_list_modes(arg1, arg2),
+        SYS_FB_SET_LOGICAL_SIZE => graphics::sys_fb_set_logical_size(arg1, arg2),
         SYS_HALT => misc::sys_halt(),
         SYS_EXIT => {
-            // exit()
+            // exit(exit_code)
             // ユーザープログラムの終了を要求する。
+            // arg1 の終了コードをタスクに記録してから、
             // 保存されたカーネルスタック（RSP/RBP）を復元して
             // run_in_usermode() の呼び出し元に return する。
+            // 記録した終了コードは SYS_WAIT / SYS_WAITPID で親が回収する。
             // この関数は戻らない
+            crate::scheduler::set_exit_code(arg1 as i32);
             crate::usermode::exit_usermode();
         }
         _ => {
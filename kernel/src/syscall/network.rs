@@ -1,10 +1,11 @@
 // syscall/network.rs — ネットワーク関連システムコール
 //
 // SYS_NET_SEND/RECV_FRAME, SYS_NET_GET_MAC,
-// SYS_NET_DNS_LOOKUP, SYS_NET_TCP_*, SYS_NET_UDP_*, SYS_NET_PING6
+// SYS_NET_DNS_LOOKUP, SYS_NET_TCP_*, SYS_NET_UDP_*, SYS_NET_PING6,
+// 統一ソケット API（SYS_SOCKET / BIND / CONNECT / LISTEN / ACCEPT / SEND / RECV / RECV_FROM）
 
 use crate::user_ptr::SyscallError;
-use super::user_slice_from_args;
+use super::{user_ptr_from_arg, user_slice_from_args};
 
 /// SYS_NET_SEND_FRAME: Ethernet フレーム送信
 ///
@@ -117,6 +118,39 @@ pub(crate) fn sys_net_get_mac(arg1: u64, arg2: u64) -> Result<u64, SyscallError>
     Ok(6)
 }
 
+/// SYS_NET_SET_CONFIG: 静的 IP 設定
+///
+/// 引数:
+///   arg1 — NetConfigArgs のポインタ（ユーザー空間）
+///
+/// 設定後は DHCP で上書きされない。アドレスが 0.0.0.0 / ループバック、
+/// サブネットマスクが不正なら InvalidArgument。
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_net_set_config(arg1: u64) -> Result<u64, SyscallError> {
+    let args = user_ptr_from_arg::<sabos_syscall::NetConfigArgs>(arg1)?.read();
+    crate::net_config::set_static_config(args.ip, args.subnet_mask, args.gateway, args.dns)
+        .map_err(|_| SyscallError::InvalidArgument)?;
+    Ok(0)
+}
+
+/// SYS_NET_CAPTURE_OPEN: Ethernet フレームのキャプチャハンドルを開く
+///
+/// 引数:
+///   arg1 — flags（CAPTURE_FLAG_TX で送信フレームも含める）
+///   arg2 — Handle の書き込み先（ユーザー空間）
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_net_capture_open(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    if arg1 & !sabos_syscall::CAPTURE_FLAG_TX != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let out_ptr = user_ptr_from_arg::<crate::handle::Handle>(arg2)?;
+    let capture_id = crate::netstack::capture_open(arg1 & sabos_syscall::CAPTURE_FLAG_TX != 0);
+    out_ptr.write(crate::handle::create_capture_handle(capture_id));
+    Ok(0)
+}
+
 // =================================================================
 // カーネル内ネットワークスタック系システムコール (40-44, 150-156)
 // =================================================================
@@ -180,8 +214,12 @@ pub(crate) fn sys_net_tcp_send(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, S
     let data_slice = user_slice_from_args(arg2, arg3)?;
     let data = data_slice.as_slice();
 
-    crate::netstack::tcp_send(conn_id, data).map_err(|_| SyscallError::Other)?;
-    Ok(0)
+    match crate::netstack::tcp_send(conn_id, data) {
+        Ok(()) => Ok(0),
+        // SHUT_WR で送信側を閉じた後の送信
+        Err("write side shut down") => Err(SyscallError::BrokenPipe),
+        Err(_) => Err(SyscallError::Other),
+    }
 }
 
 /// SYS_NET_TCP_RECV: TCP データ受信
@@ -192,7 +230,11 @@ pub(crate) fn sys_net_tcp_send(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, S
 ///   arg3 — バッファの長さ
 ///   arg4 — タイムアウト（ミリ秒）
 ///
-/// 戻り値: 受信バイト数（成功）、0（タイムアウト）、負（エラー）
+/// 戻り値: 受信バイト数（成功）、0（相手が FIN を送って EOF）、
+///         Timeout（データが来ないままタイムアウト）、負（その他のエラー）
+///
+/// タイムアウトと EOF を同じ 0 で返すと、nc のような中継ループが
+/// 「相手が閉じた」ことを検出できないので区別する。
 pub(crate) fn sys_net_tcp_recv(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
     // wait_net_condition で待ちに入るため、割り込みを有効化する
     x86_64::instructions::interrupts::enable();
@@ -209,7 +251,7 @@ pub(crate) fn sys_net_tcp_recv(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Re
             buf[..copy_len].copy_from_slice(&data[..copy_len]);
             Ok(copy_len as u64)
         }
-        Err("timeout") => Ok(0),
+        Err("timeout") => Err(SyscallError::Timeout),
         Err("connection closed") => Ok(0),
         Err(_) => Err(SyscallError::Other),
     }
@@ -229,15 +271,38 @@ pub(crate) fn sys_net_tcp_close(arg1: u64) -> Result<u64, SyscallError> {
     Ok(0)
 }
 
+/// SYS_NET_TCP_SHUTDOWN: TCP 接続の片側を閉じる（half-close）
+///
+/// 引数:
+///   arg1 — conn_id
+///   arg2 — how（SHUT_RD / SHUT_WR / SHUT_RDWR）
+///
+/// 戻り値: 0（成功）、負（エラー）
+///
+/// SHUT_WR は FIN を送るだけで待たないので、割り込みの有効化は要らない。
+pub(crate) fn sys_net_tcp_shutdown(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let conn_id = arg1 as u32;
+    let how = match arg2 {
+        sabos_syscall::SHUT_RD => crate::netstack::TcpShutdown::Read,
+        sabos_syscall::SHUT_WR => crate::netstack::TcpShutdown::Write,
+        sabos_syscall::SHUT_RDWR => crate::netstack::TcpShutdown::Both,
+        _ => return Err(SyscallError::InvalidArgument),
+    };
+    crate::netstack::tcp_shutdown(conn_id, how).map_err(|_| SyscallError::Other)?;
+    Ok(0)
+}
+
 /// SYS_NET_TCP_LISTEN: TCP リッスン開始
 ///
 /// 引数:
 ///   arg1 — ポート番号
+///   arg2 — backlog（accept 待ちの接続の上限。0 = 既定値）
 ///
 /// 戻り値: 0（成功）、負（エラー）
-pub(crate) fn sys_net_tcp_listen(arg1: u64) -> Result<u64, SyscallError> {
+pub(crate) fn sys_net_tcp_listen(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
     let port = arg1 as u16;
-    crate::netstack::tcp_listen(port).map_err(|_| SyscallError::Other)?;
+    let backlog = usize::try_from(arg2).unwrap_or(usize::MAX);
+    crate::netstack::tcp_listen(port, backlog).map_err(|_| SyscallError::Other)?;
     Ok(0)
 }
 
@@ -246,19 +311,107 @@ pub(crate) fn sys_net_tcp_listen(arg1: u64) -> Result<u64, SyscallError> {
 /// 引数:
 ///   arg1 — タイムアウト（ミリ秒）
 ///   arg2 — リッスンポート
+///   arg3 — 相手のアドレスの書き込み先（0 なら書かない）
+///          [ip0, ip1, ip2, ip3, port_lo, port_hi] の 6 バイト
 ///
 /// 戻り値: conn_id（成功）、負（エラー/タイムアウト）
-pub(crate) fn sys_net_tcp_accept(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+pub(crate) fn sys_net_tcp_accept(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, SyscallError> {
     // wait_net_condition で待ちに入るため、割り込みを有効化する
     x86_64::instructions::interrupts::enable();
     let timeout_ms = arg1;
     let listen_port = arg2 as u16;
 
-    match crate::netstack::tcp_accept(timeout_ms, listen_port) {
-        Ok(conn_id) => Ok(conn_id as u64),
-        Err("timeout") => Err(SyscallError::Other),
-        Err(_) => Err(SyscallError::Other),
+    let conn_id = match crate::netstack::tcp_accept(timeout_ms, listen_port) {
+        Ok(conn_id) => conn_id,
+        Err("timeout") => return Err(SyscallError::Other),
+        Err(_) => return Err(SyscallError::Other),
+    };
+    if arg3 != 0 {
+        let (peer_ip, peer_port) = crate::netstack::tcp_peer_addr(conn_id).ok_or(SyscallError::Other)?;
+        write_addr_info(arg3, peer_ip, peer_port)?;
     }
+    Ok(conn_id as u64)
+}
+
+/// SYS_NET_TCP_LOCAL_ADDR: TCP 接続の自分側のアドレスを取得する
+///
+/// connect でつないだ接続の送信元ポートはカーネルが選ぶので、これで調べる。
+///
+/// 引数:
+///   arg1 — conn_id
+///   arg2 — アドレスの書き込み先 [ip0, ip1, ip2, ip3, port_lo, port_hi] の 6 バイト
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_net_tcp_local_addr(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let conn_id = u32::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    let (local_ip, local_port) = crate::netstack::tcp_local_addr(conn_id).ok_or(SyscallError::InvalidArgument)?;
+    write_addr_info(arg2, local_ip, local_port)?;
+    Ok(0)
+}
+
+/// [ip0, ip1, ip2, ip3, port_lo, port_hi] の 6 バイトをユーザー空間に書く
+///
+/// IPv6 のアドレスは 4 バイトに収まらないので 0.0.0.0 にする（SYS_NET_UDP_RECV_FROM と同じ）。
+fn write_addr_info(ptr: u64, ip: crate::netstack::IpAddr, port: u16) -> Result<(), SyscallError> {
+    let v4 = match ip {
+        crate::netstack::IpAddr::V4(v4) => v4,
+        crate::netstack::IpAddr::V6(_) => [0; 4],
+    };
+    let info_slice = user_slice_from_args(ptr, 6)?;
+    let info = info_slice.as_mut_slice();
+    info[0..4].copy_from_slice(&v4);
+    info[4..6].copy_from_slice(&port.to_le_bytes());
+    Ok(())
+}
+
+/// SYS_SENDFILE: ファイルハンドルの内容を TCP 接続へ送る
+///
+/// ファイルの中身をユーザー空間のバッファに読み出さず、カーネル内でハンドルの
+/// データから MSS ずつ切り出して tcp_send に渡す（httpd の静的ファイル配信用）。
+/// ファイルポジションは変えない。
+///
+/// 引数:
+///   arg1 — 送信先の conn_id
+///   arg2 — 読み出すファイルハンドルのポインタ（READ 権限が必要）
+///   arg3 — ファイル内の開始位置
+///   arg4 — 送る最大バイト数（ファイル末尾に達したらそこで止める）
+///
+/// 戻り値: 送ったバイト数（途中で送れなくなったら、それまでに送った分）、負（エラー）
+pub(crate) fn sys_sendfile(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    let conn_id = u32::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg2)?.read();
+    let offset = usize::try_from(arg3).map_err(|_| SyscallError::InvalidArgument)?;
+    let count = usize::try_from(arg4).unwrap_or(usize::MAX);
+    let sent = sendfile(conn_id, &handle, offset, count)?;
+    Ok(sent as u64)
+}
+
+/// SYS_SENDFILE の本体（selftest からも呼ぶ）
+///
+/// 1 バイトも送れないうちの失敗はエラー、送った後の失敗はそこまでのバイト数を返す
+/// （write(2) の部分書き込みと同じ扱いで、呼び出し側は残りを送り直せる）。
+pub fn sendfile(conn_id: u32, handle: &crate::handle::Handle, offset: usize, count: usize) -> Result<usize, SyscallError> {
+    let mss = crate::netstack::tcp_mss(conn_id).ok_or(SyscallError::InvalidArgument)?;
+    let mut sent = 0usize;
+    while sent < count {
+        let chunk_len = mss.min(count - sent);
+        let chunk = match crate::handle::read_range(handle, offset.saturating_add(sent), chunk_len) {
+            Ok(chunk) => chunk,
+            Err(e) if sent == 0 => return Err(e),
+            Err(_) => break,
+        };
+        // ファイル末尾
+        if chunk.is_empty() {
+            break;
+        }
+        match crate::netstack::tcp_send(conn_id, &chunk) {
+            Ok(()) => sent += chunk.len(),
+            Err(_) if sent > 0 => break,
+            Err("write side shut down") => return Err(SyscallError::BrokenPipe),
+            Err(_) => return Err(SyscallError::Other),
+        }
+    }
+    Ok(sent)
 }
 
 /// SYS_NET_UDP_BIND: UDP ソケットバインド
@@ -307,7 +460,26 @@ pub(crate) fn sys_net_udp_send_to(arg1: u64) -> Result<u64, SyscallError> {
     let data_slice = user_slice_from_args(data_ptr, data_len)?;
     let data = data_slice.as_slice();
 
-    crate::netstack::udp_send_to(socket_id, dst_ip, dst_port, data)
+    crate::netstack::udp_send_to(socket_id, crate::netstack::IpAddr::V4(dst_ip), dst_port, data)
+        .map_err(|_| SyscallError::Other)?;
+    Ok(0)
+}
+
+/// SYS_NET_UDP_SEND_TO6: IPv6 宛ての UDP データ送信
+///
+/// 引数:
+///   arg1 — UdpSendTo6Args 構造体ポインタ（ユーザー空間）
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_net_udp_send_to6(arg1: u64) -> Result<u64, SyscallError> {
+    // 近隣探索（NDP）の応答を待つことがあるため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let args = user_ptr_from_arg::<sabos_syscall::UdpSendTo6Args>(arg1)?.read();
+
+    let data_slice = user_slice_from_args(args.data_ptr, args.data_len)?;
+    let data = data_slice.as_slice();
+
+    crate::netstack::udp_send_to(args.socket_id, crate::netstack::IpAddr::V6(args.dst_ip), args.dst_port, data)
         .map_err(|_| SyscallError::Other)?;
     Ok(0)
 }
@@ -352,9 +524,15 @@ pub(crate) fn sys_net_udp_recv_from(arg1: u64) -> Result<u64, SyscallError> {
             buf[..copy_len].copy_from_slice(&data[..copy_len]);
 
             // src_info: [ip0, ip1, ip2, ip3, port_lo, port_hi]
+            // IPv6 の送信元は 4 バイトに収まらないので 0.0.0.0 にする
+            // （IPv6 を扱うなら SYS_NET_UDP_RECV_FROM6 を使う）
+            let src_v4 = match src_ip {
+                crate::netstack::IpAddr::V4(v4) => v4,
+                crate::netstack::IpAddr::V6(_) => [0; 4],
+            };
             let src_info_slice = user_slice_from_args(src_info_ptr, 6)?;
             let src_info = src_info_slice.as_mut_slice();
-            src_info[0..4].copy_from_slice(&src_ip);
+            src_info[0..4].copy_from_slice(&src_v4);
             src_info[4..6].copy_from_slice(&src_port.to_le_bytes());
 
             Ok(copy_len as u64)
@@ -364,6 +542,35 @@ pub(crate) fn sys_net_udp_recv_from(arg1: u64) -> Result<u64, SyscallError> {
     }
 }
 
+/// SYS_NET_UDP_RECV_FROM6: UDP データ受信（IPv4 / IPv6 両対応）
+///
+/// 引数:
+///   arg1 — UdpRecvFromArgs 構造体ポインタ（ユーザー空間）。
+///          src_info_ptr は UDP_SRC_INFO6_LEN (18) バイトのバッファを指す
+///
+/// 戻り値: 受信バイト数（成功）、負（エラー）
+pub(crate) fn sys_net_udp_recv_from6(arg1: u64) -> Result<u64, SyscallError> {
+    // wait_net_condition で待ちに入るため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let args = user_ptr_from_arg::<sabos_syscall::UdpRecvFromArgs>(arg1)?.read();
+
+    let (src_ip, src_port, data) = crate::netstack::udp_recv_from(args.socket_id, args.timeout_ms)
+        .map_err(|_| SyscallError::Other)?;
+
+    let buf_slice = user_slice_from_args(args.buf_ptr, args.buf_len)?;
+    let buf = buf_slice.as_mut_slice();
+    let copy_len = core::cmp::min(data.len(), buf.len());
+    buf[..copy_len].copy_from_slice(&data[..copy_len]);
+
+    // src_info: [アドレス 16 バイト（IPv4 は IPv4-mapped）, port_lo, port_hi]
+    let src_info_slice = user_slice_from_args(args.src_info_ptr, sabos_syscall::UDP_SRC_INFO6_LEN as u64)?;
+    let src_info = src_info_slice.as_mut_slice();
+    src_info[0..16].copy_from_slice(&src_ip.to_ipv6_mapped());
+    src_info[16..18].copy_from_slice(&src_port.to_le_bytes());
+
+    Ok(copy_len as u64)
+}
+
 /// SYS_NET_UDP_CLOSE: UDP ソケットクローズ
 ///
 /// 引数:
@@ -408,3 +615,275 @@ pub(crate) fn sys_net_ping6(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, Sysc
         Err(_) => Err(SyscallError::Other),
     }
 }
+
+// =================================================================
+// 統一ソケット API (180-189)
+// =================================================================
+//
+// ソケットはハンドルとして返す。実際の処理は socket.rs が
+// netstack の TCP / UDP 実装に振り分ける。
+
+/// ユーザーの SockAddr をカーネルの IpAddr + ポートに変換する
+fn sockaddr_to_ip(addr: &sabos_syscall::SockAddr) -> Result<(crate::netstack::IpAddr, u16), SyscallError> {
+    let ip = match addr.family as u64 {
+        sabos_syscall::AF_INET => {
+            let mut v4 = [0u8; 4];
+            v4.copy_from_slice(&addr.addr[..4]);
+            crate::netstack::IpAddr::V4(v4)
+        }
+        sabos_syscall::AF_INET6 => crate::netstack::IpAddr::V6(addr.addr),
+        _ => return Err(SyscallError::NotSupported),
+    };
+    Ok((ip, addr.port))
+}
+
+/// カーネルの IpAddr + ポートをユーザーの SockAddr に変換する
+fn ip_to_sockaddr(ip: crate::netstack::IpAddr, port: u16) -> sabos_syscall::SockAddr {
+    let mut addr = sabos_syscall::SockAddr { port, ..Default::default() };
+    match ip {
+        crate::netstack::IpAddr::V4(v4) => {
+            addr.family = sabos_syscall::AF_INET as u16;
+            addr.addr[..4].copy_from_slice(&v4);
+        }
+        crate::netstack::IpAddr::V6(v6) => {
+            addr.family = sabos_syscall::AF_INET6 as u16;
+            addr.addr = v6;
+        }
+    }
+    addr
+}
+
+/// SYS_SOCKET: ソケットを作成してハンドルを返す
+///
+/// 引数:
+///   arg1 — アドレスファミリー（AF_INET / AF_INET6）
+///   arg2 — 種類（SOCK_STREAM / SOCK_DGRAM）
+///   arg3 — プロトコル（0 なら種類から自動選択）
+///   arg4 — 作成したハンドルの書き込み先（ユーザー空間）
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_socket(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    let out_ptr = user_ptr_from_arg::<crate::handle::Handle>(arg4)?;
+    let socket_id = crate::socket::create(arg1, arg2, arg3)?;
+    out_ptr.write(crate::handle::create_socket_handle(socket_id));
+    Ok(0)
+}
+
+/// SYS_BIND: ソケットをローカルアドレスに割り当てる
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — SockAddr のポインタ（IP アドレスは無視。割り当てたポートが書き戻される）
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_bind(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let addr_ptr = user_ptr_from_arg::<sabos_syscall::SockAddr>(arg2)?;
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_WRITE)?;
+
+    let mut addr = addr_ptr.read();
+    addr.port = crate::socket::bind(socket_id, addr.port)?;
+    addr_ptr.write(addr);
+    Ok(0)
+}
+
+/// SYS_CONNECT: 接続先を設定する（TCP はハンドシェイク完了まで待つ）
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — 接続先 SockAddr のポインタ
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_connect(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    // TCP のハンドシェイク待ちに入るため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let addr = user_ptr_from_arg::<sabos_syscall::SockAddr>(arg2)?.read();
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_WRITE)?;
+
+    let (ip, port) = sockaddr_to_ip(&addr)?;
+    crate::socket::connect(socket_id, ip, port)?;
+    Ok(0)
+}
+
+/// SYS_LISTEN: TCP の待ち受けを開始する
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ（bind 済みであること）
+///   arg2 — backlog（accept 待ちの接続の上限。0 = 既定値）
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_listen(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_WRITE)?;
+    let backlog = usize::try_from(arg2).unwrap_or(usize::MAX);
+    crate::socket::listen(socket_id, backlog)?;
+    Ok(0)
+}
+
+/// SYS_ACCEPT: 接続を受け入れる
+///
+/// 引数:
+///   arg1 — 待ち受けソケットハンドルのポインタ
+///   arg2 — 新しいソケットハンドルの書き込み先
+///   arg3 — タイムアウト（ミリ秒）。0 = ノンブロッキング、SOCKET_WAIT_FOREVER = 無期限
+///   arg4 — 相手の SockAddr の書き込み先（0 なら書き込まない）
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_accept(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    // wait_net_condition で待ちに入るため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let out_ptr = user_ptr_from_arg::<crate::handle::Handle>(arg2)?;
+    let peer_ptr = if arg4 == 0 {
+        None
+    } else {
+        Some(user_ptr_from_arg::<sabos_syscall::SockAddr>(arg4)?)
+    };
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_READ)?;
+
+    let (new_id, peer_ip, peer_port) = crate::socket::accept(socket_id, arg3)?;
+    out_ptr.write(crate::handle::create_socket_handle(new_id));
+    if let Some(peer_ptr) = peer_ptr {
+        peer_ptr.write(ip_to_sockaddr(peer_ip, peer_port));
+    }
+    Ok(0)
+}
+
+/// SYS_SEND: データを送信する
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — データのポインタ
+///   arg3 — データの長さ
+///   arg4 — 宛先 SockAddr のポインタ（0 なら接続先。TCP では無視）
+///
+/// 戻り値: 送信したバイト数（成功）、負（エラー）
+pub(crate) fn sys_send(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    // TCP の ACK 待ちに入ることがあるため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let data = user_slice_from_args(arg2, arg3)?;
+    let dst = if arg4 == 0 {
+        None
+    } else {
+        Some(sockaddr_to_ip(&user_ptr_from_arg::<sabos_syscall::SockAddr>(arg4)?.read())?)
+    };
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_WRITE)?;
+
+    let n = crate::socket::send(socket_id, data.as_slice(), dst)?;
+    Ok(n as u64)
+}
+
+/// SYS_RECV: データを受信する
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — 受信バッファのポインタ
+///   arg3 — 受信バッファの長さ
+///   arg4 — タイムアウト（ミリ秒）。0 = ノンブロッキング、SOCKET_WAIT_FOREVER = 無期限
+///
+/// 戻り値: 受信したバイト数（TCP で 0 は EOF）、負（エラー）
+pub(crate) fn sys_recv(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    // wait_net_condition で待ちに入るため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let buf = user_slice_from_args(arg2, arg3)?;
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_READ)?;
+
+    let (n, _) = crate::socket::recv(socket_id, buf.as_mut_slice(), arg4)?;
+    Ok(n as u64)
+}
+
+/// SYS_RECV_FROM: 送信元アドレス付きでデータを受信する
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — 受信バッファのポインタ
+///   arg3 — 受信バッファの長さ
+///   arg4 — SockRecvFromArgs のポインタ（timeout_ms を読み、from に送信元を書き込む）
+///
+/// 戻り値: 受信したバイト数、負（エラー）
+pub(crate) fn sys_recv_from(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    // wait_net_condition で待ちに入るため、割り込みを有効化する
+    x86_64::instructions::interrupts::enable();
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let buf = user_slice_from_args(arg2, arg3)?;
+    let args_ptr = user_ptr_from_arg::<sabos_syscall::SockRecvFromArgs>(arg4)?;
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_READ)?;
+
+    let mut args = args_ptr.read();
+    let (n, from) = crate::socket::recv(socket_id, buf.as_mut_slice(), args.timeout_ms)?;
+    args.from = match from {
+        Some((ip, port)) => ip_to_sockaddr(ip, port),
+        None => sabos_syscall::SockAddr::default(),
+    };
+    args_ptr.write(args);
+    Ok(n as u64)
+}
+
+/// SYS_SETSOCKOPT: ソケットオプションを設定する
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — level（IPPROTO_TCP など）
+///   arg3 — optname（TCP_NODELAY など）
+///   arg4 — 設定する値
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_setsockopt(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_WRITE)?;
+    crate::socket::setsockopt(socket_id, arg2, arg3, arg4)?;
+    Ok(0)
+}
+
+/// SYS_GETSOCKOPT: ソケットオプションの現在値を取得する
+///
+/// 引数:
+///   arg1 — ソケットハンドルのポインタ
+///   arg2 — level（IPPROTO_TCP / SOL_SOCKET）
+///   arg3 — optname（TCP_NODELAY / SO_RCVTIMEO / SO_REUSEADDR）
+///   arg4 — 値（u64）の書き込み先
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_getsockopt(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    let handle = user_ptr_from_arg::<crate::handle::Handle>(arg1)?.read();
+    let socket_id = crate::handle::get_socket_id(&handle, crate::handle::HANDLE_RIGHT_READ)?;
+    let out_ptr = user_ptr_from_arg::<u64>(arg4)?;
+    let value = crate::socket::getsockopt(socket_id, arg2, arg3)?;
+    out_ptr.write(value);
+    Ok(0)
+}
+
+/// SYS_NET_SETSOCKOPT: conn_id / socket_id 指定でソケットオプションを設定する
+///
+/// 引数:
+///   arg1 — sock_id（TCP の conn_id または UDP の socket_id）
+///   arg2 — level（IPPROTO_TCP / SOL_SOCKET）
+///   arg3 — optname（TCP_NODELAY / SO_RCVTIMEO / SO_REUSEADDR）
+///   arg4 — 設定する値
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_net_setsockopt(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    let sock_id = u32::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    crate::netstack::set_socket_option(sock_id, arg2, arg3, arg4).map_err(crate::socket::sockopt_error)?;
+    Ok(0)
+}
+
+/// SYS_NET_GETSOCKOPT: conn_id / socket_id 指定でソケットオプションの現在値を取得する
+///
+/// 引数:
+///   arg1 — sock_id（TCP の conn_id または UDP の socket_id）
+///   arg2 — level（IPPROTO_TCP / SOL_SOCKET）
+///   arg3 — optname（TCP_NODELAY / SO_RCVTIMEO / SO_REUSEADDR）
+///   arg4 — 値（u64）の書き込み先
+///
+/// 戻り値: 0（成功）、負（エラー）
+pub(crate) fn sys_net_getsockopt(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> Result<u64, SyscallError> {
+    let sock_id = u32::try_from(arg1).map_err(|_| SyscallError::InvalidArgument)?;
+    let out_ptr = user_ptr_from_arg::<u64>(arg4)?;
+    let value = crate::netstack::get_socket_option(sock_id, arg2, arg3).map_err(crate::socket::sockopt_error)?;
+    out_ptr.write(value);
+    Ok(0)
+}
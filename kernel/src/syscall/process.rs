@@ -1,6 +1,6 @@
 // syscall/process.rs — プロセス管理・環境変数関連システムコール
 //
-// SYS_EXEC/SPAWN, SYS_YIELD/SLEEP/WAIT/WAITPID/GETPID/KILL,
+// SYS_EXEC/SPAWN, SYS_YIELD/SLEEP/WAIT/WAITPID/GETPID/KILL, SYS_SETPRIORITY/GETPRIORITY,
 // SYS_GETENV/SETENV/LISTENV, exec_by_path*, exec_for_test*
 
 use alloc::string::String;
@@ -134,23 +134,37 @@ pub fn exec_for_test(path: &str) -> bool {
 /// exec_by_path と同じロジックだが、wait_for_child を呼ばずに
 /// spawn だけして task_id を返す。呼び出し元で waitpid を使って回収する。
 pub fn exec_spawn_for_test(path: &str) -> Result<u64, SyscallError> {
-    use alloc::string::String;
-    use alloc::vec::Vec;
-    use x86_64::registers::control::Cr3;
+    exec_spawn_with_args_for_test(path, &[path])
+}
+
+/// selftest 用: 引数付きで ELF を spawn してタスク ID を返す
+///
+/// args は argv 全体（args[0] はプログラム名）。
+/// 終了コードを指定して終了する子プロセスを作るテスト等で使う。
+pub fn exec_spawn_with_args_for_test(path: &str, args: &[&str]) -> Result<u64, SyscallError> {
+    exec_spawn_with_env_for_test(path, args, None, None)
+}
 
+/// selftest 用: 環境変数とカレントディレクトリを指定して ELF を spawn してタスク ID を返す
+///
+/// `env_vars` / `cwd` は scheduler::spawn_user_with_env() と同じ（None = 親から継承）。
+pub fn exec_spawn_with_env_for_test(
+    path: &str,
+    args: &[&str],
+    env_vars: Option<Vec<(String, String)>>,
+    cwd: Option<String>,
+) -> Result<u64, SyscallError> {
     let process_name = String::from(
         path.rsplit('/').next().unwrap_or(path)
     );
 
     let elf_data = crate::vfs::read_file(path).map_err(crate::vfs::vfs_error_to_syscall)?;
 
-    let args_vec: Vec<&str> = alloc::vec![path];
-
     let (current_cr3, current_flags) = Cr3::read();
     unsafe {
         crate::paging::switch_to_kernel_page_table();
     }
-    let task_id = match crate::scheduler::spawn_user(&process_name, &elf_data, &args_vec) {
+    let task_id = match crate::scheduler::spawn_user_with_env(&process_name, &elf_data, args, env_vars, cwd) {
         Ok(id) => id,
         Err(_) => {
             unsafe { Cr3::write(current_cr3, current_flags); }
@@ -166,42 +180,44 @@ pub fn exec_spawn_for_test(path: &str) -> Result<u64, SyscallError> {
 ///
 /// 指定したパスの ELF を args と env_vars 付きで spawn し、終了を待つ。
 /// テスト用なので、環境変数は呼び出し元のタスクに一時的に設定してから
-/// spawn する（spawn 時に子に継承される）。
+/// spawn し（spawn 時に子に継承される）、spawn の後で元に戻す。
 pub fn exec_with_args_for_test(path: &str, args: &[&str], env_vars: &[(&str, &str)]) -> bool {
     use alloc::string::String;
+    use alloc::vec::Vec;
 
     let process_name = String::from(
         path.rsplit('/').next().unwrap_or(path)
     );
 
     // 環境変数を現在のタスクに一時的に設定する（spawn で子に継承される）
+    let saved: Vec<(&str, Option<String>)> = env_vars.iter()
+        .map(|&(key, _)| (key, crate::scheduler::get_env_var(key)))
+        .collect();
     for &(key, value) in env_vars {
         crate::scheduler::set_env_var(key, value);
     }
 
-    // VFS 経由でファイルを読み込む
-    let elf_data = match crate::vfs::read_file(path) {
-        Ok(data) => data,
-        Err(_) => return false,
-    };
-
-    // カーネルのページテーブルで spawn する
-    let (current_cr3, current_flags) = Cr3::read();
-    unsafe {
-        crate::paging::switch_to_kernel_page_table();
-    }
-    let task_id = match crate::scheduler::spawn_user(&process_name, &elf_data, args) {
-        Ok(id) => id,
-        Err(_) => {
-            unsafe { Cr3::write(current_cr3, current_flags); }
-            return false;
+    // VFS 経由でファイルを読み込み、カーネルのページテーブルで spawn する
+    let spawned = crate::vfs::read_file(path).ok().and_then(|elf_data| {
+        let (current_cr3, current_flags) = Cr3::read();
+        unsafe {
+            crate::paging::switch_to_kernel_page_table();
         }
+        let result = crate::scheduler::spawn_user(&process_name, &elf_data, args);
+        unsafe { Cr3::write(current_cr3, current_flags); }
+        result.ok()
+    });
+
+    // 子プロセスには継承済みなので、呼び出し元の環境変数を元に戻す
+    for (key, value) in &saved {
+        match value {
+            Some(v) => crate::scheduler::set_env_var(key, v),
+            None => crate::scheduler::remove_env_var(key),
+        }
+    }
+    let Some(task_id) = spawned else {
+        return false;
     };
-    unsafe { Cr3::write(current_cr3, current_flags); }
-
-    // テスト用の環境変数を削除（クリーンアップ）
-    // 注: 簡略化のため、キーを空文字列に設定（現在は削除 API がないため）
-    // 子プロセスには既に継承済みなので問題ない
 
     // 子プロセスの終了を待つ
     match crate::scheduler::wait_for_child(task_id, 0) {
@@ -309,7 +325,7 @@ pub(crate) fn sys_sleep(arg1: u64) -> Result<u64, SyscallError> {
 ///   arg2 — タイムアウト (ms)。0 なら無期限待ち
 ///
 /// 戻り値:
-///   終了した子プロセスの終了コード（成功時）
+///   終了した子プロセスの終了コード（成功時、encode_exit_code() でエンコード済み）
 ///   負の値（エラー時）
 ///
 /// 動作:
@@ -326,13 +342,35 @@ pub(crate) fn sys_wait(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
 
     let result = crate::scheduler::wait_for_child(target_task_id, timeout_ms);
     match result {
-        Ok(exit_code) => Ok(exit_code as u64),
+        Ok(exit_code) => Ok(encode_exit_code(exit_code)),
         Err(crate::scheduler::WaitError::NoChild) => Err(SyscallError::InvalidArgument),
         Err(crate::scheduler::WaitError::NotChild) => Err(SyscallError::PermissionDenied),
         Err(crate::scheduler::WaitError::Timeout) => Err(SyscallError::Timeout),
     }
 }
 
+/// 終了コード（i32）を SYS_WAIT の戻り値にエンコードする。
+///
+/// 終了コードをそのまま u64 に符号拡張すると、kill された子の -1 などが
+/// 負の errno（エラー）と区別できなくなる。
+/// そこで終了コードのビット列を u32 として下位 32 ビットに詰め、上位 32 ビットは 0 にする。
+/// こうすると i64 として見たとき戻り値は常に 0 以上になり、
+/// 「負ならエラー、0 以上なら終了コード」という規約が保たれる。
+/// ユーザー側は `ret as u32 as i32` でデコードできる。
+fn encode_exit_code(exit_code: i32) -> u64 {
+    exit_code as u32 as u64
+}
+
+/// selftest 用: SYS_WAIT と同じ経路で子プロセスの終了を待ち、生の戻り値を返す
+///
+/// エンコード済みの終了コード、または負の errno（u64 として）が返る。
+pub fn wait_for_test(task_id: u64, timeout_ms: u64) -> u64 {
+    match sys_wait(task_id, timeout_ms) {
+        Ok(value) => value,
+        Err(err) => err.to_errno(),
+    }
+}
+
 /// SYS_WAITPID: 子プロセスの終了を待つ（拡張版）
 ///
 /// 引数:
@@ -383,28 +421,81 @@ pub(crate) fn sys_getpid() -> Result<u64, SyscallError> {
     Ok(crate::scheduler::current_task_id())
 }
 
-/// SYS_KILL: タスクを強制終了する
+/// SYS_KILL: タスクにシグナルを送る
 ///
 /// 引数:
-///   arg1 — 終了させるタスクの ID
+///   arg1 — 対象タスクの ID
+///   arg2 — シグナル番号（0 なら SIG_KILL。引数 1 つの旧来の kill と互換）
 ///
 /// 戻り値:
 ///   0（成功時）
 ///
 /// エラー:
-///   - InvalidArgument: 自分自身を kill しようとした、またはタスクが見つからない
+///   - InvalidArgument: 自分自身を SIG_KILL しようとした、タスクが見つからない、
+///     または未知のシグナル番号
 ///   - PermissionDenied: 既に終了済み
-pub(crate) fn sys_kill(arg1: u64) -> Result<u64, SyscallError> {
+pub(crate) fn sys_kill(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
     let task_id = arg1;
-    match crate::scheduler::kill_task(task_id) {
+    let signal = if arg2 == 0 { sabos_syscall::SIG_KILL } else { arg2 };
+    match crate::scheduler::send_signal(task_id, signal) {
         Ok(()) => Ok(0),
         Err("cannot kill self") => Err(SyscallError::InvalidArgument),
         Err("task not found") => Err(SyscallError::InvalidArgument),
+        Err("invalid signal") => Err(SyscallError::InvalidArgument),
         Err("task already finished") => Err(SyscallError::PermissionDenied),
         Err(_) => Err(SyscallError::Other),
     }
 }
 
+/// SYS_SETPRIORITY: タスクの優先度を設定する
+///
+/// 引数:
+///   arg1 — タスク ID（0 なら自分）
+///   arg2 — 優先度（PRIORITY_MIN..=PRIORITY_MAX、大きいほど CPU を多く使える）
+///
+/// 戻り値:
+///   0（成功時）
+///   InvalidArgument（タスクがない / 終了済み、優先度が範囲外）
+///   PermissionDenied（自分・同じプロセスのスレッド・自分の子以外、または自分より高い優先度）
+pub(crate) fn sys_setpriority(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let caller = crate::scheduler::current_task_id();
+    let task_id = if arg1 == 0 { caller } else { arg1 };
+    let priority = u8::try_from(arg2).map_err(|_| SyscallError::InvalidArgument)?;
+    match crate::scheduler::set_priority(caller, task_id, priority) {
+        Ok(()) => Ok(0),
+        Err("not permitted") => Err(SyscallError::PermissionDenied),
+        Err(_) => Err(SyscallError::InvalidArgument),
+    }
+}
+
+/// SYS_GETPRIORITY: タスクの優先度を取得する
+///
+/// 引数:
+///   arg1 — タスク ID（0 なら自分）
+///
+/// 戻り値:
+///   優先度（成功時）
+///   InvalidArgument（タスクがない / 終了済み）
+pub(crate) fn sys_getpriority(arg1: u64) -> Result<u64, SyscallError> {
+    let task_id = if arg1 == 0 { crate::scheduler::current_task_id() } else { arg1 };
+    crate::scheduler::get_priority(task_id)
+        .map(u64::from)
+        .ok_or(SyscallError::InvalidArgument)
+}
+
+/// SYS_SIGPENDING: 保留中のシグナルを取得してクリアする
+///
+/// 引数: なし
+///
+/// 戻り値:
+///   保留中シグナルのビットマスク（ビット n = シグナル番号 n、なければ 0）
+///
+/// SIG_TERM を受けたサービスはこれをポーリングし、
+/// 状態を書き出してから自分で exit する。
+pub(crate) fn sys_sigpending() -> Result<u64, SyscallError> {
+    Ok(crate::scheduler::take_pending_signals())
+}
+
 // =================================================================
 // 環境変数関連システムコール
 // =================================================================
@@ -488,3 +579,65 @@ pub(crate) fn sys_listenv(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
     buf.as_mut_slice()[..data.len()].copy_from_slice(data.as_bytes());
     Ok(data.len() as u64)
 }
+
+/// SYS_GETCWD: カレントディレクトリを取得する
+///
+/// 引数:
+///   arg1 — バッファのポインタ（ユーザー空間）
+///   arg2 — バッファの長さ
+///
+/// 戻り値:
+///   書き込んだバイト数（成功時）
+///   -4 (BUFFER_OVERFLOW): バッファが小さすぎる
+pub(crate) fn sys_getcwd(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let buf = user_slice_from_args(arg1, arg2)?;
+    let cwd = crate::scheduler::current_cwd();
+
+    if cwd.len() > buf.as_slice().len() {
+        return Err(SyscallError::BufferOverflow);
+    }
+
+    buf.as_mut_slice()[..cwd.len()].copy_from_slice(cwd.as_bytes());
+    Ok(cwd.len() as u64)
+}
+
+/// SYS_CHDIR: カレントディレクトリを変更する
+///
+/// 引数:
+///   arg1 — パスのポインタ（ユーザー空間）
+///   arg2 — パスの長さ
+///
+/// 相対パスは今のカレントディレクトリからたどる。".." は normalize_path と同じく使えない。
+/// 同じプロセスのスレッドすべてのカレントディレクトリが変わる。
+///
+/// 戻り値:
+///   0（成功時）
+///   -10 (INVALID_ARGUMENT): ディレクトリではない
+pub(crate) fn sys_chdir(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    let path_slice = user_slice_from_args(arg1, arg2)?;
+    let path = path_slice.as_str().map_err(|_| SyscallError::InvalidUtf8)?;
+
+    let dir = resolve_dir_path(path)?;
+    crate::scheduler::set_process_cwd(&dir);
+    Ok(0)
+}
+
+/// カレントディレクトリを基準にパスを解決し、ディレクトリであることを確かめる
+///
+/// SYS_CHDIR と SYS_SPAWN_REDIRECTED の作業ディレクトリ指定で使う。
+/// 返すのは normalize_path 済みの絶対パス。
+pub(super) fn resolve_dir_path(path: &str) -> Result<String, SyscallError> {
+    let joined = if path.starts_with('/') {
+        String::from(path)
+    } else {
+        let mut cwd = crate::scheduler::current_cwd();
+        cwd.push('/');
+        cwd.push_str(path);
+        cwd
+    };
+    let normalized = crate::vfs::normalize_path(&joined).map_err(crate::vfs::vfs_error_to_syscall)?;
+    match crate::vfs::node_kind(&normalized).map_err(crate::vfs::vfs_error_to_syscall)? {
+        crate::vfs::VfsNodeKind::Directory => Ok(normalized),
+        crate::vfs::VfsNodeKind::File => Err(SyscallError::InvalidArgument),
+    }
+}
@@ -1,6 +1,6 @@
 // syscall/sysinfo.rs — システム情報関連システムコール
 //
-// SYS_GET_MEM/TASK/NET_INFO, SYS_PCI_CONFIG_READ,
+// SYS_GET_MEM/TASK/NET_INFO, SYS_GET_TASK_INFO, SYS_SCHED_STATS, SYS_PCI_CONFIG_READ, SYS_PCI_READ_BAR,
 // SYS_CLOCK_MONOTONIC/REALTIME, write_mem_info, write_task_list
 
 use crate::user_ptr::SyscallError;
@@ -109,6 +109,62 @@ pub(crate) fn sys_get_task_list(arg1: u64, arg2: u64) -> Result<u64, SyscallErro
     Ok(write_task_list(buf) as u64)
 }
 
+/// SYS_GET_TASK_INFO: タスクのメモリ使用量を取得
+///
+/// 引数:
+///   arg1 — タスク ID（0 なら自分）
+///   arg2 — 結果を書き込む TaskMemInfo へのポインタ
+///
+/// 戻り値:
+///   0（成功時）
+///   負の値（エラー時。存在しない / 終了済みのタスクは InvalidArgument）
+///
+/// /proc/<pid>/status と同じ値を構造体で返す。
+/// JSON を解析しなくて済むので、mmap の前後で数え比べるような用途に向いている。
+pub(crate) fn sys_get_task_info(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    use sabos_syscall::TaskMemInfo;
+
+    let out = super::user_ptr_from_arg::<TaskMemInfo>(arg2)?;
+    let task_id = if arg1 == 0 { crate::scheduler::current_task_id() } else { arg1 };
+    let detail = match crate::scheduler::task_detail(task_id) {
+        Some(d) if d.state != crate::scheduler::TaskState::Finished => d,
+        _ => return Err(SyscallError::InvalidArgument),
+    };
+    let vmas = crate::scheduler::get_vma_list_for_task(task_id).unwrap_or_default();
+
+    out.write(TaskMemInfo {
+        user_frames: detail.user_frames as u64,
+        mmap_frames: detail.mmap_frames as u64,
+        vma_count: vmas.len() as u64,
+        vm_bytes: vmas.iter().map(|v| v.size()).sum(),
+    });
+    Ok(0)
+}
+
+/// SYS_SCHED_STATS: スケジューラの統計を取得
+///
+/// 引数:
+///   arg1 — 結果を書き込む SchedStats へのポインタ
+///   arg2 — バッファの長さ（SchedStats の大きさ以上）
+///
+/// 戻り値:
+///   書き込んだバイト数（成功時）
+///   BufferOverflow（バッファが SchedStats より小さい）
+///
+/// タイマー割り込み・コンテキストスイッチ・プリエンプションの回数（起動からの累計）。
+/// 2 回読んだ差を取れば、その間のスケジューリングの様子が分かる。
+pub(crate) fn sys_sched_stats(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
+    use sabos_syscall::SchedStats;
+
+    let size = core::mem::size_of::<SchedStats>();
+    if arg2 < size as u64 {
+        return Err(SyscallError::BufferOverflow);
+    }
+    let out = super::user_ptr_from_arg::<SchedStats>(arg1)?;
+    out.write(crate::scheduler::sched_stats());
+    Ok(size as u64)
+}
+
 /// SYS_GET_NET_INFO: ネットワーク情報を取得
 ///
 /// 引数:
@@ -123,6 +179,7 @@ pub(crate) fn sys_get_task_list(arg1: u64, arg2: u64) -> Result<u64, SyscallErro
 ///   ip=X.X.X.X
 ///   gateway=X.X.X.X
 ///   dns=X.X.X.X
+///   ipv6=XXXX::XXXX
 ///   mac=XX:XX:XX:XX:XX:XX
 pub(crate) fn sys_get_net_info(arg1: u64, arg2: u64) -> Result<u64, SyscallError> {
     use core::fmt::Write;
@@ -140,6 +197,7 @@ pub(crate) fn sys_get_net_info(arg1: u64, arg2: u64) -> Result<u64, SyscallError
     let _ = writeln!(writer, "ip={}.{}.{}.{}", my_ip[0], my_ip[1], my_ip[2], my_ip[3]);
     let _ = writeln!(writer, "gateway={}.{}.{}.{}", gateway[0], gateway[1], gateway[2], gateway[3]);
     let _ = writeln!(writer, "dns={}.{}.{}.{}", dns[0], dns[1], dns[2], dns[3]);
+    let _ = writeln!(writer, "ipv6={}", crate::netstack::format_ipv6(&crate::net_config::get_my_ipv6()));
 
     // MAC アドレスを取得（virtio-net が初期化されていれば）
     let drv = crate::virtio_net::VIRTIO_NET.lock();
@@ -221,6 +279,48 @@ pub(crate) fn sys_pci_config_read(arg1: u64, arg2: u64, arg3: u64, arg4: u64) ->
     Ok(value as u64)
 }
 
+/// SYS_PCI_READ_BAR: PCI デバイスの BAR をデコードして返す
+///
+/// 引数:
+///   arg1 — BDF を詰めた値 (bus << 16 | device << 8 | function)
+///   arg2 — BAR 番号 (0-5)
+///   arg3 — 結果を書き込む PciBarInfo へのポインタ
+///
+/// 戻り値:
+///   0（成功時）
+///   負の値（エラー時。未実装の BAR や 64 ビット BAR の上位半分は InvalidArgument）
+///
+/// サイズの取得には BAR への書き込みが必要だが、動いているデバイスの BAR を
+/// 書き換えるのは危ないので、起動時に pci::cache_bars() で記録した値を返すだけにする。
+/// ユーザー空間には書き込み手段を渡さない。
+pub(crate) fn sys_pci_read_bar(arg1: u64, arg2: u64, arg3: u64) -> Result<u64, SyscallError> {
+    use sabos_syscall::{PciBarInfo, PCI_BAR_FLAG_PREFETCHABLE, PCI_BAR_KIND_IO, PCI_BAR_KIND_MEM32, PCI_BAR_KIND_MEM64};
+
+    let bus = (arg1 >> 16) as u8;
+    let device = (arg1 >> 8) as u8;
+    let function = arg1 as u8;
+    if (arg1 >> 24) != 0 || device > 31 || function > 7 || arg2 > 5 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let out = super::user_ptr_from_arg::<PciBarInfo>(arg3)?;
+
+    let bar = crate::pci::cached_bar(bus, device, function, arg2 as u8)
+        .ok_or(SyscallError::InvalidArgument)?;
+
+    let kind = match bar.kind {
+        crate::pci::BarKind::Memory32 => PCI_BAR_KIND_MEM32,
+        crate::pci::BarKind::Memory64 => PCI_BAR_KIND_MEM64,
+        crate::pci::BarKind::Io => PCI_BAR_KIND_IO,
+    };
+    out.write(PciBarInfo {
+        base: bar.base,
+        size: bar.size,
+        kind,
+        flags: if bar.prefetchable { PCI_BAR_FLAG_PREFETCHABLE } else { 0 },
+    });
+    Ok(0)
+}
+
 /// SYS_CLOCK_MONOTONIC: 起動からの経過ミリ秒を返す
 ///
 /// PIT (Programmable Interval Timer) のティックカウントをミリ秒に変換する。
@@ -0,0 +1,143 @@
+// timer.rs — IPC メッセージで知らせるタイマー（SYS_TIMER_CREATE / SYS_TIMER_CANCEL）
+//
+// 周期的な処理をしたいプログラムが sleep のループでメインスレッドを止めなくて済むよう、
+// 期限が来たら作ったタスクに IPC メッセージを送る。受け取る側は ipc_recv で
+// ほかのタスクからのメッセージと同じように待てるので、イベントループにまとめやすい。
+//
+// ## 期限の管理
+//
+// 期限の判定はタイマー割り込み（scheduler::preempt）のスリープ解除に任せる。
+// 専用のカーネルタスク timer_task がいちばん近い期限まで眠り、起きたら期限の来た
+// タイマーのメッセージを送る。割り込みハンドラの中で IPC キューのロックを取ったり
+// メッセージのメモリを確保したりすると、割り込まれた側がそのロックを持っていたときに
+// デッドロックするので、送るのはタスクの文脈で行う。
+//
+// ## メッセージ
+//
+// 送信元は TIMER_IPC_SENDER（どのタスク ID とも重ならない値）、中身は TimerMessage。
+// 周期タイマーの期限を取りこぼした（受け取り側が遅れた、ティックが飛んだ）ときは、
+// 何回分の期限が過ぎたかを expirations にまとめて 1 通だけ送る。
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use sabos_syscall::{TimerMessage, TIMER_IPC_SENDER};
+use spin::Mutex;
+
+use crate::scheduler;
+use crate::user_ptr::SyscallError;
+
+/// 1 タスクが同時に持てるタイマーの数
+pub const MAX_TIMERS_PER_TASK: usize = 16;
+
+struct Timer {
+    id: u64,
+    /// 作ったタスク（メッセージの宛先）
+    owner: u64,
+    /// 周期（タイマーティック）
+    interval: u64,
+    /// 次に発火するティック
+    deadline: u64,
+    periodic: bool,
+}
+
+static TIMERS: Mutex<Vec<Timer>> = Mutex::new(Vec::new());
+
+/// 次に割り当てるタイマー ID（0 は使わない）
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// timer_task のタスク ID（0 ならまだ動いていない）
+static TIMER_TASK_ID: AtomicU64 = AtomicU64::new(0);
+
+/// owner 宛のタイマーを作り、ID を返す。
+///
+/// interval_ms 後に 1 回だけ（periodic なら interval_ms ごとに）owner へメッセージを送る。
+/// 周期はタイマーティック（約 55ms）に切り上げる。
+pub fn create(owner: u64, interval_ms: u64, periodic: bool) -> Result<u64, SyscallError> {
+    if interval_ms == 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+    let interval = scheduler::ms_to_ticks(interval_ms);
+    let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+    let id = {
+        let mut timers = TIMERS.lock();
+        if timers.iter().filter(|t| t.owner == owner).count() >= MAX_TIMERS_PER_TASK {
+            return Err(SyscallError::Other);
+        }
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+        timers.push(Timer { id, owner, interval, deadline: now + interval, periodic });
+        id
+    };
+    // いま眠っている期限より早いかもしれないので、起こして期限を選び直させる
+    wake_timer_task();
+    Ok(id)
+}
+
+/// owner が作ったタイマー id を止める。
+///
+/// まだ送っていない分のメッセージは送られない（キューに入ったものは残る）。
+pub fn cancel(owner: u64, id: u64) -> Result<(), SyscallError> {
+    let mut timers = TIMERS.lock();
+    let pos = timers
+        .iter()
+        .position(|t| t.id == id && t.owner == owner)
+        .ok_or(SyscallError::InvalidArgument)?;
+    timers.swap_remove(pos);
+    Ok(())
+}
+
+/// タスクの終了時に、そのタスクが作ったタイマーをすべて止める
+pub fn cleanup_task(task_id: u64) {
+    TIMERS.lock().retain(|t| t.owner != task_id);
+}
+
+/// task_id が持っているタイマーの数
+pub fn count_for(task_id: u64) -> usize {
+    TIMERS.lock().iter().filter(|t| t.owner == task_id).count()
+}
+
+fn wake_timer_task() {
+    let id = TIMER_TASK_ID.load(Ordering::Relaxed);
+    if id != 0 {
+        scheduler::wake_task(id);
+    }
+}
+
+/// いちばん近い期限（タイマーがなければ u64::MAX）
+fn next_deadline() -> u64 {
+    TIMERS.lock().iter().map(|t| t.deadline).min().unwrap_or(u64::MAX)
+}
+
+/// 期限の来たタイマーを取り出して (宛先, メッセージ) を返す。
+/// ワンショットのタイマーは消し、周期タイマーは次の期限に進める。
+fn take_expired(now: u64) -> Vec<(u64, TimerMessage)> {
+    let mut expired = Vec::new();
+    TIMERS.lock().retain_mut(|t| {
+        if t.deadline > now {
+            return true;
+        }
+        // 取りこぼした分もまとめて数える
+        let expirations = if t.periodic { (now - t.deadline) / t.interval + 1 } else { 1 };
+        expired.push((t.owner, TimerMessage { timer_id: t.id, expirations }));
+        t.deadline += expirations * t.interval;
+        t.periodic
+    });
+    expired
+}
+
+/// タイマーのメッセージを送るカーネルタスク（main.rs で起動する）
+pub fn timer_task() {
+    TIMER_TASK_ID.store(scheduler::current_task_id(), Ordering::Relaxed);
+    loop {
+        let now = crate::interrupts::TIMER_TICK_COUNT.load(Ordering::Relaxed);
+        for (owner, msg) in take_expired(now) {
+            let mut data = [0u8; core::mem::size_of::<TimerMessage>()];
+            data[..8].copy_from_slice(&msg.timer_id.to_le_bytes());
+            data[8..].copy_from_slice(&msg.expirations.to_le_bytes());
+            // 宛先がもう終了していたら捨てる（タイマーは cleanup_task で消える）
+            let _ = crate::ipc::send(TIMER_IPC_SENDER, owner, &data);
+        }
+        // 眠る直前にタイマーが増えて期限が早まっていたら、眠らずにやり直す
+        let deadline = next_deadline();
+        scheduler::park_until(deadline, || next_deadline() < deadline);
+    }
+}
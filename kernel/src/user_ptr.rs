@@ -311,14 +311,6 @@ impl UserSlice<u8> {
         let bytes = self.as_slice();
         core::str::from_utf8(bytes).map_err(|_| SyscallError::InvalidUtf8)
     }
-
-    /// バイトスライスを UTF-8 文字列として解釈（エラー時は置換）
-    ///
-    /// 不正な UTF-8 の場合は "<invalid utf-8>" を返す。
-    pub fn as_str_lossy(&self) -> &str {
-        let bytes = self.as_slice();
-        core::str::from_utf8(bytes).unwrap_or("<invalid utf-8>")
-    }
 }
 
 #[cfg(test)]
@@ -70,6 +70,10 @@ pub struct UserProcess {
     /// - vma_list: 仮想アドレス空間のマッピング意図管理（検索・分割用）
     /// 将来の Demand Paging では VMA があっても物理フレームが未割り当ての状態がありうる。
     pub allocated_frames: Vec<PhysFrame<Size4KiB>>,
+    /// allocated_frames のうち SYS_MMAP で確保したフレームの数。
+    /// ELF やスタックの分と違って実行中に増減するので、
+    /// munmap し忘れて増え続けるプログラム（メモリリーク）を見つけるのに使う。
+    pub mmap_frames: usize,
     /// プロセスの仮想メモリ領域（VMA）リスト。
     /// ELF LOAD セグメント、ユーザースタック、mmap 領域を管理する。
     /// 空き仮想アドレスの検索や munmap での部分アンマップに使う。
@@ -124,6 +128,7 @@ pub fn create_user_process(program: &UserProgram) -> UserProcess {
         page_table_frame,
         kernel_stack,
         allocated_frames: Vec::new(),
+        mmap_frames: 0,
         vma_list: crate::vma::VmaList::new(),
     }
 }
@@ -211,7 +216,7 @@ static mut USER_ENTRY_ENVP: u64 = 0;
 //   1. 現在の RSP/RBP を SAVED_RSP/SAVED_RBP に保存
 //   2. user_ss を user_cs - 8 で計算
 //   3. iretq 用スタックフレームを構築
-//   4. iretq で Ring 3 に遷移
+//   4. swapgs して iretq で Ring 3 に遷移
 //
 // exit_usermode() が SAVED_RSP/SAVED_RBP を復元して ret すると、
 // この関数の呼び出し元（run_in_usermode 内）に戻る。
@@ -250,6 +255,11 @@ global_asm!(
     "mov rsi, [rip + {user_argv}]",
     "mov rdx, [rip + {user_envp}]",
 
+    // GS ベースをユーザーのものに切り替えてから Ring 3 に入る。
+    // swapgs から iretq までに割り込まれると GS がユーザーの値のまま
+    // カーネルが動いてしまうので、先に割り込みを止めておく（IF は RFLAGS から戻る）。
+    "cli",
+    "swapgs",
     "iretq",       // Ring 3 へ遷移！
     saved_rsp = sym SAVED_RSP,
     saved_rbp = sym SAVED_RBP,
@@ -324,7 +334,7 @@ pub fn run_in_usermode(process: &UserProcess, program: &UserProgram) {
     // TSS rsp0 にカーネルスタックのトップを設定する。
     // これを忘れると Ring 3 → Ring 0 遷移時に rsp0=0 になり triple fault する。
     unsafe {
-        gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+        crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
     }
 
     // セグメントセレクタを取得。
@@ -500,6 +510,28 @@ pub fn user_illegal_access() {
     }
 }
 
+/// マップされていないアドレスに書き込むテストプログラムの UserProgram を返す。
+///
+/// usertest で、ページフォルトの診断表示が
+/// "user write to non-present page" と分類されることを確かめるのに使う。
+pub fn get_user_illegal_write() -> UserProgram {
+    UserProgram {
+        entry: user_illegal_write,
+        data_regions: &[],
+    }
+}
+
+/// どのページテーブルにもマップされていないアドレスに書き込むテストプログラム。
+///
+/// 0x5000_0000_0000 (80TiB) はユーザー空間（mmap は 1TiB から）にも
+/// カーネルのアイデンティティマッピングにも含まれないので、
+/// P ビットなし・書き込み・Ring 3 のページフォルトになる。
+pub fn user_illegal_write() {
+    unsafe {
+        core::ptr::write_volatile(0x5000_0000_0000 as *mut u8, 0x42);
+    }
+}
+
 // =================================================================
 // ELF バイナリのロードと実行
 // =================================================================
@@ -528,6 +560,17 @@ const ELF_USER_STACK_VADDR: u64 = 0x2000000;
 /// ELF ユーザースタックのサイズ（64KiB = 16ページ）。
 const ELF_USER_STACK_SIZE: usize = 4096 * 16;
 
+/// ユーザースタックの直下に置くガードページのサイズ（1 ページ）。
+/// スタックがここまで伸びるとページフォルトになり、プロセスが終了する。
+/// ガードがないと、あふれたスタックが下にある別のマッピングを黙って壊してしまう。
+const ELF_USER_STACK_GUARD_SIZE: u64 = 4096;
+
+/// addr がユーザースタック直下のガードページに入っているか。
+/// ページフォルトハンドラが「スタックオーバーフロー」と報告するために使う。
+pub fn is_stack_guard_addr(addr: u64) -> bool {
+    (ELF_USER_STACK_VADDR - ELF_USER_STACK_GUARD_SIZE..ELF_USER_STACK_VADDR).contains(&addr)
+}
+
 /// 埋め込み ELF バイナリのデータを返す。
 ///
 /// シェルから ELF パース結果を表示するために使う。
@@ -746,7 +789,7 @@ pub fn create_elf_process(
     }
 
     // 4. ユーザースタック用のフレームを確保してマッピング
-    //    スタックは 0x2000000 (32MiB) に 16KiB 分確保する。
+    //    スタックは 0x2000000 (32MiB) に 64KiB 分確保し、その直下の 1 ページをガードページにする。
     //    スタックは高アドレスから低アドレスに向かって伸びるので、
     //    スタックトップは ELF_USER_STACK_VADDR + ELF_USER_STACK_SIZE。
     // スタックは書き込み可能・実行不可（W^X: NX ビットを設定）
@@ -762,6 +805,12 @@ pub fn create_elf_process(
     // スタックフレームは新規確保なので重複の心配なし
     all_allocated_frames.extend_from_slice(&stack_frames);
 
+    // スタックの直下をガードページにする（どこにもマッピングしない）
+    crate::paging::map_guard_page_in_process(
+        page_table_frame,
+        VirtAddr::new(ELF_USER_STACK_VADDR - ELF_USER_STACK_GUARD_SIZE),
+    );
+
     // 5. ユーザースタック上に argc/argv/envp を配置する
     //    スタックは高アドレスから低アドレスに伸びるので、
     //    文字列データ → ポインタ配列 の順で上位から配置し、
@@ -813,6 +862,14 @@ pub fn create_elf_process(
         kind: crate::vma::VmaKind::UserStack,
         name: String::from("[stack]"),
     });
+    // ガードページも VMA として登録し、/proc/maps で見えるようにする
+    let _ = vma_list.insert(crate::vma::Vma {
+        start: ELF_USER_STACK_VADDR - ELF_USER_STACK_GUARD_SIZE,
+        end: ELF_USER_STACK_VADDR,
+        prot: crate::vma::VmaProt { read: false, write: false, execute: false },
+        kind: crate::vma::VmaKind::Guard,
+        name: String::from("[guard]"),
+    });
 
     // 7. カーネルスタックを確保（プロセスごとに独立）
     let kernel_stack = alloc::vec![0u8; KERNEL_STACK_SIZE].into_boxed_slice();
@@ -821,6 +878,7 @@ pub fn create_elf_process(
         page_table_frame,
         kernel_stack,
         allocated_frames: all_allocated_frames,
+        mmap_frames: 0,
         vma_list,
     };
 
@@ -848,7 +906,7 @@ pub fn run_elf_process(process: &UserProcess, entry_point: u64, user_stack_top:
 
     // TSS rsp0 にカーネルスタックのトップを設定する
     unsafe {
-        gdt::set_tss_rsp0(VirtAddr::new(kernel_stack_top));
+        crate::percpu::set_kernel_stack(VirtAddr::new(kernel_stack_top));
     }
 
     // セグメントセレクタを取得
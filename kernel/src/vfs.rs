@@ -25,12 +25,22 @@
 // MountEntry はファクトリ関数（Box<dyn Fn() -> Box<dyn FileSystem>>）を保持する。
 // resolve() ではファクトリ関数を取得後すぐに VFS の Mutex を解放し、
 // その後 FileSystem インスタンスを生成してメソッドを呼ぶ。
+//
+// ## パス検索キャッシュ（dentry キャッシュ）
+//
+// FAT32 はパスを引くたびにルートから親ディレクトリのクラスタを読み直すので、
+// httpd が同じファイルを何度も返すような使い方では検索が無駄になる。
+// そこで正規化済みの絶対パス → (先頭クラスタ, サイズ, 種類) を DENTRY_CACHE に覚え、
+// read_file() / list_dir() はヒットすればディレクトリを辿らずに読む。
+// 作成・削除・名前変更ではそのパスと配下のエントリを捨てる。
+// 中身が動的に変わる procfs などは cacheable() が false なのでキャッシュを通らない。
 
 use alloc::boxed::Box;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use crate::user_ptr::SyscallError;
@@ -72,6 +82,22 @@ pub enum VfsNodeKind {
     Directory,
 }
 
+/// ファイルシステムの容量（df コマンドで使う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsSpace {
+    /// 全体のバイト数
+    pub total_bytes: u64,
+    /// 空きバイト数
+    pub free_bytes: u64,
+}
+
+impl FsSpace {
+    /// 使用中のバイト数
+    pub fn used_bytes(&self) -> u64 {
+        self.total_bytes.saturating_sub(self.free_bytes)
+    }
+}
+
 /// ディレクトリエントリの情報
 ///
 /// list_dir() で返されるエントリの情報を表す。
@@ -85,6 +111,18 @@ pub struct VfsDirEntry {
     pub size: usize,
 }
 
+/// パス検索の結果（ファイルシステム上のエントリの位置）
+///
+/// dentry キャッシュに覚えておき、次回はパスを辿らずにこの位置から読む。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DentryLocation {
+    /// エントリの先頭クラスタ（FAT32 の場合）
+    pub first_cluster: u32,
+    /// ファイルサイズ（ディレクトリの場合は 0）
+    pub size: u32,
+    pub kind: VfsNodeKind,
+}
+
 /// VFS ノード（ファイルまたはディレクトリ）
 ///
 /// ファイルシステム上の個々のエントリを表す trait。
@@ -122,10 +160,15 @@ pub trait VfsNode: Send + Sync {
 ///
 /// FAT32、procfs など各種ファイルシステムがこの trait を実装する。
 pub trait FileSystem: Send + Sync {
-    /// ファイルシステムの名前を返す（デバッグ用）
-    #[allow(dead_code)]
+    /// ファイルシステムの名前を返す（デバッグ・df 表示用）
     fn name(&self) -> &str;
 
+    /// 容量（全体と空き）を返す。
+    /// 容量の概念がない仮想ファイルシステム（procfs など）は NotSupported のまま。
+    fn space(&self) -> Result<FsSpace, VfsError> {
+        Err(VfsError::NotSupported)
+    }
+
     /// パスで指定されたファイルを開く
     ///
     /// # 引数
@@ -181,6 +224,73 @@ pub trait FileSystem: Send + Sync {
         Err(VfsError::ReadOnly)
     }
 
+    /// 同じファイルシステム内で名前を変える（別のディレクトリへの移動を含む）
+    ///
+    /// データをコピーせずにエントリだけ付け替える。
+    /// 対応していないファイルシステムは NotSupported を返し、呼び出し側がコピーで代用する。
+    fn rename(&self, from: &str, to: &str) -> Result<(), VfsError> {
+        let _ = (from, to);
+        Err(VfsError::NotSupported)
+    }
+
+    /// パス検索の結果を dentry キャッシュに載せてよいか
+    ///
+    /// true にするファイルシステムは lookup() / read_file_at() / list_dir_at() も実装する。
+    /// 内容が動的に変わる procfs や、ホスト側で書き換わる 9p は false のまま。
+    fn cacheable(&self) -> bool {
+        false
+    }
+
+    /// パスを辿ってエントリの位置を返す（dentry キャッシュのミス時に呼ばれる）
+    fn lookup(&self, path: &str) -> Result<DentryLocation, VfsError> {
+        let _ = path;
+        Err(VfsError::NotSupported)
+    }
+
+    /// lookup() で得た位置からファイルの全内容を読む（ディレクトリを辿らない）
+    fn read_file_at(&self, loc: &DentryLocation) -> Result<Vec<u8>, VfsError> {
+        let _ = loc;
+        Err(VfsError::NotSupported)
+    }
+
+    /// lookup() で得た位置のディレクトリを一覧する（ディレクトリを辿らない）
+    fn list_dir_at(&self, loc: &DentryLocation) -> Result<Vec<VfsDirEntry>, VfsError> {
+        let _ = loc;
+        Err(VfsError::NotSupported)
+    }
+
+    /// lookup() で得た位置からファイルを少しずつ読む（ディレクトリを辿らない）
+    fn read_file_chunked_at(
+        &self,
+        loc: &DentryLocation,
+        f: &mut dyn FnMut(&[u8]) -> Result<(), VfsError>,
+    ) -> Result<(), VfsError> {
+        let _ = (loc, f);
+        Err(VfsError::NotSupported)
+    }
+
+    /// ファイルを先頭から少しずつ読み、読めた分ごとに f を呼ぶ
+    ///
+    /// デフォルト実装は open() したノードから 4KiB ずつ読む。
+    /// open() の時点で全体を読み込むファイルシステムでは省メモリにならないので、
+    /// 大きなファイルを置くファイルシステムは固有の実装を持つ。
+    fn read_file_chunked(
+        &self,
+        path: &str,
+        f: &mut dyn FnMut(&[u8]) -> Result<(), VfsError>,
+    ) -> Result<(), VfsError> {
+        let node = self.open(path)?;
+        let mut buf = [0u8; 4096];
+        let mut offset = 0;
+        loop {
+            let n = node.read(offset, &mut buf)?;
+            if n == 0 { break; }
+            f(&buf[..n])?;
+            offset += n;
+        }
+        Ok(())
+    }
+
     /// ファイルの全内容を一括読み取り（効率化用）
     ///
     /// デフォルト実装は open() → read() を繰り返すが、
@@ -274,23 +384,7 @@ impl VfsManager {
     /// # 戻り値
     /// (FileSystem インスタンス, マウントポイント除去後の相対パス)
     fn resolve(&self, normalized_path: &str) -> Result<(Box<dyn FileSystem>, String), VfsError> {
-        // 最長一致: BTreeMap を逆順に走査して最初にマッチするものを見つける
-        let mut best_match: Option<&MountEntry> = None;
-        let mut best_len = 0;
-
-        for (key, entry) in &self.mounts {
-            if normalized_path == key.as_str()
-                || normalized_path.starts_with(&format!("{}/", key))
-                || key == "/"
-            {
-                if key.len() > best_len {
-                    best_len = key.len();
-                    best_match = Some(entry);
-                }
-            }
-        }
-
-        let entry = best_match.ok_or(VfsError::NotFound)?;
+        let entry = self.find_mount(normalized_path).ok_or(VfsError::NotFound)?;
 
         // プレフィックスを除去して相対パスを生成
         let relative = if entry.mount_point == "/" {
@@ -309,6 +403,31 @@ impl VfsManager {
         Ok((fs, String::from(relative)))
     }
 
+    /// normalize_path() 済みの絶対パスがどのマウントに属するかを最長一致で探す
+    fn find_mount(&self, normalized_path: &str) -> Option<&MountEntry> {
+        let mut best_match: Option<&MountEntry> = None;
+        let mut best_len = 0;
+
+        for (key, entry) in &self.mounts {
+            if normalized_path == key.as_str()
+                || normalized_path.starts_with(&format!("{}/", key))
+                || key == "/"
+            {
+                if key.len() > best_len {
+                    best_len = key.len();
+                    best_match = Some(entry);
+                }
+            }
+        }
+
+        best_match
+    }
+
+    /// ルート "/" を含むすべてのマウントポイントを返す（BTreeMap の順 = 辞書順）
+    fn all_mount_points(&self) -> Vec<String> {
+        self.mounts.keys().cloned().collect()
+    }
+
     /// マウントポイントの一覧を返す（ルート "/" を除く）
     fn mount_points(&self) -> Vec<String> {
         self.mounts
@@ -324,6 +443,128 @@ lazy_static! {
     static ref VFS: Mutex<VfsManager> = Mutex::new(VfsManager::new());
 }
 
+// =================================================================
+// パス検索キャッシュ（dentry キャッシュ）
+// =================================================================
+
+/// dentry キャッシュに覚えるエントリ数の上限
+const DENTRY_CACHE_ENTRIES: usize = 64;
+
+/// dentry キャッシュの 1 エントリ
+struct CachedDentry {
+    /// 正規化済みの絶対パス（マウントポイントを含むので、マウントをまたいで衝突しない）
+    path: String,
+    loc: DentryLocation,
+    /// LRU 用の最終使用時刻（DentryCache::clock の値）
+    last_used: u64,
+}
+
+/// 絶対パス → エントリの位置のキャッシュ（LRU で DENTRY_CACHE_ENTRIES 個まで）
+struct DentryCache {
+    entries: Vec<CachedDentry>,
+    clock: u64,
+    /// 無効化のたびに増える世代番号。
+    /// lookup() の最中に削除・名前変更が起きたら、その結果を載せないために使う。
+    generation: u64,
+}
+
+impl DentryCache {
+    const fn new() -> Self {
+        Self { entries: Vec::new(), clock: 0, generation: 0 }
+    }
+
+    fn get(&mut self, path: &str) -> Option<DentryLocation> {
+        self.clock += 1;
+        let clock = self.clock;
+        let entry = self.entries.iter_mut().find(|e| e.path == path)?;
+        entry.last_used = clock;
+        Some(entry.loc)
+    }
+
+    fn insert(&mut self, path: &str, loc: DentryLocation) {
+        self.clock += 1;
+        if self.entries.len() >= DENTRY_CACHE_ENTRIES {
+            // 一番長く使われていないエントリを追い出す
+            if let Some(oldest) = (0..self.entries.len()).min_by_key(|&i| self.entries[i].last_used) {
+                self.entries.swap_remove(oldest);
+            }
+        }
+        self.entries.push(CachedDentry { path: String::from(path), loc, last_used: self.clock });
+    }
+
+    /// path そのものと、その配下のエントリを捨てる。
+    /// FAT32 は大文字小文字を区別しないので、比較も区別しない。
+    fn invalidate(&mut self, path: &str) {
+        self.generation += 1;
+        self.entries.retain(|e| !is_same_or_under(&e.path, path));
+    }
+
+    fn clear(&mut self) {
+        self.generation += 1;
+        self.entries.clear();
+    }
+}
+
+/// path が base そのものか、base 配下のパスか（ASCII の大文字小文字は区別しない）
+fn is_same_or_under(path: &str, base: &str) -> bool {
+    if base == "/" {
+        return true;
+    }
+    let (p, b) = (path.as_bytes(), base.as_bytes());
+    p.len() >= b.len()
+        && p[..b.len()].eq_ignore_ascii_case(b)
+        && (p.len() == b.len() || p[b.len()] == b'/')
+}
+
+static DENTRY_CACHE: Mutex<DentryCache> = Mutex::new(DentryCache::new());
+/// キャッシュにヒットした回数
+static DENTRY_HITS: AtomicU64 = AtomicU64::new(0);
+/// キャッシュに無く、ファイルシステムのパス検索（ディレクトリの走査）をした回数
+static DENTRY_WALKS: AtomicU64 = AtomicU64::new(0);
+
+/// dentry キャッシュの統計
+#[derive(Debug, Clone, Copy)]
+pub struct DentryCacheStats {
+    pub hits: u64,
+    pub walks: u64,
+}
+
+/// dentry キャッシュの統計を返す
+pub fn dentry_cache_stats() -> DentryCacheStats {
+    DentryCacheStats {
+        hits: DENTRY_HITS.load(Ordering::Relaxed),
+        walks: DENTRY_WALKS.load(Ordering::Relaxed),
+    }
+}
+
+/// dentry キャッシュを全部捨てる。
+/// VFS を通さずにディスクを書き換えたとき（SYS_BLOCK_WRITE など）に呼ぶ。
+pub fn invalidate_dentry_cache() {
+    DENTRY_CACHE.lock().clear();
+}
+
+/// normalized のエントリの位置を dentry キャッシュから引き、無ければ fs.lookup() で辿って覚える。
+///
+/// fs.cacheable() が true のときだけ呼ぶこと。
+fn lookup_cached(fs: &dyn FileSystem, normalized: &str, relative: &str) -> Result<DentryLocation, VfsError> {
+    let generation = {
+        let mut cache = DENTRY_CACHE.lock();
+        if let Some(loc) = cache.get(normalized) {
+            DENTRY_HITS.fetch_add(1, Ordering::Relaxed);
+            return Ok(loc);
+        }
+        cache.generation
+    };
+    // ディスクを読む間はキャッシュのロックを持たない
+    DENTRY_WALKS.fetch_add(1, Ordering::Relaxed);
+    let loc = fs.lookup(relative)?;
+    let mut cache = DENTRY_CACHE.lock();
+    if cache.generation == generation {
+        cache.insert(normalized, loc);
+    }
+    Ok(loc)
+}
+
 // =================================================================
 // Public API
 // =================================================================
@@ -334,6 +575,7 @@ lazy_static! {
 /// virtio_blk::init() の後に呼び出すこと。
 pub fn init() {
     let mut vfs = VFS.lock();
+    DENTRY_CACHE.lock().clear();
     vfs.mount("/", Box::new(|| {
         Box::new(crate::fat32::Fat32::new_fs())
     }));
@@ -344,10 +586,11 @@ pub fn init() {
     // 2 台目の virtio-blk デバイスがあれば "/host" にマウントする。
     // QEMU で `-drive if=virtio,format=raw,file=fat:rw:hostfs/` を指定すると
     // ホストのビルドディレクトリが FAT32 として公開される。
-    let dev_count = crate::virtio_blk::device_count();
-    if dev_count >= 2 {
-        vfs.mount("/host", Box::new(|| {
-            Box::new(crate::fat32::Fat32::new_fs_with_index(1))
+    // ディスクは blockdev レジストリの通し番号で指定する。
+    let host_dev = crate::blockdev::find(crate::blockdev::BlockBackend::VirtioBlk(1));
+    if let Some(index) = host_dev {
+        vfs.mount("/host", Box::new(move || {
+            Box::new(crate::fat32::Fat32::new_fs_with_index(index))
         }));
     }
 
@@ -364,40 +607,36 @@ pub fn init() {
     // AHCI デバイスがあれば "/ahci" にマウントする。
     // 実機では onboard SATA ディスクが AHCI 経由で見える。
     // QEMU でも `-device ahci` + `-device ide-hd` でテスト可能。
-    let ahci_count = crate::ahci::device_count();
-    if ahci_count >= 1 {
-        vfs.mount("/ahci", Box::new(|| {
-            Box::new(crate::fat32::Fat32::new_fs_with_backend(
-                crate::fat32::BlockBackend::Ahci(0),
-            ))
+    let ahci_dev = crate::blockdev::find(crate::blockdev::BlockBackend::Ahci(0));
+    if let Some(index) = ahci_dev {
+        vfs.mount("/ahci", Box::new(move || {
+            Box::new(crate::fat32::Fat32::new_fs_with_index(index))
         }));
     }
 
     // NVMe デバイスがあれば "/nvme" にマウントする。
     // 実機では PCIe 接続の NVMe SSD が NVMe ドライバ経由で見える。
     // QEMU でも `-device nvme` でテスト可能。
-    let nvme_count = crate::nvme::device_count();
-    if nvme_count >= 1 {
-        vfs.mount("/nvme", Box::new(|| {
-            Box::new(crate::fat32::Fat32::new_fs_with_backend(
-                crate::fat32::BlockBackend::Nvme(0),
-            ))
+    let nvme_dev = crate::blockdev::find(crate::blockdev::BlockBackend::Nvme(0));
+    if let Some(index) = nvme_dev {
+        vfs.mount("/nvme", Box::new(move || {
+            Box::new(crate::fat32::Fat32::new_fs_with_index(index))
         }));
     }
 
     // 初期化結果をログ出力
     let mut msg = alloc::string::String::from("VFS initialized: / -> fat32, /proc -> procfs");
-    if dev_count >= 2 {
-        msg.push_str(", /host -> fat32[1]");
+    if let Some(index) = host_dev {
+        msg.push_str(&alloc::format!(", /host -> fat32[{}]", index));
     }
     if has_9p {
         msg.push_str(", /9p -> 9p");
     }
-    if ahci_count >= 1 {
-        msg.push_str(", /ahci -> fat32[ahci0]");
+    if let Some(index) = ahci_dev {
+        msg.push_str(&alloc::format!(", /ahci -> fat32[{}]", index));
     }
-    if nvme_count >= 1 {
-        msg.push_str(", /nvme -> fat32[nvme0]");
+    if let Some(index) = nvme_dev {
+        msg.push_str(&alloc::format!(", /nvme -> fat32[{}]", index));
     }
     crate::kprintln!("{}", msg);
 }
@@ -441,7 +680,10 @@ pub fn list_dir(path: &str) -> Result<Vec<VfsDirEntry>, VfsError> {
     };
     drop(vfs); // デッドロック防止
 
-    let mut entries = fs.list_dir(&relative)?;
+    let mut entries = match cached_location(&*fs, &normalized, &relative)? {
+        Some(loc) if loc.kind == VfsNodeKind::Directory => fs.list_dir_at(&loc)?,
+        _ => fs.list_dir(&relative)?,
+    };
 
     // ルートディレクトリの場合はマウントポイントを仮想ディレクトリエントリとして追加
     for mp in mount_points {
@@ -460,6 +702,51 @@ pub fn list_dir(path: &str) -> Result<Vec<VfsDirEntry>, VfsError> {
     Ok(entries)
 }
 
+/// ディレクトリ一覧を HTML で返す（httpd などの Web サーバー向け）
+///
+/// list_dir の結果を「1 行に 1 エントリ、ディレクトリは末尾に "/"」の生の形式にしてから
+/// sabos_textutil::dir_listing_html で整形する。ユーザー空間の httpd も同じ関数を使うので、
+/// カーネルとユーザーで一覧ページの見た目とエスケープの仕方が揃う。
+///
+/// # 引数
+/// - `path`: ディレクトリの絶対パス（ページのタイトルとリンク先にも使う）
+pub fn list_dir_html(path: &str) -> Result<String, VfsError> {
+    let normalized = normalize_path(path)?;
+    let mut raw = String::new();
+    for entry in list_dir(&normalized)? {
+        raw.push_str(&entry.name);
+        if entry.kind == VfsNodeKind::Directory {
+            raw.push('/');
+        }
+        raw.push('\n');
+    }
+    Ok(sabos_textutil::dir_listing_html(&normalized, &raw))
+}
+
+/// マウントされているすべてのマウントポイントを返す（"/" を含む）
+pub fn mounts() -> Vec<String> {
+    VFS.lock().all_mount_points()
+}
+
+/// path を含むファイルシステムの名前と容量を返す
+///
+/// 容量の概念がないファイルシステム（procfs など）では容量を None にする。
+///
+/// # 引数
+/// - `path`: ファイルシステム内の任意のパス（マウントポイントそのものでもよい）
+pub fn fs_space(path: &str) -> Result<(String, Option<FsSpace>), VfsError> {
+    let normalized = normalize_path(path)?;
+    let vfs = VFS.lock();
+    let (fs, _relative) = vfs.resolve(&normalized)?;
+    drop(vfs);
+    let space = match fs.space() {
+        Ok(space) => Some(space),
+        Err(VfsError::NotSupported) => None,
+        Err(e) => return Err(e),
+    };
+    Ok((String::from(fs.name()), space))
+}
+
 /// ファイルを作成する
 ///
 /// # 引数
@@ -470,7 +757,9 @@ pub fn create_file(path: &str, data: &[u8]) -> Result<(), VfsError> {
     let vfs = VFS.lock();
     let (fs, relative) = vfs.resolve(&normalized)?;
     drop(vfs);
-    fs.create_file(&relative, data)
+    let result = fs.create_file(&relative, data);
+    DENTRY_CACHE.lock().invalidate(&normalized);
+    result
 }
 
 /// ファイルを削除する
@@ -482,7 +771,9 @@ pub fn delete_file(path: &str) -> Result<(), VfsError> {
     let vfs = VFS.lock();
     let (fs, relative) = vfs.resolve(&normalized)?;
     drop(vfs);
-    fs.delete_file(&relative)
+    let result = fs.delete_file(&relative);
+    DENTRY_CACHE.lock().invalidate(&normalized);
+    result
 }
 
 /// ディレクトリを作成する
@@ -494,7 +785,9 @@ pub fn create_dir(path: &str) -> Result<(), VfsError> {
     let vfs = VFS.lock();
     let (fs, relative) = vfs.resolve(&normalized)?;
     drop(vfs);
-    fs.create_dir(&relative)
+    let result = fs.create_dir(&relative);
+    DENTRY_CACHE.lock().invalidate(&normalized);
+    result
 }
 
 /// ディレクトリを削除する
@@ -506,7 +799,57 @@ pub fn delete_dir(path: &str) -> Result<(), VfsError> {
     let vfs = VFS.lock();
     let (fs, relative) = vfs.resolve(&normalized)?;
     drop(vfs);
-    fs.delete_dir(&relative)
+    let result = fs.delete_dir(&relative);
+    DENTRY_CACHE.lock().invalidate(&normalized);
+    result
+}
+
+/// remove_dir_all() が辿るディレクトリの深さの上限
+///
+/// FAT32 に循環はないはずだが、壊れたディスクで "." / ".." 以外のエントリが
+/// 親を指していても無限に再帰しないようにする。
+const REMOVE_DIR_ALL_MAX_DEPTH: usize = 32;
+
+/// ディレクトリを中身ごと削除する（rm -r 相当）
+///
+/// 配下のファイルとディレクトリを深さ優先で消してから、path 自身を消す。
+/// ルートとマウントポイントは消せない（PermissionDenied）。
+/// 途中で失敗した場合、それまでに消したエントリは元に戻らない。
+///
+/// # 戻り値
+/// 削除したエントリ（ファイル + ディレクトリ）の数
+pub fn remove_dir_all(path: &str) -> Result<usize, VfsError> {
+    let normalized = normalize_path(path)?;
+    if VFS.lock().all_mount_points().iter().any(|mp| mp.eq_ignore_ascii_case(&normalized)) {
+        return Err(VfsError::PermissionDenied);
+    }
+    if node_kind(&normalized)? != VfsNodeKind::Directory {
+        return Err(VfsError::NotADirectory);
+    }
+    remove_dir_all_at(&normalized, 0)
+}
+
+fn remove_dir_all_at(dir: &str, depth: usize) -> Result<usize, VfsError> {
+    if depth >= REMOVE_DIR_ALL_MAX_DEPTH {
+        return Err(VfsError::InvalidPath);
+    }
+    let mut removed = 0;
+    for entry in list_dir(dir)? {
+        // FAT32 のサブディレクトリには自分と親を指す "." / ".." がある
+        if entry.name == "." || entry.name == ".." {
+            continue;
+        }
+        let child = format!("{}/{}", dir, entry.name);
+        match entry.kind {
+            VfsNodeKind::Directory => removed += remove_dir_all_at(&child, depth + 1)?,
+            VfsNodeKind::File => {
+                delete_file(&child)?;
+                removed += 1;
+            }
+        }
+    }
+    delete_dir(dir)?;
+    Ok(removed + 1)
 }
 
 /// ファイルの全内容を読み取る（便利関数）
@@ -525,7 +868,205 @@ pub fn read_file(path: &str) -> Result<Vec<u8>, VfsError> {
     let vfs = VFS.lock();
     let (fs, relative) = vfs.resolve(&normalized)?;
     drop(vfs); // デッドロック防止
-    fs.read_file(&relative)
+    match cached_location(&*fs, &normalized, &relative)? {
+        Some(loc) if loc.kind == VfsNodeKind::File => fs.read_file_at(&loc),
+        // ディレクトリなどはエラーの返し方をファイルシステムに任せる
+        _ => fs.read_file(&relative),
+    }
+}
+
+/// ファイルを先頭から少しずつ読み、読めた分ごとに f を呼ぶ
+///
+/// read_file() と違ってファイル全体を Vec に置かないので、ヒープより大きいファイルでも
+/// 使うメモリはチャンク 1 つ分で済む（FAT32 では 1 セクタずつ）。
+/// f が Err を返したらそこで読むのをやめ、そのエラーを返す。
+///
+/// # 戻り値
+/// 読んだバイト数の合計
+pub fn read_file_chunked(
+    path: &str,
+    mut f: impl FnMut(&[u8]) -> Result<(), VfsError>,
+) -> Result<usize, VfsError> {
+    let normalized = normalize_path(path)?;
+    let vfs = VFS.lock();
+    let (fs, relative) = vfs.resolve(&normalized)?;
+    drop(vfs); // デッドロック防止
+
+    let mut total = 0;
+    let mut counting = |chunk: &[u8]| {
+        total += chunk.len();
+        f(chunk)
+    };
+    match cached_location(&*fs, &normalized, &relative)? {
+        Some(loc) if loc.kind == VfsNodeKind::File => fs.read_file_chunked_at(&loc, &mut counting)?,
+        _ => fs.read_file_chunked(&relative, &mut counting)?,
+    }
+    Ok(total)
+}
+
+/// cacheable なファイルシステムなら dentry キャッシュ経由でエントリの位置を返す。
+///
+/// キャッシュを使わない場合（procfs など、またはマウントのルート）は None。
+/// lookup() が NotFound 以外で失敗したときは、通常のパスで読ませるため None にする。
+fn cached_location(fs: &dyn FileSystem, normalized: &str, relative: &str) -> Result<Option<DentryLocation>, VfsError> {
+    if !fs.cacheable() || relative.is_empty() {
+        return Ok(None);
+    }
+    match lookup_cached(fs, normalized, relative) {
+        Ok(loc) => Ok(Some(loc)),
+        Err(VfsError::NotFound) => Err(VfsError::NotFound),
+        Err(_) => Ok(None),
+    }
+}
+
+/// パスが指すノードの種類を返す
+///
+/// FAT32 の open() はディレクトリを開けないので、親ディレクトリの一覧から
+/// エントリを探して種類を判定する。ルートとマウントポイントはディレクトリ。
+///
+/// # 引数
+/// - `path`: 絶対パス
+pub fn node_kind(path: &str) -> Result<VfsNodeKind, VfsError> {
+    let normalized = normalize_path(path)?;
+    if normalized == "/" {
+        return Ok(VfsNodeKind::Directory);
+    }
+    parent_entry(&normalized).map(|e| e.kind)
+}
+
+/// ファイルのサイズを返す（中身は読まない）
+///
+/// 読み込む前に大きすぎないか確かめるのに使う。
+/// procfs のように内容を読むまでサイズが決まらないファイルは 0 になる。
+pub fn file_size(path: &str) -> Result<usize, VfsError> {
+    let entry = parent_entry(&normalize_path(path)?)?;
+    if entry.kind != VfsNodeKind::File {
+        return Err(VfsError::NotAFile);
+    }
+    Ok(entry.size)
+}
+
+/// 正規化済みのパスのエントリを、親ディレクトリの一覧から探す
+fn parent_entry(normalized: &str) -> Result<VfsDirEntry, VfsError> {
+    let (parent, name) = normalized.rsplit_once('/').ok_or(VfsError::InvalidPath)?;
+    let parent = if parent.is_empty() { "/" } else { parent };
+    list_dir(parent)?
+        .into_iter()
+        .find(|e| e.name.eq_ignore_ascii_case(name))
+        .ok_or(VfsError::NotFound)
+}
+
+/// コピー・移動先のパスを決める
+///
+/// dst が既存のディレクトリなら、その中に src と同じ名前で置く（cp FILE DIR と同じ）。
+/// 決まった先が既存のディレクトリなら上書きできないので NotAFile を返す。
+///
+/// # 戻り値
+/// 正規化済みのコピー先パス
+pub fn destination_path(src: &str, dst: &str) -> Result<String, VfsError> {
+    let src = normalize_path(src)?;
+    let mut dest = normalize_path(dst)?;
+    if node_kind(&dest) == Ok(VfsNodeKind::Directory) {
+        let basename = src.rsplit('/').next().unwrap_or("");
+        if basename.is_empty() {
+            return Err(VfsError::InvalidPath);
+        }
+        if dest != "/" {
+            dest.push('/');
+        }
+        dest.push_str(basename);
+        if node_kind(&dest) == Ok(VfsNodeKind::Directory) {
+            return Err(VfsError::NotAFile);
+        }
+    }
+    Ok(dest)
+}
+
+/// ファイルをコピーする
+///
+/// dst が既存のディレクトリならその中に同じ名前でコピーする。
+/// dst に既存のファイルがあれば上書きする（ディレクトリは上書きしない）。
+///
+/// FAT32 ドライバはファイルを一括でしか作れない（create_file にデータ全体を渡す）ため、
+/// 途中を小分けに書くことはできない。read_file() で読んだバッファをそのまま
+/// create_file() に渡し、中間のコピーを作らないことでメモリ使用量をファイル 1 個分に抑える。
+///
+/// # 戻り値
+/// (実際のコピー先パス, コピーしたバイト数)
+pub fn copy_file(src: &str, dst: &str) -> Result<(String, usize), VfsError> {
+    if node_kind(src)? != VfsNodeKind::File {
+        return Err(VfsError::NotAFile);
+    }
+    let dest = destination_path(src, dst)?;
+    // 自分自身への上書きは、消した時点で元データが失われるので拒否する
+    if normalize_path(src)?.eq_ignore_ascii_case(&dest) {
+        return Err(VfsError::AlreadyExists);
+    }
+
+    let data = read_file(src)?;
+    if node_kind(&dest) == Ok(VfsNodeKind::File) {
+        delete_file(&dest)?;
+    }
+    create_file(&dest, &data)?;
+    Ok((dest, data.len()))
+}
+
+/// 名前を変える（同じマウント内の移動）
+///
+/// src と dst が別のマウントにあるときは、エントリの付け替えでは済まないので
+/// NotSupported を返す（move_path() がコピー + 削除で代用する）。
+pub fn rename(src: &str, dst: &str) -> Result<(), VfsError> {
+    let src = normalize_path(src)?;
+    let dst = normalize_path(dst)?;
+    let vfs = VFS.lock();
+    let same_mount = match (vfs.find_mount(&src), vfs.find_mount(&dst)) {
+        (Some(a), Some(b)) => a.mount_point == b.mount_point,
+        _ => return Err(VfsError::NotFound),
+    };
+    if !same_mount {
+        return Err(VfsError::NotSupported);
+    }
+    let (fs, src_relative) = vfs.resolve(&src)?;
+    let (_, dst_relative) = vfs.resolve(&dst)?;
+    drop(vfs);
+    let result = fs.rename(&src_relative, &dst_relative);
+    let mut cache = DENTRY_CACHE.lock();
+    cache.invalidate(&src);
+    cache.invalidate(&dst);
+    result
+}
+
+/// ファイル・ディレクトリを移動する（mv 相当）
+///
+/// dst が既存のディレクトリならその中に同じ名前で移す。
+/// まず rename() でエントリの付け替えを試み、マウントをまたぐなどで
+/// できなければファイルに限りコピーしてから元を消す。
+/// ディレクトリを既存のファイルの上に移すことはできない。
+///
+/// # 戻り値
+/// 実際の移動先パス
+pub fn move_path(src: &str, dst: &str) -> Result<String, VfsError> {
+    let src_kind = node_kind(src)?;
+    let dest = destination_path(src, dst)?;
+    if normalize_path(src)?.eq_ignore_ascii_case(&dest) {
+        return Err(VfsError::AlreadyExists);
+    }
+    match (src_kind, node_kind(&dest)) {
+        (VfsNodeKind::Directory, Ok(VfsNodeKind::File)) => return Err(VfsError::NotADirectory),
+        // 既存ファイルへの移動は上書き
+        (VfsNodeKind::File, Ok(VfsNodeKind::File)) => delete_file(&dest)?,
+        _ => {}
+    }
+
+    match rename(src, &dest) {
+        Ok(()) => Ok(dest),
+        Err(VfsError::NotSupported) if src_kind == VfsNodeKind::File => {
+            copy_file(src, &dest)?;
+            delete_file(src)?;
+            Ok(dest)
+        }
+        Err(e) => Err(e),
+    }
 }
 
 /// VfsError を SyscallError に変換するヘルパー
@@ -25,6 +25,17 @@
 //   0x13    1     ISR Status            (割り込みステータス)
 //   0x14+   ?     Device-Specific Config (デバイス固有の設定、block は capacity 等)
 //
+// MSI-X を有効にすると 0x14 に Config MSI-X Vector、0x16 に Queue MSI-X Vector が入り、
+// Device-Specific Config は 0x18 からにずれる。
+//
+// ## 割り込み (MSI-X)
+//
+// APIC モードでデバイスが MSI-X を持っていれば、requestq の完了で
+// MSI-X ベクタ (InterruptIndex::VirtioBlkMsix) が発火する。
+// 完了の検出自体は今もポーリングで行う（VFS 経由の I/O は割り込み禁止中にも
+// 呼ばれうるので、割り込み待ちに切り替えると止まってしまう）。
+// 割り込みは完了の通知と統計（msix_irq_count）に使う。
+//
 // ## Virtqueue (Split Virtqueue)
 //
 // virtio の I/O は Virtqueue（仮想キュー）を通じて行われる。
@@ -59,7 +70,7 @@ use alloc::vec::Vec;
 use crate::pci;
 use crate::serial_println;
 use core::alloc::Layout;
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::{fence, AtomicU64, Ordering};
 use spin::Mutex;
 use x86_64::instructions::port::Port;
 
@@ -69,6 +80,21 @@ use x86_64::instructions::port::Port;
 /// インデックス 0 が最初に発見されたデバイス（通常は disk.img）。
 pub static VIRTIO_BLKS: Mutex<Vec<VirtioBlk>> = Mutex::new(Vec::new());
 
+/// MSI-X 割り込みを受けた回数（全 virtio-blk デバイスの合計）
+static MSIX_IRQ_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// これまでに受けた MSI-X 割り込みの回数
+pub fn msix_irq_count() -> u64 {
+    MSIX_IRQ_COUNT.load(Ordering::Relaxed)
+}
+
+/// MSI-X 割り込みハンドラ本体（interrupts.rs から呼ばれる）。
+/// 今は数えるだけで、待っている側は起こさない（完了はリクエスト発行側がポーリングで拾う）。
+/// 割り込みで待ち手を起こすのは TODO.md の「MSI-X / INTx の残り」を参照。
+pub fn handle_msix_interrupt() {
+    MSIX_IRQ_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
 /// virtio-blk ドライバを初期化する。
 /// PCI バスから全ての virtio-blk デバイスを探して初期化する。
 pub fn init() {
@@ -107,6 +133,13 @@ const VIRTIO_STATUS_DRIVER_OK: u8 = 4;
 /// 何か致命的なエラーが発生した
 const _VIRTIO_STATUS_FAILED: u8 = 128;
 
+/// MSI-X ベクタを割り当てない（legacy の VIRTIO_MSI_NO_VECTOR）
+const VIRTIO_MSI_NO_VECTOR: u16 = 0xFFFF;
+/// MSI-X 有効時の Config MSI-X Vector レジスタ
+const VIRTIO_MSI_CONFIG_VECTOR: u16 = 0x14;
+/// MSI-X 有効時の Queue MSI-X Vector レジスタ（Queue Select で選んだキューに効く）
+const VIRTIO_MSI_QUEUE_VECTOR: u16 = 0x16;
+
 // ============================================================
 // Virtqueue ディスクリプタのフラグ
 // ============================================================
@@ -172,6 +205,8 @@ pub struct VirtioBlk {
     last_used_idx: u16,
     /// デバイスのブロック数（容量）
     capacity: u64,
+    /// requestq の完了を MSI-X で通知しているか
+    msix: bool,
 }
 
 // VirtioBlk は raw pointer を含むが、Mutex で保護されるため Send/Sync は安全
@@ -232,11 +267,27 @@ impl VirtioBlk {
             Port::<u32>::new(io_base + 0x04).write(0);
         }
 
+        // MSI-X が使えれば有効化する
+        let mut msix = Self::enable_msix(&dev, io_base);
+
         // 5. Virtqueue 0 のセットアップ
         // Queue Select = 0（virtio-blk は requestq が queue 0）
         unsafe {
             Port::<u16>::new(io_base + 0x0E).write(0);
         }
+        if let Some(cap) = msix {
+            // requestq を MSI-X テーブルのエントリ 0 に割り当てる。
+            // 読み返して NO_VECTOR ならデバイスが割り当てを拒否したので無効化する。
+            let assigned = unsafe {
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).write(0);
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).read()
+            };
+            if assigned == VIRTIO_MSI_NO_VECTOR {
+                serial_println!("virtio-blk: MSI-X vector rejected, falling back to polling");
+                pci::disable_msix(dev.bus, dev.device, dev.function, &cap);
+                msix = None;
+            }
+        }
 
         // Queue Size を読む（デバイスが決定した固定値、通常 256 や 128）
         let queue_size = unsafe { Port::<u16>::new(io_base + 0x0C).read() };
@@ -298,10 +349,11 @@ impl VirtioBlk {
         }
 
         // デバイス固有設定: capacity (セクタ数) を読む
-        // virtio-blk の device config は offset 0x14 から始まる。
-        // capacity は 64 ビット値で offset 0x14 (下位 32 ビット) + 0x18 (上位 32 ビット)。
-        let capacity_lo = unsafe { Port::<u32>::new(io_base + 0x14).read() } as u64;
-        let capacity_hi = unsafe { Port::<u32>::new(io_base + 0x18).read() } as u64;
+        // virtio-blk の device config は offset 0x14（MSI-X 有効時は 0x18）から始まる。
+        // capacity は 64 ビット値で先頭 4 バイトが下位 32 ビット、次の 4 バイトが上位 32 ビット。
+        let device_config = if msix.is_some() { 0x18 } else { 0x14 };
+        let capacity_lo = unsafe { Port::<u32>::new(io_base + device_config).read() } as u64;
+        let capacity_hi = unsafe { Port::<u32>::new(io_base + device_config + 4).read() } as u64;
         let capacity = capacity_lo | (capacity_hi << 32);
         serial_println!("virtio-blk capacity: {} sectors ({} MiB)", capacity, capacity * 512 / 1024 / 1024);
 
@@ -315,9 +367,29 @@ impl VirtioBlk {
             next_desc: 0,
             last_used_idx: 0,
             capacity,
+            msix: msix.is_some(),
         })
     }
 
+    /// MSI-X を有効化してテーブルのエントリ 0 を VirtioBlkMsix ベクタに向ける。
+    ///
+    /// APIC が無効、デバイスに MSI-X がない、テーブルの設定に失敗した場合は None
+    /// （呼び出し元はポーリングのみで動く）。
+    fn enable_msix(dev: &pci::PciDevice, io_base: u16) -> Option<pci::MsixCapability> {
+        let address = crate::apic::msi_address()?;
+        let cap = pci::find_msix(dev.bus, dev.device, dev.function)?;
+        let vector = crate::interrupts::InterruptIndex::VirtioBlkMsix.as_u8();
+        if let Err(e) = pci::enable_msix(dev.bus, dev.device, dev.function, &cap, address, &[(0, vector)]) {
+            serial_println!("virtio-blk: {}", e);
+            return None;
+        }
+        // 設定変更通知は使わない
+        unsafe {
+            Port::<u16>::new(io_base + VIRTIO_MSI_CONFIG_VECTOR).write(VIRTIO_MSI_NO_VECTOR);
+        }
+        Some(cap)
+    }
+
     /// 指定セクタからデータを読み取る。
     ///
     /// sector: 読み取り開始セクタ番号（0始まり）
@@ -643,6 +715,11 @@ impl VirtioBlk {
     pub fn capacity(&self) -> u64 {
         self.capacity
     }
+
+    /// requestq の完了を MSI-X で通知しているかを返す。
+    pub fn uses_msix(&self) -> bool {
+        self.msix
+    }
 }
 
 /// 値を alignment の倍数に切り上げる。
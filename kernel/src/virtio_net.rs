@@ -20,18 +20,61 @@
 //   - csum_offset (2 bytes): チェックサム書き込み位置
 //
 // 今回は GSO やチェックサムオフロードは使わないので、すべて 0 で良い。
-
+//
+// ## 割り込み (MSI-X)
+//
+// APIC モードでデバイスが MSI-X を持っていれば、receiveq の完了を
+// MSI-X ベクタ (InterruptIndex::VirtioNetMsix) で受け取り、net_poller を起こす。
+// MSI-X が使えなければ従来どおり net_poller が定期的にポーリングする
+// （INTx は I/O APIC にルーティングしていないので実質ポーリング。TODO.md の「MSI-X / INTx の残り」）。
+//
+// legacy インターフェースでは MSI-X を有効にすると BAR0 のレイアウトが変わる:
+//   0x14: Config MSI-X Vector, 0x16: Queue MSI-X Vector,
+//   デバイス固有設定 (MAC) は 0x14 → 0x18 にずれる。
+//
+// ## 受信リングのあふれ
+//
+// net_poller が走れない間に受信バッファがすべて埋まると、デバイスは
+// 置き場のないフレームを黙って捨てる（legacy virtio-net には破棄数を
+// 知らせる仕組みがない）。そこで used リングに全バッファ分が溜まっていたら
+// 「あふれ」として数え、取り出し終わったら recover_rx() で全バッファが
+// 再登録されているかを確かめて receiveq を通知し直す。
+
+use crate::lock_order::{LockClass, OrderedMutex};
+use crate::netstack::NicRxStats;
 use crate::pci;
 use crate::serial_println;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::alloc::Layout;
-use core::sync::atomic::{fence, Ordering};
-use spin::Mutex;
+use core::sync::atomic::{fence, AtomicBool, AtomicU64, Ordering};
 use x86_64::instructions::port::Port;
 
 /// グローバルな virtio-net ドライバインスタンス。
-pub static VIRTIO_NET: Mutex<Option<VirtioNet>> = Mutex::new(None);
+pub static VIRTIO_NET: OrderedMutex<Option<VirtioNet>> = OrderedMutex::new(LockClass::VirtioNet, None);
+
+/// receiveq の完了を MSI-X 割り込みで受け取っているか
+static MSIX_ENABLED: AtomicBool = AtomicBool::new(false);
+/// MSI-X 割り込みを受けた回数
+static MSIX_IRQ_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// receiveq の完了を MSI-X 割り込みで受け取っているかを返す。
+/// false なら net_poller はポーリングで受信を確認する。
+pub fn uses_msix() -> bool {
+    MSIX_ENABLED.load(Ordering::Relaxed)
+}
+
+/// これまでに受けた MSI-X 割り込みの回数
+pub fn msix_irq_count() -> u64 {
+    MSIX_IRQ_COUNT.load(Ordering::Relaxed)
+}
+
+/// MSI-X 割り込みハンドラ本体（interrupts.rs から呼ばれる）。
+/// ドライバのロックは取らず、net_poller に受信を知らせるだけ。
+pub fn handle_msix_interrupt() {
+    MSIX_IRQ_COUNT.fetch_add(1, Ordering::Relaxed);
+    crate::netstack::notify_rx_interrupt();
+}
 
 /// virtio-net ドライバを初期化する。
 pub fn init() {
@@ -53,6 +96,13 @@ const VIRTIO_STATUS_DRIVER: u8 = 2;
 const VIRTIO_STATUS_DRIVER_OK: u8 = 4;
 const _VIRTIO_STATUS_FEATURES_OK: u8 = 8;
 
+/// MSI-X ベクタを割り当てない（legacy の VIRTIO_MSI_NO_VECTOR）
+const VIRTIO_MSI_NO_VECTOR: u16 = 0xFFFF;
+/// MSI-X 有効時の Config MSI-X Vector レジスタ
+const VIRTIO_MSI_CONFIG_VECTOR: u16 = 0x14;
+/// MSI-X 有効時の Queue MSI-X Vector レジスタ（Queue Select で選んだキューに効く）
+const VIRTIO_MSI_QUEUE_VECTOR: u16 = 0x16;
+
 // ============================================================
 // Virtqueue ディスクリプタのフラグ
 // ============================================================
@@ -103,8 +153,10 @@ impl VirtioNetHeader {
 
 /// 受信バッファのサイズ (MTU 1500 + Ethernet ヘッダー + 余裕)
 const RX_BUFFER_SIZE: usize = 2048;
-/// 受信バッファの数
+/// 受信バッファの数（rx_posted のビットマスクに収まること）
 const RX_BUFFER_COUNT: usize = 16;
+/// 全受信バッファがデバイスに渡っているときの rx_posted
+const RX_POSTED_ALL: u32 = (1 << RX_BUFFER_COUNT) - 1;
 
 /// virtio-net ドライバ
 pub struct VirtioNet {
@@ -126,6 +178,12 @@ pub struct VirtioNet {
     tx_next_desc: u16,
     /// transmitq の last_used_idx
     tx_last_used_idx: u16,
+    /// デバイスに渡している受信バッファのビットマスク（bit i = バッファ i）
+    rx_posted: u32,
+    /// 受信バッファがすべて埋まった状態を検出し、まだ回復していないか
+    rx_ring_exhausted: bool,
+    /// 受信統計
+    rx_stats: NicRxStats,
     /// MAC アドレス
     pub mac_address: [u8; 6],
 }
@@ -181,10 +239,26 @@ impl VirtioNet {
             Port::<u32>::new(io_base + 0x04).write(guest_features);
         }
 
+        // MSI-X が使えれば有効化する（キューへのベクタ割り当ては各キューのセットアップで行う）
+        let mut msix = Self::enable_msix(&dev, io_base);
+
         // ---- Virtqueue 0 (receiveq) のセットアップ ----
         unsafe {
             Port::<u16>::new(io_base + 0x0E).write(0);
         }
+        if let Some(cap) = msix {
+            // receiveq を MSI-X テーブルのエントリ 0 に割り当てる。
+            // 読み返して NO_VECTOR ならデバイスが割り当てを拒否したのでポーリングに戻す。
+            let assigned = unsafe {
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).write(0);
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).read()
+            };
+            if assigned == VIRTIO_MSI_NO_VECTOR {
+                serial_println!("virtio-net: MSI-X vector rejected, falling back to polling");
+                pci::disable_msix(dev.bus, dev.device, dev.function, &cap);
+                msix = None;
+            }
+        }
         let queue_size = unsafe { Port::<u16>::new(io_base + 0x0C).read() };
         serial_println!("virtio-net receiveq size: {}", queue_size);
 
@@ -204,6 +278,10 @@ impl VirtioNet {
         // ---- Virtqueue 1 (transmitq) のセットアップ ----
         unsafe {
             Port::<u16>::new(io_base + 0x0E).write(1);
+            // transmitq の完了は send_packet() 内で待つので割り込みは不要
+            if msix.is_some() {
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).write(VIRTIO_MSI_NO_VECTOR);
+            }
         }
         let tx_queue_size = unsafe { Port::<u16>::new(io_base + 0x0C).read() };
         serial_println!("virtio-net transmitq size: {}", tx_queue_size);
@@ -223,12 +301,13 @@ impl VirtioNet {
             );
         }
 
-        // MAC アドレスを読み取る (device config は offset 0x14 から)
+        // MAC アドレスを読み取る (device config は offset 0x14、MSI-X 有効時は 0x18 から)
         // virtio-net の device config: MAC アドレスが最初の 6 バイト
+        let device_config = if msix.is_some() { 0x18 } else { 0x14 };
         let mut mac_address = [0u8; 6];
         if has_mac {
             for i in 0..6 {
-                mac_address[i] = unsafe { Port::<u8>::new(io_base + 0x14 + i as u16).read() };
+                mac_address[i] = unsafe { Port::<u8>::new(io_base + device_config + i as u16).read() };
             }
             serial_println!(
                 "virtio-net MAC: {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
@@ -257,23 +336,52 @@ impl VirtioNet {
             rx_last_used_idx: 0,
             tx_next_desc: 0,
             tx_last_used_idx: 0,
+            rx_posted: 0,
+            rx_ring_exhausted: false,
+            rx_stats: NicRxStats::default(),
             mac_address,
         };
 
         // 受信バッファを receiveq に登録
         driver.fill_rx_queue();
 
+        MSIX_ENABLED.store(msix.is_some(), Ordering::Relaxed);
+        serial_println!("virtio-net interrupt mode: {}", if msix.is_some() { "MSI-X" } else { "polling" });
+
         Some(driver)
     }
 
-    /// Virtqueue 用のページアラインメモリを確保
-    fn allocate_virtqueue(queue_size: u16) -> Option<*mut u8> {
+    /// MSI-X を有効化してテーブルのエントリ 0 を VirtioNetMsix ベクタに向ける。
+    ///
+    /// APIC が無効、デバイスに MSI-X がない、テーブルの設定に失敗した場合は None
+    /// （呼び出し元はポーリングで動く）。
+    fn enable_msix(dev: &pci::PciDevice, io_base: u16) -> Option<pci::MsixCapability> {
+        let address = crate::apic::msi_address()?;
+        let cap = pci::find_msix(dev.bus, dev.device, dev.function)?;
+        let vector = crate::interrupts::InterruptIndex::VirtioNetMsix.as_u8();
+        if let Err(e) = pci::enable_msix(dev.bus, dev.device, dev.function, &cap, address, &[(0, vector)]) {
+            serial_println!("virtio-net: {}", e);
+            return None;
+        }
+        // 設定変更通知は使わない
+        unsafe {
+            Port::<u16>::new(io_base + VIRTIO_MSI_CONFIG_VECTOR).write(VIRTIO_MSI_NO_VECTOR);
+        }
+        Some(cap)
+    }
+
+    /// Virtqueue 1 本分のメモリの大きさ（ページ単位に切り上げ）
+    fn virtqueue_bytes(queue_size: u16) -> usize {
         let desc_size = (queue_size as usize) * 16;
         let avail_size = 4 + (queue_size as usize) * 2;
         let used_offset = align_up(desc_size + avail_size, 4096);
         let used_size = 4 + (queue_size as usize) * 8;
-        let total_size = align_up(used_offset + used_size, 4096);
+        align_up(used_offset + used_size, 4096)
+    }
 
+    /// Virtqueue 用のページアラインメモリを確保
+    fn allocate_virtqueue(queue_size: u16) -> Option<*mut u8> {
+        let total_size = Self::virtqueue_bytes(queue_size);
         let layout = Layout::from_size_align(total_size, 4096).ok()?;
         let ptr = unsafe { alloc::alloc::alloc_zeroed(layout) };
         if ptr.is_null() {
@@ -296,17 +404,12 @@ impl VirtioNet {
     }
 
     /// 受信バッファを receiveq に追加
+    ///
+    /// ディスクリプタ i が受信バッファ i を指す（receive_packet はこの対応を前提にする）。
     fn fill_rx_queue(&mut self) {
-        for i in 0..RX_BUFFER_COUNT {
-            let buf_addr = unsafe { self.rx_buffers.add(i * RX_BUFFER_SIZE) } as u64;
+        for _ in 0..RX_BUFFER_COUNT {
             let desc_idx = self.rx_next_desc;
-
-            // ディスクリプタを設定 (デバイスが書き込む = WRITE フラグ)
-            self.write_rx_desc(desc_idx, buf_addr, RX_BUFFER_SIZE as u32, VIRTQ_DESC_F_WRITE, 0);
-
-            // Available Ring に追加
-            self.add_to_rx_avail(desc_idx);
-
+            self.post_rx_buffer(desc_idx);
             self.rx_next_desc = (self.rx_next_desc + 1) % self.queue_size;
         }
 
@@ -314,6 +417,17 @@ impl VirtioNet {
         self.notify_rx();
     }
 
+    /// 受信バッファ 1 つ分のディスクリプタを書いて Available Ring に載せる
+    fn post_rx_buffer(&mut self, desc_idx: u16) {
+        let buf_idx = desc_idx as usize % RX_BUFFER_COUNT;
+        let buf_addr = unsafe { self.rx_buffers.add(buf_idx * RX_BUFFER_SIZE) } as u64;
+
+        // ディスクリプタを設定 (デバイスが書き込む = WRITE フラグ)
+        self.write_rx_desc(desc_idx, buf_addr, RX_BUFFER_SIZE as u32, VIRTQ_DESC_F_WRITE, 0);
+        self.add_to_rx_avail(desc_idx);
+        self.rx_posted |= 1 << buf_idx;
+    }
+
     /// receiveq のディスクリプタを書き込む
     fn write_rx_desc(&self, idx: u16, addr: u64, len: u32, flags: u16, next: u16) {
         let offset = (idx as usize) * 16;
@@ -473,6 +587,13 @@ impl VirtioNet {
         if used_idx == self.rx_last_used_idx {
             return None; // 新しいパケットなし
         }
+        // 未処理の完了が全バッファ分あれば、デバイスは置き場を失っている
+        // （この間に届いたフレームは捨てられている）。1 回のあふれとして数える。
+        let pending = used_idx.wrapping_sub(self.rx_last_used_idx) as usize;
+        if pending >= RX_BUFFER_COUNT && !self.rx_ring_exhausted {
+            self.rx_ring_exhausted = true;
+            self.rx_stats.overflows += 1;
+        }
         // Used Ring からエントリを取得
         let ring_entry_idx = (self.rx_last_used_idx % self.queue_size) as usize;
         let used_elem_ptr = unsafe { used_ptr.add(4 + ring_entry_idx * 8) };
@@ -485,26 +606,106 @@ impl VirtioNet {
         // desc_id から受信バッファのインデックスを計算
         let buf_idx = desc_id as usize % RX_BUFFER_COUNT;
         let buf_ptr = unsafe { self.rx_buffers.add(buf_idx * RX_BUFFER_SIZE) };
+        self.rx_posted &= !(1 << buf_idx);
 
+        let written_len = written_len.min(RX_BUFFER_SIZE);
         let mut data = vec![0u8; written_len];
         unsafe {
             core::ptr::copy_nonoverlapping(buf_ptr, data.as_mut_ptr(), written_len);
         }
 
         // バッファを再度 receiveq に追加
-        let buf_addr = buf_ptr as u64;
-        self.write_rx_desc(desc_id, buf_addr, RX_BUFFER_SIZE as u32, VIRTQ_DESC_F_WRITE, 0);
-        self.add_to_rx_avail(desc_id);
+        self.post_rx_buffer(desc_id);
         self.notify_rx();
 
         // virtio-net ヘッダーをスキップして Ethernet フレームを返す
         let header_size = core::mem::size_of::<VirtioNetHeader>();
         if data.len() > header_size {
+            self.rx_stats.packets += 1;
             Some(data[header_size..].to_vec())
         } else {
+            self.rx_stats.dropped += 1;
             None
         }
     }
+
+    /// 受信統計を返す
+    ///
+    /// dropped はドライバが捨てたフレーム（短すぎるもの）だけを数える。
+    /// リングがあふれている間にデバイスが捨てたフレームは数えられないので、
+    /// その目安は overflows で見る。
+    pub fn rx_stats(&self) -> NicRxStats {
+        self.rx_stats
+    }
+
+    /// あふれた受信リングを立て直す
+    ///
+    /// 受信キューを取り出し終えた後（receive_packet が None を返した後）に呼ぶ。
+    /// あふれを検出していた場合や、デバイスに渡していない受信バッファが
+    /// 残っていた場合に、全バッファを登録し直して receiveq を通知する。
+    /// 回復処理を行ったら true を返す。
+    pub fn recover_rx(&mut self) -> bool {
+        if !self.rx_ring_exhausted && self.rx_posted == RX_POSTED_ALL {
+            return false;
+        }
+        for buf_idx in 0..RX_BUFFER_COUNT {
+            if self.rx_posted & (1 << buf_idx) == 0 {
+                self.post_rx_buffer(buf_idx as u16);
+            }
+        }
+        self.notify_rx();
+        self.rx_ring_exhausted = false;
+        self.rx_stats.recoveries += 1;
+        true
+    }
+
+    /// デバイスをリセットして receiveq / transmitq を最初から作り直す
+    ///
+    /// net_poller が止まったときにウォッチドッグから呼ぶ。確保済みの Virtqueue と
+    /// 受信バッファはそのまま使い回し、中身だけ初期化して登録し直す。
+    /// MAC アドレスはドライバが覚えている値をそのまま使うので変わらない。
+    pub fn reset_rings(&mut self) {
+        let io_base = self.io_base;
+        let msix = uses_msix();
+        let vq_bytes = Self::virtqueue_bytes(self.queue_size);
+        unsafe {
+            let mut status = Port::<u8>::new(io_base + 0x12);
+            // リセットすると Virtqueue のアドレスや MSI-X ベクタの割り当ても消えるので、
+            // new() と同じ手順で設定し直す
+            status.write(0);
+            status.write(VIRTIO_STATUS_ACKNOWLEDGE);
+            status.write(VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER);
+            let device_features = Port::<u32>::new(io_base).read();
+            Port::<u32>::new(io_base + 0x04).write(device_features & VIRTIO_NET_F_MAC as u32);
+            if msix {
+                Port::<u16>::new(io_base + VIRTIO_MSI_CONFIG_VECTOR).write(VIRTIO_MSI_NO_VECTOR);
+            }
+
+            core::ptr::write_bytes(self.rx_vq_ptr, 0, vq_bytes);
+            Port::<u16>::new(io_base + 0x0E).write(0);
+            if msix {
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).write(0);
+            }
+            Port::<u32>::new(io_base + 0x08).write((self.rx_vq_ptr as u64 / 4096) as u32);
+
+            core::ptr::write_bytes(self.tx_vq_ptr, 0, vq_bytes);
+            Port::<u16>::new(io_base + 0x0E).write(1);
+            if msix {
+                Port::<u16>::new(io_base + VIRTIO_MSI_QUEUE_VECTOR).write(VIRTIO_MSI_NO_VECTOR);
+            }
+            Port::<u32>::new(io_base + 0x08).write((self.tx_vq_ptr as u64 / 4096) as u32);
+
+            status.write(VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER | VIRTIO_STATUS_DRIVER_OK);
+        }
+
+        self.rx_next_desc = 0;
+        self.rx_last_used_idx = 0;
+        self.tx_next_desc = 0;
+        self.tx_last_used_idx = 0;
+        self.rx_posted = 0;
+        self.rx_ring_exhausted = false;
+        self.fill_rx_queue();
+    }
 }
 
 fn align_up(value: usize, alignment: usize) -> usize {
@@ -22,6 +22,10 @@ pub enum VmaKind {
     ElfLoad,
     /// ユーザースタック
     UserStack,
+    /// ファイルマッピング（mmap の MMAP_FLAG_FILE。マッピング時にファイルの内容をコピー済み）
+    File,
+    /// ガードページ（ユーザースタックの直下。マッピングされておらず、触るとページフォルト）
+    Guard,
 }
 
 /// VMA のアクセス権限
@@ -141,6 +145,61 @@ impl VmaList {
         Ok(())
     }
 
+    /// [start, end) がどの VMA とも重なっていないか（MAP_FIXED の判定用）
+    pub fn is_range_free(&self, start: u64, end: u64) -> bool {
+        self.vmas.iter().all(|v| v.end <= start || end <= v.start)
+    }
+
+    /// [start, end) が隙間なく VMA で覆われているか（mprotect の対象確認用）
+    pub fn covers(&self, start: u64, end: u64) -> bool {
+        let mut pos = start;
+        for vma in &self.vmas {
+            if vma.end <= pos {
+                continue;
+            }
+            if vma.start > pos {
+                return false;
+            }
+            pos = vma.end;
+            if pos >= end {
+                return true;
+            }
+        }
+        pos >= end
+    }
+
+    /// [start, end) の VMA の権限を prot に変更する（mprotect 用）。
+    ///
+    /// 範囲の境界にまたがる VMA は分割して、範囲内の部分だけ権限を変える。
+    /// 範囲が VMA で覆われていなければ何も変えずに false を返す。
+    pub fn protect_range(&mut self, start: u64, end: u64, prot: VmaProt) -> bool {
+        if !self.covers(start, end) {
+            return false;
+        }
+
+        let mut new_vmas = Vec::with_capacity(self.vmas.len() + 2);
+        for vma in self.vmas.drain(..) {
+            if vma.end <= start || end <= vma.start {
+                new_vmas.push(vma);
+                continue;
+            }
+            if vma.start < start {
+                new_vmas.push(Vma { end: start, ..vma.clone() });
+            }
+            new_vmas.push(Vma {
+                start: vma.start.max(start),
+                end: vma.end.min(end),
+                prot: prot.clone(),
+                ..vma.clone()
+            });
+            if end < vma.end {
+                new_vmas.push(Vma { start: end, ..vma });
+            }
+        }
+        self.vmas = new_vmas;
+        true
+    }
+
     /// 指定した範囲 [base, limit) の中で、size バイト以上の空き領域を探す（first-fit）。
     ///
     /// VMA リストの隙間を走査して、最初に見つかった十分な空き領域の開始アドレスを返す。
@@ -0,0 +1,148 @@
+#![no_std]
+
+// checksum-core — CRC32 と Adler-32
+//
+// gzip / zip の展開結果の検証や、ブロック I/O のデータ破損の検出に使う。
+// no_std・アロケーション不要なので、カーネルからもユーザープログラムからも使える。
+//
+// CRC32 は gzip / zip / PNG / Ethernet と同じ IEEE 802.3 の多項式（反転表現 0xEDB88320）で、
+// 初期値と最終値を 0xFFFFFFFF で反転する。Adler-32 は zlib 形式のストリームで使う。
+//
+// CRC32 には 2 通りの実装がある:
+//   - crc32 / crc32_update: 256 エントリ (1 KiB) の表を引く。1 バイトあたり表引き 1 回で速い。
+//   - crc32_bitwise / crc32_update_bitwise: 表を持たずに 1 ビットずつ計算する。
+//     遅いがコードもデータも小さい。
+// 使わない方はリンク時に消えるので、呼び出し側が用途に合わせて選べばよい。
+
+/// CRC32 の多項式（ビット反転表現）
+const CRC32_POLY: u32 = 0xEDB8_8320;
+
+/// CRC32 の表（コンパイル時に作る）
+static CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { CRC32_POLY ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+/// data の CRC32 を返す（表引き版）
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// これまでの CRC32 に data を足した値を返す（表引き版）
+///
+/// crc には前回の戻り値を渡す（最初は 0）。データを分けて渡しても、
+/// つなげて crc32 に渡したのと同じ値になる。
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in data {
+        c = CRC32_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+/// data の CRC32 を返す（表を使わない版）
+pub fn crc32_bitwise(data: &[u8]) -> u32 {
+    crc32_update_bitwise(0, data)
+}
+
+/// これまでの CRC32 に data を足した値を返す（表を使わない版）
+pub fn crc32_update_bitwise(crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in data {
+        c ^= b as u32;
+        for _ in 0..8 {
+            // 最下位ビットが 1 なら多項式で割る（マスクで分岐をなくしている）
+            c = (c >> 1) ^ (CRC32_POLY & (c & 1).wrapping_neg());
+        }
+    }
+    !c
+}
+
+/// Adler-32 の法（65536 未満の最大の素数）
+const ADLER_MOD: u32 = 65521;
+
+/// 剰余を取らずに足し続けても u32 があふれないバイト数（zlib の NMAX と同じ）
+const ADLER_NMAX: usize = 5552;
+
+/// data の Adler-32 を返す
+pub fn adler32(data: &[u8]) -> u32 {
+    adler32_update(1, data)
+}
+
+/// これまでの Adler-32 に data を足した値を返す
+///
+/// adler には前回の戻り値を渡す（最初は 1）。
+pub fn adler32_update(adler: u32, data: &[u8]) -> u32 {
+    let mut a = adler & 0xffff;
+    let mut b = adler >> 16;
+    for chunk in data.chunks(ADLER_NMAX) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= ADLER_MOD;
+        b %= ADLER_MOD;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_crc32_known_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+        assert_eq!(crc32_bitwise(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_crc32_variants_agree() {
+        let data: Vec<u8> = (0..10000u32).map(|i| (i * 31 + i / 7) as u8).collect();
+        assert_eq!(crc32(&data), crc32_bitwise(&data));
+        // 分けて渡しても同じ
+        let (head, tail) = data.split_at(4321);
+        assert_eq!(crc32_update(crc32(head), tail), crc32(&data));
+        assert_eq!(crc32_update_bitwise(crc32_bitwise(head), tail), crc32(&data));
+    }
+
+    #[test]
+    fn test_adler32_known_vectors() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(b"123456789"), 0x091E_01DE);
+    }
+
+    #[test]
+    fn test_adler32_long_input() {
+        // NMAX を何度もまたぐ長さの 0xff でも u32 があふれない
+        let data = [0xffu8; 100_000];
+        let (head, tail) = data.split_at(12345);
+        assert_eq!(adler32_update(adler32(head), tail), adler32(&data));
+        // 法 65521 で 1 バイトずつ計算した値と一致する
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in data.iter() {
+            a = (a + byte as u64) % ADLER_MOD as u64;
+            b = (b + a) % ADLER_MOD as u64;
+        }
+        assert_eq!(adler32(&data), ((b << 16) | a) as u32);
+    }
+}
@@ -0,0 +1,273 @@
+// fsck.rs — FAT32 の整合性チェック（fsck 相当）
+//
+// blkwrite の途中でクラッシュしたりすると、FAT とディレクトリの内容が食い違うことがある。
+// check() はルートからすべてのディレクトリエントリを辿ってクラスタチェーンを
+// ビットマップに記録し、FAT 全体と突き合わせて次の問題を報告する。
+//
+// - 失われたチェーン: FAT 上は確保されているが、どのエントリからも参照されていない
+// - 交差リンク: 1 つのクラスタが 2 つのチェーン（または同じチェーン内の 2 か所）に現れる
+// - 壊れたチェーン: 範囲外のクラスタや空きクラスタを指している
+// - FSInfo の空きクラスタ数と、FAT を数えた実際の空きクラスタ数の差
+//
+// check() は読むだけで何も書き換えない。repair() は失われたチェーンだけを解放し、
+// FSInfo の空き数を数え直した値にする（交差リンクと壊れたチェーンは直さない）。
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use sabos_blockdev::BlockDevice;
+use sabos_fat_core::FsInfo;
+
+use crate::{Fat32Fs, ATTR_DIRECTORY, FAT32_EOC_MIN};
+
+/// FAT32 の不良クラスタマーカー（空きでも使用中でもない）
+const FAT32_BAD_CLUSTER: u32 = 0x0FFFFFF7;
+
+/// どのエントリからも参照されていないクラスタチェーン
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LostChain {
+    /// チェーンの先頭クラスタ
+    pub start: u32,
+    /// チェーンに含まれるクラスタ（先頭から順に）
+    pub clusters: Vec<u32>,
+}
+
+/// 2 回目に辿られたクラスタと、そのとき辿っていたエントリのパス
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossLink {
+    pub cluster: u32,
+    pub path: String,
+}
+
+/// check() の結果
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    /// 辿ったファイルの数
+    pub files: u32,
+    /// 辿ったディレクトリの数（ルートを含む）
+    pub dirs: u32,
+    /// エントリから参照されているクラスタの数
+    pub used_clusters: u32,
+    pub lost_chains: Vec<LostChain>,
+    pub cross_links: Vec<CrossLink>,
+    /// 範囲外のクラスタ・空きクラスタ・不良クラスタを指しているエントリのパス
+    pub bad_chains: Vec<String>,
+    /// FSInfo に書かれている空きクラスタ数（FSInfo がないか未設定なら None）
+    pub fsinfo_free: Option<u32>,
+    /// FAT を数えた実際の空きクラスタ数
+    pub actual_free: u32,
+}
+
+impl FsckReport {
+    /// 失われたチェーンに含まれるクラスタの合計
+    pub fn lost_clusters(&self) -> u32 {
+        self.lost_chains.iter().map(|c| c.clusters.len() as u32).sum()
+    }
+
+    /// FSInfo の空き数が実際と合っているか（FSInfo がなければ合っているとみなす）
+    pub fn fsinfo_matches(&self) -> bool {
+        self.fsinfo_free.is_none_or(|free| free == self.actual_free)
+    }
+
+    /// 何も問題が見つからなかったか
+    pub fn is_clean(&self) -> bool {
+        self.lost_chains.is_empty()
+            && self.cross_links.is_empty()
+            && self.bad_chains.is_empty()
+            && self.fsinfo_matches()
+    }
+}
+
+/// クラスタ番号ごとに 1 ビットのビットマップ
+struct ClusterBitmap {
+    bits: Vec<u64>,
+}
+
+impl ClusterBitmap {
+    fn new(limit: u32) -> Self {
+        Self { bits: vec![0; (limit as usize).div_ceil(64)] }
+    }
+
+    fn get(&self, cluster: u32) -> bool {
+        self.bits[cluster as usize / 64] & (1 << (cluster % 64)) != 0
+    }
+
+    fn set(&mut self, cluster: u32) {
+        self.bits[cluster as usize / 64] |= 1 << (cluster % 64);
+    }
+
+    fn clear(&mut self, cluster: u32) {
+        self.bits[cluster as usize / 64] &= !(1 << (cluster % 64));
+    }
+}
+
+impl<D: BlockDevice> Fat32Fs<D> {
+    /// ファイルシステム全体の整合性を調べる（何も書き換えない）
+    pub fn check(&mut self) -> Result<FsckReport, &'static str> {
+        let limit = self.cluster_limit();
+        let mut used = ClusterBitmap::new(limit);
+        let mut report = FsckReport {
+            fsinfo_free: self.fsinfo.and_then(|info| info.free_cluster_count),
+            ..FsckReport::default()
+        };
+
+        // ディレクトリを深さ優先で辿る。チェーンが壊れているディレクトリは中を読まない
+        // （循環しているチェーンを list_dir_cluster() で読むと終わらないため）
+        let mut dirs: Vec<(String, u32)> = Vec::new();
+        if self.mark_chain(self.root_cluster, "/", &mut used, &mut report)? {
+            dirs.push((String::from(""), self.root_cluster));
+        }
+        while let Some((path, cluster)) = dirs.pop() {
+            report.dirs += 1;
+            for entry in self.list_dir_cluster(cluster)? {
+                // "." と ".." は自分と親のクラスタを指すので辿らない
+                if entry.short_name[0] == b'.' {
+                    continue;
+                }
+                let child = format!("{}/{}", path, entry.name);
+                let is_dir = entry.attr & ATTR_DIRECTORY != 0;
+                if !is_dir {
+                    report.files += 1;
+                }
+                // 空のファイルはクラスタを持たない
+                if entry.first_cluster == 0 {
+                    if is_dir {
+                        report.bad_chains.push(child);
+                    }
+                    continue;
+                }
+                if self.mark_chain(entry.first_cluster, &child, &mut used, &mut report)? && is_dir {
+                    dirs.push((child, entry.first_cluster));
+                }
+            }
+        }
+
+        // FAT 全体を走査して、空きを数え、参照されていない確保済みクラスタを集める
+        let mut lost = ClusterBitmap::new(limit);
+        for cluster in 2..limit {
+            let val = self.read_fat_entry(cluster)?;
+            if val == 0 {
+                report.actual_free += 1;
+            } else if val != FAT32_BAD_CLUSTER && !used.get(cluster) {
+                lost.set(cluster);
+            }
+        }
+
+        // 失われたクラスタのうち、他の失われたクラスタから指されていないものがチェーンの先頭
+        let mut pointed = ClusterBitmap::new(limit);
+        for cluster in 2..limit {
+            if lost.get(cluster) {
+                let next = self.read_fat_entry(cluster)?;
+                if (2..limit).contains(&next) && lost.get(next) {
+                    pointed.set(next);
+                }
+            }
+        }
+        for cluster in 2..limit {
+            if lost.get(cluster) && !pointed.get(cluster) {
+                let chain = self.collect_lost_chain(cluster, limit, &mut lost)?;
+                report.lost_chains.push(chain);
+            }
+        }
+        // 先頭のない（循環している）失われたチェーンも拾う
+        for cluster in 2..limit {
+            if lost.get(cluster) {
+                let chain = self.collect_lost_chain(cluster, limit, &mut lost)?;
+                report.lost_chains.push(chain);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// check() で見つかった失われたチェーンを解放し、FSInfo の空き数を正しい値にする
+    ///
+    /// check() の後にファイルシステムを変更していないこと。
+    /// 交差リンクと壊れたチェーンは、どちらのファイルを残すか決められないので直さない。
+    ///
+    /// # 戻り値
+    /// 解放したクラスタの数
+    pub fn repair(&mut self, report: &FsckReport) -> Result<u32, &'static str> {
+        self.with_flush(|fs| {
+            let mut freed = 0u32;
+            let mut first_freed: Option<u32> = None;
+            for chain in &report.lost_chains {
+                for &cluster in &chain.clusters {
+                    if fs.read_fat_entry(cluster)? != 0 {
+                        fs.write_fat_entry(cluster, 0)?;
+                        freed += 1;
+                        first_freed = Some(first_freed.map_or(cluster, |c| c.min(cluster)));
+                    }
+                }
+            }
+            if let Some(info) = fs.fsinfo {
+                let next = match (info.next_free_cluster, first_freed) {
+                    (Some(hint), Some(c)) => Some(hint.min(c)),
+                    (hint, c) => hint.or(c),
+                };
+                fs.fsinfo = Some(FsInfo {
+                    free_cluster_count: Some(report.actual_free + freed),
+                    next_free_cluster: next,
+                });
+                fs.fsinfo_dirty = true;
+            }
+            Ok(freed)
+        })
+    }
+
+    /// start から始まるチェーンを辿って used に記録する
+    ///
+    /// 既に記録済みのクラスタに当たったら交差リンク、範囲外・空き・不良クラスタに
+    /// 当たったら壊れたチェーンとして report に追加し、false を返す。
+    fn mark_chain(
+        &mut self,
+        start: u32,
+        path: &str,
+        used: &mut ClusterBitmap,
+        report: &mut FsckReport,
+    ) -> Result<bool, &'static str> {
+        let limit = self.cluster_limit();
+        let mut cluster = start;
+        loop {
+            if !(2..limit).contains(&cluster) {
+                report.bad_chains.push(String::from(path));
+                return Ok(false);
+            }
+            if used.get(cluster) {
+                report.cross_links.push(CrossLink { cluster, path: String::from(path) });
+                return Ok(false);
+            }
+            used.set(cluster);
+            report.used_clusters += 1;
+
+            let val = self.read_fat_entry(cluster)?;
+            if val >= FAT32_EOC_MIN {
+                return Ok(true);
+            }
+            if val == 0 || val == FAT32_BAD_CLUSTER {
+                report.bad_chains.push(String::from(path));
+                return Ok(false);
+            }
+            cluster = val;
+        }
+    }
+
+    /// start から失われたクラスタを辿ってチェーンにまとめ、lost から取り除く
+    fn collect_lost_chain(
+        &mut self,
+        start: u32,
+        limit: u32,
+        lost: &mut ClusterBitmap,
+    ) -> Result<LostChain, &'static str> {
+        let mut clusters = Vec::new();
+        let mut cluster = start;
+        while (2..limit).contains(&cluster) && lost.get(cluster) {
+            lost.clear(cluster);
+            clusters.push(cluster);
+            cluster = self.read_fat_entry(cluster)?;
+        }
+        Ok(LostChain { start, clusters })
+    }
+}
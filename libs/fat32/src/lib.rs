@@ -19,6 +19,9 @@ use sabos_fat_core::{
     write_fsinfo, FatType, FsInfo, ATTR_LFN, LfnPart,
 };
 
+mod fsck;
+pub use fsck::{CrossLink, FsckReport, LostChain};
+
 /// セクタサイズ（512 バイト固定）
 pub const SECTOR_SIZE: usize = 512;
 /// ディレクトリ属性: ディレクトリ
@@ -27,6 +30,19 @@ pub const ATTR_DIRECTORY: u8 = 0x10;
 pub const ATTR_VOLUME_ID: u8 = 0x08;
 /// FAT32 の End-of-Chain マーカー最小値
 pub const FAT32_EOC_MIN: u32 = 0x0FFFFFF8;
+/// FAT セクタキャッシュに持つセクタ数（あふれたら最後に使ってから一番長いものを追い出す）
+const FAT_CACHE_SECTORS: usize = 16;
+
+/// キャッシュしている FAT セクタ 1 つ分
+struct CachedFatSector {
+    /// FAT 内のセクタ番号（1 面目の FAT の先頭からの番号）
+    index: u32,
+    data: [u8; SECTOR_SIZE],
+    /// ディスクに書き戻していない変更があるか
+    dirty: bool,
+    /// 最後に使った時刻（LRU 用の通し番号）
+    last_used: u64,
+}
 
 /// ディレクトリエントリ
 #[derive(Debug, Clone)]
@@ -50,6 +66,12 @@ pub struct Fat32Fs<D: BlockDevice> {
     root_cluster: u32,
     fsinfo_sector: u32,
     fsinfo: Option<FsInfo>,
+    /// fsinfo をディスクに書き戻していないか（FAT セクタと一緒に flush() で書く）
+    fsinfo_dirty: bool,
+    /// FAT セクタのキャッシュ（write-back、LRU）
+    fat_cache: Vec<CachedFatSector>,
+    /// LRU 用の時計。FAT セクタを使うたびに進める
+    fat_cache_clock: u64,
     /// ブロックデバイス。カーネル側で dev_index を参照するため pub にしている。
     pub dev: D,
 }
@@ -76,6 +98,9 @@ impl<D: BlockDevice> Fat32Fs<D> {
             root_cluster,
             fsinfo_sector,
             fsinfo: None,
+            fsinfo_dirty: false,
+            fat_cache: Vec::new(),
+            fat_cache_clock: 0,
             dev,
         };
         fs.load_fsinfo();
@@ -101,21 +126,21 @@ impl<D: BlockDevice> Fat32Fs<D> {
             }
         }
 
-        let total = self.total_clusters();
         let mut free = 0u32;
-        for cluster in 2..(total + 2) {
+        for cluster in 2..self.cluster_limit() {
             if self.read_fat_entry(cluster)? == 0 {
                 free += 1;
             }
         }
 
-        // 走査結果を FSInfo に反映
-        if self.fsinfo.is_some() {
+        // 走査結果を FSInfo に反映（確保のヒントはそのまま残す）
+        if let Some(info) = self.fsinfo {
             self.fsinfo = Some(FsInfo {
                 free_cluster_count: Some(free),
-                next_free_cluster: None,
+                next_free_cluster: info.next_free_cluster,
             });
-            let _ = self.flush_fsinfo();
+            self.fsinfo_dirty = true;
+            self.flush()?;
         }
 
         Ok(free)
@@ -153,11 +178,8 @@ impl<D: BlockDevice> Fat32Fs<D> {
 
     /// FAT エントリを読み取る（上位 4bit をマスク）
     fn read_fat_entry(&mut self, cluster: u32) -> Result<u32, &'static str> {
-        let fat_offset = cluster * 4;
-        let sector = self.fat_start_sector + (fat_offset / self.bpb.bytes_per_sector as u32);
-        let offset = (fat_offset % self.bpb.bytes_per_sector as u32) as usize;
-        let mut buf = [0u8; SECTOR_SIZE];
-        self.read_sector(sector as u64, &mut buf)?;
+        let (slot, offset) = self.fat_entry_slot(cluster)?;
+        let buf = &self.fat_cache[slot].data;
         let val = u32::from_le_bytes([
             buf[offset],
             buf[offset + 1],
@@ -167,26 +189,105 @@ impl<D: BlockDevice> Fat32Fs<D> {
         Ok(val)
     }
 
-    /// FAT エントリを書き込む（全 FAT に反映）
+    /// FAT エントリを書き込む（キャッシュ上で書き換え、flush() で全 FAT に反映）
     fn write_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), &'static str> {
+        let (slot, offset) = self.fat_entry_slot(cluster)?;
+        let val = value & 0x0FFFFFFF;
+        let entry = &mut self.fat_cache[slot];
+        entry.data[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+        entry.dirty = true;
+        Ok(())
+    }
+
+    /// クラスタの FAT エントリが入っているキャッシュのスロットと、セクタ内のオフセットを返す
+    fn fat_entry_slot(&mut self, cluster: u32) -> Result<(usize, usize), &'static str> {
         let fat_offset = cluster * 4;
-        let sector = self.fat_start_sector + (fat_offset / self.bpb.bytes_per_sector as u32);
+        let index = fat_offset / self.bpb.bytes_per_sector as u32;
         let offset = (fat_offset % self.bpb.bytes_per_sector as u32) as usize;
-        let val = value & 0x0FFFFFFF;
-        let bytes = val.to_le_bytes();
+        Ok((self.fat_cache_slot(index)?, offset))
+    }
+
+    /// FAT のセクタ index をキャッシュに載せ、そのスロットを返す
+    ///
+    /// キャッシュが一杯なら最後に使ってから一番長いセクタを追い出す（dirty なら先に書き戻す）。
+    fn fat_cache_slot(&mut self, index: u32) -> Result<usize, &'static str> {
+        self.fat_cache_clock += 1;
+        let now = self.fat_cache_clock;
+        if let Some(slot) = self.fat_cache.iter().position(|c| c.index == index) {
+            self.fat_cache[slot].last_used = now;
+            return Ok(slot);
+        }
+
+        let mut data = [0u8; SECTOR_SIZE];
+        self.read_sector((self.fat_start_sector + index) as u64, &mut data)?;
+        let entry = CachedFatSector { index, data, dirty: false, last_used: now };
+
+        if self.fat_cache.len() < FAT_CACHE_SECTORS {
+            self.fat_cache.push(entry);
+            return Ok(self.fat_cache.len() - 1);
+        }
+        let victim = self
+            .fat_cache
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, c)| c.last_used)
+            .map(|(slot, _)| slot)
+            .ok_or("FAT cache is empty")?;
+        self.write_back_fat_sector(victim)?;
+        self.fat_cache[victim] = entry;
+        Ok(victim)
+    }
+
+    /// キャッシュのスロットが dirty なら、全 FAT（ミラー）の同じ位置に書き戻す
+    fn write_back_fat_sector(&mut self, slot: usize) -> Result<(), &'static str> {
+        if !self.fat_cache[slot].dirty {
+            return Ok(());
+        }
+        let CachedFatSector { index, data, .. } = self.fat_cache[slot];
         for fat_idx in 0..self.bpb.num_fats {
-            let fat_sector = sector + fat_idx as u32 * self.bpb.fat_size;
-            let mut buf = [0u8; SECTOR_SIZE];
-            self.read_sector(fat_sector as u64, &mut buf)?;
-            buf[offset] = bytes[0];
-            buf[offset + 1] = bytes[1];
-            buf[offset + 2] = bytes[2];
-            buf[offset + 3] = bytes[3];
-            self.write_sector(fat_sector as u64, &buf)?;
+            let fat_sector = self.fat_start_sector + fat_idx as u32 * self.bpb.fat_size + index;
+            self.write_sector(fat_sector as u64, &data)?;
+        }
+        self.fat_cache[slot].dirty = false;
+        Ok(())
+    }
+
+    /// キャッシュ中の変更された FAT セクタと FSInfo をディスクに書き戻す
+    ///
+    /// ファイルの作成・削除などの公開メソッドは最後にこれを呼ぶので、
+    /// 呼び出し側が明示的に呼ぶ必要はない。FAT を書き終えてから FSInfo を書くので、
+    /// FSInfo の空きクラスタ数・次の空きクラスタのヒントが FAT より先に進むことはない。
+    pub fn flush(&mut self) -> Result<(), &'static str> {
+        for slot in 0..self.fat_cache.len() {
+            self.write_back_fat_sector(slot)?;
+        }
+        if self.fsinfo_dirty {
+            self.flush_fsinfo()?;
+            self.fsinfo_dirty = false;
         }
         Ok(())
     }
 
+    /// キャッシュを書き戻してから捨て、FSInfo を読み直す
+    ///
+    /// 同じデバイスをほかの経路（別の Fat32Fs やセクタ直接書き込み）で書き換えたあとに呼ぶ。
+    pub fn invalidate_cache(&mut self) -> Result<(), &'static str> {
+        self.flush()?;
+        self.fat_cache.clear();
+        self.fsinfo = None;
+        self.load_fsinfo();
+        Ok(())
+    }
+
+    /// f を実行し、成功・失敗にかかわらずキャッシュを書き戻す
+    fn with_flush<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R, &'static str>) -> Result<R, &'static str> {
+        let result = f(self);
+        let flushed = self.flush();
+        let value = result?;
+        flushed?;
+        Ok(value)
+    }
+
     /// クラスタ番号から先頭セクタへ
     fn cluster_to_sector(&self, cluster: u32) -> u32 {
         self.data_start_sector + (cluster - 2) * self.bpb.sectors_per_cluster as u32
@@ -333,17 +434,38 @@ impl<D: BlockDevice> Fat32Fs<D> {
         if entry.attr & ATTR_DIRECTORY != 0 {
             return Err("Cannot read directory");
         }
-        self.read_file_data(&entry)
+        self.read_file_at(entry.first_cluster, entry.size)
     }
 
-    fn read_file_data(&mut self, entry: &DirEntry) -> Result<Vec<u8>, &'static str> {
+    /// 先頭クラスタとサイズが分かっているファイルを、ディレクトリを辿らずに読み取る
+    ///
+    /// find_entry() の結果を覚えておけば、同じファイルを何度も読むときに
+    /// パスの検索（親ディレクトリのクラスタ読み込み）を省ける。
+    pub fn read_file_at(&mut self, first_cluster: u32, size: u32) -> Result<Vec<u8>, &'static str> {
         // ファイルサイズが分かっているので、事前に容量を確保して
         // Vec の倍々成長による一時メモリ消費を回避する
-        let mut data = Vec::with_capacity(entry.size as usize);
-        let mut remaining = entry.size as usize;
-        let mut cluster = entry.first_cluster;
+        let mut data = Vec::with_capacity(size as usize);
+        self.read_file_chunks_at(first_cluster, size, |chunk| {
+            data.extend_from_slice(chunk);
+            Ok(())
+        })?;
+        Ok(data)
+    }
+
+    /// ファイルを先頭から 1 セクタずつ読み、読めた分ごとに f を呼ぶ
+    ///
+    /// ファイル全体をメモリに置かないので、ヒープより大きいファイルも読める。
+    /// f が Err を返したらそこで読むのをやめ、そのエラーを返す。
+    pub fn read_file_chunks_at(
+        &mut self,
+        first_cluster: u32,
+        size: u32,
+        mut f: impl FnMut(&[u8]) -> Result<(), &'static str>,
+    ) -> Result<(), &'static str> {
+        let mut remaining = size as usize;
+        let mut cluster = first_cluster;
         if cluster == 0 {
-            return Ok(data);
+            return Ok(());
         }
         loop {
             let first_sector = self.cluster_to_sector(cluster);
@@ -352,10 +474,10 @@ impl<D: BlockDevice> Fat32Fs<D> {
                 let mut buf = [0u8; SECTOR_SIZE];
                 self.read_sector(sector as u64, &mut buf)?;
                 let to_copy = core::cmp::min(remaining, SECTOR_SIZE);
-                data.extend_from_slice(&buf[..to_copy]);
+                f(&buf[..to_copy])?;
                 remaining = remaining.saturating_sub(to_copy);
                 if remaining == 0 {
-                    return Ok(data);
+                    return Ok(());
                 }
             }
             match self.next_cluster(cluster)? {
@@ -363,7 +485,7 @@ impl<D: BlockDevice> Fat32Fs<D> {
                 None => break,
             }
         }
-        Ok(data)
+        Ok(())
     }
 
     /// ディレクトリ一覧
@@ -376,18 +498,27 @@ impl<D: BlockDevice> Fat32Fs<D> {
         self.list_dir_cluster(cluster)
     }
 
+    /// 先頭クラスタが分かっているディレクトリを、パスを辿らずに一覧する
+    pub fn list_dir_at(&mut self, cluster: u32) -> Result<Vec<DirEntry>, &'static str> {
+        self.list_dir_cluster(cluster)
+    }
+
     /// ファイル作成
     pub fn create_file(&mut self, path: &str, data: &[u8]) -> Result<(), &'static str> {
         let (dir_path, name) = split_parent(path)?;
-        let dir_cluster = self.find_dir_cluster(dir_path)?;
-        self.create_entry(dir_cluster, name, data, false)
+        self.with_flush(|fs| {
+            let dir_cluster = fs.find_dir_cluster(dir_path)?;
+            fs.create_entry(dir_cluster, name, data, false)
+        })
     }
 
     /// ディレクトリ作成
     pub fn create_dir(&mut self, path: &str) -> Result<(), &'static str> {
         let (dir_path, name) = split_parent(path)?;
-        let dir_cluster = self.find_dir_cluster(dir_path)?;
-        self.create_entry(dir_cluster, name, &[], true)
+        self.with_flush(|fs| {
+            let dir_cluster = fs.find_dir_cluster(dir_path)?;
+            fs.create_entry(dir_cluster, name, &[], true)
+        })
     }
 
     fn create_entry(
@@ -483,15 +614,72 @@ impl<D: BlockDevice> Fat32Fs<D> {
 
     /// ファイル削除
     pub fn delete_file(&mut self, path: &str) -> Result<(), &'static str> {
-        self.delete_entry(path, false)
+        self.with_flush(|fs| fs.delete_entry(path, false, true))
     }
 
     /// ディレクトリ削除
     pub fn delete_dir(&mut self, path: &str) -> Result<(), &'static str> {
-        self.delete_entry(path, true)
+        self.with_flush(|fs| fs.delete_entry(path, true, true))
+    }
+
+    /// ファイル・ディレクトリの名前変更（同じボリューム内の移動）
+    ///
+    /// データのクラスタはそのままに、移動先ディレクトリへエントリを書き足してから
+    /// 元のエントリを消す。途中で失敗しても「両方にある」側に倒れ、データは失われない。
+    /// ディレクトリを別の親に移したときは、中の ".." を新しい親に向け直す。
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), &'static str> {
+        self.with_flush(|fs| fs.rename_entry(from, to))
+    }
+
+    fn rename_entry(&mut self, from: &str, to: &str) -> Result<(), &'static str> {
+        let entry = self.find_entry(from)?;
+        let is_dir = entry.attr & ATTR_DIRECTORY != 0;
+
+        // ディレクトリを自分の配下へ移すと、どこからも辿れないループになる
+        let from_bytes = from.trim_end_matches('/').as_bytes();
+        let to_bytes = to.as_bytes();
+        if is_dir
+            && to_bytes.len() > from_bytes.len()
+            && to_bytes[..from_bytes.len()].eq_ignore_ascii_case(from_bytes)
+            && to_bytes[from_bytes.len()] == b'/'
+        {
+            return Err("Cannot move a directory into itself");
+        }
+
+        let (dir_path, name) = split_parent(to)?;
+        if name.is_empty() {
+            return Err("name is empty");
+        }
+        let dir_cluster = self.find_dir_cluster(dir_path)?;
+        let entries = self.list_dir_cluster(dir_cluster)?;
+        if entries.iter().any(|e| e.name == name || short_name_to_string(&e.short_name).eq_ignore_ascii_case(name)) {
+            return Err("already exists");
+        }
+
+        let short_name = make_short_name(name, &|n| {
+            entries.iter().any(|e| &e.short_name == n)
+        });
+        let checksum = lfn_checksum(&short_name);
+        let lfn_entries = build_lfn_entries(name, checksum)?;
+        self.add_dir_entries(dir_cluster, &lfn_entries, &short_name, is_dir, entry.first_cluster, entry.size)?;
+        self.delete_entry(from, is_dir, false)?;
+
+        if is_dir && entry.first_cluster >= 2 {
+            // ".." はディレクトリの先頭クラスタの 2 番目のエントリ
+            let sector = self.cluster_to_sector(entry.first_cluster) as u64;
+            let mut buf = [0u8; SECTOR_SIZE];
+            self.read_sector(sector, &mut buf)?;
+            if buf[32..34] == *b".." {
+                let dotdot = format_8_3_name("..")?;
+                write_short_entry(&mut buf, 32, &dotdot, true, dir_cluster, 0);
+                self.write_sector(sector, &buf)?;
+            }
+        }
+        Ok(())
     }
 
-    fn delete_entry(&mut self, path: &str, is_dir: bool) -> Result<(), &'static str> {
+    /// エントリを消す。free_data が false ならクラスタは解放しない（rename 用）。
+    fn delete_entry(&mut self, path: &str, is_dir: bool, free_data: bool) -> Result<(), &'static str> {
         let (dir_path, name) = split_parent(path)?;
         let dir_cluster = self.find_dir_cluster(dir_path)?;
         let mut cluster = dir_cluster;
@@ -552,7 +740,7 @@ impl<D: BlockDevice> Fat32Fs<D> {
                         if entry_is_dir != is_dir {
                             return Err("type mismatch");
                         }
-                        if entry_is_dir && first_cluster >= 2 && !self.is_dir_empty(first_cluster)? {
+                        if free_data && entry_is_dir && first_cluster >= 2 && !self.is_dir_empty(first_cluster)? {
                             return Err("Directory not empty");
                         }
                         for &lfn_off in lfn_offsets.iter() {
@@ -560,7 +748,7 @@ impl<D: BlockDevice> Fat32Fs<D> {
                         }
                         buf[offset] = 0xE5;
                         self.write_sector(sector as u64, &buf)?;
-                        if first_cluster >= 2 {
+                        if free_data && first_cluster >= 2 {
                             self.free_cluster_chain(first_cluster)?;
                         }
                         return Ok(());
@@ -659,28 +847,28 @@ impl<D: BlockDevice> Fat32Fs<D> {
         Ok(())
     }
 
+    /// データ領域のクラスタ番号の上限（この値は含まない）
+    ///
+    /// FAT のエントリ数のほうが少ないイメージでも、FAT の外を読まないように小さいほうを使う。
+    fn cluster_limit(&self) -> u32 {
+        let fat_entries = (self.bpb.fat_size * self.bpb.bytes_per_sector as u32) / 4;
+        core::cmp::min(self.total_clusters() + 2, fat_entries)
+    }
+
+    /// 空きクラスタを 1 つ確保して End-of-Chain にする
+    ///
+    /// FSInfo の next_free_cluster（前回確保したクラスタの次）から探すので、
+    /// ファイルを次々に作っても毎回先頭から FAT を走査せずに済む。
+    /// ヒントがデータ領域の外（壊れている・未設定）なら先頭から探し、
+    /// ヒントより後ろに空きがなければ先頭に戻って探す。
     fn alloc_cluster(&mut self) -> Result<u32, &'static str> {
-        let total_entries = (self.bpb.fat_size * self.bpb.bytes_per_sector as u32) / 4;
+        let limit = self.cluster_limit();
         let start = self
             .fsinfo
             .and_then(|info| info.next_free_cluster)
+            .filter(|hint| (2..limit).contains(hint))
             .unwrap_or(2);
-        for cluster in start..total_entries {
-            if self.read_fat_entry(cluster)? == 0 {
-                self.write_fat_entry(cluster, FAT32_EOC_MIN)?;
-                if let Some(info) = self.fsinfo {
-                    let free = info.free_cluster_count.map(|v| v.saturating_sub(1));
-                    let next = Some(cluster + 1);
-                    self.fsinfo = Some(FsInfo {
-                        free_cluster_count: free,
-                        next_free_cluster: next,
-                    });
-                    let _ = self.flush_fsinfo();
-                }
-                return Ok(cluster);
-            }
-        }
-        for cluster in 2..start {
+        for cluster in (start..limit).chain(2..start) {
             if self.read_fat_entry(cluster)? == 0 {
                 self.write_fat_entry(cluster, FAT32_EOC_MIN)?;
                 if let Some(info) = self.fsinfo {
@@ -690,7 +878,7 @@ impl<D: BlockDevice> Fat32Fs<D> {
                         free_cluster_count: free,
                         next_free_cluster: next,
                     });
-                    let _ = self.flush_fsinfo();
+                    self.fsinfo_dirty = true;
                 }
                 return Ok(cluster);
             }
@@ -712,16 +900,25 @@ impl<D: BlockDevice> Fat32Fs<D> {
         }
         if let Some(info) = self.fsinfo {
             let free = info.free_cluster_count.map(|v| v.saturating_add(freed));
+            // 解放したクラスタがヒントより前なら、次はそこから使う
+            let next = info.next_free_cluster.map_or(start, |hint| hint.min(start));
             self.fsinfo = Some(FsInfo {
                 free_cluster_count: free,
-                next_free_cluster: Some(start),
+                next_free_cluster: Some(next),
             });
-            let _ = self.flush_fsinfo();
+            self.fsinfo_dirty = true;
         }
         Ok(())
     }
 }
 
+/// 書き戻していない FAT セクタと FSInfo は、破棄するときに書き戻す
+impl<D: BlockDevice> Drop for Fat32Fs<D> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
 // =================================================================
 // ヘルパー関数
 // =================================================================
@@ -898,3 +1095,305 @@ fn write_short_entry(
     buf[offset + 30] = size_bytes[2];
     buf[offset + 31] = size_bytes[3];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::vec;
+
+    use sabos_blockdev::BlockError;
+
+    /// テスト用のメモリ上のブロックデバイス。読み書きの回数を数える
+    struct MemDevice {
+        sectors: Vec<[u8; SECTOR_SIZE]>,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl BlockDevice for MemDevice {
+        fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+            let src = self.sectors.get(sector as usize).ok_or(BlockError::InvalidArgument)?;
+            buf[..SECTOR_SIZE].copy_from_slice(src);
+            self.reads += 1;
+            Ok(())
+        }
+
+        fn write_sector(&mut self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
+            let dst = self.sectors.get_mut(sector as usize).ok_or(BlockError::InvalidArgument)?;
+            dst.copy_from_slice(&buf[..SECTOR_SIZE]);
+            self.writes += 1;
+            Ok(())
+        }
+    }
+
+    const TOTAL_SECTORS: u32 = 8192;
+    const RESERVED_SECTORS: u32 = 32;
+    const FAT_SIZE: u32 = 64;
+    const DATA_START: u32 = RESERVED_SECTORS + 2 * FAT_SIZE;
+
+    /// 4 MiB の FAT32 イメージを作る（1 クラスタ = 1 セクタ、FAT 2 面、ルートはクラスタ 2）
+    fn make_image() -> MemDevice {
+        let mut sectors = vec![[0u8; SECTOR_SIZE]; TOTAL_SECTORS as usize];
+
+        let bpb = &mut sectors[0];
+        bpb[11..13].copy_from_slice(&512u16.to_le_bytes());
+        bpb[13] = 1;
+        bpb[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        bpb[16] = 2;
+        bpb[32..36].copy_from_slice(&TOTAL_SECTORS.to_le_bytes());
+        bpb[36..40].copy_from_slice(&FAT_SIZE.to_le_bytes());
+        bpb[44..48].copy_from_slice(&2u32.to_le_bytes());
+        bpb[48..50].copy_from_slice(&1u16.to_le_bytes());
+        bpb[510] = 0x55;
+        bpb[511] = 0xAA;
+
+        let total_clusters = TOTAL_SECTORS - DATA_START;
+        write_fsinfo(&mut sectors[1], FsInfo {
+            free_cluster_count: Some(total_clusters - 1),
+            next_free_cluster: Some(3),
+        });
+
+        for fat in 0..2 {
+            let first = &mut sectors[(RESERVED_SECTORS + fat * FAT_SIZE) as usize];
+            first[0..4].copy_from_slice(&0x0FFF_FFF8u32.to_le_bytes());
+            first[4..8].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+            first[8..12].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+        }
+
+        MemDevice { sectors, reads: 0, writes: 0 }
+    }
+
+    #[test]
+    fn test_write_1mib_file_io_count() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        let data: Vec<u8> = (0..1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+        fs.dev.reads = 0;
+        fs.dev.writes = 0;
+        fs.create_file("/BIG.BIN", &data).unwrap();
+        std::println!("1 MiB write: {} sector reads, {} sector writes", fs.dev.reads, fs.dev.writes);
+
+        // キャッシュ導入前は 12291 reads / 12289 writes だった。
+        // データ 2048 セクタ + FAT 17 セクタ × 2 面 + ディレクトリ + FSInfo 程度に収まること
+        assert!(fs.dev.writes < 2048 + 64, "too many writes: {}", fs.dev.writes);
+        assert!(fs.dev.reads < 64, "too many reads: {}", fs.dev.reads);
+
+        let free_before = TOTAL_SECTORS - DATA_START - 1;
+        assert_eq!(fs.read_file("/BIG.BIN").unwrap(), data);
+        assert_eq!(fs.free_clusters().unwrap(), free_before - 2048);
+        assert_fats_mirrored(&fs.dev);
+    }
+
+    /// 2 面の FAT が同じ内容になっていること
+    fn assert_fats_mirrored(dev: &MemDevice) {
+        for i in 0..FAT_SIZE {
+            assert_eq!(
+                dev.sectors[(RESERVED_SECTORS + i) as usize],
+                dev.sectors[(RESERVED_SECTORS + FAT_SIZE + i) as usize],
+                "FAT sector {} differs between mirrors",
+                i
+            );
+        }
+    }
+
+    #[test]
+    fn test_fsinfo_written_with_fat_cache() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        fs.create_file("/A.TXT", &[1u8; 1500]).unwrap();
+
+        // 公開メソッドの終わりに FAT と FSInfo が書き戻されている
+        let free_before = TOTAL_SECTORS - DATA_START - 1;
+        let info = parse_fsinfo(&fs.dev.sectors[1]).unwrap();
+        assert_eq!(info.free_cluster_count, Some(free_before - 3));
+        assert_eq!(info.next_free_cluster, Some(6));
+        assert_fats_mirrored(&fs.dev);
+
+        fs.delete_file("/A.TXT").unwrap();
+        let info = parse_fsinfo(&fs.dev.sectors[1]).unwrap();
+        assert_eq!(info.free_cluster_count, Some(free_before));
+        assert_eq!(info.next_free_cluster, Some(3));
+        assert_fats_mirrored(&fs.dev);
+    }
+
+    #[test]
+    fn test_invalidate_cache_sees_external_writes() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        fs.create_file("/A.TXT", b"hello").unwrap();
+        assert_eq!(fs.read_fat_entry(3).unwrap(), FAT32_EOC_MIN);
+
+        // ほかの経路でクラスタ 3 を解放したことにする（両方の FAT とも）
+        for fat in 0..2 {
+            let sector = &mut fs.dev.sectors[(RESERVED_SECTORS + fat * FAT_SIZE) as usize];
+            sector[12..16].copy_from_slice(&0u32.to_le_bytes());
+        }
+        // キャッシュが残っているうちは古い値が見える
+        assert_eq!(fs.read_fat_entry(3).unwrap(), FAT32_EOC_MIN);
+        fs.invalidate_cache().unwrap();
+        assert_eq!(fs.read_fat_entry(3).unwrap(), 0);
+    }
+
+    /// クラスタチェーンの長さ
+    fn chain_len(fs: &mut Fat32Fs<MemDevice>, start: u32) -> u32 {
+        let mut len = 1;
+        let mut cluster = start;
+        while let Some(next) = fs.next_cluster(cluster).unwrap() {
+            len += 1;
+            cluster = next;
+        }
+        len
+    }
+
+    #[test]
+    fn test_create_50_files_updates_fsinfo() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        let free_before = fs.free_clusters().unwrap();
+
+        fs.dev.reads = 0;
+        for i in 0..50 {
+            let name = std::format!("/F{:02}.TXT", i);
+            fs.create_file(&name, &[i as u8; 600]).unwrap();
+        }
+        // ヒントから探すので、FAT を毎回先頭から読み直さない
+        // （ディレクトリの走査分を除けば、FAT の読み込みは数セクタで済む）
+        let reads = fs.dev.reads;
+
+        // 1 ファイル 2 クラスタ + ルートディレクトリが伸びた分だけ減っている
+        let dir_growth = chain_len(&mut fs, 2) - 1;
+        assert!(dir_growth >= 3);
+        let expected = free_before - 50 * 2 - dir_growth;
+        let info = parse_fsinfo(&fs.dev.sectors[1]).unwrap();
+        assert_eq!(info.free_cluster_count, Some(expected));
+        assert_eq!(fs.free_clusters().unwrap(), expected);
+
+        // FAT を全部数え直しても同じ値になる
+        fs.fsinfo = None;
+        assert_eq!(fs.free_clusters().unwrap(), expected);
+        assert!(reads < 50 * 20, "too many reads: {}", reads);
+    }
+
+    #[test]
+    fn test_alloc_falls_back_when_hint_is_invalid() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+
+        // データ領域の外を指すヒントは無視して先頭から探す
+        fs.fsinfo = Some(FsInfo { free_cluster_count: None, next_free_cluster: Some(0x0FFF_FFF0) });
+        assert_eq!(fs.alloc_cluster().unwrap(), 3);
+
+        // 使用中のクラスタを指すヒントなら、その先の空きを使う
+        fs.fsinfo = Some(FsInfo { free_cluster_count: None, next_free_cluster: Some(2) });
+        assert_eq!(fs.alloc_cluster().unwrap(), 4);
+
+        // ヒントより後ろに空きがなければ先頭に戻る
+        let last = fs.cluster_limit() - 1;
+        fs.fsinfo = Some(FsInfo { free_cluster_count: None, next_free_cluster: Some(last) });
+        assert_eq!(fs.alloc_cluster().unwrap(), last);
+        assert_eq!(fs.alloc_cluster().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_read_file_at_skips_directory_walk() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        fs.create_dir("/A").unwrap();
+        fs.create_dir("/A/B").unwrap();
+        for i in 0..40 {
+            fs.create_file(&std::format!("/A/B/F{:02}.TXT", i), &[0; 10]).unwrap();
+        }
+        fs.create_file("/A/B/DATA.BIN", &[7u8; 1500]).unwrap();
+
+        fs.dev.reads = 0;
+        let by_path = fs.read_file("/A/B/DATA.BIN").unwrap();
+        let path_reads = fs.dev.reads;
+
+        let entry = fs.find_entry("/A/B/DATA.BIN").unwrap();
+        fs.dev.reads = 0;
+        let by_location = fs.read_file_at(entry.first_cluster, entry.size).unwrap();
+        let location_reads = fs.dev.reads;
+        std::println!("read by path: {} sector reads, by location: {}", path_reads, location_reads);
+
+        assert_eq!(by_path, by_location);
+        // 1500 バイト = 3 クラスタ分だけ読めばよい
+        assert_eq!(location_reads, 3);
+        assert!(path_reads > location_reads);
+
+        let dir = fs.find_entry("/A/B").unwrap();
+        let names = |entries: Vec<DirEntry>| entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+        assert_eq!(names(fs.list_dir_at(dir.first_cluster).unwrap()), names(fs.list_dir("/A/B").unwrap()));
+    }
+
+    #[test]
+    fn test_check_clean_image() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        fs.create_dir("/D").unwrap();
+        fs.create_file("/D/A.TXT", &[1u8; 1200]).unwrap();
+        fs.create_file("/EMPTY.TXT", &[]).unwrap();
+
+        let report = fs.check().unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!(report.files, 2);
+        assert_eq!(report.dirs, 2);
+        // ルート 1 + /D 1 + A.TXT 3
+        assert_eq!(report.used_clusters, 5);
+    }
+
+    #[test]
+    fn test_check_and_repair_lost_chain() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        fs.create_file("/KEEP.TXT", &[1u8; 600]).unwrap();
+        let free_before = fs.free_clusters().unwrap();
+
+        // 書き込み途中のクラッシュを模して、どこからも参照されない 100 → 101 → 102 を作る
+        // （FSInfo の空き数は減らさない）
+        fs.write_fat_entry(100, 101).unwrap();
+        fs.write_fat_entry(101, 102).unwrap();
+        fs.write_fat_entry(102, FAT32_EOC_MIN).unwrap();
+        fs.flush().unwrap();
+
+        let mut fs = Fat32Fs::new_with_device(fs_into_dev(fs)).unwrap();
+        let report = fs.check().unwrap();
+        assert_eq!(report.lost_chains, vec![LostChain { start: 100, clusters: vec![100, 101, 102] }]);
+        assert!(report.cross_links.is_empty());
+        assert!(report.bad_chains.is_empty());
+        assert_eq!(report.fsinfo_free, Some(free_before));
+        assert_eq!(report.actual_free, free_before - 3);
+        assert!(!report.is_clean());
+
+        // check() は何も書かない
+        fs.dev.writes = 0;
+        let _ = fs.check().unwrap();
+        assert_eq!(fs.dev.writes, 0);
+
+        assert_eq!(fs.repair(&report).unwrap(), 3);
+        let mut fs = Fat32Fs::new_with_device(fs_into_dev(fs)).unwrap();
+        let after = fs.check().unwrap();
+        assert!(after.is_clean(), "{:?}", after);
+        assert_eq!(after.actual_free, free_before);
+        assert_eq!(fs.read_file("/KEEP.TXT").unwrap(), vec![1u8; 600]);
+    }
+
+    #[test]
+    fn test_check_detects_cross_link() {
+        let mut fs = Fat32Fs::new_with_device(make_image()).unwrap();
+        fs.create_file("/A.TXT", &[1u8; 1024]).unwrap();
+        fs.create_file("/B.TXT", &[2u8; 512]).unwrap();
+        let a = fs.find_entry("/A.TXT").unwrap().first_cluster;
+        let b = fs.find_entry("/B.TXT").unwrap().first_cluster;
+
+        // A の末尾を B の先頭につなぐ（A の 2 クラスタ目が B を指す）
+        let a_last = fs.next_cluster(a).unwrap().unwrap();
+        fs.write_fat_entry(a_last, b).unwrap();
+        fs.flush().unwrap();
+
+        let report = fs.check().unwrap();
+        assert_eq!(report.cross_links.len(), 1);
+        assert_eq!(report.cross_links[0].cluster, b);
+        assert!(report.lost_chains.is_empty());
+    }
+
+    /// Fat32Fs を flush して閉じ、ブロックデバイスを取り出す（再マウントのため）
+    fn fs_into_dev(mut fs: Fat32Fs<MemDevice>) -> MemDevice {
+        fs.flush().unwrap();
+        let sectors = core::mem::take(&mut fs.dev.sectors);
+        MemDevice { sectors, reads: 0, writes: 0 }
+    }
+}
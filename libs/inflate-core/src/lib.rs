@@ -0,0 +1,590 @@
+#![no_std]
+
+// inflate-core — DEFLATE (RFC 1951) の展開と gzip (RFC 1952) の読み取り
+//
+// 圧縮された initrd やディスク上の .gz ファイルを読むために、カーネルからも
+// ユーザープログラムからも使える no_std + alloc の展開器として切り出した。
+// 実装は zlib 付属の puff と同じ「ハフマン符号を 1 ビットずつたどる」素直な方式で、
+// 速さよりも短さと読みやすさを優先している。
+//
+// DEFLATE のストリームはブロックの並び:
+//   BFINAL(1bit) BTYPE(2bit) <ブロック本体>
+//   BTYPE = 0: 非圧縮（LEN, NLEN の後に生のバイト列）
+//   BTYPE = 1: 固定ハフマン符号
+//   BTYPE = 2: 動的ハフマン符号（符号長の表がブロックの先頭に入っている）
+// ビットは各バイトの下位ビットから読む。
+//
+// ## 壊れたデータと展開爆弾
+//
+// 入力は信用しない。読みすぎ・存在しない符号・出力より前を指す距離はすべてエラーにし、
+// 展開後のサイズは max_output で打ち切る（数 KB の入力が数 GB に膨らむ「展開爆弾」で
+// ヒープを使い切らないため）。gzip の ISIZE は入力の一部なので、事前確保の大きさには使わない。
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+/// inflate / gunzip が使う展開後サイズの上限（64 MiB）
+pub const DEFAULT_MAX_OUTPUT: usize = 64 * 1024 * 1024;
+
+/// 展開のエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateError {
+    /// ストリームの途中で入力が尽きた
+    UnexpectedEof,
+    /// BTYPE が 3（予約値）
+    InvalidBlockType,
+    /// 非圧縮ブロックの LEN と NLEN が補数になっていない
+    InvalidStoredLength,
+    /// 符号長の表が壊れている、または存在しない符号が現れた
+    InvalidCode,
+    /// 距離がそれまでの出力より前を指している
+    InvalidDistance,
+    /// 展開後のサイズが上限を超えた
+    OutputTooLarge,
+    /// gzip のマジックナンバー (1f 8b) がない
+    NotGzip,
+    /// gzip の圧縮方式が deflate (8) ではない、または予約フラグが立っている
+    UnsupportedGzip,
+    /// gzip のトレーラの ISIZE が展開結果の長さと合わない
+    SizeMismatch,
+    /// gzip のトレーラの CRC32 が展開結果と合わない
+    ChecksumMismatch,
+}
+
+/// gzip ヘッダの内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GzipHeader<'a> {
+    /// 元のファイル名（FNAME。終端の 0 は含まない）
+    pub name: Option<&'a [u8]>,
+    /// 元のファイルの更新時刻（UNIX 時間。0 は不明）
+    pub mtime: u32,
+    /// ヘッダのバイト数（この後ろから DEFLATE のストリームが始まる）
+    pub header_len: usize,
+}
+
+const FTEXT: u8 = 0x01;
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// gzip ヘッダを読む（RFC 1952 2.3）
+///
+/// FHCRC の CRC16 は読み飛ばすだけで検証しない。
+pub fn parse_gzip_header(data: &[u8]) -> Result<GzipHeader<'_>, InflateError> {
+    if data.len() < 2 || data[0] != 0x1f || data[1] != 0x8b {
+        return Err(InflateError::NotGzip);
+    }
+    if data.len() < 10 {
+        return Err(InflateError::UnexpectedEof);
+    }
+    let flags = data[3];
+    if data[2] != 8 || flags & !(FTEXT | FHCRC | FEXTRA | FNAME | FCOMMENT) != 0 {
+        return Err(InflateError::UnsupportedGzip);
+    }
+    let mtime = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+
+    let mut off = 10;
+    if flags & FEXTRA != 0 {
+        let xlen = data.get(off..off + 2).ok_or(InflateError::UnexpectedEof)?;
+        off += 2 + u16::from_le_bytes([xlen[0], xlen[1]]) as usize;
+    }
+    let mut name = None;
+    if flags & FNAME != 0 {
+        let rest = data.get(off..).ok_or(InflateError::UnexpectedEof)?;
+        let len = rest.iter().position(|&b| b == 0).ok_or(InflateError::UnexpectedEof)?;
+        name = Some(&rest[..len]);
+        off += len + 1;
+    }
+    if flags & FCOMMENT != 0 {
+        let rest = data.get(off..).ok_or(InflateError::UnexpectedEof)?;
+        let len = rest.iter().position(|&b| b == 0).ok_or(InflateError::UnexpectedEof)?;
+        off += len + 1;
+    }
+    if flags & FHCRC != 0 {
+        off += 2;
+    }
+    if off > data.len() {
+        return Err(InflateError::UnexpectedEof);
+    }
+
+    Ok(GzipHeader { name, mtime, header_len: off })
+}
+
+/// gzip ファイル全体を展開する（上限は DEFAULT_MAX_OUTPUT）
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+    gunzip_with_limit(data, DEFAULT_MAX_OUTPUT)
+}
+
+/// gzip ファイル全体を展開する（展開後 max_output バイトを超えたらエラー）
+///
+/// `cat a.gz b.gz > c.gz` のように複数のメンバーが連結されていれば、順に展開してつなげる。
+/// メンバーごとにトレーラの CRC32 と ISIZE（展開後のサイズの下位 32bit）を検証する。
+pub fn gunzip_with_limit(data: &[u8], max_output: usize) -> Result<Vec<u8>, InflateError> {
+    let mut out = Vec::new();
+    let mut rest = data;
+    loop {
+        let header = parse_gzip_header(rest)?;
+        let start = out.len();
+        let consumed = inflate_into(&rest[header.header_len..], &mut out, max_output)?;
+        let trailer_at = header.header_len + consumed;
+        let trailer = rest.get(trailer_at..trailer_at + 8).ok_or(InflateError::UnexpectedEof)?;
+        let isize = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        if isize != (out.len() - start) as u32 {
+            return Err(InflateError::SizeMismatch);
+        }
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        if crc != sabos_checksum_core::crc32(&out[start..]) {
+            return Err(InflateError::ChecksumMismatch);
+        }
+        rest = &rest[trailer_at + 8..];
+        if rest.is_empty() {
+            return Ok(out);
+        }
+    }
+}
+
+/// DEFLATE のストリームを展開する（上限は DEFAULT_MAX_OUTPUT）
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, InflateError> {
+    inflate_with_limit(data, DEFAULT_MAX_OUTPUT)
+}
+
+/// DEFLATE のストリームを展開する（展開後 max_output バイトを超えたらエラー）
+///
+/// 最後のブロック (BFINAL) の後ろに残ったバイトは無視する。
+pub fn inflate_with_limit(data: &[u8], max_output: usize) -> Result<Vec<u8>, InflateError> {
+    let mut out = Vec::new();
+    inflate_into(data, &mut out, max_output)?;
+    Ok(out)
+}
+
+/// DEFLATE のストリームを展開して out に追記し、読んだ入力のバイト数を返す
+///
+/// max_output は out 全体（追記前の分も含む）の上限。
+/// 戻り値はバイト境界に切り上げてあるので、gzip / zip ではその直後がトレーラになる。
+pub fn inflate_into(data: &[u8], out: &mut Vec<u8>, max_output: usize) -> Result<usize, InflateError> {
+    let mut s = State { input: BitReader::new(data), out, max_output };
+    loop {
+        let last = s.input.bits(1)? == 1;
+        match s.input.bits(2)? {
+            0 => s.stored()?,
+            1 => s.fixed()?,
+            2 => s.dynamic()?,
+            _ => return Err(InflateError::InvalidBlockType),
+        }
+        if last {
+            return Ok(s.input.pos);
+        }
+    }
+}
+
+/// 入力をビット単位で読む（各バイトの下位ビットから）
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// 次に bitbuf へ取り込むバイトの位置
+    pos: usize,
+    bitbuf: u32,
+    bitcnt: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0, bitbuf: 0, bitcnt: 0 }
+    }
+
+    /// n ビット（最大 16）読む
+    fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+        while self.bitcnt < n {
+            let byte = *self.data.get(self.pos).ok_or(InflateError::UnexpectedEof)?;
+            self.bitbuf |= (byte as u32) << self.bitcnt;
+            self.pos += 1;
+            self.bitcnt += 8;
+        }
+        let v = self.bitbuf & ((1u32 << n) - 1);
+        self.bitbuf >>= n;
+        self.bitcnt -= n;
+        Ok(v)
+    }
+
+    /// 読みかけのバイトの残りビットを捨てて、バイト境界にそろえる
+    fn align_to_byte(&mut self) {
+        self.bitbuf = 0;
+        self.bitcnt = 0;
+    }
+}
+
+/// 符号長の最大値
+const MAX_BITS: usize = 15;
+/// リテラル/長さ符号の数（286, 287 は使われないが固定符号の表には含まれる）
+const MAX_LCODES: usize = 288;
+/// 距離符号の数
+const MAX_DCODES: usize = 30;
+
+/// 正準ハフマン符号の表
+///
+/// count[len] は長さ len の符号の数、symbol は符号の小さい順に並べたシンボル。
+/// 正準ハフマン符号では、この 2 つだけで符号からシンボルを引ける。
+struct Huffman {
+    count: [u16; MAX_BITS + 1],
+    symbol: [u16; MAX_LCODES],
+}
+
+impl Huffman {
+    /// 各シンボルの符号長から表を作る
+    ///
+    /// 符号が足りない（不完全な）表は許す。距離符号が 1 つだけのストリームは
+    /// 正しいものでもそうなるため。使われていない符号が現れたら decode がエラーにする。
+    fn new(lengths: &[u8]) -> Result<Self, InflateError> {
+        let mut h = Huffman { count: [0; MAX_BITS + 1], symbol: [0; MAX_LCODES] };
+        for &len in lengths {
+            h.count[len as usize] += 1;
+        }
+        // 符号が多すぎないか確認する（長さ len で使える符号の残りを数える）
+        let mut left: i32 = 1;
+        for len in 1..=MAX_BITS {
+            left <<= 1;
+            left -= h.count[len] as i32;
+            if left < 0 {
+                return Err(InflateError::InvalidCode);
+            }
+        }
+
+        // 長さごとの先頭位置を求め、シンボルを符号順に並べる
+        let mut offs = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offs[len + 1] = offs[len] + h.count[len];
+        }
+        for (sym, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                h.symbol[offs[len as usize] as usize] = sym as u16;
+                offs[len as usize] += 1;
+            }
+        }
+        Ok(h)
+    }
+}
+
+/// 長さ符号 257..285 の基準値と追加ビット数
+const LEN_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// 距離符号 0..29 の基準値と追加ビット数
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// 動的ハフマンブロックで符号長の符号を並べる順序
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct State<'a, 'b> {
+    input: BitReader<'a>,
+    out: &'b mut Vec<u8>,
+    max_output: usize,
+}
+
+impl State<'_, '_> {
+    /// 出力が上限を超えないか確認する
+    fn reserve(&self, n: usize) -> Result<(), InflateError> {
+        if self.out.len().saturating_add(n) > self.max_output {
+            return Err(InflateError::OutputTooLarge);
+        }
+        Ok(())
+    }
+
+    /// 非圧縮ブロック
+    fn stored(&mut self) -> Result<(), InflateError> {
+        self.input.align_to_byte();
+        let pos = self.input.pos;
+        let header = self.input.data.get(pos..pos + 4).ok_or(InflateError::UnexpectedEof)?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        let nlen = u16::from_le_bytes([header[2], header[3]]);
+        if len != !nlen {
+            return Err(InflateError::InvalidStoredLength);
+        }
+        let start = pos + 4;
+        let body = self.input.data.get(start..start + len as usize).ok_or(InflateError::UnexpectedEof)?;
+        self.reserve(body.len())?;
+        self.out.extend_from_slice(body);
+        self.input.pos = start + len as usize;
+        Ok(())
+    }
+
+    /// 固定ハフマン符号のブロック（RFC 1951 3.2.6）
+    fn fixed(&mut self) -> Result<(), InflateError> {
+        let mut lengths = [0u8; MAX_LCODES];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        let lencode = Huffman::new(&lengths)?;
+        let distcode = Huffman::new(&[5u8; MAX_DCODES])?;
+        self.codes(&lencode, &distcode)
+    }
+
+    /// 動的ハフマン符号のブロック（RFC 1951 3.2.7）
+    fn dynamic(&mut self) -> Result<(), InflateError> {
+        let nlen = self.input.bits(5)? as usize + 257;
+        let ndist = self.input.bits(5)? as usize + 1;
+        let ncode = self.input.bits(4)? as usize + 4;
+        if nlen > 286 || ndist > MAX_DCODES {
+            return Err(InflateError::InvalidCode);
+        }
+
+        // まず符号長そのものを符号化するための表を読む
+        let mut lengths = [0u8; MAX_LCODES + MAX_DCODES];
+        for &idx in &CODE_LENGTH_ORDER[..ncode] {
+            lengths[idx] = self.input.bits(3)? as u8;
+        }
+        let lencode = Huffman::new(&lengths[..19])?;
+
+        // リテラル/長さ符号と距離符号の符号長を続けて読む（16/17/18 は繰り返し）
+        let mut index = 0;
+        while index < nlen + ndist {
+            let sym = self.decode(&lencode)?;
+            let (value, repeat) = match sym {
+                0..=15 => {
+                    lengths[index] = sym as u8;
+                    index += 1;
+                    continue;
+                }
+                16 => {
+                    if index == 0 {
+                        return Err(InflateError::InvalidCode);
+                    }
+                    (lengths[index - 1], 3 + self.input.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.input.bits(3)? as usize),
+                _ => (0, 11 + self.input.bits(7)? as usize),
+            };
+            if index + repeat > nlen + ndist {
+                return Err(InflateError::InvalidCode);
+            }
+            lengths[index..index + repeat].fill(value);
+            index += repeat;
+        }
+        // ブロックの終わり (256) の符号がなければ終われない
+        if lengths[256] == 0 {
+            return Err(InflateError::InvalidCode);
+        }
+
+        let lencode = Huffman::new(&lengths[..nlen])?;
+        let distcode = Huffman::new(&lengths[nlen..nlen + ndist])?;
+        self.codes(&lencode, &distcode)
+    }
+
+    /// 符号を 1 つ読んでシンボルを返す
+    ///
+    /// 長さ 1 の符号から順に「この長さの符号の範囲に入っているか」を調べる。
+    fn decode(&mut self, h: &Huffman) -> Result<u16, InflateError> {
+        let mut code: i32 = 0; // これまでに読んだビット列
+        let mut first: i32 = 0; // 長さ len の最初の符号
+        let mut index: i32 = 0; // 長さ len の最初のシンボルの symbol 上の位置
+        for len in 1..=MAX_BITS {
+            code |= self.input.bits(1)? as i32;
+            let count = h.count[len] as i32;
+            if code - count < first {
+                return Ok(h.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(InflateError::InvalidCode)
+    }
+
+    /// ハフマン符号で圧縮されたブロックの本体を展開する
+    fn codes(&mut self, lencode: &Huffman, distcode: &Huffman) -> Result<(), InflateError> {
+        loop {
+            let sym = self.decode(lencode)? as usize;
+            if sym < 256 {
+                self.reserve(1)?;
+                self.out.push(sym as u8);
+                continue;
+            }
+            if sym == 256 {
+                return Ok(());
+            }
+
+            // 長さと距離の組: 出力の dist バイト前から len バイトをコピーする
+            let sym = sym - 257;
+            if sym >= LEN_BASE.len() {
+                return Err(InflateError::InvalidCode);
+            }
+            let len = LEN_BASE[sym] as usize + self.input.bits(LEN_EXTRA[sym] as u32)? as usize;
+            let dsym = self.decode(distcode)? as usize;
+            if dsym >= DIST_BASE.len() {
+                return Err(InflateError::InvalidCode);
+            }
+            let dist = DIST_BASE[dsym] as usize + self.input.bits(DIST_EXTRA[dsym] as u32)? as usize;
+            if dist > self.out.len() {
+                return Err(InflateError::InvalidDistance);
+            }
+            self.reserve(len)?;
+            // コピー元とコピー先が重なることがある（dist < len で直前のパターンを繰り返す）
+            // ので、1 バイトずつ積む
+            let start = self.out.len() - dist;
+            for i in 0..len {
+                let b = self.out[start + i];
+                self.out.push(b);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::format;
+    use std::string::String;
+    use std::vec;
+
+    const HELLO_GZ: &[u8] = include_bytes!("../testdata/hello.txt.gz");
+    const STORED_GZ: &[u8] = include_bytes!("../testdata/stored.gz");
+    const TEXT_GZ: &[u8] = include_bytes!("../testdata/text.gz");
+
+    /// text.gz の元の内容
+    fn text_fixture() -> Vec<u8> {
+        let mut s = String::new();
+        for i in 0..200 {
+            s.push_str(&format!("{}: The quick brown fox jumps over the lazy dog.\n", i));
+        }
+        s.into_bytes()
+    }
+
+    #[test]
+    fn test_gzip_header() {
+        let h = parse_gzip_header(HELLO_GZ).unwrap();
+        assert_eq!(h.name, Some(&b"hello.txt"[..]));
+        // 2026-01-01 00:00:00 UTC
+        assert_eq!(h.mtime, 1767225600);
+        assert_eq!(h.header_len, 20);
+
+        assert_eq!(parse_gzip_header(b"PK\x03\x04"), Err(InflateError::NotGzip));
+        assert_eq!(parse_gzip_header(&HELLO_GZ[..12]), Err(InflateError::UnexpectedEof));
+        let mut bad = HELLO_GZ.to_vec();
+        bad[2] = 0; // deflate 以外の圧縮方式
+        assert_eq!(parse_gzip_header(&bad), Err(InflateError::UnsupportedGzip));
+    }
+
+    #[test]
+    fn test_gunzip_fixed() {
+        assert_eq!(gunzip(HELLO_GZ).unwrap(), b"Hello, SABOS!\n");
+    }
+
+    #[test]
+    fn test_gunzip_stored() {
+        let expected: Vec<u8> = (0..4).flat_map(|_| 0..=255u8).collect();
+        assert_eq!(gunzip(STORED_GZ).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_gunzip_dynamic() {
+        assert_eq!(gunzip(TEXT_GZ).unwrap(), text_fixture());
+    }
+
+    #[test]
+    fn test_gunzip_multi_member() {
+        let mut data = HELLO_GZ.to_vec();
+        data.extend_from_slice(HELLO_GZ);
+        assert_eq!(gunzip(&data).unwrap(), b"Hello, SABOS!\nHello, SABOS!\n");
+    }
+
+    #[test]
+    fn test_gunzip_truncated_and_corrupt() {
+        // 途中で切れている
+        assert_eq!(gunzip(&TEXT_GZ[..TEXT_GZ.len() / 2]), Err(InflateError::UnexpectedEof));
+        // トレーラがない
+        assert_eq!(gunzip(&HELLO_GZ[..HELLO_GZ.len() - 8]), Err(InflateError::UnexpectedEof));
+        // ISIZE が合わない
+        let mut bad = HELLO_GZ.to_vec();
+        let n = bad.len();
+        bad[n - 4] ^= 1;
+        assert_eq!(gunzip(&bad), Err(InflateError::SizeMismatch));
+        // CRC32 が合わない
+        let mut bad = HELLO_GZ.to_vec();
+        bad[n - 8] ^= 1;
+        assert_eq!(gunzip(&bad), Err(InflateError::ChecksumMismatch));
+    }
+
+    #[test]
+    fn test_inflate_raw() {
+        // 固定ハフマン: "a" を 1 文字
+        assert_eq!(inflate(&[0x4b, 0x04, 0x00]).unwrap(), b"a");
+        // 非圧縮ブロック: "abc"
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c']).unwrap(), b"abc");
+        // 空のストリーム
+        assert_eq!(inflate(&[0x03, 0x00]).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_inflate_invalid() {
+        // BTYPE = 3
+        assert_eq!(inflate(&[0x07]), Err(InflateError::InvalidBlockType));
+        // LEN と NLEN が補数でない
+        assert_eq!(inflate(&[0x01, 0x03, 0x00, 0x00, 0x00]), Err(InflateError::InvalidStoredLength));
+        // 出力の前を指す距離（固定ハフマンで長さ 3・距離 1 を先頭に置く）
+        assert_eq!(inflate(&[0x03, 0x02]), Err(InflateError::InvalidDistance));
+        // 空の入力
+        assert_eq!(inflate(&[]), Err(InflateError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_output_limit() {
+        // 約 1 MiB のゼロを圧縮したストリームは数 KB しかないが、上限で止まる
+        let member = gzip_zeros_fixture();
+        assert!(member.len() < 8192);
+        assert_eq!(gunzip(&member).unwrap(), vec![0u8; ZEROS_LEN as usize]);
+        assert_eq!(gunzip_with_limit(&member, 4096), Err(InflateError::OutputTooLarge));
+        assert_eq!(gunzip_with_limit(STORED_GZ, 1000), Err(InflateError::OutputTooLarge));
+        assert_eq!(gunzip_with_limit(STORED_GZ, 1024).unwrap().len(), 1024);
+    }
+
+    /// gzip_zeros_fixture が展開後に出すゼロの数（リテラル 1 つ + 長さ 258 の組 4064 個）
+    const ZEROS_LEN: u32 = 1 + 258 * 4064;
+
+    /// ZEROS_LEN バイトのゼロを固定ハフマンで圧縮した gzip を組み立てる
+    ///
+    /// リテラル 0 を 1 つ置いた後、長さ 258・距離 1 の組を繰り返す。
+    fn gzip_zeros_fixture() -> Vec<u8> {
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+        let mut bitbuf: u32 = 0;
+        let mut bitcnt = 0;
+        // ハフマン符号は上位ビットから詰める
+        let mut put_code = |code: u32, n: u32| {
+            for i in (0..n).rev() {
+                bitbuf |= ((code >> i) & 1) << bitcnt;
+                bitcnt += 1;
+                if bitcnt == 8 {
+                    out.push(bitbuf as u8);
+                    bitbuf = 0;
+                    bitcnt = 0;
+                }
+            }
+        };
+        put_code(0b110, 3); // BFINAL = 1, BTYPE = 1（下位ビットから読むので 1, 1, 0 の順に並ぶ）
+        put_code(0x30, 8); // リテラル 0
+        for _ in 0..(ZEROS_LEN - 1) / 258 {
+            put_code(0xc5, 8); // 長さ符号 285 (258)
+            put_code(0, 5); // 距離 1
+        }
+        put_code(0, 7); // ブロックの終わり (256)
+        put_code(0, 7); // 残りのビットを 0 で埋める
+        let crc = sabos_checksum_core::crc32(&vec![0u8; ZEROS_LEN as usize]);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.extend_from_slice(&ZEROS_LEN.to_le_bytes());
+        out
+    }
+}
@@ -0,0 +1,409 @@
+#![no_std]
+
+// line-editor — 対話プログラム向けの 1 行編集
+//
+// カーネルのシェルと ED.ELF で共有する。1 文字ずつ feed() に渡すと、
+// 行バッファ・カーソル・履歴を更新し、画面への反映を RenderSink に書き出す。
+//
+// 扱うキー:
+//   表示可能な文字  カーソル位置に挿入
+//   Backspace       カーソルの前の 1 文字を削除（0x08 と DEL 0x7F）
+//   ←/→            カーソル移動（ESC [ D / ESC [ C）
+//   ↑/↓            履歴の呼び出し（ESC [ A / ESC [ B）
+//   Enter           行を確定して履歴に積む（'\n' と '\r'）
+//   Tab             呼び出し側に知らせるだけ（補完の中身はプログラムごとに違うため）
+//
+// 描画は ANSI エスケープシーケンスで行う。フレームバッファのコンソールも
+// シリアル端末もこれを解釈するので、出力先を問わず同じ文字列でよい。
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use core::ops::Range;
+
+/// 編集結果の出力先
+pub trait RenderSink {
+    /// 文字列をそのまま出力する（ANSI エスケープシーケンスを含む）
+    fn write(&mut self, s: &str);
+
+    /// プロンプトを出力する。行全体を描き直すとき、行頭に戻った直後に呼ばれる
+    fn prompt(&mut self);
+}
+
+/// feed() に 1 文字渡した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// 編集中。呼び出し側は何もしなくてよい
+    Pending,
+    /// Enter で行が確定した
+    Submit(String),
+    /// Tab が押された。補完するなら replace_range() と redraw() を使う
+    Tab,
+}
+
+/// 入力中のエスケープシーケンスの解釈状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscState {
+    /// 通常の文字
+    Normal,
+    /// ESC を受け取った直後
+    Escape,
+    /// "ESC [" の後、終端文字待ち（"ESC [ 1 ; 5 A" のような修飾付きも読み飛ばす）
+    Csi,
+}
+
+/// 行バッファ・カーソル・履歴を持つ 1 行エディタ
+pub struct LineEditor {
+    /// 現在入力中の行
+    buffer: String,
+    /// 行内のカーソル位置（バイト単位。常に char 境界）
+    cursor: usize,
+    /// 確定した行の履歴（古い順）
+    history: VecDeque<String>,
+    /// 覚えておく履歴の件数。古いものから捨てる
+    history_max: usize,
+    /// 上下キーで履歴を辿っている最中なら、表示中の履歴のインデックス
+    history_pos: Option<usize>,
+    /// 履歴を辿り始める前に入力していた行。下キーで最後まで戻ったら復元する
+    saved_line: String,
+    /// 矢印キーのエスケープシーケンスの解釈状態
+    esc_state: EscState,
+}
+
+impl LineEditor {
+    /// 履歴を history_max 件まで覚えるエディタを作る。0 なら履歴を持たない
+    pub fn new(history_max: usize) -> Self {
+        Self {
+            buffer: String::new(),
+            cursor: 0,
+            history: VecDeque::new(),
+            history_max,
+            history_pos: None,
+            saved_line: String::new(),
+            esc_state: EscState::Normal,
+        }
+    }
+
+    /// 入力中の行
+    pub fn line(&self) -> &str {
+        &self.buffer
+    }
+
+    /// 行内のカーソル位置（バイト単位）
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// 確定した行の履歴（古い順）
+    pub fn history(&self) -> &VecDeque<String> {
+        &self.history
+    }
+
+    /// 1 文字ぶん行を編集する
+    pub fn feed(&mut self, c: char, sink: &mut dyn RenderSink) -> Event {
+        match self.esc_state {
+            EscState::Escape => {
+                // ESC [ 以外（Alt+キー等）は読み捨てる
+                self.esc_state = if c == '[' { EscState::Csi } else { EscState::Normal };
+                return Event::Pending;
+            }
+            EscState::Csi => {
+                match c {
+                    // パラメータ部分は読み飛ばす
+                    '0'..='9' | ';' => {}
+                    '@'..='~' => {
+                        self.esc_state = EscState::Normal;
+                        self.handle_csi(c, sink);
+                    }
+                    _ => self.esc_state = EscState::Normal,
+                }
+                return Event::Pending;
+            }
+            EscState::Normal => {}
+        }
+
+        match c {
+            // Enter: 行を確定して履歴に積む
+            '\n' | '\r' => {
+                let line = core::mem::take(&mut self.buffer);
+                self.cursor = 0;
+                self.history_pos = None;
+                self.saved_line.clear();
+                self.push_history(&line);
+                return Event::Submit(line);
+            }
+            '\x1b' => self.esc_state = EscState::Escape,
+            '\t' => return Event::Tab,
+            // Backspace: カーソルの前の 1 文字を削除
+            '\x08' | '\x7f' => {
+                if let Some(prev) = self.buffer[..self.cursor].chars().next_back() {
+                    self.cursor -= prev.len_utf8();
+                    self.buffer.remove(self.cursor);
+                    if self.cursor == self.buffer.len() {
+                        // 行末なら 1 文字戻って空白で消し、また戻る（シリアル端末の 0x08 は戻るだけなので）
+                        sink.write("\x08 \x08");
+                    } else {
+                        self.redraw(sink);
+                    }
+                }
+            }
+            // 表示可能な文字: カーソル位置に挿入してエコー
+            c if !c.is_control() => {
+                self.buffer.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+                if self.cursor == self.buffer.len() {
+                    let mut utf8 = [0u8; 4];
+                    sink.write(c.encode_utf8(&mut utf8));
+                } else {
+                    self.redraw(sink);
+                }
+            }
+            // その他の制御文字: 無視
+            _ => {}
+        }
+        Event::Pending
+    }
+
+    /// 行の range の部分を text に置き換え、カーソルを置き換えた部分の直後に置く。
+    /// 画面は描き直さないので、必要なら続けて redraw() を呼ぶ。
+    ///
+    /// range は char 境界でなければならない（String::replace_range と同じく panic する）。
+    pub fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        let start = range.start;
+        self.buffer.replace_range(range, text);
+        self.cursor = start + text.len();
+    }
+
+    /// プロンプトから行全体を描き直し、画面上のカーソルを行内のカーソル位置に合わせる。
+    ///
+    /// 行頭に戻って（\r）書き直し、行末の残りを ESC[K で消し、ESC[nD でカーソルを戻す。
+    /// 画面幅を超えて折り返した長い行は正しく描き直せない（カーソル移動が行をまたげないため）。
+    pub fn redraw(&self, sink: &mut dyn RenderSink) {
+        sink.write("\r");
+        sink.prompt();
+        sink.write(&self.buffer);
+        sink.write("\x1b[K");
+        let back = self.buffer[self.cursor..].chars().count();
+        if back > 0 {
+            sink.write(&format!("\x1b[{}D", back));
+        }
+    }
+
+    /// 矢印キー（CSI の終端文字）を処理する。それ以外の CSI は無視する。
+    fn handle_csi(&mut self, key: char, sink: &mut dyn RenderSink) {
+        match key {
+            // 上: ひとつ古い履歴
+            'A' => {
+                let pos = match self.history_pos {
+                    None if !self.history.is_empty() => {
+                        self.saved_line = core::mem::take(&mut self.buffer);
+                        self.history.len() - 1
+                    }
+                    Some(pos) if pos > 0 => pos - 1,
+                    _ => return,
+                };
+                self.history_pos = Some(pos);
+                self.buffer = self.history[pos].clone();
+                self.cursor = self.buffer.len();
+                self.redraw(sink);
+            }
+            // 下: ひとつ新しい履歴。最新より先は履歴を辿る前の入力に戻る
+            'B' => {
+                let Some(pos) = self.history_pos else {
+                    return;
+                };
+                if pos + 1 < self.history.len() {
+                    self.history_pos = Some(pos + 1);
+                    self.buffer = self.history[pos + 1].clone();
+                } else {
+                    self.history_pos = None;
+                    self.buffer = core::mem::take(&mut self.saved_line);
+                }
+                self.cursor = self.buffer.len();
+                self.redraw(sink);
+            }
+            // 右: カーソルを 1 文字進める
+            'C' => {
+                if let Some(next) = self.buffer[self.cursor..].chars().next() {
+                    self.cursor += next.len_utf8();
+                    sink.write("\x1b[C");
+                }
+            }
+            // 左: カーソルを 1 文字戻す
+            'D' => {
+                if let Some(prev) = self.buffer[..self.cursor].chars().next_back() {
+                    self.cursor -= prev.len_utf8();
+                    sink.write("\x1b[D");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 確定した行を履歴に積む。空行と、直前と同じ行は積まない。
+    fn push_history(&mut self, line: &str) {
+        let line = line.trim();
+        if self.history_max == 0 || line.is_empty() || self.history.back().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.history.len() == self.history_max {
+            self.history.pop_front();
+        }
+        self.history.push_back(String::from(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// 出力を溜めておくだけの RenderSink
+    #[derive(Default)]
+    struct Recorder {
+        out: String,
+    }
+
+    impl RenderSink for Recorder {
+        fn write(&mut self, s: &str) {
+            self.out.push_str(s);
+        }
+
+        fn prompt(&mut self) {
+            self.out.push_str("> ");
+        }
+    }
+
+    const UP: &str = "\x1b[A";
+    const DOWN: &str = "\x1b[B";
+    const RIGHT: &str = "\x1b[C";
+    const LEFT: &str = "\x1b[D";
+
+    /// キー列を順に渡し、確定した行を返す
+    fn feed(ed: &mut LineEditor, sink: &mut Recorder, keys: &str) -> Vec<String> {
+        let mut submitted = Vec::new();
+        for c in keys.chars() {
+            if let Event::Submit(line) = ed.feed(c, sink) {
+                submitted.push(line);
+            }
+        }
+        submitted
+    }
+
+    #[test]
+    fn test_insert_echoes_and_submits() {
+        let mut ed = LineEditor::new(8);
+        let mut sink = Recorder::default();
+        feed(&mut ed, &mut sink, "ls あ");
+        assert_eq!(ed.line(), "ls あ");
+        assert_eq!(ed.cursor(), "ls あ".len());
+        // 行末への挿入はそのままエコーするだけ
+        assert_eq!(sink.out, "ls あ");
+        assert_eq!(feed(&mut ed, &mut sink, "\n"), ["ls あ"]);
+        assert_eq!(ed.line(), "");
+        assert_eq!(ed.cursor(), 0);
+    }
+
+    #[test]
+    fn test_delete_at_end_and_middle() {
+        let mut ed = LineEditor::new(8);
+        let mut sink = Recorder::default();
+        feed(&mut ed, &mut sink, "abc");
+        sink.out.clear();
+        feed(&mut ed, &mut sink, "\x08");
+        assert_eq!(ed.line(), "ab");
+        assert_eq!(sink.out, "\x08 \x08");
+
+        // 途中の削除は行を描き直し、カーソルを元の位置に戻す
+        feed(&mut ed, &mut sink, "cd");
+        feed(&mut ed, &mut sink, LEFT);
+        sink.out.clear();
+        feed(&mut ed, &mut sink, "\x7f");
+        assert_eq!(ed.line(), "abd");
+        assert_eq!(ed.cursor(), 2);
+        assert_eq!(sink.out, "\r> abd\x1b[K\x1b[1D");
+
+        // 行頭での Backspace は何もしない
+        let mut empty = LineEditor::new(8);
+        sink.out.clear();
+        feed(&mut empty, &mut sink, "\x08");
+        assert_eq!(empty.line(), "");
+        assert_eq!(sink.out, "");
+    }
+
+    #[test]
+    fn test_cursor_left_right() {
+        let mut ed = LineEditor::new(8);
+        let mut sink = Recorder::default();
+        feed(&mut ed, &mut sink, "aé");
+        feed(&mut ed, &mut sink, LEFT);
+        assert_eq!(ed.cursor(), 1);
+        // 修飾付き（ESC [ 1 ; 5 D）も左キー扱い。行頭より前には行かない
+        feed(&mut ed, &mut sink, "\x1b[1;5D");
+        feed(&mut ed, &mut sink, LEFT);
+        assert_eq!(ed.cursor(), 0);
+        feed(&mut ed, &mut sink, "X");
+        assert_eq!(ed.line(), "Xaé");
+        assert_eq!(ed.cursor(), 1);
+        feed(&mut ed, &mut sink, RIGHT);
+        feed(&mut ed, &mut sink, RIGHT);
+        feed(&mut ed, &mut sink, RIGHT);
+        assert_eq!(ed.cursor(), "Xaé".len());
+    }
+
+    #[test]
+    fn test_history_recall() {
+        let mut ed = LineEditor::new(8);
+        let mut sink = Recorder::default();
+        feed(&mut ed, &mut sink, "ls\npwd\n");
+        // 直前と同じ行と空行は積まない
+        feed(&mut ed, &mut sink, "pwd\n\n");
+        assert_eq!(ed.history().len(), 2);
+
+        // 入力途中で上キー → 新しい順に辿り、一番古いところで止まる。下キーで元の入力に戻る
+        feed(&mut ed, &mut sink, "ec");
+        feed(&mut ed, &mut sink, UP);
+        assert_eq!(ed.line(), "pwd");
+        feed(&mut ed, &mut sink, UP);
+        feed(&mut ed, &mut sink, UP);
+        assert_eq!(ed.line(), "ls");
+        feed(&mut ed, &mut sink, DOWN);
+        assert_eq!(ed.line(), "pwd");
+        feed(&mut ed, &mut sink, DOWN);
+        assert_eq!(ed.line(), "ec");
+        assert_eq!(ed.cursor(), 2);
+
+        // 呼び出した履歴を編集して確定できる
+        feed(&mut ed, &mut sink, UP);
+        assert_eq!(feed(&mut ed, &mut sink, "\x08\x08\x08cat\n"), ["cat"]);
+        assert_eq!(ed.history().back().map(String::as_str), Some("cat"));
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let mut ed = LineEditor::new(4);
+        let mut sink = Recorder::default();
+        for i in 0..6 {
+            feed(&mut ed, &mut sink, &alloc::format!("cmd{}\n", i));
+        }
+        assert_eq!(ed.history().len(), 4);
+        assert_eq!(ed.history().front().map(String::as_str), Some("cmd2"));
+
+        let mut no_history = LineEditor::new(0);
+        feed(&mut no_history, &mut sink, "ls\n");
+        assert!(no_history.history().is_empty());
+    }
+
+    #[test]
+    fn test_tab_and_replace_range() {
+        let mut ed = LineEditor::new(8);
+        let mut sink = Recorder::default();
+        feed(&mut ed, &mut sink, "cat he");
+        assert_eq!(ed.feed('\t', &mut sink), Event::Tab);
+        ed.replace_range(4..6, "HELLO.TXT ");
+        assert_eq!(ed.line(), "cat HELLO.TXT ");
+        assert_eq!(ed.cursor(), ed.line().len());
+    }
+}
@@ -25,6 +25,14 @@
 // - Futex: 120-129
 // - 時刻: 130-139
 // - ファイルハンドル操作拡張: 140-149
+// - シグナル: 160-169
+// - デバイス情報: 170-179
+// - ネットワーク拡張 2: 200-209
+// - グラフィックス拡張: 210-219
+// - クリップボード: 220-229
+// - タイマー: 230-239
+// - スケジューラ: 240-249
+// - プロセス拡張: 250-259
 
 #![no_std]
 
@@ -58,6 +66,7 @@ pub const SYS_DIR_CREATE: u64 = 15;  // dir_create(path_ptr, path_len) — デ
 pub const SYS_DIR_REMOVE: u64 = 16;  // dir_remove(path_ptr, path_len) — ディレクトリ削除
 pub const SYS_FS_STAT: u64 = 17;     // fs_stat(buf_ptr, buf_len) — ファイルシステム統計情報
 // 18: 予約（SYS_FS_REGISTER は削除済み — モノリシック化により不要）
+pub const SYS_DIR_REMOVE_ALL: u64 = 19; // dir_remove_all(path_ptr, path_len) — ディレクトリを中身ごと削除
 
 // =================================================================
 // システム情報 (20-29)
@@ -70,9 +79,40 @@ pub const SYS_GET_FB_INFO: u64 = 24;      // get_fb_info(buf_ptr, buf_len) — 
 pub const SYS_MOUSE_READ: u64 = 25;       // mouse_read(buf_ptr, buf_len) — マウス状態取得
 pub const SYS_CLOCK_MONOTONIC: u64 = 26;  // clock_monotonic() — 起動からの経過ミリ秒を返す
 pub const SYS_GETRANDOM: u64 = 27;        // getrandom(buf_ptr, len) — ランダムバイトを生成
-pub const SYS_MMAP: u64 = 28;             // mmap(addr_hint, len, prot, flags) — 匿名ページをマッピング
+pub const SYS_MMAP: u64 = 28;             // mmap(addr_hint, len, prot, flags) — 匿名ページ / ファイルをマッピング
 pub const SYS_MUNMAP: u64 = 29;           // munmap(addr, len) — ページのマッピングを解除
 
+/// SYS_MMAP の prot: 読み取り可能（必須）
+pub const MMAP_PROT_READ: u64 = 0x1;
+/// SYS_MMAP の prot: 書き込み可能
+pub const MMAP_PROT_WRITE: u64 = 0x2;
+/// SYS_MPROTECT の prot: 実行可能（書き込み可能と同時には指定できない: W^X）
+pub const MMAP_PROT_EXEC: u64 = 0x4;
+/// SYS_MPROTECT の prot: アクセス不可（触るとページフォルトでプロセスが終了する）
+pub const MMAP_PROT_NONE: u64 = 0x0;
+/// SYS_MMAP の flags: 匿名マッピング（ファイルに紐付かない、ゼロ初期化）
+pub const MMAP_FLAG_ANONYMOUS: u64 = 0x1;
+/// SYS_MMAP の flags: addr_hint ちょうどにマッピングする。
+/// 既存の領域と重なるときは別の場所に移さずエラーにする
+pub const MMAP_FLAG_FIXED: u64 = 0x2;
+/// SYS_MMAP の flags: ファイルマッピング。arg1 は addr_hint ではなく MmapFileArgs のポインタになる
+pub const MMAP_FLAG_FILE: u64 = 0x4;
+
+/// SYS_MMAP に MMAP_FLAG_FILE を付けたときの引数構造体（arg1 にこのポインタを渡す）
+///
+/// 引数が 4 つに収まらないので、ハンドル・ファイル内オフセット・addr_hint をまとめて渡す。
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MmapFileArgs {
+    /// READ 権限付きのファイルハンドル（Handle の id と token）
+    pub handle_id: u64,
+    pub handle_token: u64,
+    /// マッピングするファイル内の開始位置（4KiB アライン）
+    pub offset: u64,
+    /// マッピング先アドレスのヒント（0 ならカーネルが決定）
+    pub addr_hint: u64,
+}
+
 // =================================================================
 // プロセス管理 (30-39)
 // =================================================================
@@ -82,7 +122,7 @@ pub const SYS_YIELD: u64 = 32;   // yield() — CPU を譲る
 pub const SYS_SLEEP: u64 = 33;   // sleep(ms) — 指定ミリ秒スリープ
 pub const SYS_WAIT: u64 = 34;    // wait(task_id, timeout_ms) — 子プロセスの終了を待つ
 pub const SYS_GETPID: u64 = 35;  // getpid() — 自分のタスク ID を取得
-pub const SYS_KILL: u64 = 36;    // kill(task_id) — タスクを強制終了
+pub const SYS_KILL: u64 = 36;    // kill(task_id, signal) — タスクにシグナルを送る（signal=0 は SIG_KILL）
 pub const SYS_GETENV: u64 = 37;  // getenv(key_ptr, key_len, val_buf_ptr, val_buf_len) — 環境変数を取得
 pub const SYS_SETENV: u64 = 38;  // setenv(key_ptr, key_len, val_ptr, val_len) — 環境変数を設定
 pub const SYS_LISTENV: u64 = 39; // listenv(buf_ptr, buf_len) — 全環境変数を一覧取得
@@ -98,6 +138,42 @@ pub const SYS_NET_TCP_CLOSE: u64 = 44;    // net_tcp_close(conn_id) → 0/-1
 pub const SYS_NET_SEND_FRAME: u64 = 45;   // net_send_frame(buf_ptr, len) — Ethernet フレーム送信（当面残す）
 pub const SYS_NET_RECV_FRAME: u64 = 46;   // net_recv_frame(buf_ptr, len, timeout_ms) — Ethernet フレーム受信（当面残す）
 pub const SYS_NET_GET_MAC: u64 = 47;      // net_get_mac(buf_ptr, len) — MAC アドレス取得
+pub const SYS_NET_SET_CONFIG: u64 = 48;   // net_set_config(args_ptr) — 静的 IP 設定（以降 DHCP を使わない）
+pub const SYS_NET_CAPTURE_OPEN: u64 = 49; // net_capture_open(flags, handle_out_ptr) — フレームキャプチャ用ハンドルを開く
+
+/// SYS_NET_SET_CONFIG の引数構造体（IPv4 アドレスはすべてネットワークバイトオーダー）
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct NetConfigArgs {
+    pub ip: [u8; 4],
+    pub subnet_mask: [u8; 4],
+    /// 0.0.0.0 ならデフォルトルートなし
+    pub gateway: [u8; 4],
+    pub dns: [u8; 4],
+}
+
+/// SYS_NET_CAPTURE_OPEN の flags: 送信したフレームもキャプチャする
+pub const CAPTURE_FLAG_TX: u64 = 1;
+
+/// キャプチャしたフレームの向き: 受信
+pub const CAPTURE_DIR_RX: u8 = 0;
+/// キャプチャしたフレームの向き: 送信
+pub const CAPTURE_DIR_TX: u8 = 1;
+
+/// キャプチャハンドルの read 1 回分の先頭に付くヘッダ（直後にフレーム本体が続く）
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CaptureFrameHeader {
+    /// 起動からの経過時間（ミリ秒）
+    pub timestamp_ms: u64,
+    /// フレーム本来の長さ（バッファが小さいと本体はこれより短く切られる）
+    pub len: u32,
+    /// このキャプチャでキューあふれにより捨てたフレームの累計
+    pub dropped: u32,
+    /// CAPTURE_DIR_RX / CAPTURE_DIR_TX
+    pub direction: u8,
+    pub _reserved: [u8; 7],
+}
 
 // =================================================================
 // システム制御 (50-59)
@@ -106,8 +182,19 @@ pub const SYS_HALT: u64 = 50;        // halt() — システム停止
 pub const SYS_DRAW_PIXEL: u64 = 51;  // draw_pixel(x, y, rgb) — 1ピクセル描画
 pub const SYS_DRAW_RECT: u64 = 52;   // draw_rect(x, y, w_h, rgb) — 矩形描画（w/h は packed）
 pub const SYS_DRAW_LINE: u64 = 53;   // draw_line(xy0, xy1, rgb) — 直線描画（x,y は packed）
-pub const SYS_DRAW_BLIT: u64 = 54;   // draw_blit(x, y, w_h, buf_ptr) — 画像描画
+pub const SYS_DRAW_BLIT: u64 = 54;   // draw_blit(x_fmt, y, w_h, buf_ptr) — 画像描画（x の上位 32bit はピクセル並び）
 pub const SYS_DRAW_TEXT: u64 = 55;   // draw_text(xy, fg_bg, buf_ptr, len) — 文字列描画
+pub const SYS_FB_SET_DOUBLE_BUFFER: u64 = 56; // fb_set_double_buffer(enabled) — ダブルバッファモード切替
+pub const SYS_FB_PRESENT: u64 = 57;   // fb_present() — バックバッファを画面に一括転送
+pub const SYS_FB_SET_CLIP: u64 = 58;  // fb_set_clip(x, y, w, h) — クリップ領域を設定（w = h = 0 で解除）
+pub const SYS_SET_CURSOR: u64 = 59;   // set_cursor(bitmap_ptr, w, h, hotspot) — マウスカーソルの設定（w = h = 0 で非表示）
+
+/// SYS_DRAW_BLIT のピクセル並び: ハードウェアと同じ（変換なしの高速パス）
+pub const BLIT_FORMAT_NATIVE: u64 = 0;
+/// SYS_DRAW_BLIT のピクセル並び: R, G, B, X（ハードウェアが BGR ならカーネルが変換する）
+pub const BLIT_FORMAT_RGBX: u64 = 1;
+/// SYS_DRAW_BLIT のフラグ（ピクセル並びと OR する）: 4 バイト目をアルファ値として画面の内容と合成する
+pub const BLIT_FLAG_ALPHA: u64 = 0x100;
 
 // =================================================================
 // 終了 (60)
@@ -147,6 +234,10 @@ pub const SYS_IPC_RECV_FROM: u64 = 95;   // ipc_recv_from(from_sender, sender_pt
 // サウンド (100-109)
 // =================================================================
 pub const SYS_SOUND_PLAY: u64 = 100;  // sound_play(freq_hz, duration_ms) — 正弦波ビープ音再生
+pub const SYS_SOUND_PLAY_PCM: u64 = 101; // sound_play_pcm(buf_ptr, len, sample_rate, flags) — 16bit stereo PCM 再生
+
+/// SYS_SOUND_PLAY_PCM の flags: 再生完了を待たず、キューに入った分だけで戻る
+pub const SOUND_PCM_NONBLOCK: u64 = 1;
 
 // =================================================================
 // スレッド (110-119)
@@ -154,6 +245,8 @@ pub const SYS_SOUND_PLAY: u64 = 100;  // sound_play(freq_hz, duration_ms) — 
 pub const SYS_THREAD_CREATE: u64 = 110; // thread_create(entry_ptr, stack_ptr, arg) -> thread_id
 pub const SYS_THREAD_EXIT: u64 = 111;   // thread_exit(exit_code) — スレッド終了
 pub const SYS_THREAD_JOIN: u64 = 112;   // thread_join(thread_id, timeout_ms) -> exit_code
+pub const SYS_THREAD_SET_TLS: u64 = 113; // thread_set_tls(ptr) — 現在のスレッドの TLS ポインタを設定
+pub const SYS_THREAD_GET_TLS: u64 = 114; // thread_get_tls() -> ptr — 現在のスレッドの TLS ポインタ
 
 // =================================================================
 // Futex (120-129)
@@ -171,17 +264,28 @@ pub const SYS_CLOCK_REALTIME: u64 = 130; // clock_realtime() — UNIX エポッ
 pub const SYS_HANDLE_CREATE_FILE: u64 = 140; // handle_create_file(dir_handle_ptr, name_ptr, name_len, out_handle_ptr) — ディレクトリ内にファイルを作成
 pub const SYS_HANDLE_UNLINK: u64 = 141;      // handle_unlink(dir_handle_ptr, name_ptr, name_len) — ディレクトリ内のファイル/ディレクトリを削除
 pub const SYS_HANDLE_MKDIR: u64 = 142;       // handle_mkdir(dir_handle_ptr, name_ptr, name_len) — ディレクトリ内にサブディレクトリを作成
+pub const SYS_FSYNC: u64 = 143;              // fsync(handle_ptr) — ハンドルに書き込んだ内容をディスクに書き戻す
 
 // =================================================================
 // ネットワーク拡張 (150-159) — TCP listen/accept, UDP, IPv6 ping
 // =================================================================
-pub const SYS_NET_TCP_LISTEN: u64 = 150;     // net_tcp_listen(port) → 0/-1
-pub const SYS_NET_TCP_ACCEPT: u64 = 151;     // net_tcp_accept(timeout_ms, listen_port) → conn_id/-1
+pub const SYS_NET_TCP_LISTEN: u64 = 150;     // net_tcp_listen(port, backlog) → 0/-1（backlog 0 = 既定値）
+pub const SYS_NET_TCP_ACCEPT: u64 = 151;     // net_tcp_accept(timeout_ms, listen_port, peer_info_ptr) → conn_id/-1（相手は 6 バイト）
 pub const SYS_NET_UDP_BIND: u64 = 152;       // net_udp_bind(port) → socket_id|(port<<32)
 pub const SYS_NET_UDP_SEND_TO: u64 = 153;    // net_udp_send_to(args_struct_ptr) → 0/-1
 pub const SYS_NET_UDP_RECV_FROM: u64 = 154;  // net_udp_recv_from(args_struct_ptr) → bytes/-1
 pub const SYS_NET_UDP_CLOSE: u64 = 155;      // net_udp_close(socket_id) → 0/-1
 pub const SYS_NET_PING6: u64 = 156;          // net_ping6(dst_ip_ptr, timeout_ms, src_ip_ptr) → 0/-1
+pub const SYS_NET_UDP_SEND_TO6: u64 = 157;   // net_udp_send_to6(args_struct_ptr) → 0/-1
+pub const SYS_NET_UDP_RECV_FROM6: u64 = 158; // net_udp_recv_from6(args_struct_ptr) → bytes/-1（送信元は 18 バイト）
+pub const SYS_NET_TCP_SHUTDOWN: u64 = 159;   // net_tcp_shutdown(conn_id, how) → 0/-1（half-close）
+
+/// SYS_NET_TCP_SHUTDOWN の how: 受信側を閉じる（値は POSIX に合わせている）
+pub const SHUT_RD: u64 = 0;
+/// SYS_NET_TCP_SHUTDOWN の how: 送信側を閉じる（FIN を送るが受信は続けられる）
+pub const SHUT_WR: u64 = 1;
+/// SYS_NET_TCP_SHUTDOWN の how: 両方閉じる
+pub const SHUT_RDWR: u64 = 2;
 
 /// UDP send_to の引数構造体（ユーザー空間でスタック上に作成してポインタで渡す）
 #[repr(C)]
@@ -196,6 +300,7 @@ pub struct UdpSendToArgs {
 
 /// UDP recv_from の引数構造体
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct UdpRecvFromArgs {
     pub socket_id: u32,
     pub _pad: u32,
@@ -204,3 +309,236 @@ pub struct UdpRecvFromArgs {
     pub timeout_ms: u64,
     pub src_info_ptr: u64, // [u8; 6] = [ip0, ip1, ip2, ip3, port_lo, port_hi]
 }
+
+/// UDP send_to6 の引数構造体（宛先が IPv6 アドレス）
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UdpSendTo6Args {
+    pub socket_id: u32,
+    pub dst_port: u16,
+    pub _pad: u16,
+    pub dst_ip: [u8; 16],
+    pub data_ptr: u64,
+    pub data_len: u64,
+}
+
+/// SYS_NET_UDP_RECV_FROM6 が書き込む送信元情報のサイズ。
+/// 引数は UdpRecvFromArgs と同じで、src_info_ptr の先に
+/// [アドレス 16 バイト, port_lo, port_hi] を書く。
+/// IPv4 の送信元は IPv4-mapped アドレス (::ffff:a.b.c.d) で表す。
+pub const UDP_SRC_INFO6_LEN: usize = 18;
+
+// =================================================================
+// シグナル (160-169)
+// =================================================================
+pub const SYS_SIGPENDING: u64 = 160; // sigpending() — 保留中シグナルのビットマスクを取得してクリア
+
+/// SIG_KILL: 即座に強制終了する（ハンドラなし、無視できない）
+pub const SIG_KILL: u64 = 9;
+/// SIG_TERM: 終了要求。デフォルト動作は「保留フラグを立てるだけ」で、
+/// プロセスは SYS_SIGPENDING でポーリングして後始末してから exit する。
+pub const SIG_TERM: u64 = 15;
+
+// =================================================================
+// デバイス情報 (170-179)
+// =================================================================
+pub const SYS_PCI_READ_BAR: u64 = 170; // pci_read_bar(bdf, bar_index, out_ptr) — BAR のベース/サイズ/種類を取得
+
+/// PciBarInfo.kind: 32 ビットメモリ BAR
+pub const PCI_BAR_KIND_MEM32: u32 = 0;
+/// PciBarInfo.kind: 64 ビットメモリ BAR（BAR を 2 つ消費する）
+pub const PCI_BAR_KIND_MEM64: u32 = 1;
+/// PciBarInfo.kind: I/O ポート BAR
+pub const PCI_BAR_KIND_IO: u32 = 2;
+/// PciBarInfo.flags: プリフェッチ可能なメモリ BAR
+pub const PCI_BAR_FLAG_PREFETCHABLE: u32 = 1;
+
+/// SYS_PCI_READ_BAR の結果構造体（カーネルがユーザーバッファに書き込む）
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct PciBarInfo {
+    pub base: u64,
+    pub size: u64,
+    pub kind: u32,
+    pub flags: u32,
+}
+
+// =================================================================
+// 統一ソケット API (180-189) — ソケットをハンドルとして扱う
+// =================================================================
+// プロトコルごとの SYS_NET_TCP_* / SYS_NET_UDP_* と違い、TCP も UDP も
+// 同じハンドル（SYS_OPEN と同じ Handle 構造体）で操作する。
+// ハンドルなので SYS_HANDLE_READ / WRITE / CLOSE もそのまま使える。
+pub const SYS_SOCKET: u64 = 180;     // socket(domain, type, proto, handle_out_ptr) — ソケット作成
+pub const SYS_BIND: u64 = 181;       // bind(handle_ptr, addr_ptr) — ローカルアドレスに割り当て（実際のアドレスを書き戻す）
+pub const SYS_CONNECT: u64 = 182;    // connect(handle_ptr, addr_ptr) — TCP は接続、UDP は既定の宛先を設定
+pub const SYS_LISTEN: u64 = 183;     // listen(handle_ptr, backlog) — TCP の待ち受け開始
+pub const SYS_ACCEPT: u64 = 184;     // accept(handle_ptr, new_handle_out_ptr, timeout_ms, peer_addr_ptr) — 接続の受け入れ
+pub const SYS_SEND: u64 = 185;       // send(handle_ptr, buf_ptr, len, addr_ptr) — 送信（addr_ptr=0 なら接続先へ）
+pub const SYS_RECV: u64 = 186;       // recv(handle_ptr, buf_ptr, len, timeout_ms) — 受信
+pub const SYS_RECV_FROM: u64 = 187;  // recv_from(handle_ptr, buf_ptr, len, args_ptr) — 送信元アドレス付き受信
+pub const SYS_SETSOCKOPT: u64 = 188; // setsockopt(handle_ptr, level, optname, value) — ソケットオプションの設定
+pub const SYS_GETSOCKOPT: u64 = 189; // getsockopt(handle_ptr, level, optname, value_out_ptr) — ソケットオプションの取得
+
+/// ソケットのアドレスファミリー: IPv4（値は POSIX に合わせている）
+pub const AF_INET: u64 = 2;
+/// ソケットのアドレスファミリー: IPv6
+pub const AF_INET6: u64 = 10;
+/// ソケットの種類: ストリーム（TCP）
+pub const SOCK_STREAM: u64 = 1;
+/// ソケットの種類: データグラム（UDP）
+pub const SOCK_DGRAM: u64 = 2;
+/// プロトコル番号: TCP（0 を渡せば種類から自動で決まる）
+pub const IPPROTO_TCP: u64 = 6;
+/// プロトコル番号: UDP
+pub const IPPROTO_UDP: u64 = 17;
+
+/// SYS_SETSOCKOPT の optname（level = IPPROTO_TCP）: 0 以外で Nagle アルゴリズムを無効にする
+pub const TCP_NODELAY: u64 = 1;
+/// SYS_SETSOCKOPT の level: プロトコルによらないソケット共通のオプション
+pub const SOL_SOCKET: u64 = 1;
+/// SYS_SETSOCKOPT の optname（level = SOL_SOCKET）: 0 以外で使用中の UDP ポートに後から bind できるようにする
+pub const SO_REUSEADDR: u64 = 2;
+/// SYS_SETSOCKOPT の optname（level = SOL_SOCKET）: 0 以外で TCP の close を RST による強制切断にする
+/// （linger 時間 0 の SO_LINGER。TIME_WAIT を残さないので、すぐに同じポートを使い直せる）
+pub const SO_LINGER: u64 = 13;
+/// SYS_SETSOCKOPT の optname（level = SOL_SOCKET）: 無期限待ちの受信のタイムアウト（ミリ秒、0 = 設定なし）
+pub const SO_RCVTIMEO: u64 = 20;
+
+/// SYS_RECV / SYS_RECV_FROM / SYS_ACCEPT の timeout_ms: 届くまで無期限に待つ。
+/// timeout_ms = 0 はノンブロッキング（データがなければ WouldBlock）。
+pub const SOCKET_WAIT_FOREVER: u64 = u64::MAX;
+
+/// ソケットアドレス（IPv4 / IPv6 共通）
+///
+/// family が AF_INET なら addr の先頭 4 バイトが IPv4 アドレス、
+/// AF_INET6 なら 16 バイト全体が IPv6 アドレス。port はホストバイトオーダー。
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct SockAddr {
+    pub family: u16,
+    pub port: u16,
+    pub _pad: u32,
+    pub addr: [u8; 16],
+}
+
+/// SYS_RECV_FROM の追加引数（引数レジスタが足りないので構造体で渡す）
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct SockRecvFromArgs {
+    /// タイムアウト（ミリ秒）。0 = ノンブロッキング、SOCKET_WAIT_FOREVER = 無期限
+    pub timeout_ms: u64,
+    /// 送信元アドレスの書き込み先（カーネルが埋める）
+    pub from: SockAddr,
+}
+
+// =================================================================
+// メモリ管理拡張 (190-199)
+// =================================================================
+pub const SYS_MPROTECT: u64 = 190;   // mprotect(addr, len, prot) — マッピング済みページの保護属性を変更
+pub const SYS_GET_TASK_INFO: u64 = 191;  // get_task_info(task_id, info_ptr) — タスクのメモリ使用量を取得（0 = 自分）
+
+/// SYS_GET_TASK_INFO の結果構造体（カーネルがユーザーバッファに書き込む）
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TaskMemInfo {
+    /// プロセスが持つ物理フレームの総数（ELF・スタック・mmap）
+    pub user_frames: u64,
+    /// user_frames のうち SYS_MMAP で確保したフレーム数（SYS_MUNMAP で減る）
+    pub mmap_frames: u64,
+    /// VMA の数
+    pub vma_count: u64,
+    /// VMA の合計サイズ（バイト）
+    pub vm_bytes: u64,
+}
+
+// =================================================================
+// ネットワーク拡張 2 (200-209) — conn_id / socket_id 指定のソケット操作
+// =================================================================
+// 統一ソケット API のハンドルを使わない SYS_NET_TCP_* / SYS_NET_UDP_* 向け。
+// TCP の conn_id と UDP の socket_id は同じ ID 空間なので、どちらも sock_id で指定する。
+pub const SYS_NET_SETSOCKOPT: u64 = 200; // net_setsockopt(sock_id, level, optname, value) — ソケットオプションの設定
+pub const SYS_NET_GETSOCKOPT: u64 = 201; // net_getsockopt(sock_id, level, optname, value_out_ptr) — ソケットオプションの取得
+pub const SYS_NET_TCP_LOCAL_ADDR: u64 = 202; // net_tcp_local_addr(conn_id, info_ptr) — TCP 接続の自分側のアドレス（6 バイト）
+pub const SYS_SENDFILE: u64 = 203;         // sendfile(conn_id, handle_ptr, offset, count) — ファイルハンドルの内容をカーネル内で TCP に送る
+
+// =================================================================
+// グラフィックス拡張 (210-219)
+// =================================================================
+// GOP は ExitBootServices の後は使えないので、画面モードは起動時のものから変えられない。
+// 代わりに論理解像度を設定すると、描画を整数倍に拡大して画面中央に描く。
+pub const SYS_FB_LIST_MODES: u64 = 210;       // fb_list_modes(buf_ptr, buf_len) — 起動時に GOP が対応していた解像度の一覧（FbMode の配列）
+pub const SYS_FB_SET_LOGICAL_SIZE: u64 = 211; // fb_set_logical_size(w, h) — 論理解像度を設定して拡大率を返す（w = h = 0 で解除）
+
+/// SYS_FB_LIST_MODES が返す画面モード 1 つ分
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FbMode {
+    pub width: u32,
+    pub height: u32,
+}
+
+// =================================================================
+// クリップボード (220-229)
+// =================================================================
+// プログラム間でテキストを受け渡すための、カーネルが持つ 1 つだけのバッファ（最大 64KiB）。
+pub const SYS_CLIPBOARD_SET: u64 = 220; // clipboard_set(buf_ptr, len) — クリップボードの中身を置き換える
+pub const SYS_CLIPBOARD_GET: u64 = 221; // clipboard_get(buf_ptr, len) — クリップボードの中身を読み、長さを返す
+
+// =================================================================
+// タイマー (230-239)
+// =================================================================
+// 期限が来ると、作ったタスクに送信元 TIMER_IPC_SENDER の IPC メッセージ（TimerMessage）が届く。
+// 1 タスクが同時に持てるタイマーは 16 個まで。タスクが終了すると自動で止まる。
+pub const SYS_TIMER_CREATE: u64 = 230; // timer_create(interval_ms, periodic) — タイマーを作り、タイマー ID を返す
+pub const SYS_TIMER_CANCEL: u64 = 231; // timer_cancel(timer_id) — タイマーを止める
+
+/// タイマーの IPC メッセージの送信元（どのタスク ID とも重ならない）
+pub const TIMER_IPC_SENDER: u64 = u64::MAX;
+
+/// タイマーが発火したときに届く IPC メッセージの中身
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimerMessage {
+    /// SYS_TIMER_CREATE が返したタイマー ID
+    pub timer_id: u64,
+    /// 前回のメッセージから過ぎた期限の数（周期タイマーで通知が遅れたときに 2 以上になる）
+    pub expirations: u64,
+}
+
+// =================================================================
+// スケジューラ (240-249)
+// =================================================================
+pub const SYS_SCHED_STATS: u64 = 240; // sched_stats(buf_ptr, buf_len) — スケジューラの統計（SchedStats）を取得
+pub const SYS_SETPRIORITY: u64 = 241; // setpriority(task_id, prio) — タスクの優先度を設定
+pub const SYS_GETPRIORITY: u64 = 242; // getpriority(task_id) — タスクの優先度を取得
+
+// =================================================================
+// プロセス拡張 (250-259)
+// =================================================================
+// プロセス管理 (30-39) が埋まっているので、こちらに続ける。
+pub const SYS_GETCWD: u64 = 250; // getcwd(buf_ptr, buf_len) — カレントディレクトリを取得
+pub const SYS_CHDIR: u64 = 251;  // chdir(path_ptr, path_len) — カレントディレクトリを変更
+
+/// 優先度の最小値（いちばん CPU をもらえない）
+pub const PRIORITY_MIN: u8 = 0;
+/// 新しいタスクの優先度
+pub const PRIORITY_DEFAULT: u8 = 4;
+/// 優先度の最大値
+pub const PRIORITY_MAX: u8 = 7;
+
+/// SYS_SCHED_STATS が返すスケジューラの統計（起動からの累計）
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SchedStats {
+    /// タイマー割り込みの回数
+    pub timer_ticks: u64,
+    /// コンテキストスイッチの回数（preempt_switches + yield_switches）
+    pub context_switches: u64,
+    /// タイマー割り込みからプリエンプションを試みた回数
+    pub preempt_calls: u64,
+    /// プリエンプションで実際に切り替えた回数
+    pub preempt_switches: u64,
+    /// yield（sleep や待ちを含む）で切り替えた回数
+    pub yield_switches: u64,
+}
@@ -0,0 +1,360 @@
+#![no_std]
+
+// tar-core — POSIX ustar 形式の tar アーカイブの読み書き
+//
+// 起動時に読み込むユーザープログラムなどを 1 つにまとめる、圧縮なしの単純な形式として使う。
+// 読み取りは元のバイト列を借用したまま各エントリのデータのスライスを返すので、
+// カーネルに埋め込んだ tar をそのまま読み取り専用のファイルシステムとして見せられる。
+// inflate-core の gunzip と組み合わせれば .tar.gz も読める。
+//
+// tar ファイルの構造:
+//   [ヘッダ 512 バイト][データ（512 バイト単位に 0 埋め）] ← エントリの数だけ
+//   [0 のブロック 512 バイト × 2]                          ← 終わりの印
+//
+// ヘッダの主な欄（数値は NUL か空白で終わる 8 進数の文字列）:
+//   name[100]@0  mode[8]@100  size[12]@124  mtime[12]@136  chksum[8]@148
+//   typeflag@156  magic[6]@257 ("ustar\0")  version[2]@263 ("00")  prefix[155]@345
+// 100 バイトを超えるパスは "/" で prefix と name に分けて入れる。
+//
+// chksum は chksum 欄を空白 8 個とみなしたヘッダ全体のバイトの和。
+// 壊れたヘッダを読んで大きな size を信じてしまわないよう、読むときは必ず確かめる。
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// tar のブロックの大きさ
+pub const BLOCK_SIZE: usize = 512;
+
+const NAME_LEN: usize = 100;
+const PREFIX_LEN: usize = 155;
+const MAGIC: &[u8; 6] = b"ustar\0";
+const VERSION: &[u8; 2] = b"00";
+
+/// size 欄（8 進 11 桁）に入る最大値
+const MAX_SIZE: u64 = 0o77777777777;
+
+/// tar の読み書きのエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarError {
+    /// ヘッダやデータがアーカイブの途中で切れている
+    Truncated,
+    /// ヘッダの chksum が合わない
+    BadChecksum,
+    /// 数値の欄が 8 進数でない、または名前が UTF-8 でない
+    InvalidHeader,
+    /// パスが長すぎて prefix と name に分けても入らない（書き込み時）
+    NameTooLong,
+    /// データが size 欄に入らないほど大きい（書き込み時）
+    TooLarge,
+}
+
+/// エントリの種類（typeflag）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarKind {
+    /// 通常のファイル（'0'。古い形式では NUL）
+    File,
+    /// ディレクトリ（'5'）
+    Directory,
+    /// シンボリックリンク（'2'）
+    Symlink,
+    /// それ以外（ハードリンク、デバイス、pax の拡張ヘッダなど）
+    Other(u8),
+}
+
+impl TarKind {
+    fn from_flag(flag: u8) -> Self {
+        match flag {
+            b'0' | 0 => TarKind::File,
+            b'5' => TarKind::Directory,
+            b'2' => TarKind::Symlink,
+            other => TarKind::Other(other),
+        }
+    }
+
+    fn flag(self) -> u8 {
+        match self {
+            TarKind::File => b'0',
+            TarKind::Directory => b'5',
+            TarKind::Symlink => b'2',
+            TarKind::Other(flag) => flag,
+        }
+    }
+}
+
+/// アーカイブ内の 1 エントリ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TarEntry<'a> {
+    /// パス（prefix と name をつないだもの。ディレクトリは末尾が "/" のことがある）
+    pub path: String,
+    /// エントリの種類
+    pub kind: TarKind,
+    /// パーミッション（mode 欄の下位 12 ビット）
+    pub mode: u32,
+    /// 更新時刻（UNIX 時間）
+    pub mtime: u64,
+    /// データ（ファイル以外では通常は空）
+    pub data: &'a [u8],
+}
+
+impl TarEntry<'_> {
+    /// データのバイト数
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+/// NUL までの部分を返す
+fn field(b: &[u8]) -> &[u8] {
+    match b.iter().position(|&c| c == 0) {
+        Some(end) => &b[..end],
+        None => b,
+    }
+}
+
+/// 8 進数の欄を読む（前後の空白と NUL は無視する。空なら 0）
+fn parse_octal(b: &[u8]) -> Result<u64, TarError> {
+    let digits = field(b).trim_ascii();
+    let mut v: u64 = 0;
+    for &c in digits {
+        if !(b'0'..=b'7').contains(&c) {
+            return Err(TarError::InvalidHeader);
+        }
+        v = v.checked_mul(8).ok_or(TarError::InvalidHeader)? + (c - b'0') as u64;
+    }
+    Ok(v)
+}
+
+/// ヘッダの chksum を計算する（chksum 欄は空白として数える）
+fn header_checksum(header: &[u8]) -> u64 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum()
+}
+
+/// ヘッダ 1 つを解釈する
+fn parse_header<'a>(header: &[u8], data: &'a [u8]) -> Result<TarEntry<'a>, TarError> {
+    if parse_octal(&header[148..156])? != header_checksum(header) {
+        return Err(TarError::BadChecksum);
+    }
+    let name = core::str::from_utf8(field(&header[..NAME_LEN])).map_err(|_| TarError::InvalidHeader)?;
+    // prefix 欄は ustar 形式のときだけ意味がある（古い形式では別の用途に使われていた）
+    let prefix = if &header[257..263] == MAGIC {
+        core::str::from_utf8(field(&header[345..345 + PREFIX_LEN])).map_err(|_| TarError::InvalidHeader)?
+    } else {
+        ""
+    };
+    let mut path = String::from(prefix);
+    if !prefix.is_empty() {
+        path.push('/');
+    }
+    path.push_str(name);
+
+    Ok(TarEntry {
+        path,
+        kind: TarKind::from_flag(header[156]),
+        mode: (parse_octal(&header[100..108])? & 0o7777) as u32,
+        mtime: parse_octal(&header[136..148])?,
+        data,
+    })
+}
+
+/// アーカイブのエントリ一覧を返す（並びはアーカイブの順）
+///
+/// 0 のブロック、またはアーカイブの終わり（ブロック境界）に来たら止まる。
+pub fn parse_tar(archive: &[u8]) -> Result<Vec<TarEntry<'_>>, TarError> {
+    let mut entries = Vec::new();
+    let mut off = 0;
+    while off < archive.len() {
+        let header = archive.get(off..off + BLOCK_SIZE).ok_or(TarError::Truncated)?;
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = parse_octal(&header[124..136])? as usize;
+        let start = off + BLOCK_SIZE;
+        let data = archive.get(start..start + size).ok_or(TarError::Truncated)?;
+        entries.push(parse_header(header, data)?);
+        off = start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    }
+    Ok(entries)
+}
+
+/// tar アーカイブを組み立てる
+///
+/// append_* でエントリを足していき、finish で終わりの印を付けたバイト列を受け取る。
+#[derive(Debug, Default)]
+pub struct TarWriter {
+    buf: Vec<u8>,
+}
+
+impl TarWriter {
+    pub fn new() -> Self {
+        TarWriter { buf: Vec::new() }
+    }
+
+    /// ファイルを追加する（mode 0644）
+    pub fn append_file(&mut self, path: &str, data: &[u8]) -> Result<(), TarError> {
+        self.append(path, TarKind::File, 0o644, data)
+    }
+
+    /// ディレクトリを追加する（mode 0755。パスの末尾には "/" を付けて書く）
+    pub fn append_dir(&mut self, path: &str) -> Result<(), TarError> {
+        let mut path = String::from(path.trim_end_matches('/'));
+        path.push('/');
+        self.append(&path, TarKind::Directory, 0o755, &[])
+    }
+
+    /// ヘッダとデータを 1 エントリ分追加する（mtime は 0）
+    pub fn append(&mut self, path: &str, kind: TarKind, mode: u32, data: &[u8]) -> Result<(), TarError> {
+        if data.len() as u64 > MAX_SIZE {
+            return Err(TarError::TooLarge);
+        }
+        let (prefix, name) = split_path(path)?;
+
+        let mut header = [0u8; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], (mode & 0o7777) as u64);
+        write_octal(&mut header[108..116], 0); // uid
+        write_octal(&mut header[116..124], 0); // gid
+        write_octal(&mut header[124..136], data.len() as u64);
+        write_octal(&mut header[136..148], 0); // mtime
+        header[156] = kind.flag();
+        header[257..263].copy_from_slice(MAGIC);
+        header[263..265].copy_from_slice(VERSION);
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        // chksum は 6 桁の 8 進数 + NUL + 空白（GNU tar と同じ書き方）
+        let sum = header_checksum(&header);
+        write_octal(&mut header[148..155], sum);
+        header[155] = b' ';
+
+        self.buf.extend_from_slice(&header);
+        self.buf.extend_from_slice(data);
+        let padding = data.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE - data.len();
+        self.buf.resize(self.buf.len() + padding, 0);
+        Ok(())
+    }
+
+    /// 終わりの印（0 のブロック 2 つ）を付けてアーカイブを返す
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buf.resize(self.buf.len() + 2 * BLOCK_SIZE, 0);
+        self.buf
+    }
+}
+
+/// 欄の長さ - 1 桁の 0 埋め 8 進数 + NUL を書く
+fn write_octal(dst: &mut [u8], mut value: u64) {
+    let digits = dst.len() - 1;
+    for i in (0..digits).rev() {
+        dst[i] = b'0' + (value & 7) as u8;
+        value >>= 3;
+    }
+    dst[digits] = 0;
+}
+
+/// パスを (prefix, name) に分ける
+///
+/// name に入れば prefix は空。入らなければ name が 100 バイト以内になる最初の "/" で分ける。
+fn split_path(path: &str) -> Result<(&str, &str), TarError> {
+    if path.len() <= NAME_LEN {
+        return Ok(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= PREFIX_LEN && !name.is_empty() && name.len() <= NAME_LEN)
+        .ok_or(TarError::NameTooLong)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::format;
+    use std::vec;
+
+    /// Python の tarfile (USTAR_FORMAT) で作ったアーカイブ
+    /// （BIN/、BIN/HELLO.TXT、prefix を使う長いパスの 600 バイトのファイル）
+    const HOST_TAR: &[u8] = include_bytes!("../testdata/host.tar");
+
+    #[test]
+    fn test_round_trip_two_files() {
+        let big: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let mut w = TarWriter::new();
+        w.append_file("HELLO.TXT", b"Hello, SABOS!\n").unwrap();
+        w.append_file("BIN/BIG.BIN", &big).unwrap();
+        let archive = w.finish();
+        // ヘッダ 2 + データ 1 + 2 + 終わりの印 2
+        assert_eq!(archive.len(), 7 * BLOCK_SIZE);
+
+        let entries = parse_tar(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "HELLO.TXT");
+        assert_eq!(entries[0].kind, TarKind::File);
+        assert_eq!(entries[0].mode, 0o644);
+        assert_eq!(entries[0].data, b"Hello, SABOS!\n");
+        assert_eq!(entries[1].path, "BIN/BIG.BIN");
+        assert_eq!(entries[1].size(), 1000);
+        assert_eq!(entries[1].data, &big[..]);
+    }
+
+    #[test]
+    fn test_round_trip_dir_and_long_path() {
+        let long = format!("DEEP/{}/README.TXT", "D".repeat(100));
+        let mut w = TarWriter::new();
+        w.append_dir("DEEP").unwrap();
+        w.append_file(&long, b"x").unwrap();
+        let archive = w.finish();
+
+        let entries = parse_tar(&archive).unwrap();
+        assert_eq!(entries[0].path, "DEEP/");
+        assert_eq!(entries[0].kind, TarKind::Directory);
+        assert_eq!(entries[0].mode, 0o755);
+        assert_eq!(entries[1].path, long);
+
+        let too_long = "N".repeat(NAME_LEN + 1);
+        assert_eq!(TarWriter::new().append_file(&too_long, b""), Err(TarError::NameTooLong));
+    }
+
+    #[test]
+    fn test_parse_host_tar() {
+        let entries = parse_tar(HOST_TAR).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, "BIN/");
+        assert_eq!(entries[0].kind, TarKind::Directory);
+        assert_eq!(entries[1].path, "BIN/HELLO.TXT");
+        assert_eq!(entries[1].data, b"Hello, SABOS!\n");
+        // 2026-01-01 00:00:00 UTC
+        assert_eq!(entries[1].mtime, 1767225600);
+        assert_eq!(entries[2].path, format!("DEEP/{}/README.TXT", "D".repeat(100)));
+        assert_eq!(entries[2].data, &vec![b'x'; 600][..]);
+    }
+
+    #[test]
+    fn test_reject_bad_checksum() {
+        let mut w = TarWriter::new();
+        w.append_file("HELLO.TXT", b"Hello, SABOS!\n").unwrap();
+        let mut archive = w.finish();
+        // 名前を 1 文字書き換える
+        archive[0] = b'J';
+        assert_eq!(parse_tar(&archive), Err(TarError::BadChecksum));
+
+        let mut host = HOST_TAR.to_vec();
+        host[124 + 10] = b'7'; // BIN/ の size を書き換える
+        assert_eq!(parse_tar(&host), Err(TarError::BadChecksum));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut w = TarWriter::new();
+        w.append_file("HELLO.TXT", b"Hello, SABOS!\n").unwrap();
+        let archive = w.finish();
+        assert_eq!(parse_tar(&archive[..BLOCK_SIZE + 4]), Err(TarError::Truncated));
+        assert_eq!(parse_tar(&archive[..100]), Err(TarError::Truncated));
+        // 終わりの印がなくてもブロック境界で終わっていれば読める
+        assert_eq!(parse_tar(&archive[..2 * BLOCK_SIZE]).unwrap().len(), 1);
+        assert!(parse_tar(&[]).unwrap().is_empty());
+    }
+}
@@ -3,6 +3,7 @@
 extern crate alloc;
 
 use alloc::string::String;
+use alloc::vec::Vec;
 
 /// リテラル文字列の置換を行う（正規表現は使わない）
 ///
@@ -81,3 +82,191 @@ pub fn replace_literal(line: &str, from: &str, to: &str, global: bool) -> (Strin
         (String::from(line), false)
     }
 }
+
+/// HTML に埋め込むために特殊文字をエスケープする
+///
+/// 要素の中身にも属性値（"..." / '...' のどちらで囲んでも）にも使えるよう、
+/// `& < > " '` の 5 文字を文字参照に置き換える。
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// ディレクトリ一覧の HTML を作る（httpd などの Web サーバー向け）
+///
+/// `entries` はディレクトリ一覧の生の形式（1 行に 1 エントリ、ディレクトリは末尾に "/"）で、
+/// SYS_HANDLE_ENUM / SYS_LIST_DIR が返すものをそのまま渡せる。
+/// `display_path` は URL 上のパスで、各エントリへのリンク先はその下になる。
+/// ルート以外では親ディレクトリへの ".." リンクを先頭に付ける。
+/// エントリ名とパスはすべて escape_html を通すので、名前に HTML が含まれていても
+/// ページの構造は壊れない。
+pub fn dir_listing_html(display_path: &str, entries: &str) -> String {
+    let title = escape_html(display_path);
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\n");
+    html.push_str("<title>Index of ");
+    html.push_str(&title);
+    html.push_str("</title>\n");
+    // 簡単なスタイル
+    html.push_str("<style>body{font-family:monospace;margin:2em}a{text-decoration:none}a:hover{text-decoration:underline}li{margin:0.3em 0}</style>\n");
+    html.push_str("</head><body>\n");
+    html.push_str("<h1>Index of ");
+    html.push_str(&title);
+    html.push_str("</h1>\n<hr>\n<ul>\n");
+
+    if display_path != "/" {
+        html.push_str("<li><a href=\"");
+        html.push_str(&escape_html(parent_path(display_path)));
+        html.push_str("\">..</a></li>\n");
+    }
+
+    let base = display_path.trim_end_matches('/');
+    for name in entries.split('\n').map(str::trim).filter(|name| !name.is_empty()) {
+        html.push_str("<li><a href=\"");
+        html.push_str(&escape_html(base));
+        html.push('/');
+        html.push_str(&escape_html(name));
+        html.push_str("\">");
+        html.push_str(&escape_html(name));
+        html.push_str("</a></li>\n");
+    }
+
+    html.push_str("</ul>\n<hr>\n<p><em>SABOS httpd</em></p>\n</body></html>\n");
+    html
+}
+
+/// 親ディレクトリのパス（末尾 "/" 付き。"/" の親は "/"）
+fn parent_path(path: &str) -> &str {
+    let trimmed = path.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(pos) => &trimmed[..pos + 1],
+        None => "/",
+    }
+}
+
+/// バイト列を少しずつ受け取り、UTF-8 の文字列として取り出す
+///
+/// ファイルを 1 セクタずつ読んで表示するときのように、チャンクの境界で
+/// 多バイト文字が切れても、続きのチャンクが来るまで持ち越して正しく繋げる。
+/// 不正なバイト列は U+FFFD に置き換える。
+#[derive(Debug, Default)]
+pub struct Utf8ChunkDecoder {
+    /// 前のチャンクの末尾で切れていた文字の断片（最大 3 バイト）
+    pending: Vec<u8>,
+}
+
+impl Utf8ChunkDecoder {
+    /// static にも置けるよう const fn にしておく
+    pub const fn new() -> Self {
+        Self { pending: Vec::new() }
+    }
+
+    /// chunk を解釈し、確定した文字列を順に out に渡す
+    pub fn push(&mut self, chunk: &[u8], out: &mut impl FnMut(&str)) {
+        if self.pending.is_empty() {
+            self.decode(chunk, out);
+        } else {
+            let mut joined = core::mem::take(&mut self.pending);
+            joined.extend_from_slice(chunk);
+            self.decode(&joined, out);
+        }
+    }
+
+    /// 入力の終わり。切れたまま残っている断片があれば U+FFFD として出す
+    pub fn finish(&mut self, out: &mut impl FnMut(&str)) {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            out("\u{FFFD}");
+        }
+    }
+
+    fn decode(&mut self, mut data: &[u8], out: &mut impl FnMut(&str)) {
+        loop {
+            match core::str::from_utf8(data) {
+                Ok(s) => {
+                    if !s.is_empty() {
+                        out(s);
+                    }
+                    return;
+                }
+                Err(e) => {
+                    let (valid, rest) = data.split_at(e.valid_up_to());
+                    if let Ok(s) = core::str::from_utf8(valid)
+                        && !s.is_empty()
+                    {
+                        out(s);
+                    }
+                    match e.error_len() {
+                        Some(n) => {
+                            out("\u{FFFD}");
+                            data = &rest[n..];
+                        }
+                        // 末尾で文字が切れている: 次のチャンクまで持ち越す
+                        None => {
+                            self.pending.extend_from_slice(rest);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_chunks(chunks: &[&[u8]]) -> String {
+        let mut decoder = Utf8ChunkDecoder::new();
+        let mut text = String::new();
+        for chunk in chunks {
+            decoder.push(chunk, &mut |s| text.push_str(s));
+        }
+        decoder.finish(&mut |s| text.push_str(s));
+        text
+    }
+
+    #[test]
+    fn test_utf8_split_across_chunks() {
+        // "あ" = E3 81 82 を 3 つのチャンクに分ける
+        assert_eq!(decode_chunks(&[b"a\xE3", b"\x81", b"\x82b"]), "aあb");
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&#39;");
+        assert_eq!(escape_html("HELLO.TXT"), "HELLO.TXT");
+    }
+
+    #[test]
+    fn test_dir_listing_html() {
+        let html = dir_listing_html("/", "HELLO.TXT\nSUB/\n");
+        assert!(html.contains("<a href=\"/HELLO.TXT\">HELLO.TXT</a>"));
+        assert!(html.contains("<a href=\"/SUB/\">SUB/</a>"));
+        // ルートには親へのリンクがない
+        assert!(!html.contains(">..</a>"));
+
+        let html = dir_listing_html("/DOCS/SUB/", "<script>x</script>\n");
+        assert!(html.contains("<a href=\"/DOCS/\">..</a>"));
+        assert!(html.contains("<a href=\"/DOCS/SUB/&lt;script&gt;x&lt;/script&gt;\">"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn test_utf8_invalid_and_truncated() {
+        assert_eq!(decode_chunks(&[b"a\xFFb"]), "a\u{FFFD}b");
+        // 最後のチャンクで切れたまま終わる
+        assert_eq!(decode_chunks(&[b"ok\xE3\x81"]), "ok\u{FFFD}");
+    }
+}
@@ -0,0 +1,250 @@
+#![no_std]
+
+// wav-core — WAV (RIFF/WAVE) ファイルの解析
+//
+// カーネルのシェル（play コマンド）から使う。no_std・アロケーション不要で、
+// 解析結果は元のバイト列を借用したまま data チャンクのスライスを返す。
+//
+// WAV ファイルの構造:
+//   "RIFF" <size:u32> "WAVE"
+//   "fmt " <size:u32> <audio_format:u16> <channels:u16> <sample_rate:u32>
+//          <byte_rate:u32> <block_align:u16> <bits_per_sample:u16> [拡張...]
+//   (LIST などの任意チャンク)
+//   "data" <size:u32> <PCM データ>
+//
+// チャンクは 2 バイト境界にパディングされる（サイズが奇数なら 1 バイト詰め物が入る）。
+// 数値はすべてリトルエンディアン。
+
+/// fmt チャンクの audio_format: 非圧縮 PCM
+pub const WAVE_FORMAT_PCM: u16 = 1;
+
+/// WAV の解析結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavInfo<'a> {
+    /// サンプルレート（Hz）
+    pub sample_rate: u32,
+    /// チャンネル数（1 = モノラル, 2 = ステレオ）
+    pub channels: u16,
+    /// 1 サンプルのビット数（8 / 16 など）
+    pub bits_per_sample: u16,
+    /// 1 フレーム（全チャンネル分の 1 サンプル）のバイト数
+    pub block_align: u16,
+    /// data チャンクの中身（block_align の倍数に切り詰め済み）
+    pub data: &'a [u8],
+}
+
+impl WavInfo<'_> {
+    /// data に含まれるフレーム数
+    pub fn frames(&self) -> usize {
+        self.data.len() / self.block_align as usize
+    }
+}
+
+/// WAV 解析のエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavError {
+    /// RIFF ヘッダにも満たない
+    TooShort,
+    /// 先頭が "RIFF" / "WAVE" ではない
+    NotWave,
+    /// fmt チャンクがない、または短すぎる
+    MissingFmt,
+    /// data チャンクがない
+    MissingData,
+    /// PCM 以外のフォーマット（audio_format の値）
+    UnsupportedFormat(u16),
+    /// チャンネル数やビット深度が矛盾している
+    InvalidFormat,
+}
+
+fn read_u16(b: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([b[off], b[off + 1]])
+}
+
+fn read_u32(b: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([b[off], b[off + 1], b[off + 2], b[off + 3]])
+}
+
+/// WAV ファイルを解析する。
+///
+/// 非圧縮 PCM (audio_format == 1) 以外は UnsupportedFormat で拒否する。
+/// data チャンクのサイズがファイルの残りより大きい場合（録音途中で
+/// 書き出されたファイル等）は、実際にあるところまでに切り詰める。
+pub fn parse_wav(bytes: &[u8]) -> Result<WavInfo<'_>, WavError> {
+    if bytes.len() < 12 {
+        return Err(WavError::TooShort);
+    }
+    if &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavError::NotWave);
+    }
+
+    // (channels, sample_rate, block_align, bits_per_sample)
+    let mut fmt: Option<(u16, u32, u16, u16)> = None;
+    let mut off = 12;
+    while off + 8 <= bytes.len() {
+        let id = &bytes[off..off + 4];
+        let size = read_u32(bytes, off + 4) as usize;
+        let body_start = off + 8;
+        let body_end = body_start.saturating_add(size).min(bytes.len());
+        let body = &bytes[body_start..body_end];
+
+        if id == b"fmt " {
+            if body.len() < 16 {
+                return Err(WavError::MissingFmt);
+            }
+            let format = read_u16(body, 0);
+            if format != WAVE_FORMAT_PCM {
+                return Err(WavError::UnsupportedFormat(format));
+            }
+            fmt = Some((read_u16(body, 2), read_u32(body, 4), read_u16(body, 12), read_u16(body, 14)));
+        } else if id == b"data" {
+            // fmt は data より前にある決まり
+            let (channels, sample_rate, block_align, bits_per_sample) = fmt.ok_or(WavError::MissingFmt)?;
+            if channels == 0
+                || sample_rate == 0
+                || bits_per_sample == 0
+                || bits_per_sample % 8 != 0
+                || block_align as u32 != channels as u32 * (bits_per_sample as u32 / 8)
+            {
+                return Err(WavError::InvalidFormat);
+            }
+            let usable = body.len() - body.len() % block_align as usize;
+            return Ok(WavInfo {
+                sample_rate,
+                channels,
+                bits_per_sample,
+                block_align,
+                data: &body[..usable],
+            });
+        }
+
+        // 次のチャンクへ（奇数サイズは 1 バイトのパディングを飛ばす）
+        off = body_start.saturating_add(size).saturating_add(size & 1);
+    }
+
+    if fmt.is_none() {
+        Err(WavError::MissingFmt)
+    } else {
+        Err(WavError::MissingData)
+    }
+}
+
+/// 16bit signed stereo に変換できる形式（8/16bit, モノラル/ステレオ）かどうか
+pub fn is_convertible(info: &WavInfo) -> bool {
+    (info.bits_per_sample == 8 || info.bits_per_sample == 16) && (info.channels == 1 || info.channels == 2)
+}
+
+/// start_frame 番目のフレームから、16bit signed LE stereo に変換して out に書く。
+///
+/// AC97 の PCM 出力は 16bit stereo 固定なので、8bit（符号なし）は 16bit に広げ、
+/// モノラルは左右に同じ値を複製する。out に入るだけ（4 バイト/フレーム）変換し、
+/// 書いたフレーム数を返す。is_convertible() が false の形式では 0 を返す。
+pub fn to_s16_stereo(info: &WavInfo, start_frame: usize, out: &mut [u8]) -> usize {
+    if !is_convertible(info) {
+        return 0;
+    }
+    let frames = info.frames().saturating_sub(start_frame).min(out.len() / 4);
+    let align = info.block_align as usize;
+    for i in 0..frames {
+        let frame = &info.data[(start_frame + i) * align..(start_frame + i + 1) * align];
+        let sample = |ch: usize| -> [u8; 2] {
+            if info.bits_per_sample == 8 {
+                // 8bit WAV は符号なし（128 が無音）
+                (((frame[ch] as i16) - 128) << 8).to_le_bytes()
+            } else {
+                [frame[ch * 2], frame[ch * 2 + 1]]
+            }
+        };
+        let left = sample(0);
+        let right = if info.channels == 2 { sample(1) } else { left };
+        out[i * 4..i * 4 + 2].copy_from_slice(&left);
+        out[i * 4 + 2..i * 4 + 4].copy_from_slice(&right);
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    /// 44 バイトヘッダ（RIFF + fmt 16 バイト + data）の標準的な WAV を組み立てる
+    fn make_wav(format: u16, channels: u16, rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let mut v = Vec::new();
+        v.extend_from_slice(b"RIFF");
+        v.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        v.extend_from_slice(b"WAVE");
+        v.extend_from_slice(b"fmt ");
+        v.extend_from_slice(&16u32.to_le_bytes());
+        v.extend_from_slice(&format.to_le_bytes());
+        v.extend_from_slice(&channels.to_le_bytes());
+        v.extend_from_slice(&rate.to_le_bytes());
+        v.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+        v.extend_from_slice(&block_align.to_le_bytes());
+        v.extend_from_slice(&bits.to_le_bytes());
+        v.extend_from_slice(b"data");
+        v.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        v.extend_from_slice(data);
+        v
+    }
+
+    #[test]
+    fn test_parse_44_byte_header() {
+        let pcm = [1u8, 0, 2, 0, 3, 0, 4, 0];
+        let wav = make_wav(WAVE_FORMAT_PCM, 2, 22050, 16, &pcm);
+        assert_eq!(wav.len(), 44 + pcm.len());
+
+        let info = parse_wav(&wav).unwrap();
+        assert_eq!(info.sample_rate, 22050);
+        assert_eq!(info.channels, 2);
+        assert_eq!(info.bits_per_sample, 16);
+        assert_eq!(info.block_align, 4);
+        assert_eq!(info.data, &pcm);
+        assert_eq!(info.frames(), 2);
+    }
+
+    #[test]
+    fn test_reject_non_pcm() {
+        // 3 = IEEE float
+        let wav = make_wav(3, 1, 44100, 32, &[0; 8]);
+        assert_eq!(parse_wav(&wav), Err(WavError::UnsupportedFormat(3)));
+    }
+
+    #[test]
+    fn test_reject_not_wave() {
+        assert_eq!(parse_wav(b"RIFF"), Err(WavError::TooShort));
+        let mut wav = make_wav(WAVE_FORMAT_PCM, 1, 8000, 8, &[0x80; 4]);
+        wav[8..12].copy_from_slice(b"AVI ");
+        assert_eq!(parse_wav(&wav), Err(WavError::NotWave));
+    }
+
+    #[test]
+    fn test_skip_unknown_chunk_and_truncated_data() {
+        // fmt と data の間に奇数サイズの LIST チャンクを挟み、data のサイズを実際より大きくする
+        let mut wav = make_wav(WAVE_FORMAT_PCM, 1, 8000, 16, &[]);
+        wav.truncate(36);
+        wav.extend_from_slice(b"LIST");
+        wav.extend_from_slice(&3u32.to_le_bytes());
+        wav.extend_from_slice(&[b'a', b'b', b'c', 0]); // 3 バイト + パディング
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&100u32.to_le_bytes());
+        wav.extend_from_slice(&[0x10, 0x00, 0x20, 0x00, 0x30]); // 末尾の半端な 1 バイトは捨てられる
+
+        let info = parse_wav(&wav).unwrap();
+        assert_eq!(info.data, &[0x10, 0x00, 0x20, 0x00]);
+        assert_eq!(info.frames(), 2);
+    }
+
+    #[test]
+    fn test_to_s16_stereo_from_mono_8bit() {
+        let wav = make_wav(WAVE_FORMAT_PCM, 1, 8000, 8, &[0x80, 0xFF, 0x00]);
+        let info = parse_wav(&wav).unwrap();
+        let mut out = [0u8; 16];
+        assert_eq!(to_s16_stereo(&info, 1, &mut out), 2);
+        // 0xFF → 127 << 8 = 0x7F00、0x00 → -128 << 8 = 0x8000。左右に複製される
+        assert_eq!(&out[..8], &[0x00, 0x7F, 0x00, 0x7F, 0x00, 0x80, 0x00, 0x80]);
+    }
+}
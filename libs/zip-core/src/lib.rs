@@ -0,0 +1,313 @@
+#![no_std]
+
+// zip-core — ZIP アーカイブの読み取り
+//
+// カーネルのシェル（unzip コマンド）から使う。アーカイブ全体をメモリに読んだ前提で、
+// エントリの一覧は元のバイト列を借用したまま返し、中身は extract で展開する。
+//
+// ZIP ファイルの構造（数値はすべてリトルエンディアン）:
+//   [ローカルファイルヘッダ "PK\x03\x04" + 名前 + extra][データ]  ← エントリの数だけ
+//   [セントラルディレクトリヘッダ "PK\x01\x02" + 名前 + extra + コメント] ← エントリの数だけ
+//   [End of Central Directory (EOCD) "PK\x05\x06" + コメント]
+//
+// 正しい一覧はファイル末尾の EOCD → セントラルディレクトリの順にたどって得る。
+// ローカルヘッダのサイズ欄はデータディスクリプタ（フラグの bit 3）を使うと 0 になるので、
+// サイズと CRC32 はセントラルディレクトリの値を使い、ローカルヘッダからはデータの位置だけを求める。
+//
+// 対応しているのは無圧縮 (method 0) と deflate (method 8) だけ。
+// ZIP64・分割アーカイブ・暗号化は Unsupported にする。
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use sabos_inflate_core::InflateError;
+
+/// 圧縮方式: 無圧縮
+pub const METHOD_STORED: u16 = 0;
+/// 圧縮方式: deflate
+pub const METHOD_DEFLATE: u16 = 8;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const EOCD_SIG: u32 = 0x0605_4b50;
+
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const EOCD_LEN: usize = 22;
+
+/// 汎用フラグ bit 0: 暗号化されている
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+/// ZIP 読み取りのエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipError {
+    /// EOCD が見つからない（ZIP ファイルではない）
+    NotZip,
+    /// ヘッダやデータがファイルの途中で切れている
+    Truncated,
+    /// シグネチャや長さが矛盾している
+    InvalidHeader,
+    /// ZIP64・分割アーカイブ・暗号化のいずれか
+    Unsupported,
+    /// 無圧縮と deflate 以外の圧縮方式（method の値）
+    UnsupportedMethod(u16),
+    /// 展開後のサイズが上限を超える
+    TooLarge,
+    /// deflate の展開に失敗した
+    Inflate(InflateError),
+    /// 展開後のサイズがセントラルディレクトリの値と合わない
+    SizeMismatch,
+    /// 展開後の CRC32 がセントラルディレクトリの値と合わない
+    ChecksumMismatch,
+}
+
+/// アーカイブ内の 1 エントリ（セントラルディレクトリの内容）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZipEntry<'a> {
+    /// エントリ名（区切りは "/"。ディレクトリは末尾が "/"）
+    pub name: &'a [u8],
+    /// 圧縮方式（METHOD_STORED / METHOD_DEFLATE など）
+    pub method: u16,
+    /// 展開後のデータの CRC32
+    pub crc32: u32,
+    /// 圧縮後のサイズ
+    pub compressed_size: u32,
+    /// 展開後のサイズ
+    pub uncompressed_size: u32,
+    flags: u16,
+    local_header_offset: u32,
+}
+
+impl<'a> ZipEntry<'a> {
+    /// エントリ名を UTF-8 として返す（UTF-8 でなければ None）
+    pub fn name_str(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.name).ok()
+    }
+
+    /// ディレクトリのエントリか（名前が "/" で終わる）
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with(b"/")
+    }
+}
+
+fn read_u16(b: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes([b[off], b[off + 1]])
+}
+
+fn read_u32(b: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([b[off], b[off + 1], b[off + 2], b[off + 3]])
+}
+
+/// EOCD の位置を探す
+///
+/// EOCD の後ろには最大 65535 バイトのコメントが付くので、末尾から後ろ向きに探す。
+fn find_eocd(data: &[u8]) -> Option<usize> {
+    if data.len() < EOCD_LEN {
+        return None;
+    }
+    let last = data.len() - EOCD_LEN;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|&off| read_u32(data, off) == EOCD_SIG && off + EOCD_LEN + read_u16(data, off + 20) as usize == data.len())
+}
+
+/// アーカイブのエントリ一覧を返す（セントラルディレクトリの順）
+pub fn parse_zip(data: &[u8]) -> Result<Vec<ZipEntry<'_>>, ZipError> {
+    let eocd = find_eocd(data).ok_or(ZipError::NotZip)?;
+    let disk = read_u16(data, eocd + 4);
+    let cd_disk = read_u16(data, eocd + 6);
+    let entries_on_disk = read_u16(data, eocd + 8);
+    let total_entries = read_u16(data, eocd + 10);
+    let cd_size = read_u32(data, eocd + 12);
+    let cd_offset = read_u32(data, eocd + 16);
+    // 分割アーカイブは扱わない。ZIP64 ではこれらの欄が 0xFFFF / 0xFFFFFFFF になる
+    if disk != 0 || cd_disk != 0 || entries_on_disk != total_entries {
+        return Err(ZipError::Unsupported);
+    }
+    if total_entries == u16::MAX || cd_size == u32::MAX || cd_offset == u32::MAX {
+        return Err(ZipError::Unsupported);
+    }
+    let cd_end = (cd_offset as usize).checked_add(cd_size as usize).ok_or(ZipError::InvalidHeader)?;
+    if cd_end > eocd {
+        return Err(ZipError::InvalidHeader);
+    }
+
+    let mut entries = Vec::with_capacity(total_entries as usize);
+    let mut off = cd_offset as usize;
+    for _ in 0..total_entries {
+        if off + CENTRAL_HEADER_LEN > cd_end {
+            return Err(ZipError::Truncated);
+        }
+        if read_u32(data, off) != CENTRAL_HEADER_SIG {
+            return Err(ZipError::InvalidHeader);
+        }
+        let name_len = read_u16(data, off + 28) as usize;
+        let extra_len = read_u16(data, off + 30) as usize;
+        let comment_len = read_u16(data, off + 32) as usize;
+        let name_start = off + CENTRAL_HEADER_LEN;
+        let next = name_start + name_len + extra_len + comment_len;
+        if next > cd_end {
+            return Err(ZipError::Truncated);
+        }
+        entries.push(ZipEntry {
+            name: &data[name_start..name_start + name_len],
+            method: read_u16(data, off + 10),
+            crc32: read_u32(data, off + 16),
+            compressed_size: read_u32(data, off + 20),
+            uncompressed_size: read_u32(data, off + 24),
+            flags: read_u16(data, off + 8),
+            local_header_offset: read_u32(data, off + 42),
+        });
+        off = next;
+    }
+    Ok(entries)
+}
+
+/// エントリの中身を展開して返す（上限は sabos_inflate_core::DEFAULT_MAX_OUTPUT）
+pub fn extract(data: &[u8], entry: &ZipEntry) -> Result<Vec<u8>, ZipError> {
+    extract_with_limit(data, entry, sabos_inflate_core::DEFAULT_MAX_OUTPUT)
+}
+
+/// エントリの中身を展開して返す（展開後 max_output バイトを超えるならエラー）
+///
+/// 展開はセントラルディレクトリの uncompressed_size で打ち切り、
+/// 最後にサイズと CRC32 が一致することを確かめる。
+pub fn extract_with_limit(data: &[u8], entry: &ZipEntry, max_output: usize) -> Result<Vec<u8>, ZipError> {
+    if entry.flags & FLAG_ENCRYPTED != 0 {
+        return Err(ZipError::Unsupported);
+    }
+    let expected = entry.uncompressed_size as usize;
+    if expected > max_output {
+        return Err(ZipError::TooLarge);
+    }
+
+    // ローカルヘッダの名前と extra の長さはセントラルディレクトリと違うことがあるので読み直す
+    let off = entry.local_header_offset as usize;
+    let header = data.get(off..off + LOCAL_HEADER_LEN).ok_or(ZipError::Truncated)?;
+    if read_u32(header, 0) != LOCAL_HEADER_SIG {
+        return Err(ZipError::InvalidHeader);
+    }
+    let start = off + LOCAL_HEADER_LEN + read_u16(header, 26) as usize + read_u16(header, 28) as usize;
+    let body = data
+        .get(start..start + entry.compressed_size as usize)
+        .ok_or(ZipError::Truncated)?;
+
+    let out = match entry.method {
+        METHOD_STORED => body.to_vec(),
+        METHOD_DEFLATE => match sabos_inflate_core::inflate_with_limit(body, expected) {
+            Ok(out) => out,
+            // 宣言より長く展開されるのはサイズの不一致として扱う
+            Err(InflateError::OutputTooLarge) => return Err(ZipError::SizeMismatch),
+            Err(e) => return Err(ZipError::Inflate(e)),
+        },
+        method => return Err(ZipError::UnsupportedMethod(method)),
+    };
+    if out.len() != expected {
+        return Err(ZipError::SizeMismatch);
+    }
+    if sabos_checksum_core::crc32(&out) != entry.crc32 {
+        return Err(ZipError::ChecksumMismatch);
+    }
+    Ok(out)
+}
+
+/// エントリ名を展開先の下の相対パスとして使ってよいか
+///
+/// "../" や絶対パスで展開先の外に書き出させる（いわゆる Zip Slip）のを防ぐため、
+/// 次のような名前は拒否する:
+///   - 空、"/" で始まる、"\" や ":"（Windows のドライブ名）や NUL を含む
+///   - "." / ".." / 空の要素を含む（末尾の "/" 1 つはディレクトリの印として許す）
+pub fn is_safe_path(name: &str) -> bool {
+    if name.is_empty() || name.starts_with('/') || name.contains(['\\', ':', '\0']) {
+        return false;
+    }
+    let body = name.strip_suffix('/').unwrap_or(name);
+    body.split('/').all(|c| !c.is_empty() && c != "." && c != "..")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::format;
+    use std::string::String;
+
+    const TWO_ZIP: &[u8] = include_bytes!("../testdata/two.zip");
+    const EVIL_ZIP: &[u8] = include_bytes!("../testdata/evil.zip");
+
+    /// two.zip の DOCS/README.TXT の内容
+    fn readme_fixture() -> String {
+        let mut s = String::new();
+        for i in 0..50 {
+            s.push_str(&format!("line {}: SABOS zip-core test data\n", i));
+        }
+        s
+    }
+
+    #[test]
+    fn test_parse_two_files() {
+        let entries = parse_zip(TWO_ZIP).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].name_str(), Some("HELLO.TXT"));
+        assert_eq!(entries[0].method, METHOD_STORED);
+        assert_eq!(entries[0].uncompressed_size, 14);
+        assert!(!entries[0].is_dir());
+        assert_eq!(extract(TWO_ZIP, &entries[0]).unwrap(), b"Hello, SABOS!\n");
+
+        assert_eq!(entries[1].name_str(), Some("DOCS/README.TXT"));
+        assert_eq!(entries[1].method, METHOD_DEFLATE);
+        assert!(entries[1].compressed_size < entries[1].uncompressed_size);
+        assert_eq!(extract(TWO_ZIP, &entries[1]).unwrap(), readme_fixture().as_bytes());
+    }
+
+    #[test]
+    fn test_not_zip_and_truncated() {
+        assert_eq!(parse_zip(b"hello"), Err(ZipError::NotZip));
+        assert_eq!(parse_zip(&TWO_ZIP[..TWO_ZIP.len() - 1]), Err(ZipError::NotZip));
+        // EOCD だけ残してセントラルディレクトリを壊す
+        let mut bad = TWO_ZIP.to_vec();
+        let eocd = find_eocd(&bad).unwrap();
+        let cd = read_u32(&bad, eocd + 16) as usize;
+        bad[cd] = 0;
+        assert_eq!(parse_zip(&bad), Err(ZipError::InvalidHeader));
+    }
+
+    #[test]
+    fn test_extract_detects_corruption() {
+        let entries = parse_zip(TWO_ZIP).unwrap();
+        // 無圧縮のデータを 1 バイト書き換えると CRC32 が合わなくなる
+        let mut bad = TWO_ZIP.to_vec();
+        let data_start = LOCAL_HEADER_LEN + "HELLO.TXT".len();
+        bad[data_start] ^= 0x20;
+        assert_eq!(extract(&bad, &entries[0]), Err(ZipError::ChecksumMismatch));
+
+        // セントラルディレクトリが小さいサイズを宣言していても、その先は展開しない
+        let mut small = entries[1];
+        small.uncompressed_size = 100;
+        assert_eq!(extract(TWO_ZIP, &small), Err(ZipError::SizeMismatch));
+        assert_eq!(extract_with_limit(TWO_ZIP, &entries[1], 1000), Err(ZipError::TooLarge));
+    }
+
+    #[test]
+    fn test_is_safe_path() {
+        assert!(is_safe_path("HELLO.TXT"));
+        assert!(is_safe_path("DOCS/README.TXT"));
+        assert!(is_safe_path("DOCS/"));
+        assert!(!is_safe_path(""));
+        assert!(!is_safe_path("/etc/passwd"));
+        assert!(!is_safe_path("../EVIL.TXT"));
+        assert!(!is_safe_path("DOCS/../../EVIL.TXT"));
+        assert!(!is_safe_path("DOCS/./A"));
+        assert!(!is_safe_path("DOCS//A"));
+        assert!(!is_safe_path("..\\EVIL.TXT"));
+        assert!(!is_safe_path("C:EVIL.TXT"));
+
+        // 一覧には出るが、展開先の外を指すので使えない
+        let entries = parse_zip(EVIL_ZIP).unwrap();
+        assert_eq!(entries[0].name_str(), Some("../EVIL.TXT"));
+        assert!(!is_safe_path(entries[0].name_str().unwrap()));
+    }
+}
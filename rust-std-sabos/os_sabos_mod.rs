@@ -15,3 +15,42 @@ pub mod prelude {
     #[stable(feature = "rust1", since = "1.0.0")]
     pub use super::ffi::{OsStrExt, OsStringExt};
 }
+
+/// SABOS 固有のプロセス情報
+#[unstable(feature = "sabos_ext", issue = "none")]
+pub mod process {
+    /// プロセスのメモリ使用量（SYS_GET_TASK_INFO の結果。レイアウトはカーネルの TaskMemInfo と同じ）
+    #[derive(Clone, Copy, Debug, Default)]
+    #[repr(C)]
+    pub struct MemInfo {
+        /// プロセスが持つ物理フレームの総数（ELF・スタック・mmap）
+        pub user_frames: u64,
+        /// user_frames のうち mmap で確保したフレーム数。増え続けるならリークを疑う
+        pub mmap_frames: u64,
+        /// VMA の数
+        pub vma_count: u64,
+        /// VMA の合計サイズ（バイト）
+        pub vm_bytes: u64,
+    }
+
+    /// 自分自身のメモリ使用量を取得する
+    pub fn mem_info() -> crate::io::Result<MemInfo> {
+        let mut info = MemInfo::default();
+        let ret: u64;
+        unsafe {
+            core::arch::asm!(
+                "int 0x80",
+                in("rax") 191u64, // SYS_GET_TASK_INFO
+                in("rdi") 0u64,
+                in("rsi") &mut info as *mut MemInfo as u64,
+                lateout("rax") ret,
+                lateout("rcx") _,
+                lateout("r11") _,
+            );
+        }
+        if (ret as i64) < 0 {
+            return Err(crate::io::Error::new(crate::io::ErrorKind::Other, "SYS_GET_TASK_INFO failed"));
+        }
+        Ok(info)
+    }
+}
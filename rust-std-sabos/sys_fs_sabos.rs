@@ -1,13 +1,14 @@
 // sys/fs/sabos.rs — SABOS ファイルシステム PAL 実装
 //
 // SABOS のハンドルベース syscall (SYS_OPEN=70, SYS_HANDLE_READ=71, SYS_HANDLE_WRITE=72,
-// SYS_HANDLE_CLOSE=73, SYS_HANDLE_STAT=77, SYS_HANDLE_SEEK=78) と、
+// SYS_HANDLE_CLOSE=73, SYS_HANDLE_STAT=77, SYS_HANDLE_SEEK=78, SYS_FSYNC=143) と、
 // パスベース syscall (SYS_FILE_DELETE=12, SYS_DIR_CREATE=15, SYS_DIR_REMOVE=16,
-// SYS_DIR_LIST=13) を使って std::fs のインターフェースを実装する。
+// SYS_DIR_REMOVE_ALL=19, SYS_DIR_LIST=13) を使って std::fs のインターフェースを実装する。
 //
 // unsupported.rs をベースに、SABOS で実装可能な操作だけ syscall に接続。
 // リンク関連やパーミッション変更など SABOS 未対応の操作は unsupported() を返す。
 
+use crate::borrow::Cow;
 use crate::ffi::OsString;
 use crate::fmt;
 use crate::fs::TryLockError;
@@ -25,12 +26,14 @@ const SYS_DIR_LIST: u64 = 13;
 const SYS_FILE_WRITE: u64 = 14;
 const SYS_DIR_CREATE: u64 = 15;
 const SYS_DIR_REMOVE: u64 = 16;
+const SYS_DIR_REMOVE_ALL: u64 = 19;
 const SYS_OPEN: u64 = 70;
 const SYS_HANDLE_READ: u64 = 71;
 const SYS_HANDLE_WRITE: u64 = 72;
 const SYS_HANDLE_CLOSE: u64 = 73;
 const SYS_HANDLE_STAT: u64 = 77;
 const SYS_HANDLE_SEEK: u64 = 78;
+const SYS_FSYNC: u64 = 143;
 
 // ハンドルの権限ビット
 const HANDLE_RIGHTS_FILE_READ: u32 = 0x000D; // READ | SEEK | STAT
@@ -173,6 +176,23 @@ fn syscall_handle_close(h: &SabosHandle) -> io::Result<()> {
     Ok(())
 }
 
+/// SYS_FSYNC(143): ハンドルに書き込んだ内容をディスクに書き戻す
+fn syscall_fsync(h: &SabosHandle) -> io::Result<()> {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") SYS_FSYNC,
+            in("rdi") h as *const SabosHandle as u64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    check_syscall_result(ret)?;
+    Ok(())
+}
+
 /// SYS_HANDLE_STAT(77): ハンドルのメタデータを取得する
 fn syscall_handle_stat(h: &SabosHandle) -> io::Result<SabosHandleStat> {
     let mut stat = SabosHandleStat {
@@ -268,6 +288,24 @@ fn syscall_dir_remove(path: &[u8]) -> io::Result<()> {
     Ok(())
 }
 
+/// SYS_DIR_REMOVE_ALL(19): ディレクトリを中身ごと削除する
+fn syscall_dir_remove_all(path: &[u8]) -> io::Result<()> {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") SYS_DIR_REMOVE_ALL,
+            in("rdi") path.as_ptr() as u64,
+            in("rsi") path.len() as u64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    check_syscall_result(ret)?;
+    Ok(())
+}
+
 /// SYS_DIR_LIST(13): ディレクトリの内容一覧を取得する
 /// 改行区切りのエントリ名が返る。ディレクトリは末尾に "/" が付く。
 fn syscall_dir_list(path: &[u8], buf: &mut [u8]) -> io::Result<usize> {
@@ -311,9 +349,18 @@ fn syscall_file_write(path: &[u8], data: &[u8]) -> io::Result<()> {
 // ============================================================
 // パスを &[u8] に変換するヘルパー
 // ============================================================
-fn path_to_bytes(path: &Path) -> &[u8] {
+/// 相対パスはカレントディレクトリ（SYS_GETCWD）からたどった絶対パスにする。
+/// カーネルの VFS は相対パスをルートからのパスとして扱うため、ここで解決しておく。
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
     use crate::os::sabos::ffi::OsStrExt;
-    path.as_os_str().as_bytes()
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.starts_with(b"/") {
+        return Cow::Borrowed(bytes);
+    }
+    match crate::env::current_dir() {
+        Ok(cwd) => Cow::Owned(cwd.join(path).into_os_string().into_vec()),
+        Err(_) => Cow::Borrowed(bytes),
+    }
 }
 
 // ============================================================
@@ -585,7 +632,7 @@ impl File {
 
         // truncate=true の場合: 空データで上書きしてから open する
         if opts.truncate && opts.write {
-            syscall_file_write(path_bytes, &[])?;
+            syscall_file_write(&path_bytes, &[])?;
         }
 
         // create=true + write=true の場合:
@@ -593,7 +640,7 @@ impl File {
         // ただし create_new の場合、既存ファイルがあればエラーにしたい。
         if opts.create_new {
             // まず open して存在確認 → 存在していたらエラー
-            if let Ok(h) = syscall_open(path_bytes, HANDLE_RIGHTS_FILE_READ) {
+            if let Ok(h) = syscall_open(&path_bytes, HANDLE_RIGHTS_FILE_READ) {
                 let _ = syscall_handle_close(&h);
                 return Err(io::Error::new(
                     io::ErrorKind::AlreadyExists,
@@ -609,7 +656,7 @@ impl File {
             HANDLE_RIGHTS_FILE_READ
         };
 
-        let handle = syscall_open(path_bytes, rights)?;
+        let handle = syscall_open(&path_bytes, rights)?;
 
         // append モードの場合、末尾にシークする
         if opts.append {
@@ -627,13 +674,14 @@ impl File {
         })
     }
 
+    /// 書き込んだ内容を close を待たずにディスクへ書き戻す（File::sync_all）
     pub fn fsync(&self) -> io::Result<()> {
-        // SABOS はクローズ時にフラッシュするため、fsync は何もしない
-        Ok(())
+        syscall_fsync(&self.handle)
     }
 
+    /// FAT32 ではデータとディレクトリエントリ（サイズ）を一緒に書き戻すので fsync と同じ
     pub fn datasync(&self) -> io::Result<()> {
-        Ok(())
+        syscall_fsync(&self.handle)
     }
 
     pub fn lock(&self) -> io::Result<()> {
@@ -766,7 +814,7 @@ impl DirBuilder {
     }
 
     pub fn mkdir(&self, p: &Path) -> io::Result<()> {
-        syscall_dir_create(path_to_bytes(p))
+        syscall_dir_create(&path_to_bytes(p))
     }
 }
 
@@ -780,7 +828,7 @@ pub fn readdir(p: &Path) -> io::Result<ReadDir> {
 
     // バッファを用意して SYS_DIR_LIST を呼ぶ
     let mut buf = crate::vec![0u8; 4096];
-    let n = syscall_dir_list(path_bytes, &mut buf)?;
+    let n = syscall_dir_list(&path_bytes, &mut buf)?;
     let data = &buf[..n];
 
     // 改行区切りでパースする
@@ -816,7 +864,7 @@ pub fn readdir(p: &Path) -> io::Result<ReadDir> {
 
 /// ファイルを削除する
 pub fn unlink(p: &Path) -> io::Result<()> {
-    syscall_file_delete(path_to_bytes(p))
+    syscall_file_delete(&path_to_bytes(p))
 }
 
 /// ファイル名を変更する（SABOS 未対応）
@@ -841,12 +889,16 @@ pub fn set_times_nofollow(_p: &Path, _times: FileTimes) -> io::Result<()> {
 
 /// ディレクトリを削除する
 pub fn rmdir(p: &Path) -> io::Result<()> {
-    syscall_dir_remove(path_to_bytes(p))
+    syscall_dir_remove(&path_to_bytes(p))
 }
 
 /// ディレクトリを再帰的に削除する
-/// common.rs の実装を使う
-pub use crate::sys::fs::common::remove_dir_all;
+///
+/// common.rs の実装は lstat() でディレクトリを開こうとするが、SABOS の SYS_OPEN は
+/// FAT32 のディレクトリを開けないので、カーネルの vfs::remove_dir_all に任せる。
+pub fn remove_dir_all(p: &Path) -> io::Result<()> {
+    syscall_dir_remove_all(&path_to_bytes(p))
+}
 
 /// ファイルまたはディレクトリが存在するか確認する
 /// common.rs の exists を使う（stat → NotFound で判定）
@@ -871,7 +923,7 @@ pub fn link(_src: &Path, _dst: &Path) -> io::Result<()> {
 /// ファイルを open → stat → close してメタデータを返す
 pub fn stat(p: &Path) -> io::Result<FileAttr> {
     let path_bytes = path_to_bytes(p);
-    let handle = syscall_open(path_bytes, HANDLE_RIGHTS_FILE_READ)?;
+    let handle = syscall_open(&path_bytes, HANDLE_RIGHTS_FILE_READ)?;
     let stat = syscall_handle_stat(&handle);
     let _ = syscall_handle_close(&handle);
     let stat = stat?;
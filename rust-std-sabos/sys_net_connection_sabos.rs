@@ -4,11 +4,14 @@
 // システムコール（int 0x80）で直接 DNS / TCP / UDP の操作を行う。
 //
 // この PAL は std::net::TcpStream / TcpListener / UdpSocket / lookup_host を
-// カーネル syscall に接続する。IPv6 は SABOS が IPv4 のみのため unsupported。
+// カーネル syscall に接続する。IPv6 は UdpSocket のみ対応。
+// カーネルの TCP は IPv6 も扱えるが（SYS_SOCKET + SYS_CONNECT の AF_INET6）、
+// この PAL の TcpStream / TcpListener は IPv4 アドレスしか渡せない conn_id 系の
+// syscall (SYS_NET_TCP_*) を使っているので、std からの TCP over IPv6 は対象外。
 
 use crate::fmt;
 use crate::io::{self, BorrowedCursor, IoSlice, IoSliceMut};
-use crate::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, ToSocketAddrs};
+use crate::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
 use crate::time::Duration;
 use crate::vec;
 
@@ -24,11 +27,31 @@ const SYS_NET_TCP_LISTEN: u64 = 150;
 const SYS_NET_TCP_ACCEPT: u64 = 151;
 const SYS_NET_UDP_BIND: u64 = 152;
 const SYS_NET_UDP_SEND_TO: u64 = 153;
-const SYS_NET_UDP_RECV_FROM: u64 = 154;
 const SYS_NET_UDP_CLOSE: u64 = 155;
-
-/// デフォルトの TCP recv タイムアウト（5 秒）
-const DEFAULT_RECV_TIMEOUT_MS: u64 = 5000;
+const SYS_NET_UDP_SEND_TO6: u64 = 157;
+const SYS_NET_UDP_RECV_FROM6: u64 = 158;
+const SYS_NET_TCP_SHUTDOWN: u64 = 159;
+const SYS_NET_SETSOCKOPT: u64 = 200;
+const SYS_NET_GETSOCKOPT: u64 = 201;
+const SYS_NET_TCP_LOCAL_ADDR: u64 = 202;
+
+/// SYS_NET_TCP_SHUTDOWN の how
+const SHUT_RD: u64 = 0;
+const SHUT_WR: u64 = 1;
+const SHUT_RDWR: u64 = 2;
+
+/// SYS_NET_SETSOCKOPT / SYS_NET_GETSOCKOPT の level と optname
+const SOL_SOCKET: u64 = 1;
+const SO_RCVTIMEO: u64 = 20;
+const SO_LINGER: u64 = 13;
+const IPPROTO_TCP: u64 = 6;
+const TCP_NODELAY: u64 = 1;
+
+/// TcpListener::bind の backlog（std の unix 実装と同じ 128。カーネルの上限でもある）
+const LISTEN_BACKLOG: u64 = 128;
+
+/// TcpListener::accept が 1 回の SYS_NET_TCP_ACCEPT で待つ時間（ミリ秒）
+const ACCEPT_POLL_MS: u64 = 1000;
 
 // ============================================================
 // unsupported ヘルパー
@@ -133,6 +156,48 @@ fn syscall_result(ret: u64, err_msg: &'static str) -> io::Result<i64> {
     }
 }
 
+// ============================================================
+// ソケットオプション
+// ============================================================
+// TCP の conn_id と UDP の socket_id は同じ ID 空間なので、どちらもここで扱える。
+
+/// SYS_NET_SETSOCKOPT(sock_id, level, optname, value)
+fn setsockopt(sock_id: u32, level: u64, optname: u64, value: u64) -> io::Result<()> {
+    let ret = syscall4(SYS_NET_SETSOCKOPT, sock_id as u64, level, optname, value);
+    syscall_result(ret, "setsockopt failed")?;
+    Ok(())
+}
+
+/// SYS_NET_GETSOCKOPT(sock_id, level, optname, value_out_ptr)
+fn getsockopt(sock_id: u32, level: u64, optname: u64) -> io::Result<u64> {
+    let mut value: u64 = 0;
+    let ret = syscall4(SYS_NET_GETSOCKOPT, sock_id as u64, level, optname, &mut value as *mut u64 as u64);
+    syscall_result(ret, "getsockopt failed")?;
+    Ok(value)
+}
+
+/// 受信タイムアウトを SO_RCVTIMEO に設定する
+///
+/// カーネルの受信 syscall に timeout_ms = 0 を渡すと SO_RCVTIMEO で待つ。
+/// None は「設定なし」（カーネル既定の 5 秒）。std の慣例どおりゼロ時間はエラー。
+fn set_recv_timeout(sock_id: u32, dur: Option<Duration>) -> io::Result<()> {
+    let timeout_ms = match dur {
+        Some(d) if d.is_zero() => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot set a 0 duration timeout"));
+        }
+        // 1ms 未満は切り上げる（0 だと「設定なし」になってしまう）
+        Some(d) => (d.as_millis() as u64).max(1),
+        None => 0,
+    };
+    setsockopt(sock_id, SOL_SOCKET, SO_RCVTIMEO, timeout_ms)
+}
+
+/// SO_RCVTIMEO の現在値
+fn recv_timeout(sock_id: u32) -> io::Result<Option<Duration>> {
+    let timeout_ms = getsockopt(sock_id, SOL_SOCKET, SO_RCVTIMEO)?;
+    Ok((timeout_ms != 0).then(|| Duration::from_millis(timeout_ms)))
+}
+
 // ============================================================
 // UDP 引数構造体（sabos-syscall crate と同じレイアウト）
 // ============================================================
@@ -150,7 +215,7 @@ struct UdpSendToArgs {
     data_len: u64,
 }
 
-/// UDP recv_from の引数構造体
+/// UDP recv_from の引数構造体（SYS_NET_UDP_RECV_FROM6 で使う）
 #[repr(C)]
 struct UdpRecvFromArgs {
     socket_id: u32,
@@ -158,25 +223,48 @@ struct UdpRecvFromArgs {
     buf_ptr: u64,
     buf_len: u64,
     timeout_ms: u64,
-    src_info_ptr: u64, // [u8; 6] = [ip0, ip1, ip2, ip3, port_lo, port_hi]
+    src_info_ptr: u64, // [u8; UDP_SRC_INFO6_LEN]
+}
+
+/// UDP send_to6 の引数構造体（宛先が IPv6 アドレス）
+#[repr(C)]
+struct UdpSendTo6Args {
+    socket_id: u32,
+    dst_port: u16,
+    _pad: u16,
+    dst_ip: [u8; 16],
+    data_ptr: u64,
+    data_len: u64,
 }
 
+/// SYS_NET_UDP_RECV_FROM6 の送信元情報: [アドレス 16 バイト, port_lo, port_hi]
+/// IPv4 の送信元は IPv4-mapped アドレス (::ffff:a.b.c.d) で返ってくる。
+const UDP_SRC_INFO6_LEN: usize = 18;
+
 // ============================================================
 // SocketAddr ↔ バイト列の変換ヘルパー
 // ============================================================
 
 /// SocketAddr から IPv4 アドレス 4 バイト + ポート 2 バイト LE を取得する。
-/// IPv6 アドレスの場合はエラーを返す。
+/// IPv6 アドレスの場合はエラーを返す（std の TCP は IPv4 のみ。ファイル先頭を参照）。
 fn socket_addr_to_ipv4_port(addr: &SocketAddr) -> io::Result<([u8; 4], u16)> {
     match addr {
         SocketAddr::V4(v4) => Ok((v4.ip().octets(), v4.port())),
         SocketAddr::V6(_) => Err(io::Error::new(
             io::ErrorKind::Unsupported,
-            "IPv6 is not supported on SABOS",
+            "std TCP over IPv6 is not supported on SABOS",
         )),
     }
 }
 
+/// SYS_NET_TCP_ACCEPT / SYS_NET_TCP_LOCAL_ADDR が書くアドレス [ip0..ip3, port_lo, port_hi] を SocketAddr にする
+fn addr_info_to_socket_addr(info: &[u8; 6]) -> SocketAddr {
+    SocketAddr::V4(SocketAddrV4::new(
+        Ipv4Addr::new(info[0], info[1], info[2], info[3]),
+        u16::from_le_bytes([info[4], info[5]]),
+    ))
+}
+
 // ============================================================
 // TcpStream
 // ============================================================
@@ -187,9 +275,7 @@ pub struct TcpStream {
     conn_id: u32,
     /// 接続先アドレス（connect 時に記録）
     peer_addr: SocketAddr,
-    /// 読み取りタイムアウト
-    read_timeout: Option<Duration>,
-    /// 書き込みタイムアウト
+    /// 書き込みタイムアウト（読み取りタイムアウトはカーネルの SO_RCVTIMEO に持たせる）
     write_timeout: Option<Duration>,
 }
 
@@ -233,26 +319,23 @@ impl TcpStream {
         Ok(TcpStream {
             conn_id: conn_id as u32,
             peer_addr: *addr,
-            read_timeout: None,
             write_timeout: None,
         })
     }
 
     pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
-        // &self しかないため unsafe で変更する（std PAL の慣例）
-        let self_mut = unsafe { &mut *(self as *const Self as *mut Self) };
-        self_mut.read_timeout = dur;
-        Ok(())
+        set_recv_timeout(self.conn_id, dur)
     }
 
     pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        // &self しかないため unsafe で変更する（std PAL の慣例）
         let self_mut = unsafe { &mut *(self as *const Self as *mut Self) };
         self_mut.write_timeout = dur;
         Ok(())
     }
 
     pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
-        Ok(self.read_timeout)
+        recv_timeout(self.conn_id)
     }
 
     pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
@@ -264,19 +347,14 @@ impl TcpStream {
     }
 
     pub fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
-        // タイムアウト値を決定（デフォルト 5 秒）
-        let timeout_ms = self
-            .read_timeout
-            .map(|d| d.as_millis() as u64)
-            .unwrap_or(DEFAULT_RECV_TIMEOUT_MS);
-
         // SYS_NET_TCP_RECV(conn_id, buf_ptr, buf_len, timeout_ms) → 受信バイト数
+        // timeout_ms = 0 なら SO_RCVTIMEO（未設定ならカーネル既定の 5 秒）で待つ
         let ret = syscall4(
             SYS_NET_TCP_RECV,
             self.conn_id as u64,
             buf.as_mut_ptr() as u64,
             buf.len() as u64,
-            timeout_ms,
+            0,
         );
 
         let n = syscall_result(ret, "TCP recv timed out")
@@ -323,6 +401,10 @@ impl TcpStream {
             buf.as_ptr() as u64,
             buf.len() as u64,
         );
+        // -61 (BrokenPipe) は shutdown(Write) の後の書き込み
+        if ret as i64 == -61 {
+            return Err(io::Error::new(io::ErrorKind::BrokenPipe, "TCP write side is shut down"));
+        }
         syscall_result(ret, "TCP send failed")
             .map_err(|_| io::Error::new(io::ErrorKind::ConnectionReset, "TCP send failed"))?;
         Ok(buf.len())
@@ -347,16 +429,24 @@ impl TcpStream {
     }
 
     pub fn socket_addr(&self) -> io::Result<SocketAddr> {
-        // ローカルアドレスは不明 → 0.0.0.0:0 を返す
-        Ok(SocketAddr::V4(SocketAddrV4::new(
-            Ipv4Addr::new(0, 0, 0, 0),
-            0,
-        )))
-    }
-
-    pub fn shutdown(&self, _how: Shutdown) -> io::Result<()> {
-        // half-close 未対応なので TCP_CLOSE で全閉じする
-        syscall1(SYS_NET_TCP_CLOSE, self.conn_id as u64);
+        // SYS_NET_TCP_LOCAL_ADDR(conn_id, info_ptr) → 0
+        // connect した側の送信元ポートはカーネルが選ぶので、毎回問い合わせる
+        let mut info = [0u8; 6];
+        let ret = syscall2(SYS_NET_TCP_LOCAL_ADDR, self.conn_id as u64, info.as_mut_ptr() as u64);
+        syscall_result(ret, "TCP local_addr failed")?;
+        Ok(addr_info_to_socket_addr(&info))
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        // 接続は残したまま片側だけ閉じる。後始末は Drop の TCP_CLOSE で行う
+        let how = match how {
+            Shutdown::Read => SHUT_RD,
+            Shutdown::Write => SHUT_WR,
+            Shutdown::Both => SHUT_RDWR,
+        };
+        let ret = syscall2(SYS_NET_TCP_SHUTDOWN, self.conn_id as u64, how);
+        syscall_result(ret, "TCP shutdown failed")
+            .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "TCP shutdown failed"))?;
         Ok(())
     }
 
@@ -364,22 +454,32 @@ impl TcpStream {
         unsupported()
     }
 
-    pub fn set_linger(&self, _linger: Option<Duration>) -> io::Result<()> {
-        unsupported()
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        // カーネルは linger 時間 0（close で RST を送る強制切断）だけに対応している
+        let value = match linger {
+            None => 0,
+            Some(d) if d.is_zero() => 1,
+            Some(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "only zero linger is supported on SABOS",
+                ));
+            }
+        };
+        setsockopt(self.conn_id, SOL_SOCKET, SO_LINGER, value)
     }
 
     pub fn linger(&self) -> io::Result<Option<Duration>> {
-        unsupported()
+        let value = getsockopt(self.conn_id, SOL_SOCKET, SO_LINGER)?;
+        Ok((value != 0).then_some(Duration::ZERO))
     }
 
-    pub fn set_nodelay(&self, _nodelay: bool) -> io::Result<()> {
-        // スタブ: Nagle 制御未対応だが、エラーにはしない
-        Ok(())
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        setsockopt(self.conn_id, IPPROTO_TCP, TCP_NODELAY, nodelay as u64)
     }
 
     pub fn nodelay(&self) -> io::Result<bool> {
-        // 常に true（SABOS の TCP は小さなパケットをすぐ送る）
-        Ok(true)
+        Ok(getsockopt(self.conn_id, IPPROTO_TCP, TCP_NODELAY)? != 0)
     }
 
     pub fn set_ttl(&self, _ttl: u32) -> io::Result<()> {
@@ -429,14 +529,14 @@ impl TcpListener {
     /// 指定アドレスでリッスンを開始する
     ///
     /// addr のポート番号でリッスンする。IP アドレスは無視（SABOS は 0.0.0.0 固定）。
-    /// SYS_NET_TCP_LISTEN(port) → 0（成功）/ 負（エラー）
+    /// SYS_NET_TCP_LISTEN(port, backlog) → 0（成功）/ 負（エラー）
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
         let addrs = addr.to_socket_addrs()?;
         let mut last_err =
             io::Error::new(io::ErrorKind::InvalidInput, "no addresses to bind to");
         for a in addrs {
             let port = a.port();
-            let ret = syscall1(SYS_NET_TCP_LISTEN, port as u64);
+            let ret = syscall2(SYS_NET_TCP_LISTEN, port as u64, LISTEN_BACKLOG);
             match syscall_result(ret, "TCP listen failed") {
                 Ok(_) => return Ok(TcpListener { port }),
                 Err(_) => last_err = io::Error::new(io::ErrorKind::AddrInUse, "TCP listen failed"),
@@ -453,19 +553,27 @@ impl TcpListener {
     }
 
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
-        // SYS_NET_TCP_ACCEPT(timeout_ms, listen_port) → conn_id
-        // timeout_ms = 0 でブロッキング待ち
-        let ret = syscall2(SYS_NET_TCP_ACCEPT, 0, self.port as u64);
-        let conn_id = syscall_result(ret, "TCP accept failed")
-            .map_err(|_| io::Error::new(io::ErrorKind::ConnectionAborted, "TCP accept failed"))?;
-
-        // accept 時のクライアントアドレスはカーネルが返さないので 0.0.0.0:0 とする
-        let peer = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0));
+        // SYS_NET_TCP_ACCEPT(timeout_ms, listen_port, peer_info_ptr) → conn_id
+        // カーネルは timeout_ms = 0 を短いポーリングとして扱い、タイムアウトも他の失敗と
+        // 同じ負の値で返すので、接続が来るまで区切って待ち直す（ブロッキング accept）
+        let mut peer_info = [0u8; 6];
+        let conn_id = loop {
+            let ret = syscall3(
+                SYS_NET_TCP_ACCEPT,
+                ACCEPT_POLL_MS,
+                self.port as u64,
+                peer_info.as_mut_ptr() as u64,
+            );
+            if let Ok(conn_id) = syscall_result(ret, "TCP accept failed") {
+                break conn_id as u32;
+            }
+        };
+
+        let peer = addr_info_to_socket_addr(&peer_info);
         Ok((
             TcpStream {
-                conn_id: conn_id as u32,
+                conn_id,
                 peer_addr: peer,
-                read_timeout: None,
                 write_timeout: None,
             },
             peer,
@@ -519,8 +627,6 @@ pub struct UdpSocket {
     socket_id: u32,
     /// バインドしているローカルアドレス
     local_addr: SocketAddr,
-    /// 読み取りタイムアウト
-    read_timeout: Option<Duration>,
     /// 書き込みタイムアウト（SABOS では未使用だが API 互換のため保持）
     write_timeout: Option<Duration>,
     /// connect() で設定されたデフォルト送信先アドレス
@@ -542,13 +648,23 @@ impl UdpSocket {
                 // 戻り値: socket_id(下位32bit) | local_port(上位32bit)
                 let socket_id = ret as u32;
                 let local_port = (ret >> 32) as u16;
-                return Ok(UdpSocket {
-                    socket_id,
-                    local_addr: SocketAddr::V4(SocketAddrV4::new(
-                        Ipv4Addr::new(0, 0, 0, 0),
+                // ソケットは IPv4 / IPv6 の両方で使えるので、
+                // 表示上のアドレスは bind に渡されたファミリーに合わせる
+                let local_addr = match a {
+                    SocketAddr::V4(_) => SocketAddr::V4(SocketAddrV4::new(
+                        Ipv4Addr::UNSPECIFIED,
                         local_port,
                     )),
-                    read_timeout: None,
+                    SocketAddr::V6(_) => SocketAddr::V6(SocketAddrV6::new(
+                        Ipv6Addr::UNSPECIFIED,
+                        local_port,
+                        0,
+                        0,
+                    )),
+                };
+                return Ok(UdpSocket {
+                    socket_id,
+                    local_addr,
                     write_timeout: None,
                     connected_addr: None,
                 });
@@ -569,23 +685,19 @@ impl UdpSocket {
     }
 
     pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
-        let timeout_ms = self
-            .read_timeout
-            .map(|d| d.as_millis() as u64)
-            .unwrap_or(DEFAULT_RECV_TIMEOUT_MS);
-
-        // 送信元情報: [ip0, ip1, ip2, ip3, port_lo, port_hi]
-        let mut src_info = [0u8; 6];
+        // 送信元情報: [アドレス 16 バイト, port_lo, port_hi]
+        let mut src_info = [0u8; UDP_SRC_INFO6_LEN];
         let args = UdpRecvFromArgs {
             socket_id: self.socket_id,
             _pad: 0,
             buf_ptr: buf.as_mut_ptr() as u64,
             buf_len: buf.len() as u64,
-            timeout_ms,
+            // SO_RCVTIMEO（未設定ならカーネル既定の 5 秒）で待つ
+            timeout_ms: 0,
             src_info_ptr: src_info.as_mut_ptr() as u64,
         };
 
-        let ret = syscall1(SYS_NET_UDP_RECV_FROM, &args as *const _ as u64);
+        let ret = syscall1(SYS_NET_UDP_RECV_FROM6, &args as *const _ as u64);
         let n = syscall_result(ret, "UDP recv timed out")
             .map_err(|e| {
                 if e.kind() == io::ErrorKind::TimedOut {
@@ -595,9 +707,14 @@ impl UdpSocket {
                 }
             })?;
 
-        let src_ip = Ipv4Addr::new(src_info[0], src_info[1], src_info[2], src_info[3]);
-        let src_port = u16::from_le_bytes([src_info[4], src_info[5]]);
-        let addr = SocketAddr::V4(SocketAddrV4::new(src_ip, src_port));
+        let mut ip_bytes = [0u8; 16];
+        ip_bytes.copy_from_slice(&src_info[..16]);
+        let src_ip = Ipv6Addr::from(ip_bytes);
+        let src_port = u16::from_le_bytes([src_info[16], src_info[17]]);
+        let addr = match src_ip.to_ipv4_mapped() {
+            Some(v4) => SocketAddr::V4(SocketAddrV4::new(v4, src_port)),
+            None => SocketAddr::V6(SocketAddrV6::new(src_ip, src_port, 0, 0)),
+        };
         Ok((n as usize, addr))
     }
 
@@ -606,7 +723,10 @@ impl UdpSocket {
     }
 
     pub fn send_to(&self, buf: &[u8], addr: &SocketAddr) -> io::Result<usize> {
-        let (ip_bytes, port) = socket_addr_to_ipv4_port(addr)?;
+        let (ip_bytes, port) = match addr {
+            SocketAddr::V4(v4) => (v4.ip().octets(), v4.port()),
+            SocketAddr::V6(v6) => return self.send_to6(buf, v6),
+        };
 
         let args = UdpSendToArgs {
             socket_id: self.socket_id,
@@ -623,14 +743,29 @@ impl UdpSocket {
         Ok(buf.len())
     }
 
+    /// IPv6 宛てに送信する（SYS_NET_UDP_SEND_TO6）
+    fn send_to6(&self, buf: &[u8], addr: &SocketAddrV6) -> io::Result<usize> {
+        let args = UdpSendTo6Args {
+            socket_id: self.socket_id,
+            dst_port: addr.port(),
+            _pad: 0,
+            dst_ip: addr.ip().octets(),
+            data_ptr: buf.as_ptr() as u64,
+            data_len: buf.len() as u64,
+        };
+
+        let ret = syscall1(SYS_NET_UDP_SEND_TO6, &args as *const _ as u64);
+        syscall_result(ret, "UDP send failed")
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "UDP send failed"))?;
+        Ok(buf.len())
+    }
+
     pub fn duplicate(&self) -> io::Result<UdpSocket> {
         unsupported()
     }
 
     pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
-        let self_mut = unsafe { &mut *(self as *const Self as *mut Self) };
-        self_mut.read_timeout = dur;
-        Ok(())
+        set_recv_timeout(self.socket_id, dur)
     }
 
     pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
@@ -640,7 +775,7 @@ impl UdpSocket {
     }
 
     pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
-        Ok(self.read_timeout)
+        recv_timeout(self.socket_id)
     }
 
     pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
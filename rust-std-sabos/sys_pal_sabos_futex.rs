@@ -0,0 +1,111 @@
+// sys/pal/sabos/futex.rs — SABOS 用 futex（std::sync の Mutex / Condvar / RwLock / Once の土台）
+//
+// SYS_FUTEX(120) の FUTEX_WAIT / FUTEX_WAKE を std の futex インターフェースに合わせる。
+// sys/sync/{mutex,condvar,rwlock,once}/mod.rs の futex 実装がこれを使うので、
+// ロックを取れないスレッドはスピンせずカーネルで眠り、解放側の wake で起こされる。
+//
+// ## カーネルの futex との違いを埋めるところ
+//
+// - タイムアウトの単位はミリ秒で、0 は「無期限」。std の Some(Duration::ZERO) や
+//   1ms 未満の待ちが無期限にならないよう、ミリ秒に切り上げて最低 1ms にする。
+// - FUTEX_WAIT は起こされてもタイムアウトしても 0 を返すので、タイムアウトしたかは
+//   SYS_CLOCK_MONOTONIC で経過時間を測って判定する。
+//
+// requeue や bitset の操作は、std の futex 実装（Condvar も含めて）が使わないので用意していない。
+
+use crate::sync::atomic::Atomic;
+use crate::time::Duration;
+
+/// futex として使う 32 ビット以上の Atomic
+pub type Futex = Atomic<Primitive>;
+/// Futex の中身の型
+pub type Primitive = u32;
+
+/// futex として使う 8 ビット以上の Atomic（SABOS の futex は 32 ビット単位なので Futex と同じ）
+pub type SmallFutex = Atomic<SmallPrimitive>;
+/// SmallFutex の中身の型
+pub type SmallPrimitive = u32;
+
+/// FUTEX_WAIT: 値が expected のままなら眠る
+const FUTEX_WAIT: u64 = 0;
+/// FUTEX_WAKE: 眠っているスレッドを起こす
+const FUTEX_WAKE: u64 = 1;
+
+/// SYS_FUTEX(120): futex 操作
+///
+/// 引数:
+///   rdi — AtomicU32 のアドレス
+///   rsi — 操作（FUTEX_WAIT / FUTEX_WAKE）
+///   rdx — WAIT: expected / WAKE: 起こす最大数
+///   r10 — WAIT: タイムアウト (ms, 0 = 無期限)
+///
+/// 戻り値:
+///   WAIT: 0（起床した）/ 負の値（値が expected と違ったので眠らなかった）
+///   WAKE: 起こしたスレッドの数
+fn syscall_futex(addr: u64, op: u64, val: u64, timeout_ms: u64) -> i64 {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 120u64,         // SYS_FUTEX
+            in("rdi") addr,
+            in("rsi") op,
+            in("rdx") val,
+            in("r10") timeout_ms,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    ret as i64
+}
+
+/// SYS_CLOCK_MONOTONIC(26): 起動からの経過ミリ秒
+fn clock_monotonic_ms() -> u64 {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 26u64,          // SYS_CLOCK_MONOTONIC
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    ret
+}
+
+/// futex の値が expected なら、起こされるかタイムアウトするまで眠る。
+///
+/// タイムアウトしたときだけ false を返す。値が違って眠らなかったときや、
+/// 理由なく起きたとき（spurious wakeup）も true を返すので、呼び出し側は値を確かめ直す。
+pub fn futex_wait(futex: &Atomic<u32>, expected: u32, timeout: Option<Duration>) -> bool {
+    let timeout_ms = match timeout {
+        // 端数は切り上げる（早く起きすぎると、タイムアウトのつもりで条件を見落とす）
+        Some(t) => {
+            let ms = t.as_millis() + u128::from(t.subsec_nanos() % 1_000_000 != 0);
+            u64::try_from(ms).unwrap_or(u64::MAX).max(1)
+        }
+        None => 0,
+    };
+    let start = if timeout.is_some() { clock_monotonic_ms() } else { 0 };
+
+    syscall_futex(futex.as_ptr() as u64, FUTEX_WAIT, expected as u64, timeout_ms);
+
+    match timeout {
+        Some(_) => clock_monotonic_ms().saturating_sub(start) < timeout_ms,
+        None => true,
+    }
+}
+
+/// futex で眠っているスレッドを 1 つ起こす。起こしたら true。
+#[inline]
+pub fn futex_wake(futex: &Atomic<u32>) -> bool {
+    syscall_futex(futex.as_ptr() as u64, FUTEX_WAKE, 1, 0) > 0
+}
+
+/// futex で眠っているスレッドをすべて起こす
+#[inline]
+pub fn futex_wake_all(futex: &Atomic<u32>) {
+    syscall_futex(futex.as_ptr() as u64, FUTEX_WAKE, u32::MAX as u64, 0);
+}
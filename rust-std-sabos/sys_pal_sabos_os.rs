@@ -2,28 +2,66 @@
 //
 // unsupported/os.rs をベースに、SABOS 向けの OS 関数を実装。
 // - exit() / getpid(): SABOS システムコール経由
-// - getcwd(): SABOS はフラット FAT32 なのでルート "/" を返す
+// - getcwd() / chdir(): SYS_GETCWD(250) / SYS_CHDIR(251) 経由
 // - temp_dir(): ファイルシステムのルート "/" を返す
 // - home_dir(): ルート "/" を返す
-// - chdir() / current_exe(): unsupported（カーネル側に未実装）
+// - current_exe(): unsupported（カーネル側に未実装）
 
 use super::unsupported;
 use crate::ffi::{OsStr, OsString};
 use crate::marker::PhantomData;
+use crate::os::sabos::ffi::{OsStrExt, OsStringExt};
 use crate::path::{self, PathBuf};
 use crate::{fmt, io};
 
-/// カレントディレクトリを取得する。
-/// SABOS はフラット FAT32 ファイルシステムで、ディレクトリ階層の概念が薄いため
-/// 常にルート "/" を返す。将来 chdir を実装したら対応する。
+/// カレントディレクトリのバッファサイズ（FAT32 のパスはこれより短い）
+const CWD_BUF_SIZE: usize = 1024;
+
+/// カレントディレクトリを取得する: SYS_GETCWD(250) を呼ぶ
 pub fn getcwd() -> io::Result<PathBuf> {
-    Ok(PathBuf::from("/"))
+    let mut buf = crate::vec![0u8; CWD_BUF_SIZE];
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 250u64,  // SYS_GETCWD
+            in("rdi") buf.as_mut_ptr() as u64,
+            in("rsi") buf.len() as u64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    let ret = ret as i64;
+    if ret < 0 {
+        return Err(io::Error::other("SYS_GETCWD failed"));
+    }
+    buf.truncate(ret as usize);
+    Ok(PathBuf::from(OsString::from_vec(buf)))
 }
 
-/// カレントディレクトリを変更する。
-/// SABOS カーネルに cwd の概念がまだないため unsupported。
-pub fn chdir(_: &path::Path) -> io::Result<()> {
-    unsupported()
+/// カレントディレクトリを変更する: SYS_CHDIR(251) を呼ぶ。
+/// 相対パスはカーネルが今のカレントディレクトリからたどる。
+pub fn chdir(p: &path::Path) -> io::Result<()> {
+    let bytes = p.as_os_str().as_bytes();
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 251u64,  // SYS_CHDIR
+            in("rdi") bytes.as_ptr() as u64,
+            in("rsi") bytes.len() as u64,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    match ret as i64 {
+        0 => Ok(()),
+        -10 => Err(io::Error::new(io::ErrorKind::NotADirectory, "not a directory")),
+        -20 => Err(io::Error::new(io::ErrorKind::NotFound, "no such directory")),
+        _ => Err(io::Error::other("SYS_CHDIR failed")),
+    }
 }
 
 pub struct SplitPaths<'a>(!, PhantomData<&'a ()>);
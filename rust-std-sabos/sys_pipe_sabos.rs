@@ -10,7 +10,8 @@
 // - SYS_HANDLE_READ(71) / SYS_HANDLE_WRITE(72) でデータ転送
 // - SYS_HANDLE_CLOSE(73) で後始末
 // - read が WouldBlock(-60) を返したら SYS_YIELD(32) して再試行
-// - read が 0 を返したら EOF
+// - read が 0 を返したら EOF（書き込み端がすべて閉じられ、データも読み切った）
+// - read は溜まっている分だけ返す。BufReader が使う read_buf もこの read で実装する
 
 use crate::io;
 
@@ -137,14 +138,22 @@ impl Pipe {
         Ok(total)
     }
 
-    // --- 未サポート機能 ---
-
-    pub fn read_buf(&self, _buf: crate::io::BorrowedCursor<'_>) -> io::Result<()> {
-        Err(io::Error::UNSUPPORTED_PLATFORM)
+    /// BorrowedCursor に読み取る（BufReader の fill_buf から呼ばれる）。
+    pub fn read_buf(&self, mut cursor: crate::io::BorrowedCursor<'_>) -> io::Result<()> {
+        let buf = cursor.ensure_init();
+        let n = self.read(buf.init_mut())?;
+        cursor.advance(n);
+        Ok(())
     }
 
-    pub fn read_vectored(&self, _bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
-        Err(io::Error::UNSUPPORTED_PLATFORM)
+    /// 最初の空でないバッファにだけ読む（scatter read は未対応）。
+    pub fn read_vectored(&self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        for buf in bufs {
+            if !buf.is_empty() {
+                return self.read(buf);
+            }
+        }
+        Ok(0)
     }
 
     #[inline]
@@ -152,6 +161,8 @@ impl Pipe {
         false
     }
 
+    // --- 未サポート機能 ---
+
     pub fn write_vectored(&self, _bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
         Err(io::Error::UNSUPPORTED_PLATFORM)
     }
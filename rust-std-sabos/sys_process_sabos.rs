@@ -13,6 +13,12 @@
 //
 // stderr リダイレクトは未対応（SABOS の SpawnRedirectArgs は stdin/stdout のみ）。
 //
+// ## 環境変数と作業ディレクトリ
+//
+// Command::env / env_remove / env_clear / current_dir が指定された場合も
+// SYS_SPAWN_REDIRECTED を使い、SpawnRedirectArgs の env / cwd で子に渡す。
+// env は変更を反映した後の環境変数すべてを引数バッファと同じ形式の "KEY=VALUE" で並べる。
+//
 // ## 引数バッファ形式
 //
 // SABOS は null 終端文字列を使わない。引数バッファは長さプレフィックス形式:
@@ -103,6 +109,12 @@ struct SpawnRedirectArgs {
     /// stdout のリダイレクト先ハンドル ID。u64::MAX = リダイレクトなし（コンソール）
     stdout_handle_id: u64,
     stdout_handle_token: u64,
+    /// 子の環境変数すべて（"KEY=VALUE" の引数バッファ形式）。0 = 親から継承
+    env_ptr: u64,
+    env_len: u64,
+    /// 子のカレントディレクトリ。長さ 0 = 親から継承
+    cwd_ptr: u64,
+    cwd_len: u64,
 }
 
 /// SYS_WAIT(34): 子プロセスの終了を待つ。
@@ -112,7 +124,7 @@ struct SpawnRedirectArgs {
 ///   rsi — タイムアウト（ms、0 なら無期限）
 ///
 /// 戻り値:
-///   0 以上 — 終了コード
+///   0 以上 — 終了コード（i32 を u32 として下位 32 ビットに詰めた値。`as u32 as i32` で戻す）
 ///   負の値 — エラー
 fn syscall_wait(task_id: u64, timeout_ms: u64) -> i64 {
     let ret: u64;
@@ -134,6 +146,7 @@ fn syscall_wait(task_id: u64, timeout_ms: u64) -> i64 {
 ///
 /// 引数:
 ///   rdi — タスク ID
+///   rsi — シグナル番号（9 = SIG_KILL。std の Child::kill は常に強制終了）
 ///
 /// 戻り値:
 ///   0 — 成功
@@ -145,6 +158,7 @@ fn syscall_kill(task_id: u64) -> i64 {
             "int 0x80",
             in("rax") 36u64,              // SYS_KILL
             in("rdi") task_id,
+            in("rsi") 9u64,               // SIG_KILL
             lateout("rax") ret,
             lateout("rcx") _,
             lateout("r11") _,
@@ -153,6 +167,27 @@ fn syscall_kill(task_id: u64) -> i64 {
     ret as i64
 }
 
+/// 子に渡す環境変数を "KEY=VALUE" の引数バッファ形式に変換する。
+///
+/// 引数と違って数が読めないので、固定長ではなく Vec に積む。
+/// 長さが u16 に収まらないエントリは InvalidInput にする。
+fn build_env_buffer<'a>(
+    vars: impl Iterator<Item = (&'a OsString, &'a OsString)>,
+) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    for (key, value) in vars {
+        let len = key.len() + 1 + value.len();
+        let len = u16::try_from(len).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "environment variable is too long")
+        })?;
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(key.as_bytes());
+        buf.push(b'=');
+        buf.extend_from_slice(value.as_bytes());
+    }
+    Ok(buf)
+}
+
 /// 引数リストを SABOS 形式のバッファに変換する。
 ///
 /// フォーマット: [u16 LE len][bytes][u16 LE len][bytes]...
@@ -267,6 +302,7 @@ impl Command {
     ///
     /// stdin/stdout に MakePipe が指定されている場合は SYS_PIPE でパイプを作成し、
     /// SYS_SPAWN_REDIRECTED で子プロセスにパイプの一端を渡す。
+    /// 環境変数や作業ディレクトリが指定されている場合も SYS_SPAWN_REDIRECTED を使う。
     /// それ以外は SYS_SPAWN で通常起動する。
     pub fn spawn(
         &mut self,
@@ -290,9 +326,22 @@ impl Command {
         let needs_stdin_pipe = matches!(self.stdin, Some(Stdio::MakePipe));
         let needs_stdout_pipe = matches!(self.stdout, Some(Stdio::MakePipe));
 
-        if needs_stdin_pipe || needs_stdout_pipe {
-            // パイプ付きスポーン: SYS_SPAWN_REDIRECTED を使用
-            self.spawn_with_pipes(program_bytes, args_ptr, args_buf_len, needs_stdin_pipe, needs_stdout_pipe)
+        // 環境変数の変更（env / env_remove / env_clear）があれば、子の環境変数すべてを作る
+        let env_buf = match self.env.capture_if_changed() {
+            Some(vars) => Some(build_env_buffer(vars.iter())?),
+            None => None,
+        };
+
+        if needs_stdin_pipe || needs_stdout_pipe || env_buf.is_some() || self.cwd.is_some() {
+            // リダイレクト付きスポーン: SYS_SPAWN_REDIRECTED を使用
+            self.spawn_redirected(
+                program_bytes,
+                args_ptr,
+                args_buf_len,
+                env_buf.as_deref(),
+                needs_stdin_pipe,
+                needs_stdout_pipe,
+            )
         } else {
             // 通常スポーン: SYS_SPAWN を使用
             self.spawn_simple(program_bytes, args_ptr, args_buf_len)
@@ -329,7 +378,10 @@ impl Command {
         Ok((process, pipes))
     }
 
-    /// パイプ付きスポーン。SYS_PIPE + SYS_SPAWN_REDIRECTED を使用する。
+    /// リダイレクト付きスポーン。SYS_PIPE + SYS_SPAWN_REDIRECTED を使用する。
+    ///
+    /// `env` が Some なら子の環境変数をそれで置き換え、self.cwd があれば子の
+    /// カレントディレクトリにする。
     ///
     /// ## 動作
     ///
@@ -344,11 +396,12 @@ impl Command {
     /// 2. read_end を子プロセスの stdin として渡す
     /// 3. 親で read_end を drop
     /// 4. 親で write_end を StdioPipes.stdin に返す
-    fn spawn_with_pipes(
+    fn spawn_redirected(
         &self,
         program_bytes: &[u8],
         args_ptr: *const u8,
         args_len: usize,
+        env: Option<&[u8]>,
         needs_stdin_pipe: bool,
         needs_stdout_pipe: bool,
     ) -> io::Result<(Process, StdioPipes)> {
@@ -385,6 +438,9 @@ impl Command {
                 (u64::MAX, 0u64, None)
             };
 
+        // 作業ディレクトリ（空なら継承）。相対パスはカーネルが親のカレントディレクトリからたどる
+        let cwd_bytes = self.cwd.as_ref().map_or(&[][..], |c| c.as_bytes());
+
         // SYS_SPAWN_REDIRECTED で子プロセスを起動
         let spawn_args = SpawnRedirectArgs {
             path_ptr: program_bytes.as_ptr() as u64,
@@ -395,6 +451,11 @@ impl Command {
             stdin_handle_token: stdin_child_handle_token,
             stdout_handle_id: stdout_child_handle_id,
             stdout_handle_token: stdout_child_handle_token,
+            // 空の環境（env_clear だけ）でも「継承」と区別できるよう、ポインタは 0 にしない
+            env_ptr: env.map_or(0, |e| e.as_ptr() as u64),
+            env_len: env.map_or(0, |e| e.len() as u64),
+            cwd_ptr: cwd_bytes.as_ptr() as u64,
+            cwd_len: cwd_bytes.len() as u64,
         };
 
         let ret = syscall_spawn_redirected(&spawn_args);
@@ -640,7 +701,7 @@ impl Process {
                 "SYS_WAIT failed",
             ));
         }
-        let status = ExitStatus(ret as i32);
+        let status = ExitStatus(ret as u32 as i32);
         self.status = Some(status);
         Ok(status)
     }
@@ -655,7 +716,7 @@ impl Process {
             // まだ終了していない（タイムアウト）
             return Ok(None);
         }
-        let status = ExitStatus(ret as i32);
+        let status = ExitStatus(ret as u32 as i32);
         self.status = Some(status);
         Ok(Some(status))
     }
@@ -0,0 +1,225 @@
+// sys/thread_local/key/sabos.rs — SABOS 用 TLS キー（thread_local! の key モード）
+//
+// スレッドごとに 1 ページのキーテーブルを SYS_MMAP で確保し、その先頭アドレスを
+// SYS_THREAD_SET_TLS(113) でカーネルのタスクに覚えさせる。キーはテーブルの添字で、
+// どのスレッドでも同じキーが自分のテーブルの同じ位置を指す。
+//
+// 以前は no_threads モード（thread_local がただのグローバル変数）だったため、
+// std::thread::current() がどのスレッドでもメインスレッドを返し、
+// mpsc のように「待っているスレッドを起こす」仕組みが正しく動かなかった。
+//
+// ## デストラクタ
+//
+// カーネルにはスレッド終了時のコールバックがないので、xous と同じく
+// キーごとのデストラクタをリストで持ち、スレッドの終了直前に destroy_tls() で実行する
+// （sys/thread/sabos.rs の _thread_entry_rust から呼ぶ）。
+// メインスレッドの値はプロセスの終了でまとめて消えるので実行しない。
+//
+// ## 速さ
+//
+// get / set のたびに SYS_THREAD_GET_TLS(114) を呼ぶ。std の中で thread_local を使うのは
+// スレッドハンドルの取得やブロックする前の準備など、もともと重い処理の前後なので許容する。
+
+use crate::alloc::System;
+use crate::mem::ManuallyDrop;
+use crate::ptr;
+use crate::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use crate::sync::atomic::{Atomic, AtomicPtr, AtomicUsize};
+
+pub type Key = usize;
+pub type Dtor = unsafe extern "C" fn(*mut u8);
+
+/// キーテーブルの大きさ（1 ページ）
+const TLS_MEMORY_SIZE: usize = 4096;
+/// キーテーブルに入るポインタの数（キー 0 は使わない）
+const TLS_SLOTS: usize = TLS_MEMORY_SIZE / size_of::<*mut u8>();
+
+/// MMAP のプロテクションフラグ: 読み取り可能
+const MMAP_PROT_READ: u64 = 0x1;
+/// MMAP のプロテクションフラグ: 書き込み可能
+const MMAP_PROT_WRITE: u64 = 0x2;
+/// MMAP のフラグ: 匿名マッピング
+const MMAP_FLAG_ANONYMOUS: u64 = 0x1;
+
+/// 次に割り当てるキー（1 から始まる）
+static TLS_KEY_INDEX: Atomic<usize> = AtomicUsize::new(1);
+
+/// デストラクタ付きのキーのリスト（登録したら外さない）
+static DTORS: Atomic<*mut Node> = AtomicPtr::new(ptr::null_mut());
+
+////////////////////////////////////////////////////////////////////////////////
+// syscall ヘルパー（インラインアセンブリ）
+////////////////////////////////////////////////////////////////////////////////
+
+/// SYS_MMAP(28): キーテーブル用の匿名ページを確保する（ゼロ埋めされている）
+fn syscall_mmap(len: u64) -> i64 {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 28u64,          // SYS_MMAP
+            in("rdi") 0u64,
+            in("rsi") len,
+            in("rdx") MMAP_PROT_READ | MMAP_PROT_WRITE,
+            in("r10") MMAP_FLAG_ANONYMOUS,
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    ret as i64
+}
+
+/// SYS_MUNMAP(29): スレッドの終了時にキーテーブルを解放する
+fn syscall_munmap(addr: u64, len: u64) {
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 29u64,          // SYS_MUNMAP
+            in("rdi") addr,
+            in("rsi") len,
+            lateout("rax") _,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+}
+
+/// SYS_THREAD_SET_TLS(113): 現在のスレッドの TLS ポインタを設定する
+fn syscall_thread_set_tls(ptr: u64) {
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 113u64,         // SYS_THREAD_SET_TLS
+            in("rdi") ptr,
+            lateout("rax") _,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+}
+
+/// SYS_THREAD_GET_TLS(114): 現在のスレッドの TLS ポインタを返す（未設定なら 0）
+fn syscall_thread_get_tls() -> u64 {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "int 0x80",
+            in("rax") 114u64,         // SYS_THREAD_GET_TLS
+            lateout("rax") ret,
+            lateout("rcx") _,
+            lateout("r11") _,
+        );
+    }
+    ret
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// キーテーブル
+////////////////////////////////////////////////////////////////////////////////
+
+/// 現在のスレッドのキーテーブル。まだなければ確保して SYS_THREAD_SET_TLS で登録する。
+fn tls_table() -> &'static mut [*mut u8] {
+    let mut table = syscall_thread_get_tls();
+    if table == 0 {
+        let addr = syscall_mmap(TLS_MEMORY_SIZE as u64);
+        if addr <= 0 {
+            rtabort!("cannot allocate thread local storage");
+        }
+        table = addr as u64;
+        syscall_thread_set_tls(table);
+    }
+    unsafe {
+        core::slice::from_raw_parts_mut(ptr::with_exposed_provenance_mut(table as usize), TLS_SLOTS)
+    }
+}
+
+#[inline]
+pub fn create(dtor: Option<Dtor>) -> Key {
+    // キーは全スレッドで共通。テーブルに収まらなくなったら続けられない
+    let key = TLS_KEY_INDEX.fetch_add(1, Relaxed);
+    if key >= TLS_SLOTS {
+        rtabort!("too many thread local keys");
+    }
+    if let Some(f) = dtor {
+        unsafe { register_dtor(key, f) };
+    }
+    key
+}
+
+#[inline]
+pub unsafe fn set(key: Key, value: *mut u8) {
+    tls_table()[key] = value;
+}
+
+#[inline]
+pub unsafe fn get(key: Key) -> *mut u8 {
+    tls_table()[key]
+}
+
+#[inline]
+pub unsafe fn destroy(_key: Key) {
+    // キーは再利用しない（thread_local のキーはプロセスで数十個程度なので足りる）
+}
+
+struct Node {
+    dtor: Dtor,
+    key: Key,
+    next: *mut Node,
+}
+
+unsafe fn register_dtor(key: Key, dtor: Dtor) {
+    // グローバルアロケータが thread_local を使っていても再帰しないよう System で確保する
+    let mut node =
+        ManuallyDrop::new(Box::new_in(Node { key, dtor, next: ptr::null_mut() }, System));
+
+    let mut head = DTORS.load(Acquire);
+    loop {
+        node.next = head;
+        match DTORS.compare_exchange(head, &mut **node, Release, Acquire) {
+            Ok(_) => return,
+            Err(cur) => head = cur,
+        }
+    }
+}
+
+/// 現在のスレッドの thread_local の値を破棄し、キーテーブルを解放する。
+/// スレッドの終了直前に呼ぶ。
+pub unsafe fn destroy_tls() {
+    let table = syscall_thread_get_tls();
+    // テーブルがなければ、このスレッドは thread_local を使っていない
+    if table == 0 {
+        return;
+    }
+
+    unsafe { run_dtors() };
+
+    syscall_thread_set_tls(0);
+    syscall_munmap(table, TLS_MEMORY_SIZE as u64);
+}
+
+// デストラクタの中の解放がテーブルの解放より後ろに並べ替えられないよう inline しない
+#[inline(never)]
+unsafe fn run_dtors() {
+    // デストラクタが新しい thread_local を作ることがあるので、何も残らなくなるまで
+    // 最大 5 周する（Windows / xous と同じ回数）
+    let mut any_run = true;
+    for _ in 0..5 {
+        if !any_run {
+            break;
+        }
+        any_run = false;
+        let mut cur = DTORS.load(Acquire);
+        while !cur.is_null() {
+            let ptr = unsafe { get((*cur).key) };
+            if !ptr.is_null() {
+                unsafe { set((*cur).key, ptr::null_mut()) };
+                unsafe { ((*cur).dtor)(ptr as *mut _) };
+                any_run = true;
+            }
+            unsafe { cur = (*cur).next };
+        }
+    }
+
+    crate::rt::thread_cleanup();
+}
@@ -9,17 +9,12 @@
 // join 時に SYS_MUNMAP(29) で解放する。x86_64 ではスタックは下向きに伸びるため、
 // スタックトップ = 確保した領域の末尾（16 バイトアラインメント）。
 //
-// ## thread_local の制約（no_threads モード）
+// ## thread_local
 //
-// SABOS は thread_local で `no_threads` モードを使っている。
-// このモードでは thread-local 変数が実際にはグローバルな Cell になる。
-// そのため ThreadInit::init() を呼ぶと set_current() が rtabort! する
-// （メインスレッドで既に CURRENT が設定済みのため）。
-//
-// 対策として init.init() をスキップし、rust_start を直接取り出して実行する。
-// これにより std::thread::current() はスポーンしたスレッドからは
-// メインスレッドのハンドルを返す（不正確だがクラッシュしない）。
-// 将来 thread_local を `thread_local_key` モードに切り替えれば正しく動作する。
+// thread_local は key モード（sys/thread_local/key/sabos.rs）で、スレッドごとの
+// キーテーブルを SYS_THREAD_SET_TLS で持つ。スレッドの開始時に ThreadInit::init() で
+// std::thread::current() のハンドルを設定し、終了直前に destroy_tls() で
+// thread_local の値を破棄する。
 
 use crate::num::NonZero;
 use crate::thread::ThreadInit;
@@ -228,29 +223,19 @@ core::arch::global_asm!(
 ///
 /// _thread_entry_trampoline から call で呼ばれる。
 /// rdi = arg = Box<ThreadInit> のポインタ。
-///
-/// ## no_threads モードの制約
-///
-/// SABOS は thread_local で `no_threads` モードを使っている。
-/// このモードでは thread-local 変数が実際にはグローバルな Cell になるため、
-/// ThreadInit::init() を呼ぶと set_current() が rtabort! する
-/// （メインスレッドで既に CURRENT が設定済みのため）。
-///
-/// そのため init.init() をスキップし、rust_start を直接取り出して実行する。
 #[unsafe(no_mangle)]
 extern "C" fn _thread_entry_rust(arg: u64) -> ! {
     // arg は Box<ThreadInit> のポインタ。
     // Box::from_raw でヒープ上の ThreadInit を復元する。
     let init = unsafe { Box::from_raw(arg as *mut ThreadInit) };
 
-    // no_threads モードでは init.init() を呼べない（rtabort! するため）。
-    // 代わりに ThreadInit を destructure して rust_start を直接取り出す。
-    // handle は使わないが、正しく drop する。
-    let ThreadInit { handle: _handle, rust_start } = *init;
-
-    // ユーザーのクロージャを実行
+    // このスレッドの std::thread::current() を設定してから、ユーザーのクロージャを実行
+    let rust_start = init.init();
     rust_start();
 
+    // thread_local の値を破棄してキーテーブルを解放する
+    unsafe { crate::sys::thread_local::key::destroy_tls() };
+
     // スレッドを終了（終了コード 0）
     syscall_thread_exit(0);
 }
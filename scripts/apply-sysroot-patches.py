@@ -128,20 +128,34 @@ def patch_stdio_mod(content: str) -> str:
 
 
 def patch_thread_local_mod(content: str) -> str:
-    """sys/thread_local/mod.rs: no_threads と guard の条件に sabos を追加"""
-    # no_threads ブロック: 8スペース + target_os = "vexos", の後に追加
-    content = insert_after_line(
-        content,
-        '        target_os = "vexos",',
-        '        target_os = "sabos",'
-    )
-    # guard ブロック: 12スペース + target_os = "vexos", の後に追加
+    """sys/thread_local/mod.rs: sabos を key モード（TLS キーテーブル）にする。
+
+    target_thread_local を持たないので、no_threads に入れなければ os モードが選ばれる。
+    key モジュールに sabos ブランチを足し、guard は hermit / xous と同じく
+    std 自身がデストラクタを呼ぶ（enable は何もしない）側に入れる。
+
+    以前のパッチ（no_threads と wasm 側の guard に sabos を入れていた）が当たった
+    sysroot もそのまま移行できるよう、古い行を取り除いてから当てる。"""
+    old_lines = ('        target_os = "sabos",', '            target_os = "sabos",')
+    content = "\n".join(line for line in content.split("\n") if line not in old_lines)
+    # guard ブロック: 12スペース + target_os = "xous", の後に追加
     content = insert_after_line(
         content,
-        '            target_os = "vexos",',
+        '            target_os = "xous",',
         '            target_os = "sabos",'
     )
-    return content
+    # key モジュール: 最後の _ => {} の直前に追加
+    sabos_branch = (
+        '        target_os = "sabos" => {\n'
+        '            mod racy;\n'
+        '            mod sabos;\n'
+        '            pub(super) use racy::LazyKey;\n'
+        '            pub(crate) use sabos::destroy_tls;\n'
+        '            pub(super) use sabos::{Key, get, set};\n'
+        '            use sabos::{create, destroy};\n'
+        '        }'
+    )
+    return insert_before_line(content, "        _ => {}", sabos_branch)
 
 
 def patch_env_consts(content: str) -> str:
@@ -310,6 +324,16 @@ def patch_env_mod(content: str) -> str:
     return content
 
 
+def patch_sync_futex_mod(content: str) -> str:
+    """sys/sync/{mutex,condvar,rwlock,once,thread_parking}/mod.rs: futex 実装を使う条件に sabos を追加する。
+    どれも futex ブランチの any(...) が target_os = "hermit", を含むので、その直後に足す。"""
+    return insert_after_line(
+        content,
+        '        target_os = "hermit",',
+        '        target_os = "sabos",'
+    )
+
+
 # ============================================================
 # メイン
 # ============================================================
@@ -325,7 +349,7 @@ def main():
         ("sys/pal/mod.rs", 'target_os = "sabos"', patch_pal_mod),
         ("sys/alloc/mod.rs", 'target_os = "sabos"', patch_alloc_mod),
         ("sys/stdio/mod.rs", 'target_os = "sabos"', patch_stdio_mod),
-        ("sys/thread_local/mod.rs", 'target_os = "sabos"', patch_thread_local_mod),
+        ("sys/thread_local/mod.rs", 'use sabos::destroy_tls', patch_thread_local_mod),
         ("sys/env_consts.rs", 'target_os = "sabos"', patch_env_consts),
         ("sys/io/error/mod.rs", 'target_os = "sabos"', patch_io_error_mod),
         ("sys/random/mod.rs", 'target_os = "sabos"', patch_random_mod),
@@ -338,6 +362,11 @@ def main():
         ("sys/process/mod.rs", 'target_os = "sabos"', patch_process_mod),
         ("sys/pipe/mod.rs", 'target_os = "sabos"', patch_pipe_mod),
         ("sys/thread/mod.rs", 'target_os = "sabos"', patch_thread_mod),
+        ("sys/sync/mutex/mod.rs", 'target_os = "sabos"', patch_sync_futex_mod),
+        ("sys/sync/condvar/mod.rs", 'target_os = "sabos"', patch_sync_futex_mod),
+        ("sys/sync/rwlock/mod.rs", 'target_os = "sabos"', patch_sync_futex_mod),
+        ("sys/sync/once/mod.rs", 'target_os = "sabos"', patch_sync_futex_mod),
+        ("sys/sync/thread_parking/mod.rs", 'target_os = "sabos"', patch_sync_futex_mod),
     ]
 
     for rel_path, marker, patch_fn in patches:
@@ -0,0 +1,35 @@
+#!/bin/bash
+# build-kernel.sh — シンボルテーブルつきでカーネルをビルドする
+#
+# シンボルテーブル（関数名と RVA の表）はビルドしたカーネルの PDB からしか作れないので、
+#   1. カーネルをビルドする（前回のテーブル、初回は空のテーブルが埋め込まれる）
+#   2. PDB からテーブルを作る（scripts/gen-kernel-symbols.py）
+#   3. テーブルが変わっていたら埋め込み直して再ビルドし、2 に戻る
+# をテーブルが変わらなくなるまで繰り返す。
+# テーブルは .text より後ろの .rdata に入るので、普通は 2 回目のビルドで落ち着く。
+#
+# 使い方: scripts/build-kernel.sh [cargo build に渡す引数...]
+
+set -e
+
+SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
+cd "$SCRIPT_DIR/../kernel"
+
+TARGET_DIR=target/x86_64-unknown-uefi/debug
+export SABOS_SYMBOLS="$PWD/target/kernel-symbols.bin"
+
+for _ in 1 2 3 4; do
+    cargo build "$@"
+    status=0
+    python3 "$SCRIPT_DIR/gen-kernel-symbols.py" \
+        "$TARGET_DIR/sabos.efi" "$TARGET_DIR/sabos.pdb" "$SABOS_SYMBOLS" || status=$?
+    if [ "$status" -eq 0 ]; then
+        exit 0
+    fi
+    if [ "$status" -ne 10 ]; then
+        exit "$status"
+    fi
+done
+
+echo "error: kernel symbol table did not converge" >&2
+exit 1
@@ -0,0 +1,142 @@
+#!/usr/bin/env python3
+"""
+gen-kernel-symbols.py — カーネルのシンボルテーブルを作る
+
+パニック時のバックトレースや addr2line コマンドで戻りアドレスを関数名に直すため、
+ビルドしたカーネル (sabos.efi) の PDB から関数の公開シンボルを取り出し、
+カーネルに埋め込む形式（kernel/src/symbols.rs が読む）で書き出す。
+
+使い方:
+  python3 scripts/gen-kernel-symbols.py <sabos.efi> <sabos.pdb> <出力ファイル>
+
+終了コード:
+  0  出力ファイルは既に最新だった（埋め込まれているテーブルと一致）
+  10 出力ファイルを書き換えた（カーネルを再ビルドする必要がある）
+  その他 エラー
+
+llvm-pdbutil / llvm-cxxfilt が無い環境では警告だけ出して 0 で終わる
+（シンボルテーブルは任意なので、無くてもカーネルは動く）。
+コマンド名は環境変数 LLVM_PDBUTIL / LLVM_CXXFILT で変えられる。
+
+出力形式（すべてリトルエンディアン）:
+  ヘッダ   magic "KSYM", エントリ数 u32, .text の終わりの RVA u32
+  エントリ RVA u32, 名前のオフセット u32, 名前の長さ u32（RVA の昇順）
+  名前     UTF-8 の文字列を並べたもの（オフセットはこの領域の先頭から）
+"""
+
+import os
+import re
+import shutil
+import struct
+import subprocess
+import sys
+
+MAGIC = b"KSYM"
+UPDATED = 10
+
+# llvm-pdbutil dump --publics の 1 シンボルぶん（名前の行と flags/addr の行）
+PUBLIC_RE = re.compile(
+    r"S_PUB32 \[size = \d+\] `([^`]+)`\s*\n\s*flags = ([^,]*(?:, [^,]+)*?), addr = (\d+):(\d+)"
+)
+
+
+def read_sections(efi_path):
+    """PE のセクションヘッダを読み、(名前, RVA, サイズ) のリストを返す（番号は 1 始まり）"""
+    with open(efi_path, "rb") as f:
+        data = f.read()
+    pe_off = struct.unpack_from("<I", data, 0x3C)[0]
+    if data[pe_off:pe_off + 4] != b"PE\0\0":
+        raise ValueError(f"{efi_path}: not a PE image")
+    num_sections = struct.unpack_from("<H", data, pe_off + 6)[0]
+    opt_size = struct.unpack_from("<H", data, pe_off + 20)[0]
+    sec_off = pe_off + 24 + opt_size
+    sections = []
+    for i in range(num_sections):
+        name, vsize, rva = struct.unpack_from("<8sII", data, sec_off + i * 40)
+        sections.append((name.rstrip(b"\0").decode(), rva, vsize))
+    return sections
+
+
+def read_publics(pdbutil, pdb_path):
+    """関数の公開シンボルを (セクション番号, オフセット, マングルされた名前) で返す"""
+    out = subprocess.run(
+        [pdbutil, "dump", "--publics", pdb_path],
+        check=True, capture_output=True, text=True,
+    ).stdout
+    symbols = []
+    for m in PUBLIC_RE.finditer(out):
+        name, flags, section, offset = m.group(1), m.group(2), int(m.group(3)), int(m.group(4))
+        if "function" in flags:
+            symbols.append((section, offset, name))
+    return symbols
+
+
+def demangle(cxxfilt, names):
+    """llvm-cxxfilt でまとめてデマングルする（Rust の v0 マングリングも読める）"""
+    out = subprocess.run(
+        [cxxfilt], input="\n".join(names) + "\n",
+        check=True, capture_output=True, text=True,
+    ).stdout
+    demangled = out.split("\n")[:len(names)]
+    if len(demangled) != len(names):
+        raise ValueError("llvm-cxxfilt returned fewer lines than expected")
+    return demangled
+
+
+def build_table(efi_path, pdb_path, pdbutil, cxxfilt):
+    sections = read_sections(efi_path)
+    text = next((s for s in sections if s[0] == ".text"), None)
+    if text is None:
+        raise ValueError(f"{efi_path}: no .text section")
+    text_end = text[1] + text[2]
+
+    publics = read_publics(pdbutil, pdb_path)
+    names = demangle(cxxfilt, [name for _, _, name in publics])
+
+    # 同じアドレスに複数の名前があるとき（同じ中身の関数がまとめられた等）は 1 つだけ残す。
+    # どれを残すかがビルドごとに変わらないよう、名前の順で最初のものにする。
+    by_rva = {}
+    for (section, offset, _), name in zip(publics, names):
+        if not 1 <= section <= len(sections):
+            continue
+        rva = sections[section - 1][1] + offset
+        if rva not in by_rva or name < by_rva[rva]:
+            by_rva[rva] = name
+
+    entries = bytearray()
+    strings = bytearray()
+    for rva in sorted(by_rva):
+        encoded = by_rva[rva].encode("utf-8")
+        entries += struct.pack("<III", rva, len(strings), len(encoded))
+        strings += encoded
+    header = MAGIC + struct.pack("<II", len(by_rva), text_end)
+    return bytes(header + entries + strings), len(by_rva)
+
+
+def main():
+    if len(sys.argv) != 4:
+        print(__doc__.strip(), file=sys.stderr)
+        return 2
+    efi_path, pdb_path, out_path = sys.argv[1:]
+    pdbutil = os.environ.get("LLVM_PDBUTIL", "llvm-pdbutil")
+    cxxfilt = os.environ.get("LLVM_CXXFILT", "llvm-cxxfilt")
+    for tool in (pdbutil, cxxfilt):
+        if shutil.which(tool) is None:
+            print(f"warning: {tool} not found, kernel symbol table is not generated", file=sys.stderr)
+            return 0
+
+    table, count = build_table(efi_path, pdb_path, pdbutil, cxxfilt)
+    try:
+        with open(out_path, "rb") as f:
+            if f.read() == table:
+                return 0
+    except FileNotFoundError:
+        pass
+    with open(out_path, "wb") as f:
+        f.write(table)
+    print(f"kernel symbols: {count} functions, {len(table)} bytes -> {out_path}")
+    return UPDATED
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,71 @@
+#!/usr/bin/env python3
+"""measure-tcp-echo.py — telnetd との TCP 往復時間を測るスクリプト
+
+virtio-net の受信を MSI-X 割り込みで起こす場合と、ポーリングで拾う場合の
+遅延を比べるために使う。hostfwd で転送された telnetd（tsh）に空行を送り、
+次の "tsh>" プロンプトが返ってくるまでの時間を繰り返し測る。
+
+使い方:
+  ./scripts/run-qemu.sh --bg              # MSI-X あり
+  python3 scripts/measure-tcp-echo.py
+  ./scripts/run-qemu.sh --bg --no-msix    # MSI-X なし（ポーリング）
+  python3 scripts/measure-tcp-echo.py
+
+オプション:
+  --port P    ホスト側 telnet ポート（デフォルト: 12323）
+  --count N   測る回数（デフォルト: 100）
+"""
+
+import argparse
+import socket
+import statistics
+import sys
+import time
+
+PROMPT = b"tsh>"
+
+
+def read_until_prompt(sock, timeout):
+    """PROMPT が来るまで読み捨てる。来なければ TimeoutError"""
+    deadline = time.monotonic() + timeout
+    buf = b""
+    while PROMPT not in buf:
+        remaining = deadline - time.monotonic()
+        if remaining <= 0:
+            raise TimeoutError("prompt not received")
+        sock.settimeout(remaining)
+        chunk = sock.recv(4096)
+        if not chunk:
+            raise ConnectionError("connection closed")
+        # 途中で切れたプロンプトも拾えるよう、末尾だけ残す
+        buf = (buf + chunk)[-256:]
+
+
+def main():
+    parser = argparse.ArgumentParser(description="telnetd との TCP 往復時間を測る")
+    parser.add_argument("--port", type=int, default=12323)
+    parser.add_argument("--count", type=int, default=100)
+    args = parser.parse_args()
+
+    with socket.create_connection(("127.0.0.1", args.port), timeout=30) as sock:
+        sock.setsockopt(socket.IPPROTO_TCP, socket.TCP_NODELAY, 1)
+        read_until_prompt(sock, 30)
+
+        samples = []
+        for _ in range(args.count):
+            start = time.perf_counter()
+            sock.sendall(b"\r")
+            read_until_prompt(sock, 5)
+            samples.append((time.perf_counter() - start) * 1000)
+        sock.sendall(b"exit\r")
+
+    samples.sort()
+    p90 = samples[int(len(samples) * 0.9) - 1]
+    print(f"round trips: {len(samples)}")
+    print(f"min {samples[0]:.2f} ms / median {statistics.median(samples):.2f} ms"
+          f" / p90 {p90:.2f} ms / max {samples[-1]:.2f} ms")
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -53,6 +53,9 @@ cp "$PATCH_DIR/sys_pal_sabos_common.rs" "$PAL_DIR/common.rs"
 echo "[COPY] sys/pal/sabos/os.rs"
 cp "$PATCH_DIR/sys_pal_sabos_os.rs" "$PAL_DIR/os.rs"
 
+echo "[COPY] sys/pal/sabos/futex.rs"
+cp "$PATCH_DIR/sys_pal_sabos_futex.rs" "$PAL_DIR/futex.rs"
+
 # ---- 2. alloc ファイルのコピー ----
 
 echo "[COPY] sys/alloc/sabos.rs"
@@ -108,6 +111,11 @@ cp "$PATCH_DIR/sys_pipe_sabos.rs" "$STD_SRC/sys/pipe/sabos.rs"
 echo "[COPY] sys/thread/sabos.rs"
 cp "$PATCH_DIR/sys_thread_sabos.rs" "$STD_SRC/sys/thread/sabos.rs"
 
+# ---- 3l. thread_local/key ファイルのコピー ----
+
+echo "[COPY] sys/thread_local/key/sabos.rs"
+cp "$PATCH_DIR/sys_thread_local_key_sabos.rs" "$STD_SRC/sys/thread_local/key/sabos.rs"
+
 # ---- 3f. os/sabos ディレクトリの作成とファイルコピー ----
 
 OS_SABOS_DIR="$STD_SRC/os/sabos"
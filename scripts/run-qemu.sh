@@ -11,6 +11,7 @@
 #   --bg             バックグラウンド実行（PID とログパスを表示して戻る）
 #   --log FILE       ログファイルを指定（デフォルト: ./logs/YYYYMMDD-HHMMSS.$$.log）
 #   --telnet-port P  ホスト側 telnet ポートを指定（デフォルト: 12323）
+#   --no-msix        virtio-net の MSI-X を外す（ポーリングとの遅延比較用）
 #
 # 機能:
 #   - 起動前に既存 QEMU プロセスを自動 pkill（モニターポートでマッチ）
@@ -29,6 +30,7 @@ MONITOR_PORT=55582
 TELNET_HOST_PORT=12323
 BG_MODE=false
 LOG_FILE=""
+VIRTIO_NET_DEVICE="virtio-net-pci,netdev=net0"
 
 # --- OVMF ファームウェア検出 ---
 # Makefile と同じロジック: 4M 版を優先、なければ通常版
@@ -67,6 +69,11 @@ while [ $# -gt 0 ]; do
             TELNET_HOST_PORT="$2"
             shift 2
             ;;
+        --no-msix)
+            # vectors=0 で MSI-X ケイパビリティが消え、カーネルはポーリングに戻る
+            VIRTIO_NET_DEVICE="virtio-net-pci,netdev=net0,vectors=0"
+            shift
+            ;;
         *)
             echo "Unknown option: $1" >&2
             exit 1
@@ -95,6 +102,7 @@ build_qemu_args() {
         -nodefaults
         -machine q35
         -m 256
+        -smp 2
         -cpu max
         -vga std
         -drive "if=pflash,format=raw,readonly=on,file=$OVMF_CODE"
@@ -103,7 +111,7 @@ build_qemu_args() {
         -drive "if=virtio,format=raw,file=disk.img"
         -drive "if=virtio,format=raw,file=hostfs.img"
         -netdev "user,id=net0,ipv4=on,ipv6=on,hostfwd=tcp::${TELNET_HOST_PORT}-:2323"
-        -device virtio-net-pci,netdev=net0
+        -device "$VIRTIO_NET_DEVICE"
         -virtfs "local,id=fsdev0,path=.,mount_tag=hostfs9p,security_model=none"
         -netdev "user,id=net1"
         -device "e1000e,netdev=net1"
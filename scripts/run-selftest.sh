@@ -30,6 +30,9 @@ TELNET_LOG="logs/selftest-telnet-${TIMESTAMP}.$$.log"
 
 MONITOR_PORT=55582
 TELNET_HOST_PORT=12323
+# カーネル selftest の tcp6_host_echo がつなぐ IPv6 エコーサーバーのポート
+# （kernel/src/netstack/tcp.rs の TCP6_ECHO_PORT と合わせる）
+TCP6_ECHO_PORT=12307
 KEY_DELAY=0.3
 GUI_SCREENSHOT_PATH_FILE="scripts/gui-screenshot-path.txt"
 
@@ -40,6 +43,9 @@ cleanup() {
         kill "$QEMU_PID" 2>/dev/null || true
         wait "$QEMU_PID" 2>/dev/null || true
     fi
+    if [ -n "$ECHO6_PID" ]; then
+        kill "$ECHO6_PID" 2>/dev/null || true
+    fi
     # telnet のテンポラリログだけ削除（メインログは残す）
     rm -f "$TELNET_LOG"
 }
@@ -53,6 +59,10 @@ sleep 1
 # EFI をコピー
 cp kernel/target/x86_64-unknown-uefi/debug/sabos.efi esp/EFI/BOOT/BOOTX64.EFI
 
+# IPv6 TCP テストの相手（SLIRP が fec0::2 宛の接続をホストの ::1 に中継する）
+python3 scripts/tcp6-echo-server.py --port "$TCP6_ECHO_PORT" &
+ECHO6_PID=$!
+
 echo "Starting QEMU..."
 echo "Log file: $LOG_FILE"
 
@@ -69,6 +79,7 @@ qemu-system-x86_64 \
     -nodefaults \
     -machine q35 \
     -m 256 \
+    -smp 2 \
     -cpu max \
     -vga std \
     -drive if=pflash,format=raw,readonly=on,file="$OVMF_CODE" \
@@ -225,6 +236,36 @@ else
             timeout { exit 1 }
         }
         # std バイナリの各テスト結果を検証する
+        # sync_all の後は close 前でも別のハンドルから内容が読めること（SYS_FSYNC）
+        expect {
+            \"fs::sync_all OK\" { }
+            \"fs::sync_all FAILED\" { exit 1 }
+            timeout { exit 1 }
+        }
+        # 子が書いて終わったパイプを BufReader で最後まで読み、その後 EOF になること
+        expect {
+            \"process::pipe_eof OK\" { }
+            \"process::pipe_eof FAILED\" { exit 1 }
+            timeout { exit 1 }
+        }
+        # Command::env で渡した環境変数が子に見えること（SpawnRedirectArgs の env）
+        expect {
+            \"process::env OK\" { }
+            \"process::env FAIL\" { exit 1 }
+            timeout { exit 1 }
+        }
+        # 2 スレッドの Mutex カウンタで取りこぼしがないこと（futex ベースの std::sync）
+        expect {
+            \"sync::mutex_counter OK\" { }
+            \"sync::mutex_counter FAILED\" { exit 1 }
+            timeout { exit 1 }
+        }
+        # 別スレッド間の mpsc で 1000 個の整数が届くこと（key モードの thread_local + futex の park）
+        expect {
+            \"sync::mpsc_sum OK\" { }
+            \"sync::mpsc_sum FAILED\" { exit 1 }
+            timeout { exit 1 }
+        }
         expect {
             \"serde::from_str OK\" { }
             timeout { exit 1 }
@@ -235,6 +276,12 @@ else
             \"net::tcp_parse OK\" { }
             timeout { exit 1 }
         }
+        # ループバックの accept が相手のアドレスとして接続元の local_addr を返すこと
+        expect {
+            \"net::tcp_accept_peer OK\" { }
+            \"net::tcp_accept_peer FAILED\" { exit 1 }
+            timeout { exit 1 }
+        }
         expect \"tsh>\"
         send \"exit\r\"
         expect eof
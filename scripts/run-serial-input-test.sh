@@ -0,0 +1,114 @@
+#!/bin/bash
+# run-serial-input-test.sh — シリアル入力でシェルを操作できるかを確認するスクリプト
+#
+# ディスプレイなし（-display none）で QEMU を起動し、シリアル（COM1）の標準入力に
+# FIFO をつなぐ。ユーザーシェルのプロンプトが出たらシリアルから "help\n" を送り、
+# ヘルプの出力がシリアルに返ってくることを確認する。
+# キーボード（sendkey）を一切使わないので、ヘッドレス環境・CI でのシリアル操作の確認になる。
+#
+# 使い方:
+#   ./scripts/run-serial-input-test.sh
+#
+# 成功なら終了コード 0、失敗なら 1。ログは ./logs/ に保存される。
+
+set -e
+
+RED='\033[0;31m'
+GREEN='\033[0;32m'
+NC='\033[0m' # No Color
+
+# プロジェクトルートへ移動
+SCRIPT_DIR="$(cd "$(dirname "$0")" && pwd)"
+cd "$SCRIPT_DIR/.."
+
+mkdir -p logs
+TIMESTAMP="$(date +%Y%m%d-%H%M%S)"
+LOG_FILE="logs/serial-input-${TIMESTAMP}.$$.log"
+FIFO="logs/serial-input-${TIMESTAMP}.$$.fifo"
+MONITOR_PORT=55584
+
+OVMF_CODE="${OVMF_CODE:-$(ls /usr/share/OVMF/OVMF_CODE_4M.fd /usr/share/OVMF/OVMF_CODE.fd 2>/dev/null | head -1)}"
+OVMF_VARS="${OVMF_VARS:-$(ls /usr/share/OVMF/OVMF_VARS_4M.fd /usr/share/OVMF/OVMF_VARS.fd 2>/dev/null | head -1)}"
+
+if [ -z "$OVMF_CODE" ] || [ -z "$OVMF_VARS" ]; then
+    echo -e "${RED}ERROR: OVMF not found. Install with: sudo apt-get install ovmf${NC}"
+    exit 1
+fi
+
+cleanup() {
+    exec 3>&- 2>/dev/null || true
+    if [ -n "$QEMU_PID" ] && kill -0 "$QEMU_PID" 2>/dev/null; then
+        kill "$QEMU_PID" 2>/dev/null || true
+        wait "$QEMU_PID" 2>/dev/null || true
+    fi
+    rm -f "$FIFO"
+}
+trap cleanup EXIT
+
+pkill -9 -f "qemu-system-x86_64.*$MONITOR_PORT" 2>/dev/null || true
+sleep 1
+
+cp kernel/target/x86_64-unknown-uefi/debug/sabos.efi esp/EFI/BOOT/BOOTX64.EFI
+
+# シリアルの標準入力にする FIFO。
+# 書き込み側（fd 3）を開いたままにしておかないと、QEMU が EOF を受け取ってしまう。
+rm -f "$FIFO"
+mkfifo "$FIFO"
+
+echo "Starting QEMU (serial only)..."
+echo "Log file: $LOG_FILE"
+
+qemu-system-x86_64 \
+    -nodefaults \
+    -machine q35 \
+    -m 256 \
+    -smp 2 \
+    -cpu max \
+    -vga std \
+    -drive if=pflash,format=raw,readonly=on,file="$OVMF_CODE" \
+    -drive if=pflash,format=raw,readonly=on,file="$OVMF_VARS" \
+    -drive format=raw,file=fat:rw:esp \
+    -drive if=virtio,format=raw,file=disk.img \
+    -drive if=virtio,format=raw,file=hostfs.img \
+    -netdev user,id=net0,ipv4=on,ipv6=on -device virtio-net-pci,netdev=net0 \
+    -audiodev id=snd0,driver=none -device AC97,audiodev=snd0 \
+    -device isa-debug-exit,iobase=0xf4,iosize=0x04 \
+    -serial stdio \
+    -display none \
+    -monitor telnet:127.0.0.1:$MONITOR_PORT,server,nowait < "$FIFO" > "$LOG_FILE" 2>&1 &
+
+QEMU_PID=$!
+exec 3> "$FIFO"
+
+echo "Waiting for user shell prompt..."
+for i in {1..30}; do
+    if grep -q "Entering supervisor loop" "$LOG_FILE" 2>/dev/null; then
+        break
+    fi
+    sleep 1
+done
+if ! grep -q "user>" "$LOG_FILE" 2>/dev/null; then
+    echo -e "${RED}ERROR: User shell prompt not found after 30 seconds${NC}"
+    cat "$LOG_FILE"
+    exit 1
+fi
+sleep 1
+
+# シリアルから help を送る。端末エミュレータと同じく Enter は CR で送る
+# （カーネル側で CR を '\n' に変換している）
+printf 'help\r' >&3
+for i in {1..15}; do
+    if grep -q "Available Commands" "$LOG_FILE" 2>/dev/null; then
+        break
+    fi
+    sleep 1
+done
+
+if grep -q "Available Commands" "$LOG_FILE"; then
+    echo -e "${GREEN}Serial input PASSED: 'help' output appeared on serial${NC}"
+    exit 0
+else
+    echo -e "${RED}Serial input FAILED: no 'help' output on serial${NC}"
+    echo "Full log: $LOG_FILE"
+    exit 1
+fi
@@ -92,6 +92,7 @@ fi
 qemu-system-x86_64 \
     -nodefaults \
     -machine q35 \
+    -smp 2 \
     -cpu max \
     -vga std \
     -drive if=pflash,format=raw,readonly=on,file="$OVMF_CODE" \
@@ -0,0 +1,43 @@
+#!/usr/bin/env python3
+"""tcp6-echo-server.py — selftest の IPv6 TCP テストの相手になるエコーサーバー
+
+QEMU の SLIRP は、ゲストからホストアドレス (fec0::2) への TCP 接続を
+ホストの ::1 に中継する。run-selftest.sh がこのスクリプトを [::1]:PORT で動かし、
+カーネル selftest の tcp6_host_echo が実際の NIC 越しに IPv6 でつないで
+送ったデータがそのまま返ってくるかを確かめる。
+
+使い方:
+  python3 scripts/tcp6-echo-server.py [--port P]   # デフォルト: 12307
+"""
+
+import argparse
+import socket
+import sys
+import threading
+
+
+def echo(conn):
+    with conn:
+        while True:
+            data = conn.recv(4096)
+            if not data:
+                return
+            conn.sendall(data)
+
+
+def main():
+    parser = argparse.ArgumentParser(description="[::1] で待ち受ける TCP エコーサーバー")
+    parser.add_argument("--port", type=int, default=12307)
+    args = parser.parse_args()
+
+    with socket.socket(socket.AF_INET6, socket.SOCK_STREAM) as server:
+        server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+        server.bind(("::1", args.port))
+        server.listen()
+        while True:
+            conn, _ = server.accept()
+            threading.Thread(target=echo, args=(conn,), daemon=True).start()
+
+
+if __name__ == "__main__":
+    sys.exit(main())
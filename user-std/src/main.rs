@@ -52,6 +52,23 @@ fn main() {
     // テストファイルを削除して後始末
     let _ = std::fs::remove_file("/STDTEST.TXT");
 
+    // File::sync_all() テスト（SYS_FSYNC 経由）
+    // close する前でも、sync_all の後なら別のハンドルから書いた内容が読める
+    {
+        use std::io::Write;
+        let synced = std::fs::File::create("/STDSYNC.TXT").and_then(|mut file| {
+            file.write_all(b"synced by std")?;
+            file.sync_all()?;
+            std::fs::read("/STDSYNC.TXT")
+        });
+        match synced {
+            Ok(data) if data == b"synced by std" => println!("fs::sync_all OK"),
+            Ok(data) => println!("fs::sync_all FAILED: {:?}", String::from_utf8_lossy(&data)),
+            Err(e) => println!("fs::sync_all FAILED: {}", e),
+        }
+        let _ = std::fs::remove_file("/STDSYNC.TXT");
+    }
+
     // === std::time テスト ===
 
     // std::time::Instant::now() テスト（SYS_CLOCK_MONOTONIC 経由）
@@ -109,7 +126,7 @@ fn main() {
 
     // === std::env テスト ===
 
-    // std::env::current_dir() テスト（getcwd → "/" を返す）
+    // std::env::current_dir() テスト（SYS_GETCWD 経由。起動時は "/"）
     match std::env::current_dir() {
         Ok(path) => println!("env::current_dir OK: {}", path.display()),
         Err(e) => println!("env::current_dir error: {}", e),
@@ -181,6 +198,102 @@ fn main() {
         Err(e) => println!("process::output error: {}", e),
     }
 
+    // === パイプの部分読み取りと EOF テスト ===
+
+    // EXIT0.ELF upper は stdin を EOF まで読んで大文字にして返す。
+    // stdin を閉じると子が書き終えて exit し、BufReader の read_line / read_to_string が
+    // 全データを返した後、次の read が 0（EOF）になることを確かめる。
+    {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::process::Stdio;
+
+        let payload: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+        let result = std::process::Command::new("/EXIT0.ELF")
+            .arg("upper")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                let mut stdin = child.stdin.take().expect("stdin is piped");
+                stdin.write_all(payload.as_bytes())?;
+                drop(stdin);
+                let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+                let mut first = String::new();
+                reader.read_line(&mut first)?;
+                let mut rest = String::new();
+                reader.read_to_string(&mut rest)?;
+                let eof = reader.read(&mut [0u8; 16])? == 0;
+                child.wait()?;
+                Ok((first, rest, eof))
+            });
+        match result {
+            Ok((first, rest, true))
+                if first == "LINE 0\n" && format!("{}{}", first, rest) == payload.to_uppercase() =>
+            {
+                println!("process::pipe_eof OK");
+            }
+            Ok((first, rest, eof)) => println!(
+                "process::pipe_eof FAILED: first={:?} len={} eof={}",
+                first,
+                first.len() + rest.len(),
+                eof
+            ),
+            Err(e) => println!("process::pipe_eof FAILED: {}", e),
+        }
+    }
+
+    // === Command::env / env_clear / current_dir テスト ===
+
+    // Command::env() テスト（SpawnRedirectArgs の env 経由）
+    // EXIT0.ELF の getenv は値が一致すれば 0、違えば 1、無ければ 2 で終了する。
+    match std::process::Command::new("/EXIT0.ELF")
+        .args(["getenv", "SABOS_CMD_ENV", "from_command"])
+        .env("SABOS_CMD_ENV", "from_command")
+        .status()
+    {
+        Ok(status) if status.code() == Some(0) => println!("process::env OK"),
+        Ok(status) => println!("process::env FAIL: exit_code={:?}", status.code()),
+        Err(e) => println!("process::env error: {}", e),
+    }
+
+    // Command::env_clear() テスト: 親の SABOS_TEST が子に見えない（終了コード 2）
+    match std::process::Command::new("/EXIT0.ELF")
+        .args(["getenv", "SABOS_TEST", "hello_env"])
+        .env_clear()
+        .status()
+    {
+        Ok(status) if status.code() == Some(2) => println!("process::env_clear OK"),
+        Ok(status) => println!("process::env_clear FAIL: exit_code={:?}", status.code()),
+        Err(e) => println!("process::env_clear error: {}", e),
+    }
+
+    // Command::current_dir() テスト（SpawnRedirectArgs の cwd 経由）
+    // EXIT0.ELF の getcwd はカレントディレクトリが引数と一致すれば 0 で終了する。
+    let _ = std::fs::create_dir_all("/STDCWD");
+    match std::process::Command::new("/EXIT0.ELF")
+        .args(["getcwd", "/STDCWD"])
+        .current_dir("/STDCWD")
+        .status()
+    {
+        Ok(status) if status.code() == Some(0) => println!("process::current_dir OK"),
+        Ok(status) => println!("process::current_dir FAIL: exit_code={:?}", status.code()),
+        Err(e) => println!("process::current_dir error: {}", e),
+    }
+
+    // std::env::set_current_dir() テスト（SYS_CHDIR 経由）
+    // 相対パスの書き込みがカレントディレクトリの下に入ることを確かめて、"/" に戻す。
+    let cwd_ok = std::env::set_current_dir("/STDCWD").is_ok()
+        && std::env::current_dir().ok().as_deref() == Some(std::path::Path::new("/STDCWD"))
+        && std::fs::write("CWD.TXT", b"cwd").is_ok()
+        && std::fs::read("/STDCWD/CWD.TXT").ok().as_deref() == Some(&b"cwd"[..]);
+    let _ = std::env::set_current_dir("/");
+    let _ = std::fs::remove_dir_all("/STDCWD");
+    if cwd_ok {
+        println!("env::set_current_dir OK");
+    } else {
+        println!("env::set_current_dir FAILED");
+    }
+
     // === std::thread テスト ===
 
     // std::thread::spawn() テスト（SYS_THREAD_CREATE + SYS_THREAD_JOIN 経由）
@@ -224,6 +337,116 @@ fn main() {
     std::thread::yield_now();
     println!("thread::yield_now OK");
 
+    // === std::sync テスト ===
+
+    // Mutex テスト（SYS_FUTEX 経由）
+    // 2 スレッドで 100 万回ずつ加算して、取りこぼしがないか確かめる。
+    // ロックを取れない側は FUTEX_WAIT で眠り、unlock の FUTEX_WAKE で起こされる。
+    use std::sync::{Arc, Condvar, Mutex, RwLock};
+    const MUTEX_ITERATIONS: u64 = 1_000_000;
+    let counter = Arc::new(Mutex::new(0u64));
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let counter = Arc::clone(&counter);
+            std::thread::spawn(move || {
+                for _ in 0..MUTEX_ITERATIONS {
+                    *counter.lock().unwrap() += 1;
+                }
+            })
+        })
+        .collect();
+    let joined = workers.into_iter().all(|w| w.join().is_ok());
+    let total = *counter.lock().unwrap();
+    if joined && total == 2 * MUTEX_ITERATIONS {
+        println!("sync::mutex_counter OK: {}", total);
+    } else {
+        println!("sync::mutex_counter FAILED: expected {}, got {}", 2 * MUTEX_ITERATIONS, total);
+    }
+
+    // Condvar テスト: 別スレッドが立てたフラグを wait で待つ
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = Arc::clone(&pair);
+    let notifier = std::thread::spawn(move || {
+        let (ready, cvar) = &*pair2;
+        *ready.lock().unwrap() = true;
+        cvar.notify_one();
+    });
+    {
+        let (ready, cvar) = &*pair;
+        let guard = cvar.wait_while(ready.lock().unwrap(), |ready| !*ready).unwrap();
+        if *guard {
+            println!("sync::condvar_notify OK");
+        } else {
+            println!("sync::condvar_notify FAILED");
+        }
+    }
+    let _ = notifier.join();
+
+    // Condvar のタイムアウト: 誰も notify しなければ timed_out() になる
+    {
+        let (lock, cvar) = (Mutex::new(()), Condvar::new());
+        let (_guard, result) = cvar
+            .wait_timeout(lock.lock().unwrap(), std::time::Duration::from_millis(100))
+            .unwrap();
+        if result.timed_out() {
+            println!("sync::condvar_timeout OK");
+        } else {
+            println!("sync::condvar_timeout FAILED: woke without notify");
+        }
+    }
+
+    // RwLock テスト: 読み取りロックは同時に取れ、書き込みは排他になる
+    let rwlock = RwLock::new(1u32);
+    {
+        let r1 = rwlock.read().unwrap();
+        let r2 = rwlock.read().unwrap();
+        let both = *r1 + *r2;
+        let blocked = rwlock.try_write().is_err();
+        drop((r1, r2));
+        *rwlock.write().unwrap() += both;
+        if blocked && *rwlock.read().unwrap() == 3 {
+            println!("sync::rwlock OK");
+        } else {
+            println!("sync::rwlock FAILED");
+        }
+    }
+
+    // mpsc テスト: 生産者スレッドが 1000 個の整数を送り、消費者スレッドが合計する。
+    // 受信側は待っている間 thread::park で眠り、送信側の unpark で起こされる。
+    use std::sync::mpsc;
+    const MPSC_COUNT: u64 = 1000;
+    let (tx, rx) = mpsc::channel::<u64>();
+    let producer = std::thread::spawn(move || {
+        for i in 1..=MPSC_COUNT {
+            tx.send(i).unwrap();
+        }
+    });
+    let consumer = std::thread::spawn(move || rx.iter().sum::<u64>());
+    let _ = producer.join();
+    match consumer.join() {
+        Ok(sum) if sum == MPSC_COUNT * (MPSC_COUNT + 1) / 2 => println!("sync::mpsc_sum OK: {}", sum),
+        Ok(sum) => println!("sync::mpsc_sum FAILED: expected {}, got {}", MPSC_COUNT * (MPSC_COUNT + 1) / 2, sum),
+        Err(_) => println!("sync::mpsc_sum FAILED: consumer panicked"),
+    }
+
+    // thread_local と thread::current() がスレッドごとに分かれているか
+    std::thread_local!(static LOCAL: std::cell::Cell<u32> = const { std::cell::Cell::new(0) });
+    LOCAL.set(1);
+    let main_id = std::thread::current().id();
+    let other = std::thread::Builder::new()
+        .name("worker".into())
+        .spawn(move || {
+            LOCAL.get() == 0
+                && std::thread::current().id() != main_id
+                && std::thread::current().name() == Some("worker")
+        })
+        .map(|h| h.join());
+    if matches!(other, Ok(Ok(true))) && LOCAL.get() == 1 {
+        println!("thread::local_current OK");
+    } else {
+        println!("thread::local_current FAILED");
+    }
+
     // === serde_json テスト ===
 
     // 外部クレート（serde + serde_json）がビルド・動作するかの検証
@@ -261,6 +484,37 @@ fn main() {
     let addr: std::net::SocketAddr = "10.0.2.2:80".parse().unwrap();
     println!("net::tcp_parse OK: {}", addr);
 
+    // TcpListener::accept が相手の SocketAddr を返すこと（ループバックなので外部ネットワーク不要）。
+    // 相手のポートは、接続した側の TcpStream::local_addr と一致するはず。
+    {
+        use std::net::{TcpListener, TcpStream};
+        const ACCEPT_PORT: u16 = 40110;
+        match TcpListener::bind(("127.0.0.1", ACCEPT_PORT)) {
+            Ok(listener) => {
+                // 接続は accept が返るまで閉じないよう、TcpStream ごと返してもらう
+                let client = std::thread::spawn(|| {
+                    let stream = TcpStream::connect(("127.0.0.1", ACCEPT_PORT))?;
+                    let local = stream.local_addr()?;
+                    Ok::<_, std::io::Error>((stream, local))
+                });
+                let accepted = listener.accept();
+                match (accepted, client.join()) {
+                    (Ok((_server, peer)), Ok(Ok((_client, local))))
+                        if peer.port() == local.port() && peer.ip() == local.ip() =>
+                    {
+                        println!("net::tcp_accept_peer OK: {}", peer);
+                    }
+                    (accepted, joined) => println!(
+                        "net::tcp_accept_peer FAILED: {:?} / {:?}",
+                        accepted.map(|(_, peer)| peer),
+                        joined.map(|r| r.map(|(_, local)| local))
+                    ),
+                }
+            }
+            Err(e) => println!("net::tcp_accept_peer FAILED: bind: {}", e),
+        }
+    }
+
     // DNS 解決テスト（std::net::ToSocketAddrs 経由で lookup_host を呼ぶ）
     use std::net::ToSocketAddrs;
     match ("example.com", 80).to_socket_addrs() {
@@ -283,6 +537,11 @@ fn main() {
                 println!("net::udp_bind OK");
                 // read_timeout を設定（10 秒、QEMU SLIRP の応答遅延に対応）
                 let _ = sock.set_read_timeout(Some(std::time::Duration::from_secs(10)));
+                // タイムアウトはカーネルの SO_RCVTIMEO に保存されるので、読み戻して確かめる
+                match sock.read_timeout() {
+                    Ok(Some(d)) if d == std::time::Duration::from_secs(10) => println!("net::udp_read_timeout OK"),
+                    other => println!("net::udp_read_timeout FAILED: {:?}", other),
+                }
 
                 // DNS クエリを手動構築: example.com の A レコード
                 let mut query = [0u8; 29];
@@ -81,6 +81,7 @@ fn alloc_error(layout: Layout) -> ! {
         asm!(
             "int 0x80",
             in("rax") 60u64, // SYS_EXIT
+            in("rdi") 134u64, // 異常終了を示す終了コード（std の abort と同じ値）
             lateout("rax") _,
             lateout("rcx") _,
             lateout("r11") _,
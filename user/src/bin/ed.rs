@@ -1,6 +1,11 @@
 // ed.rs — SABOS コンソール簡易エディタ（ed 風）
 //
 // 行指向の最小エディタ。フルスクリーンやカーソル移動は行わない。
+//
+// 入力行の編集（Backspace・左右キー・上下キーのコマンド履歴）は
+// カーネルのシェルと共通の sabos-line-editor で行う。
+// y / x はカーネルのクリップボードを使うので、シェルの clip や他のプログラムと
+// 行をやり取りできる。
 
 #![no_std]
 #![no_main]
@@ -18,9 +23,10 @@ mod syscall;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::panic::PanicInfo;
+use sabos_line_editor::{Event, LineEditor, RenderSink};
 
-/// 行バッファの最大サイズ（シェルと同等）
-const LINE_BUFFER_SIZE: usize = 256;
+/// 覚えておくコマンド履歴の件数（シェルと同じ）
+const HISTORY_MAX: usize = 32;
 
 #[unsafe(no_mangle)]
 pub extern "C" fn _start() -> ! {
@@ -30,16 +36,8 @@ pub extern "C" fn _start() -> ! {
 
 fn ed_main() -> ! {
     syscall::write_str("SABOS ed (line editor)\n");
-    syscall::write_str("File (blank for new): ");
-
-    let mut line_buf = [0u8; LINE_BUFFER_SIZE];
-    let name_len = read_line(&mut line_buf);
-    let mut file_name = if name_len == 0 {
-        String::new()
-    } else {
-        let s = core::str::from_utf8(&line_buf[..name_len]).unwrap_or("").trim();
-        String::from(s)
-    };
+    let mut editor = LineEditor::new(HISTORY_MAX);
+    let mut file_name = String::from(read_line(&mut editor, "File (blank for new): ").trim());
 
     let mut lines: Vec<String> = Vec::new();
     if !file_name.is_empty() {
@@ -53,20 +51,8 @@ fn ed_main() -> ! {
     }
 
     loop {
-        syscall::write_str("ed> ");
-        let len = read_line(&mut line_buf);
-        if len == 0 {
-            continue;
-        }
-
-        let line = match core::str::from_utf8(&line_buf[..len]) {
-            Ok(s) => s.trim(),
-            Err(_) => {
-                syscall::write_str("Error: invalid UTF-8\n");
-                continue;
-            }
-        };
-
+        let line = read_line(&mut editor, "ed> ");
+        let line = line.trim();
         if line.is_empty() {
             continue;
         }
@@ -78,18 +64,20 @@ fn ed_main() -> ! {
             "a" => cmd_append(&mut lines),
             "i" => cmd_insert(&mut lines, args),
             "d" => cmd_delete(&mut lines, args),
+            "y" => cmd_yank(&lines, args),
+            "x" => cmd_paste(&mut lines, args),
             "w" => {
-                let _ = cmd_write(&lines, &mut file_name, &mut line_buf);
+                let _ = cmd_write(&lines, &mut file_name);
             }
             "q" => break,
             "wq" => {
-                if cmd_write(&lines, &mut file_name, &mut line_buf) {
+                if cmd_write(&lines, &mut file_name) {
                     break;
                 }
             }
             _ => {
                 syscall::write_str("Error: unknown command\n");
-                syscall::write_str("Commands: p n a i d w q wq\n");
+                syscall::write_str("Commands: p n a i d y x w q wq\n");
             }
         }
     }
@@ -97,31 +85,36 @@ fn ed_main() -> ! {
     syscall::exit();
 }
 
-/// 改行まで1行を読み取る（エコーバックあり）
-fn read_line(buf: &mut [u8]) -> usize {
-    let mut len = 0;
+/// プロンプトを出して、Enter まで 1 行を読み取る（エコーバックあり）。
+/// 行の編集（Backspace・カーソル移動・履歴）は LineEditor に任せる。
+fn read_line(editor: &mut LineEditor, prompt: &str) -> String {
+    let mut sink = ConsoleSink { prompt };
+    sink.prompt();
     loop {
         let c = syscall::read_char();
-        match c {
-            '\n' | '\r' => {
-                syscall::write_str("\n");
-                return len;
-            }
-            '\x08' | '\x7f' => {
-                if len > 0 {
-                    len -= 1;
-                    syscall::write_str("\x08 \x08");
-                }
-            }
-            c if c.is_ascii() && !c.is_ascii_control() => {
-                if len < buf.len() {
-                    buf[len] = c as u8;
-                    len += 1;
-                    syscall::write(&[c as u8]);
-                }
-            }
-            _ => {}
+        // read_char はバイトをそのまま char にするので、ASCII 以外は読み捨てる
+        if !c.is_ascii() {
+            continue;
         }
+        if let Event::Submit(line) = editor.feed(c, &mut sink) {
+            syscall::write_str("\n");
+            return line;
+        }
+    }
+}
+
+/// 行エディタの出力先。コンソールに書き、描き直すときは prompt を出し直す
+struct ConsoleSink<'a> {
+    prompt: &'a str,
+}
+
+impl RenderSink for ConsoleSink<'_> {
+    fn write(&mut self, s: &str) {
+        syscall::write_str(s);
+    }
+
+    fn prompt(&mut self) {
+        syscall::write_str(self.prompt);
     }
 }
 
@@ -193,14 +186,14 @@ fn cmd_print(lines: &[String], with_number: bool) {
 }
 
 fn cmd_append(lines: &mut Vec<String>) {
-    let mut buf = [0u8; LINE_BUFFER_SIZE];
+    // 本文の行はコマンドの履歴に混ぜない
+    let mut editor = LineEditor::new(0);
     loop {
-        let len = read_line(&mut buf);
-        let s = core::str::from_utf8(&buf[..len]).unwrap_or("");
+        let s = read_line(&mut editor, "");
         if s == "." {
             break;
         }
-        lines.push(String::from(s));
+        lines.push(s);
     }
 }
 
@@ -217,15 +210,14 @@ fn cmd_insert(lines: &mut Vec<String>, args: &str) {
         return;
     }
 
-    let mut buf = [0u8; LINE_BUFFER_SIZE];
+    let mut editor = LineEditor::new(0);
     let mut insert_at = num - 1;
     loop {
-        let len = read_line(&mut buf);
-        let s = core::str::from_utf8(&buf[..len]).unwrap_or("");
+        let s = read_line(&mut editor, "");
         if s == "." {
             break;
         }
-        lines.insert(insert_at, String::from(s));
+        lines.insert(insert_at, s);
         insert_at += 1;
     }
 }
@@ -245,11 +237,57 @@ fn cmd_delete(lines: &mut Vec<String>, args: &str) {
     lines.remove(num - 1);
 }
 
-fn cmd_write(lines: &[String], file_name: &mut String, buf: &mut [u8]) -> bool {
+/// y <n>: n 行目をクリップボードにコピーする
+fn cmd_yank(lines: &[String], args: &str) {
+    let num = match parse_u64(args.trim()) {
+        Some(v) => v as usize,
+        None => {
+            syscall::write_str("Usage: y <line_number>\n");
+            return;
+        }
+    };
+    if num == 0 || num > lines.len() {
+        syscall::write_str("Error: line number out of range\n");
+        return;
+    }
+    if syscall::clipboard_set(lines[num - 1].as_bytes()) < 0 {
+        syscall::write_str("Error: failed to copy\n");
+    }
+}
+
+/// x <n>: クリップボードの中身を行に分けて、n 行目の前に挿入する（n = 行数 + 1 で末尾）
+fn cmd_paste(lines: &mut Vec<String>, args: &str) {
+    let num = match parse_u64(args.trim()) {
+        Some(v) => v as usize,
+        None => {
+            syscall::write_str("Usage: x <line_number>\n");
+            return;
+        }
+    };
+    if num == 0 || num > lines.len() + 1 {
+        syscall::write_str("Error: line number out of range\n");
+        return;
+    }
+
+    let mut buf = alloc::vec![0u8; 64 * 1024];
+    let len = syscall::clipboard_get(&mut buf);
+    if len < 0 {
+        syscall::write_str("Error: failed to read clipboard\n");
+        return;
+    }
+    buf.truncate(len as usize);
+    let text = String::from_utf8_lossy(&buf);
+    let mut insert_at = num - 1;
+    for line in split_lines(&text) {
+        lines.insert(insert_at, line);
+        insert_at += 1;
+    }
+}
+
+fn cmd_write(lines: &[String], file_name: &mut String) -> bool {
     if file_name.is_empty() {
-        syscall::write_str("File: ");
-        let len = read_line(buf);
-        let s = core::str::from_utf8(&buf[..len]).unwrap_or("").trim();
+        let s = read_line(&mut LineEditor::new(0), "File: ");
+        let s = s.trim();
         if s.is_empty() {
             syscall::write_str("Error: no file name\n");
             return false;
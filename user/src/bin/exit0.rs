@@ -6,6 +6,26 @@
 // 使い方:
 //   - 引数なし: "exit0: ok\n" を出力して終了（従来と同じ）
 //   - 引数あり: 引数と環境変数の検証を行い、"exit0: args_ok\n" を出力して終了
+//   - "exit <n>": 何も出力せず終了コード n で終了（wait の終了コード取得テスト用）
+//   - "mprotect": 書き込んだページを読み取り専用にしてから書き込み、ページフォルトで
+//     強制終了される（終了コード -1）ことを親が確認する（mprotect テスト用）
+//   - "overflow": 深い再帰でスタックをあふれさせ、ガードページのページフォルトで
+//     強制終了される（終了コード -1）ことを親が確認する（ガードページのテスト用）
+//   - "mmapfile": /HELLO.TXT を mmap_file でマッピングし、中身とファイル末尾より後ろの
+//     ゼロ埋めを確かめる。成功なら終了コード 0（ファイル版 mmap のテスト用）
+//   - "meminfo": 10 ページ mmap して SYS_GET_TASK_INFO の mmap_frames が 10 増え、
+//     munmap で元に戻ることを確かめる。成功なら終了コード 0（プロセスごとの使用量テスト用）
+//   - "wildwrite": マップされていないアドレスに書き込み、ページフォルトで強制終了される
+//     （終了コード -1）。親はカーネルログの診断表示を確認する（例外の診断表示テスト用）
+//   - "getenv <KEY> <VALUE>": 環境変数 KEY を "exit0: KEY=値" と表示し、値が VALUE なら 0、
+//     違えば 1、無ければ 2 で終了する（環境変数の継承テスト用）
+//   - "getcwd <DIR>": カレントディレクトリを "exit0: cwd=値" と表示し、DIR なら 0、
+//     違えば 1 で終了する（作業ディレクトリの指定テスト用）
+//   - "sleep <ms>": ms ミリ秒眠ってから終了コード 0 で終了する（ジョブ制御のテスト用）
+//   - "upper": stdin を EOF まで読み、英字を大文字にして stdout に書く（パイプラインのテスト用）
+//   - "gsclobber": GS にユーザーデータセレクタを読み込んで GS ベースを 0 にしてから、
+//     タイマー割り込みを待ちつつ syscall を呼ぶ。カーネルが落ちずに終了コード 0 で
+//     終われば OK（swapgs のテスト用）
 
 #![no_std]
 #![no_main]
@@ -33,6 +53,39 @@ pub extern "C" fn _start(argc: usize, argv: *const *const u8, envp: *const *cons
     if args::argc() <= 1 {
         // 引数なし: 従来の動作（exec_exit0 テスト互換）
         syscall::write_str("exit0: ok\n");
+    } else if args::argv(1) == Some("exit") {
+        // 終了コード指定モード: 親が wait で受け取る値を検証する
+        let code = args::argv(2).and_then(|s| s.parse::<i32>().ok()).unwrap_or(0);
+        syscall::exit_with_code(code);
+    } else if args::argv(1) == Some("mprotect") {
+        test_mprotect();
+    } else if args::argv(1) == Some("mmapfile") {
+        test_mmap_file();
+    } else if args::argv(1) == Some("meminfo") {
+        test_meminfo();
+    } else if args::argv(1) == Some("sched") {
+        let parent = args::argv(2).and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
+        test_sched(parent);
+    } else if args::argv(1) == Some("wildwrite") {
+        // 0x5000_0000_0000 (80TiB) はどこにもマップされていない
+        unsafe { core::ptr::write_volatile(0x5000_0000_0000 as *mut u8, 0x42) };
+        syscall::exit_with_code(3);
+    } else if args::argv(1) == Some("sleep") {
+        let ms = args::argv(2).and_then(|s| s.parse::<u64>().ok()).unwrap_or(0);
+        syscall::sleep(ms);
+        syscall::exit_with_code(0);
+    } else if args::argv(1) == Some("upper") {
+        upper();
+    } else if args::argv(1) == Some("getenv") {
+        test_getenv();
+    } else if args::argv(1) == Some("getcwd") {
+        test_getcwd();
+    } else if args::argv(1) == Some("gsclobber") {
+        test_gsclobber();
+    } else if args::argv(1) == Some("overflow") {
+        // 戻ってきたらガードページが効いていない
+        recurse(0);
+        syscall::exit_with_code(3);
     } else {
         // 引数あり: 引数・環境変数の受け渡しテスト
         test_args();
@@ -41,6 +94,211 @@ pub extern "C" fn _start(argc: usize, argv: *const *const u8, envp: *const *cons
     syscall::exit();
 }
 
+/// mprotect のテスト。
+///
+/// 書き込み可能なページに書き込んだ後、読み取り専用にして読めることを確認し、
+/// もう一度書き込む。正しく保護されていればここでページフォルトが起き、
+/// カーネルがこのプロセスを終了コード -1 で終わらせるので戻ってこない。
+/// 途中の syscall が失敗したら 2、書き込みが通ってしまったら 3 で終了する。
+fn test_mprotect() -> ! {
+    let prot = syscall::MMAP_PROT_READ | syscall::MMAP_PROT_WRITE;
+    let page = match syscall::mmap(0, 4096, prot, syscall::MMAP_FLAG_ANONYMOUS) {
+        Ok(p) => p,
+        Err(_) => syscall::exit_with_code(2),
+    };
+    unsafe { core::ptr::write_volatile(page, 0x5A) };
+
+    if syscall::mprotect(page, 4096, syscall::MMAP_PROT_READ).is_err() {
+        syscall::exit_with_code(2);
+    }
+    if unsafe { core::ptr::read_volatile(page) } != 0x5A {
+        syscall::exit_with_code(2);
+    }
+
+    // 読み取り専用のページへの書き込み → ページフォルトで強制終了されるはず
+    unsafe { core::ptr::write_volatile(page, 0xA5) };
+    syscall::exit_with_code(3);
+}
+
+/// ファイル版 mmap のテスト。
+///
+/// /HELLO.TXT の先頭 1 ページをマッピングし、read で読んだ中身と一致すること、
+/// ファイル末尾より後ろがゼロで埋まっていることを確認してから munmap する。
+/// syscall が失敗したら 2、中身が違えば 3 で終了する。
+fn test_mmap_file() -> ! {
+    let handle = match syscall::open("/HELLO.TXT", syscall::HANDLE_RIGHT_READ) {
+        Ok(h) => h,
+        Err(_) => syscall::exit_with_code(2),
+    };
+    let mut buf = [0u8; 4096];
+    let n = syscall::handle_read(&handle, &mut buf);
+    if n <= 0 {
+        syscall::exit_with_code(2);
+    }
+    let n = n as usize;
+
+    let page = match syscall::mmap_file(&handle, 0, 4096, syscall::MMAP_PROT_READ, 0, false) {
+        Ok(p) => p,
+        Err(_) => syscall::exit_with_code(2),
+    };
+    let _ = syscall::handle_close(&handle);
+    let mapped = unsafe { core::slice::from_raw_parts(page as *const u8, 4096) };
+    let ok = mapped[..n] == buf[..n] && mapped[n..].iter().all(|&b| b == 0);
+
+    if syscall::munmap(page, 4096).is_err() {
+        syscall::exit_with_code(2);
+    }
+    syscall::exit_with_code(if ok { 0 } else { 3 });
+}
+
+/// プロセスごとのメモリ使用量のテスト。
+///
+/// 10 ページを mmap する前後で SYS_GET_TASK_INFO の mmap_frames を比べ、
+/// ちょうど 10 増えていること、munmap すると元の値に戻ることを確認する。
+/// syscall が失敗したら 2、mmap 後の値が違えば 3、munmap 後の値が違えば 4 で終了する。
+fn test_meminfo() -> ! {
+    const PAGES: usize = 10;
+    let before = match syscall::get_task_info(0) {
+        Ok(info) => info,
+        Err(_) => syscall::exit_with_code(2),
+    };
+    let prot = syscall::MMAP_PROT_READ | syscall::MMAP_PROT_WRITE;
+    let addr = match syscall::mmap(0, PAGES * 4096, prot, syscall::MMAP_FLAG_ANONYMOUS) {
+        Ok(p) => p,
+        Err(_) => syscall::exit_with_code(2),
+    };
+    let mapped = match syscall::get_task_info(0) {
+        Ok(info) => info,
+        Err(_) => syscall::exit_with_code(2),
+    };
+    if mapped.mmap_frames != before.mmap_frames + PAGES as u64
+        || mapped.user_frames != before.user_frames + PAGES as u64
+    {
+        syscall::exit_with_code(3);
+    }
+
+    if syscall::munmap(addr, PAGES * 4096).is_err() {
+        syscall::exit_with_code(2);
+    }
+    match syscall::get_task_info(0) {
+        Ok(info) if info.mmap_frames == before.mmap_frames => syscall::exit_with_code(0),
+        Ok(_) => syscall::exit_with_code(4),
+        Err(_) => syscall::exit_with_code(2),
+    }
+}
+
+/// GS を書き換えてもカーネルが動き続けることのテスト。
+///
+/// `mov gs, 0x1b` で GS ベースは 0 になる。カーネルが Ring 3 の GS ベースをそのまま
+/// 使っていると、次の割り込みか syscall で gs:[0] を読んで落ちる。
+/// しばらく Ring 3 で回ってタイマー割り込みを受け、sleep で別タスクへの切り替えも挟んでから
+/// getpid を呼ぶ。GS セレクタが書き換えたままなら 0、変わっていたら 3 で終了する。
+fn test_gsclobber() -> ! {
+    unsafe { core::arch::asm!("mov gs, {0:x}", in(reg) 0x1bu16, options(nostack, preserves_flags)) };
+    for i in 0..20_000_000u64 {
+        core::hint::black_box(i);
+    }
+    syscall::sleep(10);
+    let pid = syscall::getpid();
+    let gs: u16;
+    unsafe { core::arch::asm!("mov {0:x}, gs", out(reg) gs, options(nomem, nostack, preserves_flags)) };
+    syscall::exit_with_code(if pid != 0 && gs == 0x1b { 0 } else { 3 });
+}
+
+/// スケジューラの統計と優先度のテスト。
+///
+/// SYS_SCHED_STATS が読めて値のつじつまが合うことを確認してから、優先度の制限を試す:
+/// 自分の優先度は下げられるが上げられない（下げた後に元へ戻すのも不可）、範囲外は不正な引数、
+/// 親（子でも同じプロセスでもない）の優先度は変えられない。
+/// 成功なら 0、統計が読めない / 合わないなら 2、優先度の結果が違えば 3 で終了する。
+fn test_sched(parent: u64) -> ! {
+    const INVALID_ARGUMENT: i64 = -10;
+    const PERMISSION_DENIED: i64 = -30;
+
+    match syscall::sched_stats() {
+        Ok(s) if s.timer_ticks > 0 && s.context_switches == s.preempt_switches + s.yield_switches => {}
+        _ => syscall::exit_with_code(2),
+    }
+
+    let default = syscall::PRIORITY_DEFAULT;
+    let ok = syscall::getpriority(0) == default as i64
+        && syscall::setpriority(0, default + 1) == PERMISSION_DENIED
+        && syscall::setpriority(0, syscall::PRIORITY_MAX + 1) == INVALID_ARGUMENT
+        && syscall::setpriority(0, default - 1) == 0
+        && syscall::getpriority(0) == (default - 1) as i64
+        && syscall::setpriority(0, default) == PERMISSION_DENIED
+        && syscall::setpriority(parent, syscall::PRIORITY_MIN) == PERMISSION_DENIED;
+    syscall::exit_with_code(if ok { 0 } else { 3 });
+}
+
+/// stdin を EOF まで読み、英字を大文字にして stdout に書く。
+///
+/// シェルのパイプラインの途中の段として使う（stdin / stdout はパイプにつながっている）。
+fn upper() {
+    let mut buf = [0u8; 256];
+    loop {
+        let n = syscall::read(&mut buf);
+        if n <= 0 {
+            break;
+        }
+        let chunk = &mut buf[..n as usize];
+        chunk.make_ascii_uppercase();
+        syscall::write(chunk);
+    }
+}
+
+/// 環境変数の継承のテスト。
+///
+/// argv[2] の環境変数を表示し、値が argv[3] と一致すれば 0、違えば 1、無ければ 2 で終了する。
+/// 親は終了コードで「自分が spawn 前に設定した値が子に見えたか」を確かめる。
+fn test_getenv() -> ! {
+    let key = args::argv(2).unwrap_or("");
+    let expected = args::argv(3).unwrap_or("");
+    syscall::write_str("exit0: ");
+    syscall::write_str(key);
+    match args::getenv(key) {
+        Some(value) => {
+            syscall::write_str("=");
+            syscall::write_str(value);
+            syscall::write_str("\n");
+            syscall::exit_with_code(if value == expected { 0 } else { 1 });
+        }
+        None => {
+            syscall::write_str(" is not set\n");
+            syscall::exit_with_code(2);
+        }
+    }
+}
+
+/// カレントディレクトリの確認。
+///
+/// カレントディレクトリを表示し、argv[2] と一致すれば 0、違えば（取れなければ）1 で終了する。
+fn test_getcwd() -> ! {
+    let expected = args::argv(2).unwrap_or("");
+    let mut buf = [0u8; 256];
+    let cwd = match syscall::getcwd(&mut buf) {
+        Ok(n) => core::str::from_utf8(&buf[..n]).unwrap_or(""),
+        Err(_) => syscall::exit_with_code(1),
+    };
+    syscall::write_str("exit0: cwd=");
+    syscall::write_str(cwd);
+    syscall::write_str("\n");
+    syscall::exit_with_code(if cwd == expected { 0 } else { 1 });
+}
+
+/// スタックを使い切るまで再帰する。
+///
+/// 1 段ごとに 512 バイトの配列をスタックに置き、black_box で最適化による
+/// 削除や末尾呼び出し化を防ぐ。64KiB のスタックなら 100 段ほどでガードページに届く。
+fn recurse(depth: u64) -> u64 {
+    let buf = [depth as u8; 512];
+    core::hint::black_box(&buf);
+    if depth == u64::MAX {
+        return 0;
+    }
+    recurse(depth + 1) + buf[0] as u64
+}
+
 /// 引数と環境変数の受け渡しテスト。
 ///
 /// テスト条件:
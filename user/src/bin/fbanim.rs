@@ -0,0 +1,103 @@
+// fbanim.rs — ダブルバッファ描画の動作確認用アニメーション
+//
+// 画面内を跳ね回る矩形を描く。1 フレームごとに
+//   1. 前の位置を背景色で消す
+//   2. 新しい位置に矩形を描く
+//   3. SYS_FB_PRESENT で画面に一括転送する
+// という手順で描画する。直接モードだと 1 と 2 の間の「矩形が消えた瞬間」が
+// 画面に出てちらつくが、ダブルバッファモードなら完成したフレームだけが見える。
+//
+// 使い方:
+//   FBANIM.ELF          — ダブルバッファで描画（何かキーを押すと終了）
+//   FBANIM.ELF direct   — 比較用に直接モードで描画
+
+#![no_std]
+#![no_main]
+
+#[path = "../args.rs"]
+mod args;
+#[path = "../syscall.rs"]
+mod syscall;
+
+use core::panic::PanicInfo;
+
+/// 矩形のサイズ（ピクセル）
+const BOX_SIZE: u32 = 48;
+/// 1 フレームあたりの移動量（ピクセル）
+const SPEED: i32 = 6;
+/// フレーム間隔（ミリ秒）
+const FRAME_MS: u64 = 16;
+/// キー入力がなくても終了するまでのフレーム数
+const MAX_FRAMES: u32 = 1000;
+
+const BG: (u8, u8, u8) = (0, 0, 128);
+const BOX: (u8, u8, u8) = (255, 200, 60);
+
+#[unsafe(no_mangle)]
+extern "C" fn _start(argc: usize, argv: *const *const u8, envp: *const *const u8) -> ! {
+    unsafe { args::init(argc, argv, envp); }
+    let double_buffer = args::argv(1) != Some("direct");
+
+    let mut info = syscall::FramebufferInfo {
+        width: 0,
+        height: 0,
+        stride: 0,
+        pixel_format: 0,
+        bytes_per_pixel: 0,
+    };
+    if syscall::get_fb_info(&mut info) < 0 || info.width <= BOX_SIZE || info.height <= BOX_SIZE {
+        syscall::write_str("fbanim: framebuffer not available\n");
+        syscall::exit_with_code(1);
+    }
+
+    if double_buffer && syscall::fb_set_double_buffer(true) < 0 {
+        syscall::write_str("fbanim: double buffer is in use by another task\n");
+        syscall::exit_with_code(1);
+    }
+    // キー入力で終了できるようにフォーカスを取る（シェルに文字が渡らないように）
+    syscall::console_grab(true);
+
+    let max_x = (info.width - BOX_SIZE) as i32;
+    let max_y = (info.height - BOX_SIZE) as i32;
+    let (mut x, mut y) = (0i32, 0i32);
+    let (mut dx, mut dy) = (SPEED, SPEED);
+
+    syscall::draw_rect(0, 0, info.width, info.height, BG.0, BG.1, BG.2);
+    let mut key = [0u8; 8];
+    for _ in 0..MAX_FRAMES {
+        if syscall::key_read(&mut key) > 0 {
+            break;
+        }
+
+        // 前のフレームの矩形を消してから次の位置に描く
+        syscall::draw_rect(x as u32, y as u32, BOX_SIZE, BOX_SIZE, BG.0, BG.1, BG.2);
+        x += dx;
+        y += dy;
+        if x <= 0 || x >= max_x {
+            dx = -dx;
+            x = x.clamp(0, max_x);
+        }
+        if y <= 0 || y >= max_y {
+            dy = -dy;
+            y = y.clamp(0, max_y);
+        }
+        syscall::draw_rect(x as u32, y as u32, BOX_SIZE, BOX_SIZE, BOX.0, BOX.1, BOX.2);
+
+        if double_buffer {
+            syscall::fb_present();
+        }
+        syscall::sleep(FRAME_MS);
+    }
+
+    syscall::console_grab(false);
+    if double_buffer {
+        syscall::fb_set_double_buffer(false);
+    }
+    syscall::clear_screen();
+    syscall::exit();
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    syscall::exit();
+}
@@ -653,7 +653,9 @@ fn gui_loop() -> ! {
             dx: 0,
             dy: 0,
             buttons: 0,
-            _pad: [0; 3],
+            scroll: 0,
+            device_id: 0,
+            _pad: [0; 1],
         };
         if syscall::mouse_read(&mut mouse_state) > 0 {
             wm.update_mouse(&mut state, &mut cursor, &mouse_state, &taskbar);
@@ -1123,7 +1125,9 @@ impl WindowManager {
                 dx: 0,
                 dy: 0,
                 buttons: 0,
-                _pad: [0; 3],
+                scroll: 0,
+                device_id: 0,
+                _pad: [0; 1],
             },
             mouse_seq: 0,
         }
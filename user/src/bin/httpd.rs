@@ -123,7 +123,7 @@ fn handle_connection(stream: net::TcpStream) {
     }
 
     // まずファイルとして開いてみる
-    let data = match read_file(path) {
+    let (handle, size) = match open_file(path) {
         Ok(v) => v,
         Err(_) => {
             // ファイルが見つからなければディレクトリとして試す
@@ -148,12 +148,21 @@ fn handle_connection(stream: net::TcpStream) {
     header.push_str(content_type);
     header.push_str("\r\n");
     header.push_str("Content-Length: ");
-    header.push_str(&itoa(data.len() as u64));
+    header.push_str(&itoa(size));
     header.push_str("\r\n");
     header.push_str("Connection: close\r\n\r\n");
 
     let _ = stream.write_all(header.as_bytes());
-    let _ = stream.write_all(&data);
+    // 本文はユーザー空間に読み出さず、SYS_SENDFILE でカーネルから直接送る。
+    // 途中までしか送れなかったら残りを送り直し、1 バイトも進まなければ諦める
+    let mut sent = 0u64;
+    while sent < size {
+        match stream.send_file(&handle, sent, size - sent) {
+            Ok(n) if n > 0 => sent += n,
+            _ => break,
+        }
+    }
+    let _ = syscall::handle_close(&handle);
     // stream は Drop で自動クローズ
 }
 
@@ -177,29 +186,24 @@ fn read_http_request(stream: &net::TcpStream) -> Result<Vec<u8>, ()> {
     Ok(buf)
 }
 
-fn read_file(path: &str) -> Result<Vec<u8>, ()> {
+/// ファイルを開き、ハンドルとサイズを返す（ディレクトリなら Err）
+fn open_file(path: &str) -> Result<(syscall::Handle, u64), ()> {
     let handle = syscall::open(path, syscall::HANDLE_RIGHTS_FILE_READ).map_err(|_| ())?;
-    let mut out = Vec::new();
-    let mut buf = [0u8; FILE_BUFFER_SIZE];
-    loop {
-        let n = syscall::handle_read(&handle, &mut buf);
-        if n < 0 {
+    match syscall::handle_stat(&handle) {
+        // kind 0 = File
+        Ok(stat) if stat.kind == 0 => Ok((handle, stat.size)),
+        _ => {
             let _ = syscall::handle_close(&handle);
-            return Err(());
+            Err(())
         }
-        if n == 0 {
-            break;
-        }
-        out.extend_from_slice(&buf[..n as usize]);
     }
-    let _ = syscall::handle_close(&handle);
-    Ok(out)
 }
 
 /// ディレクトリの内容を HTML で返す
 ///
-/// handle_enum で取得したエントリ名（改行区切り）を HTML のリンク一覧に変換する。
-/// 各エントリはクリックでアクセスできるリンクになる。
+/// handle_enum で取得したエントリ名（改行区切り）を sabos_textutil::dir_listing_html で
+/// リンク一覧に変換する（カーネルの vfs::list_dir_html と同じ実装）。
+/// エントリ名はエスケープされるので、名前に HTML が含まれていてもページは壊れない。
 fn list_directory(dir_path: &str, display_path: &str) -> Result<String, ()> {
     let handle = syscall::open(dir_path, syscall::HANDLE_RIGHTS_DIRECTORY_READ).map_err(|_| ())?;
     let mut buf = [0u8; FILE_BUFFER_SIZE];
@@ -215,65 +219,7 @@ fn list_directory(dir_path: &str, display_path: &str) -> Result<String, ()> {
         ""
     };
 
-    // HTML を構築
-    let mut html = String::new();
-    html.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\n");
-    html.push_str("<title>Index of ");
-    html.push_str(display_path);
-    html.push_str("</title>\n");
-    // 簡単なスタイル
-    html.push_str("<style>body{font-family:monospace;margin:2em}a{text-decoration:none}a:hover{text-decoration:underline}li{margin:0.3em 0}</style>\n");
-    html.push_str("</head><body>\n");
-    html.push_str("<h1>Index of ");
-    html.push_str(display_path);
-    html.push_str("</h1>\n<hr>\n<ul>\n");
-
-    // 親ディレクトリへのリンク（ルート以外）
-    if dir_path != "/" {
-        html.push_str("<li><a href=\"");
-        // 親パスを計算
-        let parent = parent_path(display_path);
-        html.push_str(&parent);
-        html.push_str("\">..</a></li>\n");
-    }
-
-    // エントリをリンクとして追加
-    for entry in entries_text.split('\n') {
-        let name = entry.trim();
-        if name.is_empty() {
-            continue;
-        }
-        html.push_str("<li><a href=\"");
-        // リンク先のパスを構築
-        if display_path.ends_with('/') {
-            html.push_str(display_path);
-        } else {
-            html.push_str(display_path);
-            html.push('/');
-        }
-        html.push_str(name);
-        html.push_str("\">");
-        html.push_str(name);
-        html.push_str("</a></li>\n");
-    }
-
-    html.push_str("</ul>\n<hr>\n<p><em>SABOS httpd</em></p>\n</body></html>\n");
-    Ok(html)
-}
-
-/// 親パスを返す（"/" なら "/" のまま）
-fn parent_path(path: &str) -> String {
-    // 末尾の "/" を除去してから最後の "/" を探す
-    let trimmed = if path.ends_with('/') && path.len() > 1 {
-        &path[..path.len() - 1]
-    } else {
-        path
-    };
-    match trimmed.rfind('/') {
-        Some(0) => String::from("/"),
-        Some(pos) => String::from(&trimmed[..pos + 1]),
-        None => String::from("/"),
-    }
+    Ok(sabos_textutil::dir_listing_html(display_path, entries_text))
 }
 
 /// Content-Type を推測する
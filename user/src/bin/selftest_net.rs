@@ -10,6 +10,8 @@
 
 extern crate alloc;
 
+use alloc::vec::Vec;
+
 #[path = "../allocator.rs"]
 mod allocator;
 #[path = "../net.rs"]
@@ -157,10 +159,172 @@ fn run_selftest_net() {
         }
     }
 
+    // テスト 5: nc の双方向中継ロジック（モック端点、ネットワーク不要）
+    total += 1;
+    if test_relay_step() {
+        syscall::write_str("[PASS] net_relay_step\n");
+        passed += 1;
+    } else {
+        syscall::write_str("[FAIL] net_relay_step\n");
+    }
+
+    // テスト 6: 統一ソケット API で TCP 接続（example.com:80）
+    //
+    // SYS_SOCKET / SYS_CONNECT / SYS_SEND で HTTP GET を送り、
+    // 応答はソケットハンドルに対する汎用の SYS_HANDLE_READ で読む。
+    total += 1;
+    if test_socket_tcp_connect() {
+        syscall::write_str("[PASS] net_socket_tcp_connect\n");
+        passed += 1;
+    } else {
+        syscall::write_str("[FAIL] net_socket_tcp_connect\n");
+    }
+
+    // テスト 7: 統一ソケット API で UDP bind / sendto / recvfrom（DNS クエリ）
+    total += 1;
+    if test_socket_udp_dns() {
+        syscall::write_str("[PASS] net_socket_udp_dns\n");
+        passed += 1;
+    } else {
+        syscall::write_str("[FAIL] net_socket_udp_dns\n");
+    }
+
     // 結果出力
     write_summary(passed, total);
 }
 
+/// 統一ソケット API で TCP 接続し、HTTP 応答の先頭を読む
+fn test_socket_tcp_connect() -> bool {
+    let Ok(ip) = net::dns_lookup("example.com") else {
+        return false;
+    };
+    let Ok(sock) = syscall::socket(syscall::AF_INET, syscall::SOCK_STREAM, 0) else {
+        return false;
+    };
+
+    let ok = (|| {
+        if syscall::connect(&sock, &syscall::sockaddr_v4(ip.octets, 80)) < 0 {
+            return false;
+        }
+        let req = b"GET / HTTP/1.0\r\nHost: example.com\r\nConnection: close\r\n\r\n";
+        if syscall::send(&sock, req, None) != req.len() as i64 {
+            return false;
+        }
+        // ソケットもハンドルなので汎用の handle_read で読める（データが来るまでブロック）
+        let mut buf = [0u8; 5];
+        let n = syscall::handle_read(&sock, &mut buf);
+        n == 5 && &buf == b"HTTP/"
+    })();
+
+    syscall::handle_close(&sock);
+    ok
+}
+
+/// 統一ソケット API で UDP ソケットをエフェメラルポートに bind し、
+/// DNS サーバー (10.0.2.3:53) とクエリをやりとりする
+fn test_socket_udp_dns() -> bool {
+    let Ok(sock) = syscall::socket(syscall::AF_INET, syscall::SOCK_DGRAM, syscall::IPPROTO_UDP) else {
+        return false;
+    };
+
+    let ok = (|| {
+        let mut local = syscall::sockaddr_v4([0; 4], 0);
+        if syscall::bind(&sock, &mut local) < 0 || local.port == 0 {
+            return false;
+        }
+
+        // example.com の A レコードの問い合わせ（ID = 0x5A0C）
+        let mut query = [0u8; 29];
+        query[0] = 0x5A; query[1] = 0x0C; // ID
+        query[2] = 0x01; // Flags: RD=1
+        query[5] = 0x01; // QDCOUNT=1
+        query[12] = 7;
+        query[13..20].copy_from_slice(b"example");
+        query[20] = 3;
+        query[21..24].copy_from_slice(b"com");
+        query[26] = 0x01; // QTYPE=A
+        query[28] = 0x01; // QCLASS=IN
+
+        let dns = syscall::sockaddr_v4([10, 0, 2, 3], 53);
+        if syscall::send(&sock, &query, Some(&dns)) != query.len() as i64 {
+            return false;
+        }
+
+        let mut buf = [0u8; 512];
+        let mut from = syscall::SockAddr::default();
+        let n = syscall::recv_from(&sock, &mut buf, 5000, &mut from);
+        n >= 2 && from == dns && buf[0] == 0x5A && buf[1] == 0x0C
+    })();
+
+    syscall::handle_close(&sock);
+    ok
+}
+
+/// 中継テスト用のモック端点
+///
+/// chunks を 1 回の poll_read につき 1 つずつ返し、尽きたら eof なら Closed、
+/// そうでなければ Idle を返す。write_all で書かれたデータは out に溜める。
+struct MockEndpoint {
+    chunks: &'static [&'static [u8]],
+    next: usize,
+    eof: bool,
+    out: Vec<u8>,
+}
+
+impl MockEndpoint {
+    fn new(chunks: &'static [&'static [u8]], eof: bool) -> Self {
+        Self { chunks, next: 0, eof, out: Vec::new() }
+    }
+}
+
+impl net::RelayEndpoint for MockEndpoint {
+    fn poll_read(&mut self, buf: &mut [u8]) -> net::RelayRead {
+        if let Some(chunk) = self.chunks.get(self.next) {
+            self.next += 1;
+            buf[..chunk.len()].copy_from_slice(chunk);
+            net::RelayRead::Data(chunk.len())
+        } else if self.eof {
+            net::RelayRead::Closed
+        } else {
+            net::RelayRead::Idle
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> bool {
+        self.out.extend_from_slice(data);
+        true
+    }
+}
+
+/// relay_step が両方向にデータを流し、片側の EOF で Closed を返すことを確認する。
+///
+/// a は "hel" "lo" を送ってから EOF、b は "world" を送ったあと黙る。
+/// 1 回目で両方向、2 回目で a → b だけ、3 回目で a の EOF を検出するはず。
+fn test_relay_step() -> bool {
+    let mut buf = [0u8; 64];
+
+    // どちらも黙っていれば Idle
+    let mut quiet_a = MockEndpoint::new(&[], false);
+    let mut quiet_b = MockEndpoint::new(&[], false);
+    if net::relay_step(&mut quiet_a, &mut quiet_b, &mut buf) != net::RelayStatus::Idle {
+        return false;
+    }
+
+    let mut a = MockEndpoint::new(&[b"hel", b"lo"], true);
+    let mut b = MockEndpoint::new(&[b"world"], false);
+    let expected = [
+        net::RelayStatus::Progress,
+        net::RelayStatus::Progress,
+        net::RelayStatus::Closed,
+    ];
+    for want in expected {
+        if net::relay_step(&mut a, &mut b, &mut buf) != want {
+            return false;
+        }
+    }
+    b.out == b"hello" && a.out == b"world"
+}
+
 /// テスト結果のサマリーを出力する
 fn write_summary(passed: u32, total: u32) {
     syscall::write_str("=== NET SELFTEST END: ");
@@ -225,7 +225,7 @@ fn execute_command(line: &[u8], state: &mut ShellState) {
         "ps" => cmd_ps(),
         "top" => cmd_top(),
         "ip" => cmd_ip(),
-        "lspci" => cmd_lspci(),
+        "lspci" => cmd_lspci(args),
         "run" => cmd_run(args, state),
         "spawn" => cmd_spawn(args, state),
         "kill" => cmd_kill(args),
@@ -661,10 +661,10 @@ fn cmd_help() {
     syscall::write_str("  ps                - Show task list\n");
     syscall::write_str("  top               - System monitor (real-time ps + mem)\n");
     syscall::write_str("  ip                - Show network information\n");
-    syscall::write_str("  lspci             - List PCI devices\n");
+    syscall::write_str("  lspci [-v]        - List PCI devices (-v: show BARs)\n");
     syscall::write_str("  run <file>        - Run ELF program (foreground)\n");
     syscall::write_str("  spawn <file>      - Run ELF program (background)\n");
-    syscall::write_str("  kill <task_id>    - Kill a task by ID\n");
+    syscall::write_str("  kill [-TERM] <id> - Kill a task (-TERM: request graceful exit)\n");
     syscall::write_str("  sleep <ms>        - Sleep for milliseconds\n");
     syscall::write_str("  dns <domain>      - DNS lookup\n");
     syscall::write_str("  ping6 <ipv6_addr> - IPv6 ping (ICMPv6 Echo)\n");
@@ -2078,7 +2078,17 @@ fn cmd_ip() {
 ///
 /// PCI Configuration Space を読み取って、バス 0 のデバイスを列挙する。
 /// 直接 I/O ポートを叩かず、システムコール経由で読み取る。
-fn cmd_lspci() {
+/// `-v` を付けると SYS_PCI_READ_BAR で各デバイスの BAR も表示する。
+fn cmd_lspci(args: &str) {
+    let verbose = match args.trim() {
+        "" => false,
+        "-v" => true,
+        _ => {
+            syscall::write_str("Usage: lspci [-v]\n");
+            return;
+        }
+    };
+
     syscall::write_str("PCI devices on bus 0:\n");
     syscall::write_str("  BDF       Vendor Device Class\n");
     syscall::write_str("  --------- ------ ------ --------\n");
@@ -2158,6 +2168,10 @@ fn cmd_lspci() {
             write_hex_u8(prog_if);
             syscall::write_str("\n");
 
+            if verbose {
+                write_pci_bars(device, function);
+            }
+
             count += 1;
         }
     }
@@ -2167,6 +2181,33 @@ fn cmd_lspci() {
     syscall::write_str(" devices\n");
 }
 
+/// lspci -v 用: デバイスの有効な BAR を 1 行ずつ表示する
+///
+/// 未実装の BAR や 64 ビット BAR の上位半分はエラーが返るので読み飛ばす。
+fn write_pci_bars(device: u8, function: u8) {
+    for index in 0..6u8 {
+        let mut info = syscall::PciBarInfo::default();
+        if syscall::pci_read_bar(0, device, function, index, &mut info) < 0 {
+            continue;
+        }
+        let kind = match info.kind {
+            syscall::PCI_BAR_KIND_MEM32 => "mem32",
+            syscall::PCI_BAR_KIND_MEM64 => "mem64",
+            _ => "io",
+        };
+        let prefetch = if info.flags & syscall::PCI_BAR_FLAG_PREFETCHABLE != 0 {
+            " prefetchable"
+        } else {
+            ""
+        };
+        let line = format!(
+            "      BAR{}: {:<5} base={:#x} size={:#x}{}\n",
+            index, kind, info.base, info.size, prefetch,
+        );
+        syscall::write_str(&line);
+    }
+}
+
 // =================================================================
 // プロセス実行コマンド
 // =================================================================
@@ -2264,14 +2305,29 @@ fn cmd_spawn(args: &str, state: &ShellState) {
 /// kill コマンド: タスクを強制終了
 ///
 /// 使い方:
-///   kill <task_id>
+///   kill [-TERM|-KILL] <task_id>
 ///
 /// ps コマンドでタスク ID を確認してから使う。
+/// シグナル省略時は SIG_KILL（即座に強制終了）。
+/// -TERM は終了要求フラグを立てるだけで、対象が後始末してから終了する。
 /// 自分自身（シェル）の kill はカーネル側で拒否される。
 fn cmd_kill(args: &str) {
-    let id_str = args.trim();
+    let mut parts = args.split_whitespace();
+    let mut signal = syscall::SIG_KILL;
+    let mut id_str = parts.next().unwrap_or("");
+    if let Some(sig_str) = id_str.strip_prefix('-') {
+        signal = match sig_str {
+            "TERM" | "15" => syscall::SIG_TERM,
+            "KILL" | "9" => syscall::SIG_KILL,
+            _ => {
+                syscall::write_str("Error: unknown signal (use -TERM or -KILL)\n");
+                return;
+            }
+        };
+        id_str = parts.next().unwrap_or("");
+    }
     if id_str.is_empty() {
-        syscall::write_str("Usage: kill <task_id>\n");
+        syscall::write_str("Usage: kill [-TERM|-KILL] <task_id>\n");
         syscall::write_str("  Use 'ps' to see task IDs.\n");
         return;
     }
@@ -2284,11 +2340,15 @@ fn cmd_kill(args: &str) {
         }
     };
 
-    let result = syscall::kill(task_id);
+    let result = syscall::kill_signal(task_id, signal);
     if result == 0 {
         syscall::write_str("Task ");
         write_number(task_id);
-        syscall::write_str(" killed.\n");
+        if signal == syscall::SIG_TERM {
+            syscall::write_str(" sent SIGTERM.\n");
+        } else {
+            syscall::write_str(" killed.\n");
+        }
     } else {
         syscall::write_str("Error: failed to kill task ");
         write_number(task_id);
@@ -2751,8 +2811,8 @@ fn cmd_http(args: &str) {
 /// - クライアントモード: `nc <host> <port>` — 指定ホスト:ポートに接続
 /// - サーバーモード: `nc -l <port>` — 指定ポートで待ち受け
 ///
-/// キーボード入力をそのまま送信し、受信データをそのまま表示する。
-/// 相手が切断（recv が 0 またはエラー）すると終了する。
+/// キーボード入力を 1 行ずつ送信し、受信データをそのまま表示する。
+/// 相手が切断（recv が EOF またはエラー）するか、Esc / Ctrl-D で終了する。
 fn cmd_nc(args: &str) {
     let args = args.trim();
     if args.is_empty() {
@@ -2832,7 +2892,7 @@ fn nc_client(host: &str, port: u16) {
             return;
         }
     };
-    syscall::write_str("Connected! (peer will close to exit)\n");
+    syscall::write_str("Connected! (Esc to quit)\n");
 
     // キーボード入力↔ソケットの双方向中継
     nc_relay_loop(stream.conn_id());
@@ -2865,7 +2925,7 @@ fn nc_server(port: u16) {
             return;
         }
     };
-    syscall::write_str("Client connected! (peer will close to exit)\n");
+    syscall::write_str("Client connected! (Esc to quit)\n");
 
     // キーボード入力↔ソケットの双方向中継
     nc_relay_loop(stream.conn_id());
@@ -2876,78 +2936,102 @@ fn nc_server(port: u16) {
 
 /// nc のメインループ: キーボード入力を送信し、受信データを表示する
 ///
-/// ノンブロッキング key_read でキーボードをポーリングし、
-/// 短いタイムアウトの TCP recv で受信データを確認する。
-/// 相手が切断したら（recv エラー）ループを抜ける。
+/// コンソール側もソケット側もノンブロッキング（ソケットは 50ms の短いタイムアウト）で
+/// 交互にポーリングし、net::relay_step で両方向に流す。
+/// 相手が切断する（recv が EOF/エラー）か、Esc / Ctrl-D で入力を終えるとループを抜ける。
 fn nc_relay_loop(conn_id: u32) {
     // キーボードフォーカスを取得（GUI 環境で nc がキーを読めるように）
     syscall::console_grab(true);
 
-    let mut key_buf = [0u8; 64];
-    let mut recv_buf = [0u8; 1024];
-    // 1行分のキーボード入力バッファ（Enter で送信するモード）
-    let mut line_buf = [0u8; 256];
-    let mut line_len: usize = 0;
+    let mut console = NcConsole::new();
+    let mut socket = net::TcpRelay::new(conn_id, 50);
+    let mut buf = [0u8; 1024];
 
-    loop {
-        // --- キーボード入力をノンブロッキングで読む ---
-        let key_n = syscall::key_read(&mut key_buf);
-        if key_n > 0 {
-            for i in 0..(key_n as usize) {
-                let c = key_buf[i];
-                match c {
-                    // Enter: 行バッファの内容 + 改行を送信
-                    b'\n' | b'\r' => {
-                        if line_len > 0 {
-                            let _ = net::raw_send(conn_id, &line_buf[..line_len]);
-                            line_len = 0;
-                        }
-                        let _ = net::raw_send(conn_id, b"\r\n");
-                        syscall::write_str("\n");
-                    }
-                    // Backspace
-                    0x08 | 0x7f => {
-                        if line_len > 0 {
-                            line_len -= 1;
-                            syscall::write_str("\x08 \x08");
-                        }
-                    }
-                    // 通常の印字可能文字
-                    c if c >= 0x20 && c < 0x7f => {
-                        if line_len < line_buf.len() {
-                            line_buf[line_len] = c;
-                            line_len += 1;
-                            syscall::write(&[c]);
-                        }
+    // Idle のときの待ちはソケット側の recv タイムアウトが兼ねるので、ここでは sleep しない
+    while net::relay_step(&mut console, &mut socket, &mut buf) != net::RelayStatus::Closed {}
+
+    // キーボードフォーカスを解放
+    syscall::console_grab(false);
+}
+
+/// nc のコンソール側の中継端点
+///
+/// キー入力を 1 行ずつ溜めてエコーし、Enter で「行 + CRLF」をまとめて返す。
+/// key_read で一度に複数キーを受け取ることがあるので、未処理のキーは
+/// key_buf に残しておき、次の poll_read で続きから処理する。
+struct NcConsole {
+    key_buf: [u8; 64],
+    key_pos: usize,
+    key_len: usize,
+    line_buf: [u8; 256],
+    line_len: usize,
+}
+
+impl NcConsole {
+    fn new() -> Self {
+        Self {
+            key_buf: [0; 64],
+            key_pos: 0,
+            key_len: 0,
+            line_buf: [0; 256],
+            line_len: 0,
+        }
+    }
+}
+
+impl net::RelayEndpoint for NcConsole {
+    fn poll_read(&mut self, buf: &mut [u8]) -> net::RelayRead {
+        loop {
+            if self.key_pos == self.key_len {
+                let n = syscall::key_read(&mut self.key_buf);
+                if n <= 0 {
+                    return net::RelayRead::Idle;
+                }
+                self.key_pos = 0;
+                self.key_len = n as usize;
+            }
+            let c = self.key_buf[self.key_pos];
+            self.key_pos += 1;
+            match c {
+                // Enter: 行バッファの内容 + CRLF を送信
+                b'\n' | b'\r' => {
+                    syscall::write_str("\n");
+                    let n = self.line_len.min(buf.len().saturating_sub(2));
+                    buf[..n].copy_from_slice(&self.line_buf[..n]);
+                    buf[n..n + 2].copy_from_slice(b"\r\n");
+                    self.line_len = 0;
+                    return net::RelayRead::Data(n + 2);
+                }
+                // Esc / Ctrl-D: 入力終了（書きかけの行は捨てる）
+                0x1b | 0x04 => {
+                    syscall::write_str("\n");
+                    return net::RelayRead::Closed;
+                }
+                // Backspace
+                0x08 | 0x7f => {
+                    if self.line_len > 0 {
+                        self.line_len -= 1;
+                        syscall::write_str("\x08 \x08");
                     }
-                    _ => {}
                 }
-            }
-        }
-
-        // --- TCP 受信（短タイムアウトでポーリング） ---
-        match net::raw_recv(conn_id, &mut recv_buf, 50) {
-            Ok(n) if n > 0 => {
-                // 受信データをそのまま表示
-                if let Ok(text) = core::str::from_utf8(&recv_buf[..n]) {
-                    syscall::write_str(text);
-                } else {
-                    // バイナリデータもベストエフォートで表示
-                    syscall::write(&recv_buf[..n]);
+                // 通常の印字可能文字
+                0x20..=0x7e => {
+                    if self.line_len < self.line_buf.len() {
+                        self.line_buf[self.line_len] = c;
+                        self.line_len += 1;
+                        syscall::write(&[c]);
+                    }
                 }
-            }
-            Ok(_) => {
-                // 0 バイト = 正常だが何もない、次のポーリングへ
-            }
-            Err(_) => {
-                // エラー = 相手が切断した可能性が高い
-                break;
+                _ => {}
             }
         }
     }
 
-    // キーボードフォーカスを解放
-    syscall::console_grab(false);
+    fn write_all(&mut self, data: &[u8]) -> bool {
+        // 受信データをそのまま表示（バイナリもベストエフォート）
+        syscall::write(data);
+        true
+    }
 }
 
 /// gui コマンド: GUI サービスに描画要求を送る
@@ -3016,6 +3100,11 @@ fn cmd_gui(args: &str) {
                 text.push_str(&format!("heap_size: {}\n", v));
             }
             text.push_str(&format!("heap_source: {}\n", heap_source));
+            for key in ["heap_used", "heap_largest_free", "heap_free_blocks"] {
+                if let Some(v) = json::json_find_u64(s, key) {
+                    text.push_str(&format!("{}: {}\n", key, v));
+                }
+            }
 
             let _ = gui.clear(16, 16, 40);
             if gui.text(16, 16, (255, 255, 255), (16, 16, 40), text.as_str()).is_err() {
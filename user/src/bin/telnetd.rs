@@ -42,7 +42,7 @@ fn telnetd_main() -> ! {
 
     loop {
         // リッスン開始
-        if net::raw_listen(TELNET_PORT).is_err() {
+        if net::raw_listen(TELNET_PORT, 0).is_err() {
             syscall::write_str("telnetd: listen failed, retrying\n");
             syscall::sleep(500);
             continue;
@@ -85,14 +85,15 @@ fn telnetd_main() -> ! {
             while i < sessions.len() {
                 let conn_id = sessions[i].conn_id;
                 match net::raw_recv(conn_id, &mut tcp_buf, 0) {
-                    Ok(0) => {
+                    Err(net::NetError::Timeout) => {
                         i += 1;
                     }
-                    Ok(n) => {
+                    Ok(n) if n > 0 => {
                         handle_tcp_input(&mut sessions[i], &tcp_buf[..n]);
                         i += 1;
                     }
-                    Err(_) => {
+                    // 0 バイト = 相手が切断（EOF）、それ以外のエラーも切断扱い
+                    _ => {
                         let session = sessions.remove(i);
                         close_session(session);
                     }
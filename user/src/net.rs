@@ -173,11 +173,24 @@ impl TcpStream {
         Ok(())
     }
 
+    /// ファイルハンドルの内容を offset から count バイト送る
+    ///
+    /// SYS_SENDFILE でカーネル内から直接送るので、ファイルを読み出すバッファが要らない。
+    /// 途中で送れなくなったら、それまでに送ったバイト数を返す。
+    pub fn send_file(&self, handle: &syscall::Handle, offset: u64, count: u64) -> Result<u64, NetError> {
+        let ret = syscall::sendfile(self.conn_id, handle, offset, count);
+        if ret < 0 {
+            Err(NetError::SendFailed)
+        } else {
+            Ok(ret as u64)
+        }
+    }
+
     /// データを受信する
     ///
     /// 設定された recv_timeout_ms でタイムアウト付き受信を行う。
-    /// 戻り値は受信バイト数。0 はデータなし（タイムアウト）。
-    /// エラーは接続切断など。
+    /// 戻り値は受信バイト数。0 は相手が接続を閉じた（EOF）。
+    /// データが来ないままタイムアウトすると Err(NetError::Timeout)。
     pub fn read(&self, buf: &mut [u8]) -> Result<usize, NetError> {
         raw_recv(self.conn_id, buf, self.recv_timeout_ms)
     }
@@ -238,7 +251,15 @@ impl TcpListener {
     /// }
     /// ```
     pub fn bind(port: u16) -> Result<Self, NetError> {
-        raw_listen(port)?;
+        Self::bind_with_backlog(port, 0)
+    }
+
+    /// accept 待ちの接続の上限（backlog）を指定してリッスンを開始する
+    ///
+    /// accept が追いつかずに backlog を超えた接続要求は、カーネルが RST で断る。
+    /// 0 ならカーネルの既定値。
+    pub fn bind_with_backlog(port: u16, backlog: u32) -> Result<Self, NetError> {
+        raw_listen(port, backlog)?;
         Ok(Self { port })
     }
 
@@ -332,10 +353,13 @@ pub fn raw_send(conn_id: u32, data: &[u8]) -> Result<(), NetError> {
 
 /// 低レベル: TCP データ受信（conn_id 指定）
 ///
-/// 受信バイト数を返す。0 はタイムアウト（データなし）。
+/// 受信バイト数を返す。0 は相手が接続を閉じた（EOF）。
+/// データが来ないままタイムアウトした場合は Err(NetError::Timeout)。
 pub fn raw_recv(conn_id: u32, buf: &mut [u8], timeout_ms: u64) -> Result<usize, NetError> {
     let ret = syscall::net_tcp_recv(conn_id, buf, timeout_ms);
-    if ret < 0 {
+    if ret == -42 {
+        Err(NetError::Timeout)
+    } else if ret < 0 {
         Err(NetError::RecvFailed)
     } else {
         Ok(ret as usize)
@@ -353,8 +377,10 @@ pub fn raw_close(conn_id: u32) -> Result<(), NetError> {
 }
 
 /// 低レベル: TCP リッスン開始
-pub fn raw_listen(port: u16) -> Result<(), NetError> {
-    let ret = syscall::net_tcp_listen(port);
+///
+/// backlog は accept 待ちの接続の上限（0 = カーネルの既定値）。
+pub fn raw_listen(port: u16, backlog: u32) -> Result<(), NetError> {
+    let ret = syscall::net_tcp_listen(port, backlog);
     if ret < 0 { Err(NetError::ListenFailed) } else { Ok(()) }
 }
 
@@ -371,6 +397,111 @@ pub fn raw_accept(timeout_ms: u64, listen_port: u16) -> Result<u32, NetError> {
     }
 }
 
+// =================================================================
+// 双方向中継（nc 向け）
+// =================================================================
+//
+// nc はコンソールとソケットの間でデータを双方向に流し続ける。
+// どちらか一方をブロッキングで読むと、もう一方からのデータが届いても
+// 処理できずに固まってしまうので、両端をノンブロッキングで交互にポーリングする。
+// 端点をトレイトにしておくと、実際のソケットやキーボードなしで
+// 中継ロジックだけをテストできる（selftest_net のモック端点）。
+
+/// 中継端点からのノンブロッキング読み取りの結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayRead {
+    /// n バイト読めた
+    Data(usize),
+    /// 今は読めるデータがない
+    Idle,
+    /// EOF（相手が閉じた、または入力が終わった）
+    Closed,
+}
+
+/// relay_step の 1 回分の結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayStatus {
+    /// どちらかの向きでデータを転送した
+    Progress,
+    /// どちらの向きにも転送するデータがなかった
+    Idle,
+    /// どちらかの端点が閉じた（中継を終えるべき）
+    Closed,
+}
+
+/// 中継の片側の端点
+pub trait RelayEndpoint {
+    /// ブロックせずに読む（短いタイムアウト付きのポーリングも可）
+    fn poll_read(&mut self, buf: &mut [u8]) -> RelayRead;
+    /// data を全部書く。書けなかったら false（相手が切断した）
+    fn write_all(&mut self, data: &[u8]) -> bool;
+}
+
+/// TCP 接続を中継端点として扱うラッパー
+///
+/// poll_timeout_ms だけ受信を待つ。データが届けば net_poller が起こしてくれるので、
+/// 中継ループのスロットリングも兼ねる。
+pub struct TcpRelay {
+    conn_id: u32,
+    poll_timeout_ms: u64,
+}
+
+impl TcpRelay {
+    pub fn new(conn_id: u32, poll_timeout_ms: u64) -> Self {
+        Self { conn_id, poll_timeout_ms }
+    }
+}
+
+impl RelayEndpoint for TcpRelay {
+    fn poll_read(&mut self, buf: &mut [u8]) -> RelayRead {
+        match raw_recv(self.conn_id, buf, self.poll_timeout_ms) {
+            Ok(0) => RelayRead::Closed,
+            Ok(n) => RelayRead::Data(n),
+            Err(NetError::Timeout) => RelayRead::Idle,
+            Err(_) => RelayRead::Closed,
+        }
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> bool {
+        raw_send(self.conn_id, data).is_ok()
+    }
+}
+
+/// src から 1 回読んで dst に書く
+fn relay_one<S: RelayEndpoint, D: RelayEndpoint>(src: &mut S, dst: &mut D, buf: &mut [u8]) -> RelayStatus {
+    match src.poll_read(buf) {
+        RelayRead::Data(0) | RelayRead::Idle => RelayStatus::Idle,
+        RelayRead::Data(n) => {
+            if dst.write_all(&buf[..n]) {
+                RelayStatus::Progress
+            } else {
+                RelayStatus::Closed
+            }
+        }
+        RelayRead::Closed => RelayStatus::Closed,
+    }
+}
+
+/// a → b、b → a の順に 1 回ずつ中継する
+///
+/// 呼び出し側は Closed が返るまでループで呼び続ける。
+/// Idle が返ったときに少し待つかどうかは端点のポーリング方法次第。
+pub fn relay_step<A: RelayEndpoint, B: RelayEndpoint>(a: &mut A, b: &mut B, buf: &mut [u8]) -> RelayStatus {
+    let forward = relay_one(a, b, buf);
+    if forward == RelayStatus::Closed {
+        return RelayStatus::Closed;
+    }
+    let backward = relay_one(b, a, buf);
+    if backward == RelayStatus::Closed {
+        return RelayStatus::Closed;
+    }
+    if forward == RelayStatus::Progress || backward == RelayStatus::Progress {
+        RelayStatus::Progress
+    } else {
+        RelayStatus::Idle
+    }
+}
+
 // =================================================================
 // UdpSocket — UDP ソケットの抽象化
 // =================================================================
@@ -12,7 +12,7 @@
 // - ls [path]: ディレクトリ一覧
 // - cat <file>: ファイル内容を表示
 // - write <file> <text>: ファイルを作成/上書き
-// - rm <file>: ファイルを削除
+// - rm [-r] <file>: ファイルを削除（-r でディレクトリを中身ごと）
 // - cd <dir>: カレントディレクトリを変更
 // - pwd: カレントディレクトリを表示
 // - pushd <dir>: ディレクトリスタックに積んで移動
@@ -359,7 +359,7 @@ fn cmd_help() {
     syscall::write_str("  ls [path]         - List directory contents\n");
     syscall::write_str("  cat <file>        - Display file contents\n");
     syscall::write_str("  write <file> <text> - Create/overwrite file\n");
-    syscall::write_str("  rm <file>         - Delete file\n");
+    syscall::write_str("  rm [-r] <file>    - Delete file (-r: directory tree)\n");
     syscall::write_str("  mkdir <dir>       - Create directory\n");
     syscall::write_str("  rmdir <dir>       - Remove empty directory\n");
     syscall::write_str("  cd <dir>          - Change current directory\n");
@@ -510,13 +510,31 @@ fn cmd_write(args: &str, state: &ShellState) {
 }
 
 /// rm コマンド: ファイルを削除
+///
+/// `rm -r <dir>` ならディレクトリを中身ごと削除する。
 fn cmd_rm(args: &str, state: &ShellState) {
-    if args.is_empty() {
-        syscall::write_str("Usage: rm <filename>\n");
+    let (recursive, target) = match args.strip_prefix("-r") {
+        Some(rest) if rest.is_empty() || rest.starts_with(' ') => (true, rest.trim()),
+        _ => (false, args.trim()),
+    };
+    if target.is_empty() {
+        syscall::write_str("Usage: rm [-r] <filename>\n");
         return;
     }
 
-    let abs_path = resolve_path(&state.cwd_text, args);
+    let abs_path = resolve_path(&state.cwd_text, target);
+
+    if recursive {
+        let removed = syscall::dir_remove_all(&abs_path);
+        if removed < 0 {
+            syscall::write_str("Error: Failed to remove directory tree\n");
+            return;
+        }
+        syscall::write_str("Removed ");
+        write_number(removed as u64);
+        syscall::write_str(" entries\n");
+        return;
+    }
 
     if syscall::file_delete(&abs_path) < 0 {
         syscall::write_str("Error: Failed to delete file\n");
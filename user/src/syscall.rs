@@ -61,8 +61,13 @@ pub struct MouseState {
     pub y: i32,
     pub dx: i32,
     pub dy: i32,
+    /// ボタン: bit0 = 左, bit1 = 右, bit2 = 中, bit3 = 4 番目, bit4 = 5 番目
     pub buttons: u8,
-    pub _pad: [u8; 3],
+    /// 前回の読み取りからのホイールの回転量（正 = 上スクロール）
+    pub scroll: i8,
+    /// マウスのデバイス ID（0 = 標準, 3 = ホイール付き, 4 = ホイール + 5 ボタン）
+    pub device_id: u8,
+    pub _pad: [u8; 1],
 }
 
 /// Handle の読み取り権限（ファイル内容を読む）
@@ -385,17 +390,53 @@ pub fn draw_line(x0: u32, y0: u32, x1: u32, y1: u32, r: u8, g: u8, b: u8) -> Sys
     unsafe { syscall3(SYS_DRAW_LINE, packed0, packed1, rgb as u64) as i64 }
 }
 
-/// 画像描画（RGBX）
+/// 画像描画（ハードウェアと同じピクセル並び）
+///
+/// buf は get_fb_info() の pixel_format に合わせて詰めておく。
+/// カーネルは行単位のコピーだけで済むので最も速い。
 pub fn draw_blit(x: u32, y: u32, w: u32, h: u32, buf: &[u8]) -> SyscallResult {
     let packed_wh = ((w as u64) << 32) | (h as u64);
     let ptr = buf.as_ptr() as u64;
-    unsafe { syscall4(SYS_DRAW_BLIT, x as u64, y as u64, packed_wh, ptr) as i64 }
+    let x_fmt = (BLIT_FORMAT_NATIVE << 32) | x as u64;
+    unsafe { syscall4(SYS_DRAW_BLIT, x_fmt, y as u64, packed_wh, ptr) as i64 }
+}
+
+/// 画像描画（RGBX）
+///
+/// ハードウェアが BGR の場合はカーネルがピクセルごとに並べ替えるので、
+/// draw_blit より遅い。
+pub fn draw_blit_rgbx(x: u32, y: u32, w: u32, h: u32, buf: &[u8]) -> SyscallResult {
+    let packed_wh = ((w as u64) << 32) | (h as u64);
+    let ptr = buf.as_ptr() as u64;
+    let x_fmt = (BLIT_FORMAT_RGBX << 32) | x as u64;
+    unsafe { syscall4(SYS_DRAW_BLIT, x_fmt, y as u64, packed_wh, ptr) as i64 }
+}
+
+/// 半透明画像描画（RGBA）
+///
+/// 4 バイト目をアルファ値（0 = 透明, 255 = 不透明）として、画面の内容と合成する。
+/// アルファ 0 のピクセルは描かない。
+pub fn draw_blit_rgba(x: u32, y: u32, w: u32, h: u32, buf: &[u8]) -> SyscallResult {
+    let packed_wh = ((w as u64) << 32) | (h as u64);
+    let ptr = buf.as_ptr() as u64;
+    let x_fmt = ((BLIT_FORMAT_RGBX | BLIT_FLAG_ALPHA) << 32) | x as u64;
+    unsafe { syscall4(SYS_DRAW_BLIT, x_fmt, y as u64, packed_wh, ptr) as i64 }
 }
 
 /// 文字列描画（RGB）
+///
+/// 成功すると描いた幅（ピクセル）を返す。
 pub fn draw_text(x: u32, y: u32, fg: (u8, u8, u8), bg: (u8, u8, u8), text: &str) -> SyscallResult {
+    draw_text_scaled(x, y, fg, bg, text, 1)
+}
+
+/// 拡大率を指定した文字列描画（RGB）
+///
+/// scale 倍（1〜8）に拡大した 8x8 フォントで描く。タイトルや高解像度の画面向け。
+/// 成功すると描いた幅（ピクセル）を返すので、続けて文字列を並べるときに使える。
+pub fn draw_text_scaled(x: u32, y: u32, fg: (u8, u8, u8), bg: (u8, u8, u8), text: &str, scale: u8) -> SyscallResult {
     let packed_xy = ((x as u64) << 32) | (y as u64);
-    let fg_rgb = ((fg.0 as u32) << 16) | ((fg.1 as u32) << 8) | (fg.2 as u32);
+    let fg_rgb = ((scale as u32) << 24) | ((fg.0 as u32) << 16) | ((fg.1 as u32) << 8) | (fg.2 as u32);
     let bg_rgb = ((bg.0 as u32) << 16) | ((bg.1 as u32) << 8) | (bg.2 as u32);
     let packed_fg_bg = ((fg_rgb as u64) << 32) | (bg_rgb as u64);
     let ptr = text.as_ptr() as u64;
@@ -403,6 +444,90 @@ pub fn draw_text(x: u32, y: u32, fg: (u8, u8, u8), bg: (u8, u8, u8), text: &str)
     unsafe { syscall4(SYS_DRAW_TEXT, packed_xy, packed_fg_bg, ptr, len) as i64 }
 }
 
+/// ダブルバッファモードの切り替え
+///
+/// 有効にすると draw_* はバックバッファに描くだけになり、fb_present() で
+/// まとめて画面に出る。1 フレーム分を描き終えてから present すれば
+/// 描画途中の状態が見えない（ちらつかない）。
+///
+/// # 戻り値
+/// - 0（成功時）
+/// - 負の値（エラー時。他のタスクが使用中なら PermissionDenied）
+pub fn fb_set_double_buffer(enabled: bool) -> SyscallResult {
+    unsafe { syscall1(SYS_FB_SET_DOUBLE_BUFFER, if enabled { 1 } else { 0 }) as i64 }
+}
+
+/// バックバッファに描いた内容を画面に一括転送する
+pub fn fb_present() -> SyscallResult {
+    unsafe { syscall0(SYS_FB_PRESENT) as i64 }
+}
+
+/// クリップ領域を設定する
+///
+/// 以降の draw_* は (x, y, w, h) の外に描かなくなる。
+/// ウィンドウの中だけに描きたいときに使う。
+pub fn fb_set_clip(x: u32, y: u32, w: u32, h: u32) -> SyscallResult {
+    unsafe { syscall4(SYS_FB_SET_CLIP, x as u64, y as u64, w as u64, h as u64) as i64 }
+}
+
+/// カーネルが描くマウスカーソルを独自の画像で表示する
+///
+/// image は R, G, B, A の 4 bytes/pixel で w x h（各 64 まで）。
+/// (hot_x, hot_y) は画像内でマウス位置に合わせる点。
+/// 自前でカーソルを描くより遅延が少なく、描画内容も壊さない。
+pub fn set_cursor(image: &[u8], w: u32, h: u32, hot_x: u32, hot_y: u32) -> SyscallResult {
+    let hotspot = ((hot_x as u64) << 32) | (hot_y as u64);
+    unsafe { syscall4(SYS_SET_CURSOR, image.as_ptr() as u64, w as u64, h as u64, hotspot) as i64 }
+}
+
+/// カーネルが描くマウスカーソルを既定の矢印で表示する
+pub fn show_cursor() -> SyscallResult {
+    // bitmap_ptr = 0 なら既定の矢印（w = h = 0 だと非表示の意味になるので 1 を渡す）
+    unsafe { syscall4(SYS_SET_CURSOR, 0, 1, 1, 0) as i64 }
+}
+
+/// カーネルが描くマウスカーソルを隠す
+pub fn hide_cursor() -> SyscallResult {
+    unsafe { syscall4(SYS_SET_CURSOR, 0, 0, 0, 0) as i64 }
+}
+
+/// クリップ領域を解除する
+pub fn fb_clear_clip() -> SyscallResult {
+    unsafe { syscall4(SYS_FB_SET_CLIP, 0, 0, 0, 0) as i64 }
+}
+
+/// 起動時に GOP が対応していた画面モード（解像度）を modes に詰める
+///
+/// 戻り値は詰めた数。ExitBootServices の後なのでモードの切り替えはできない。
+pub fn fb_list_modes(modes: &mut [FbMode]) -> SyscallResult {
+    let len = core::mem::size_of_val(modes) as u64;
+    unsafe { syscall2(SYS_FB_LIST_MODES, modes.as_mut_ptr() as u64, len) as i64 }
+}
+
+/// 論理解像度を設定する（w = h = 0 で解除）
+///
+/// 以降の draw_* は (w, h) の座標系で描き、カーネルが整数倍に拡大して画面中央に出す。
+/// 戻り値は拡大率。低解像度のゲームを大きな画面で遊ぶときに使う。
+pub fn fb_set_logical_size(w: u32, h: u32) -> SyscallResult {
+    unsafe { syscall2(SYS_FB_SET_LOGICAL_SIZE, w as u64, h as u64) as i64 }
+}
+
+// =================================================================
+// クリップボード
+// =================================================================
+
+/// クリップボードの中身を data で置き換える（64KiB まで）
+pub fn clipboard_set(data: &[u8]) -> SyscallResult {
+    unsafe { syscall2(SYS_CLIPBOARD_SET, data.as_ptr() as u64, data.len() as u64) as i64 }
+}
+
+/// クリップボードの中身を buf に読み、その長さを返す
+///
+/// buf が足りなければ何も読まずに BufferOverflow (-4) を返す。
+pub fn clipboard_get(buf: &mut [u8]) -> SyscallResult {
+    unsafe { syscall2(SYS_CLIPBOARD_GET, buf.as_mut_ptr() as u64, buf.len() as u64) as i64 }
+}
+
 // =================================================================
 // テスト/デバッグ関連
 // =================================================================
@@ -419,13 +544,21 @@ pub fn selftest(auto_exit: bool) -> SyscallResult {
     unsafe { syscall1(SYS_SELFTEST, if auto_exit { 1 } else { 0 }) as i64 }
 }
 
-/// プログラムを終了する
+/// プログラムを終了する（終了コード 0）
 ///
 /// この関数は戻らない。カーネルがプロセスを終了し、
 /// 呼び出し元（シェルなど）に制御を返す。
 pub fn exit() -> ! {
+    exit_with_code(0)
+}
+
+/// 終了コードを指定してプログラムを終了する
+///
+/// 終了コードはカーネルのタスク情報に記録され、
+/// 親プロセスが `wait()` / `waitpid()` で受け取れる。
+pub fn exit_with_code(code: i32) -> ! {
     unsafe {
-        syscall0(SYS_EXIT);
+        syscall1(SYS_EXIT, code as u64);
     }
     // カーネルが制御を返さないので、ここには到達しない
     // しかし Rust の型システムを満たすために無限ループ
@@ -562,6 +695,30 @@ pub fn ipc_recv_handle(sender_out: &mut u64, buf: &mut [u8], handle_out: &mut Ha
     unsafe { syscall4(SYS_IPC_RECV_HANDLE, sender_ptr, buf_ptr, buf_len, handle_out_ptr) as i64 }
 }
 
+/// 期限が来たら IPC メッセージで知らせるタイマーを作り、タイマー ID を返す
+///
+/// interval_ms 後に（periodic なら interval_ms ごとに）送信元 TIMER_IPC_SENDER の
+/// メッセージが届く。中身は TimerMessage（timer_message() で取り出せる）。
+pub fn timer_create(interval_ms: u64, periodic: bool) -> SyscallResult {
+    unsafe { syscall2(SYS_TIMER_CREATE, interval_ms, periodic as u64) as i64 }
+}
+
+/// タイマーを止める
+pub fn timer_cancel(timer_id: u64) -> SyscallResult {
+    unsafe { syscall1(SYS_TIMER_CANCEL, timer_id) as i64 }
+}
+
+/// ipc_recv で受け取ったメッセージがタイマーのものなら、その中身を返す
+pub fn timer_message(sender: u64, data: &[u8]) -> Option<TimerMessage> {
+    if sender != TIMER_IPC_SENDER || data.len() < 16 {
+        return None;
+    }
+    Some(TimerMessage {
+        timer_id: u64::from_le_bytes(data[..8].try_into().ok()?),
+        expirations: u64::from_le_bytes(data[8..16].try_into().ok()?),
+    })
+}
+
 // =================================================================
 // システム情報関連
 // =================================================================
@@ -638,6 +795,7 @@ pub fn get_task_list(buf: &mut [u8]) -> SyscallResult {
 /// ip=X.X.X.X
 /// gateway=X.X.X.X
 /// dns=X.X.X.X
+/// ipv6=XXXX::XXXX
 /// mac=XX:XX:XX:XX:XX:XX
 /// ```
 pub fn get_net_info(buf: &mut [u8]) -> SyscallResult {
@@ -684,6 +842,17 @@ pub fn pci_config_read(bus: u8, device: u8, function: u8, offset: u8, size: u8)
     }
 }
 
+/// PCI デバイスの BAR をデコードして取得する
+///
+/// ベースアドレス・サイズ・種類（PCI_BAR_KIND_*）を info に書き込む。
+/// サイズ計測のための BAR 書き換えはカーネル側で行い、元の値に戻される。
+/// 未実装の BAR や 64 ビット BAR の上位半分を指定するとエラーになる。
+pub fn pci_read_bar(bus: u8, device: u8, function: u8, bar_index: u8, info: &mut PciBarInfo) -> SyscallResult {
+    let bdf = ((bus as u64) << 16) | ((device as u64) << 8) | (function as u64);
+    let ptr = info as *mut PciBarInfo as u64;
+    unsafe { syscall3(SYS_PCI_READ_BAR, bdf, bar_index as u64, ptr) as i64 }
+}
+
 // =================================================================
 // プロセス管理関連
 // =================================================================
@@ -819,6 +988,10 @@ pub fn spawn_redirected(
         stdin_handle_token: u64,
         stdout_handle_id: u64,
         stdout_handle_token: u64,
+        env_ptr: u64,
+        env_len: u64,
+        cwd_ptr: u64,
+        cwd_len: u64,
     }
 
     let mut args_buf = [0u8; ARGS_BUF_SIZE];
@@ -849,6 +1022,11 @@ pub fn spawn_redirected(
             Some(h) => h.token,
             None => 0,
         },
+        // 環境変数とカレントディレクトリは親から継承する
+        env_ptr: 0,
+        env_len: 0,
+        cwd_ptr: 0,
+        cwd_len: 0,
     };
 
     unsafe {
@@ -908,7 +1086,9 @@ pub fn sleep(ms: u64) {
 /// - `timeout_ms`: タイムアウト (ms)。0 なら無期限待ち
 ///
 /// # 戻り値
-/// - 終了コード（成功時）
+/// - 終了コード（成功時）。i32 の終了コードが u32 として下位 32 ビットに
+///   詰められているので、`ret as u32 as i32` で元の値に戻せる
+///   （kill された子の -1 も負の errno と混ざらない）
 /// - 負の値（エラー時）
 ///   - -10: 子プロセスがない、または指定したタスクが存在しない
 ///   - -30: 指定したタスクは子プロセスではない
@@ -955,7 +1135,7 @@ pub fn getpid() -> u64 {
     unsafe { syscall0(SYS_GETPID) }
 }
 
-/// タスクを強制終了する
+/// タスクを強制終了する（SIG_KILL）
 ///
 /// # 引数
 /// - `task_id`: 終了させるタスクの ID
@@ -964,7 +1144,29 @@ pub fn getpid() -> u64 {
 /// - 0（成功時）
 /// - 負の値（エラー時: 自分自身を kill、タスク不在、既に終了済み）
 pub fn kill(task_id: u64) -> SyscallResult {
-    unsafe { syscall1(SYS_KILL, task_id) as i64 }
+    kill_signal(task_id, SIG_KILL)
+}
+
+/// タスクにシグナルを送る
+///
+/// # 引数
+/// - `task_id`: 対象タスクの ID
+/// - `signal`: `SIG_KILL`（即座に終了）または `SIG_TERM`（終了要求フラグを立てる）
+///
+/// # 戻り値
+/// - 0（成功時）
+/// - 負の値（エラー時: タスク不在、既に終了済み、未知のシグナル）
+pub fn kill_signal(task_id: u64, signal: u64) -> SyscallResult {
+    unsafe { syscall2(SYS_KILL, task_id, signal) as i64 }
+}
+
+/// 保留中のシグナルを取得してクリアする
+///
+/// # 戻り値
+/// 保留中シグナルのビットマスク（ビット n = シグナル番号 n）。
+/// 例: `sigpending() & (1 << SIG_TERM) != 0` なら終了要求を受けている。
+pub fn sigpending() -> u64 {
+    unsafe { syscall0(SYS_SIGPENDING) }
 }
 
 // =================================================================
@@ -1040,6 +1242,41 @@ pub fn listenv(buf: &mut [u8]) -> Result<usize, SyscallResult> {
     }
 }
 
+/// カレントディレクトリを取得する
+///
+/// # 戻り値
+/// - `Ok(n)`: パスのバイト数（buf[..n] に書き込み済み）
+/// - `Err(-4)`: バッファが小さすぎる
+pub fn getcwd(buf: &mut [u8]) -> Result<usize, SyscallResult> {
+    let ret = unsafe {
+        syscall2(
+            SYS_GETCWD,
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+        ) as i64
+    };
+    if ret < 0 {
+        Err(ret)
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// カレントディレクトリを変更する（相対パスは今のカレントディレクトリから）
+///
+/// # 戻り値
+/// - 0（成功時）
+/// - 負の値（エラー時。-10: ディレクトリではない）
+pub fn chdir(path: &str) -> SyscallResult {
+    unsafe {
+        syscall2(
+            SYS_CHDIR,
+            path.as_ptr() as u64,
+            path.len() as u64,
+        ) as i64
+    }
+}
+
 // =================================================================
 // システム制御関連
 // =================================================================
@@ -1139,6 +1376,20 @@ pub fn dir_remove(path: &str) -> SyscallResult {
     unsafe { syscall2(SYS_DIR_REMOVE, path_ptr, path_len) as i64 }
 }
 
+/// ディレクトリを中身ごと削除する（パスベース）
+///
+/// # 引数
+/// - `path`: ディレクトリパス
+///
+/// # 戻り値
+/// - 削除したエントリの数（成功時）
+/// - 負の値（エラー時）
+pub fn dir_remove_all(path: &str) -> SyscallResult {
+    let path_ptr = path.as_ptr() as u64;
+    let path_len = path.len() as u64;
+    unsafe { syscall2(SYS_DIR_REMOVE_ALL, path_ptr, path_len) as i64 }
+}
+
 /// ファイルシステム統計情報を取得する
 ///
 /// JSON 形式でファイルシステムの使用状況をバッファに書き込む。
@@ -1445,6 +1696,17 @@ pub fn handle_mkdir(dir_handle: &Handle, name: &str) -> SyscallResult {
     unsafe { syscall3(SYS_HANDLE_MKDIR, dir_handle_ptr, name_ptr, name_len) as i64 }
 }
 
+/// ハンドルに書き込んだ内容をディスクに書き戻す
+///
+/// close を待たずに書き戻す。パイプなど書き戻すものがないハンドルでは何もしない。
+///
+/// # 戻り値
+/// - 0（成功時）
+/// - 負の値（エラー時）
+pub fn fsync(handle: &Handle) -> SyscallResult {
+    unsafe { syscall1(SYS_FSYNC, handle as *const Handle as u64) as i64 }
+}
+
 // =================================================================
 // 時刻・乱数
 // =================================================================
@@ -1478,13 +1740,6 @@ pub fn getrandom(buf: &mut [u8]) -> Result<usize, SyscallResult> {
     }
 }
 
-/// mmap のプロテクションフラグ: 読み取り可能
-pub const MMAP_PROT_READ: u64 = 0x1;
-/// mmap のプロテクションフラグ: 書き込み可能
-pub const MMAP_PROT_WRITE: u64 = 0x2;
-/// mmap のフラグ: 匿名マッピング（ファイルに紐付かない）
-pub const MMAP_FLAG_ANONYMOUS: u64 = 0x1;
-
 /// 匿名メモリをマッピングする（mmap）。
 ///
 /// ユーザー空間に新しいゼロ初期化済みページを動的に確保する。
@@ -1495,7 +1750,7 @@ pub const MMAP_FLAG_ANONYMOUS: u64 = 0x1;
 /// - `addr_hint`: マッピング先アドレスのヒント（0 ならカーネルが決定）
 /// - `len`: 確保するバイト数（4KiB 単位にアラインされる）
 /// - `prot`: プロテクションフラグ（MMAP_PROT_READ | MMAP_PROT_WRITE）
-/// - `flags`: マッピングフラグ（MMAP_FLAG_ANONYMOUS）
+/// - `flags`: マッピングフラグ（MMAP_FLAG_ANONYMOUS、必要なら MMAP_FLAG_FIXED を OR）
 ///
 /// # 戻り値
 /// - Ok(ptr): マッピングされたメモリの先頭アドレス
@@ -1512,6 +1767,119 @@ pub fn mmap(addr_hint: u64, len: usize, prot: u64, flags: u64) -> Result<*mut u8
     }
 }
 
+/// ファイルをメモリにマッピングする（ファイル版 mmap）。
+///
+/// ファイルの offset から len バイト分をページにコピーしてマッピングする。
+/// ファイル末尾を越えた部分はゼロで埋まる。書き込み可能にしても
+/// ファイルには書き戻されない（MAP_PRIVATE 相当）。
+///
+/// # 引数
+/// - `handle`: READ 権限付きのファイルハンドル
+/// - `offset`: ファイル内の開始位置（4KiB アライン必須）
+/// - `len`: マッピングするバイト数（4KiB 単位にアラインされる）
+/// - `prot`: プロテクションフラグ（MMAP_PROT_READ | MMAP_PROT_WRITE）
+/// - `addr_hint`: マッピング先アドレスのヒント（0 ならカーネルが決定）
+/// - `fixed`: true なら addr_hint ちょうどにマッピングする（埋まっていればエラー）
+///
+/// # 戻り値
+/// - Ok(ptr): マッピングされたメモリの先頭アドレス
+/// - Err(errno): エラー時
+pub fn mmap_file(
+    handle: &Handle,
+    offset: u64,
+    len: usize,
+    prot: u64,
+    addr_hint: u64,
+    fixed: bool,
+) -> Result<*mut u8, SyscallResult> {
+    let args = sabos_syscall::MmapFileArgs {
+        handle_id: handle.id,
+        handle_token: handle.token,
+        offset,
+        addr_hint,
+    };
+    let flags = MMAP_FLAG_FILE | if fixed { MMAP_FLAG_FIXED } else { 0 };
+    let args_ptr = &args as *const sabos_syscall::MmapFileArgs as u64;
+    let result = unsafe { syscall4(SYS_MMAP, args_ptr, len as u64, prot, flags) as i64 };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(result as *mut u8)
+    }
+}
+
+/// マッピング済みページの保護属性を変更する（mprotect）。
+///
+/// # 引数
+/// - `addr`: 先頭アドレス（4KiB アライン必須）
+/// - `len`: バイト数（4KiB 単位に切り上げられる）
+/// - `prot`: MMAP_PROT_READ | MMAP_PROT_WRITE | MMAP_PROT_EXEC、または MMAP_PROT_NONE
+///
+/// # 戻り値
+/// - Ok(0): 成功
+/// - Err(errno): エラー時
+#[allow(dead_code)]
+pub fn mprotect(addr: *mut u8, len: usize, prot: u64) -> Result<u64, SyscallResult> {
+    let result = unsafe { syscall3(SYS_MPROTECT, addr as u64, len as u64, prot) as i64 };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(result as u64)
+    }
+}
+
+/// タスクのメモリ使用量を取得する（SYS_GET_TASK_INFO）。
+///
+/// # 引数
+/// - `task_id`: 対象のタスク ID（0 なら自分）
+///
+/// # 戻り値
+/// - Ok(TaskMemInfo): 成功
+/// - Err(errno): 存在しない / 終了済みのタスクなど
+#[allow(dead_code)]
+pub fn get_task_info(task_id: u64) -> Result<TaskMemInfo, SyscallResult> {
+    let mut info = TaskMemInfo::default();
+    let ptr = &mut info as *mut TaskMemInfo as u64;
+    let result = unsafe { syscall2(SYS_GET_TASK_INFO, task_id, ptr) as i64 };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(info)
+    }
+}
+
+/// スケジューラの統計を取得する（SYS_SCHED_STATS）。
+///
+/// 値は起動からの累計。2 回読んだ差を取れば、その間の切り替え回数などが分かる。
+#[allow(dead_code)]
+pub fn sched_stats() -> Result<SchedStats, SyscallResult> {
+    let mut stats = SchedStats::default();
+    let ptr = &mut stats as *mut SchedStats as u64;
+    let len = core::mem::size_of::<SchedStats>() as u64;
+    let result = unsafe { syscall2(SYS_SCHED_STATS, ptr, len) as i64 };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(stats)
+    }
+}
+
+/// タスクの優先度を設定する（SYS_SETPRIORITY）。
+///
+/// # 引数
+/// - `task_id`: 対象のタスク ID（0 なら自分）。自分・同じプロセスのスレッド・自分の子だけ
+/// - `priority`: PRIORITY_MIN..=PRIORITY_MAX。自分の優先度より高くはできない
+#[allow(dead_code)]
+pub fn setpriority(task_id: u64, priority: u8) -> SyscallResult {
+    unsafe { syscall2(SYS_SETPRIORITY, task_id, priority as u64) as i64 }
+}
+
+/// タスクの優先度を取得する（SYS_GETPRIORITY、task_id = 0 なら自分）
+#[allow(dead_code)]
+pub fn getpriority(task_id: u64) -> SyscallResult {
+    unsafe { syscall1(SYS_GETPRIORITY, task_id) as i64 }
+}
+
 /// メモリマッピングを解除する（munmap）。
 ///
 /// mmap で確保したメモリを解放する。
@@ -1552,6 +1920,29 @@ pub fn sound_play(freq_hz: u32, duration_ms: u32) -> SyscallResult {
     unsafe { syscall2(SYS_SOUND_PLAY, freq_hz as u64, duration_ms as u64) as i64 }
 }
 
+/// AC97 ドライバで 16bit stereo PCM データを再生する。
+///
+/// # 引数
+/// - `pcm`: 16bit signed little-endian の L/R 交互データ。長さは 4 の倍数。
+/// - `sample_rate`: サンプルレート (Hz)。8000〜48000 の範囲。
+/// - `nonblock`: true なら再生完了を待たず、キューに入った分だけで戻る
+///
+/// # 戻り値
+/// - キューに入れたバイト数（ブロッキング時は pcm.len()）
+/// - 負の値（エラー時: 引数範囲外、AC97 未検出、別レートの再生中）
+pub fn sound_play_pcm(pcm: &[u8], sample_rate: u32, nonblock: bool) -> SyscallResult {
+    let flags = if nonblock { SOUND_PCM_NONBLOCK } else { 0 };
+    unsafe {
+        syscall4(
+            SYS_SOUND_PLAY_PCM,
+            pcm.as_ptr() as u64,
+            pcm.len() as u64,
+            sample_rate as u64,
+            flags,
+        ) as i64
+    }
+}
+
 // =================================================================
 // Futex 関連
 // =================================================================
@@ -1633,6 +2024,16 @@ pub fn thread_join(thread_id: u64, timeout_ms: u64) -> SyscallResult {
     unsafe { syscall2(SYS_THREAD_JOIN, thread_id, timeout_ms) as i64 }
 }
 
+/// 現在のスレッドの TLS ポインタを設定する（カーネルは値を解釈しない）
+pub fn thread_set_tls(ptr: u64) -> SyscallResult {
+    unsafe { syscall1(SYS_THREAD_SET_TLS, ptr) as i64 }
+}
+
+/// 現在のスレッドの TLS ポインタを返す（未設定なら 0）
+pub fn thread_get_tls() -> u64 {
+    unsafe { syscall0(SYS_THREAD_GET_TLS) }
+}
+
 // =================================================================
 // ネットワーク（カーネル内ネットワークスタック直接呼び出し）
 // =================================================================
@@ -1652,6 +2053,31 @@ pub fn net_dns_lookup(domain: &str, result_ip: &mut [u8; 4]) -> SyscallResult {
     }
 }
 
+/// 静的 IP 設定にする（以降 DHCP で上書きされない）
+///
+/// gateway が 0.0.0.0 ならデフォルトルートなし。
+pub fn net_set_config(ip: [u8; 4], subnet_mask: [u8; 4], gateway: [u8; 4], dns: [u8; 4]) -> SyscallResult {
+    let args = sabos_syscall::NetConfigArgs { ip, subnet_mask, gateway, dns };
+    unsafe { syscall1(SYS_NET_SET_CONFIG, &args as *const _ as u64) as i64 }
+}
+
+/// Ethernet フレームのキャプチャハンドルを開く
+///
+/// include_tx が true なら送信したフレームも流れてくる。
+/// handle_read 1 回ごとに CaptureFrameHeader + フレーム本体が 1 つ返る。
+pub fn net_capture_open(include_tx: bool) -> Result<Handle, SyscallResult> {
+    let flags = if include_tx { sabos_syscall::CAPTURE_FLAG_TX } else { 0 };
+    let mut handle = Handle { id: 0, token: 0 };
+    let result = unsafe {
+        syscall2(SYS_NET_CAPTURE_OPEN, flags, &mut handle as *mut Handle as u64) as i64
+    };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(handle)
+    }
+}
+
 /// TCP 接続の確立
 ///
 /// 指定した IP アドレスとポートに TCP 接続する。
@@ -1698,9 +2124,19 @@ pub fn net_tcp_close(conn_id: u32) -> SyscallResult {
     unsafe { syscall1(SYS_NET_TCP_CLOSE, conn_id as u64) as i64 }
 }
 
+/// TCP 接続の片側を閉じる（half-close）
+///
+/// how は SHUT_RD / SHUT_WR / SHUT_RDWR。SHUT_WR の後も受信は続けられる。
+/// 接続の後始末には別途 net_tcp_close を呼ぶ。
+pub fn net_tcp_shutdown(conn_id: u32, how: u64) -> SyscallResult {
+    unsafe { syscall2(SYS_NET_TCP_SHUTDOWN, conn_id as u64, how) as i64 }
+}
+
 /// TCP リッスン開始
-pub fn net_tcp_listen(port: u16) -> SyscallResult {
-    unsafe { syscall1(SYS_NET_TCP_LISTEN, port as u64) as i64 }
+///
+/// backlog は accept 待ちの接続の上限（0 = カーネルの既定値）。あふれた接続要求は RST で断られる。
+pub fn net_tcp_listen(port: u16, backlog: u32) -> SyscallResult {
+    unsafe { syscall2(SYS_NET_TCP_LISTEN, port as u64, backlog as u64) as i64 }
 }
 
 /// TCP 接続の受け入れ
@@ -1709,14 +2145,52 @@ pub fn net_tcp_listen(port: u16) -> SyscallResult {
 /// 成功時は conn_id を返す。
 pub fn net_tcp_accept(timeout_ms: u64, listen_port: u16) -> SyscallResult {
     unsafe {
-        syscall2(
+        syscall3(
+            SYS_NET_TCP_ACCEPT,
+            timeout_ms,
+            listen_port as u64,
+            0, // 相手のアドレスは要らない
+        ) as i64
+    }
+}
+
+/// TCP 接続の受け入れ（相手のアドレス付き）
+///
+/// peer_info に [ip0, ip1, ip2, ip3, port_lo, port_hi] を書き込む。
+pub fn net_tcp_accept_from(timeout_ms: u64, listen_port: u16, peer_info: &mut [u8; 6]) -> SyscallResult {
+    unsafe {
+        syscall3(
             SYS_NET_TCP_ACCEPT,
             timeout_ms,
             listen_port as u64,
+            peer_info.as_mut_ptr() as u64,
         ) as i64
     }
 }
 
+/// ファイルハンドルの内容を TCP 接続へ送る（sendfile）
+///
+/// offset から最大 count バイトを、ユーザー空間のバッファを通さずにカーネル内で送る。
+/// 送ったバイト数を返す。ファイル末尾や途中の送信失敗で count より少ないことがある。
+pub fn sendfile(conn_id: u32, handle: &Handle, offset: u64, count: u64) -> SyscallResult {
+    unsafe {
+        syscall4(
+            SYS_SENDFILE,
+            conn_id as u64,
+            handle as *const Handle as u64,
+            offset,
+            count,
+        ) as i64
+    }
+}
+
+/// TCP 接続の自分側のアドレスを取得する
+///
+/// info に [ip0, ip1, ip2, ip3, port_lo, port_hi] を書き込む。
+pub fn net_tcp_local_addr(conn_id: u32, info: &mut [u8; 6]) -> SyscallResult {
+    unsafe { syscall2(SYS_NET_TCP_LOCAL_ADDR, conn_id as u64, info.as_mut_ptr() as u64) as i64 }
+}
+
 /// UDP ソケットバインド
 ///
 /// 成功時は socket_id | (local_port << 32) を返す。
@@ -1760,6 +2234,24 @@ pub fn net_udp_close(socket_id: u32) -> SyscallResult {
     unsafe { syscall1(SYS_NET_UDP_CLOSE, socket_id as u64) as i64 }
 }
 
+/// conn_id / socket_id 指定でソケットオプションを設定する
+pub fn net_setsockopt(sock_id: u32, level: u64, optname: u64, value: u64) -> SyscallResult {
+    unsafe { syscall4(SYS_NET_SETSOCKOPT, sock_id as u64, level, optname, value) as i64 }
+}
+
+/// conn_id / socket_id 指定でソケットオプションの現在値を取得する
+pub fn net_getsockopt(sock_id: u32, level: u64, optname: u64) -> Result<u64, SyscallResult> {
+    let mut value: u64 = 0;
+    let result = unsafe {
+        syscall4(SYS_NET_GETSOCKOPT, sock_id as u64, level, optname, &mut value as *mut u64 as u64) as i64
+    };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(value)
+    }
+}
+
 /// IPv6 ping
 ///
 /// 成功時は src_ip に応答元 IPv6 アドレスを書き込む。
@@ -1773,3 +2265,145 @@ pub fn net_ping6(dst_ip: &[u8; 16], timeout_ms: u32, src_ip: &mut [u8; 16]) -> S
         ) as i64
     }
 }
+
+// =================================================================
+// 統一ソケット API（ソケットをハンドルとして扱う）
+// =================================================================
+
+/// IPv4 アドレスとポートから SockAddr を作る
+pub fn sockaddr_v4(ip: [u8; 4], port: u16) -> SockAddr {
+    let mut addr = SockAddr { family: AF_INET as u16, port, ..Default::default() };
+    addr.addr[..4].copy_from_slice(&ip);
+    addr
+}
+
+/// ソケットを作成する
+///
+/// domain は AF_INET / AF_INET6、ty は SOCK_STREAM / SOCK_DGRAM、
+/// proto は 0（自動）か IPPROTO_TCP / IPPROTO_UDP。
+/// 返るハンドルは handle_read / handle_write / handle_close でも使える。
+pub fn socket(domain: u64, ty: u64, proto: u64) -> Result<Handle, SyscallResult> {
+    let mut handle = Handle { id: 0, token: 0 };
+    let result = unsafe {
+        syscall4(SYS_SOCKET, domain, ty, proto, &mut handle as *mut Handle as u64) as i64
+    };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(handle)
+    }
+}
+
+/// ソケットをローカルアドレスに割り当てる
+///
+/// addr.port = 0 の UDP ソケットにはエフェメラルポートが割り当てられ、
+/// 実際のポートが addr に書き戻される。
+pub fn bind(handle: &Handle, addr: &mut SockAddr) -> SyscallResult {
+    unsafe {
+        syscall2(SYS_BIND, handle as *const Handle as u64, addr as *mut SockAddr as u64) as i64
+    }
+}
+
+/// 接続先を設定する（TCP は接続を確立する）
+pub fn connect(handle: &Handle, addr: &SockAddr) -> SyscallResult {
+    unsafe {
+        syscall2(SYS_CONNECT, handle as *const Handle as u64, addr as *const SockAddr as u64) as i64
+    }
+}
+
+/// TCP の待ち受けを開始する
+pub fn listen(handle: &Handle, backlog: u32) -> SyscallResult {
+    unsafe { syscall2(SYS_LISTEN, handle as *const Handle as u64, backlog as u64) as i64 }
+}
+
+/// 接続を受け入れ、(新しいソケット, 相手のアドレス) を返す
+///
+/// timeout_ms = 0 はノンブロッキング、SOCKET_WAIT_FOREVER は無期限に待つ。
+pub fn accept(handle: &Handle, timeout_ms: u64) -> Result<(Handle, SockAddr), SyscallResult> {
+    let mut new_handle = Handle { id: 0, token: 0 };
+    let mut peer = SockAddr::default();
+    let result = unsafe {
+        syscall4(
+            SYS_ACCEPT,
+            handle as *const Handle as u64,
+            &mut new_handle as *mut Handle as u64,
+            timeout_ms,
+            &mut peer as *mut SockAddr as u64,
+        ) as i64
+    };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok((new_handle, peer))
+    }
+}
+
+/// データを送信する
+///
+/// dst が None なら接続先に送る（TCP では dst は無視される）。
+pub fn send(handle: &Handle, data: &[u8], dst: Option<&SockAddr>) -> SyscallResult {
+    let dst_ptr = dst.map_or(0, |a| a as *const SockAddr as u64);
+    unsafe {
+        syscall4(
+            SYS_SEND,
+            handle as *const Handle as u64,
+            data.as_ptr() as u64,
+            data.len() as u64,
+            dst_ptr,
+        ) as i64
+    }
+}
+
+/// データを受信する
+///
+/// timeout_ms = 0 はノンブロッキング、SOCKET_WAIT_FOREVER は無期限に待つ。
+/// TCP で 0 が返れば相手が接続を閉じた（EOF）。
+pub fn recv(handle: &Handle, buf: &mut [u8], timeout_ms: u64) -> SyscallResult {
+    unsafe {
+        syscall4(
+            SYS_RECV,
+            handle as *const Handle as u64,
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+            timeout_ms,
+        ) as i64
+    }
+}
+
+/// 送信元アドレス付きでデータを受信する
+///
+/// 成功時は受信バイト数を返し、from に送信元を書き込む。
+pub fn recv_from(handle: &Handle, buf: &mut [u8], timeout_ms: u64, from: &mut SockAddr) -> SyscallResult {
+    let mut args = SockRecvFromArgs { timeout_ms, from: SockAddr::default() };
+    let result = unsafe {
+        syscall4(
+            SYS_RECV_FROM,
+            handle as *const Handle as u64,
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+            &mut args as *mut SockRecvFromArgs as u64,
+        ) as i64
+    };
+    *from = args.from;
+    result
+}
+
+/// ソケットオプションを設定する
+///
+/// level は IPPROTO_TCP / SOL_SOCKET、optname は TCP_NODELAY / SO_RCVTIMEO / SO_REUSEADDR。
+pub fn setsockopt(handle: &Handle, level: u64, optname: u64, value: u64) -> SyscallResult {
+    unsafe { syscall4(SYS_SETSOCKOPT, handle as *const Handle as u64, level, optname, value) as i64 }
+}
+
+/// ソケットオプションの現在値を取得する
+pub fn getsockopt(handle: &Handle, level: u64, optname: u64) -> Result<u64, SyscallResult> {
+    let mut value: u64 = 0;
+    let result = unsafe {
+        syscall4(SYS_GETSOCKOPT, handle as *const Handle as u64, level, optname, &mut value as *mut u64 as u64) as i64
+    };
+    if result < 0 {
+        Err(result)
+    } else {
+        Ok(value)
+    }
+}